@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 use anyhow::{Context, Result};
-use reqwest::blocking::Client;
+use portfolio_tracker::http;
 use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -30,13 +30,11 @@ pub fn tickers() -> Result<()> {
         std::env::var("CGECKO_API_KEY").with_context(|| "CGECKO_API_KEY env var missing")?;
     let url = "https://api.coingecko.com/api/v3/coins/markets?vs_currency=usd&order=market_cap_desc&per_page=250&page=1";
 
-    let client = Client::new();
-    let response = client
+    let request = http::client()?
         .get(url)
-        .header("x-cg-demo-api-key", api_key)
-        .send()?;
+        .header("x-cg-demo-api-key", api_key);
 
-    let coins: Vec<CoinInfo> = response.json()?;
+    let coins: Vec<CoinInfo> = http::send_json(request)?;
 
     let file_path = "./data/coingecko.csv";
     let file = std::fs::File::create(file_path)?; // override if exists
@@ -0,0 +1,623 @@
+use crate::currency::{Currency, CurrencyType};
+use crate::math;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::snapshot::{self, Snapshot};
+use anyhow::{Result, bail};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::PathBuf;
+use time::macros::format_description;
+use time::{Duration, OffsetDateTime};
+
+const ALERTS_FILE: &str = "alerts.json";
+const STATE_FILE: &str = "alert_state.json";
+
+/// Condition that fires an alert, evaluated by `watch`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Trigger {
+    /// Ticker price, in the base currency, is at or above the level
+    PriceAbove { ticker: String, price: Decimal },
+    /// Ticker price, in the base currency, is at or below the level
+    PriceBelow { ticker: String, price: Decimal },
+    /// Portfolio value moved more than `percent` (up or down) since the
+    /// snapshot taken a day earlier
+    PortfolioMove { portfolio: String, percent: Decimal },
+    /// Position of `asset` in the portfolio is at or above the level
+    PositionAbove {
+        portfolio: String,
+        asset: String,
+        level: PositionLevel,
+    },
+    /// Position of `asset` in the portfolio is at or below the level
+    PositionBelow {
+        portfolio: String,
+        asset: String,
+        level: PositionLevel,
+    },
+}
+
+/// Asset of a position trigger standing for all stable coins together
+pub const STABLES: &str = "STABLES";
+
+/// Level of a position trigger
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PositionLevel {
+    /// Value in the portfolio base currency
+    Value(Decimal),
+    /// Percent of the portfolio value
+    Weight(Decimal),
+}
+
+impl fmt::Display for PositionLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionLevel::Value(value) => write!(f, "{}", value),
+            PositionLevel::Weight(percent) => write!(f, "{}% of the portfolio", percent),
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::PriceAbove { ticker, price } => write!(f, "{} >= {}", ticker, price),
+            Trigger::PriceBelow { ticker, price } => write!(f, "{} <= {}", ticker, price),
+            Trigger::PortfolioMove { portfolio, percent } => {
+                write!(f, "{} moves > {}% in a day", portfolio, percent)
+            }
+            Trigger::PositionAbove {
+                portfolio,
+                asset,
+                level,
+            } => write!(f, "{} in {} >= {}", asset, portfolio, level),
+            Trigger::PositionBelow {
+                portfolio,
+                asset,
+                level,
+            } => write!(f, "{} in {} <= {}", asset, portfolio, level),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    pub id: u32,
+    pub trigger: Trigger,
+    /// Minutes before a firing alert notifies again, `alert_cooldown_mins`
+    /// from the config when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cooldown_mins: Option<i64>,
+}
+
+impl Alert {
+    pub fn cooldown(&self, settings: &Settings) -> Duration {
+        Duration::minutes(
+            self.cooldown_mins
+                .unwrap_or(settings.alert_cooldown_mins as i64),
+        )
+    }
+}
+
+/// What `watch` remembers of an alert across polls and restarts, in unix seconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlertState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notified_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snoozed_until: Option<i64>,
+}
+
+impl AlertState {
+    /// Whether an alert firing at `now` notifies: not while snoozed, nor
+    /// within `cooldown` of its last notification
+    pub fn due(&self, cooldown: Duration, now: OffsetDateTime) -> bool {
+        let now = now.unix_timestamp();
+        let snoozed = self.snoozed_until.is_some_and(|until| now < until);
+        let cooling = self
+            .notified_at
+            .is_some_and(|at| now - at < cooldown.whole_seconds());
+        !snoozed && !cooling
+    }
+}
+
+fn state_path(settings: &Settings) -> PathBuf {
+    settings.state_dir().join(STATE_FILE)
+}
+
+/// State of every alert that notified or was snoozed, by id
+pub fn load_state(settings: &Settings) -> Result<BTreeMap<u32, AlertState>> {
+    let path = state_path(settings);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+fn save_state(state: &BTreeMap<u32, AlertState>, settings: &Settings) -> Result<()> {
+    std::fs::create_dir_all(settings.state_dir())?;
+    std::fs::write(state_path(settings), serde_json::to_vec_pretty(state)?)?;
+    Ok(())
+}
+
+/// Decides which firing alerts of a `watch` poll notify, so an alert that
+/// keeps firing doesn't notify at every poll
+pub struct Throttle {
+    state: BTreeMap<u32, AlertState>,
+    now: OffsetDateTime,
+    changed: bool,
+}
+
+impl Throttle {
+    pub fn load(settings: &Settings) -> Result<Self> {
+        Ok(Throttle {
+            state: load_state(settings)?,
+            now: settings.clock.now(),
+            changed: false,
+        })
+    }
+
+    /// Whether `alert`, firing now, notifies, which is then remembered
+    pub fn notify(&mut self, alert: &Alert, settings: &Settings) -> bool {
+        let state = self.state.entry(alert.id).or_default();
+        if !state.due(alert.cooldown(settings), self.now) {
+            return false;
+        }
+        state.notified_at = Some(self.now.unix_timestamp());
+        self.changed = true;
+        true
+    }
+
+    /// Store the notifications of the poll for the next one
+    pub fn save(&self, settings: &Settings) -> Result<()> {
+        if self.changed {
+            save_state(&self.state, settings)?;
+        }
+        Ok(())
+    }
+}
+
+fn alerts_path(settings: &Settings) -> PathBuf {
+    settings.state_dir().join(ALERTS_FILE)
+}
+
+pub fn load(settings: &Settings) -> Result<Vec<Alert>> {
+    let path = alerts_path(settings);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+fn save(alerts: &[Alert], settings: &Settings) -> Result<()> {
+    std::fs::create_dir_all(settings.state_dir())?;
+    std::fs::write(alerts_path(settings), serde_json::to_vec_pretty(alerts)?)?;
+    Ok(())
+}
+
+/// Store a new alert, returning its id
+pub fn add(
+    trigger: Trigger,
+    cooldown: Option<Duration>,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<u32> {
+    match &trigger {
+        Trigger::PriceAbove { ticker, .. } | Trigger::PriceBelow { ticker, .. } => {
+            Currency::new(ticker)?;
+        }
+        Trigger::PortfolioMove {
+            portfolio, percent, ..
+        } => {
+            if !settings.path_for(portfolio).is_file() {
+                bail!("portfolio '{}' doesn't exist", portfolio);
+            }
+            if *percent <= dec!(0) {
+                bail!("move percent must be positive");
+            }
+        }
+        Trigger::PositionAbove {
+            portfolio,
+            asset,
+            level,
+        }
+        | Trigger::PositionBelow {
+            portfolio,
+            asset,
+            level,
+        } => {
+            if !settings.path_for(portfolio).is_file() {
+                bail!("portfolio '{}' doesn't exist", portfolio);
+            }
+            if asset != STABLES {
+                Currency::new(asset)?;
+            }
+            match level {
+                PositionLevel::Value(value) if *value < dec!(0) => {
+                    bail!("position value can't be negative")
+                }
+                PositionLevel::Weight(percent) if *percent < dec!(0) || *percent > dec!(100) => {
+                    bail!("position weight must be between 0 and 100 percent")
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut alerts = load(settings)?;
+    let id = alerts.iter().map(|a| a.id).max().unwrap_or(0) + 1;
+    reporter.status(&format!("🔔 Added alert #{}: {}", id, trigger));
+    alerts.push(Alert {
+        id,
+        trigger,
+        cooldown_mins: cooldown.map(|c| c.whole_minutes()),
+    });
+    save(&alerts, settings)?;
+    Ok(id)
+}
+
+pub fn remove(id: u32, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let mut alerts = load(settings)?;
+    let before = alerts.len();
+    alerts.retain(|a| a.id != id);
+    if alerts.len() == before {
+        bail!("no alert with id {}", id);
+    }
+    save(&alerts, settings)?;
+    // the id of a removed alert can come back, a new one starts afresh
+    let mut state = load_state(settings)?;
+    if state.remove(&id).is_some() {
+        save_state(&state, settings)?;
+    }
+    reporter.status(&format!("Removed alert #{}", id));
+    Ok(())
+}
+
+fn format_time(ts: i64) -> Result<String> {
+    Ok(
+        OffsetDateTime::from_unix_timestamp(ts)?.format(format_description!(
+            "[year]-[month]-[day] [hour]:[minute] UTC"
+        ))?,
+    )
+}
+
+/// Minutes as the largest unit of `alert snooze` and `--cooldown` they are
+/// a whole number of, e.g. `90m` or `6h`
+fn format_minutes(minutes: i64) -> String {
+    match minutes {
+        0 => "0m".to_string(),
+        m if m % (60 * 24) == 0 => format!("{}d", m / (60 * 24)),
+        m if m % 60 == 0 => format!("{}h", m / 60),
+        m => format!("{}m", m),
+    }
+}
+
+/// `alert snooze`: don't notify of alert `id` for `duration`, a zero
+/// duration ends a snooze
+pub fn snooze(
+    id: u32,
+    duration: Duration,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    if !load(settings)?.iter().any(|a| a.id == id) {
+        bail!("no alert with id {}", id);
+    }
+    let mut state = load_state(settings)?;
+    let entry = state.entry(id).or_default();
+    if duration.is_zero() {
+        entry.snoozed_until = None;
+        reporter.status(&format!("Alert #{} is no longer snoozed", id));
+    } else {
+        let until = (settings.clock.now() + duration).unix_timestamp();
+        entry.snoozed_until = Some(until);
+        reporter.status(&format!(
+            "😴 Snoozed alert #{} until {}",
+            id,
+            format_time(until)?
+        ));
+    }
+    save_state(&state, settings)
+}
+
+pub fn print_list(reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let alerts = load(settings)?;
+    if alerts.is_empty() {
+        reporter.print("No alerts defined");
+        return Ok(());
+    }
+    let state = load_state(settings)?;
+    let now = settings.clock.now().unix_timestamp();
+    let mut table = Table::new();
+    table.add_row(row!["Id", "Trigger", "Cooldown", "Snoozed until"]);
+    for alert in alerts {
+        let snoozed = match state.get(&alert.id).and_then(|s| s.snoozed_until) {
+            Some(until) if until > now => format_time(until)?,
+            _ => String::new(),
+        };
+        let cooldown = format_minutes(alert.cooldown(settings).whole_minutes());
+        table.add_row(row![alert.id, alert.trigger, cooldown, snoozed]);
+    }
+    reporter.table(&table)?;
+    Ok(())
+}
+
+/// Message for a price trigger, when the price crossed the level
+pub fn check_price(trigger: &Trigger, current: Decimal) -> Option<String> {
+    match trigger {
+        Trigger::PriceAbove { ticker, price } if current >= *price => {
+            Some(format!("{} is at {}, above {}", ticker, current, price))
+        }
+        Trigger::PriceBelow { ticker, price } if current <= *price => {
+            Some(format!("{} is at {}, below {}", ticker, current, price))
+        }
+        _ => None,
+    }
+}
+
+/// Message for a portfolio move trigger, comparing the current value with
+/// the latest snapshot taken at least a day before `now`
+pub fn check_move(
+    trigger: &Trigger,
+    current: Decimal,
+    history: &[Snapshot],
+    now: OffsetDateTime,
+) -> Option<String> {
+    let Trigger::PortfolioMove { portfolio, percent } = trigger else {
+        return None;
+    };
+    let previous = snapshot::before(history, now, Duration::days(1))?;
+    let change = math::change_pct(previous.value, current)?;
+    if change.abs().value() > *percent {
+        Some(format!(
+            "{} moved {:+.2} since yesterday ({} → {})",
+            portfolio,
+            change,
+            previous.value.round_dp(2),
+            current.round_dp(2)
+        ))
+    } else {
+        None
+    }
+}
+
+/// Message for a position trigger, given the value of every position of its
+/// portfolio in the base currency (see `Portfolio::position_values`)
+pub fn check_position(
+    trigger: &Trigger,
+    values: &HashMap<Currency, Decimal>,
+    base: &Currency,
+) -> Option<String> {
+    let (portfolio, asset, level, above) = match trigger {
+        Trigger::PositionAbove {
+            portfolio,
+            asset,
+            level,
+        } => (portfolio, asset, level, true),
+        Trigger::PositionBelow {
+            portfolio,
+            asset,
+            level,
+        } => (portfolio, asset, level, false),
+        _ => return None,
+    };
+    let value: Decimal = values
+        .iter()
+        .filter(|(currency, _)| match asset.as_str() {
+            STABLES => currency.currency_type() == CurrencyType::StableCoin,
+            ticker => currency.ticker() == ticker,
+        })
+        .map(|(_, value)| value)
+        .sum();
+    let (current, limit, shown, limit_shown) = match level {
+        PositionLevel::Value(limit) => (
+            value,
+            *limit,
+            format!("worth {} {}", value.round_dp(2), base),
+            format!("{} {}", limit, base),
+        ),
+        PositionLevel::Weight(limit) => {
+            let total: Decimal = values.values().sum();
+            let weight = math::percent_of(value, total)?;
+            (
+                weight.value(),
+                *limit,
+                format!("{:.2} of the portfolio", weight),
+                format!("{}%", limit),
+            )
+        }
+    };
+    let (crossed, side) = if above {
+        (current >= limit, "above")
+    } else {
+        (current <= limit, "below")
+    };
+    crossed.then(|| {
+        format!(
+            "{} in {} is {}, {} {}",
+            asset, portfolio, shown, side, limit_shown
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::reporter::Silent;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use tempfile::TempDir;
+    use time::macros::datetime;
+
+    fn move_trigger(percent: Decimal) -> Trigger {
+        Trigger::PortfolioMove {
+            portfolio: "alpha".to_string(),
+            percent,
+        }
+    }
+
+    #[test]
+    fn test_check_move_compares_with_yesterdays_snapshot() {
+        let now = datetime!(2024-01-12 12:00 UTC);
+        let history = [
+            Snapshot {
+                ts: (now - Duration::hours(26)).unix_timestamp(),
+                value: dec!(1000),
+            },
+            // too recent to count as yesterday's value
+            Snapshot {
+                ts: (now - Duration::hours(2)).unix_timestamp(),
+                value: dec!(940),
+            },
+        ];
+
+        let msg = check_move(&move_trigger(dec!(5)), dec!(940), &history, now).unwrap();
+        assert_eq!(msg, "alpha moved -6.00% since yesterday (1000 → 940)");
+        assert_eq!(
+            check_move(&move_trigger(dec!(5)), dec!(1040), &history, now),
+            None
+        );
+        // no snapshot old enough yet
+        assert_eq!(
+            check_move(&move_trigger(dec!(5)), dec!(10), &history[1..], now),
+            None
+        );
+    }
+
+    #[test]
+    fn test_check_price() {
+        let above = Trigger::PriceAbove {
+            ticker: "BTC".to_string(),
+            price: dec!(100000),
+        };
+        assert!(check_price(&above, dec!(100001)).is_some());
+        assert!(check_price(&above, dec!(99999)).is_none());
+        assert!(check_price(&move_trigger(dec!(1)), dec!(1)).is_none());
+    }
+
+    #[test]
+    fn test_check_position_on_value_and_weight() {
+        let usd = Currency::new("USD").unwrap();
+        let values = HashMap::from([
+            (Currency::new("ETH").unwrap(), dec!(4500)),
+            (Currency::new("USDT").unwrap(), dec!(3000)),
+            (Currency::new("USDC").unwrap(), dec!(1500)),
+            (usd.clone(), dec!(1000)),
+        ]);
+        let position = |above: bool, asset: &str, level| {
+            let (portfolio, asset) = ("alpha".to_string(), asset.to_string());
+            if above {
+                Trigger::PositionAbove {
+                    portfolio,
+                    asset,
+                    level,
+                }
+            } else {
+                Trigger::PositionBelow {
+                    portfolio,
+                    asset,
+                    level,
+                }
+            }
+        };
+
+        let eth_heavy = position(true, "ETH", PositionLevel::Weight(dec!(40)));
+        assert_eq!(
+            check_position(&eth_heavy, &values, &usd).unwrap(),
+            "ETH in alpha is 45.00% of the portfolio, above 40%"
+        );
+        let stables_low = position(false, STABLES, PositionLevel::Value(dec!(5000)));
+        assert_eq!(
+            check_position(&stables_low, &values, &usd).unwrap(),
+            "STABLES in alpha is worth 4500 USD, below 5000 USD"
+        );
+        let eth_light = position(false, "ETH", PositionLevel::Weight(dec!(40)));
+        assert_eq!(check_position(&eth_light, &values, &usd), None);
+        // nothing held counts as zero
+        let btc_low = position(false, "BTC", PositionLevel::Value(dec!(1)));
+        assert!(check_position(&btc_low, &values, &usd).is_some());
+        assert_eq!(check_position(&move_trigger(dec!(1)), &values, &usd), None);
+    }
+
+    #[test]
+    fn test_add_list_remove() {
+        let dir = TempDir::new().unwrap();
+        create_test_csv(&dir, "alpha", "created_at,pair,side,amount,price,fee\n");
+        let settings = create_test_settings(dir.path().to_path_buf());
+
+        let reporter = Silent;
+        assert_eq!(
+            add(move_trigger(dec!(5)), None, &reporter, &settings).unwrap(),
+            1
+        );
+        let price = Trigger::PriceBelow {
+            ticker: "ETH".to_string(),
+            price: dec!(2000),
+        };
+        assert_eq!(add(price.clone(), None, &reporter, &settings).unwrap(), 2);
+        assert!(add(move_trigger(dec!(-1)), None, &reporter, &settings).is_err());
+
+        remove(1, &reporter, &settings).unwrap();
+        assert_eq!(
+            load(&settings).unwrap(),
+            vec![Alert {
+                id: 2,
+                trigger: price,
+                cooldown_mins: None,
+            }]
+        );
+        assert!(remove(1, &reporter, &settings).is_err());
+    }
+
+    #[test]
+    fn test_throttle_keeps_a_firing_alert_quiet_through_cooldown_and_snooze() {
+        let dir = TempDir::new().unwrap();
+        create_test_csv(&dir, "alpha", "created_at,pair,side,amount,price,fee\n");
+        let at = |settings: &Settings, now| Settings {
+            clock: Clock::Frozen(now),
+            ..settings.clone()
+        };
+        let settings = at(
+            &create_test_settings(dir.path().to_path_buf()),
+            datetime!(2025-03-01 12:00 UTC),
+        );
+        add(move_trigger(dec!(5)), None, &Silent, &settings).unwrap();
+        let cooldown = Some(Duration::hours(6));
+        add(move_trigger(dec!(9)), cooldown, &Silent, &settings).unwrap();
+        let alerts = load(&settings).unwrap();
+        let notified = |settings: &Settings| {
+            let mut throttle = Throttle::load(settings).unwrap();
+            let notified: Vec<bool> = alerts
+                .iter()
+                .map(|a| throttle.notify(a, settings))
+                .collect();
+            throttle.save(settings).unwrap();
+            notified
+        };
+
+        assert_eq!(notified(&settings), [true, true]);
+        let later = at(&settings, datetime!(2025-03-01 12:59 UTC));
+        assert_eq!(notified(&later), [false, false]);
+        let later = at(&settings, datetime!(2025-03-01 13:00 UTC));
+        assert_eq!(notified(&later), [true, false]);
+
+        snooze(1, Duration::hours(24), &Silent, &later).unwrap();
+        let later = at(&settings, datetime!(2025-03-01 18:00 UTC));
+        assert_eq!(notified(&later), [false, true]);
+        let later = at(&settings, datetime!(2025-03-02 13:00 UTC));
+        assert_eq!(notified(&later), [true, true]);
+
+        snooze(2, Duration::hours(24), &Silent, &later).unwrap();
+        snooze(2, Duration::ZERO, &Silent, &later).unwrap();
+        assert_eq!(load_state(&later).unwrap()[&2].snoozed_until, None);
+        assert!(snooze(3, Duration::hours(1), &Silent, &later).is_err());
+        remove(1, &Silent, &later).unwrap();
+        assert!(!load_state(&later).unwrap().contains_key(&1));
+    }
+}
@@ -0,0 +1,131 @@
+//! Anonymized copies of portfolios for bug reports
+//!
+//! `export --anonymized` writes a copy of a trades portfolio in which every
+//! amount and fee is scaled by the same random factor. Pairs, sides, prices
+//! and timestamps are kept, so parsing problems and the shape of the PnL
+//! reproduce, but the balances can't be read from the file. Sources, which
+//! may name exchanges and accounts, are left out, and so are the journal
+//! and tags, which aren't part of the file.
+
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{
+    MAX_MAGNITUDE, MAX_SCALE, Trade, check_precision, parse_csv_file, write_csv_records,
+};
+use anyhow::{Result, anyhow, bail};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::path::Path;
+
+/// A random factor between 0.2 and 5, but never close to 1
+fn random_factor() -> Decimal {
+    let random = RandomState::new().hash_one(std::time::SystemTime::now());
+    let factor = dec!(0.2) + Decimal::from(random % 48_000) / dec!(10_000);
+    if (factor - dec!(1)).abs() < dec!(0.1) {
+        factor + dec!(0.5)
+    } else {
+        factor
+    }
+}
+
+/// `value` scaled by `factor`, within the precision of portfolio files
+fn scale(value: Decimal, factor: Decimal) -> Result<Decimal> {
+    if value.is_zero() {
+        return Ok(value);
+    }
+    let scaled = (value * factor)
+        .round_dp(MAX_SCALE)
+        .max(Decimal::new(1, MAX_SCALE))
+        .min(MAX_MAGNITUDE);
+    check_precision(scaled).map_err(|e| anyhow!(e))
+}
+
+/// `trades` with amounts and fees scaled by `factor` and without sources
+pub fn anonymize(trades: &[Trade], factor: Decimal) -> Result<Vec<Trade>> {
+    trades
+        .iter()
+        .map(|t| {
+            Ok(Trade {
+                created_at: t.created_at,
+                pair: t.pair.clone(),
+                side: t.side,
+                amount: scale(t.amount, factor)?,
+                price: t.price,
+                fee: scale(t.fee, factor)?,
+                source: None,
+            })
+        })
+        .collect()
+}
+
+/// `export --anonymized`: write an anonymized copy of portfolio `name` to `out`
+pub fn run(name: &str, out: &Path, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let path = settings.path_for(name);
+    if !path.is_file() {
+        bail!("portfolio '{}' doesn't exist", name);
+    }
+    let (config, trades) = parse_csv_file(&path)?;
+    let records: Vec<csv::StringRecord> = anonymize(&trades, random_factor())?
+        .iter()
+        .map(Trade::to_record)
+        .collect();
+    write_csv_records(out, &config, &records)?;
+    reporter.status(&format!(
+        "Wrote {} trades of {} with amounts scaled by a random factor to {}",
+        records.len(),
+        name,
+        out.display()
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::Silent;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_anonymized_copy_scales_amounts_and_keeps_prices() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        create_test_csv(
+            &dir,
+            "main",
+            "# base_currency: EUR
+created_at,pair,side,amount,price,fee,source
+1704883200,BTC/EUR,BUY,0.5,40000,10,import:kraken
+1704969600,BTC/EUR,SELL,0.2,42000,0,manual
+",
+        );
+        let (_, trades) = parse_csv_file(settings.path_for("main")).unwrap();
+        let scaled = anonymize(&trades, dec!(3)).unwrap();
+        assert_eq!(scaled[0].amount, dec!(1.5));
+        assert_eq!(scaled[0].fee, dec!(30));
+        assert_eq!(scaled[1].fee, dec!(0));
+        assert_eq!(scaled[1].price, dec!(42000));
+        assert_eq!(scaled[1].created_at, trades[1].created_at);
+
+        let out = dir.path().join("anon.csv");
+        run("main", &out, &Silent, &settings).unwrap();
+        let (config, copy) = parse_csv_file(&out).unwrap();
+        assert_eq!(config.base_currency, "EUR");
+        assert!(copy.iter().all(|t| t.source.is_none()));
+        let factor = copy[0].amount / trades[0].amount;
+        assert!(factor >= dec!(0.2) && factor < dec!(5.5));
+        assert_ne!(copy[0].amount, trades[0].amount);
+        assert_eq!(copy[1].amount, trades[1].amount * factor);
+    }
+
+    #[test]
+    fn test_random_factor_stays_away_from_one() {
+        for _ in 0..100 {
+            let factor = random_factor();
+            assert!((factor - dec!(1)).abs() >= dec!(0.1));
+            assert!(factor >= dec!(0.2) && factor < dec!(5.5));
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Balance assertions
+//!
+//! Borrowed from plain-text accounting: a portfolio can state what its
+//! balances must be on given days, e.g. as read off an exchange statement,
+//! in `<state dir>/assertions/<portfolio>.toml`:
+//! ```toml
+//! [[balance]]
+//! date = "2025-01-01"
+//! ticker = "BTC"
+//! balance = "1.2345"
+//! ```
+//! `doctor` and `report` recompute each balance from the trades up to the
+//! end of its day (UTC) and flag those that differ, so rows lost, doubled
+//! or mistyped later are noticed early.
+
+use crate::currency::Currency;
+use crate::portfolio::{Portfolio, list_entries};
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::warnings;
+use anyhow::{Context, Result};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use time::Date;
+
+time::serde::format_description!(assertion_date, Date, "[year]-[month]-[day]");
+
+/// Balance of `ticker` at the end of `date`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BalanceAssertion {
+    #[serde(with = "assertion_date")]
+    pub date: Date,
+    pub ticker: String,
+    pub balance: Decimal,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AssertionsFile {
+    #[serde(default)]
+    balance: Vec<BalanceAssertion>,
+}
+
+/// `<state dir>/assertions/<portfolio>.toml`
+fn assertions_path(name: &str, settings: &Settings) -> PathBuf {
+    settings
+        .state_dir()
+        .join("assertions")
+        .join(name)
+        .with_extension("toml")
+}
+
+/// Assertions of a portfolio, empty when it has none
+pub fn load(name: &str, settings: &Settings) -> Result<Vec<BalanceAssertion>> {
+    let path = assertions_path(name, settings);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let file: AssertionsFile = toml::from_str(&std::fs::read_to_string(&path)?)
+        .with_context(|| format!("parsing {}", path.display()))?;
+    Ok(file.balance)
+}
+
+/// The assertions of portfolio `name` its trades don't meet, as messages,
+/// and how many were checked
+pub fn check(name: &str, settings: &Settings) -> Result<(Vec<String>, usize)> {
+    let assertions = load(name, settings)?;
+    let mut by_date: BTreeMap<Date, Vec<&BalanceAssertion>> = BTreeMap::new();
+    for a in &assertions {
+        by_date.entry(a.date).or_default().push(a);
+    }
+
+    let mut failed = Vec::new();
+    for (date, assertions) in by_date {
+        let pf = Portfolio::from_csv_as_of(settings.path_for(name), Some(date))?;
+        for a in assertions {
+            let currency = Currency::new(&a.ticker)?;
+            let actual = pf
+                .positions
+                .get(&currency)
+                .map(|p| p.balance)
+                .unwrap_or_default();
+            if actual != a.balance {
+                failed.push(format!(
+                    "on {} {} balance is {} instead of {}",
+                    date,
+                    currency,
+                    actual.normalize(),
+                    a.balance.normalize()
+                ));
+            }
+        }
+    }
+    Ok((failed, assertions.len()))
+}
+
+/// After `report`: warn about the assertions of portfolio `name` that fail
+pub fn report(name: &str, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let (failed, checked) = check(name, settings)?;
+    for message in &failed {
+        warnings::warn(format!("assertion failed: {}", message));
+    }
+    if checked > 0 && failed.is_empty() {
+        reporter.print(&format!("Assertions:\t{} balances as asserted", checked));
+    }
+    Ok(())
+}
+
+/// For `doctor`: print the failing assertions of every portfolio, returning
+/// how many failed or couldn't be checked
+pub fn print_failures(reporter: &dyn Reporter, settings: &Settings) -> Result<usize> {
+    let mut problems = 0;
+    for entry in list_entries(settings)? {
+        match check(&entry.name, settings) {
+            Ok((failed, _)) => {
+                for message in &failed {
+                    reporter.print(&format!(
+                        "⚠️  assertion of '{}' failed: {}",
+                        entry.name, message
+                    ));
+                }
+                problems += failed.len();
+            }
+            Err(e) => {
+                reporter.print(&format!(
+                    "⚠️  can't check the assertions of '{}': {:#}",
+                    entry.name, e
+                ));
+                problems += 1;
+            }
+        }
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_recomputes_balances_at_the_end_of_each_day() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        create_test_csv(
+            &dir,
+            "pf",
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1.2345,40000,0
+1705056000,BTC/USD,SELL,0.2,50000,0
+",
+        );
+        assert_eq!(check("pf", &settings).unwrap(), (Vec::new(), 0));
+
+        let path = assertions_path("pf", &settings);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &path,
+            r#"
+[[balance]]
+date = "2024-01-10"
+ticker = "btc"
+balance = 1.2345
+
+[[balance]]
+date = "2024-01-12"
+ticker = "BTC"
+balance = "1.2345"
+
+[[balance]]
+date = "2024-01-12"
+ticker = "ETH"
+balance = 0
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            check("pf", &settings).unwrap(),
+            (
+                vec!["on 2024-01-12 BTC balance is 1.0345 instead of 1.2345".to_string()],
+                3
+            )
+        );
+
+        std::fs::write(&path, "[[balance]]\ndate = 2024-01-10\n").unwrap();
+        assert!(check("pf", &settings).is_err());
+    }
+}
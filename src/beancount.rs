@@ -0,0 +1,294 @@
+//! Beancount ledgers with cost-basis lots
+//!
+//! Rows are replayed through the lot engine, so every lot bought or
+//! received is booked at its cost, `0.5 BTC {40040 USD}`, and every sale,
+//! swap, withdrawal or fee reduces exactly the lots it consumed,
+//! `-0.25 BTC {40040 USD, 2024-01-10}`. Sales for the base currency book
+//! the gain against `Income:CapitalGains`.
+
+use crate::currency::{Currency, CurrencyType};
+use crate::lots::{Booking, Lot, Lots};
+use crate::trade::{Side, Trade, sort_chronologically};
+use anyhow::{Result, bail};
+use rust_decimal::Decimal;
+use std::fmt::Write;
+use time::OffsetDateTime;
+use time::macros::format_description;
+
+const TRANSFERS: &str = "Equity:Transfers";
+const FEES: &str = "Expenses:Fees";
+const INCOME: &str = "Income:Rewards";
+const GAINS: &str = "Income:CapitalGains";
+
+fn date(at: OffsetDateTime) -> Result<String> {
+    Ok(at.format(format_description!("[year]-[month]-[day]"))?)
+}
+
+/// Account of a portfolio, its name capitalized and cleaned to what
+/// beancount accepts, `my_binance` → `Assets:My-binance`
+pub fn account(name: &str) -> String {
+    let mut component: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    if !component.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        component.insert(0, 'P');
+    }
+    component[..1].make_ascii_uppercase();
+    format!("Assets:{}", component)
+}
+
+/// Commodities have to start with a letter, `1INCH` → `X1INCH`
+fn commodity(ticker: &str) -> String {
+    if ticker.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        ticker.to_string()
+    } else {
+        format!("X{}", ticker)
+    }
+}
+
+fn amount(value: Decimal, ticker: &str) -> String {
+    format!("{} {}", value.normalize(), commodity(ticker))
+}
+
+/// Unit costs are rounded the same way for the lot and its reductions, so they match
+fn unit(cost: Decimal, base: &str) -> String {
+    amount(cost.round_dp(8), base)
+}
+
+/// Postings of what a row received
+fn acquisition(asset: &Currency, value: Decimal, lot: Option<Lot>, base: &str) -> String {
+    match lot {
+        None => amount(value, asset.ticker()),
+        Some(lot) if asset.currency_type() == CurrencyType::Crypto => {
+            format!(
+                "{} {{{}}}",
+                amount(value, asset.ticker()),
+                unit(lot.cost, base)
+            )
+        }
+        // fiat and stable coins are held as plain units, converted at their cost
+        Some(lot) => format!(
+            "{} @ {}",
+            amount(value, asset.ticker()),
+            unit(lot.cost, base)
+        ),
+    }
+}
+
+/// Postings of what a row gave up, one reduction per lot consumed
+fn disposal(
+    asset: &Currency,
+    value: Decimal,
+    booking: &Booking,
+    base: &str,
+    at: OffsetDateTime,
+) -> Result<Vec<String>> {
+    if asset.ticker() == base {
+        return Ok(vec![amount(-value, base)]);
+    }
+    if asset.currency_type() != CurrencyType::Crypto {
+        let cost = booking.cost / value;
+        return Ok(vec![format!(
+            "{} @ {}",
+            amount(-value, asset.ticker()),
+            unit(cost, base)
+        )]);
+    }
+    let held: Decimal = booking.disposed.iter().map(|lot| lot.amount).sum();
+    if held < value {
+        bail!(
+            "{}: gives up {} {} with only {} held",
+            date(at)?,
+            value.normalize(),
+            asset,
+            held.normalize()
+        );
+    }
+    booking
+        .disposed
+        .iter()
+        .map(|lot| {
+            Ok(format!(
+                "{} {{{}, {}}}",
+                amount(-lot.amount, asset.ticker()),
+                unit(lot.cost, base),
+                date(lot.acquired)?
+            ))
+        })
+        .collect()
+}
+
+/// Transactions of the rows of one portfolio, booked on `account(name)`
+fn transactions(name: &str, base: &Currency, trades: &[Trade], out: &mut String) -> Result<()> {
+    let assets = account(name);
+    let base_ticker = base.ticker();
+    let mut trades: Vec<&Trade> = trades.iter().collect();
+    sort_chronologically(&mut trades);
+    let mut lots = Lots::new(base.clone());
+    for t in trades {
+        let booking = lots.apply(t)?;
+        // (account, amount) of each posting
+        let mut postings: Vec<(&str, String)> = Vec::new();
+        if t.side.is_cash() {
+            let asset = t.pair.asset(t.side)?;
+            match t.side {
+                Side::Deposit | Side::Income => {
+                    let from = if t.side == Side::Deposit {
+                        TRANSFERS
+                    } else {
+                        INCOME
+                    };
+                    postings.push((
+                        &assets,
+                        acquisition(asset, t.amount, booking.acquired, base_ticker),
+                    ));
+                    postings.push((from, amount(-booking.cost, base_ticker)));
+                }
+                _ => {
+                    let to = if t.side == Side::Withdraw {
+                        TRANSFERS
+                    } else {
+                        FEES
+                    };
+                    for p in disposal(asset, t.amount, &booking, base_ticker, t.created_at)? {
+                        postings.push((&assets, p));
+                    }
+                    postings.push((to, amount(booking.cost, base_ticker)));
+                }
+            }
+        } else {
+            let pair = t.pair.trading_pair(t.side)?;
+            let tx = t.to_tx()?;
+            let (given, received) = if t.side == Side::Buy {
+                (&pair.quote, &pair.base)
+            } else {
+                (&pair.base, &pair.quote)
+            };
+            let sale = t.side == Side::Sell && received == base;
+            for p in disposal(given, tx.sell_size, &booking, base_ticker, t.created_at)? {
+                // the sale price is informational, beancount balances reductions at cost
+                let p = if sale {
+                    format!("{} @ {}", p, amount(t.price, base_ticker))
+                } else {
+                    p
+                };
+                postings.push((&assets, p));
+            }
+            postings.push((
+                &assets,
+                acquisition(received, tx.buy_size, booking.acquired, base_ticker),
+            ));
+            let gain = tx.buy_size - booking.cost;
+            if sale && !gain.is_zero() {
+                postings.push((GAINS, amount(-gain, base_ticker)));
+            }
+        }
+
+        writeln!(out, "{} * \"{} {}\"", date(t.created_at)?, t.side, t.pair)?;
+        writeln!(out, "  id: \"{}\"", t.id())?;
+        if let Some(source) = &t.source {
+            writeln!(out, "  source: \"{}\"", source)?;
+        }
+        for (account, amount) in postings {
+            writeln!(out, "  {:<30}  {}", account, amount)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Ledger of `portfolios`, given as (name, base currency, rows), with the
+/// accounts opened on the day of the first row
+pub fn ledger(portfolios: &[(String, Currency, Vec<Trade>)]) -> Result<String> {
+    let mut out = String::new();
+    let Some(first) = portfolios
+        .iter()
+        .flat_map(|(_, _, trades)| trades.iter().map(|t| t.created_at))
+        .min()
+    else {
+        return Ok(out);
+    };
+    let opened = date(first)?;
+    for account in [TRANSFERS, FEES, INCOME, GAINS] {
+        writeln!(out, "{} open {}", opened, account)?;
+    }
+    for (name, _, _) in portfolios {
+        writeln!(out, "{} open {}", opened, account(name))?;
+    }
+    writeln!(out)?;
+    for (name, base, trades) in portfolios {
+        writeln!(out, "; portfolio {}, base currency {}", name, base)?;
+        writeln!(out)?;
+        transactions(name, base, trades, &mut out)?;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::transactions_from;
+
+    #[test]
+    fn test_ledger_books_lots_at_cost_and_reduces_them() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee,source
+1704067200,USD,DEPOSIT,30000,1,0,manual
+1704883200,BTC/USD,BUY,0.5,40000,20,
+1706745600,BTC/USD,BUY,0.1,50000,0,
+1710460800,BTC/USD,SELL,0.55,60000,10,
+1711929600,ETH/BTC,BUY,1,0.05,0,
+",
+        );
+        let usd = Currency::new("USD").unwrap();
+        let ledger = ledger(&[("my_pf".to_string(), usd, trades)]).unwrap();
+        let entries: Vec<&str> = ledger.split("\n\n").collect();
+        assert_eq!(
+            entries[0],
+            "2024-01-01 open Equity:Transfers
+2024-01-01 open Expenses:Fees
+2024-01-01 open Income:Rewards
+2024-01-01 open Income:CapitalGains
+2024-01-01 open Assets:My-pf"
+        );
+        assert_eq!(
+            entries[3],
+            "2024-01-10 * \"Buy BTC/USD\"
+  id: \"6a25bc60\"
+  Assets:My-pf                    -20020 USD
+  Assets:My-pf                    0.5 BTC {40040 USD}"
+        );
+        // the whole january lot and half of the february one
+        assert_eq!(
+            entries[5],
+            "2024-03-15 * \"Sell BTC/USD\"
+  id: \"160f9a88\"
+  Assets:My-pf                    -0.5 BTC {40040 USD, 2024-01-10} @ 60000 USD
+  Assets:My-pf                    -0.05 BTC {50000 USD, 2024-02-01} @ 60000 USD
+  Assets:My-pf                    32990 USD
+  Income:CapitalGains             -10470 USD"
+        );
+        // the ETH cost what the BTC paid for it cost
+        assert!(entries[6].contains("-0.05 BTC {50000 USD, 2024-02-01}\n"));
+        assert!(entries[6].ends_with("1 ETH {2500 USD}"));
+        assert_eq!(account("2x"), "Assets:2x");
+        assert_eq!(commodity("1INCH"), "X1INCH");
+    }
+
+    #[test]
+    fn test_ledger_refuses_to_reduce_lots_not_held() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,0.5,40000,0
+1710460800,BTC,WITHDRAW,1,60000,0
+",
+        );
+        let usd = Currency::new("USD").unwrap();
+        let err = ledger(&[("main".to_string(), usd, trades)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "2024-03-15: gives up 1 BTC with only 0.5 held"
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Trading behavior
+//!
+//! `stats --behavior` puts numbers on habits of active traders: how often
+//! they buy, how long what they sell was held, and how many of their sales
+//! made a gain. Holding times follow the FIFO lots of `lots`, so a sale
+//! counts from when the units it disposes of were bought.
+
+use crate::currency::Currency;
+use crate::lots::Lots;
+use crate::math::{self, Percent};
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{Side, Trade, parse_csv_file, sort_chronologically};
+use anyhow::Result;
+use rust_decimal::Decimal;
+use thousands::Separable;
+use time::Duration;
+
+/// Trading habits of a portfolio, see `analyze`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Behavior {
+    pub buys: usize,
+    /// Median time between consecutive buys, of any asset
+    pub median_between_buys: Option<Duration>,
+    /// Parts of lots given up by buys and sells, e.g. BTC sold or swapped
+    pub disposed_lots: usize,
+    /// Mean time the disposed lots were held
+    pub mean_holding: Option<Duration>,
+    /// Sales for the base currency, the rows realizing a gain or loss
+    pub sales: usize,
+    /// Sales whose proceeds, after the fee, exceed the cost of what was sold
+    pub wins: usize,
+    pub realized: Decimal,
+}
+
+impl Behavior {
+    /// Share of the sales that made a gain
+    pub fn win_rate(&self) -> Option<Percent> {
+        math::percent_of(Decimal::from(self.wins), Decimal::from(self.sales))
+    }
+}
+
+fn median(mut spans: Vec<i64>) -> Option<Duration> {
+    spans.sort_unstable();
+    let mid = spans.len() / 2;
+    let seconds = match spans.len() {
+        0 => return None,
+        n if n % 2 == 0 => (spans[mid - 1] + spans[mid]) / 2,
+        _ => spans[mid],
+    };
+    Some(Duration::seconds(seconds))
+}
+
+/// Replay `trades` and measure the habits they show, gains in `base`
+pub fn analyze(trades: &[Trade], base: &Currency) -> Result<Behavior> {
+    let mut trades: Vec<&Trade> = trades.iter().collect();
+    sort_chronologically(&mut trades);
+    let mut lots = Lots::new(base.clone());
+    let mut buy_times = Vec::new();
+    let mut held = Vec::new();
+    let (mut sales, mut wins, mut realized) = (0, 0, Decimal::ZERO);
+
+    for t in trades {
+        let booking = lots.apply(t)?;
+        if !matches!(t.side, Side::Buy | Side::Sell) {
+            continue;
+        }
+        if t.side == Side::Buy {
+            buy_times.push(t.created_at);
+        }
+        held.extend(
+            booking
+                .disposed
+                .iter()
+                .map(|lot| (t.created_at - lot.acquired).whole_seconds()),
+        );
+        let tx = t.to_tx()?;
+        if t.side == Side::Sell && tx.buy == *base {
+            let gain = tx.buy_size - booking.cost;
+            sales += 1;
+            wins += usize::from(gain > Decimal::ZERO);
+            realized += gain;
+        }
+    }
+
+    let between = buy_times
+        .windows(2)
+        .map(|w| (w[1] - w[0]).whole_seconds())
+        .collect();
+    let mean_holding = match held.len() {
+        0 => None,
+        n => Some(Duration::seconds(held.iter().sum::<i64>() / n as i64)),
+    };
+    Ok(Behavior {
+        buys: buy_times.len(),
+        median_between_buys: median(between),
+        disposed_lots: held.len(),
+        mean_holding,
+        sales,
+        wins,
+        realized,
+    })
+}
+
+/// `2.5 days`, or hours below a day
+fn span(d: Duration) -> String {
+    let seconds = Decimal::from(d.whole_seconds());
+    if d < Duration::days(1) {
+        format!("{} hours", (seconds / Decimal::from(3600)).round_dp(1))
+    } else {
+        format!("{} days", (seconds / Decimal::from(86400)).round_dp(1))
+    }
+}
+
+/// `stats --behavior`: print the trading habits of a portfolio
+pub fn run(name: &str, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let (config, trades) = parse_csv_file(settings.path_for(name))?;
+    let base = Currency::new(&config.base_currency)?;
+    let b = analyze(&trades, &base)?;
+
+    reporter.print(&format!("Portfolio:\t{} ({})", name, base));
+    match b.median_between_buys {
+        Some(between) => reporter.print(&format!(
+            "Buys:\t\t{}, a median {} apart",
+            b.buys,
+            span(between)
+        )),
+        None => reporter.print(&format!("Buys:\t\t{}, too few to space out", b.buys)),
+    }
+    match b.mean_holding {
+        Some(holding) => reporter.print(&format!(
+            "Holding:\t{} lots sold or swapped, held {} on average",
+            b.disposed_lots,
+            span(holding)
+        )),
+        None => reporter.print("Holding:\tno lots sold or swapped yet"),
+    }
+    match b.win_rate() {
+        Some(rate) => reporter.print(&format!(
+            "Win rate:\t{:.2} of {} sales for {} ({} with a gain), realized {} {}",
+            rate.round_dp(2),
+            b.sales,
+            base,
+            b.wins,
+            settings
+                .rounding
+                .cents(b.realized)
+                .normalize()
+                .separate_with_commas(),
+            base
+        )),
+        None => reporter.print(&format!("Win rate:\tno sales for {} yet", base)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::transactions_from;
+    use rust_decimal::dec;
+
+    #[test]
+    fn test_analyze_spaces_buys_ages_disposed_lots_and_counts_winning_sales() {
+        let usd = Currency::new("USD").unwrap();
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704067200,BTC/USD,BUY,1,40000,0
+1704153600,ETH/USD,BUY,10,2000,0
+1704499200,BTC/USD,BUY,1,50000,0
+1705276800,BTC/USD,SELL,1.5,48000,100
+1705276800,USD,DEPOSIT,1000,1,0
+1706140800,ETH/USD,SELL,10,1900,0
+",
+        );
+        let b = analyze(&trades, &usd).unwrap();
+
+        // a day, then four days apart
+        assert_eq!(b.buys, 3);
+        assert_eq!(b.median_between_buys, Some(Duration::hours(60)));
+        // 1 BTC held 14 days, 0.5 held 9 days, the ETH 23 days
+        assert_eq!(b.disposed_lots, 3);
+        assert_eq!(b.mean_holding, Some(Duration::days(46) / 3));
+        // 72000 - 100 for 65000 of BTC, 19000 for 20000 of ETH
+        assert_eq!((b.sales, b.wins), (2, 1));
+        assert_eq!(b.realized, dec!(5900));
+        assert_eq!(b.win_rate(), Some(Percent::new(dec!(50))));
+
+        assert_eq!(span(Duration::hours(36)), "1.5 days");
+        assert_eq!(span(Duration::minutes(90)), "1.5 hours");
+        assert_eq!(median(vec![]), None);
+        assert_eq!(median(vec![3, 1, 2]), Some(Duration::seconds(2)));
+    }
+}
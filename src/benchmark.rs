@@ -0,0 +1,256 @@
+use crate::currency::{Currency, CurrencyType};
+use crate::math;
+use crate::position_cache;
+use crate::price_history;
+use crate::quote::{LiveQuotes, quote_in_base};
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{Side, Trade, parse_csv_file};
+use anyhow::{Result, bail};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use std::collections::HashMap;
+use thousands::Separable;
+use time::Date;
+
+/// Composite of crypto assets to compare a portfolio with, weights in percent
+///
+/// Defined in the config file, e.g.
+/// ```toml
+/// [benchmarks.my60_40]
+/// BTC = 60
+/// ETH = 40
+/// ```
+/// A single crypto ticker works as a benchmark without any config.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Benchmark {
+    pub name: String,
+    pub weights: Vec<(Currency, Decimal)>,
+}
+
+impl Benchmark {
+    pub fn new(name: &str, weights: &[(&str, Decimal)]) -> Result<Self> {
+        let mut parsed = Vec::new();
+        for (ticker, weight) in weights {
+            let currency = Currency::new(ticker)?;
+            if currency.currency_type() != CurrencyType::Crypto {
+                bail!("benchmark '{}': {} isn't a crypto asset", name, currency);
+            }
+            if *weight <= dec!(0) {
+                bail!(
+                    "benchmark '{}': weight of {} must be positive",
+                    name,
+                    currency
+                );
+            }
+            parsed.push((currency, *weight));
+        }
+        parsed.sort_by_key(|(c, w)| (std::cmp::Reverse(*w), c.to_string()));
+        let total: Decimal = parsed.iter().map(|(_, w)| *w).sum();
+        if total != dec!(100) {
+            bail!(
+                "benchmark '{}': weights add up to {}%, not 100%",
+                name,
+                total
+            );
+        }
+        Ok(Benchmark {
+            name: name.to_string(),
+            weights: parsed,
+        })
+    }
+
+    /// Benchmark `name` from the config, or a single crypto ticker
+    pub fn from_settings(name: &str, settings: &Settings) -> Result<Self> {
+        let configured = settings
+            .benchmarks
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name));
+        if let Some((name, weights)) = configured {
+            let weights: Vec<(&str, Decimal)> =
+                weights.iter().map(|(t, w)| (t.as_str(), *w)).collect();
+            return Benchmark::new(name, &weights);
+        }
+        match Currency::new(name) {
+            Ok(c) if c.currency_type() == CurrencyType::Crypto => {
+                Benchmark::new(c.ticker(), &[(c.ticker(), dec!(100))])
+            }
+            _ => bail!(
+                "unknown benchmark '{}', define it as [benchmarks.{}] in the config file",
+                name,
+                name
+            ),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        self.weights
+            .iter()
+            .map(|(c, w)| format!("{}% {}", w.normalize(), c))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Current value of investing the same `flows` into the benchmark on
+    /// the same days, buying and selling all components at their weights
+    pub fn value(
+        &self,
+        flows: &[Flow],
+        mut price_on: impl FnMut(&Currency, Date) -> Result<Decimal>,
+        mut current_price: impl FnMut(&Currency) -> Result<Decimal>,
+    ) -> Result<Decimal> {
+        let mut units: HashMap<&Currency, Decimal> = HashMap::new();
+        for flow in flows {
+            for (currency, weight) in &self.weights {
+                let price = price_on(currency, flow.date)?;
+                let held = units.entry(currency).or_default();
+                // a sale can't take out more than the benchmark holds
+                *held = (*held + flow.amount * *weight / dec!(100) / price).max(dec!(0));
+            }
+        }
+        let mut value = dec!(0);
+        for (currency, held) in units {
+            value += held * current_price(currency)?;
+        }
+        Ok(value)
+    }
+}
+
+/// Money put into (positive) or taken out of (negative) crypto on one day
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flow {
+    pub date: Date,
+    pub amount: Decimal,
+}
+
+/// Buys and sells quoted in the base currency, as money flows
+pub fn flows(trades: &[Trade], base: &Currency) -> Result<Vec<Flow>> {
+    let mut flows = Vec::new();
+    for t in trades {
+        if t.side.is_cash() || t.pair.trading_pair(t.side)?.quote != *base {
+            continue;
+        }
+        let amount = match t.side {
+            Side::Buy => t.amount * t.price + t.fee,
+            _ => -(t.amount * t.price - t.fee),
+        };
+        flows.push(Flow {
+            date: t.created_at.date(),
+            amount,
+        });
+    }
+    flows.sort_by_key(|f| f.date);
+    Ok(flows)
+}
+
+/// Print the portfolio value next to the value of the same money invested
+/// in the benchmark
+pub fn report(
+    name: &str,
+    benchmark: &str,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let benchmark = Benchmark::from_settings(benchmark, settings)?;
+    let path = settings.path_for(name);
+    let (config, trades) = parse_csv_file(&path)?;
+    let base = Currency::new(&config.base_currency)?;
+    let flows = flows(&trades, &base)?;
+    reporter.print("");
+    if flows.is_empty() {
+        reporter.print(&format!(
+            "No buys in {} to compare with {}",
+            base, benchmark.name
+        ));
+        return Ok(());
+    }
+
+    let pf_value = position_cache::load(&path, settings)?.market_value(&LiveQuotes)?;
+    let bench_value = benchmark.value(
+        &flows,
+        |c, date| price_history::price_on(c, base.ticker(), date, settings),
+        |c| quote_in_base(c, base.ticker()),
+    )?;
+    let invested: Decimal = flows.iter().map(|f| f.amount).sum();
+
+    let money = |d: Decimal| {
+        format!(
+            "{} {}",
+            d.round_dp(2).normalize().separate_with_commas(),
+            base
+        )
+    };
+    let pct = |value: Decimal| {
+        math::change_pct(invested, value).map_or(String::new(), |p| format!(" ({:+.2})", p))
+    };
+    reporter.print(&format!(
+        "Benchmark {} ({}):",
+        benchmark.name,
+        benchmark.describe()
+    ));
+    reporter.print(&format!(
+        "Invested:\t{} (buys net of sales)",
+        money(invested)
+    ));
+    reporter.print(&format!("Portfolio:\t{}{}", money(pf_value), pct(pf_value)));
+    reporter.print(&format!(
+        "Benchmark:\t{}{}",
+        money(bench_value),
+        pct(bench_value)
+    ));
+    reporter.print(&format!("Difference:\t{}", money(pf_value - bench_value)));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::transactions_from;
+    use anyhow::anyhow;
+    use time::macros::date;
+
+    #[test]
+    fn test_benchmark_weights_must_add_up() {
+        let b = Benchmark::new("mix", &[("eth", dec!(40)), ("btc", dec!(60))]).unwrap();
+        assert_eq!(b.describe(), "60% BTC, 40% ETH");
+        assert!(Benchmark::new("mix", &[("BTC", dec!(60)), ("ETH", dec!(30))]).is_err());
+        assert!(Benchmark::new("mix", &[("BTC", dec!(60)), ("USD", dec!(40))]).is_err());
+    }
+
+    #[test]
+    fn test_value_invests_flows_at_historical_prices() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704067200,BTC/USD,BUY,1,40000,0
+1706745600,ETH/USD,BUY,10,2000,0
+1709251200,BTC/USD,SELL,0.5,60000,0
+1709251200,ETH/BTC,BUY,1,0.05,0
+",
+        );
+        let usd = Currency::new("USD").unwrap();
+        let flows = flows(&trades, &usd).unwrap();
+        assert_eq!(
+            flows.iter().map(|f| f.amount).collect::<Vec<_>>(),
+            vec![dec!(40000), dec!(20000), dec!(-30000)]
+        );
+
+        let b = Benchmark::new("mix", &[("BTC", dec!(50)), ("ETH", dec!(50))]).unwrap();
+        let history = |c: &Currency, date: Date| match (c.ticker(), date) {
+            ("BTC", d) if d == date!(2024 - 01 - 01) => Ok(dec!(40000)),
+            ("BTC", d) if d == date!(2024 - 02 - 01) => Ok(dec!(40000)),
+            ("BTC", _) => Ok(dec!(60000)),
+            ("ETH", d) if d == date!(2024 - 01 - 01) => Ok(dec!(2000)),
+            ("ETH", d) if d == date!(2024 - 02 - 01) => Ok(dec!(2000)),
+            ("ETH", _) => Ok(dec!(3000)),
+            _ => Err(anyhow!("no price")),
+        };
+        // bought 0.75 BTC and 15 ETH, sold 0.25 BTC and 5 ETH
+        let value = b
+            .value(&flows, history, |c| match c.ticker() {
+                "BTC" => Ok(dec!(100000)),
+                _ => Ok(dec!(4000)),
+            })
+            .unwrap();
+        assert_eq!(value, dec!(90000));
+    }
+}
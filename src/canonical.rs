@@ -0,0 +1,187 @@
+//! Canonical portfolio files
+//!
+//! Every command that writes rows writes them in one canonical form (see
+//! `Trade::to_record`), so a file edited by hand or by another program
+//! changes shape on the first tool-driven edit. `fmt` does that rewrite on
+//! its own, as a separate commit-able step: rows keep their order, metadata
+//! keeps unknown keys, and formatting a formatted file changes nothing.
+
+use crate::index;
+use crate::portfolio::list_entries;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{
+    PortfolioKind, SOURCE_COLUMN, Trade, extract_csv_config, render_csv_records, trades_reader,
+};
+use crate::writer;
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+
+/// The canonical text of a trades portfolio file
+pub fn render(input: &str) -> Result<Vec<u8>> {
+    let (config, data) = extract_csv_config(input)?;
+    config.ensure_trades()?;
+    let metadata_lines = input[..input.len() - data.len()].lines().count() as u64;
+    let mut reader = trades_reader(data.as_bytes());
+    let headers = reader.headers()?.clone();
+    let with_source = headers.iter().any(|h| h == SOURCE_COLUMN);
+
+    let mut records = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let line = metadata_lines + record.position().map_or(0, |p| p.line());
+        let trade: Trade = record
+            .deserialize(Some(&headers))
+            .with_context(|| format!("line {}", line))?;
+        records.push(trade.to_record());
+    }
+    render_csv_records(&config, &records, with_source)
+}
+
+/// Rewrite portfolio `name` in canonical form, or with `check` only tell
+/// whether it is; true when it wasn't canonical
+fn format(name: &str, path: &Path, check: bool, settings: &Settings) -> Result<bool> {
+    let input = std::fs::read_to_string(path)?;
+    let output = render(&input)?;
+    if output == input.as_bytes() {
+        return Ok(false);
+    }
+    if !check {
+        extract_csv_config(&input)?.0.ensure_open()?;
+        let _lock = writer::lock(name, settings)?;
+        let tmp = path.with_extension("csv.tmp");
+        std::fs::write(&tmp, output)?;
+        std::fs::rename(&tmp, path)?;
+        index::record_write(name, settings);
+    }
+    Ok(true)
+}
+
+/// `fmt`: rewrite portfolio `name`, or every trades portfolio, in
+/// canonical form; with `check` fail when one isn't instead
+pub fn run(
+    name: Option<&str>,
+    check: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let targets = match name {
+        Some(name) => vec![(name.to_string(), settings.path_for(name))],
+        None => list_entries(settings)?
+            .into_iter()
+            .filter(|e| {
+                crate::trade::read_csv_config(&e.path)
+                    .is_ok_and(|config| config.kind == PortfolioKind::Trades)
+            })
+            .map(|e| (e.name, e.path))
+            .collect(),
+    };
+
+    let mut changed = 0;
+    for (name, path) in &targets {
+        let formatted = format(name, path, check, settings)
+            .with_context(|| format!("formatting '{}'", name))?;
+        if formatted {
+            changed += 1;
+            reporter.status(&match check {
+                true => format!("{} is not canonical", path.display()),
+                false => format!("Formatted {}", path.display()),
+            });
+        }
+    }
+
+    if check && changed > 0 {
+        bail!("{} portfolio(s) need `fmt`", changed);
+    }
+    if changed == 0 {
+        reporter.status(&format!("{} portfolio(s) already canonical", targets.len()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trade::{MAX_MAGNITUDE, MAX_SCALE};
+    use proptest::prelude::*;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_render_keeps_order_and_metadata_and_exact_values() {
+        let input = "# base_currency: eur
+# synced_by: nas
+created_at , pair , side,amount,price,fee,source
+1704883200, btc/eur ,buy,1.50,40000.00,7.5,
+1704800000,ETH/EUR,Sell,2,2.5E3,0,manual
+";
+        let output = String::from_utf8(render(input).unwrap()).unwrap();
+        assert_eq!(
+            output,
+            "# base_currency: EUR
+# synced_by: nas
+created_at,pair,side,amount,price,fee,source
+1704883200,BTC/EUR,BUY,1.5,40000,7.5,
+1704800000,ETH/EUR,SELL,2,2500,0,manual
+"
+        );
+        assert_eq!(render(&output).unwrap(), output.as_bytes());
+
+        let precise = "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,0.123456789012345678,40000,1
+";
+        assert_eq!(render(precise).unwrap(), precise.as_bytes());
+    }
+
+    fn decimal() -> impl Strategy<Value = String> {
+        // any value `check_precision` accepts
+        (0..=MAX_SCALE)
+            .prop_flat_map(|dp| {
+                let max = (MAX_MAGNITUDE.mantissa() * 10i128.pow(dp)).min(Decimal::MAX.mantissa());
+                (1..=max, Just(dp), 0usize..3)
+            })
+            // with trailing zeros, a `.0` at least
+            .prop_map(|(n, dp, zeros)| {
+                let d = Decimal::from_i128_with_scale(n, dp);
+                match dp {
+                    0 => format!("{}.{}", d, "0".repeat(zeros + 1)),
+                    _ => format!("{}{}", d, "0".repeat(zeros)),
+                }
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn test_render_is_idempotent_and_keeps_the_trades(
+            rows in prop::collection::vec(
+                (
+                    1_300_000_000i64..1_700_000_000,
+                    prop::sample::select(vec!["btc/usd", " ETH/USD", "Sol/usdc ", "USD"]),
+                    prop::sample::select(vec!["buy", "SELL", "Buy"]),
+                    decimal(),
+                    decimal(),
+                    decimal(),
+                ),
+                0..8,
+            )
+        ) {
+            let mut input = "# base_currency: USD\ncreated_at,pair,side,amount,price,fee\n".to_string();
+            for (ts, pair, side, amount, price, fee) in &rows {
+                let (pair, side) = match pair.trim() {
+                    "USD" => (*pair, "deposit"),
+                    _ => (*pair, *side),
+                };
+                input.push_str(&format!("{ts},{pair},{side},{amount},{price},{fee}\n"));
+            }
+            let once = render(&input).unwrap();
+            let once = String::from_utf8(once).unwrap();
+            prop_assert_eq!(render(&once).unwrap(), once.as_bytes());
+
+            let read = |text: &str| -> Vec<Trade> {
+                let (_, data) = extract_csv_config(text).unwrap();
+                trades_reader(data.as_bytes()).deserialize().collect::<Result<_, _>>().unwrap()
+            };
+            prop_assert_eq!(read(&input), read(&once));
+        }
+    }
+}
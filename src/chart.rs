@@ -0,0 +1,167 @@
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use std::collections::BTreeMap;
+use time::{Date, Duration};
+
+/// Cells of `heatmap`, from days without activity to the busiest ones
+const HEAT: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+/// Plot `values` as an ASCII line, one column per value, `height` rows high
+///
+/// The rows are labelled with the value at that height, highest first:
+/// ```text
+/// 42000.00 |     *
+/// 41000.00 |  **
+/// 40000.00 |*
+/// ```
+pub fn line(values: &[Decimal], height: usize) -> String {
+    let values: Vec<f64> = values.iter().filter_map(|v| v.to_f64()).collect();
+    if values.is_empty() || height == 0 {
+        return String::new();
+    }
+
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    // a flat line is drawn on a single row
+    let height = if max > min { height.max(2) } else { 1 };
+    let step = if height > 1 {
+        (max - min) / (height - 1) as f64
+    } else {
+        0.0
+    };
+
+    let row_of = |v: f64| -> usize {
+        if step == 0.0 {
+            0
+        } else {
+            ((v - min) / step).round() as usize
+        }
+    };
+
+    let label_width = format!("{:.2}", max).len();
+    let mut out = String::new();
+    for row in (0..height).rev() {
+        let label = min + step * row as f64;
+        let cells: String = values
+            .iter()
+            .map(|v| if row_of(*v) == row { '*' } else { ' ' })
+            .collect();
+        out.push_str(&format!(
+            "{:>width$.2} |{}\n",
+            label,
+            cells.trim_end(),
+            width = label_width
+        ));
+    }
+    out
+}
+
+/// Calendar of `values` per day, GitHub-style: one column per week from the
+/// week starting on `first`, one row per weekday, up to `last`
+///
+/// Days are shaded by their value relative to the largest one, with month
+/// names above the weeks they start in:
+/// ```text
+///     Jan     Feb
+/// Mon ·░·····█·
+/// Tue ··▒···
+/// ```
+pub fn heatmap(values: &BTreeMap<Date, Decimal>, first: Date, last: Date) -> String {
+    let weeks = ((last - first).whole_days().max(0) / 7 + 1) as usize;
+    let max = values
+        .range(first..=last)
+        .map(|(_, v)| *v)
+        .max()
+        .unwrap_or_default();
+    let level = |v: Decimal| -> usize {
+        if v <= Decimal::ZERO || max <= Decimal::ZERO {
+            0
+        } else {
+            (v * Decimal::from(HEAT.len() - 1) / max)
+                .ceil()
+                .to_usize()
+                .unwrap_or(0)
+                .clamp(1, HEAT.len() - 1)
+        }
+    };
+
+    let mut months = vec![' '; weeks + 3];
+    for week in 0..weeks {
+        let start = first + Duration::weeks(week as i64);
+        let end = start + Duration::days(6);
+        if start.day() == 1 || end.month() != start.month() {
+            let name = &end.month().to_string()[..3];
+            for (i, c) in name.chars().enumerate() {
+                months[week + i] = c;
+            }
+        }
+    }
+    let mut out = format!("    {}\n", months.iter().collect::<String>().trim_end());
+    for weekday in 0..7 {
+        let day = first + Duration::days(weekday);
+        let cells: String = (0..weeks)
+            .map(|week| {
+                let day = day + Duration::weeks(week as i64);
+                if day > last {
+                    ' '
+                } else {
+                    HEAT[level(values.get(&day).copied().unwrap_or_default())]
+                }
+            })
+            .collect();
+        out.push_str(&format!(
+            "{} {}\n",
+            &day.weekday().to_string()[..3],
+            cells.trim_end()
+        ));
+    }
+    out.push_str(&format!(
+        "    Less {} More\n",
+        HEAT.iter().collect::<String>()
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::dec;
+    use time::macros::date;
+
+    #[test]
+    fn test_heatmap_shades_days_by_their_share_of_the_busiest() {
+        let values = BTreeMap::from([
+            (date!(2024 - 01 - 29), dec!(1)),
+            (date!(2024 - 02 - 01), dec!(4)),
+            (date!(2024 - 02 - 06), dec!(2)),
+            // outside the range
+            (date!(2024 - 03 - 01), dec!(100)),
+        ]);
+        let chart = heatmap(&values, date!(2024 - 01 - 22), date!(2024 - 02 - 07));
+        assert_eq!(
+            chart,
+            "     Feb
+Mon ·░·
+Tue ··▒
+Wed ···
+Thu ·█
+Fri ··
+Sat ··
+Sun ··
+    Less ·░▒▓█ More
+"
+        );
+    }
+
+    #[test]
+    fn test_line_plots_one_column_per_value() {
+        let chart = line(&[dec!(40000), dec!(41000), dec!(41000), dec!(42000)], 3);
+        assert_eq!(chart, "42000.00 |   *\n41000.00 | **\n40000.00 |*\n");
+    }
+
+    #[test]
+    fn test_flat_line_uses_single_row() {
+        assert_eq!(line(&[dec!(5), dec!(5)], 4), "5.00 |**\n");
+        assert_eq!(line(&[], 4), "");
+    }
+}
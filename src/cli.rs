@@ -1,5 +1,19 @@
-use clap::{Parser, Subcommand, builder::ValueParser};
+use crate::clock;
+use crate::export::ExportFormat;
+use crate::format::OutputFormat;
+use crate::monthly::StatementFormat;
+use crate::period::Period;
+use crate::portfolio::ListSort;
+use crate::quote;
+use crate::reporter::OutputMode;
+use crate::stats::HeatmapValue;
+use crate::trade::{TxPrice, check_precision};
+use crate::widget::Widget;
+use clap::{ArgGroup, Args, Parser, Subcommand, builder::ValueParser};
 use rust_decimal::Decimal;
+use std::path::{Path, PathBuf};
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime};
 
 /// CSV Portfolio Tracker
 ///
@@ -11,13 +25,54 @@ pub struct Cli {
     pub commands: Cmd,
     #[arg(short, long)]
     pub portfolio_dir: Option<String>,
+    /// Don't offer the first-run setup wizard when no config file exists
+    #[arg(long)]
+    pub no_wizard: bool,
+    /// Don't pipe output longer than the terminal through $PAGER
+    #[arg(long)]
+    pub no_pager: bool,
+    /// Only print results, not where the config was loaded from or what a
+    /// command changed
+    #[arg(long, global = true)]
+    pub quiet: bool,
+    /// Where results go
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub output: OutputMode,
+    /// Give up on network requests once they took this many seconds in
+    /// total, so a hung provider can't stall a cron job
+    #[arg(long, global = true, value_name = "SECS")]
+    pub timeout: Option<u64>,
+    /// Take all prices from this `ticker,price[,date]` CSV and make no
+    /// network requests, for reproducible offline runs
+    #[arg(long, global = true, value_name = "FILE")]
+    pub fixture_prices: Option<PathBuf>,
+    /// Run as if it were this time, RFC 3339 or unix seconds, like
+    /// `CSVPT_FAKE_NOW`
+    #[arg(long, global = true, value_name = "TIME", value_parser = parse_now)]
+    pub now: Option<OffsetDateTime>,
 }
 
 #[derive(Debug, Clone, Subcommand)]
 pub enum Cmd {
     /// List all portfolios
     #[command(visible_aliases = ["l", "ls"])]
-    List,
+    List {
+        /// Only list portfolios whose name matches the glob pattern, e.g. 'kraken*'
+        #[arg(short, long = "match")]
+        pattern: Option<String>,
+        /// Only list portfolios with this base currency
+        #[arg(long)]
+        base_currency: Option<String>,
+        /// Also list portfolios matching `ignore_patterns` from the config
+        #[arg(long)]
+        include_ignored: bool,
+        /// Sort order
+        #[arg(long, value_enum, default_value_t)]
+        sort: ListSort,
+        /// Output format, timestamps are ISO-8601 UTC in json and csv
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
     /// Create new portfolio
     #[command(alias = "n")]
     New {
@@ -25,32 +80,794 @@ pub enum Cmd {
         name: String,
         #[arg(long)]
         currency: Option<String>,
+        /// Store only current holdings (ticker, qty, cost) instead of trades
+        #[arg(long)]
+        holdings: bool,
     },
     /// Show all transactions from portfolio
     #[command(alias = "s")]
     Show {
+        #[arg(short, long, required_unless_present = "stdin")]
+        name: Option<String>,
+        /// Read the trades from stdin instead, as a trade CSV or any export
+        /// `import` reads
+        #[arg(long, conflicts_with_all = ["name", "with_journal"])]
+        stdin: bool,
+        /// Only rows from this source, e.g. 'manual', 'import' or 'import:binance'
+        #[arg(long)]
+        source: Option<String>,
+        /// Output format, timestamps are ISO-8601 UTC in json and csv
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+        /// Also copy the output to the system clipboard
+        #[arg(long)]
+        copy: bool,
+        /// Add trade ids and journal entries
+        #[arg(long)]
+        with_journal: bool,
+    },
+    /// Summarize portfolio rows per side and per source
+    Stats {
+        #[arg(short, long)]
+        name: String,
+        /// Also count rows per week, month or (fiscal) year
+        #[arg(long, value_enum)]
+        by: Option<Period>,
+        /// Invested amount and realized and unrealized PnL per trade tag instead
+        #[arg(long, conflicts_with = "by")]
+        by_tag: bool,
+        /// List trades whose fee is off the configured exchange fee tier instead
+        #[arg(long, conflicts_with_all = ["by", "by_tag"])]
+        fee_check: bool,
+        /// Calendar of trades, or invested amounts, per day over the past year instead
+        #[arg(long, value_enum, value_name = "VALUE", num_args = 0..=1,
+            default_missing_value = "trades", conflicts_with_all = ["by", "by_tag", "fee_check"])]
+        heatmap: Option<HeatmapValue>,
+        /// Time between buys, holding time of sold lots and win rate of sales instead
+        #[arg(long, conflicts_with_all = ["by", "by_tag", "fee_check", "heatmap"])]
+        behavior: bool,
+        /// Volume-weighted average buy and sell price per pair and year instead
+        #[arg(long, conflicts_with_all = ["by", "by_tag", "fee_check", "heatmap", "behavior"])]
+        prices: bool,
+    },
+    /// Trading and network fees paid per asset
+    Fees {
+        #[arg(short, long)]
+        name: String,
+    },
+    /// Income received per asset, with the yield it made on the average balance
+    Income {
         #[arg(short, long)]
         name: String,
     },
     /// Report portfolio PnL
     #[command(alias = "r")]
     Report {
+        #[arg(short, long, required_unless_present_any = ["all", "stdin"])]
+        name: Option<String>,
+        /// Value the trades read from stdin instead, as a trade CSV or any
+        /// export `import` reads
+        #[arg(long, conflicts_with_all = ["name", "all", "diff", "save", "supply", "benchmark", "fx", "as_of", "widget"])]
+        stdin: bool,
+        /// Value all portfolios, trade-based and holdings-only
+        #[arg(long, conflicts_with = "name")]
+        all: bool,
+        /// With --all, also value portfolios matching `ignore_patterns` from the config
+        #[arg(long, requires = "all")]
+        include_ignored: bool,
+        /// Show what changed since the previous run, or since a run saved with --save
+        #[arg(long, value_name = "LABEL", num_args = 0..=1, default_missing_value = "last", conflicts_with = "all")]
+        diff: Option<String>,
+        /// Also keep this run under a label, to diff against later
+        #[arg(long, value_name = "LABEL", conflicts_with = "all")]
+        save: Option<String>,
+        /// Also show supply and market cap of the held assets, fetched once a day
+        #[arg(long, conflicts_with = "all")]
+        supply: bool,
+        /// Compare with the same buys and sells in a benchmark from the config, or a single ticker
+        #[arg(long, value_name = "NAME", conflicts_with = "all")]
+        benchmark: Option<String>,
+        /// Also split the PnL into asset-price and FX effects, for non-USD bases holding USD-priced assets
+        #[arg(long, conflicts_with = "all")]
+        fx: bool,
+        /// Value the portfolio at the end of this day (YYYY-MM-DD), at that day's prices
+        #[arg(long, value_name = "DATE", value_parser = parse_date,
+            conflicts_with_all = ["all", "diff", "save", "supply", "benchmark", "fx"])]
+        as_of: Option<Date>,
+        /// Also copy the output to the system clipboard
+        #[arg(long)]
+        copy: bool,
+        /// Exit with an error when warnings were printed, e.g. a quote provider failed
+        #[arg(long)]
+        fail_on_warning: bool,
+        /// Print only the value and daily change as a status bar line instead,
+        /// of the portfolio or with --all the total
+        #[arg(long, value_enum,
+            conflicts_with_all = ["diff", "save", "supply", "benchmark", "fx", "as_of", "copy", "fail_on_warning"])]
+        widget: Option<Widget>,
+        #[command(flatten)]
+        quotes: QuoteOverrides,
+    },
+    /// Realized gains of sold crypto, converted at the FX rates of the trade dates
+    Tax {
+        #[arg(short, long)]
+        name: String,
+        /// Only disposals of the (fiscal) year starting in this year
+        #[arg(long)]
+        year: Option<i32>,
+        /// Currency of the report, e.g. EUR, defaults to the portfolio base currency
+        #[arg(long)]
+        currency: Option<String>,
+    },
+    /// Monthly statement: values at both ends, deposits, realized gains,
+    /// income and fees, e.g. from cron on the 1st
+    Statement {
         #[arg(short, long)]
         name: String,
+        /// Month of the statement, YYYY-MM, defaults to the month before today
+        #[arg(long, value_parser = parse_month)]
+        month: Option<Date>,
+        #[arg(long, value_enum, default_value_t)]
+        format: StatementFormat,
+    },
+    /// Recompute portfolio value and PnL under hypothetical price moves
+    Scenario {
+        #[arg(short, long)]
+        name: String,
+        /// Run a scenario saved with `scenarios save` instead
+        #[arg(long, value_name = "SCENARIO")]
+        saved: Option<String>,
+        /// Price shocks per ticker, e.g. --btc -30% --eth -40%
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            required_unless_present = "saved",
+            conflicts_with = "saved"
+        )]
+        shocks: Vec<String>,
+    },
+    /// Manage named scenarios saved in the config file
+    Scenarios {
+        #[command(subcommand)]
+        cmd: ScenarioCmd,
+    },
+    /// Scheduled dollar-cost averaging buys of the plans in the config file
+    Dca {
+        #[command(subcommand)]
+        cmd: DcaCmd,
+    },
+    /// Show a position of a portfolio, computed from its trades only, or
+    /// manage its stop-loss and take-profit levels
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Position {
+        #[arg(short, long, required = true)]
+        name: Option<String>,
+        /// Pair, e.g. BTC/USD, or a ticker paired with the portfolio base currency
+        #[arg(required = true)]
+        pair: Option<String>,
+        /// Show the average entry price after each trade, as a table and chart
+        #[arg(long)]
+        history: bool,
+        /// Show buys per quarter with their quantity and average cost
+        #[arg(long)]
+        entries: bool,
+        #[command(subcommand)]
+        cmd: Option<PositionCmd>,
     },
     /// Add transaction to portfolio
     AddTx {
+        #[command(flatten)]
+        tx: TxArgs,
+        /// Take the options not given here from this entry of `templates`
+        /// in the config
+        #[arg(long)]
+        template: Option<String>,
+        /// Allow a buy or sell quoted in a fiat currency other than the
+        /// portfolio base currency, e.g. BTC/USD in a EUR portfolio
+        #[arg(long)]
+        mixed: bool,
+    },
+    /// Set a holding of a holdings-only portfolio, quantity 0 removes it
+    Hold {
         #[arg(short, long)]
         name: String,
         #[arg(short, long)]
         ticker: String,
-        #[arg(long)]
-        side: String, // BUY or SELL
-        #[arg(short, long, value_parser = ValueParser::new(Decimal::from_str_exact))]
+        #[arg(short, long, value_parser = parse_amount)]
         qty: Decimal,
-        #[arg(short, long, value_parser = ValueParser::new(Decimal::from_str_exact))]
+        /// Total cost in the base currency, leave out when unknown
+        #[arg(long, value_parser = parse_amount)]
+        cost: Option<Decimal>,
+    },
+    /// List positions worth less than a threshold and suggest consolidating them
+    Dust {
+        #[arg(short, long)]
+        name: String,
+        /// Value in the base currency, defaults to `dust_threshold` from the config
+        #[arg(long, value_parser = ValueParser::new(Decimal::from_str_exact))]
+        threshold: Option<Decimal>,
+        /// Asset to consolidate into, defaults to the portfolio base currency
+        #[arg(long)]
+        into: Option<String>,
+        /// Write the planned orders as portfolio rows to this CSV file
+        #[arg(long)]
+        emit: Option<PathBuf>,
+    },
+    /// Export trades and computed positions for analytics tools, e.g. DuckDB or Polars
+    Export {
+        /// Only this portfolio, all portfolios of the portfolio dir by default;
+        /// for ics only the DCA plans of this portfolio
+        #[arg(short, long, required_if_eq("anonymized", "true"))]
+        name: Option<String>,
+        #[arg(long, value_enum, required_unless_present = "anonymized")]
+        format: Option<ExportFormat>,
+        /// Write the portfolio as CSV with amounts scaled by a random factor
+        /// and without sources, to share in bug reports
+        #[arg(long, conflicts_with = "format")]
+        anonymized: bool,
+        /// Database file for sqlite, directory for parquet, file for the text formats
+        out: PathBuf,
+    },
+    /// Export all portfolios, state and config into a single archive
+    ExportWorkspace {
+        /// Archive file to create, e.g. workspace.cpt
+        out: PathBuf,
+    },
+    /// Import every CSV or XLSX export of a directory, or rows from the clipboard, into a portfolio
+    Import {
+        #[arg(short, long)]
+        name: String,
+        /// Directory of CSV or XLSX exports in the portfolio format, or Koinly's and CoinTracking's universal formats
+        #[arg(long, required_unless_present_any = ["clipboard", "wizard"])]
+        dir: Option<PathBuf>,
+        /// Import CSV or tab separated rows copied to the clipboard, e.g. from an
+        /// exchange's web table, after a preview
+        #[arg(long, conflicts_with = "dir")]
+        clipboard: bool,
+        /// Learn the columns of an export in another format from FILE, asking
+        /// to confirm them, save them as an import profile and import FILE
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["dir", "clipboard"])]
+        wizard: Option<PathBuf>,
+        /// Create the portfolio when it doesn't exist, suggesting the currency
+        /// most trades are quoted in as its base
+        #[arg(long)]
+        create: bool,
+        /// Don't ask when importing more than confirm_threshold rows
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Restore a workspace archive created by export-workspace
+    ImportWorkspace {
+        archive: PathBuf,
+        /// Overwrite existing files
+        #[arg(long)]
+        force: bool,
+        /// Don't ask when overwriting more than confirm_threshold files
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Check the workspace for problems (e.g. sync conflict copies)
+    Doctor,
+    /// Rewrite trades portfolios in canonical form, keeping row order and metadata
+    Fmt {
+        /// Only this portfolio, all trades portfolios by default
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Fail when a portfolio isn't canonical instead of rewriting it
+        #[arg(long)]
+        check: bool,
+    },
+    /// Check the config file
+    Config {
+        #[command(subcommand)]
+        cmd: ConfigCmd,
+    },
+    /// Print version and environment details for bug reports
+    Info {
+        /// Skip the quote provider connectivity check
+        #[arg(long)]
+        offline: bool,
+        /// Print the local usage stats of each command instead, see `usage_stats`
+        #[arg(long)]
+        stats: bool,
+        /// Count portfolios and trades and show the disk usage of the
+        /// workspace instead, e.g. before a backup
+        #[arg(long, conflicts_with_all = ["offline", "stats"])]
+        workspace: bool,
+    },
+    /// Search the CoinGecko registry by symbol, name or id, e.g. for the
+    /// ticker `add-tx` expects
+    Tickers {
+        query: String,
+        /// Most matches to list
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Show ticker type, supply and market cap
+    TickerInfo {
+        #[arg(required = true)]
+        tickers: Vec<String>,
+        /// Skip fetching supply data
+        #[arg(long)]
+        offline: bool,
+        #[command(flatten)]
+        quotes: QuoteOverrides,
+    },
+    /// Manual prices for assets the quote providers don't quote
+    Price {
+        #[command(subcommand)]
+        cmd: PriceCmd,
+    },
+    /// Manage price and portfolio alerts, evaluated by `watch`
+    Alert {
+        #[command(subcommand)]
+        cmd: AlertCmd,
+    },
+    /// Tag a trade, e.g. with its strategy, for `stats --by-tag`
+    Tag {
+        #[arg(short, long)]
+        name: String,
+        /// Trade id, or its start, as shown by `show --with-journal`
+        #[arg(long)]
+        tx: String,
+        #[arg(required = true)]
+        tags: Vec<String>,
+        /// Remove the tags instead
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Replace a trade with smaller ones of the same price, sharing its fee
+    /// in proportion, e.g. when it stands for several tax lots
+    SplitTx {
+        #[arg(short, long)]
+        name: String,
+        /// Trade id, or its start, as shown by `show --with-journal`
+        #[arg(long, alias = "tx")]
+        id: String,
+        /// Amounts of the parts, e.g. 0.3,0.2; the rest of the amount is one more part
+        #[arg(long, required = true, value_delimiter = ',', value_parser = parse_amount)]
+        into: Vec<Decimal>,
+    },
+    /// Notes on trades, e.g. why they were made
+    Journal {
+        #[command(subcommand)]
+        cmd: JournalCmd,
+    },
+    /// Write an immutable, checksummed statement of a portfolio at the end of a day
+    Freeze {
+        #[arg(short, long)]
+        name: String,
+        /// Day of the statement, YYYY-MM-DD
+        #[arg(long, value_parser = parse_date)]
+        date: Date,
+    },
+    /// Mark a finished portfolio as closed: it's still reported, but no
+    /// longer written to
+    Close {
+        #[arg(short, long)]
+        name: String,
+        /// Open a closed portfolio again
+        #[arg(long)]
+        reopen: bool,
+    },
+    /// Chart the daily values of a portfolio from its snapshots
+    History {
+        #[arg(short, long, required_unless_present = "all")]
+        name: Option<String>,
+        /// The total of all portfolios, in the base currency of the config
+        #[arg(long, conflicts_with_all = ["name", "reconstruct"])]
+        all: bool,
+        /// First rebuild the values before the first snapshot from the trades
+        /// and historical prices, back to DAYS days ago
+        #[arg(long, value_name = "DAYS", num_args = 0..=1, default_missing_value = "365")]
+        reconstruct: Option<u32>,
+    },
+    /// Record the current value of portfolios, used by alerts and history,
+    /// or downsample old snapshots
+    #[command(args_conflicts_with_subcommands = true)]
+    Snapshot {
+        /// Only this portfolio, all portfolios when omitted
+        #[arg(short, long)]
+        name: Option<String>,
+        #[command(subcommand)]
+        cmd: Option<SnapshotCmd>,
+    },
+    /// Periodically value portfolios, take snapshots and fire alerts
+    Watch {
+        /// Seconds between checks
+        #[arg(long, default_value_t = 300)]
+        interval: u64,
+        /// Check once and exit, e.g. when run from cron
+        #[arg(long)]
+        once: bool,
+        /// Also import CSV exports dropped into the configured inbox_dir
+        #[arg(long)]
+        inbox: bool,
+        #[command(flatten)]
+        quotes: QuoteOverrides,
+    },
+    /// Serve current quotes to other local tools at
+    /// http://127.0.0.1:PORT/prices?tickers=BTC,ETH
+    Serve {
+        #[arg(long, default_value_t = 8787)]
+        port: u16,
+        /// Seconds a fetched quote is served before it's fetched again
+        #[arg(long, default_value_t = 60)]
+        cache_secs: u64,
+    },
+    /// Merge sync conflict copies (Syncthing, Dropbox) back into the portfolio
+    MergeConflict {
+        #[arg(short, long)]
+        name: String,
+        /// Merge this file instead of the sync conflict copies, e.g. a copy
+        /// of the portfolio in another data dir
+        #[arg(long, value_name = "PATH")]
+        from: Option<PathBuf>,
+        /// Keep the conflict copies after merging
+        #[arg(long)]
+        keep: bool,
+        /// Don't ask when merging more than confirm_threshold rows and files
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum PositionCmd {
+    /// Set the stop-loss level of a position, a price in the portfolio base currency
+    SetStop {
+        #[arg(short, long)]
+        name: String,
+        ticker: String,
+        #[arg(value_parser = ValueParser::new(Decimal::from_str_exact))]
+        price: Decimal,
+    },
+    /// Set the take-profit level of a position, a price in the portfolio base currency
+    SetTarget {
+        #[arg(short, long)]
+        name: String,
+        ticker: String,
+        #[arg(value_parser = ValueParser::new(Decimal::from_str_exact))]
         price: Decimal,
-        #[arg(short, long, value_parser = ValueParser::new(Decimal::from_str_exact))]
-        fee: Decimal,
     },
+    /// Remove the stop-loss and take-profit levels of a position
+    ClearLevels {
+        #[arg(short, long)]
+        name: String,
+        ticker: String,
+    },
+    /// List the stop-loss and take-profit levels
+    Levels {
+        #[arg(short, long)]
+        name: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum AlertCmd {
+    /// Alert when a ticker price crosses a level
+    #[command(group(ArgGroup::new("level").required(true)))]
+    Price {
+        ticker: String,
+        #[arg(long, group = "level", value_parser = ValueParser::new(Decimal::from_str_exact))]
+        above: Option<Decimal>,
+        #[arg(long, group = "level", value_parser = ValueParser::new(Decimal::from_str_exact))]
+        below: Option<Decimal>,
+        /// Notify again at most once per DURATION while it fires, like 6h,
+        /// instead of `alert_cooldown_mins` from the config
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        cooldown: Option<Duration>,
+    },
+    /// Alert when a portfolio value moves more than PCT percent since yesterday's snapshot
+    Move {
+        #[arg(short, long)]
+        name: String,
+        #[arg(long, default_value = "5", value_parser = ValueParser::new(Decimal::from_str_exact))]
+        pct: Decimal,
+        /// Notify again at most once per DURATION while it fires, like 6h,
+        /// instead of `alert_cooldown_mins` from the config
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        cooldown: Option<Duration>,
+    },
+    /// Alert when a position crosses a value or a weight in its portfolio,
+    /// e.g. `ETH --above-pct 40` or `stables --below 5000`
+    #[command(group(ArgGroup::new("level").required(true)))]
+    Position {
+        #[arg(short, long)]
+        name: String,
+        /// Ticker, or `stables` for all stable coins together
+        asset: String,
+        /// Value in the base currency
+        #[arg(long, group = "level", value_parser = ValueParser::new(Decimal::from_str_exact))]
+        above: Option<Decimal>,
+        #[arg(long, group = "level", value_parser = ValueParser::new(Decimal::from_str_exact))]
+        below: Option<Decimal>,
+        /// Percent of the portfolio value
+        #[arg(long, group = "level", value_parser = ValueParser::new(Decimal::from_str_exact))]
+        above_pct: Option<Decimal>,
+        #[arg(long, group = "level", value_parser = ValueParser::new(Decimal::from_str_exact))]
+        below_pct: Option<Decimal>,
+        /// Notify again at most once per DURATION while it fires, like 6h,
+        /// instead of `alert_cooldown_mins` from the config
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        cooldown: Option<Duration>,
+    },
+    /// List all alerts
+    List,
+    /// Remove an alert by id
+    Remove { id: u32 },
+    /// Don't notify of an alert for DURATION, like 24h; `0h` ends a snooze
+    Snooze {
+        id: u32,
+        #[arg(value_parser = parse_duration)]
+        duration: Duration,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum PriceCmd {
+    /// Value TICKER at PRICE whenever no quote provider quotes it
+    Set {
+        ticker: String,
+        #[arg(value_parser = ValueParser::new(Decimal::from_str_exact))]
+        price: Decimal,
+        /// Currency of the price
+        #[arg(long, default_value = "USD")]
+        currency: String,
+        /// Last day (YYYY-MM-DD) the price is used on, reports warn the week before
+        #[arg(long, value_name = "DATE", value_parser = parse_date)]
+        until: Option<Date>,
+    },
+    /// List manual prices and when they expire
+    List,
+    /// Remove the manual price of a ticker
+    Remove { ticker: String },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ScenarioCmd {
+    /// List saved scenarios
+    List,
+    /// Save a scenario, replacing one of the same name
+    Save {
+        /// Scenario name, e.g. bear2026
+        scenario: String,
+        /// Price shocks per ticker, e.g. --btc -60% --eth -75%
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+        shocks: Vec<String>,
+    },
+    /// Remove a saved scenario
+    Remove { scenario: String },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum DcaCmd {
+    /// List the buys due in the next days
+    Due {
+        /// Only plans of this portfolio
+        #[arg(short, long)]
+        name: Option<String>,
+        #[arg(long, default_value = "30")]
+        days: u32,
+    },
+    /// Check the buys scheduled in the past days against the recorded ones
+    Status {
+        /// Only plans of this portfolio
+        #[arg(short, long)]
+        name: Option<String>,
+        #[arg(long, default_value = "90")]
+        days: u32,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum JournalCmd {
+    /// Add an entry to a trade
+    Add {
+        #[arg(short, long)]
+        name: String,
+        /// Trade id, or its start, as shown by `show --with-journal`
+        #[arg(long)]
+        tx: String,
+        text: String,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum SnapshotCmd {
+    /// Keep old snapshots at a lower resolution, by the snapshot_retention config
+    Compact {
+        /// Only this portfolio, all portfolios when omitted
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Don't ask when removing more than confirm_threshold snapshots
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ConfigCmd {
+    /// Report unknown keys, values of the wrong type and invalid values,
+    /// with their line
+    Validate {
+        /// Check this file instead of the config dotfile
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+    },
+    /// Print the effective settings, as TOML
+    Show {
+        /// Note after each setting where its value came from: default,
+        /// dotfile, env var or CLI flag
+        #[arg(long)]
+        origins: bool,
+    },
+}
+
+impl Cmd {
+    /// Whether the command only prints, so its output can go through the pager
+    pub fn pages(&self) -> bool {
+        match self {
+            Cmd::List { .. }
+            | Cmd::Show { .. }
+            | Cmd::Stats { .. }
+            | Cmd::Fees { .. }
+            | Cmd::Income { .. }
+            | Cmd::Report { .. }
+            | Cmd::Tax { .. }
+            | Cmd::Statement { .. }
+            | Cmd::Scenario { .. }
+            | Cmd::History {
+                reconstruct: None, ..
+            }
+            | Cmd::Doctor
+            | Cmd::Config {
+                cmd: ConfigCmd::Show { .. },
+            }
+            | Cmd::Info { .. }
+            | Cmd::Tickers { .. }
+            | Cmd::TickerInfo { .. } => true,
+            Cmd::Alert { cmd } => matches!(cmd, AlertCmd::List),
+            Cmd::Price { cmd } => matches!(cmd, PriceCmd::List),
+            Cmd::Position { cmd, .. } => cmd
+                .as_ref()
+                .is_none_or(|cmd| matches!(cmd, PositionCmd::Levels { .. })),
+            Cmd::Scenarios { cmd } => matches!(cmd, ScenarioCmd::List),
+            Cmd::Dca { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether `--output json` and `--output csv` work: the command prints
+    /// rows, or only says what it did; lines, charts and documents have no
+    /// machine format
+    pub fn machine_output(&self) -> bool {
+        match self {
+            Cmd::Stats { behavior, .. } => !behavior,
+            Cmd::Report { widget, .. } => widget.is_none(),
+            Cmd::Info {
+                stats, workspace, ..
+            } => *stats || *workspace,
+            Cmd::TickerInfo { offline, .. } => !offline,
+            Cmd::Position {
+                history,
+                entries,
+                cmd: None,
+                ..
+            } => *history || *entries,
+            Cmd::Export { out, .. } => out.as_path() != Path::new("-"),
+            Cmd::Statement { .. }
+            | Cmd::Config { .. }
+            | Cmd::Doctor
+            | Cmd::Serve { .. }
+            | Cmd::Watch { .. } => false,
+            _ => true,
+        }
+    }
+
+    /// Whether a first run of the command offers the setup wizard; not for
+    /// `config`, which inspects the config itself, nor `import-workspace`,
+    /// which brings its own and refuses to overwrite one
+    pub fn offers_wizard(&self) -> bool {
+        !matches!(self, Cmd::Config { .. } | Cmd::ImportWorkspace { .. })
+    }
+
+    /// Quote provider and cache overrides of the commands fetching quotes
+    pub fn quote_overrides(&self) -> Option<&QuoteOverrides> {
+        match self {
+            Cmd::Report { quotes, .. }
+            | Cmd::TickerInfo { quotes, .. }
+            | Cmd::Watch { quotes, .. } => Some(quotes),
+            _ => None,
+        }
+    }
+
+    /// Whether the output should also be copied to the clipboard
+    pub fn copies(&self) -> bool {
+        match self {
+            Cmd::Show { copy, .. } | Cmd::Report { copy, .. } => *copy,
+            _ => false,
+        }
+    }
+}
+
+/// Options of `add-tx`, all required unless a template gives them
+#[derive(Debug, Clone, Default, Args)]
+pub struct TxArgs {
+    #[arg(short, long)]
+    pub name: Option<String>,
+    #[arg(short, long)]
+    pub ticker: Option<String>,
+    #[arg(long)]
+    pub side: Option<String>, // BUY, SELL, DEPOSIT, WITHDRAW, FEE or INCOME
+    #[arg(short, long, value_parser = parse_amount)]
+    pub qty: Option<Decimal>,
+    /// Unit price, or `market` for the current quote
+    #[arg(short, long, value_parser = parse_price)]
+    pub price: Option<TxPrice>,
+    #[arg(short, long, value_parser = parse_amount)]
+    pub fee: Option<Decimal>,
+    /// How the row entered the file, recorded in the `source` column
+    /// [default: manual]
+    #[arg(long)]
+    pub source: Option<String>,
+}
+
+/// One-off overrides of the `quote_provider` setting and of cached quotes
+#[derive(Debug, Clone, Default, Args)]
+pub struct QuoteOverrides {
+    /// Fetch quotes from these providers instead of the configured ones,
+    /// e.g. binance or file:prices.csv
+    #[arg(long, value_name = "SPEC", value_parser = parse_provider)]
+    pub provider: Option<String>,
+    /// Fetch quotes and supply data fresh instead of using cached ones
+    #[arg(long)]
+    pub no_cache: bool,
+}
+
+fn parse_provider(s: &str) -> Result<String, String> {
+    quote::parse_providers(s).map_err(|e| e.to_string())?;
+    Ok(s.to_string())
+}
+
+/// An exact decimal within the limits of trade amounts, see `trade::check_precision`
+fn parse_amount(s: &str) -> Result<Decimal, String> {
+    let d =
+        Decimal::from_str_exact(s.trim()).map_err(|e| format!("invalid number '{}': {}", s, e))?;
+    check_precision(d)
+}
+
+/// `market` or an amount, see `parse_amount`
+fn parse_price(s: &str) -> Result<TxPrice, String> {
+    if s.trim().eq_ignore_ascii_case("market") {
+        return Ok(TxPrice::Market);
+    }
+    parse_amount(s).map(TxPrice::Fixed)
+}
+
+fn parse_date(s: &str) -> Result<Date, String> {
+    Date::parse(s, format_description!("[year]-[month]-[day]"))
+        .map_err(|_| format!("invalid date '{}', expected YYYY-MM-DD", s))
+}
+
+fn parse_now(s: &str) -> Result<OffsetDateTime, String> {
+    clock::parse(s).map_err(|_| format!("invalid time '{}', expected RFC 3339 or unix seconds", s))
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    clock::parse_duration(s).map_err(|_| {
+        format!(
+            "invalid duration '{}', expected a number with m, h, d or w, like 24h",
+            s
+        )
+    })
+}
+
+/// First day of the month `s`, YYYY-MM
+fn parse_month(s: &str) -> Result<Date, String> {
+    Date::parse(
+        &format!("{}-01", s),
+        format_description!("[year]-[month]-[day]"),
+    )
+    .map_err(|_| format!("invalid month '{}', expected YYYY-MM", s))
 }
@@ -0,0 +1,115 @@
+use anyhow::{Context, Result, bail};
+use std::sync::{LazyLock, OnceLock};
+use time::format_description::well_known::Rfc3339;
+use time::{Date, Duration, OffsetDateTime};
+
+/// Freezes the clock of every command, for reproducible integration tests
+pub const FAKE_NOW_VAR: &str = "CSVPT_FAKE_NOW";
+
+/// Where commands get the current time from, see `Settings::clock`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Clock {
+    #[default]
+    System,
+    /// Always the same instant
+    Frozen(OffsetDateTime),
+}
+
+impl Clock {
+    pub fn now(self) -> OffsetDateTime {
+        match self {
+            Clock::System => OffsetDateTime::now_utc(),
+            Clock::Frozen(at) => at,
+        }
+    }
+
+    /// Today in UTC
+    pub fn today(self) -> Date {
+        self.now().date()
+    }
+
+    /// Frozen at `CSVPT_FAKE_NOW`, RFC 3339 or unix seconds, when set
+    pub fn from_env() -> Result<Clock> {
+        match std::env::var(FAKE_NOW_VAR) {
+            Ok(value) => parse(&value)
+                .map(Clock::Frozen)
+                .with_context(|| format!("invalid {}: '{}'", FAKE_NOW_VAR, value)),
+            Err(_) => Ok(Clock::System),
+        }
+    }
+}
+
+pub fn parse(value: &str) -> Result<OffsetDateTime> {
+    let value = value.trim();
+    Ok(match value.parse::<i64>() {
+        Ok(ts) => OffsetDateTime::from_unix_timestamp(ts)?,
+        Err(_) => OffsetDateTime::parse(value, &Rfc3339)?,
+    })
+}
+
+/// Parse a duration like `30m`, `24h`, `2d` or `1w`
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let Some(unit) = value.chars().last() else {
+        bail!("empty duration");
+    };
+    let count: i64 = value[..value.len() - unit.len_utf8()].parse()?;
+    if count < 0 {
+        bail!("negative duration");
+    }
+    Ok(match unit {
+        'm' => Duration::minutes(count),
+        'h' => Duration::hours(count),
+        'd' => Duration::days(count),
+        'w' => Duration::weeks(count),
+        _ => bail!("unknown unit '{}'", unit),
+    })
+}
+
+static ENV: LazyLock<Clock> = LazyLock::new(|| Clock::from_env().unwrap_or_default());
+
+/// Set by `--now`, takes precedence over `CSVPT_FAKE_NOW`
+static FROZEN: OnceLock<Clock> = OnceLock::new();
+
+/// Freeze `env()` at `at` for the rest of the run
+pub fn freeze(at: OffsetDateTime) {
+    let _ = FROZEN.set(Clock::Frozen(at));
+}
+
+/// Clock of code without settings at hand, like row validation while parsing;
+/// an invalid `CSVPT_FAKE_NOW` is reported by `Settings::load`
+pub fn env() -> Clock {
+    FROZEN.get().copied().unwrap_or(*ENV)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_parse_accepts_rfc3339_and_unix_seconds() {
+        assert_eq!(
+            parse("2024-03-15T12:00:00+01:00").unwrap(),
+            datetime!(2024-03-15 11:00 UTC)
+        );
+        assert_eq!(parse("1710460800").unwrap(), datetime!(2024-03-15 0:00 UTC));
+        assert!(parse("yesterday").is_err());
+
+        let frozen = Clock::Frozen(datetime!(2024-03-15 23:59 UTC));
+        assert_eq!(frozen.now(), frozen.now());
+        assert_eq!(frozen.today(), time::macros::date!(2024 - 03 - 15));
+    }
+
+    #[test]
+    fn test_parse_duration_takes_a_count_and_a_unit() {
+        assert_eq!(parse_duration("30m").unwrap(), Duration::minutes(30));
+        assert_eq!(parse_duration(" 24h").unwrap(), Duration::hours(24));
+        assert_eq!(parse_duration("2d").unwrap(), Duration::days(2));
+        assert_eq!(parse_duration("1w").unwrap(), Duration::days(7));
+        assert!(parse_duration("24").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("-1h").is_err());
+        assert!(parse_duration("").is_err());
+    }
+}
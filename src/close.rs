@@ -0,0 +1,86 @@
+//! Closing portfolios
+//!
+//! An account that is finished, e.g. an exchange that was left, must often
+//! stay around to be audited. `close` records `# closed_at: <time>` in the
+//! portfolio metadata: reports, exports and `list` still read it, but
+//! commands writing to it (`add-tx`, `hold`, `import`, `split-tx`,
+//! `merge-conflict`, embedded writers) refuse until `close --reopen`.
+
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::extract_csv_config;
+use crate::writer;
+use anyhow::{Context, Result, bail};
+use std::io::Write;
+
+/// `close`: mark portfolio `name` as closed, or open again with `reopen`
+pub fn close(name: &str, reopen: bool, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let path = settings.path_for(name);
+    let _lock = writer::lock(name, settings)?;
+    let input_data =
+        std::fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+    let (mut config, data) = extract_csv_config(&input_data)?;
+
+    let status = match (config.closed_at, reopen) {
+        (None, false) => {
+            let now = settings.clock.now().replace_nanosecond(0)?;
+            config.closed_at = Some(now);
+            format!("Closed {}, reopen it with `close --reopen`", name)
+        }
+        (Some(closed_at), false) => bail!("{} was already closed on {}", name, closed_at.date()),
+        (Some(_), true) => {
+            config.closed_at = None;
+            format!("Reopened {}", name)
+        }
+        (None, true) => bail!("{} isn't closed", name),
+    };
+
+    // written aside and renamed over, like every rewrite of a portfolio
+    let tmp = path.with_extension("csv.tmp");
+    {
+        let mut file = std::fs::File::create(&tmp)?;
+        config.write_to(&mut file)?;
+        file.write_all(data.as_bytes())?;
+    }
+    std::fs::rename(&tmp, &path)?;
+    reporter.status(&status);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::reporter::Silent;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use crate::trade::{ensure_portfolio_file, read_csv_config};
+    use tempfile::TempDir;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_close_marks_the_metadata_and_reopen_clears_it() {
+        let dir = TempDir::new().unwrap();
+        let settings = Settings {
+            clock: Clock::Frozen(datetime!(2025-06-30 18:00:00.5 UTC)),
+            ..(*create_test_settings(dir.path().to_path_buf())).clone()
+        };
+        let content = "# base_currency: EUR
+created_at,pair,side,amount,price,fee
+1704883200,BTC/EUR,BUY,1,40000,0
+";
+        create_test_csv(&dir, "old", content);
+        let path = settings.path_for("old");
+
+        close("old", false, &Silent, &settings).unwrap();
+        let config = read_csv_config(&path).unwrap();
+        assert_eq!(config.closed_at, Some(datetime!(2025-06-30 18:00 UTC)));
+        assert_eq!(config.base_currency, "EUR");
+        assert!(ensure_portfolio_file(&path).is_err());
+        assert!(close("old", false, &Silent, &settings).is_err());
+
+        close("old", true, &Silent, &settings).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+        assert!(ensure_portfolio_file(&path).is_ok());
+        assert!(close("old", true, &Silent, &settings).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! Schema check of the config dotfile
+//!
+//! The config crate ignores unknown keys and reports wrong types without
+//! saying where they are, so a typo like `portfolio_dri` silently falls back
+//! to the default. The file is checked on its own first: unknown top-level
+//! keys come with the closest known key, and every problem with its line.
+
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use anyhow::{Context, Result, bail};
+use std::fmt;
+use std::path::Path;
+use toml::de::DeTable;
+
+/// A problem in the config file, at a 1-based line and column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+fn problem_at(text: &str, offset: usize, message: String) -> Problem {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Problem {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+        message,
+    }
+}
+
+/// Top-level keys of the settings
+fn known_keys() -> Vec<String> {
+    match serde_json::to_value(Settings::default()) {
+        Ok(serde_json::Value::Object(fields)) => fields.keys().cloned().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Edit distance between `a` and `b`
+pub(crate) fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The known key `key` was most likely meant as, if any is close enough
+fn suggestion<'a>(key: &str, known: &'a [String]) -> Option<&'a str> {
+    let key = key.to_ascii_lowercase();
+    known
+        .iter()
+        .map(|k| (distance(&key, k), k))
+        .filter(|(d, k)| *d <= (k.len() / 3).max(2))
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, k)| k.as_str())
+}
+
+/// Problems of the config file `text`: syntax errors, unknown keys and
+/// values of the wrong type, in the order they appear
+pub fn check(text: &str) -> Vec<Problem> {
+    let table = match DeTable::parse(text) {
+        Ok(table) => table,
+        Err(e) => {
+            let offset = e.span().map_or(0, |s| s.start);
+            return vec![problem_at(text, offset, e.message().to_string())];
+        }
+    };
+    let known = known_keys();
+    let mut problems = Vec::new();
+    for key in table.get_ref().keys() {
+        if known.iter().any(|k| k == key.get_ref().as_ref()) {
+            continue;
+        }
+        let message = match suggestion(key.get_ref(), &known) {
+            Some(k) => format!("unknown key `{}`, did you mean `{}`?", key.get_ref(), k),
+            None => format!("unknown key `{}`", key.get_ref()),
+        };
+        problems.push(problem_at(text, key.span().start, message));
+    }
+    if let Err(e) = toml::from_str::<Settings>(text) {
+        let offset = e.span().map_or(0, |s| s.start);
+        problems.push(problem_at(text, offset, e.message().to_string()));
+    }
+    problems.sort_by_key(|p| (p.line, p.column));
+    problems
+}
+
+/// `check` of the file at `path`, each problem prefixed with the path
+pub fn check_file(path: &Path) -> Result<Vec<String>> {
+    let text =
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    Ok(check(&text)
+        .iter()
+        .map(|p| format!("{}:{}", path.display(), p))
+        .collect())
+}
+
+/// `config validate`: report the problems of the config file and the
+/// warnings its values would load with, failing on problems
+pub fn validate(path: &Path, reporter: &dyn Reporter) -> Result<()> {
+    if !path.is_file() {
+        bail!("no config file at {}", path.display());
+    }
+    let problems = check_file(path)?;
+    for problem in &problems {
+        reporter.print(&format!("❌ {}", problem));
+    }
+    if !problems.is_empty() {
+        bail!("{} problems in {}", problems.len(), path.display());
+    }
+    let mut settings: Settings = toml::from_str(&std::fs::read_to_string(path)?)?;
+    for warning in settings.validate() {
+        reporter.print(&format!("⚠️  {}", warning));
+    }
+    reporter.print(&format!("✅ {} is valid", path.display()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_locates_unknown_keys_and_wrong_types() {
+        let text = "base_currency = \"EUR\"
+portfolio_dri = \"~/portfolios\"
+http_timeout_secs = \"ten\"
+color = true
+
+[fee_tiers.binance]
+maker_bps = 10
+taker_bps = 10
+";
+        let problems = check(text);
+        assert_eq!(problems.len(), 3);
+        assert_eq!(
+            problems[0].to_string(),
+            "2:1: unknown key `portfolio_dri`, did you mean `portfolio_dir`?"
+        );
+        assert_eq!((problems[1].line, problems[1].column), (3, 21));
+        assert!(problems[1].message.contains("invalid type"));
+        assert_eq!(problems[2].to_string(), "4:1: unknown key `color`");
+
+        assert!(check("rounding = \"half-up\"\n").is_empty());
+        assert_eq!(check("base_currency = \n")[0].line, 1);
+    }
+}
@@ -0,0 +1,62 @@
+//! `config show`: the settings every command runs with, after all layers of
+//! `Settings::load` are merged, as TOML
+//!
+//! With `--origins` each line ends in a comment naming the layer its value
+//! came from, e.g. `quote_provider = "binance"  # env LPT_QUOTE_PROVIDER`.
+
+use crate::reporter::Reporter;
+use crate::settings::{Origin, Settings};
+use anyhow::{Result, bail};
+use std::collections::BTreeMap;
+
+/// Lines longer than this aren't padded to align the origin comments
+const ALIGN_WIDTH: usize = 60;
+
+/// One `key = value` line per top-level setting, ending in its origin when
+/// `origins` are given
+pub fn lines(
+    settings: &Settings,
+    origins: Option<&BTreeMap<String, Origin>>,
+) -> Result<Vec<String>> {
+    let serde_json::Value::Object(fields) = serde_json::to_value(settings)? else {
+        bail!("settings don't serialize to a table");
+    };
+    let settings: Vec<(String, String)> = fields
+        .into_iter()
+        .map(|(key, value)| {
+            let line = match toml::Value::try_from(&value) {
+                Ok(value) => format!("{} = {}", key, value),
+                Err(_) => format!("# {} is not set", key),
+            };
+            (key, line)
+        })
+        .collect();
+    let Some(origins) = origins else {
+        return Ok(settings.into_iter().map(|(_, line)| line).collect());
+    };
+
+    let width = settings
+        .iter()
+        .map(|(_, line)| line.chars().count())
+        .filter(|len| *len <= ALIGN_WIDTH)
+        .max()
+        .unwrap_or_default();
+    Ok(settings
+        .into_iter()
+        .map(|(key, line)| {
+            let origin = origins.get(&key).unwrap_or(&Origin::Default);
+            format!("{:width$}  # {}", line, origin)
+        })
+        .collect())
+}
+
+pub fn print(
+    reporter: &dyn Reporter,
+    settings: &Settings,
+    origins: Option<&BTreeMap<String, Origin>>,
+) -> Result<()> {
+    for line in lines(settings, origins)? {
+        reporter.print(&line);
+    }
+    Ok(())
+}
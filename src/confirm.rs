@@ -0,0 +1,124 @@
+use crate::settings::Settings;
+use anyhow::{Result, bail};
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Go ahead with a destructive batch operation touching `count` rows or files
+///
+/// Up to `confirm_threshold` it just runs. Above it, `--yes` is needed, or
+/// an answer on the terminal after seeing `summary` and its `details`.
+/// Without a terminal the operation is refused, so scripts don't hang.
+pub fn confirm(
+    summary: &str,
+    details: &[String],
+    count: usize,
+    yes: bool,
+    settings: &Settings,
+) -> Result<()> {
+    if yes || count <= settings.confirm_threshold {
+        return Ok(());
+    }
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        bail!(
+            "{}, more than confirm_threshold ({}), pass --yes to go ahead",
+            summary,
+            settings.confirm_threshold
+        );
+    }
+    ask(&mut stdin.lock(), &mut std::io::stdout(), summary, details)
+}
+
+/// Go ahead after showing `summary` and its `details`, whatever the count,
+/// for input that wasn't reviewed before like pasted rows
+pub fn confirm_preview(summary: &str, details: &[String], yes: bool) -> Result<()> {
+    if yes {
+        return Ok(());
+    }
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        bail!("{}, pass --yes to go ahead without a preview", summary);
+    }
+    ask(&mut stdin.lock(), &mut std::io::stdout(), summary, details)
+}
+
+/// Answer to a yes/no `question` defaulting to yes, or yes right away
+/// with `yes`; without a terminal only `yes` can answer it
+pub fn accept(question: &str, yes: bool) -> Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        bail!("{} Pass --yes to accept", question);
+    }
+    ask_default_yes(&mut stdin.lock(), &mut std::io::stdout(), question)
+}
+
+fn ask_default_yes<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+) -> Result<bool> {
+    write!(output, "{} [Y/n] ", question)?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(!matches!(answer.trim().to_lowercase().as_str(), "n" | "no"))
+}
+
+fn ask<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    summary: &str,
+    details: &[String],
+) -> Result<()> {
+    writeln!(output, "{}:", summary)?;
+    for line in details {
+        writeln!(output, "  {}", line)?;
+    }
+    write!(output, "Continue? [y/N] ")?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => bail!("cancelled, nothing was changed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::create_test_settings;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_confirm_asks_only_above_threshold() {
+        let dir = TempDir::new().unwrap();
+        let settings = Settings {
+            confirm_threshold: 10,
+            ..(*create_test_settings(dir.path().to_path_buf())).clone()
+        };
+        assert!(confirm("Rewrite 10 rows", &[], 10, false, &settings).is_ok());
+        assert!(confirm("Rewrite 11 rows", &[], 11, true, &settings).is_ok());
+
+        let details = vec!["+11 rows from a.csv".to_string()];
+        let mut output = Vec::new();
+        ask(
+            &mut Cursor::new("y\n"),
+            &mut output,
+            "Rewrite 11 rows",
+            &details,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Rewrite 11 rows:\n  +11 rows from a.csv\nContinue? [y/N] "
+        );
+        assert!(ask(&mut Cursor::new("\n"), &mut Vec::new(), "Rewrite", &details).is_err());
+
+        assert!(ask_default_yes(&mut Cursor::new("\n"), &mut Vec::new(), "Use EUR?").unwrap());
+        assert!(!ask_default_yes(&mut Cursor::new("n\n"), &mut Vec::new(), "Use EUR?").unwrap());
+    }
+}
@@ -0,0 +1,563 @@
+use crate::confirm::confirm;
+use crate::index;
+use crate::portfolio::{PortfolioEntry, list_all_entries};
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{
+    Trade, parse_csv_file, parse_csv_records, parse_csv_text_records, sort_records_chronologically,
+    write_csv_records,
+};
+use anyhow::{Result, bail};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// A copy of a portfolio file created by a file sync tool when
+/// the same file was modified on two machines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// Name of the portfolio the conflict copy belongs to
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Returns the original portfolio name if the file name looks like a sync conflict copy
+///
+/// Recognized patterns:
+/// - Syncthing: `foo.sync-conflict-20240101-120000-ABCDEFG.csv`
+/// - Dropbox: `foo (conflicted copy 2024-01-01).csv`, `foo (Ann's conflicted copy 2024-01-01).csv`
+pub fn conflict_origin(file_name: &str) -> Option<String> {
+    let stem = file_name.strip_suffix(".csv")?;
+
+    if let Some((name, _)) = stem.split_once(".sync-conflict-") {
+        return (!name.is_empty()).then(|| name.to_string());
+    }
+
+    if stem.ends_with(')') && stem.contains("conflicted copy") {
+        let (name, _) = stem.rsplit_once(" (")?;
+        return (!name.is_empty()).then(|| name.to_string());
+    }
+
+    None
+}
+
+/// Find all sync conflict copies in the portfolio dir, sorted by path
+pub fn find_conflicts(settings: &Settings) -> Result<Vec<Conflict>> {
+    let mut conflicts = Vec::new();
+    for entry in settings.portfolio_dir.read_dir()? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if let Some(name) = conflict_origin(&file_name) {
+            conflicts.push(Conflict { name, path });
+        }
+    }
+    conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(conflicts)
+}
+
+/// Print a one line hint for every conflict copy found
+pub fn print_conflict_warnings(conflicts: &[Conflict], reporter: &dyn Reporter) {
+    for c in conflicts {
+        reporter.print(&format!(
+            "⚠️  sync conflict copy of '{}': {} (run `merge-conflict --name {}`)",
+            c.name,
+            c.path.display(),
+            c.name
+        ));
+    }
+}
+
+/// Portfolios of different data dirs that look like copies of each other
+#[derive(Debug, Clone, PartialEq)]
+pub enum Duplicate {
+    /// Portfolios of the same name, in the order of `data_dirs`
+    Name { name: String, paths: Vec<PathBuf> },
+    /// Portfolios of different names sharing trades, by trade id
+    Trades { paths: [PathBuf; 2], shared: usize },
+}
+
+/// Find portfolios duplicated across the data dirs, e.g. a synced copy of a
+/// portfolio also kept in another dir
+pub fn find_duplicates(settings: &Settings) -> Result<Vec<Duplicate>> {
+    if settings.data_dirs().len() < 2 {
+        return Ok(Vec::new());
+    }
+    let entries = list_all_entries(settings)?;
+    let mut duplicates: Vec<Duplicate> = entries
+        .chunk_by(|a, b| a.name == b.name)
+        .filter(|same| same.len() > 1)
+        .map(|same| Duplicate::Name {
+            name: same[0].name.clone(),
+            paths: same.iter().map(|e| e.path.clone()).collect(),
+        })
+        .collect();
+
+    // holdings-only and unreadable files have no trade ids to compare
+    let ids: Vec<(&PortfolioEntry, HashSet<String>)> = entries
+        .iter()
+        .filter_map(|e| {
+            let (_, trades) = parse_csv_file(&e.path).ok()?;
+            Some((e, trades.iter().map(Trade::id).collect()))
+        })
+        .collect();
+    for (i, (a, a_ids)) in ids.iter().enumerate() {
+        for (b, b_ids) in &ids[i + 1..] {
+            if a.name == b.name || a.path.parent() == b.path.parent() {
+                continue;
+            }
+            let shared = a_ids.intersection(b_ids).count();
+            if shared > 0 {
+                duplicates.push(Duplicate::Trades {
+                    paths: [a.path.clone(), b.path.clone()],
+                    shared,
+                });
+            }
+        }
+    }
+    Ok(duplicates)
+}
+
+/// Print a one line hint for every duplicate found, offering `merge-conflict
+/// --from` when one of the files is in the portfolio dir
+pub fn print_duplicate_warnings(
+    duplicates: &[Duplicate],
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) {
+    let own = |path: &Path| path.parent() == Some(settings.portfolio_dir.as_path());
+    let name = |path: &Path| {
+        path.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    };
+    let merge_hint = |into: &Path, from: &Path| {
+        format!(
+            "rename one, or run `merge-conflict --name {} --from {}`",
+            name(into),
+            from.display()
+        )
+    };
+    for d in duplicates {
+        match d {
+            Duplicate::Name { name, paths } => {
+                let listed: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                let hint = match paths.as_slice() {
+                    [into, from, ..] if own(into) => merge_hint(into, from),
+                    _ => "rename one".to_string(),
+                };
+                reporter.print(&format!(
+                    "⚠️  portfolio '{}' exists in several data dirs: {} ({})",
+                    name,
+                    listed.join(", "),
+                    hint
+                ));
+            }
+            Duplicate::Trades { paths, shared } => {
+                let [a, b] = paths;
+                let hint = if own(a) {
+                    merge_hint(a, b)
+                } else if own(b) {
+                    merge_hint(b, a)
+                } else {
+                    "rename one if they are the same portfolio".to_string()
+                };
+                reporter.print(&format!(
+                    "⚠️  {} and {} share {} trades ({})",
+                    a.display(),
+                    b.display(),
+                    shared,
+                    hint
+                ));
+            }
+        }
+    }
+}
+
+/// Merge all conflict copies of a portfolio back into the portfolio file
+///
+/// A three-way merge against the common ancestor: the version of the
+/// portfolio committed in git, when the portfolio dir is a git work tree.
+/// Rows added in a copy are added, rows of the ancestor deleted in a copy are
+/// deleted, and an edit (a row replaced by a different one) is taken from
+/// whichever side made it. When both sides edited the same trade (same
+/// timestamp and pair), both versions are kept and reported for review.
+///
+/// Sync tools don't keep the ancestor, so without git the merge falls back
+/// to the union of the rows: a row deleted on one machine can't be told
+/// apart from a row added on the other one, and is kept.
+/// Duplicates (same timestamp and values) are dropped. The result is ordered
+/// by timestamp, keeping file order for equal timestamps.
+///
+/// With `from`, that file is merged instead of the conflict copies, e.g. a
+/// copy of the portfolio in another data dir.
+pub fn merge(
+    name: &str,
+    from: Option<&Path>,
+    keep: bool,
+    yes: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let path = settings.path_for(name);
+    let conflicts: Vec<Conflict> = match from {
+        Some(from) => {
+            if !from.is_file() {
+                bail!("{} doesn't exist", from.display());
+            }
+            if from.canonicalize()? == path.canonicalize()? {
+                bail!("{} is the portfolio itself", from.display());
+            }
+            vec![Conflict {
+                name: name.to_string(),
+                path: from.to_path_buf(),
+            }]
+        }
+        None => find_conflicts(settings)?
+            .into_iter()
+            .filter(|c| c.name == name)
+            .collect(),
+    };
+
+    if conflicts.is_empty() {
+        reporter.print(&format!("No sync conflicts found for portfolio '{}'", name));
+        return Ok(());
+    }
+
+    let (config, mut records) = parse_csv_records(&path)?;
+    config.ensure_open()?;
+    let ancestor = git_ancestor(&path);
+    if ancestor.is_none() {
+        reporter.print(&format!(
+            "No committed version of {} in git to merge against: keeping the rows of all copies, \
+             rows deleted in one copy are kept",
+            path.display()
+        ));
+    }
+    let ancestor: HashSet<Vec<String>> = ancestor.iter().flatten().map(record_key).collect();
+    let mut merged = Vec::new();
+    let mut both_edited = Vec::new();
+
+    for conflict in &conflicts {
+        let (other_config, other_records) = parse_csv_records(&conflict.path)?;
+        if other_config.base_currency != config.base_currency {
+            bail!(
+                "base currency differs: {} is {}, {} is {}",
+                path.display(),
+                config.base_currency,
+                conflict.path.display(),
+                other_config.base_currency
+            );
+        }
+
+        let outcome = merge_records(&ancestor, &mut records, other_records);
+        both_edited.extend(outcome.both_edited);
+        merged.push((outcome.added, outcome.deleted));
+    }
+
+    let mut details: Vec<String> = conflicts
+        .iter()
+        .zip(&merged)
+        .map(|(c, (added, deleted))| {
+            format!("+{} -{} rows from {}", added, deleted, c.path.display())
+        })
+        .collect();
+    if !keep {
+        details.extend(
+            conflicts
+                .iter()
+                .map(|c| format!("delete {}", c.path.display())),
+        );
+    }
+    let added: usize = merged.iter().map(|(added, _)| added).sum();
+    let deleted: usize = merged.iter().map(|(_, deleted)| deleted).sum();
+    confirm(
+        &format!(
+            "Merging adds {} and deletes {} rows of {} and rewrites it",
+            added,
+            deleted,
+            path.display()
+        ),
+        &details,
+        added + deleted + if keep { 0 } else { conflicts.len() },
+        yes,
+        settings,
+    )?;
+    for (conflict, (added, deleted)) in conflicts.iter().zip(&merged) {
+        reporter.status(&format!(
+            "Merged {} new and {} deleted trades from {}",
+            added,
+            deleted,
+            conflict.path.display()
+        ));
+    }
+    for (created_at, pair) in &both_edited {
+        reporter.print(&format!(
+            "⚠️  both copies changed the {} trade at {}, kept both versions: check them",
+            pair, created_at
+        ));
+    }
+
+    sort_records_chronologically(&mut records);
+    write_csv_records(&path, &config, &records)?;
+    index::record_write(name, settings);
+
+    if !keep {
+        for conflict in &conflicts {
+            std::fs::remove_file(&conflict.path)?;
+        }
+    }
+
+    let merged_files = match from {
+        Some(from) => from.display().to_string(),
+        None => format!("{} conflict copies", conflicts.len()),
+    };
+    reporter.status(&format!(
+        "✅ Merged {} into {} ({} trades)",
+        merged_files,
+        path.display(),
+        records.len()
+    ));
+    Ok(())
+}
+
+fn record_key(record: &csv::StringRecord) -> Vec<String> {
+    record.iter().map(|f| f.trim().to_string()).collect()
+}
+
+/// Timestamp and pair, telling which rows are versions of the same trade
+fn trade_key(record: &csv::StringRecord) -> (String, String) {
+    (record[0].to_string(), record[1].to_string())
+}
+
+/// The version of the portfolio file committed in git, `None` when the file
+/// isn't in a git work tree, isn't committed or git isn't installed
+fn git_ancestor(path: &Path) -> Option<Vec<csv::StringRecord>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(path.parent()?)
+        .arg("show")
+        .arg(format!("HEAD:./{}", path.file_name()?.to_str()?))
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let (_, records) = parse_csv_text_records(&String::from_utf8(output.stdout).ok()?).ok()?;
+    Some(records)
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct MergeOutcome {
+    added: usize,
+    deleted: usize,
+    /// Trades of the ancestor both sides replaced by a different version
+    both_edited: Vec<(String, String)>,
+}
+
+/// Merge `theirs` into `ours` against the rows of their common `ancestor`
+fn merge_records(
+    ancestor: &HashSet<Vec<String>>,
+    ours: &mut Vec<csv::StringRecord>,
+    theirs: Vec<csv::StringRecord>,
+) -> MergeOutcome {
+    let our_keys: HashSet<Vec<String>> = ours.iter().map(record_key).collect();
+    let their_keys: HashSet<Vec<String>> = theirs.iter().map(record_key).collect();
+    let mut outcome = MergeOutcome::default();
+
+    // ancestor rows they deleted or edited away
+    let before = ours.len();
+    ours.retain(|r| {
+        let key = record_key(r);
+        !ancestor.contains(&key) || their_keys.contains(&key)
+    });
+    outcome.deleted = before - ours.len();
+
+    // trades of the ancestor we changed, to spot the ones they changed too
+    let our_edits: HashSet<(String, String)> = ours
+        .iter()
+        .filter(|r| !ancestor.contains(&record_key(r)))
+        .map(trade_key)
+        .collect();
+    let ancestor_trades: HashSet<(String, String)> = ancestor
+        .iter()
+        .filter(|key| !our_keys.contains(*key) && !their_keys.contains(*key))
+        .filter(|key| key.len() > 1)
+        .map(|key| (key[0].clone(), key[1].clone()))
+        .collect();
+
+    let mut seen = our_keys;
+    for record in theirs {
+        let key = record_key(&record);
+        if ancestor.contains(&key) || !seen.insert(key) {
+            continue;
+        }
+        let trade = trade_key(&record);
+        if ancestor_trades.contains(&trade) && our_edits.contains(&trade) {
+            outcome.both_edited.push(trade);
+        }
+        ours.push(record);
+        outcome.added += 1;
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::Silent;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use rstest::rstest;
+    use tempfile::TempDir;
+
+    #[rstest]
+    #[case("foo.sync-conflict-20240101-120000-ABCDEFG.csv", Some("foo"))]
+    #[case("my.pfl.sync-conflict-20240101-120000-ABCDEFG.csv", Some("my.pfl"))]
+    #[case("foo (conflicted copy 2024-01-01).csv", Some("foo"))]
+    #[case("foo (Ann's conflicted copy 2024-01-01).csv", Some("foo"))]
+    #[case("foo.csv", None)]
+    #[case("foo (copy).csv", None)]
+    #[case("foo.sync-conflict-20240101-120000-ABCDEFG.txt", None)]
+    fn test_conflict_origin(#[case] file_name: &str, #[case] expected: Option<&str>) {
+        assert_eq!(conflict_origin(file_name).as_deref(), expected);
+    }
+
+    const HEAD: &str = "# base_currency: USD\ncreated_at,pair,side,amount,price,fee\n";
+
+    #[test]
+    fn test_merge_unions_rows_in_timestamp_order() {
+        let dir = TempDir::new().unwrap();
+        let common = "1704883200,BTC/USD,BUY,1.0,40000.00,7.50\n";
+        let local = "1717977600,ETH/USD,BUY,2,3100.00,7.7\n";
+        let remote = "1710460800,BTC/USD,BUY,3,20000.00,10.00\n";
+        create_test_csv(&dir, "pf", &format!("{HEAD}{common}{local}"));
+        create_test_csv(
+            &dir,
+            "pf.sync-conflict-20240101-120000-ABCDEFG",
+            &format!("{HEAD}{common}{remote}"),
+        );
+        let settings = create_test_settings(dir.path().to_path_buf());
+
+        assert_eq!(find_conflicts(&settings).unwrap().len(), 1);
+        merge("pf", None, false, false, &Silent, &settings).unwrap();
+
+        let merged = std::fs::read_to_string(settings.path_for("pf")).unwrap();
+        // rows are rewritten in canonical form
+        assert_eq!(
+            merged,
+            format!(
+                "{HEAD}1704883200,BTC/USD,BUY,1,40000,7.5\n\
+                 1710460800,BTC/USD,BUY,3,20000,10\n\
+                 1717977600,ETH/USD,BUY,2,3100,7.7\n"
+            )
+        );
+        assert!(find_conflicts(&settings).unwrap().is_empty());
+    }
+
+    fn records(rows: &[&str]) -> Vec<csv::StringRecord> {
+        rows.iter()
+            .map(|r| csv::StringRecord::from(r.split(',').collect::<Vec<_>>()))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_records_against_ancestor() {
+        let ancestor: HashSet<Vec<String>> = records(&[
+            "1,BTC/USD,BUY,1,100,0",
+            "2,BTC/USD,BUY,1,200,0",
+            "3,ETH/USD,BUY,1,10,0",
+            "4,ETH/USD,SELL,1,20,0",
+        ])
+        .iter()
+        .map(record_key)
+        .collect();
+        // we deleted 2 and edited 4, they deleted 3, edited 4 too and added 5
+        let mut ours = records(&[
+            "1,BTC/USD,BUY,1,100,0",
+            "3,ETH/USD,BUY,1,10,0",
+            "4,ETH/USD,SELL,1,21,0",
+        ]);
+        let theirs = records(&[
+            "1,BTC/USD,BUY,1,100,0",
+            "2,BTC/USD,BUY,1,200,0",
+            "4,ETH/USD,SELL,1,22,0",
+            "5,BTC/USD,SELL,1,300,0",
+        ]);
+
+        let outcome = merge_records(&ancestor, &mut ours, theirs);
+
+        assert_eq!(
+            ours,
+            records(&[
+                "1,BTC/USD,BUY,1,100,0",
+                "4,ETH/USD,SELL,1,21,0",
+                "4,ETH/USD,SELL,1,22,0",
+                "5,BTC/USD,SELL,1,300,0",
+            ])
+        );
+        assert_eq!(
+            outcome,
+            MergeOutcome {
+                added: 2,
+                deleted: 1,
+                both_edited: vec![("4".to_string(), "ETH/USD".to_string())],
+            }
+        );
+    }
+
+    #[test]
+    fn test_merge_deletes_rows_deleted_since_the_git_commit() {
+        let dir = TempDir::new().unwrap();
+        let first = "1704883200,BTC/USD,BUY,1,40000,7.5\n";
+        let second = "1710460800,BTC/USD,BUY,3,20000,10\n";
+        create_test_csv(&dir, "pf", &format!("{HEAD}{first}{second}"));
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+                .args(args)
+                .current_dir(dir.path())
+                .stdout(Stdio::null())
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(&["init", "-q"]);
+        git(&["add", "pf.csv"]);
+        git(&["commit", "-q", "-m", "base"]);
+
+        // one machine deleted the second trade, the other one added a third
+        let third = "1717977600,ETH/USD,BUY,2,3100,7.7\n";
+        create_test_csv(&dir, "pf", &format!("{HEAD}{first}"));
+        create_test_csv(
+            &dir,
+            "pf.sync-conflict-20240101-120000-ABCDEFG",
+            &format!("{HEAD}{first}{second}{third}"),
+        );
+        let settings = create_test_settings(dir.path().to_path_buf());
+        merge("pf", None, false, false, &Silent, &settings).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(settings.path_for("pf")).unwrap(),
+            format!("{HEAD}{first}{third}")
+        );
+    }
+
+    #[test]
+    fn test_merge_rejects_different_base_currency() {
+        let dir = TempDir::new().unwrap();
+        create_test_csv(&dir, "pf", HEAD);
+        create_test_csv(
+            &dir,
+            "pf (conflicted copy 2024-01-01)",
+            "# base_currency: EUR\ncreated_at,pair,side,amount,price,fee\n",
+        );
+        let settings = create_test_settings(dir.path().to_path_buf());
+
+        let err = merge("pf", None, true, false, &Silent, &settings).unwrap_err();
+        assert!(err.to_string().contains("base currency differs"));
+    }
+}
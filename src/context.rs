@@ -0,0 +1,113 @@
+use crate::cli::Cli;
+use crate::clock;
+use crate::http;
+use crate::manual_price;
+use crate::quote::{self, LiveQuotes, Prices};
+use crate::reporter::{self, Reporter, Terminal};
+use crate::settings::Settings;
+use crate::warnings;
+use anyhow::Result;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// What every command runs with: the settings, with the clock, the prices
+/// positions are valued at and where output goes
+///
+/// Built once by `main`; tests build one with fixed prices instead of the
+/// live quote providers.
+pub struct AppContext {
+    pub settings: Settings,
+    pub prices: Box<dyn Prices>,
+    pub reporter: Box<dyn Reporter>,
+}
+
+impl AppContext {
+    /// Context valuing at live quotes, printing to the terminal
+    pub fn new(settings: Settings) -> Self {
+        AppContext {
+            settings,
+            prices: Box::new(LiveQuotes),
+            reporter: Box::new(Terminal::default()),
+        }
+    }
+
+    /// Load the settings for `cli` and configure the quote providers from them
+    pub fn load(cli: &Cli) -> Result<Self> {
+        let settings = Settings::load(cli)?;
+        http::set_timeout_secs(settings.http_timeout_secs);
+        if let Some(secs) = cli.timeout {
+            http::set_deadline(Duration::from_secs(secs));
+        }
+        if let Some(path) = &settings.fixture_prices {
+            http::disable(format!("--fixture-prices {}", path.display()));
+        }
+        if let Some(at) = cli.now {
+            clock::freeze(at);
+        }
+        quote::configure(
+            quote::parse_providers(&settings.quote_provider)?,
+            settings.quote_discrepancy_pct,
+        );
+        quote::configure_delisted(settings.delisted.clone());
+        quote::configure_symbol_overrides(settings.symbol_overrides.clone());
+        match manual_price::load(&settings) {
+            Ok(prices) => quote::configure_manual(prices, settings.clock.now().date()),
+            Err(e) => warnings::warn(format!("can't read manual prices: {}", e)),
+        }
+        Ok(AppContext {
+            reporter: reporter::for_mode(cli.output, cli.quiet),
+            ..AppContext::new(settings)
+        })
+    }
+
+    pub fn with_prices(self, prices: impl Prices + 'static) -> Self {
+        AppContext {
+            prices: Box::new(prices),
+            ..self
+        }
+    }
+
+    pub fn with_reporter(self, reporter: impl Reporter + 'static) -> Self {
+        AppContext {
+            reporter: Box::new(reporter),
+            ..self
+        }
+    }
+
+    pub fn now(&self) -> OffsetDateTime {
+        self.settings.clock.now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::Currency;
+    use crate::portfolio::Portfolio;
+    use crate::test_utils::helpers::create_test_csv;
+    use rust_decimal::dec;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_context_values_at_its_prices() {
+        let dir = TempDir::new().unwrap();
+        let path = create_test_csv(
+            &dir,
+            "main",
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,0.5,40000,0
+",
+        );
+        let ctx = AppContext::new(Settings::default())
+            .with_prices(HashMap::from([("BTC".to_string(), dec!(60000))]));
+        let pf = Portfolio::from_csv(&path).unwrap();
+        assert_eq!(pf.market_value(ctx.prices.as_ref()).unwrap(), dec!(30000));
+        assert_eq!(pf.unrealized_pnl(ctx.prices.as_ref()).unwrap(), dec!(10000));
+        assert!(
+            ctx.prices
+                .price_in(&Currency::new("ETH").unwrap(), "USD")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use crate::portfolio::Portfolio;
+use crate::reporter::Reporter;
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thousands::Separable;
+use time::OffsetDateTime;
+
+/// Money moved in (positive) or out (negative) of a portfolio, in the base currency
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Contribution {
+    pub at: OffsetDateTime,
+    pub amount: Decimal,
+}
+
+impl Contribution {
+    pub fn new(at: OffsetDateTime, amount: Decimal) -> Self {
+        Contribution { at, amount }
+    }
+}
+
+/// Net contributions per month, with the running total: `(YYYY-MM, net, cumulative)`
+pub fn monthly(contributions: &[Contribution]) -> Vec<(String, Decimal, Decimal)> {
+    let mut months: BTreeMap<(i32, u8), Decimal> = BTreeMap::new();
+    for c in contributions {
+        *months.entry((c.at.year(), c.at.month() as u8)).or_default() += c.amount;
+    }
+
+    let mut cumulative = dec!(0);
+    months
+        .into_iter()
+        .map(|((year, month), net)| {
+            cumulative += net;
+            (format!("{}-{:02}", year, month), net, cumulative)
+        })
+        .collect()
+}
+
+/// Print contributions vs market growth, skipped for portfolios without
+/// deposit or withdraw rows
+///
+/// `crypto_value` is the current value of the crypto positions, base
+/// currency cash held in the portfolio is added to it.
+pub fn print_section(pf: &Portfolio, crypto_value: Decimal, reporter: &dyn Reporter) {
+    if pf.contributions.is_empty() {
+        return;
+    }
+
+    let mut table = Table::new();
+    table.add_row(row!["Month", "Net deposits", "Cumulative"]);
+    for (month, net, cumulative) in monthly(&pf.contributions) {
+        table.add_row(row![
+            month,
+            format!("{} {}", net.round_dp(2).separate_with_commas(), pf.base),
+            format!(
+                "{} {}",
+                cumulative.round_dp(2).separate_with_commas(),
+                pf.base
+            )
+        ]);
+    }
+    reporter.print(table.to_string().trim_end());
+
+    let deposits: Decimal = pf.contributions.iter().map(|c| c.amount).sum();
+    let invested = deposits + pf.implicit_funding;
+    let value = crypto_value + pf.balance(&pf.base);
+    let fmt = |d: Decimal| format!("{} {}", d.round_dp(2).separate_with_underscores(), pf.base);
+
+    reporter.print(&format!("Net deposits:\t{}", fmt(deposits)));
+    if pf.implicit_funding > dec!(0) {
+        // buys without a matching deposit row are still money put in
+        reporter.print(&format!("Unrecorded:\t{}", fmt(pf.implicit_funding)));
+    }
+    reporter.print(&format!("Value:\t\t{}", fmt(value)));
+    reporter.print(&format!("Growth:\t\t{}", fmt(value - invested)));
+    reporter.print("=================================");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::Currency;
+    use crate::test_utils::helpers::create_test_csv;
+    use tempfile::TempDir;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_monthly_accumulates_net_deposits() {
+        let contributions = [
+            Contribution::new(datetime!(2024-02-03 10:00 UTC), dec!(500)),
+            Contribution::new(datetime!(2024-01-10 10:00 UTC), dec!(1000)),
+            Contribution::new(datetime!(2024-02-20 10:00 UTC), dec!(-200)),
+        ];
+        assert_eq!(
+            monthly(&contributions),
+            vec![
+                ("2024-01".to_string(), dec!(1000), dec!(1000)),
+                ("2024-02".to_string(), dec!(300), dec!(1300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_csv_tracks_deposits_withdrawals_and_implicit_funding() {
+        let dir = TempDir::new().unwrap();
+        let path = create_test_csv(
+            &dir,
+            "savings",
+            "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,USD,DEPOSIT,1000,1,0
+1704969600,BTC/USD,BUY,0.02,40000,5
+1705056000,USD,WITHDRAW,100,1,0
+1705142400,BTC/USD,BUY,0.01,40000,0
+",
+        );
+        let pf = Portfolio::from_csv(path).unwrap();
+
+        let usd = Currency::new("USD").unwrap();
+        let btc = Currency::new("BTC").unwrap();
+        assert_eq!(pf.balance(&btc), dec!(0.03));
+        // 1000 - 805 spent - 100 withdrawn, the remaining 95 doesn't cover the second buy
+        assert_eq!(pf.balance(&usd), dec!(0));
+        assert_eq!(pf.implicit_funding, dec!(305));
+        let net: Decimal = pf.contributions.iter().map(|c| c.amount).sum();
+        assert_eq!(net, dec!(900));
+    }
+
+    #[test]
+    fn test_from_csv_rejects_pair_on_deposit_row() {
+        let dir = TempDir::new().unwrap();
+        let path = create_test_csv(
+            &dir,
+            "bad",
+            "created_at,pair,side,amount,price,fee\n1704883200,BTC/USD,DEPOSIT,1,1,0\n",
+        );
+        let err = Portfolio::from_csv(path).unwrap_err();
+        assert!(err.to_string().contains("needs a single ticker"), "{err}");
+    }
+}
@@ -0,0 +1,587 @@
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::tags;
+use crate::trade::{Instrument, Side, Trade, parse_csv_file};
+use anyhow::{Result, bail};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+use time::macros::format_description;
+use time::{Date, Duration, Month, OffsetDateTime};
+
+time::serde::format_description!(plan_date, Date, "[year]-[month]-[day]");
+
+/// How often a plan buys
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Cadence {
+    Daily,
+    Weekly,
+    Biweekly,
+    /// On the day of the month of `start`, or the month's last day when it's shorter
+    Monthly,
+}
+
+/// A dollar-cost averaging schedule from the config file
+///
+/// ```toml
+/// [dca.btc-weekly]
+/// portfolio = "main"
+/// asset = "BTC"
+/// amount = 100
+/// cadence = "weekly"
+/// start = "2024-01-05"
+/// tag = "dca"
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DcaPlan {
+    /// Portfolio the buys go to, for reference and `--name` filters
+    #[serde(default)]
+    pub portfolio: Option<String>,
+    pub asset: String,
+    /// Spent per buy
+    pub amount: Decimal,
+    /// Currency of `amount`, the configured base currency by default
+    #[serde(default)]
+    pub currency: Option<String>,
+    pub cadence: Cadence,
+    /// Day of the first buy
+    #[serde(with = "plan_date")]
+    pub start: Date,
+    /// Only buys with this trade tag count as the plan's, any buy of the
+    /// asset by default
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// How far, in percent, the amount spent on a buy may be off `amount`
+    #[serde(default = "default_tolerance_pct")]
+    pub tolerance_pct: Decimal,
+    /// Days before or after its day a buy still counts
+    #[serde(default = "default_window_days")]
+    pub window_days: u32,
+}
+
+fn default_tolerance_pct() -> Decimal {
+    dec!(10)
+}
+
+fn default_window_days() -> u32 {
+    3
+}
+
+/// How a scheduled buy went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Contribution {
+    /// A matching buy is recorded
+    Made,
+    /// No matching buy yet, but its window is still open
+    Open,
+    /// No matching buy within its window
+    Missed,
+}
+
+impl DcaPlan {
+    /// Day of the `n`th buy, counting from 0
+    pub fn nth(&self, n: u32) -> Option<Date> {
+        match self.cadence {
+            Cadence::Daily => self.start.checked_add(Duration::days(n as i64)),
+            Cadence::Weekly => self.start.checked_add(Duration::weeks(n as i64)),
+            Cadence::Biweekly => self.start.checked_add(Duration::weeks(2 * n as i64)),
+            Cadence::Monthly => {
+                let months = self.start.month() as i32 - 1 + n as i32;
+                let year = self.start.year() + months.div_euclid(12);
+                let month = Month::try_from(months.rem_euclid(12) as u8 + 1).ok()?;
+                let day = self.start.day().min(month.length(year));
+                Date::from_calendar_date(year, month, day).ok()
+            }
+        }
+    }
+
+    /// Days of the buys from `from` to `until`, both included
+    pub fn dates_between(&self, from: Date, until: Date) -> Vec<Date> {
+        (0..)
+            .map_while(|n| self.nth(n).filter(|day| *day <= until))
+            .filter(|day| *day >= from)
+            .collect()
+    }
+
+    fn currency<'a>(&'a self, settings: &'a Settings) -> &'a str {
+        self.currency
+            .as_deref()
+            .unwrap_or(settings.base_currency.ticker())
+    }
+
+    /// Whether `t` is a buy of the asset for about `amount`; the amount is
+    /// only checked when the buy is priced in the plan's currency
+    fn matches(&self, t: &Trade, currency: &str) -> bool {
+        let Instrument::Pair(pair) = &t.pair else {
+            return false;
+        };
+        if t.side != Side::Buy || !pair.base.ticker().eq_ignore_ascii_case(&self.asset) {
+            return false;
+        }
+        if !pair.quote.ticker().eq_ignore_ascii_case(currency) {
+            return true;
+        }
+        let spent = t.amount * t.price + t.fee;
+        (spent - self.amount).abs() * dec!(100) <= self.amount * self.tolerance_pct
+    }
+
+    /// How each buy scheduled from `from` to `today` went, given the
+    /// recorded `trades`; a trade counts for one scheduled buy at most
+    pub fn contributions(
+        &self,
+        trades: &[&Trade],
+        from: Date,
+        today: Date,
+        settings: &Settings,
+    ) -> Vec<(Date, Contribution)> {
+        let window = Duration::days(self.window_days as i64);
+        let currency = self.currency(settings);
+        let mut used = vec![false; trades.len()];
+        self.dates_between(from, today)
+            .into_iter()
+            .map(|day| {
+                let found = trades.iter().enumerate().position(|(i, t)| {
+                    let on = t.created_at.date();
+                    !used[i]
+                        && day - window <= on
+                        && on <= day + window
+                        && self.matches(t, currency)
+                });
+                let contribution = match found {
+                    Some(i) => {
+                        used[i] = true;
+                        Contribution::Made
+                    }
+                    None if day + window >= today => Contribution::Open,
+                    None => Contribution::Missed,
+                };
+                (day, contribution)
+            })
+            .collect()
+    }
+
+    /// `buy 100 USD of BTC`
+    pub fn describe(&self, settings: &Settings) -> String {
+        format!(
+            "buy {} {} of {}",
+            self.amount.normalize(),
+            self.currency(settings),
+            self.asset
+        )
+    }
+}
+
+/// Configured plans, only those of portfolio `name` when given
+fn plans<'a>(name: Option<&str>, settings: &'a Settings) -> Result<Vec<(&'a String, &'a DcaPlan)>> {
+    if settings.dca.is_empty() {
+        bail!("no DCA plans configured, add a [dca.<name>] table to the config file");
+    }
+    Ok(settings
+        .dca
+        .iter()
+        .filter(|(_, plan)| name.is_none_or(|n| plan.portfolio.as_deref() == Some(n)))
+        .collect())
+}
+
+/// `dca due`: the scheduled buys of the next `days` days, today included
+pub fn due(
+    name: Option<&str>,
+    days: u32,
+    today: Date,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let until = today + Duration::days(days as i64);
+    let mut buys: Vec<(Date, &String, &DcaPlan)> = plans(name, settings)?
+        .into_iter()
+        .flat_map(|(plan_name, plan)| {
+            plan.dates_between(today, until)
+                .into_iter()
+                .map(move |day| (day, plan_name, plan))
+        })
+        .collect();
+    if buys.is_empty() {
+        reporter.print(&format!("No DCA buys due in the next {} days", days));
+        return Ok(());
+    }
+    buys.sort_by_key(|(day, name, _)| (*day, name.as_str()));
+
+    let mut table = Table::new();
+    table.add_row(row!["Date", "In", "Plan", "Portfolio", "Buy"]);
+    for (day, plan_name, plan) in buys {
+        let days_left = (day - today).whole_days();
+        let when = match days_left {
+            0 => "today".to_string(),
+            1 => "tomorrow".to_string(),
+            n => format!("{} days", n),
+        };
+        table.add_row(row![
+            day,
+            when,
+            plan_name,
+            plan.portfolio.as_deref().unwrap_or("-"),
+            plan.describe(settings)
+        ]);
+    }
+    reporter.table(&table)?;
+    Ok(())
+}
+
+/// The buys recorded for `plan`, sorted by time: those of its portfolio,
+/// with its tag when it has one
+fn recorded_buys(portfolio: &str, plan: &DcaPlan, settings: &Settings) -> Result<Vec<Trade>> {
+    let (_, mut trades) = parse_csv_file(settings.path_for(portfolio))?;
+    if let Some(tag) = &plan.tag {
+        let tags = tags::load(portfolio, settings)?;
+        trades.retain(|t| tags.get(&t.id()).is_some_and(|tags| tags.contains(tag)));
+    }
+    trades.retain(|t| t.side == Side::Buy);
+    trades.sort_by_key(|t| t.created_at);
+    Ok(trades)
+}
+
+fn contributions_of(
+    portfolio: &str,
+    plan: &DcaPlan,
+    from: Date,
+    today: Date,
+    settings: &Settings,
+) -> Result<Vec<(Date, Contribution)>> {
+    let trades = recorded_buys(portfolio, plan, settings)?;
+    let trades: Vec<&Trade> = trades.iter().collect();
+    Ok(plan.contributions(&trades, from, today, settings))
+}
+
+/// `dca status`: the scheduled buys of the past `days` days that were
+/// made, and those missed
+pub fn status(
+    name: Option<&str>,
+    days: u32,
+    today: Date,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let from = today - Duration::days(days as i64);
+    let mut table = Table::new();
+    table.add_row(row![
+        "Plan",
+        "Portfolio",
+        "Scheduled",
+        "Made",
+        "Missed",
+        "Last missed"
+    ]);
+    let mut missed = 0;
+    for (plan_name, plan) in plans(name, settings)? {
+        let Some(portfolio) = &plan.portfolio else {
+            table.add_row(row![
+                plan_name,
+                "-",
+                "-",
+                "-",
+                "-",
+                "set `portfolio` to check"
+            ]);
+            continue;
+        };
+        let contributions = contributions_of(portfolio, plan, from, today, settings)?;
+        let count = |c: Contribution| contributions.iter().filter(|(_, s)| *s == c).count();
+        let last_missed = contributions
+            .iter()
+            .rev()
+            .find(|(_, c)| *c == Contribution::Missed)
+            .map_or("-".to_string(), |(day, _)| day.to_string());
+        missed += count(Contribution::Missed);
+        table.add_row(row![
+            plan_name,
+            portfolio,
+            r->contributions.len(),
+            r->count(Contribution::Made),
+            r->count(Contribution::Missed),
+            last_missed
+        ]);
+    }
+    reporter.table(&table)?;
+    match missed {
+        0 => reporter.print(&format!("No DCA buys missed in the past {} days", days)),
+        n => reporter.print(&format!(
+            "{} DCA buy(s) missed in the past {} days",
+            n, days
+        )),
+    }
+    Ok(())
+}
+
+/// For `watch`: the plans whose buy of a day was missed, with its window
+/// closed yesterday, as `(plan, day)`; plans that can't be checked are
+/// warned about, the daemon keeps running
+pub fn newly_missed(today: Date, settings: &Settings) -> Vec<(String, Date)> {
+    let mut missed = Vec::new();
+    for (plan_name, plan) in &settings.dca {
+        let Some(portfolio) = &plan.portfolio else {
+            continue;
+        };
+        let closed = today - Duration::days(plan.window_days as i64 + 1);
+        // earlier scheduled buys take their trades first, as in `dca status`
+        let from = closed - Duration::days(2 * plan.window_days as i64 + 31);
+        let contributions = match contributions_of(portfolio, plan, from, today, settings) {
+            Ok(contributions) => contributions,
+            Err(e) => {
+                crate::warnings::warn(format!("can't check DCA plan '{}': {}", plan_name, e));
+                continue;
+            }
+        };
+        for (day, contribution) in contributions {
+            if day == closed && contribution == Contribution::Missed {
+                missed.push((plan_name.clone(), day));
+            }
+        }
+    }
+    missed
+}
+
+fn ics_date(day: Date) -> Result<String> {
+    Ok(day.format(format_description!("[year][month][day]"))?)
+}
+
+/// Escape TEXT values, RFC 5545 3.3.11
+fn ics_text(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Content line folded at 75 octets, ended with CRLF
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn rrule(plan: &DcaPlan) -> String {
+    match plan.cadence {
+        Cadence::Daily => "FREQ=DAILY".to_string(),
+        Cadence::Weekly => "FREQ=WEEKLY".to_string(),
+        Cadence::Biweekly => "FREQ=WEEKLY;INTERVAL=2".to_string(),
+        Cadence::Monthly if plan.start.day() <= 28 => {
+            format!("FREQ=MONTHLY;BYMONTHDAY={}", plan.start.day())
+        }
+        // the last of these days each month, so the 31st falls back to the 30th or 28th
+        Cadence::Monthly => {
+            let days: Vec<String> = (28..=plan.start.day()).map(|d| d.to_string()).collect();
+            format!("FREQ=MONTHLY;BYMONTHDAY={};BYSETPOS=-1", days.join(","))
+        }
+    }
+}
+
+/// Calendar with one recurring all-day event per plan, each with a
+/// reminder at 9:00 on the day
+pub fn calendar(
+    plans: &[(&String, &DcaPlan)],
+    now: OffsetDateTime,
+    settings: &Settings,
+) -> Result<String> {
+    let stamp = now.format(format_description!(
+        "[year][month][day]T[hour][minute][second]Z"
+    ))?;
+    let mut out = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//portfolio-tracker//DCA plans//EN",
+        "CALSCALE:GREGORIAN",
+    ] {
+        push_line(&mut out, line);
+    }
+    for (name, plan) in plans {
+        let summary = ics_text(&format!("DCA: {}", plan.describe(settings)));
+        let mut description = format!("Plan {}", name);
+        if let Some(portfolio) = &plan.portfolio {
+            write!(description, ", portfolio {}", portfolio)?;
+        }
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(&mut out, &format!("UID:dca-{}@portfolio-tracker", name));
+        push_line(&mut out, &format!("DTSTAMP:{}", stamp));
+        push_line(
+            &mut out,
+            &format!("DTSTART;VALUE=DATE:{}", ics_date(plan.start)?),
+        );
+        push_line(&mut out, &format!("RRULE:{}", rrule(plan)));
+        push_line(&mut out, &format!("SUMMARY:{}", summary));
+        push_line(&mut out, &format!("DESCRIPTION:{}", ics_text(&description)));
+        push_line(&mut out, "BEGIN:VALARM");
+        push_line(&mut out, "ACTION:DISPLAY");
+        push_line(&mut out, &format!("DESCRIPTION:{}", summary));
+        push_line(&mut out, "TRIGGER:PT9H");
+        push_line(&mut out, "END:VALARM");
+        push_line(&mut out, "END:VEVENT");
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    Ok(out)
+}
+
+/// `export --format ics`: the DCA plans as a calendar, `-` for stdout
+pub fn export_calendar(
+    name: Option<&str>,
+    out: &Path,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let plans = plans(name, settings)?;
+    let calendar = calendar(&plans, settings.clock.now(), settings)?;
+    if out == Path::new("-") {
+        reporter.print(calendar.trim_end());
+        return Ok(());
+    }
+    std::fs::write(out, calendar)?;
+    reporter.status(&format!(
+        "Exported {} DCA plans to {}",
+        plans.len(),
+        out.display()
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::dec;
+    use time::macros::{date, datetime};
+
+    fn plan(cadence: Cadence, start: Date) -> DcaPlan {
+        DcaPlan {
+            portfolio: Some("main".to_string()),
+            asset: "BTC".to_string(),
+            amount: dec!(100),
+            currency: None,
+            cadence,
+            start,
+            tag: None,
+            tolerance_pct: default_tolerance_pct(),
+            window_days: default_window_days(),
+        }
+    }
+
+    #[test]
+    fn test_monthly_plans_fall_back_to_the_last_day_of_short_months() {
+        let monthly = plan(Cadence::Monthly, date!(2024 - 01 - 31));
+        assert_eq!(
+            monthly.dates_between(date!(2024 - 02 - 01), date!(2024 - 04 - 30)),
+            vec![
+                date!(2024 - 02 - 29),
+                date!(2024 - 03 - 31),
+                date!(2024 - 04 - 30)
+            ]
+        );
+        assert_eq!(
+            rrule(&monthly),
+            "FREQ=MONTHLY;BYMONTHDAY=28,29,30,31;BYSETPOS=-1"
+        );
+
+        let biweekly = plan(Cadence::Biweekly, date!(2024 - 01 - 05));
+        assert_eq!(
+            biweekly.dates_between(date!(2024 - 01 - 10), date!(2024 - 02 - 10)),
+            vec![date!(2024 - 01 - 19), date!(2024 - 02 - 02)]
+        );
+        assert!(
+            biweekly
+                .dates_between(date!(2023 - 01 - 01), date!(2023 - 12 - 31))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_contributions_match_buys_within_the_window_and_tolerance() {
+        let settings = Settings::default();
+        let weekly = plan(Cadence::Weekly, date!(2024 - 01 - 01));
+        let trades = crate::test_utils::helpers::transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704067200,BTC/USD,BUY,0.0025,40000,0
+1704758400,BTC/USD,BUY,0.001,40000,0
+1704931200,ETH/USD,BUY,0.04,2500,0
+1705363200,BTC/EUR,BUY,0.001,40000,0
+1705968000,BTC/USD,BUY,0.00275,40000,0
+",
+        );
+        let trades: Vec<&Trade> = trades.iter().collect();
+
+        // 40 USD of BTC on the 9th is too little, ETH the wrong asset, the
+        // EUR buy on the 16th isn't checked for its amount
+        assert_eq!(
+            weekly.contributions(
+                &trades,
+                date!(2024 - 01 - 01),
+                date!(2024 - 01 - 30),
+                &settings
+            ),
+            vec![
+                (date!(2024 - 01 - 01), Contribution::Made),
+                (date!(2024 - 01 - 08), Contribution::Missed),
+                (date!(2024 - 01 - 15), Contribution::Made),
+                (date!(2024 - 01 - 22), Contribution::Made),
+                (date!(2024 - 01 - 29), Contribution::Open),
+            ]
+        );
+
+        let strict = DcaPlan {
+            tolerance_pct: dec!(5),
+            window_days: 0,
+            ..weekly
+        };
+        assert_eq!(
+            strict.contributions(
+                &trades,
+                date!(2024 - 01 - 15),
+                date!(2024 - 01 - 23),
+                &settings
+            ),
+            vec![
+                (date!(2024 - 01 - 15), Contribution::Missed),
+                (date!(2024 - 01 - 22), Contribution::Missed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_calendar_has_a_recurring_event_with_an_alarm_per_plan() {
+        let settings = Settings::default();
+        let name = "btc-weekly".to_string();
+        let weekly = plan(Cadence::Weekly, date!(2024 - 01 - 05));
+        let ics = calendar(
+            &[(&name, &weekly)],
+            datetime!(2024-01-01 12:00 UTC),
+            &settings,
+        )
+        .unwrap();
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.contains(
+            "UID:dca-btc-weekly@portfolio-tracker\r\n\
+             DTSTAMP:20240101T120000Z\r\n\
+             DTSTART;VALUE=DATE:20240105\r\n\
+             RRULE:FREQ=WEEKLY\r\n\
+             SUMMARY:DCA: buy 100 USD of BTC\r\n\
+             DESCRIPTION:Plan btc-weekly\\, portfolio main\r\n\
+             BEGIN:VALARM\r\n"
+        ));
+        assert!(ics.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+
+        let mut folded = String::new();
+        push_line(&mut folded, &"x".repeat(80));
+        assert_eq!(
+            folded,
+            format!("{}\r\n {}\r\n", "x".repeat(75), "x".repeat(5))
+        );
+    }
+}
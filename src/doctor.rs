@@ -0,0 +1,40 @@
+use crate::assertions;
+use crate::conflict::{
+    find_conflicts, find_duplicates, print_conflict_warnings, print_duplicate_warnings,
+};
+use crate::lint;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use anyhow::{Result, bail};
+
+/// Run workspace health checks and print the problems found
+pub fn run(reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let mut problems = 0;
+
+    let conflicts = find_conflicts(settings)?;
+    print_conflict_warnings(&conflicts, reporter);
+    problems += conflicts.len();
+
+    let duplicates = find_duplicates(settings)?;
+    print_duplicate_warnings(&duplicates, reporter, settings);
+    problems += duplicates.len();
+
+    problems += assertions::print_failures(reporter, settings)?;
+
+    let (findings, lint_errors) = lint::print_findings(reporter, settings)?;
+    problems += findings;
+
+    if problems == 0 {
+        reporter.print(&format!(
+            "✅ No problems found in {}",
+            settings.portfolio_dir.display()
+        ));
+    } else {
+        reporter.print(&format!("Found {} problem(s)", problems));
+    }
+    if lint_errors > 0 {
+        bail!("{} row(s) break lint rules set to error", lint_errors);
+    }
+
+    Ok(())
+}
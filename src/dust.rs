@@ -0,0 +1,209 @@
+use crate::currency::{Currency, CurrencyType};
+use crate::portfolio::Portfolio;
+use crate::quote::quote_in_base;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{Instrument, Side, Trade, TradingPair};
+use anyhow::{Context, Result};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use std::path::Path;
+use time::OffsetDateTime;
+
+/// A position worth less than the dust threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dust {
+    pub currency: Currency,
+    pub balance: Decimal,
+    /// Value in the portfolio base currency
+    pub value: Decimal,
+}
+
+/// Crypto positions with a value below `threshold`, smallest first
+///
+/// `price` returns the unit price in the base currency.
+pub fn find_dust<F>(pf: &Portfolio, threshold: Decimal, mut price: F) -> Result<Vec<Dust>>
+where
+    F: FnMut(&Currency) -> Result<Decimal>,
+{
+    let mut dust = Vec::new();
+    for (currency, position) in pf.positions.iter() {
+        if currency.currency_type() != CurrencyType::Crypto || position.balance <= dec!(0) {
+            continue;
+        }
+        let value = position.balance * price(currency)?;
+        if value < threshold {
+            dust.push(Dust {
+                currency: currency.clone(),
+                balance: position.balance,
+                value,
+            });
+        }
+    }
+    dust.sort_by_key(|d| d.value);
+    Ok(dust)
+}
+
+/// Planned sell orders moving each dust position into `target`
+///
+/// `target_price` is the unit price of the target in the base currency,
+/// dust already held in the target asset is skipped. Orders are dated `now`.
+pub fn plan_orders(
+    dust: &[Dust],
+    target: &Currency,
+    target_price: Decimal,
+    now: OffsetDateTime,
+) -> Result<Vec<Trade>> {
+    let now = now.replace_nanosecond(0)?;
+    let mut orders = Vec::new();
+    for d in dust.iter().filter(|d| &d.currency != target) {
+        let pair = TradingPair::new(d.currency.clone(), target.clone())?;
+        orders.push(Trade {
+            created_at: now,
+            pair: Instrument::Pair(pair),
+            side: Side::Sell,
+            amount: d.balance,
+            price: (d.value / d.balance / target_price).normalize(),
+            fee: dec!(0),
+            source: Some("dust".to_string()),
+        });
+    }
+    Ok(orders)
+}
+
+fn unit_price(currency: &Currency, base: &Currency) -> Result<Decimal> {
+    if currency == base {
+        return Ok(dec!(1));
+    }
+    quote_in_base(currency, base.ticker())
+}
+
+/// List dust positions of a portfolio and suggest consolidation into `target`
+///
+/// With `emit`, the planned orders are written as portfolio rows to that
+/// file, ready to be appended once executed.
+pub fn run(
+    name: &str,
+    threshold: Option<Decimal>,
+    target: Option<&str>,
+    emit: Option<&Path>,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let pf = Portfolio::from_csv(settings.path_for(name))?;
+    let threshold = threshold.unwrap_or(settings.dust_threshold);
+    let target = match target {
+        Some(t) => Currency::new(t)?,
+        None => pf.base.clone(),
+    };
+
+    let dust = find_dust(&pf, threshold, |c| unit_price(c, &pf.base))?;
+    if dust.is_empty() {
+        reporter.print(&format!(
+            "No positions worth less than {} {}",
+            threshold, pf.base
+        ));
+        return Ok(());
+    }
+
+    let orders = plan_orders(
+        &dust,
+        &target,
+        unit_price(&target, &pf.base)?,
+        settings.clock.now(),
+    )?;
+
+    let mut table = Table::new();
+    table.add_row(row!["Ticker", "Balance", "Value", "Suggested order"]);
+    for d in &dust {
+        let order = orders
+            .iter()
+            .find(|o| matches!(&o.pair, Instrument::Pair(p) if p.base == d.currency))
+            .map(|o| format!("SELL {} {} @ {}", o.amount, o.pair, o.price.round_dp(8)))
+            .unwrap_or_else(|| "-".to_string());
+        table.add_row(row![
+            d.currency,
+            d.balance,
+            format!("{} {}", d.value.round_dp(2), pf.base),
+            order
+        ]);
+    }
+    reporter.table(&table)?;
+
+    if let Some(path) = emit {
+        let mut wtr =
+            csv::Writer::from_path(path).with_context(|| format!("creating {}", path.display()))?;
+        for order in &orders {
+            wtr.serialize(order)?;
+        }
+        wtr.flush()?;
+        reporter.status(&format!(
+            "Wrote {} planned order(s) to {}",
+            orders.len(),
+            path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn currency(t: &str) -> Currency {
+        Currency::new(t).unwrap()
+    }
+
+    fn portfolio() -> Portfolio {
+        let mut pf = Portfolio::new();
+        pf.deposit_at_cost(currency("BTC"), dec!(1), dec!(40000));
+        pf.deposit_at_cost(currency("DOGE"), dec!(20), dec!(3));
+        pf.deposit_at_cost(currency("ADA"), dec!(5), dec!(2));
+        pf.deposit_at_cost(currency("USD"), dec!(3), dec!(3));
+        pf
+    }
+
+    fn price(c: &Currency) -> Result<Decimal> {
+        Ok(match c.ticker() {
+            "BTC" => dec!(50000),
+            "DOGE" => dec!(0.1),
+            "ADA" => dec!(0.5),
+            _ => dec!(1),
+        })
+    }
+
+    #[test]
+    fn test_find_dust_lists_small_crypto_positions_smallest_first() {
+        let dust = find_dust(&portfolio(), dec!(10), price).unwrap();
+        let tickers: Vec<&str> = dust.iter().map(|d| d.currency.ticker()).collect();
+        // USD cash is never dust
+        assert_eq!(tickers, vec!["DOGE", "ADA"]);
+        assert_eq!(dust[0].value, dec!(2.0));
+    }
+
+    #[test]
+    fn test_plan_orders_prices_dust_in_target() {
+        let dust = find_dust(&portfolio(), dec!(10), price).unwrap();
+        let now = datetime!(2024-03-01 12:00:00.5 UTC);
+        let orders = plan_orders(&dust, &currency("BTC"), dec!(50000), now).unwrap();
+
+        assert_eq!(orders.len(), 2);
+        assert_eq!(orders[0].pair.to_string(), "DOGE/BTC");
+        assert_eq!(orders[0].side, Side::Sell);
+        assert_eq!(orders[0].amount, dec!(20));
+        assert_eq!(orders[0].price, dec!(0.000002));
+        assert_eq!(orders[0].created_at, datetime!(2024-03-01 12:00:00 UTC));
+    }
+
+    #[test]
+    fn test_plan_orders_skips_target_asset() {
+        let dust = find_dust(&portfolio(), dec!(10), price).unwrap();
+        let now = datetime!(2024-03-01 12:00:00 UTC);
+        let orders = plan_orders(&dust, &currency("ADA"), dec!(0.5), now).unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].pair.to_string(), "DOGE/ADA");
+    }
+}
@@ -0,0 +1,338 @@
+use crate::beancount;
+use crate::currency::Currency;
+use crate::dca;
+use crate::ledger;
+use crate::ofx;
+use crate::parquet;
+use crate::portfolio::{PortfolioEntry, list_entries};
+use crate::position_cache;
+use crate::qif;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::sqlite;
+use crate::trade::{PortfolioKind, Trade, parse_csv_file, read_csv_config};
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+use std::path::Path;
+use time::OffsetDateTime;
+
+/// File format of `export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// One SQLite database with a `trades` and a `positions` table
+    Sqlite,
+    /// A directory with `trades.parquet` and `positions.parquet`
+    Parquet,
+    /// A ledger-cli / hledger journal, `-` for stdout
+    Ledger,
+    /// A Beancount ledger with cost-basis lots, `-` for stdout
+    Beancount,
+    /// QIF investment accounts for Quicken and GnuCash, `-` for stdout
+    Qif,
+    /// An OFX investment statement per portfolio, `-` for stdout
+    Ofx,
+    /// The DCA plans of the config file as iCalendar events, `-` for stdout
+    Ics,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// Amounts, written exactly: never through a float
+    Decimal,
+    Text,
+    Timestamp,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: &'static str,
+    pub kind: ColumnType,
+    pub nullable: bool,
+}
+
+/// One cell of an exported table
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Decimal(Decimal),
+    Text(String),
+    Timestamp(OffsetDateTime),
+}
+
+/// Rows of one exported table, written by the format's writer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub name: &'static str,
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    fn new(name: &'static str, columns: &[(&'static str, ColumnType, bool)]) -> Self {
+        Table {
+            name,
+            columns: columns
+                .iter()
+                .map(|(name, kind, nullable)| Column {
+                    name,
+                    kind: *kind,
+                    nullable: *nullable,
+                })
+                .collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// `CREATE TABLE` statement, timestamps as ISO-8601 text like SQLite's date functions expect,
+    /// decimals as text as SQLite has no exact numeric type
+    pub fn create_sql(&self) -> String {
+        let columns: Vec<String> = self
+            .columns
+            .iter()
+            .map(|c| {
+                let null = if c.nullable { "" } else { " NOT NULL" };
+                format!("{} TEXT{}", c.name, null)
+            })
+            .collect();
+        format!("CREATE TABLE {}({})", self.name, columns.join(", "))
+    }
+}
+
+fn decimal(d: Decimal) -> Value {
+    Value::Decimal(d)
+}
+
+fn text(s: impl ToString) -> Value {
+    Value::Text(s.to_string())
+}
+
+/// Table of the trades of all `portfolios`, pairs split into asset and quote
+pub fn trades_table(portfolios: &[(String, Vec<Trade>)]) -> Table {
+    use ColumnType::*;
+    let mut table = Table::new(
+        "trades",
+        &[
+            ("portfolio", Text, false),
+            ("id", Text, false),
+            ("created_at", Timestamp, false),
+            ("pair", Text, false),
+            ("side", Text, false),
+            ("asset", Text, false),
+            ("quote", Text, true),
+            ("amount", Decimal, false),
+            ("price", Decimal, false),
+            ("fee", Decimal, false),
+            ("source", Text, true),
+        ],
+    );
+    for (name, trades) in portfolios {
+        for t in trades {
+            let (asset, quote) = match t.pair.trading_pair(t.side) {
+                Ok(pair) => (text(&pair.base), text(&pair.quote)),
+                Err(_) => (text(&t.pair), Value::Null),
+            };
+            table.rows.push(vec![
+                text(name),
+                text(t.id()),
+                Value::Timestamp(t.created_at),
+                text(&t.pair),
+                text(t.side.as_str()),
+                asset,
+                quote,
+                decimal(t.amount),
+                decimal(t.price),
+                decimal(t.fee),
+                t.source.as_ref().map_or(Value::Null, text),
+            ]);
+        }
+    }
+    table
+}
+
+fn positions_table() -> Table {
+    use ColumnType::*;
+    Table::new(
+        "positions",
+        &[
+            ("portfolio", Text, false),
+            ("base_currency", Text, false),
+            ("ticker", Text, false),
+            ("balance", Decimal, false),
+            ("cost_base", Decimal, false),
+        ],
+    )
+}
+
+/// Trades and computed positions of `entries`
+fn tables(entries: &[PortfolioEntry], settings: &Settings) -> Result<Vec<Table>> {
+    let mut trades = Vec::new();
+    let mut positions = positions_table();
+    for entry in entries {
+        let context = || format!("exporting '{}'", entry.name);
+        let config = read_csv_config(&entry.path).with_context(context)?;
+        if config.kind == PortfolioKind::Trades {
+            let (_, rows) = parse_csv_file(&entry.path).with_context(context)?;
+            trades.push((entry.name.clone(), rows));
+        }
+        let pf = position_cache::load(&entry.path, settings).with_context(context)?;
+        let mut held: Vec<_> = pf.positions.values().collect();
+        held.sort_by(|a, b| a.currency.ticker().cmp(b.currency.ticker()));
+        for p in held {
+            positions.rows.push(vec![
+                text(&entry.name),
+                text(&config.base_currency),
+                text(&p.currency),
+                decimal(p.balance),
+                decimal(p.cost_base),
+            ]);
+        }
+    }
+    Ok(vec![trades_table(&trades), positions])
+}
+
+/// `export`: write the trades and positions of one or all portfolios for analytics tools
+pub fn run(
+    name: Option<&str>,
+    format: ExportFormat,
+    out: &Path,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    if format == ExportFormat::Ics {
+        return dca::export_calendar(name, out, reporter, settings);
+    }
+    let entries: Vec<PortfolioEntry> = list_entries(settings)?
+        .into_iter()
+        .filter(|e| name.is_none_or(|n| e.name == n))
+        .collect();
+    if let Some(name) = name
+        && entries.is_empty()
+    {
+        bail!(
+            "no portfolio '{}' in {}",
+            name,
+            settings.portfolio_dir.display()
+        );
+    }
+    let tables = match format {
+        ExportFormat::Sqlite | ExportFormat::Parquet => tables(&entries, settings)?,
+        _ => return export_journal(&entries, format, out, settings.clock.now(), reporter),
+    };
+
+    if format == ExportFormat::Sqlite {
+        sqlite::write(out, &tables)?;
+    } else {
+        std::fs::create_dir_all(out)?;
+        for table in &tables {
+            parquet::write(&out.join(table.name).with_extension("parquet"), table)?;
+        }
+    }
+    reporter.status(&format!(
+        "Exported {} trades and {} positions of {} portfolios to {}",
+        tables[0].rows.len(),
+        tables[1].rows.len(),
+        entries.len(),
+        out.display()
+    ));
+    Ok(())
+}
+
+/// One text file of all trades portfolios of `entries`, holdings have no rows to book;
+/// `now` dates the OFX statements
+fn export_journal(
+    entries: &[PortfolioEntry],
+    format: ExportFormat,
+    out: &Path,
+    now: OffsetDateTime,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let mut portfolios = Vec::new();
+    for entry in entries {
+        let context = || format!("exporting '{}'", entry.name);
+        let config = read_csv_config(&entry.path).with_context(context)?;
+        if config.kind != PortfolioKind::Trades {
+            continue;
+        }
+        let (_, trades) = parse_csv_file(&entry.path).with_context(context)?;
+        let base = Currency::new(&config.base_currency).with_context(context)?;
+        portfolios.push((entry.name.clone(), base, trades));
+    }
+    let rows: usize = portfolios.iter().map(|(_, _, trades)| trades.len()).sum();
+
+    let journal = match format {
+        ExportFormat::Beancount => beancount::ledger(&portfolios)?,
+        ExportFormat::Qif => {
+            let mut journal = String::new();
+            for (name, base, trades) in &portfolios {
+                journal.push_str(&qif::account(name, &qif::entries(trades, base)?)?);
+            }
+            journal
+        }
+        ExportFormat::Ofx => {
+            let statements = portfolios
+                .iter()
+                .map(|(name, base, trades)| {
+                    Ok(ofx::Statement {
+                        name: name.clone(),
+                        base: base.ticker().to_string(),
+                        entries: qif::entries(trades, base)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            ofx::document(&statements, now)?
+        }
+        _ => {
+            let mut journal = String::new();
+            for (name, base, trades) in &portfolios {
+                journal.push_str(&format!("; portfolio {}, base currency {}\n\n", name, base));
+                journal.push_str(&ledger::journal(name, base.ticker(), trades)?);
+            }
+            journal
+        }
+    };
+    if out == Path::new("-") {
+        reporter.print(journal.trim_end());
+        return Ok(());
+    }
+    std::fs::write(out, journal)?;
+    reporter.status(&format!(
+        "Exported {} trades of {} portfolios to {}",
+        rows,
+        entries.len(),
+        out.display()
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::transactions_from;
+    use rust_decimal::dec;
+
+    #[test]
+    fn test_trades_table_splits_pairs_and_keeps_nulls() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee,source
+1704883200,BTC/USD,BUY,0.5,40000,7.5,manual
+1704969600,USD,DEPOSIT,100,1,0,
+",
+        );
+        let table = trades_table(&[("main".to_string(), trades)]);
+        assert_eq!(
+            table.create_sql(),
+            "CREATE TABLE trades(portfolio TEXT NOT NULL, id TEXT NOT NULL, \
+             created_at TEXT NOT NULL, pair TEXT NOT NULL, side TEXT NOT NULL, \
+             asset TEXT NOT NULL, quote TEXT, amount TEXT NOT NULL, \
+             price TEXT NOT NULL, fee TEXT NOT NULL, source TEXT)"
+        );
+        assert_eq!(
+            table.rows[0][5..8],
+            [text("BTC"), text("USD"), decimal(dec!(0.5))]
+        );
+        assert_eq!(table.rows[0][10], text("manual"));
+        assert_eq!(table.rows[1][5..7], [text("USD"), Value::Null]);
+        assert_eq!(table.rows[1][10], Value::Null);
+    }
+}
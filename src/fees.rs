@@ -0,0 +1,304 @@
+use crate::math::{self, Percent};
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{Side, Trade, parse_csv_file};
+use anyhow::{Result, bail};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thousands::Separable;
+use time::macros::format_description;
+
+/// Fee tier of trades without a configured exchange
+pub const DEFAULT_TIER: &str = "default";
+
+/// Fee schedule of an exchange, in basis points of the traded amount
+///
+/// ```toml
+/// [fee_tiers.binance]
+/// maker_bps = 10
+/// taker_bps = 10
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct FeeTier {
+    /// Negative for exchanges paying a rebate
+    pub maker_bps: Decimal,
+    pub taker_bps: Decimal,
+}
+
+/// Fees paid in one asset
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AssetFees {
+    /// `fee` column of buys and sells, paid in the quote currency
+    pub trading: Decimal,
+    /// Amount of `FEE` rows, e.g. on-chain withdrawal fees
+    pub network: Decimal,
+    /// Network fees valued in the base currency at the time they were paid
+    pub network_value: Decimal,
+}
+
+/// Trading and network fees per asset, by ticker
+pub fn by_asset(trades: &[Trade]) -> Result<BTreeMap<String, AssetFees>> {
+    let mut fees: BTreeMap<String, AssetFees> = BTreeMap::new();
+    for t in trades {
+        match t.side {
+            Side::Buy | Side::Sell if !t.fee.is_zero() => {
+                let quote = &t.pair.trading_pair(t.side)?.quote;
+                fees.entry(quote.to_string()).or_default().trading += t.fee;
+            }
+            Side::Fee => {
+                let f = fees.entry(t.pair.asset(t.side)?.to_string()).or_default();
+                f.network += t.amount;
+                f.network_value += t.amount * t.price;
+            }
+            _ => {}
+        }
+    }
+    Ok(fees)
+}
+
+/// Print the fees of a portfolio per asset, with their total in the base currency
+pub fn run(name: &str, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let (config, trades) = parse_csv_file(settings.path_for(name))?;
+    let base = config.base_currency;
+    let fees = by_asset(&trades)?;
+    if fees.is_empty() {
+        reporter.print("No fees recorded");
+        return Ok(());
+    }
+
+    let amount = |d: Decimal| {
+        if d.is_zero() {
+            "-".to_string()
+        } else {
+            d.normalize().separate_with_commas()
+        }
+    };
+    let mut table = Table::new();
+    table.add_row(row![
+        "Asset",
+        "Trading fees",
+        "Network fees",
+        "Network fees value"
+    ]);
+    let mut total = dec!(0);
+    let mut other_trading = false;
+    for (asset, f) in &fees {
+        table.add_row(row![
+            asset,
+            r->amount(f.trading),
+            r->amount(f.network),
+            r->if f.network.is_zero() {
+                "-".to_string()
+            } else {
+                format!("{} {}", f.network_value.round_dp(2).normalize().separate_with_commas(), base)
+            }
+        ]);
+        total += f.network_value;
+        if *asset == base {
+            total += f.trading;
+        } else if !f.trading.is_zero() {
+            other_trading = true;
+        }
+    }
+    reporter.table(&table)?;
+    reporter.print(&format!(
+        "Total fees:\t{} {}",
+        total.round_dp(2).normalize().separate_with_commas(),
+        base
+    ));
+    if other_trading {
+        reporter.print(&format!(
+            "Trading fees paid in other currencies than {} aren't in the total",
+            base
+        ));
+    }
+    Ok(())
+}
+
+/// Exchange of a trade, `binance` for rows with source `import:binance`
+fn exchange(trade: &Trade) -> Option<&str> {
+    trade.source.as_deref()?.strip_prefix("import:")
+}
+
+/// A buy or sell whose fee is off the schedule of its exchange
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeDeviation<'a> {
+    pub trade: &'a Trade,
+    /// Name of the fee tier the fee was checked against
+    pub tier: String,
+    /// Fees at the maker and at the taker rate, in the quote currency
+    pub expected: (Decimal, Decimal),
+}
+
+impl FeeDeviation<'_> {
+    /// How far the fee is from the nearest expected one; `None` when that
+    /// one is zero
+    pub fn off_pct(&self) -> Option<Percent> {
+        let (low, high) = self.expected;
+        let nearest = if self.trade.fee < low { low } else { high };
+        math::change_pct(nearest, self.trade.fee)
+    }
+}
+
+/// Buys and sells whose fee is more than `tolerance_pct` below the maker or
+/// above the taker fee of their tier: the tier of their exchange, else
+/// `default`; trades without a tier aren't checked
+pub fn check<'a>(
+    trades: &'a [Trade],
+    tiers: &BTreeMap<String, FeeTier>,
+    tolerance_pct: Decimal,
+) -> Vec<FeeDeviation<'a>> {
+    let tolerance = tolerance_pct / dec!(100);
+    trades
+        .iter()
+        .filter(|t| matches!(t.side, Side::Buy | Side::Sell))
+        .filter_map(|t| {
+            let (tier, rates) = exchange(t)
+                .and_then(|e| tiers.get_key_value(e))
+                .or_else(|| tiers.get_key_value(DEFAULT_TIER))?;
+            let notional = t.amount * t.price;
+            let low = notional * rates.maker_bps / dec!(10000);
+            let high = notional * rates.taker_bps / dec!(10000);
+            let off = t.fee < low - low.abs() * tolerance || t.fee > high * (dec!(1) + tolerance);
+            off.then(|| FeeDeviation {
+                trade: t,
+                tier: tier.clone(),
+                expected: (low, high),
+            })
+        })
+        .collect()
+}
+
+/// `stats --fee-check`: list the trades whose fee is off the configured schedule
+pub fn print_check(name: &str, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    if settings.fee_tiers.is_empty() {
+        bail!(
+            "no fee tiers configured, add a [fee_tiers.<exchange>] table, or [fee_tiers.default], to the config file"
+        );
+    }
+    let (_, trades) = parse_csv_file(settings.path_for(name))?;
+    let deviations = check(
+        &trades,
+        &settings.fee_tiers,
+        settings.fee_check_tolerance_pct,
+    );
+    let checked = trades
+        .iter()
+        .filter(|t| matches!(t.side, Side::Buy | Side::Sell))
+        .filter(|t| {
+            exchange(t).is_some_and(|e| settings.fee_tiers.contains_key(e))
+                || settings.fee_tiers.contains_key(DEFAULT_TIER)
+        })
+        .count();
+    if deviations.is_empty() {
+        reporter.print(&format!(
+            "All {} checked trades have fees within {}% of their schedule",
+            checked, settings.fee_check_tolerance_pct
+        ));
+        return Ok(());
+    }
+
+    let date = format_description!("[year]-[month]-[day]");
+    let mut table = Table::new();
+    table.add_row(row![
+        "Id", "Date", "Pair", "Side", "Tier", "Fee", "Expected", "Off"
+    ]);
+    for d in &deviations {
+        let t = d.trade;
+        let quote = t.pair.trading_pair(t.side)?.quote.to_string();
+        let (low, high) = d.expected;
+        let expected = if low == high {
+            format!("{} {}", low.round_dp(8).normalize(), quote)
+        } else {
+            format!(
+                "{} - {} {}",
+                low.round_dp(8).normalize(),
+                high.round_dp(8).normalize(),
+                quote
+            )
+        };
+        table.add_row(row![
+            t.id(),
+            t.created_at.format(date)?,
+            t.pair,
+            t.side,
+            d.tier,
+            r->format!("{} {}", t.fee.normalize(), quote),
+            r->expected,
+            r->d.off_pct().map_or("-".to_string(), |pct| format!("{:+.0}", pct))
+        ]);
+    }
+    reporter.table(&table)?;
+    reporter.print(&format!(
+        "{} of {} checked trades have fees off their schedule by more than {}%",
+        deviations.len(),
+        checked,
+        settings.fee_check_tolerance_pct
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::transactions_from;
+
+    #[test]
+    fn test_by_asset_separates_trading_and_network_fees() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704067200,BTC/USD,BUY,1,40000,10
+1706745600,ETH/BTC,BUY,2,0.05,0.0001
+1709251200,BTC,FEE,0.0005,60000,0
+1709337600,BTC,FEE,0.0005,62000,0
+1709424000,BTC/USD,SELL,0.5,60000,0
+",
+        );
+        let fees = by_asset(&trades).unwrap();
+        assert_eq!(fees.keys().collect::<Vec<_>>(), vec!["BTC", "USD"]);
+        assert_eq!(
+            fees["BTC"],
+            AssetFees {
+                trading: dec!(0.0001),
+                network: dec!(0.001),
+                network_value: dec!(61),
+            }
+        );
+        assert_eq!(fees["USD"].trading, dec!(10));
+    }
+
+    #[test]
+    fn test_check_uses_the_exchange_tier_then_default() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee,source
+1704067200,BTC/USD,BUY,1,40000,40,import:kraken
+1704153600,BTC/USD,BUY,1,40000,100,import:kraken
+1704240000,BTC/USD,BUY,1,40000,100,manual
+1704326400,BTC/USD,SELL,1,40000,0,import:binance
+1704412800,BTC,FEE,0.001,40000,0,manual
+",
+        );
+        let tier = |maker_bps, taker_bps| FeeTier {
+            maker_bps,
+            taker_bps,
+        };
+        let mut tiers = BTreeMap::from([("kraken".to_string(), tier(dec!(16), dec!(26)))]);
+
+        let off = check(&trades, &tiers, dec!(25));
+        assert_eq!(off.len(), 1);
+        assert_eq!(off[0].trade.fee, dec!(40));
+        assert_eq!(off[0].expected, (dec!(64), dec!(104)));
+        assert_eq!(off[0].off_pct(), Some(Percent::new(dec!(-37.5))));
+        assert!(check(&trades, &tiers, dec!(40)).is_empty());
+
+        // rebates: a zero fee is within a negative maker rate
+        tiers.insert(DEFAULT_TIER.to_string(), tier(dec!(-2), dec!(10)));
+        let off = check(&trades, &tiers, dec!(25));
+        let fees: Vec<Decimal> = off.iter().map(|d| d.trade.fee).collect();
+        assert_eq!(fees, vec![dec!(40), dec!(100)]);
+        assert_eq!(off[1].tier, DEFAULT_TIER);
+    }
+}
@@ -0,0 +1,268 @@
+use crate::settings::Settings;
+use anyhow::Result;
+use clap::ValueEnum;
+use prettytable::{Cell, Row, Table};
+use rust_decimal::Decimal;
+use serde_json::{Map, Value};
+use thousands::Separable;
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcOffset};
+
+/// How a command renders its rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Table for people
+    #[default]
+    Table,
+    /// Array of objects, one per row
+    Json,
+    /// CSV with a header row
+    Csv,
+}
+
+/// One value of an output row
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    Text(String),
+    Number(Decimal),
+    /// Shown in the command's display format in tables, always ISO-8601 UTC
+    /// in JSON and CSV so parsers get the same values everywhere
+    Time(OffsetDateTime),
+}
+
+impl From<&str> for Field {
+    fn from(s: &str) -> Self {
+        Field::Text(s.to_string())
+    }
+}
+
+impl From<String> for Field {
+    fn from(s: String) -> Self {
+        Field::Text(s)
+    }
+}
+
+impl From<Decimal> for Field {
+    fn from(d: Decimal) -> Self {
+        Field::Number(d)
+    }
+}
+
+impl From<OffsetDateTime> for Field {
+    fn from(ts: OffsetDateTime) -> Self {
+        Field::Time(ts)
+    }
+}
+
+/// Machine timestamp: ISO-8601 in UTC with a `Z` suffix, whole seconds
+pub fn iso_utc(ts: OffsetDateTime) -> String {
+    ts.to_offset(UtcOffset::UTC)
+        .replace_nanosecond(0)
+        .unwrap_or(ts)
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| ts.unix_timestamp().to_string())
+}
+
+/// Price for display: cents rounded per the `rounding` setting, or
+/// significant figures for prices below `small_price_below` (0.00001234
+/// instead of 0.00)
+pub fn price(d: Decimal, settings: &Settings) -> String {
+    if !d.is_zero() && d.abs() < settings.small_price_below {
+        d.round_sf(settings.price_sig_figs)
+            .unwrap_or(d)
+            .normalize()
+            .to_string()
+    } else {
+        settings.rounding.cents(d).separate_with_commas()
+    }
+}
+
+/// Rows of a command's output under named columns
+///
+/// Commands collect their rows here instead of printing them, so tables,
+/// JSON and CSV are rendered the same way for every command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Records {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Field>>,
+}
+
+impl Records {
+    pub fn new(columns: &[&str]) -> Self {
+        Records {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Field>) {
+        self.rows.push(row);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Render in `format`, times in tables through `display_time`
+    pub fn render(
+        &self,
+        format: OutputFormat,
+        display_time: impl Fn(OffsetDateTime) -> String,
+    ) -> Result<String> {
+        let text = |field: &Field| match field {
+            Field::Text(s) => s.clone(),
+            Field::Number(d) => d.to_string(),
+            Field::Time(ts) => iso_utc(*ts),
+        };
+        match format {
+            OutputFormat::Table => {
+                let mut table = Table::new();
+                table.add_row(Row::new(
+                    self.columns.iter().map(|c| Cell::new(c)).collect(),
+                ));
+                for row in &self.rows {
+                    table.add_row(Row::new(
+                        row.iter()
+                            .map(|field| match field {
+                                Field::Time(ts) => Cell::new(&display_time(*ts)),
+                                field => Cell::new(&text(field)),
+                            })
+                            .collect(),
+                    ));
+                }
+                Ok(table.to_string())
+            }
+            OutputFormat::Json => {
+                let rows: Vec<Value> = self
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        let object: Map<String, Value> = self
+                            .columns
+                            .iter()
+                            .zip(row)
+                            .map(|(column, field)| (column.clone(), Value::String(text(field))))
+                            .collect();
+                        Value::Object(object)
+                    })
+                    .collect();
+                Ok(serde_json::to_string_pretty(&rows)? + "\n")
+            }
+            OutputFormat::Csv => {
+                let mut wtr = csv::Writer::from_writer(Vec::new());
+                wtr.write_record(&self.columns)?;
+                for row in &self.rows {
+                    wtr.write_record(row.iter().map(text))?;
+                }
+                Ok(String::from_utf8(wtr.into_inner()?)?)
+            }
+        }
+    }
+
+    pub fn print(
+        &self,
+        format: OutputFormat,
+        display_time: impl Fn(OffsetDateTime) -> String,
+    ) -> Result<()> {
+        print!("{}", self.render(format, display_time)?);
+        Ok(())
+    }
+}
+
+/// Rows of a table built for the terminal, its first row naming the columns
+impl From<&Table> for Records {
+    fn from(table: &Table) -> Self {
+        let mut rows = table.row_iter().map(|row| {
+            row.iter()
+                .map(|cell| cell.get_content())
+                .collect::<Vec<_>>()
+        });
+        Records {
+            columns: rows.next().unwrap_or_default(),
+            rows: rows
+                .map(|row| row.into_iter().map(Field::Text).collect())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prettytable::row;
+    use rust_decimal::dec;
+    use time::macros::{datetime, format_description};
+
+    #[test]
+    fn test_price_uses_sig_figs_below_threshold() {
+        let settings = Settings::default();
+        assert_eq!(price(dec!(0.0000123456789), &settings), "0.000012345679");
+        assert_eq!(price(dec!(0.5), &settings), "0.5");
+        assert_eq!(price(dec!(43210.126), &settings), "43,210.13");
+        assert_eq!(price(dec!(0), &settings), "0");
+
+        let settings = Settings {
+            small_price_below: dec!(0.01),
+            price_sig_figs: 3,
+            ..Settings::default()
+        };
+        assert_eq!(price(dec!(0.0000123456789), &settings), "0.0000123");
+        assert_eq!(price(dec!(0.5), &settings), "0.5");
+    }
+
+    #[test]
+    fn test_price_rounds_cents_per_the_rounding_setting() {
+        let half_even = Settings::default();
+        assert_eq!(price(dec!(2.125), &half_even), "2.12");
+        assert_eq!(price(dec!(1234.135), &half_even), "1,234.14");
+
+        let half_up = Settings {
+            rounding: crate::settings::Rounding::HalfUp,
+            ..Settings::default()
+        };
+        assert_eq!(price(dec!(2.125), &half_up), "2.13");
+        assert_eq!(price(dec!(1234.135), &half_up), "1,234.14");
+    }
+
+    #[test]
+    fn test_records_from_table_take_the_first_row_as_columns() {
+        let mut table = Table::new();
+        table.add_row(row!["Ticker", "Value"]);
+        table.add_row(row!["BTC", r->"1,000.00"]);
+        assert_eq!(
+            Records::from(&table)
+                .render(OutputFormat::Csv, iso_utc)
+                .unwrap(),
+            "Ticker,Value\nBTC,\"1,000.00\"\n"
+        );
+        assert_eq!(Records::from(&Table::new()), Records::new(&[]));
+    }
+
+    #[test]
+    fn test_machine_formats_use_iso_utc_timestamps() {
+        let mut records = Records::new(&["created_at", "pair", "amount"]);
+        records.push(vec![
+            datetime!(2024-01-10 12:30:15.5 +02:00).into(),
+            "BTC/USD".into(),
+            dec!(0.5).into(),
+        ]);
+        let display = |ts: OffsetDateTime| {
+            ts.format(format_description!("[day].[month].[year]"))
+                .unwrap()
+        };
+
+        assert_eq!(
+            records.render(OutputFormat::Csv, display).unwrap(),
+            "created_at,pair,amount\n2024-01-10T10:30:15Z,BTC/USD,0.5\n"
+        );
+        let json: Value =
+            serde_json::from_str(&records.render(OutputFormat::Json, display).unwrap()).unwrap();
+        assert_eq!(json[0]["created_at"], "2024-01-10T10:30:15Z");
+        assert!(
+            records
+                .render(OutputFormat::Table, display)
+                .unwrap()
+                .contains("| 10.01.2024 | BTC/USD | 0.5    |")
+        );
+    }
+}
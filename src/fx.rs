@@ -0,0 +1,282 @@
+use crate::currency::{Currency, CurrencyType};
+use crate::http;
+use crate::settings::Settings;
+use anyhow::{Context, Result, anyhow, bail};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::str::FromStr;
+use time::{Date, Duration};
+
+/// ECB data API, daily reference rates of one currency against EUR as CSV
+const ECB_URL: &str = "https://data-api.ecb.europa.eu/service/data/EXR";
+/// Rates are published on TARGET working days only, weekends and holidays
+/// use the last rate published before them
+const MAX_GAP_DAYS: i64 = 7;
+
+/// Fiat currency used for FX conversion of `currency`, stable coins count as USD
+pub fn fx_currency(currency: &Currency) -> Option<&str> {
+    match currency.currency_type() {
+        CurrencyType::Fiat => Some(currency.ticker()),
+        CurrencyType::StableCoin => Some("USD"),
+        CurrencyType::Crypto => None,
+    }
+}
+
+/// Daily ECB reference rates of one currency, in units per 1 EUR
+///
+/// Cached in `<state dir>/fx/ecb-<CURRENCY>.json` together with the fetched
+/// date range, so later reports for the same period don't hit the network.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EcbRates {
+    pub currency: String,
+    /// First and last day of the fetched range, `YYYY-MM-DD`
+    pub from: String,
+    pub to: String,
+    /// Rate by `YYYY-MM-DD` day, which sorts chronologically
+    pub rates: BTreeMap<String, Decimal>,
+}
+
+impl EcbRates {
+    pub fn covers(&self, from: Date, to: Date) -> bool {
+        !self.rates.is_empty() && self.from <= from.to_string() && self.to >= to.to_string()
+    }
+
+    /// Rate of `date`, or the last one published within a week before it
+    pub fn rate_on(&self, date: Date) -> Result<Decimal> {
+        let earliest = (date - Duration::days(MAX_GAP_DAYS)).to_string();
+        self.rates
+            .range(..=date.to_string())
+            .next_back()
+            .filter(|(day, _)| **day >= earliest)
+            .map(|(_, rate)| *rate)
+            .ok_or(anyhow!(
+                "no ECB {} reference rate on {} or the week before",
+                self.currency,
+                date
+            ))
+    }
+}
+
+/// Parse the ECB data API CSV (`TIME_PERIOD` and `OBS_VALUE` columns)
+pub fn parse_ecb_csv(data: &str) -> Result<BTreeMap<String, Decimal>> {
+    let mut reader = csv::Reader::from_reader(data.as_bytes());
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .ok_or(anyhow!("ECB response has no {} column", name))
+    };
+    let (day_idx, value_idx) = (column("TIME_PERIOD")?, column("OBS_VALUE")?);
+
+    let mut rates = BTreeMap::new();
+    for record in reader.records() {
+        let record = record?;
+        let (Some(day), Some(value)) = (record.get(day_idx), record.get(value_idx)) else {
+            continue;
+        };
+        // days without a fixing have an empty value
+        if value.is_empty() {
+            continue;
+        }
+        let rate = Decimal::from_str(value)
+            .with_context(|| format!("invalid ECB rate '{}' on {}", value, day))?;
+        rates.insert(day.to_string(), rate);
+    }
+    Ok(rates)
+}
+
+fn cache_path(currency: &str, settings: &Settings) -> PathBuf {
+    settings
+        .state_dir()
+        .join("fx")
+        .join(format!("ecb-{}.json", currency))
+}
+
+/// ECB rates of `currency` covering `from..=to`, fetched in one request when
+/// the cache doesn't cover the range yet
+pub fn ecb_rates(currency: &str, from: Date, to: Date, settings: &Settings) -> Result<EcbRates> {
+    let path = cache_path(currency, settings);
+    let mut cached = match std::fs::read(&path) {
+        Ok(data) => serde_json::from_slice::<EcbRates>(&data)
+            .with_context(|| format!("parsing {}", path.display()))?,
+        Err(_) => EcbRates {
+            currency: currency.to_string(),
+            ..EcbRates::default()
+        },
+    };
+    if cached.covers(from, to) {
+        return Ok(cached);
+    }
+
+    // fetch the union with the cached range, so the cache stays contiguous
+    // (and a week before `from`, for the rate of a weekend or holiday)
+    let mut start = (from - Duration::days(MAX_GAP_DAYS)).to_string();
+    let mut end = to.to_string();
+    if !cached.rates.is_empty() {
+        start = start.min(cached.from.clone());
+        end = end.max(cached.to.clone());
+    }
+    let url = format!(
+        "{}/D.{}.EUR.SP00.A?format=csvdata&startPeriod={}&endPeriod={}",
+        ECB_URL, currency, start, end
+    );
+    let rates = parse_ecb_csv(&http::get_text(&url)?)
+        .with_context(|| format!("reading ECB {} rates", currency))?;
+    if rates.is_empty() {
+        bail!(
+            "ECB has no {} reference rates for {}..{}",
+            currency,
+            start,
+            end
+        );
+    }
+
+    cached.rates.extend(rates);
+    cached.from = start;
+    cached.to = end;
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(&path, serde_json::to_vec_pretty(&cached)?)?;
+    Ok(cached)
+}
+
+/// Converts fiat amounts at the ECB reference rates of a given day
+#[derive(Debug, Default)]
+pub struct FxConverter {
+    rates: HashMap<String, EcbRates>,
+}
+
+impl FxConverter {
+    /// Load the rates of all `currencies` for the whole range up front, one
+    /// request per currency at most
+    pub fn load(currencies: &[&str], from: Date, to: Date, settings: &Settings) -> Result<Self> {
+        let mut rates = HashMap::new();
+        for currency in currencies.iter().filter(|c| **c != "EUR") {
+            rates.insert(
+                currency.to_string(),
+                ecb_rates(currency, from, to, settings)?,
+            );
+        }
+        Ok(FxConverter { rates })
+    }
+
+    /// Whether no rates are loaded, i.e. nothing needs converting
+    pub fn is_empty(&self) -> bool {
+        self.rates.is_empty()
+    }
+
+    pub fn from_rates(rates: Vec<EcbRates>) -> Self {
+        FxConverter {
+            rates: rates.into_iter().map(|r| (r.currency.clone(), r)).collect(),
+        }
+    }
+
+    fn per_eur(&self, currency: &str, date: Date) -> Result<Decimal> {
+        if currency == "EUR" {
+            return Ok(dec!(1));
+        }
+        self.rates
+            .get(currency)
+            .ok_or(anyhow!("no ECB rates loaded for {}", currency))?
+            .rate_on(date)
+    }
+
+    /// Convert `amount` of fiat `from` into `to`, crossing through EUR
+    pub fn convert(&self, amount: Decimal, from: &str, to: &str, date: Date) -> Result<Decimal> {
+        if from == to {
+            return Ok(amount);
+        }
+        Ok(amount / self.per_eur(from, date)? * self.per_eur(to, date)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::create_test_settings;
+    use tempfile::TempDir;
+    use time::macros::date;
+
+    const ECB_CSV: &str =
+        "KEY,FREQ,CURRENCY,CURRENCY_DENOM,EXR_TYPE,EXR_SUFFIX,TIME_PERIOD,OBS_VALUE,OBS_STATUS
+EXR.D.USD.EUR.SP00.A,D,USD,EUR,SP00,A,2024-01-04,1.0953,A
+EXR.D.USD.EUR.SP00.A,D,USD,EUR,SP00,A,2024-01-05,1.0921,A
+EXR.D.USD.EUR.SP00.A,D,USD,EUR,SP00,A,2024-01-08,,M
+EXR.D.USD.EUR.SP00.A,D,USD,EUR,SP00,A,2024-01-09,1.0940,A
+";
+
+    fn usd_rates() -> EcbRates {
+        EcbRates {
+            currency: "USD".to_string(),
+            from: "2024-01-01".to_string(),
+            to: "2024-01-09".to_string(),
+            rates: parse_ecb_csv(ECB_CSV).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_parse_ecb_csv_skips_days_without_value() {
+        let rates = parse_ecb_csv(ECB_CSV).unwrap();
+        assert_eq!(rates.len(), 3);
+        assert_eq!(rates["2024-01-05"], dec!(1.0921));
+        assert!(parse_ecb_csv("DAY,VALUE\n").is_err());
+    }
+
+    #[test]
+    fn test_rate_on_uses_last_published_rate() {
+        let rates = usd_rates();
+        assert_eq!(rates.rate_on(date!(2024 - 01 - 04)).unwrap(), dec!(1.0953));
+        // weekend and a missing fixing fall back to friday
+        assert_eq!(rates.rate_on(date!(2024 - 01 - 07)).unwrap(), dec!(1.0921));
+        assert_eq!(rates.rate_on(date!(2024 - 01 - 08)).unwrap(), dec!(1.0921));
+        assert!(rates.rate_on(date!(2024 - 01 - 03)).is_err());
+        assert!(rates.rate_on(date!(2024 - 02 - 01)).is_err());
+    }
+
+    #[test]
+    fn test_convert_crosses_through_eur() {
+        let cad = EcbRates {
+            currency: "CAD".to_string(),
+            from: "2024-01-01".to_string(),
+            to: "2024-01-09".to_string(),
+            rates: BTreeMap::from([("2024-01-05".to_string(), dec!(1.4587))]),
+        };
+        let fx = FxConverter::from_rates(vec![usd_rates(), cad]);
+        let day = date!(2024 - 01 - 05);
+
+        assert_eq!(
+            fx.convert(dec!(1092.1), "USD", "EUR", day).unwrap(),
+            dec!(1000)
+        );
+        assert_eq!(
+            fx.convert(dec!(1000), "EUR", "USD", day).unwrap(),
+            dec!(1092.1)
+        );
+        assert_eq!(
+            fx.convert(dec!(1092.1), "USD", "CAD", day).unwrap(),
+            dec!(1458.7)
+        );
+        assert_eq!(fx.convert(dec!(5), "USD", "USD", day).unwrap(), dec!(5));
+    }
+
+    #[test]
+    fn test_ecb_rates_reads_covering_cache_without_fetching() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        let path = cache_path("USD", &settings);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, serde_json::to_vec(&usd_rates()).unwrap()).unwrap();
+
+        let rates = ecb_rates(
+            "USD",
+            date!(2024 - 01 - 04),
+            date!(2024 - 01 - 09),
+            &settings,
+        );
+        assert_eq!(rates.unwrap(), usd_rates());
+        assert!(!usd_rates().covers(date!(2023 - 12 - 31), date!(2024 - 01 - 09)));
+    }
+}
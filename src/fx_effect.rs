@@ -0,0 +1,264 @@
+use crate::currency::{Currency, CurrencyType};
+use crate::fx::{FxConverter, fx_currency};
+use crate::quote::quote_in_base;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{Side, Trade, parse_csv_file, sort_chronologically};
+use anyhow::{Result, anyhow, bail};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use thousands::Separable;
+use time::Date;
+
+/// Crypto and stable coins are priced in USD, so that's the currency whose
+/// moves against the base make up the FX effect. Stable coins are valued at
+/// their USD peg.
+const PRICING_CURRENCY: &str = "USD";
+
+/// PnL of one held asset split into the price move and the currency move
+#[derive(Debug, Clone, PartialEq)]
+pub struct FxSplit {
+    pub asset: String,
+    pub quantity: Decimal,
+    /// Current value in the base currency
+    pub value: Decimal,
+    /// Cost in the base currency, at the FX rates of the trade dates
+    pub cost: Decimal,
+    /// Cost in USD, at the FX rates of the trade dates
+    pub cost_usd: Decimal,
+    /// Price move in USD, converted at today's rate
+    pub asset_pnl: Decimal,
+    /// What the USD/base move since the trades added to (or took from) the PnL
+    pub fx_pnl: Decimal,
+}
+
+/// (quantity, cost in base, cost in USD)
+type Held = (Decimal, Decimal, Decimal);
+
+fn reduce(held: &mut Held, amount: Decimal) {
+    let (quantity, cost, cost_usd) = held;
+    if quantity.is_zero() {
+        return;
+    }
+    let amount = amount.min(*quantity);
+    *cost -= *cost * amount / *quantity;
+    *cost_usd -= *cost_usd * amount / *quantity;
+    *quantity -= amount;
+}
+
+/// Split the PnL of the crypto and stable coin holdings of a fiat-based
+/// portfolio into asset-price PnL and FX PnL
+///
+/// Holdings are tracked at average cost in both the base currency and USD,
+/// each trade converted at the ECB rate of its date. The price move is the
+/// USD value today over the USD cost; the rest of the PnL in the base
+/// currency is the effect of USD moving against the base.
+pub fn split(
+    trades: &[Trade],
+    base: &Currency,
+    fx: &FxConverter,
+    today: Date,
+    mut price_of: impl FnMut(&Currency) -> Result<Decimal>,
+) -> Result<Vec<FxSplit>> {
+    let base_fx = base.ticker();
+    let mut trades: Vec<&Trade> = trades.iter().collect();
+    sort_chronologically(&mut trades);
+
+    // by ticker, Currency isn't Ord
+    let mut held: BTreeMap<String, Held> = BTreeMap::new();
+    let tracked = |c: &Currency| c.currency_type() != CurrencyType::Fiat;
+    for t in trades {
+        let day = t.created_at.date();
+        // amount of `currency` in the base currency and in USD on the trade date
+        let costs = |amount: Decimal, currency: &Currency| -> Result<(Decimal, Decimal)> {
+            let from = fx_currency(currency).ok_or(anyhow!(
+                "{} {} is valued in crypto, only fiat or stable coin quotes can be converted",
+                t.side,
+                t.pair
+            ))?;
+            Ok((
+                fx.convert(amount, from, base_fx, day)?,
+                fx.convert(amount, from, PRICING_CURRENCY, day)?,
+            ))
+        };
+        match t.side {
+            Side::Buy | Side::Sell => {
+                let pair = t.pair.trading_pair(t.side)?;
+                let (bought, spent, got, paid) = if t.side == Side::Buy {
+                    let paid = t.amount * t.price + t.fee;
+                    (&pair.base, &pair.quote, t.amount, paid)
+                } else {
+                    let got = t.amount * t.price - t.fee;
+                    (&pair.quote, &pair.base, got, t.amount)
+                };
+                if tracked(bought) {
+                    let value = if t.side == Side::Buy { paid } else { got };
+                    let (cost, cost_usd) = costs(value, &pair.quote)?;
+                    let h = held.entry(bought.to_string()).or_default();
+                    h.0 += got;
+                    h.1 += cost;
+                    h.2 += cost_usd;
+                }
+                if let Some(h) = held.get_mut(&spent.to_string()) {
+                    reduce(h, paid);
+                }
+            }
+            Side::Deposit | Side::Income => {
+                let asset = t.pair.asset(t.side)?;
+                if tracked(asset) {
+                    // deposits and income are valued in the base currency
+                    let (cost, cost_usd) = costs(t.amount * t.price, base)?;
+                    let h = held.entry(asset.to_string()).or_default();
+                    h.0 += t.amount;
+                    h.1 += cost;
+                    h.2 += cost_usd;
+                }
+            }
+            Side::Withdraw | Side::Fee => {
+                if let Some(h) = held.get_mut(&t.pair.asset(t.side)?.to_string()) {
+                    reduce(h, t.amount);
+                }
+            }
+        }
+    }
+
+    let mut splits = Vec::new();
+    for (asset, (quantity, cost, cost_usd)) in held {
+        if quantity.is_zero() {
+            continue;
+        }
+        let currency = Currency::new(&asset)?;
+        // stable coins are valued at their peg, quotes only cover crypto
+        let value = if currency.currency_type() == CurrencyType::StableCoin {
+            fx.convert(quantity, PRICING_CURRENCY, base_fx, today)?
+        } else {
+            quantity * price_of(&currency)?
+        };
+        let value_usd = fx.convert(value, base_fx, PRICING_CURRENCY, today)?;
+        let asset_pnl = fx.convert(value_usd - cost_usd, PRICING_CURRENCY, base_fx, today)?;
+        splits.push(FxSplit {
+            asset,
+            quantity,
+            value,
+            cost,
+            cost_usd,
+            asset_pnl,
+            fx_pnl: value - cost - asset_pnl,
+        });
+    }
+    Ok(splits)
+}
+
+/// `report --fx`: asset-price PnL next to the FX PnL of every holding
+pub fn report(name: &str, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let (config, trades) = parse_csv_file(settings.path_for(name))?;
+    let base = Currency::new(&config.base_currency)?;
+    if base.currency_type() != CurrencyType::Fiat || base.ticker() == PRICING_CURRENCY {
+        bail!(
+            "the FX effect needs a fiat base other than {}, '{}' is based in {}",
+            PRICING_CURRENCY,
+            name,
+            base
+        );
+    }
+    let Some(first) = trades.iter().map(|t| t.created_at).min() else {
+        reporter.print("\nNo trades, no FX effect");
+        return Ok(());
+    };
+
+    let today = settings.clock.today();
+    let mut currencies = vec![PRICING_CURRENCY, base.ticker()];
+    for t in &trades {
+        if let Ok(pair) = t.pair.trading_pair(t.side)
+            && let Some(c) = fx_currency(&pair.quote)
+            && !currencies.contains(&c)
+        {
+            currencies.push(c);
+        }
+    }
+    let fx = FxConverter::load(&currencies, first.date(), today, settings)?;
+    let splits = split(&trades, &base, &fx, today, |c| {
+        quote_in_base(c, base.ticker())
+    })?;
+
+    let money = |d: Decimal| {
+        format!(
+            "{} {}",
+            settings
+                .rounding
+                .cents(d)
+                .normalize()
+                .separate_with_commas(),
+            base
+        )
+    };
+    reporter.print(&format!("\nFX effect ({}/{}):", PRICING_CURRENCY, base));
+    let mut table = Table::new();
+    table.add_row(row!["Asset", "Value", "Cost", "Asset PnL", "FX PnL"]);
+    for s in &splits {
+        table.add_row(row![
+            s.asset,
+            r->money(s.value),
+            r->money(s.cost),
+            r->money(s.asset_pnl),
+            r->money(s.fx_pnl)
+        ]);
+    }
+    reporter.print(table.to_string().trim_end());
+    let total = |f: fn(&FxSplit) -> Decimal| splits.iter().map(f).sum::<Decimal>();
+    reporter.print(&format!("Asset PnL:\t{}", money(total(|s| s.asset_pnl))));
+    reporter.print(&format!("FX PnL:\t\t{}", money(total(|s| s.fx_pnl))));
+    reporter.print("Costs at the ECB reference rates of each trade date, values at today's");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fx::EcbRates;
+    use crate::test_utils::helpers::transactions_from;
+    use rust_decimal::dec;
+    use time::macros::date;
+
+    #[test]
+    fn test_split_separates_price_and_currency_moves() {
+        // 1.25 USD per EUR in january, 1.00 today
+        let fx = FxConverter::from_rates(vec![EcbRates {
+            currency: "USD".to_string(),
+            from: "2024-01-01".to_string(),
+            to: "2024-06-30".to_string(),
+            rates: [
+                ("2024-01-10".to_string(), dec!(1.25)),
+                ("2024-06-28".to_string(), dec!(1.00)),
+            ]
+            .into(),
+        }]);
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704883200,USDT/EUR,BUY,1000,0.8,0
+1704883200,BTC/USDT,BUY,0.01,40000,0
+",
+        );
+        let eur = Currency::new("EUR").unwrap();
+        // BTC at 50000 USD, which are EUR as well at 1.00
+        let splits = split(&trades, &eur, &fx, date!(2024 - 06 - 30), |_| {
+            Ok(dec!(50000))
+        })
+        .unwrap();
+
+        let btc = &splits[0];
+        assert_eq!(btc.asset, "BTC");
+        assert_eq!((btc.cost_usd, btc.value), (dec!(400), dec!(500)));
+        // 400 USD cost 320 EUR, the 100 USD price gain is 100 EUR today
+        assert_eq!(
+            (btc.cost, btc.asset_pnl, btc.fx_pnl),
+            (dec!(320), dec!(100), dec!(80))
+        );
+
+        // 600 USDT left, bought at 0.80 EUR: all of its PnL is currency
+        let usdt = &splits[1];
+        assert_eq!((usdt.quantity, usdt.cost), (dec!(600), dec!(480)));
+        assert_eq!((usdt.asset_pnl, usdt.fx_pnl), (dec!(0), dec!(120)));
+    }
+}
@@ -0,0 +1,231 @@
+//! Value history of a portfolio
+//!
+//! The history is the series of snapshots taken by `snapshot` and `watch`.
+//! For the time before the first snapshot, `history --reconstruct` rebuilds
+//! daily values from the trades and historical prices: the holdings at each
+//! midnight UTC valued at that midnight's price, like a snapshot would have.
+
+use crate::chart;
+use crate::currency::Currency;
+use crate::format::{OutputFormat, Records, iso_utc};
+use crate::interrupt;
+use crate::math;
+use crate::portfolio::Portfolio;
+use crate::price_history;
+use crate::quote::Prices;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::snapshot::{self, Snapshot};
+use crate::trade::parse_csv_file;
+use anyhow::Result;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, BTreeSet};
+use thousands::Separable;
+use time::{Date, Duration, OffsetDateTime};
+
+/// Rows of the chart of `history`
+const CHART_HEIGHT: usize = 10;
+
+/// Historical daily prices of one day, see `price_history::price_on`
+struct PricesOn<'a> {
+    day: Date,
+    settings: &'a Settings,
+}
+
+impl Prices for PricesOn<'_> {
+    fn price_in(&self, currency: &Currency, base: &str) -> Result<Decimal> {
+        price_history::price_on(currency, base, self.day, self.settings)
+    }
+}
+
+/// Last value of each day, oldest first
+pub fn daily(snapshots: &[Snapshot]) -> Vec<(Date, Decimal)> {
+    let mut days = BTreeMap::new();
+    for s in snapshots {
+        if let Ok(at) = OffsetDateTime::from_unix_timestamp(s.ts) {
+            days.insert(at.date(), s.value);
+        }
+    }
+    days.into_iter().collect()
+}
+
+/// Rebuild the daily values of the last `days` days before the first
+/// snapshot from the trades, keeping the snapshots; returns how many were added
+pub fn reconstruct(name: &str, days: u32, settings: &Settings) -> Result<usize> {
+    let path = settings.path_for(name);
+    let (_, trades) = parse_csv_file(&path)?;
+    let Some(first_trade) = trades.iter().map(|t| t.created_at.date()).min() else {
+        return Ok(0);
+    };
+    let snapshots = snapshot::load(name, settings)?;
+    let until = snapshots
+        .first()
+        .map_or(i64::MAX, |s| s.ts)
+        .min(settings.clock.now().unix_timestamp() + 1);
+    let trade_days: BTreeSet<Date> = trades.iter().map(|t| t.created_at.date()).collect();
+
+    // holdings at midnight are those of the trades of the days before
+    let mut day = first_trade.next_day().unwrap_or(first_trade);
+    day = day.max(settings.clock.now().date() - Duration::days(days as i64));
+    let latest_trade_day = |day: Date| {
+        let previous = day.previous_day().unwrap_or(day);
+        (previous, trade_days.range(..=previous).next_back().copied())
+    };
+    let (previous, mut last_trade_day) = latest_trade_day(day);
+    let mut pf = Portfolio::from_csv_as_of(&path, Some(previous))?;
+    let mut rebuilt = Vec::new();
+    while midnight(day) < until {
+        interrupt::check()?;
+        let (previous, latest) = latest_trade_day(day);
+        if latest != last_trade_day {
+            pf = Portfolio::from_csv_as_of(&path, Some(previous))?;
+            last_trade_day = latest;
+        }
+        let value = pf.market_value(&PricesOn { day, settings })?;
+        rebuilt.push(Snapshot {
+            ts: midnight(day),
+            value,
+        });
+        day = day.next_day().unwrap_or(day);
+    }
+
+    let added = rebuilt.len();
+    if added > 0 {
+        rebuilt.extend(snapshots);
+        snapshot::replace(name, &rebuilt, settings)?;
+    }
+    Ok(added)
+}
+
+fn midnight(day: Date) -> i64 {
+    day.midnight().assume_utc().unix_timestamp()
+}
+
+/// `history`: chart of the daily values of a portfolio, rebuilt from its
+/// trades first with `reconstruct`
+pub fn run(
+    name: &str,
+    reconstruct_days: Option<u32>,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    if let Some(days) = reconstruct_days {
+        let added = reconstruct(name, days, settings)?;
+        reporter.status(&format!(
+            "Reconstructed {} daily values of {} from its trades",
+            added, name
+        ));
+    }
+    let snapshots = snapshot::load(name, settings)?;
+    if snapshots.is_empty() {
+        reporter.print(&format!(
+            "No history of {} yet, take snapshots with `snapshot` or `watch`, \
+             or rebuild it from the trades with --reconstruct",
+            name
+        ));
+        return Ok(());
+    }
+    print_chart(&snapshots, reporter, settings)
+}
+
+/// `history --all`: chart of the daily total of all portfolios, in the
+/// `base_currency` of the config
+pub fn run_total(reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let snapshots = snapshot::load_total(settings)?;
+    if snapshots.is_empty() {
+        reporter.print(
+            "No workspace history yet, take snapshots of all portfolios with `snapshot` or `watch`",
+        );
+        return Ok(());
+    }
+    print_chart(&snapshots, reporter, settings)
+}
+
+/// The chart of the daily values, their days and values in machine output
+fn print_chart(snapshots: &[Snapshot], reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let days = daily(snapshots);
+    if reporter.format(OutputFormat::Table) != OutputFormat::Table {
+        let mut records = Records::new(&["day", "value"]);
+        for (day, value) in &days {
+            records.push(vec![day.to_string().into(), (*value).into()]);
+        }
+        return reporter.records(&records, OutputFormat::Table, &iso_utc);
+    }
+    let (Some((first_day, first)), Some((last_day, last))) = (days.first(), days.last()) else {
+        return Ok(());
+    };
+    let values: Vec<Decimal> = days.iter().map(|(_, value)| *value).collect();
+    reporter.print(chart::line(&values, CHART_HEIGHT).trim_end());
+    let fmt = |d: Decimal| settings.rounding.cents(d).separate_with_commas();
+    let change = math::change_pct(*first, *last).map_or(String::new(), |p| format!(" ({:+.2})", p));
+    reporter.print(&format!(
+        "{}: {} → {}: {}{}, {} days",
+        first_day,
+        fmt(*first),
+        last_day,
+        fmt(*last),
+        change,
+        days.len()
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use rust_decimal::dec;
+    use tempfile::TempDir;
+    use time::macros::{date, datetime};
+
+    #[test]
+    fn test_reconstruct_values_holdings_of_each_midnight_before_the_first_snapshot() {
+        let dir = TempDir::new().unwrap();
+        let settings = Settings {
+            clock: Clock::Frozen(datetime!(2024-01-06 12:00 UTC)),
+            ..(*create_test_settings(dir.path().to_path_buf())).clone()
+        };
+        create_test_csv(
+            &dir,
+            "main",
+            "created_at,pair,side,amount,price,fee
+1704108000,BTC/USD,BUY,1,40000,0
+1704294000,BTC/USD,BUY,1,45000,0
+",
+        );
+        let prices = dir.path().join(".csvpt/prices");
+        std::fs::create_dir_all(&prices).unwrap();
+        std::fs::write(
+            prices.join("BTC-USD.json"),
+            r#"{"2024-01-02": 41000, "2024-01-03": 42000, "2024-01-04": 46000,
+                "2024-01-05": 44000}"#,
+        )
+        .unwrap();
+        // the first snapshot, taken on the 5th at noon
+        snapshot::record(
+            "main",
+            Snapshot {
+                ts: 1704456000,
+                value: dec!(90000),
+            },
+            &settings,
+        )
+        .unwrap();
+
+        assert_eq!(reconstruct("main", 30, &settings).unwrap(), 4);
+        let values: Vec<(Date, Decimal)> = daily(&snapshot::load("main", &settings).unwrap());
+        assert_eq!(
+            values,
+            vec![
+                (date!(2024 - 01 - 02), dec!(41000)),
+                (date!(2024 - 01 - 03), dec!(42000)),
+                (date!(2024 - 01 - 04), dec!(92000)),
+                // the 5th ends with the snapshot
+                (date!(2024 - 01 - 05), dec!(90000)),
+            ]
+        );
+        // the days before the first snapshot are there now
+        assert_eq!(reconstruct("main", 30, &settings).unwrap(), 0);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::currency::Currency;
+use crate::format::{Field, OutputFormat, Records};
+use crate::index;
+use crate::portfolio::Portfolio;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{CsvConfig, PortfolioKind, extract_csv_config, trades_reader};
+use anyhow::{Result, bail};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub static HOLDINGS_HEADER: [&str; 3] = ["ticker", "qty", "cost"];
+
+/// Current amount of one asset in a holdings-only portfolio
+///
+/// Holdings-only portfolios keep no trade history, e.g. for a cold storage
+/// wallet which isn't worth reconstructing:
+/// ```csv
+/// # base_currency: USD
+/// # kind: holdings
+/// ticker,qty,cost
+/// BTC,1.5,30000
+/// ETH,10,
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Holding {
+    pub ticker: Currency,
+    pub qty: Decimal,
+    /// Total cost in the base currency, empty when unknown
+    #[serde(default)]
+    pub cost: Option<Decimal>,
+}
+
+/// Read a holdings-only portfolio file
+pub fn load<T: AsRef<Path>>(path: T) -> Result<(CsvConfig, Vec<Holding>)> {
+    let input_data = std::fs::read_to_string(path)?;
+    let (config, data) = extract_csv_config(input_data.as_str())?;
+    if config.kind != PortfolioKind::Holdings {
+        bail!("not a holdings-only portfolio (missing '# kind: holdings')");
+    }
+    let mut reader = trades_reader(data.as_bytes());
+    let holdings: Vec<Holding> = reader.deserialize().collect::<Result<_, csv::Error>>()?;
+    for h in &holdings {
+        if h.qty <= dec!(0) {
+            bail!("{} quantity must be positive, got {}", h.ticker, h.qty);
+        }
+        if h.cost.is_some_and(|c| c < dec!(0)) {
+            bail!("{} cost can't be negative", h.ticker);
+        }
+    }
+    Ok((config, holdings))
+}
+
+/// Write a complete holdings file, one row per ticker in alphabetical order
+fn write<T: AsRef<Path>>(path: T, config: &CsvConfig, holdings: &[Holding]) -> Result<()> {
+    let path = path.as_ref();
+    let tmp = path.with_extension("csv.tmp");
+    {
+        let mut file = std::fs::File::create(&tmp)?;
+        config.write_to(&mut file)?;
+        let mut wtr = csv::Writer::from_writer(file);
+        wtr.write_record(HOLDINGS_HEADER)?;
+        let mut sorted: Vec<&Holding> = holdings.iter().collect();
+        sorted.sort_by_key(|h| h.ticker.ticker());
+        for h in sorted {
+            wtr.write_record([
+                h.ticker.to_string(),
+                h.qty.normalize().to_string(),
+                h.cost
+                    .map(|c| c.normalize().to_string())
+                    .unwrap_or_default(),
+            ])?;
+        }
+        wtr.flush()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Holdings as portfolio positions, with their cost as cost base
+pub fn to_portfolio(config: &CsvConfig, holdings: &[Holding]) -> Result<Portfolio> {
+    let mut pf = Portfolio::new();
+    pf.base = Currency::new(&config.base_currency)?;
+    for h in holdings {
+        pf.deposit_at_cost(h.ticker.clone(), h.qty, h.cost.unwrap_or_default());
+        if h.cost.is_none() {
+            pf.unknown_cost.insert(h.ticker.clone());
+        }
+    }
+    Ok(pf)
+}
+
+/// Set the holding of `ticker` in a holdings-only portfolio, a zero quantity removes it
+pub fn hold(
+    name: &str,
+    ticker: &str,
+    qty: Decimal,
+    cost: Option<Decimal>,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let path = settings.path_for(name);
+    let (config, mut holdings) = load(&path)?;
+    config.ensure_open()?;
+    let ticker = Currency::new(ticker)?;
+    if qty < dec!(0) {
+        bail!("quantity can't be negative, got {}", qty);
+    }
+    if cost.is_some_and(|c| c < dec!(0)) {
+        bail!("cost can't be negative");
+    }
+
+    holdings.retain(|h| h.ticker != ticker);
+    if qty > dec!(0) {
+        holdings.push(Holding {
+            ticker: ticker.clone(),
+            qty,
+            cost,
+        });
+    }
+    write(&path, &config, &holdings)?;
+    index::record_write(name, settings);
+
+    if qty > dec!(0) {
+        reporter.status(&format!(
+            "✅ Set {} {} in holdings portfolio '{}'",
+            qty, ticker, name
+        ));
+    } else {
+        reporter.status(&format!(
+            "✅ Removed {} from holdings portfolio '{}'",
+            ticker, name
+        ));
+    }
+    Ok(())
+}
+
+/// Print the holdings table, used by `show`
+pub fn print_table<T: AsRef<Path>>(
+    path: T,
+    format: OutputFormat,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let format = reporter.format(format);
+    let (config, holdings) = load(path)?;
+    if holdings.is_empty() && format == OutputFormat::Table {
+        reporter.print("No holdings found");
+        return Ok(());
+    }
+    let mut records = Records::new(&["ticker", "qty", "cost"]);
+    for h in &holdings {
+        let cost: Field = match h.cost {
+            Some(c) if format == OutputFormat::Table => {
+                format!("{} {}", c, config.base_currency).into()
+            }
+            Some(c) => c.into(),
+            None if format == OutputFormat::Table => "-".into(),
+            None => "".into(),
+        };
+        records.push(vec![h.ticker.to_string().into(), h.qty.into(), cost]);
+    }
+    reporter.records(&records, format, &|ts| ts.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::Buffer;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use tempfile::TempDir;
+
+    const HOLDINGS: &str = "# base_currency: EUR
+# kind: holdings
+ticker,qty,cost
+btc , 1.5 , 30000
+ETH,10,
+";
+
+    #[test]
+    fn test_to_portfolio_marks_holdings_without_cost() {
+        let dir = TempDir::new().unwrap();
+        let path = create_test_csv(&dir, "cold", HOLDINGS);
+        let (config, holdings) = load(&path).unwrap();
+        let pf = to_portfolio(&config, &holdings).unwrap();
+
+        let btc = Currency::new("BTC").unwrap();
+        let eth = Currency::new("ETH").unwrap();
+        assert_eq!(pf.base, Currency::new("EUR").unwrap());
+        assert_eq!(pf.balance(&btc), dec!(1.5));
+        assert_eq!(pf.positions[&btc].cost_base, dec!(30000));
+        assert_eq!(pf.balance(&eth), dec!(10));
+        assert!(pf.unknown_cost.contains(&eth) && !pf.unknown_cost.contains(&btc));
+    }
+
+    #[test]
+    fn test_set_updates_and_removes_holdings() {
+        let dir = TempDir::new().unwrap();
+        let path = create_test_csv(&dir, "cold", HOLDINGS);
+        let settings = create_test_settings(dir.path().to_path_buf());
+
+        let reporter = Buffer::default();
+        hold(
+            "cold",
+            "sol",
+            dec!(20),
+            Some(dec!(2000.50)),
+            &reporter,
+            &settings,
+        )
+        .unwrap();
+        hold("cold", "BTC", dec!(2), None, &reporter, &settings).unwrap();
+        hold("cold", "eth", dec!(0), None, &reporter, &settings).unwrap();
+        assert!(
+            reporter
+                .text()
+                .ends_with("✅ Removed ETH from holdings portfolio 'cold'\n")
+        );
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# base_currency: EUR
+# kind: holdings
+ticker,qty,cost
+BTC,2,
+SOL,20,2000.5
+"
+        );
+    }
+
+    #[test]
+    fn test_load_rejects_trades_file_and_bad_rows() {
+        let dir = TempDir::new().unwrap();
+        let trades = create_test_csv(&dir, "trades", "created_at,pair,side,amount,price,fee\n");
+        assert!(load(&trades).is_err());
+
+        let bad = create_test_csv(&dir, "bad", "# kind: holdings\nticker,qty,cost\nBTC,-1,\n");
+        assert!(load(&bad).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+use crate::interrupt;
+use anyhow::{Context, Result, anyhow, bail};
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TIMEOUT_SECS: u64 = 10;
+
+/// Set once from `Settings::http_timeout_secs` at startup
+static TIMEOUT_SECS: AtomicU64 = AtomicU64::new(DEFAULT_TIMEOUT_SECS);
+
+/// End of the time all requests of this run may take together, and that
+/// time, from `--timeout`
+static DEADLINE: Mutex<Option<(Instant, Duration)>> = Mutex::new(None);
+
+/// Why requests are refused this run, set by `--fixture-prices`
+static DISABLED: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn set_timeout_secs(secs: u64) {
+    TIMEOUT_SECS.store(secs, Ordering::Relaxed);
+}
+
+/// Give up on requests once `total` has passed from now
+pub fn set_deadline(total: Duration) {
+    *DEADLINE.lock().unwrap() = Some((Instant::now() + total, total));
+}
+
+/// Refuse all requests of this run, naming `reason` in the error
+pub fn disable(reason: String) {
+    *DISABLED.lock().unwrap() = Some(reason);
+}
+
+fn timeout() -> Duration {
+    Duration::from_secs(TIMEOUT_SECS.load(Ordering::Relaxed))
+}
+
+/// Timeout of the next request: the configured one, cut to what is left
+/// until the deadline
+fn budget(
+    timeout: Duration,
+    deadline: Option<(Instant, Duration)>,
+    now: Instant,
+) -> Result<Duration> {
+    match deadline {
+        None => Ok(timeout),
+        Some((end, total)) => {
+            let left = end.saturating_duration_since(now);
+            if left.is_zero() {
+                bail!(
+                    "network requests stopped, they took longer than --timeout {}s",
+                    total.as_secs()
+                );
+            }
+            Ok(timeout.min(left))
+        }
+    }
+}
+
+/// Blocking client shared by quote fetching and the ticker table importer
+///
+/// Requests time out after the configured `http_timeout_secs`. A proxy from
+/// the standard `HTTPS_PROXY` env var (or `https_proxy`) is used for https
+/// requests, honouring `NO_PROXY`.
+pub fn client() -> Result<Client> {
+    client_within(timeout())
+}
+
+/// `client` with requests timing out after `timeout` instead of the
+/// configured one
+fn client_within(timeout: Duration) -> Result<Client> {
+    interrupt::check()?;
+    if let Some(reason) = DISABLED.lock().unwrap().as_ref() {
+        bail!("network requests are disabled by {}", reason);
+    }
+    let timeout = budget(timeout, *DEADLINE.lock().unwrap(), Instant::now())?;
+    build_client(timeout, https_proxy_from_env())
+}
+
+fn https_proxy_from_env() -> Option<String> {
+    ["HTTPS_PROXY", "https_proxy"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok())
+        .filter(|v| !v.trim().is_empty())
+}
+
+fn build_client(timeout: Duration, https_proxy: Option<String>) -> Result<Client> {
+    let mut builder = Client::builder().timeout(timeout).connect_timeout(timeout);
+    if let Some(url) = https_proxy {
+        let proxy = reqwest::Proxy::https(&url)
+            .with_context(|| format!("invalid HTTPS_PROXY url '{}'", url))?
+            .no_proxy(reqwest::NoProxy::from_env());
+        builder = builder.proxy(proxy);
+    }
+    builder.build().context("building http client")
+}
+
+fn to_err(e: reqwest::Error, timeout: Duration) -> anyhow::Error {
+    if e.is_timeout() {
+        let url = e.url().map(|u| u.to_string()).unwrap_or_default();
+        if let Some((end, total)) = *DEADLINE.lock().unwrap()
+            && Instant::now() >= end
+        {
+            return anyhow!(
+                "request to {} stopped, network requests took longer than --timeout {}s",
+                url,
+                total.as_secs()
+            );
+        }
+        anyhow!(
+            "request to {} timed out after {}s (increase `http_timeout_secs` in the config or LPT_HTTP_TIMEOUT_SECS)",
+            url,
+            timeout.as_secs()
+        )
+    } else {
+        anyhow::Error::from(e)
+    }
+}
+
+/// Send the request and decode the JSON response body
+pub fn send_json<T: DeserializeOwned>(request: RequestBuilder) -> Result<T> {
+    send_json_within(request, timeout())
+}
+
+/// `send_json` of a request built by a client timing out after `timeout`
+fn send_json_within<T: DeserializeOwned>(request: RequestBuilder, timeout: Duration) -> Result<T> {
+    request
+        .send()
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| to_err(e, timeout))?
+        .json::<T>()
+        .map_err(|e| to_err(e, timeout))
+}
+
+/// GET the url and decode the JSON response body
+pub fn get_json<T: DeserializeOwned>(url: &str) -> Result<T> {
+    get_json_within(url, timeout())
+}
+
+fn get_json_within<T: DeserializeOwned>(url: &str, timeout: Duration) -> Result<T> {
+    send_json_within(client_within(timeout)?.get(url), timeout)
+}
+
+/// GET the url and return the response body as text, e.g. CSV data
+pub fn get_text(url: &str) -> Result<String> {
+    client()?
+        .get(url)
+        .send()
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.text())
+        .map_err(|e| to_err(e, timeout()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_unresponsive_server_times_out_with_clear_error() {
+        // accepts connections but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/price", listener.local_addr().unwrap());
+
+        let err = get_json_within::<serde_json::Value>(&url, Duration::from_secs(1)).unwrap_err();
+
+        assert!(err.to_string().contains("timed out after 1s"), "got: {err}");
+        drop(listener);
+    }
+
+    #[test]
+    fn test_deadline_cuts_request_timeouts_then_stops_requests() {
+        let now = Instant::now();
+        let ten = Duration::from_secs(10);
+        assert_eq!(budget(ten, None, now).unwrap(), ten);
+
+        let deadline = Some((now + Duration::from_secs(3), Duration::from_secs(60)));
+        assert_eq!(budget(ten, deadline, now).unwrap(), Duration::from_secs(3));
+        assert_eq!(
+            budget(Duration::from_secs(1), deadline, now).unwrap(),
+            Duration::from_secs(1)
+        );
+        let err = budget(ten, deadline, now + Duration::from_secs(4)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "network requests stopped, they took longer than --timeout 60s"
+        );
+    }
+
+    #[test]
+    fn test_invalid_proxy_url_is_reported() {
+        let err = build_client(timeout(), Some("not a url".to_string())).unwrap_err();
+        assert!(err.to_string().contains("invalid HTTPS_PROXY url"));
+    }
+
+    #[test]
+    fn test_https_proxy_is_accepted() {
+        assert!(build_client(timeout(), Some("http://proxy.local:3128".to_string())).is_ok());
+    }
+}
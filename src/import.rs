@@ -0,0 +1,847 @@
+use crate::confirm::{self, confirm, confirm_preview};
+use crate::currency::{Currency, CurrencyType};
+use crate::import_profile::{self, Profile};
+use crate::index;
+use crate::interrupt;
+use crate::portfolio;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{
+    CSV_HEADER, CsvConfig, Instrument, PortfolioKind, Side, Trade, ensure_portfolio_file,
+    extract_csv_config, parse_csv_records, sort_records_chronologically, trades_reader,
+    write_csv_records,
+};
+use crate::universal::{self, Format};
+use crate::warnings;
+use crate::xlsx;
+use anyhow::{Context, Result, anyhow, bail};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Rows of one export file, parsed on a worker thread
+#[derive(Debug)]
+struct ParsedFile {
+    trades: Vec<Trade>,
+    /// Rows that couldn't be parsed, as `line N: reason`
+    failed: Vec<String>,
+    /// Rows whose total doesn't match their numbers, as `line N: reason`
+    mismatched: Vec<String>,
+}
+
+/// Outcome of importing one export file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileReport {
+    pub file: PathBuf,
+    pub imported: usize,
+    /// Rows already in the portfolio, or in an earlier file
+    pub skipped: usize,
+    /// Failed rows as `line N: reason`, or the reason the whole file failed
+    pub failed: Vec<String>,
+    /// Imported rows whose total column doesn't match, see `reconcile`
+    pub mismatched: Vec<String>,
+}
+
+/// Names of the optional column with the exchange's total of a row
+const TOTAL_COLUMNS: [&str; 2] = ["total", "cost"];
+
+/// Why the exchange's `total` of a buy or sell doesn't match its amount ×
+/// price, with or without the fee, by more than `tolerance`; exchanges
+/// differ in whether the fee is included and in the sign of sells
+fn reconcile(trade: &Trade, total: &str, tolerance: Decimal) -> Result<Option<String>> {
+    let total = Decimal::from_str_exact(total).map_err(|_| anyhow!("invalid total '{}'", total))?;
+    let gross = trade.amount * trade.price;
+    let net = match trade.side {
+        Side::Buy => gross + trade.fee,
+        Side::Sell => gross - trade.fee,
+        _ => return Ok(None),
+    };
+    let off = (total.abs() - net).abs().min((total.abs() - gross).abs());
+    if off <= tolerance {
+        return Ok(None);
+    }
+    Ok(Some(format!(
+        "total {} is off by {} from amount × price {} and fee {}",
+        total.normalize(),
+        (total.abs() - net).abs().normalize(),
+        gross.normalize(),
+        trade.fee.normalize()
+    )))
+}
+
+/// Source recorded for rows of `file`, `import:<exchange>` from the leading
+/// letters of the file name, e.g. `binance-2024.csv` → `import:binance`
+pub fn source_for(file: &Path) -> String {
+    let stem = file
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    let exchange: String = stem.chars().take_while(|c| c.is_alphanumeric()).collect();
+    if exchange.is_empty() {
+        "import".to_string()
+    } else {
+        format!("import:{}", exchange)
+    }
+}
+
+/// An export as CSV, XLSX workbooks as the CSV of their first sheet
+fn read_export(path: &Path) -> Result<String> {
+    if xlsx::is_xlsx(path) {
+        xlsx::to_csv(path)
+    } else {
+        Ok(std::fs::read_to_string(path)?)
+    }
+}
+
+/// Parse the rows of one export, in the portfolio CSV format, one of the
+/// universal formats of Koinly and CoinTracking or the format of a saved
+/// import profile, valued in `base`
+fn parse_file(path: &Path, base: &str, settings: &Settings) -> Result<ParsedFile> {
+    parse_input(&read_export(path)?, &source_for(path), base, settings)
+}
+
+/// Parse rows in any of the formats of `parse_file`, recorded with `source`
+fn parse_input(input: &str, source: &str, base: &str, settings: &Settings) -> Result<ParsedFile> {
+    let (_, data) = extract_csv_config(input)?;
+    // lines of `# key: value` metadata above the header
+    let offset = input.lines().count() - data.lines().count();
+    let mut reader = trades_reader(data.as_bytes());
+    let headers = reader.headers()?.clone();
+    if let Some(format) = universal::detect(&headers) {
+        return parse_universal(format, reader, offset, source, base, settings);
+    }
+    if let Some((name, profile)) = import_profile::detect(&headers, settings)? {
+        let source = format!("import:{}", name);
+        return parse_profiled(&profile, reader, offset, &source);
+    }
+    if headers.iter().take(CSV_HEADER.len()).ne(CSV_HEADER) {
+        bail!("expected header '{}'", CSV_HEADER.join(","));
+    }
+    let total_column = headers
+        .iter()
+        .position(|h| TOTAL_COLUMNS.contains(&h.trim().to_ascii_lowercase().as_str()));
+
+    let mut parsed = ParsedFile {
+        trades: Vec::new(),
+        failed: Vec::new(),
+        mismatched: Vec::new(),
+    };
+    for record in reader.records() {
+        match record.and_then(|r| Ok((r.deserialize::<Trade>(Some(&headers))?, r))) {
+            Ok((mut trade, record)) => {
+                let line = record.position().map_or(0, |p| p.line() as usize) + offset;
+                let total = total_column
+                    .and_then(|c| record.get(c))
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty());
+                if let Some(total) = total {
+                    match reconcile(&trade, total, settings.import_total_tolerance) {
+                        Ok(None) => {}
+                        Ok(Some(reason)) => {
+                            parsed.mismatched.push(format!("line {}: {}", line, reason))
+                        }
+                        Err(e) => {
+                            parsed.failed.push(format!("line {}: {}", line, e));
+                            continue;
+                        }
+                    }
+                }
+                if trade.source.is_none() {
+                    trade.source = Some(source.to_string());
+                }
+                parsed.trades.push(trade);
+            }
+            Err(e) => {
+                let line = e.position().map(|p| p.line() as usize).unwrap_or(0) + offset;
+                let reason = match e.kind() {
+                    csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+                    _ => e.to_string(),
+                };
+                parsed.failed.push(format!("line {}: {}", line, reason));
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+/// Rows of a Koinly or CoinTracking export, one record can become several rows
+fn parse_universal<R: std::io::Read>(
+    format: Format,
+    mut reader: csv::Reader<R>,
+    offset: usize,
+    source: &str,
+    base: &str,
+    settings: &Settings,
+) -> Result<ParsedFile> {
+    let headers = reader.headers()?.clone();
+    let mut parsed = ParsedFile {
+        trades: Vec::new(),
+        failed: Vec::new(),
+        mismatched: Vec::new(),
+    };
+    for record in reader.records() {
+        let converted = record.map_err(anyhow::Error::from).and_then(|r| {
+            let line = r.position().map_or(0, |p| p.line() as usize) + offset;
+            universal::to_trades(format, &headers, &r, base, settings)
+                .map_err(|e| anyhow::anyhow!("line {}: {:#}", line, e))
+        });
+        match converted {
+            Ok(trades) => {
+                for mut trade in trades {
+                    trade.source = Some(source.to_string());
+                    parsed.trades.push(trade);
+                }
+            }
+            Err(e) => parsed.failed.push(format!("{:#}", e)),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Rows of an export read with an import profile
+fn parse_profiled<R: std::io::Read>(
+    profile: &Profile,
+    mut reader: csv::Reader<R>,
+    offset: usize,
+    source: &str,
+) -> Result<ParsedFile> {
+    let headers = reader.headers()?.clone();
+    let mut parsed = ParsedFile {
+        trades: Vec::new(),
+        failed: Vec::new(),
+        mismatched: Vec::new(),
+    };
+    for record in reader.records() {
+        let converted = record.map_err(anyhow::Error::from).and_then(|r| {
+            let line = r.position().map_or(0, |p| p.line() as usize) + offset;
+            profile
+                .to_trade(&headers, &r)
+                .map_err(|e| anyhow!("line {}: {:#}", line, e))
+        });
+        match converted {
+            Ok(mut trade) => {
+                trade.source = Some(source.to_string());
+                parsed.trades.push(trade);
+            }
+            Err(e) => parsed.failed.push(format!("{:#}", e)),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Parse `files` on up to one thread per CPU, results in the order of `files`
+fn parse_files(files: &[PathBuf], base: &str, settings: &Settings) -> Vec<Result<ParsedFile>> {
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = files.len().div_ceil(workers).max(1);
+    std::thread::scope(|s| {
+        let handles: Vec<_> = files
+            .chunks(chunk)
+            .map(|chunk| {
+                s.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|f| parse_file(f, base, settings))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("import worker panicked"))
+            .collect()
+    })
+}
+
+/// Trade fields without the source, rows with the same key are duplicates
+fn trade_key(record: &csv::StringRecord) -> Vec<String> {
+    record
+        .iter()
+        .take(CSV_HEADER.len())
+        .map(|f| f.trim().to_string())
+        .collect()
+}
+
+/// Check that every row of an export parses, without importing it
+pub fn validate(path: &Path, base: &str, settings: &Settings) -> Result<()> {
+    let parsed = parse_file(path, base, settings)?;
+    if let Some(reason) = parsed.failed.first() {
+        bail!(
+            "{} of {} rows failed, {}",
+            parsed.failed.len(),
+            parsed.failed.len() + parsed.trades.len(),
+            reason
+        );
+    }
+    Ok(())
+}
+
+/// The `*.csv` and `*.xlsx` files directly in `dir`, sorted by name
+pub fn export_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("reading {}", dir.display()))?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.is_file()
+                && (xlsx::is_xlsx(p)
+                    || p.extension().is_some_and(|e| e.eq_ignore_ascii_case("csv")))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Import every `*.csv` and `*.xlsx` of `dir` into a portfolio, see `import_files`
+pub fn import_dir(
+    name: &str,
+    dir: &Path,
+    yes: bool,
+    settings: &Settings,
+) -> Result<Vec<FileReport>> {
+    let files = export_files(dir)?;
+    if files.is_empty() {
+        bail!("no CSV or XLSX files in {}", dir.display());
+    }
+    import_files(name, &files, yes, settings)
+}
+
+/// Import export files into a portfolio
+///
+/// Files are parsed in parallel; the new rows of all files are then merged
+/// into the portfolio in timestamp order with a single write. Rows already
+/// in the portfolio are skipped, so importing the same exports twice is safe.
+pub fn import_files(
+    name: &str,
+    files: &[PathBuf],
+    yes: bool,
+    settings: &Settings,
+) -> Result<Vec<FileReport>> {
+    let path = settings.path_for(name);
+    ensure_portfolio_file(&path)?;
+    let (config, _) = parse_csv_records(&path)?;
+    let parsed = parse_files(files, &config.base_currency, settings);
+    merge(
+        name,
+        files.iter().cloned().zip(parsed).collect(),
+        None,
+        yes,
+        settings,
+    )
+}
+
+/// Label of clipboard rows in reports and their recorded source
+const CLIPBOARD: &str = "clipboard";
+/// Rows shown in the preview of pasted rows
+const PREVIEW_ROWS: usize = 20;
+
+/// Pasted rows as CSV: tab separated rows, as copied from a web table or
+/// a spreadsheet, are converted, and rows without a header get the
+/// portfolio header when they start with a timestamp
+fn pasted_csv(text: &str) -> Result<String> {
+    let first = text.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+    let mut csv = if first.contains('\t') {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(b'\t')
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(text.as_bytes());
+        let mut out = csv::Writer::from_writer(Vec::new());
+        for record in reader.records() {
+            out.write_record(record?.iter().map(str::trim))?;
+        }
+        String::from_utf8(out.into_inner()?)?
+    } else {
+        text.to_string()
+    };
+    let first_field = first.split(['\t', ',']).next().unwrap_or("").trim();
+    if first_field.parse::<i64>().is_ok() {
+        csv.insert_str(0, &format!("{}\n", CSV_HEADER.join(",")));
+    }
+    Ok(csv)
+}
+
+fn parse_pasted(text: &str, base: &str, settings: &Settings) -> Result<ParsedFile> {
+    let source = format!("import:{}", CLIPBOARD);
+    parse_input(&pasted_csv(text)?, &source, base, settings)
+}
+
+/// Trades of `text` in any of the formats `import` reads, for `--stdin`;
+/// the base currency of its metadata, or the configured one
+pub fn parse_text(text: &str, settings: &Settings) -> Result<(CsvConfig, Vec<Trade>)> {
+    if text.trim().is_empty() {
+        bail!("no trades on stdin");
+    }
+    let (mut config, _) = extract_csv_config(text)?;
+    let has_base = text
+        .lines()
+        .take_while(|l| l.starts_with('#'))
+        .any(|l| l[1..].trim_start().starts_with("base_currency"));
+    if !has_base {
+        config.base_currency = settings.base_currency.to_string();
+    }
+    let parsed = parse_input(&pasted_csv(text)?, "stdin", &config.base_currency, settings)?;
+    if !parsed.failed.is_empty() {
+        bail!(
+            "{} row(s) on stdin can't be read:\n{}",
+            parsed.failed.len(),
+            parsed.failed.join("\n")
+        );
+    }
+    for reason in parsed.mismatched {
+        warnings::warn(reason);
+    }
+    Ok((config, parsed.trades))
+}
+
+/// `import --clipboard`: import CSV or TSV rows pasted from the clipboard,
+/// after a preview of the new rows
+pub fn import_text(name: &str, text: &str, yes: bool, settings: &Settings) -> Result<FileReport> {
+    if text.trim().is_empty() {
+        bail!("the clipboard is empty");
+    }
+    let path = settings.path_for(name);
+    ensure_portfolio_file(&path)?;
+    let (config, _) = parse_csv_records(&path)?;
+    let parsed = parse_pasted(text, &config.base_currency, settings);
+    let mut reports = merge(
+        name,
+        vec![(PathBuf::from(CLIPBOARD), parsed)],
+        Some(PREVIEW_ROWS),
+        yes,
+        settings,
+    )?;
+    Ok(reports.remove(0))
+}
+
+/// Merge parsed files into the portfolio, see `import_files`; with
+/// `preview`, that many of the new rows are shown and the import always
+/// asks to go ahead
+fn merge(
+    name: &str,
+    parsed_files: Vec<(PathBuf, Result<ParsedFile>)>,
+    preview: Option<usize>,
+    yes: bool,
+    settings: &Settings,
+) -> Result<Vec<FileReport>> {
+    let path = settings.path_for(name);
+    let (config, mut records) = parse_csv_records(&path)?;
+    let mut seen: HashSet<Vec<String>> = records.iter().map(trade_key).collect();
+    let before = records.len();
+    let mut reports = Vec::new();
+    for (file, parsed) in parsed_files {
+        let mut report = FileReport {
+            file,
+            imported: 0,
+            skipped: 0,
+            failed: Vec::new(),
+            mismatched: Vec::new(),
+        };
+        match parsed {
+            Ok(parsed) => {
+                for trade in parsed.trades {
+                    let record = trade.to_record();
+                    if seen.insert(trade_key(&record)) {
+                        records.push(record);
+                        report.imported += 1;
+                    } else {
+                        report.skipped += 1;
+                    }
+                }
+                report.failed = parsed.failed;
+                report.mismatched = parsed.mismatched;
+            }
+            Err(e) => report.failed.push(format!("{:#}", e)),
+        }
+        reports.push(report);
+    }
+
+    if records.len() > before {
+        interrupt::check().context("nothing was imported")?;
+        let summary = format!(
+            "Importing adds {} rows to {} and rewrites it",
+            records.len() - before,
+            path.display()
+        );
+        match preview {
+            Some(shown) => {
+                let new = &records[before..];
+                let mut details = vec![CSV_HEADER.join(",")];
+                details.extend(
+                    new.iter()
+                        .take(shown)
+                        .map(|r| r.iter().collect::<Vec<_>>().join(",")),
+                );
+                if new.len() > shown {
+                    details.push(format!("... and {} more", new.len() - shown));
+                }
+                for r in &reports {
+                    details.extend(r.failed.iter().map(|reason| format!("❌ {}", reason)));
+                }
+                confirm_preview(&summary, &details, yes)?;
+            }
+            None => {
+                let details: Vec<String> = reports
+                    .iter()
+                    .map(|r| {
+                        format!(
+                            "+{} rows from {} ({} duplicates skipped)",
+                            r.imported,
+                            r.file.display(),
+                            r.skipped
+                        )
+                    })
+                    .collect();
+                confirm(&summary, &details, records.len() - before, yes, settings)?;
+            }
+        }
+        sort_records_chronologically(&mut records);
+        write_csv_records(&path, &config, &records)?;
+        index::record_write(name, settings);
+    }
+    Ok(reports)
+}
+
+/// Most common fiat quote currency of the trades with its count, ties
+/// going to the currency sorting first
+pub fn infer_base(trades: &[Trade]) -> Option<(Currency, usize)> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for trade in trades {
+        if let Instrument::Pair(pair) = &trade.pair
+            && pair.quote.currency_type() == CurrencyType::Fiat
+        {
+            *counts.entry(pair.quote.ticker()).or_default() += 1;
+        }
+    }
+    let (ticker, count) = counts.into_iter().rev().max_by_key(|(_, count)| *count)?;
+    Some((Currency::new(ticker).ok()?, count))
+}
+
+/// Create portfolio `name` for imported `trades`, in the currency most of
+/// them are quoted in when it's not the configured base and that's accepted
+fn create_for(
+    name: &str,
+    trades: &[Trade],
+    yes: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let mut settings = settings.clone();
+    if let Some((currency, count)) = infer_base(trades)
+        && currency != settings.base_currency
+    {
+        let question = format!(
+            "{} of {} imported trades are quoted in {}. Create '{}' with base currency {} instead of {}?",
+            count,
+            trades.len(),
+            currency,
+            name,
+            currency,
+            settings.base_currency
+        );
+        if confirm::accept(&question, yes)? {
+            settings.base_currency = currency;
+        }
+    }
+    portfolio::new(name, PortfolioKind::Trades, reporter, &settings)
+}
+
+/// `import --wizard`: ask on the terminal how to read export `file`, and
+/// save that as an import profile for the exports of its source
+fn learn(file: &Path, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let csv = read_export(file)?;
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    let mut output = std::io::stdout();
+    let profile = import_profile::ask(&mut input, &mut output, &csv)?;
+    let source = source_for(file);
+    let default = source.strip_prefix("import:").unwrap_or("export");
+    let profile_name = import_profile::ask_name(&mut input, &mut output, default)?;
+    let path = import_profile::save(&profile_name, &profile, settings)?;
+    reporter.print(&format!(
+        "✅ Saved import profile '{}' to {}, exports with the same columns are read with it",
+        profile_name,
+        path.display()
+    ));
+    Ok(())
+}
+
+/// `import --dir`, `import --clipboard`, `import --wizard`: import and print
+/// a summary per file; with `create`, a missing portfolio is created first
+pub fn run(
+    name: &str,
+    dir: Option<&Path>,
+    wizard: Option<&Path>,
+    create: bool,
+    yes: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    if let Some(file) = wizard {
+        learn(file, reporter, settings)?;
+    }
+    let files = match (dir, wizard) {
+        (Some(dir), _) => Some(export_files(dir)?),
+        (None, Some(file)) => Some(vec![file.to_path_buf()]),
+        (None, None) => None,
+    };
+    let pasted = match files {
+        Some(_) => None,
+        None => Some(crate::output::paste_from_clipboard()?),
+    };
+    if create && !settings.path_for(name).exists() {
+        let base = settings.base_currency.ticker();
+        let parsed = match (&files, &pasted) {
+            (Some(files), _) => parse_files(files, base, settings),
+            (None, text) => vec![parse_pasted(text.as_deref().unwrap_or(""), base, settings)],
+        };
+        let trades: Vec<Trade> = parsed
+            .into_iter()
+            .filter_map(Result::ok)
+            .flat_map(|p| p.trades)
+            .collect();
+        create_for(name, &trades, yes, reporter, settings)?;
+    }
+    let reports = match (dir, files, pasted) {
+        (Some(dir), ..) => import_dir(name, dir, yes, settings)?,
+        (None, Some(files), _) => import_files(name, &files, yes, settings)?,
+        (None, None, text) => vec![import_text(name, &text.unwrap_or_default(), yes, settings)?],
+    };
+
+    let mut table = Table::new();
+    table.add_row(row!["File", "Imported", "Skipped", "Failed"]);
+    for r in &reports {
+        let file = r.file.file_name().unwrap_or_default().to_string_lossy();
+        table.add_row(row![file, r->r.imported, r->r.skipped, r->r.failed.len()]);
+    }
+    reporter.table(&table)?;
+    for r in &reports {
+        let file = r.file.file_name().unwrap_or_default().to_string_lossy();
+        for reason in &r.failed {
+            reporter.print(&format!("❌ {}: {}", file, reason));
+        }
+        for reason in &r.mismatched {
+            warnings::warn(format!("{}: {}", file, reason));
+        }
+    }
+
+    let imported: usize = reports.iter().map(|r| r.imported).sum();
+    let from = match (dir, wizard) {
+        (Some(_), _) => format!("{} files", reports.len()),
+        (None, Some(file)) => file.display().to_string(),
+        (None, None) => format!("the {}", CLIPBOARD),
+    };
+    reporter.status(&format!(
+        "✅ Imported {} trades from {} into {}",
+        imported,
+        from,
+        settings.path_for(name).display()
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings, create_test_xlsx};
+    use rust_decimal::dec;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_import_dir_merges_files_in_timestamp_order() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        create_test_csv(
+            &dir,
+            "pf",
+            "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,7.5
+",
+        );
+        let exports = dir.path().join("exports");
+        std::fs::create_dir(&exports).unwrap();
+        std::fs::write(
+            exports.join("kraken-2024.csv"),
+            "created_at,pair,side,amount,price,fee
+1717977600,ETH/USD,BUY,2,3100,7.7
+1704883200,BTC/USD,BUY,1.0,40000.00,7.50
+1710460800,BTC/USD,HOLD,3,20000,10
+",
+        )
+        .unwrap();
+        std::fs::write(
+            exports.join("binance.csv"),
+            "created_at,pair,side,amount,price,fee
+1710460800,BTC/USD,BUY,3,20000,10
+",
+        )
+        .unwrap();
+        std::fs::write(exports.join("notes.csv"), "date,what\n2024-01-01,hello\n").unwrap();
+
+        let reports = import_dir("pf", &exports, false, &settings).unwrap();
+        let summary: Vec<(usize, usize, usize)> = reports
+            .iter()
+            .map(|r| (r.imported, r.skipped, r.failed.len()))
+            .collect();
+        // binance, kraken, notes
+        assert_eq!(summary, vec![(1, 0, 0), (1, 1, 1), (0, 0, 1)]);
+        assert!(reports[1].failed[0].starts_with("line 4: "));
+
+        let content = std::fs::read_to_string(settings.path_for("pf")).unwrap();
+        assert!(content.ends_with(
+            "1704883200,BTC/USD,BUY,1,40000,7.5,
+1710460800,BTC/USD,BUY,3,20000,10,import:binance
+1717977600,ETH/USD,BUY,2,3100,7.7,import:kraken
+"
+        ));
+    }
+
+    #[test]
+    fn test_import_flags_rows_off_their_total_column() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        create_test_csv(&dir, "pf", "created_at,pair,side,amount,price,fee\n");
+        let exports = TempDir::new().unwrap();
+        std::fs::write(
+            exports.path().join("kraken.csv"),
+            "created_at,pair,side,amount,price,fee,total
+1704883200,BTC/USD,BUY,0.5,40000,7.5,20007.5
+1704883300,BTC/USD,BUY,0.1,40000,1,4000
+1704883400,BTC/USD,SELL,0.1,41000,1,-4099.004
+1704883500,BTC/USD,BUY,0.2,40000,1,8100
+1704883600,BTC/USD,BUY,0.2,40000,1,n/a
+1704883700,BTC/USD,BUY,0.3,40000,1,
+",
+        )
+        .unwrap();
+
+        let reports = import_dir("pf", exports.path(), false, &settings).unwrap();
+        assert_eq!(reports[0].imported, 5);
+        assert_eq!(reports[0].failed, ["line 6: invalid total 'n/a'"]);
+        assert_eq!(
+            reports[0].mismatched,
+            ["line 5: total 8100 is off by 99 from amount × price 8000 and fee 1"]
+        );
+
+        let trade = |side| Trade {
+            created_at: time::macros::datetime!(2024-01-10 10:40 UTC),
+            pair: "BTC/USD".parse().unwrap(),
+            side,
+            amount: dec!(0.5),
+            price: dec!(40000),
+            fee: dec!(7.5),
+            source: None,
+        };
+        let tolerance = dec!(0.01);
+        assert_eq!(
+            reconcile(&trade(Side::Sell), "19992.5", tolerance).unwrap(),
+            None
+        );
+        assert!(
+            reconcile(&trade(Side::Sell), "19992.4", tolerance)
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(
+            reconcile(&trade(Side::Deposit), "1", tolerance).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_import_dir_reads_xlsx_exports() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        create_test_csv(&dir, "pf", "created_at,pair,side,amount,price,fee\n");
+        let exports = TempDir::new().unwrap();
+        let row = |r: u32, cells: [&str; 6]| {
+            let cells: String = cells
+                .iter()
+                .enumerate()
+                .map(|(i, v)| match v.parse::<f64>() {
+                    Ok(_) if i == 0 => format!(r#"<c s="1"><v>{}</v></c>"#, v),
+                    Ok(_) => format!("<c><v>{}</v></c>", v),
+                    Err(_) => format!(r#"<c t="inlineStr"><is><t>{}</t></is></c>"#, v),
+                })
+                .collect();
+            format!(r#"<row r="{}">{}</row>"#, r, cells)
+        };
+        let sheet = format!(
+            "<worksheet><sheetData>{}{}</sheetData></worksheet>",
+            row(1, ["created_at", "pair", "side", "amount", "price", "fee"]),
+            row(2, ["45301", "BTC/USD", "BUY", "0.5", "40000", "2.5"]),
+        );
+        create_test_xlsx(
+            &exports,
+            "bitstamp",
+            &[
+                (
+                    "xl/workbook.xml",
+                    r#"<workbook xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="Trades" sheetId="1" r:id="rId1"/></sheets></workbook>"#,
+                ),
+                (
+                    "xl/_rels/workbook.xml.rels",
+                    r#"<Relationships><Relationship Id="rId1" Type="worksheet" Target="worksheets/sheet1.xml"/></Relationships>"#,
+                ),
+                (
+                    "xl/styles.xml",
+                    r#"<styleSheet><cellXfs><xf numFmtId="0"/><xf numFmtId="22"/></cellXfs></styleSheet>"#,
+                ),
+                ("xl/worksheets/sheet1.xml", &sheet),
+            ],
+        );
+
+        let reports = import_dir("pf", exports.path(), false, &settings).unwrap();
+        assert_eq!((reports[0].imported, reports[0].failed.len()), (1, 0));
+        let content = std::fs::read_to_string(settings.path_for("pf")).unwrap();
+        assert!(content.ends_with("1704844800,BTC/USD,BUY,0.5,40000,2.5,import:bitstamp\n"));
+    }
+
+    #[test]
+    fn test_import_text_reads_pasted_tsv_rows() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        create_test_csv(
+            &dir,
+            "pf",
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,7.5
+",
+        );
+        // copied from a web table: tab separated, no header, padded cells
+        let pasted = "1704883200\tBTC/USD\tBUY\t1\t40000\t7.5\n\
+                      1710460800 \t ETH/USD\tBUY\t2\t3100\t1\n";
+        assert_eq!(
+            pasted_csv(pasted).unwrap(),
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,7.5
+1710460800,ETH/USD,BUY,2,3100,1
+"
+        );
+
+        let report = import_text("pf", pasted, true, &settings).unwrap();
+        assert_eq!((report.imported, report.skipped), (1, 1));
+        let content = std::fs::read_to_string(settings.path_for("pf")).unwrap();
+        assert!(content.ends_with("1710460800,ETH/USD,BUY,2,3100,1,import:clipboard\n"));
+    }
+
+    #[test]
+    fn test_infer_base_counts_fiat_quotes_only() {
+        let trades = crate::test_utils::helpers::transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/EUR,BUY,1,37000,5
+1704883201,ETH/BTC,BUY,10,0.05,0
+1704883202,ETH/BTC,SELL,10,0.05,0
+1704883203,BTC/USD,BUY,1,40000,5
+1704883204,EUR,DEPOSIT,1000,1,0
+",
+        );
+        // a tie between EUR and USD goes to EUR
+        assert_eq!(
+            infer_base(&trades),
+            Some((Currency::new("EUR").unwrap(), 1))
+        );
+        assert_eq!(infer_base(&trades[1..3]), None);
+    }
+}
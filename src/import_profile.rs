@@ -0,0 +1,402 @@
+//! Import profiles
+//!
+//! Exports in a format of their own can be imported once the columns holding
+//! each field of a trade are named. `import --wizard export.csv` guesses them
+//! from the header, asks to confirm or correct each, previews the rows they
+//! give and saves the mapping in `<state dir>/import_profiles/<name>.toml`:
+//! ```toml
+//! headers = ["Time", "Market", "Type", "Filled", "Avg. Price", "Fee"]
+//! date = "Time"
+//! pair = "Market"
+//! side = "Type"
+//! amount = "Filled"
+//! price = "Avg. Price"
+//! fee = "Fee"
+//! ```
+//! Later exports with the same header are read with the profile by every
+//! import, their rows recorded with the source `import:<name>`.
+
+use crate::currency::Currency;
+use crate::settings::Settings;
+use crate::trade::{CSV_HEADER, Side, Trade, TradingPair, trades_reader};
+use crate::universal;
+use crate::wizard::prompt;
+use anyhow::{Context, Result, anyhow, bail};
+use csv::StringRecord;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use time::OffsetDateTime;
+
+/// Rows parsed for the preview of the wizard
+const PREVIEW_ROWS: usize = 5;
+
+/// Columns of an export holding the fields of a trade, by header name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Header of the exports the profile reads
+    pub headers: Vec<String>,
+    pub date: String,
+    pub pair: String,
+    pub side: String,
+    pub amount: String,
+    pub price: String,
+    /// Fee in the quote currency, no fee when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee: Option<String>,
+}
+
+/// Fields of a profile with the header names they are guessed from
+const FIELDS: [(&str, &[&str]); 6] = [
+    (
+        "date",
+        &[
+            "date",
+            "time",
+            "timestamp",
+            "created_at",
+            "date(utc)",
+            "datetime",
+        ],
+    ),
+    (
+        "pair",
+        &["pair", "market", "symbol", "instrument", "product"],
+    ),
+    ("side", &["side", "type", "direction", "order type"]),
+    (
+        "amount",
+        &[
+            "amount", "qty", "quantity", "filled", "executed", "size", "vol",
+        ],
+    ),
+    ("price", &["price", "avg. price", "average price", "rate"]),
+    ("fee", &["fee", "fees", "commission"]),
+];
+
+fn profiles_dir(settings: &Settings) -> PathBuf {
+    settings.state_dir().join("import_profiles")
+}
+
+/// Saved profiles by name
+pub fn load_all(settings: &Settings) -> Result<Vec<(String, Profile)>> {
+    let dir = profiles_dir(settings);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut profiles = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != "toml") {
+            continue;
+        }
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let profile = toml::from_str(&std::fs::read_to_string(&path)?)
+            .with_context(|| format!("parsing {}", path.display()))?;
+        profiles.push((name.to_string(), profile));
+    }
+    profiles.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(profiles)
+}
+
+fn profile_path(name: &str, settings: &Settings) -> PathBuf {
+    profiles_dir(settings).join(name).with_extension("toml")
+}
+
+/// Store `profile` as `name`, replacing the profile of that name and any
+/// reading the same header, so every export has one profile
+pub fn save(name: &str, profile: &Profile, settings: &Settings) -> Result<PathBuf> {
+    let headers = StringRecord::from(profile.headers.clone());
+    for (other, old) in load_all(settings)? {
+        if other != name && same_headers(old.headers.iter(), &headers) {
+            std::fs::remove_file(profile_path(&other, settings))?;
+        }
+    }
+    std::fs::create_dir_all(profiles_dir(settings))?;
+    let path = profile_path(name, settings);
+    std::fs::write(&path, toml::to_string(profile)?)
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(path)
+}
+
+fn same_headers(a: impl Iterator<Item = impl AsRef<str>>, b: &StringRecord) -> bool {
+    a.map(|h| h.as_ref().trim().to_lowercase())
+        .eq(b.iter().map(|h| h.trim().to_lowercase()))
+}
+
+/// The saved profile reading exports with `headers`, with its name
+pub fn detect(headers: &StringRecord, settings: &Settings) -> Result<Option<(String, Profile)>> {
+    Ok(load_all(settings)?
+        .into_iter()
+        .find(|(_, profile)| same_headers(profile.headers.iter(), headers)))
+}
+
+fn column(headers: &StringRecord, name: &str) -> Option<usize> {
+    headers
+        .iter()
+        .position(|h| h.trim().eq_ignore_ascii_case(name.trim()))
+}
+
+/// A pair as exchanges write it: `BTC/USD`, `BTC-USD`, `btc_usd`, or
+/// `BTCUSDT` ending in a fiat or stable coin ticker
+fn parse_pair(s: &str) -> Result<TradingPair> {
+    let s = s.trim().to_ascii_uppercase();
+    if let Some((base, quote)) = s.split_once(['/', '-', '_']) {
+        return TradingPair::new(Currency::new(base)?, Currency::new(quote)?);
+    }
+    Currency::supported_fiat()
+        .chain(Currency::supported_stables())
+        .filter(|quote| s.len() > quote.len() && s.ends_with(quote))
+        .max_by_key(|quote| quote.len())
+        .ok_or(anyhow!("can't tell the base and quote of '{}'", s))
+        .and_then(|quote| {
+            let base = &s[..s.len() - quote.len()];
+            TradingPair::new(Currency::new(base)?, Currency::new(quote)?)
+        })
+}
+
+/// Unix seconds or milliseconds, or a date of the universal formats, with
+/// `T` or a space before the time
+fn parse_date(s: &str) -> Result<OffsetDateTime> {
+    let s = s.trim();
+    if let Ok(ts) = s.parse::<i64>() {
+        // milliseconds from a number of digits seconds won't reach for ages
+        let ts = if ts > 99_999_999_999 { ts / 1000 } else { ts };
+        return Ok(OffsetDateTime::from_unix_timestamp(ts)?);
+    }
+    match s.get(10..11) {
+        Some("T") => {
+            universal::parse_date(s).or_else(|_| universal::parse_date(&s.replacen('T', " ", 1)))
+        }
+        _ => universal::parse_date(s),
+    }
+}
+
+fn parse_number(s: &str) -> Result<Decimal> {
+    let s = s.trim();
+    Decimal::from_str(s)
+        .or_else(|_| Decimal::from_scientific(s))
+        .map(|d| d.abs())
+        .map_err(|_| anyhow!("invalid number '{}'", s))
+}
+
+impl Profile {
+    /// The trade of one row of an export
+    pub fn to_trade(&self, headers: &StringRecord, record: &StringRecord) -> Result<Trade> {
+        let field = |name: &str| -> Result<&str> {
+            let c = column(headers, name).ok_or(anyhow!("no column '{}'", name))?;
+            Ok(record.get(c).unwrap_or("").trim())
+        };
+        let side = field(&self.side)?;
+        let pair = match serde_plain::from_str::<Side>(&side.to_ascii_uppercase()) {
+            Ok(side) if side.is_cash() => Currency::new(field(&self.pair)?)?.to_string(),
+            _ => {
+                let pair = parse_pair(field(&self.pair)?)?;
+                format!("{}/{}", pair.base, pair.quote)
+            }
+        };
+        let fee = match &self.fee {
+            Some(fee) => match field(fee)? {
+                "" => Decimal::ZERO,
+                fee => parse_number(fee)?,
+            },
+            None => Decimal::ZERO,
+        };
+        // through the portfolio format, so the rows are checked like any other
+        let row = StringRecord::from(vec![
+            parse_date(field(&self.date)?)?.unix_timestamp().to_string(),
+            pair,
+            side.to_string(),
+            parse_number(field(&self.amount)?)?.to_string(),
+            parse_number(field(&self.price)?)?.to_string(),
+            fee.to_string(),
+        ]);
+        Ok(row.deserialize(Some(&StringRecord::from(CSV_HEADER.to_vec())))?)
+    }
+}
+
+/// Column of `field` guessed from the header names
+fn guess(headers: &StringRecord, field: &str) -> Option<usize> {
+    let (_, names) = FIELDS.iter().find(|(f, _)| *f == field)?;
+    names.iter().find_map(|name| column(headers, name))
+}
+
+/// Ask the column of every field of the export `csv`, re-asking until the
+/// preview of the rows they give is accepted
+pub fn ask<R: BufRead, W: Write>(input: &mut R, output: &mut W, csv: &str) -> Result<Profile> {
+    let mut reader = trades_reader(csv.as_bytes());
+    let headers = reader.headers()?.clone();
+    let rows: Vec<StringRecord> = reader
+        .records()
+        .take(PREVIEW_ROWS)
+        .collect::<Result<_, _>>()?;
+    if headers.is_empty() || rows.is_empty() {
+        bail!("the export needs a header and at least one row");
+    }
+    writeln!(output, "Columns of the export, with the first row:")?;
+    for (i, header) in headers.iter().enumerate() {
+        let sample = rows[0].get(i).unwrap_or("");
+        writeln!(output, "  {}. {}: {}", i + 1, header, sample)?;
+    }
+
+    let mut answers: Vec<Option<String>> = FIELDS
+        .iter()
+        .map(|(field, _)| guess(&headers, field).map(|c| headers[c].to_string()))
+        .collect();
+    loop {
+        for ((field, _), answer) in FIELDS.iter().zip(answers.iter_mut()) {
+            let optional = *field == "fee";
+            let question = match optional {
+                true => format!("Column of the {}, - for none", field),
+                false => format!("Column of the {}", field),
+            };
+            let default = answer
+                .clone()
+                .unwrap_or(if optional { "-" } else { "" }.into());
+            *answer = prompt(input, output, &question, &default, |s| {
+                if optional && s == "-" {
+                    return Ok(None);
+                }
+                let index = match s.parse::<usize>() {
+                    Ok(n) if n >= 1 && n <= headers.len() => Some(n - 1),
+                    _ => column(&headers, s),
+                };
+                match index {
+                    Some(c) => Ok(Some(headers[c].to_string())),
+                    None if s.is_empty() => bail!("the {} needs a column", field),
+                    None => bail!("no column '{}'", s),
+                }
+            })?;
+        }
+        let column = |i: usize| answers[i].clone().unwrap_or_default();
+        let profile = Profile {
+            headers: headers.iter().map(str::to_string).collect(),
+            date: column(0),
+            pair: column(1),
+            side: column(2),
+            amount: column(3),
+            price: column(4),
+            fee: answers[5].clone(),
+        };
+
+        writeln!(output, "The first rows read as:")?;
+        writeln!(output, "  {}", CSV_HEADER.join(","))?;
+        for (i, row) in rows.iter().enumerate() {
+            match profile.to_trade(&headers, row) {
+                Ok(trade) => {
+                    let record = trade.to_record();
+                    let fields: Vec<&str> = record.iter().take(CSV_HEADER.len()).collect();
+                    writeln!(output, "  {}", fields.join(","))?
+                }
+                Err(e) => writeln!(output, "  ❌ row {}: {:#}", i + 1, e)?,
+            }
+        }
+        let right = prompt(
+            input,
+            output,
+            "Are these rows right (y/n)",
+            "y",
+            |s| match s.to_lowercase().as_str() {
+                "y" | "yes" => Ok(true),
+                "n" | "no" => Ok(false),
+                _ => bail!("answer y or n"),
+            },
+        )?;
+        if right {
+            return Ok(profile);
+        }
+    }
+}
+
+/// Ask the name to save `profile` under, `default` when none is given
+pub fn ask_name<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    default: &str,
+) -> Result<String> {
+    prompt(input, output, "Save the mapping as profile", default, |s| {
+        if s.is_empty()
+            || !s
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!("use letters, digits, - and _");
+        }
+        Ok(s.to_lowercase())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::create_test_settings;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    const EXPORT: &str = "Time,Market,Type,Filled,Avg. Price,Fee,Status
+2024-01-10T10:40:00,BTCUSDT,buy,0.5,40000,10,done
+1705056000000,eth-usd,SELL,-2,2500,,done
+";
+
+    #[test]
+    fn test_wizard_guesses_columns_and_saved_profile_reads_exports() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        // the guesses taken, but no fee at first; then the fee by number
+        let mut input = Cursor::new("\n\n\n\n\n-\nn\n\n\n\n\n\n6\ny\nMy_Exchange\n");
+        let mut output = Vec::new();
+        let profile = ask(&mut input, &mut output, EXPORT).unwrap();
+        let name = ask_name(&mut input, &mut output, "export").unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("  2. Market: BTCUSDT\n"), "{}", output);
+        assert!(output.contains("  1704883200,BTC/USDT,BUY,0.5,40000,0\n"));
+        assert!(output.contains("  1704883200,BTC/USDT,BUY,0.5,40000,10\n"));
+        assert!(output.contains("  1705056000,ETH/USD,SELL,2,2500,0\n"));
+        assert_eq!(name, "my_exchange");
+        assert_eq!(profile.date, "Time");
+        assert_eq!(profile.amount, "Filled");
+        assert_eq!(profile.fee.as_deref(), Some("Fee"));
+
+        save(&name, &profile, &settings).unwrap();
+        let headers = StringRecord::from(vec![
+            "time",
+            "market",
+            "type",
+            "filled",
+            "avg. price",
+            "fee",
+            "status",
+        ]);
+        let (found, _) = detect(&headers, &settings).unwrap().unwrap();
+        assert_eq!(found, "my_exchange");
+        save("old", &profile, &settings).unwrap();
+        assert_eq!(load_all(&settings).unwrap().len(), 1);
+        save(&name, &profile, &settings).unwrap();
+        let other = StringRecord::from(vec!["time", "market"]);
+        assert!(detect(&other, &settings).unwrap().is_none());
+
+        let row = StringRecord::from(vec!["1705056000", "DOGE", "buy", "1", "1", "0", ""]);
+        let err = profile.to_trade(&headers, &row).unwrap_err();
+        assert!(format!("{:#}", err).contains("can't tell the base and quote of 'DOGE'"));
+    }
+
+    #[test]
+    fn test_parse_pair_and_date_in_exchange_spellings() {
+        let pair = |s| parse_pair(s).map(|p| format!("{}/{}", p.base, p.quote));
+        assert_eq!(pair("btc/usd").unwrap(), "BTC/USD");
+        assert_eq!(pair("ETH_EUR").unwrap(), "ETH/EUR");
+        assert_eq!(pair("BTCUSDC").unwrap(), "BTC/USDC");
+        assert!(pair("USD").is_err());
+
+        let ts = |s| parse_date(s).unwrap().unix_timestamp();
+        assert_eq!(ts("1704883200"), 1704883200);
+        assert_eq!(ts("1704883200000"), 1704883200);
+        assert_eq!(ts("2024-01-10T10:40:00Z"), 1704883200);
+        assert_eq!(ts("2024-01-10T10:40:00"), 1704883200);
+        assert_eq!(ts("2024-01-10 10:40 UTC"), 1704883200);
+        assert!(parse_date("10 Jan 2024").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use crate::import;
+use crate::interrupt;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::read_csv_config;
+use crate::warnings;
+use anyhow::{Context, Result, bail};
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use shellexpand::tilde;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use time::OffsetDateTime;
+use time::macros::format_description;
+
+/// Imported files are moved here, below the inbox
+const ARCHIVE_DIR: &str = "archive";
+/// Files failing validation or without a portfolio are moved here
+const REJECTED_DIR: &str = "rejected";
+
+/// What happened to one file dropped into the inbox
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Imported { portfolio: String, rows: usize },
+    Rejected { reason: String },
+}
+
+/// The configured inbox directory
+pub fn inbox_dir(settings: &Settings) -> Result<PathBuf> {
+    match &settings.inbox_dir {
+        Some(dir) => Ok(PathBuf::from(tilde(&dir.to_string_lossy()).to_string())),
+        None => bail!("no inbox configured, set inbox_dir in the config file or LPT_INBOX_DIR"),
+    }
+}
+
+/// Portfolio a dropped file goes to: the first matching `inbox_rules`
+/// pattern, else an existing portfolio named like the file's leading word
+/// (`kraken-2024.csv` → `kraken`)
+pub fn portfolio_for(file: &Path, settings: &Settings) -> Option<String> {
+    let file_name = file.file_name()?.to_string_lossy().to_lowercase();
+    let rule = settings.inbox_rules.iter().find(|(pattern, _)| {
+        glob::Pattern::new(&pattern.to_lowercase()).is_ok_and(|p| p.matches(&file_name))
+    });
+    if let Some((_, portfolio)) = rule {
+        return Some(portfolio.clone());
+    }
+    let stem: String = file_name
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    (!stem.is_empty() && settings.path_for(&stem).is_file()).then_some(stem)
+}
+
+/// Move `file` into `<inbox>/<sub>/`, prefixed with the time to keep names unique
+fn move_to(file: &Path, sub: &str, now: OffsetDateTime) -> Result<PathBuf> {
+    let dir = file.parent().unwrap_or(Path::new(".")).join(sub);
+    std::fs::create_dir_all(&dir)?;
+    let stamp = now.format(format_description!(
+        "[year][month][day]-[hour][minute][second]"
+    ))?;
+    let target = dir.join(format!(
+        "{}-{}",
+        stamp,
+        file.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    std::fs::rename(file, &target)?;
+    Ok(target)
+}
+
+/// Import one dropped file, then archive or reject it
+fn process_file(file: &Path, settings: &Settings) -> Result<Outcome> {
+    let now = settings.clock.now();
+    let outcome = match portfolio_for(file, settings) {
+        None => Outcome::Rejected {
+            reason: "no inbox rule or portfolio matches the file name".to_string(),
+        },
+        Some(portfolio) => match read_csv_config(settings.path_for(&portfolio))
+            .and_then(|config| import::validate(file, &config.base_currency, settings))
+        {
+            Err(e) => Outcome::Rejected {
+                reason: format!("{:#}", e),
+            },
+            // dropped files are imported unattended, inbox rules are the consent
+            Ok(()) => match import::import_files(&portfolio, &[file.to_path_buf()], true, settings)
+            {
+                Ok(report) => Outcome::Imported {
+                    portfolio,
+                    rows: report.iter().map(|r| r.imported).sum(),
+                },
+                // a stopped import is picked up again by the next run
+                Err(e) if interrupt::interrupted() => return Err(e),
+                Err(e) => Outcome::Rejected {
+                    reason: format!("importing into '{}' failed: {:#}", portfolio, e),
+                },
+            },
+        },
+    };
+    let sub = match outcome {
+        Outcome::Imported { .. } => ARCHIVE_DIR,
+        Outcome::Rejected { .. } => REJECTED_DIR,
+    };
+    move_to(file, sub, now)?;
+    Ok(outcome)
+}
+
+/// Import every export file currently in the inbox
+///
+/// Only `*.csv` and `*.xlsx` files are picked up, so browsers' partial downloads
+/// (`.crdownload`, `.part`) are left alone until they are complete. A file
+/// that can't be processed is a warning, so a watching daemon keeps running.
+pub fn poll(reporter: &dyn Reporter, settings: &Settings) -> Result<Vec<(PathBuf, Outcome)>> {
+    let dir = inbox_dir(settings)?;
+    std::fs::create_dir_all(&dir)?;
+    let mut outcomes = Vec::new();
+    for file in import::export_files(&dir)? {
+        interrupt::check()?;
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        let outcome = match process_file(&file, settings) {
+            Ok(outcome) => outcome,
+            Err(e) if interrupt::interrupted() => return Err(e),
+            Err(e) => {
+                warnings::warn(format!("{}: {:#}", name, e));
+                continue;
+            }
+        };
+        match &outcome {
+            Outcome::Imported { portfolio, rows } => reporter.status(&format!(
+                "📥 {}: imported {} trades into '{}'",
+                name, rows, portfolio
+            )),
+            Outcome::Rejected { reason } => {
+                warnings::warn(format!("{}: rejected, {}", name, reason))
+            }
+        }
+        outcomes.push((file, outcome));
+    }
+    Ok(outcomes)
+}
+
+/// Watch the inbox, sending on `dropped` whenever a file is written or
+/// moved into it, so `watch --inbox` imports it without waiting for the
+/// next poll
+pub fn watcher(dropped: Sender<()>, settings: &Settings) -> Result<RecommendedWatcher> {
+    let dir = inbox_dir(settings)?;
+    std::fs::create_dir_all(&dir)?;
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        // files moved out to archive/ and rejected/ no longer exist
+        if let Ok(event) = event
+            && matches!(
+                event.kind,
+                EventKind::Create(_)
+                    | EventKind::Modify(_)
+                    | EventKind::Access(AccessKind::Close(AccessMode::Write))
+            )
+            && event.paths.iter().any(|p| p.is_file())
+        {
+            // the receiver is gone once watch stops
+            let _ = dropped.send(());
+        }
+    })
+    .context("watching the inbox")?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("watching {}", dir.display()))?;
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::Silent;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use std::collections::BTreeMap;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_poll_imports_archives_and_rejects() {
+        let dir = TempDir::new().unwrap();
+        let inbox = dir.path().join("inbox");
+        let settings = Settings {
+            inbox_dir: Some(inbox.clone()),
+            inbox_rules: BTreeMap::from([("Binance*".to_string(), "main".to_string())]),
+            ..(*create_test_settings(dir.path().to_path_buf())).clone()
+        };
+        let head = "# base_currency: USD\ncreated_at,pair,side,amount,price,fee\n";
+        create_test_csv(&dir, "kraken", head);
+        create_test_csv(&dir, "main", head);
+        std::fs::create_dir(&inbox).unwrap();
+        let row = "created_at,pair,side,amount,price,fee\n1704883200,BTC/USD,BUY,1,40000,0\n";
+        std::fs::write(inbox.join("binance-jan.csv"), row).unwrap();
+        std::fs::write(inbox.join("kraken-jan.csv"), row).unwrap();
+        std::fs::write(inbox.join("coinbase.csv"), row).unwrap();
+        std::fs::write(
+            inbox.join("kraken-bad.csv"),
+            format!("{}1,BTC/USD,BUY,x,1,0\n", row),
+        )
+        .unwrap();
+        std::fs::write(inbox.join("kraken.csv.crdownload"), row).unwrap();
+
+        let outcomes: Vec<Outcome> = poll(&Silent, &settings)
+            .unwrap()
+            .into_iter()
+            .map(|(_, o)| o)
+            .collect();
+        assert_eq!(
+            outcomes[0],
+            Outcome::Imported {
+                portfolio: "main".to_string(),
+                rows: 1
+            }
+        );
+        assert!(matches!(outcomes[1], Outcome::Rejected { .. }));
+        assert!(matches!(&outcomes[2], Outcome::Rejected { reason } if reason.contains("line 3")));
+        assert_eq!(
+            outcomes[3],
+            Outcome::Imported {
+                portfolio: "kraken".to_string(),
+                rows: 1
+            }
+        );
+
+        let count = |sub: &str| std::fs::read_dir(inbox.join(sub)).unwrap().count();
+        assert_eq!((count(ARCHIVE_DIR), count(REJECTED_DIR)), (2, 2));
+        assert!(inbox.join("kraken.csv.crdownload").exists());
+        assert!(poll(&Silent, &settings).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_poll_rejects_files_whose_import_fails() {
+        let dir = TempDir::new().unwrap();
+        let inbox = dir.path().join("inbox");
+        let settings = Settings {
+            inbox_dir: Some(inbox.clone()),
+            ..(*create_test_settings(dir.path().to_path_buf())).clone()
+        };
+        // validation passes, but a closed portfolio refuses the import
+        create_test_csv(
+            &dir,
+            "kraken",
+            "# base_currency: USD\n# closed_at: 2024-06-01T00:00:00Z\ncreated_at,pair,side,amount,price,fee\n",
+        );
+        std::fs::create_dir(&inbox).unwrap();
+        let row = "created_at,pair,side,amount,price,fee\n1704883200,BTC/USD,BUY,1,40000,0\n";
+        std::fs::write(inbox.join("kraken-jan.csv"), row).unwrap();
+
+        let outcomes = poll(&Silent, &settings).unwrap();
+        assert!(
+            matches!(&outcomes[0].1, Outcome::Rejected { reason } if reason.contains("importing into 'kraken' failed"))
+        );
+        assert_eq!(
+            std::fs::read_dir(inbox.join(REJECTED_DIR)).unwrap().count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_watcher_reports_dropped_files() {
+        let dir = TempDir::new().unwrap();
+        let inbox = dir.path().join("inbox");
+        let settings = Settings {
+            inbox_dir: Some(inbox.clone()),
+            ..(*create_test_settings(dir.path().to_path_buf())).clone()
+        };
+        let (tx, rx) = std::sync::mpsc::channel();
+        let _watcher = watcher(tx, &settings).unwrap();
+
+        std::fs::write(inbox.join("kraken-jan.csv"), "created_at\n").unwrap();
+        assert!(rx.recv_timeout(std::time::Duration::from_secs(5)).is_ok());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{Side, Trade, parse_csv_file, sort_chronologically};
+use anyhow::Result;
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use std::collections::BTreeMap;
+use thousands::Separable;
+use time::OffsetDateTime;
+
+const SECONDS_PER_YEAR: Decimal = dec!(31_536_000);
+
+/// Income received in one asset and the yield it made on the holding
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AssetIncome {
+    /// Amount of `INCOME` rows
+    pub income: Decimal,
+    /// Income valued in the base currency at the time it was received
+    pub value: Decimal,
+    /// Balance averaged over time since the asset was first held
+    pub avg_balance: Decimal,
+    /// Days since the asset was first held
+    pub days: i64,
+    /// Realized yield, income over the average balance, annualized
+    pub apr: Option<Decimal>,
+}
+
+/// Balance of `asset` changed by one row
+fn balance_change(t: &Trade, asset: &str) -> Result<Decimal> {
+    if t.side.is_cash() {
+        if t.pair.asset(t.side)?.ticker() != asset {
+            return Ok(dec!(0));
+        }
+        return Ok(match t.side {
+            Side::Deposit | Side::Income => t.amount,
+            _ => -t.amount,
+        });
+    }
+    let pair = t.pair.trading_pair(t.side)?;
+    let (base, quote) = match t.side {
+        Side::Buy => (t.amount, -(t.amount * t.price + t.fee)),
+        _ => (-t.amount, t.amount * t.price - t.fee),
+    };
+    Ok(if pair.base.ticker() == asset {
+        base
+    } else if pair.quote.ticker() == asset {
+        quote
+    } else {
+        dec!(0)
+    })
+}
+
+/// Income per asset with its yield up to `now`, by ticker
+///
+/// The average balance weighs every balance by how long it was held, from
+/// the first row adding the asset until `now`, so deposits made late in
+/// the period don't dilute the yield.
+pub fn by_asset(trades: &[Trade], now: OffsetDateTime) -> Result<BTreeMap<String, AssetIncome>> {
+    let mut trades: Vec<&Trade> = trades.iter().collect();
+    sort_chronologically(&mut trades);
+
+    let mut incomes: BTreeMap<String, AssetIncome> = BTreeMap::new();
+    for t in trades.iter().filter(|t| t.side == Side::Income) {
+        let i = incomes
+            .entry(t.pair.asset(t.side)?.ticker().to_string())
+            .or_default();
+        i.income += t.amount;
+        i.value += t.amount * t.price;
+    }
+
+    for (asset, i) in incomes.iter_mut() {
+        let mut balance = dec!(0);
+        let mut held_since: Option<OffsetDateTime> = None;
+        let mut last = now;
+        // balance × seconds
+        let mut weighted = dec!(0);
+        for t in trades.iter().filter(|t| t.created_at <= now) {
+            let change = balance_change(t, asset)?;
+            if change.is_zero() {
+                continue;
+            }
+            if held_since.is_some() {
+                weighted += balance * Decimal::from((t.created_at - last).whole_seconds());
+            } else {
+                held_since = Some(t.created_at);
+            }
+            balance += change;
+            last = t.created_at;
+        }
+        let Some(since) = held_since else {
+            continue;
+        };
+        weighted += balance * Decimal::from((now - last).whole_seconds());
+        let seconds = Decimal::from((now - since).whole_seconds());
+        i.days = (now - since).whole_days();
+        if seconds > dec!(0) {
+            i.avg_balance = weighted / seconds;
+        }
+        if i.avg_balance > dec!(0) {
+            i.apr = Some(i.income / i.avg_balance * SECONDS_PER_YEAR / seconds);
+        }
+    }
+    Ok(incomes)
+}
+
+/// Print the income of a portfolio per asset with its annualized yield
+pub fn run(name: &str, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let (config, trades) = parse_csv_file(settings.path_for(name))?;
+    let base = config.base_currency;
+    let incomes = by_asset(&trades, settings.clock.now())?;
+    if incomes.is_empty() {
+        reporter.print("No income recorded");
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.add_row(row![
+        "Asset",
+        "Income",
+        "Income value",
+        "Avg balance",
+        "Days",
+        "APR"
+    ]);
+    let mut total = dec!(0);
+    for (asset, i) in &incomes {
+        table.add_row(row![
+            asset,
+            r->i.income.normalize().separate_with_commas(),
+            r->format!("{} {}", i.value.round_dp(2).normalize().separate_with_commas(), base),
+            r->i.avg_balance.round_dp(2).normalize().separate_with_commas(),
+            r->i.days,
+            r->match i.apr {
+                Some(apr) => format!("{:.2}%", apr * dec!(100)),
+                None => "-".to_string(),
+            }
+        ]);
+        total += i.value;
+    }
+    reporter.table(&table)?;
+    reporter.print(&format!(
+        "Total income:\t{} {}",
+        total.round_dp(2).normalize().separate_with_commas(),
+        base
+    ));
+    reporter.print("APR is the income over the average balance since the asset was first held");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::transactions_from;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_by_asset_annualizes_income_over_average_balance() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704067200,USDC/USD,BUY,1000,1,0
+1719792000,USDC,INCOME,10,1,0
+1719792000,USDC/USD,BUY,1000,1,0
+1704067200,ETH,DEPOSIT,1,2000,0
+",
+        );
+        // a year later: 1000 USDC for the first half, 2010 for the second
+        let now = datetime!(2025-01-01 00:00 UTC);
+        let incomes = by_asset(&trades, now).unwrap();
+        assert_eq!(incomes.keys().collect::<Vec<_>>(), vec!["USDC"]);
+
+        let usdc = &incomes["USDC"];
+        assert_eq!(
+            (usdc.income, usdc.value, usdc.days),
+            (dec!(10), dec!(10), 366)
+        );
+        assert_eq!(usdc.avg_balance.round_dp(2), dec!(1507.76));
+        assert_eq!(usdc.apr.unwrap().round_dp(5), dec!(0.00661));
+    }
+}
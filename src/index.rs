@@ -0,0 +1,227 @@
+use crate::holdings;
+use crate::portfolio::PortfolioEntry;
+use crate::settings::Settings;
+use crate::trade::{PortfolioKind, parse_csv_file, read_csv_config};
+use crate::warnings;
+use crate::workspace::sha256_hex;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const INDEX_FILE: &str = "index.json";
+const INDEX_VERSION: u32 = 1;
+
+/// Cached facts about one portfolio file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IndexEntry {
+    pub rows: usize,
+    /// Modification time, nanoseconds since the unix epoch
+    pub modified: u64,
+    pub size: u64,
+    pub checksum: String,
+    pub base_currency: String,
+    /// Parse error of the file, `None` when all trades are valid
+    pub error: Option<String>,
+}
+
+impl IndexEntry {
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Small per-workspace index, so commands reading many portfolios can skip
+/// re-parsing files which didn't change since the last run.
+///
+/// Stored in the state dir, entries are keyed by portfolio name. A file is
+/// considered unchanged while its size and modification time match.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct WorkspaceIndex {
+    pub version: u32,
+    pub entries: BTreeMap<String, IndexEntry>,
+}
+
+fn index_path(settings: &Settings) -> PathBuf {
+    settings.state_dir().join(INDEX_FILE)
+}
+
+fn file_stamp(path: &Path) -> Result<(u64, u64)> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_nanos() as u64;
+    Ok((modified, metadata.len()))
+}
+
+/// Parse the file and build a fresh index entry
+pub fn index_file(path: &Path) -> Result<IndexEntry> {
+    let (modified, size) = file_stamp(path)?;
+    let checksum = sha256_hex(&std::fs::read(path)?);
+    let parsed = read_csv_config(path).and_then(|config| match config.kind {
+        PortfolioKind::Trades => parse_csv_file(path).map(|(c, trades)| (c, trades.len())),
+        PortfolioKind::Holdings => holdings::load(path).map(|(c, rows)| (c, rows.len())),
+    });
+    let entry = match parsed {
+        Ok((config, rows)) => IndexEntry {
+            rows,
+            modified,
+            size,
+            checksum,
+            base_currency: config.base_currency,
+            error: None,
+        },
+        Err(e) => IndexEntry {
+            rows: 0,
+            modified,
+            size,
+            checksum,
+            base_currency: String::new(),
+            error: Some(e.to_string()),
+        },
+    };
+    Ok(entry)
+}
+
+impl WorkspaceIndex {
+    /// Load the index, a missing or unreadable index is treated as empty
+    pub fn load(settings: &Settings) -> Self {
+        std::fs::read(index_path(settings))
+            .ok()
+            .and_then(|data| serde_json::from_slice::<WorkspaceIndex>(&data).ok())
+            .filter(|index| index.version == INDEX_VERSION)
+            .unwrap_or_else(|| WorkspaceIndex {
+                version: INDEX_VERSION,
+                entries: BTreeMap::new(),
+            })
+    }
+
+    pub fn save(&self, settings: &Settings) -> Result<()> {
+        let path = index_path(settings);
+        std::fs::create_dir_all(settings.state_dir())?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Bring the index up to date with the given portfolio files,
+    /// re-parsing only changed files. Returns true when anything changed.
+    pub fn refresh(&mut self, portfolios: &[PortfolioEntry]) -> Result<bool> {
+        let mut changed = false;
+
+        for p in portfolios {
+            let (modified, size) = file_stamp(&p.path)?;
+            let up_to_date = self
+                .entries
+                .get(&p.name)
+                .is_some_and(|e| e.modified == modified && e.size == size);
+            if !up_to_date {
+                self.entries.insert(p.name.clone(), index_file(&p.path)?);
+                changed = true;
+            }
+        }
+
+        // forget removed portfolios
+        let before = self.entries.len();
+        self.entries
+            .retain(|name, _| portfolios.iter().any(|p| &p.name == name));
+        changed |= self.entries.len() != before;
+
+        Ok(changed)
+    }
+}
+
+/// Re-index one portfolio after a command wrote to it
+///
+/// The portfolio is already written, and the index is only a cache: when
+/// updating it fails that's a warning, `refresh` re-indexes the file on the
+/// next run as its modification time no longer matches.
+pub fn record_write(name: &str, settings: &Settings) {
+    if let Err(e) = update_entry(name, settings) {
+        warnings::warn(format!("can't update the portfolio index: {:#}", e));
+    }
+}
+
+fn update_entry(name: &str, settings: &Settings) -> Result<()> {
+    let mut index = WorkspaceIndex::load(settings);
+    index
+        .entries
+        .insert(name.to_string(), index_file(&settings.path_for(name))?);
+    index.save(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::list_entries;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use tempfile::TempDir;
+
+    const CSV: &str = "# base_currency: EUR
+created_at,pair,side,amount,price,fee
+1704883200,BTC/EUR,BUY,1.0,40000.00,7.50
+1710460800,BTC/EUR,BUY,3,20000.00,10.00
+";
+
+    #[test]
+    fn test_refresh_indexes_rows_and_validation_status() {
+        let dir = TempDir::new().unwrap();
+        create_test_csv(&dir, "good", CSV);
+        create_test_csv(&dir, "bad", "created_at,pair\n1,NOPE\n");
+        let settings = create_test_settings(dir.path().to_path_buf());
+
+        let mut index = WorkspaceIndex::load(&settings);
+        assert!(index.refresh(&list_entries(&settings).unwrap()).unwrap());
+
+        let good = &index.entries["good"];
+        assert_eq!(good.rows, 2);
+        assert_eq!(good.base_currency, "EUR");
+        assert!(good.is_valid());
+        assert_eq!(good.checksum, sha256_hex(CSV.as_bytes()));
+        assert!(!index.entries["bad"].is_valid());
+
+        // nothing changed on disk, so nothing to re-parse
+        assert!(!index.refresh(&list_entries(&settings).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_save_load_roundtrip_and_removed_files() {
+        let dir = TempDir::new().unwrap();
+        let path = create_test_csv(&dir, "good", CSV);
+        let settings = create_test_settings(dir.path().to_path_buf());
+
+        let mut index = WorkspaceIndex::load(&settings);
+        index.refresh(&list_entries(&settings).unwrap()).unwrap();
+        index.save(&settings).unwrap();
+        assert_eq!(WorkspaceIndex::load(&settings), index);
+
+        std::fs::remove_file(path).unwrap();
+        assert!(index.refresh(&list_entries(&settings).unwrap()).unwrap());
+        assert!(index.entries.is_empty());
+    }
+
+    #[test]
+    fn test_record_write_updates_single_entry() {
+        let dir = TempDir::new().unwrap();
+        create_test_csv(&dir, "good", CSV);
+        let settings = create_test_settings(dir.path().to_path_buf());
+
+        record_write("good", &settings);
+        assert_eq!(WorkspaceIndex::load(&settings).entries["good"].rows, 2);
+    }
+
+    #[test]
+    fn test_record_write_failure_is_not_an_error() {
+        let dir = TempDir::new().unwrap();
+        create_test_csv(&dir, "good", CSV);
+        let settings = create_test_settings(dir.path().to_path_buf());
+        // a file where the state dir should be
+        std::fs::write(settings.state_dir(), "").unwrap();
+
+        record_write("good", &settings);
+        assert!(WorkspaceIndex::load(&settings).entries.is_empty());
+    }
+}
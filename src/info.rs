@@ -0,0 +1,352 @@
+use crate::cli::Cli;
+use crate::index::WorkspaceIndex;
+use crate::portfolio::list_entries;
+use crate::quote::{self, GECKO_TICKER_IDS};
+use crate::reporter::Reporter;
+use crate::settings::{Settings, config_layers, config_path};
+use crate::trade::{PortfolioKind, for_each_trade, read_csv_config};
+use anyhow::Result;
+use prettytable::{Table, row};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use thousands::Separable;
+use time::OffsetDateTime;
+
+/// Entries of the state dir that commands rebuild when they're missing
+const CACHE_ENTRIES: [&str; 5] = ["positions", "prices", "fx", "supply.json", "index.json"];
+/// Entries of the state dir with the value history `snapshot` and `watch` record
+const SNAPSHOT_ENTRIES: [&str; 2] = ["snapshots", "total_snapshots.csv"];
+
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+fn file_age(path: &Path) -> Option<Duration> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    SystemTime::now().duration_since(modified).ok()
+}
+
+/// `1.5 MiB`, bytes below a KiB
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Total size in bytes and number of files below `dir`
+fn dir_usage(dir: &Path) -> (u64, usize) {
+    let Ok(entries) = dir.read_dir() else {
+        return (0, 0);
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .fold((0, 0), |(size, count), entry| {
+            let path = entry.path();
+            if path.is_dir() {
+                let (s, c) = dir_usage(&path);
+                (size + s, count + c)
+            } else {
+                let len = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                (size + len, count + 1)
+            }
+        })
+}
+
+/// Size in bytes and number of files of `path`, a file or a dir
+fn usage(path: &Path) -> (u64, usize) {
+    match std::fs::metadata(path) {
+        Ok(m) if m.is_file() => (m.len(), 1),
+        Ok(_) => dir_usage(path),
+        Err(_) => (0, 0),
+    }
+}
+
+/// Portfolios, trades and disk usage of a workspace, see `workspace`
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Survey {
+    trade_portfolios: usize,
+    holdings_portfolios: usize,
+    /// Portfolios whose rows can't be read, left out of the counts below
+    unreadable: Vec<String>,
+    trades: usize,
+    first: Option<OffsetDateTime>,
+    last: Option<OffsetDateTime>,
+    /// Bytes and files of the portfolio files
+    data: (u64, usize),
+    snapshots: (u64, usize),
+    cache: (u64, usize),
+    /// The rest of the state dir: alerts, journal, tags, import profiles, ...
+    state: (u64, usize),
+}
+
+fn survey(settings: &Settings) -> Result<Survey> {
+    let mut s = Survey::default();
+    for entry in list_entries(settings)? {
+        let (size, files) = usage(&entry.path);
+        s.data = (s.data.0 + size, s.data.1 + files);
+        let kind = read_csv_config(&entry.path).map(|c| c.kind);
+        if matches!(kind, Ok(PortfolioKind::Holdings)) {
+            s.holdings_portfolios += 1;
+            continue;
+        }
+        let (mut rows, mut first, mut last) = (0, s.first, s.last);
+        let read = for_each_trade(&entry.path, |t| {
+            rows += 1;
+            first = Some(first.map_or(t.created_at, |f| f.min(t.created_at)));
+            last = Some(last.map_or(t.created_at, |l| l.max(t.created_at)));
+            Ok(())
+        });
+        match read {
+            Ok(_) => {
+                s.trade_portfolios += 1;
+                s.trades += rows;
+                (s.first, s.last) = (first, last);
+            }
+            Err(_) => s.unreadable.push(entry.name),
+        }
+    }
+
+    let Ok(entries) = settings.state_dir().read_dir() else {
+        return Ok(s);
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let part = if CACHE_ENTRIES.contains(&name.as_str()) {
+            &mut s.cache
+        } else if SNAPSHOT_ENTRIES.contains(&name.as_str()) {
+            &mut s.snapshots
+        } else {
+            &mut s.state
+        };
+        let (size, files) = usage(&entry.path());
+        *part = (part.0 + size, part.1 + files);
+    }
+    Ok(s)
+}
+
+/// `info --workspace`: portfolios, trades, their time span and disk usage,
+/// e.g. before a backup
+pub fn workspace(reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let s = survey(settings)?;
+    let date = |t: Option<OffsetDateTime>| t.map_or("-".to_string(), |t| t.date().to_string());
+    reporter.print(&format!(
+        "portfolios:     {} ({} with trades, {} holdings-only)",
+        s.trade_portfolios + s.holdings_portfolios + s.unreadable.len(),
+        s.trade_portfolios,
+        s.holdings_portfolios
+    ));
+    if !s.unreadable.is_empty() {
+        reporter.print(&format!(
+            "unreadable:     {}, see `doctor`",
+            s.unreadable.join(", ")
+        ));
+    }
+    reporter.print(&format!(
+        "trades:         {}",
+        s.trades.separate_with_commas()
+    ));
+    reporter.print(&format!(
+        "activity:       {} to {}",
+        date(s.first),
+        date(s.last)
+    ));
+    match file_age(Path::new(GECKO_TICKER_IDS)) {
+        Some(age) => reporter.print(&format!(
+            "ticker table:   {} (updated {} ago)",
+            GECKO_TICKER_IDS,
+            format_age(age)
+        )),
+        None => reporter.print(&format!("ticker table:   {} (missing)", GECKO_TICKER_IDS)),
+    }
+
+    reporter.print(&format!(
+        "data dir:       {}",
+        settings.portfolio_dir.display()
+    ));
+    reporter.print(&format!(
+        "state dir:      {}",
+        settings.state_dir().display()
+    ));
+
+    let mut table = Table::new();
+    table.add_row(row!["Storage", "Files", "Size"]);
+    let parts = [
+        ("portfolios", s.data),
+        ("snapshots", s.snapshots),
+        ("cache", s.cache),
+        ("other state", s.state),
+    ];
+    for (label, (size, files)) in parts {
+        table.add_row(row![label, r->files, r->format_size(size)]);
+    }
+    let size: u64 = parts.iter().map(|(_, (size, _))| size).sum();
+    let files: usize = parts.iter().map(|(_, (_, files))| files).sum();
+    table.add_row(row!["total", r->files, r->format_size(size)]);
+    reporter.table(&table)?;
+    reporter.print(
+        "The cache (price history, FX rates, supply, positions, index) is rebuilt when deleted",
+    );
+    Ok(())
+}
+
+/// Print version and environment details, meant to be attached to bug reports
+pub fn run(
+    cli: &Cli,
+    check_network: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    reporter.print(&format!(
+        "{} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    ));
+    reporter.print(&format!(
+        "os:             {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    ));
+
+    let config = config_path();
+    let exists = if config.is_file() { "" } else { " (missing)" };
+    reporter.print(&format!("config file:    {}{}", config.display(), exists));
+    reporter.print(&format!(
+        "config layers:  {}",
+        config_layers(cli).join(" → ")
+    ));
+    reporter.print(&format!(
+        "data dir:       {}",
+        settings.portfolio_dir.display()
+    ));
+    reporter.print(&format!("base currency:  {}", settings.base_currency));
+    reporter.print(&format!("quote provider: {}", settings.quote_provider));
+    reporter.print(&format!("http timeout:   {}s", settings.http_timeout_secs));
+
+    match file_age(Path::new(GECKO_TICKER_IDS)) {
+        Some(age) => reporter.print(&format!(
+            "ticker table:   {} (updated {} ago)",
+            GECKO_TICKER_IDS,
+            format_age(age)
+        )),
+        None => reporter.print(&format!("ticker table:   {} (missing)", GECKO_TICKER_IDS)),
+    }
+
+    let index = WorkspaceIndex::load(settings);
+    let (size, files) = dir_usage(&settings.state_dir());
+    reporter.print(&format!(
+        "cache:          {} indexed portfolio(s), {} file(s), {} bytes in {}",
+        index.entries.len(),
+        files,
+        size,
+        settings.state_dir().display()
+    ));
+
+    if check_network {
+        match quote::ping() {
+            Ok(rtt) => reporter.print(&format!(
+                "connectivity:   {} ok ({} ms)",
+                settings.quote_provider,
+                rtt.as_millis()
+            )),
+            Err(e) => reporter.print(&format!(
+                "connectivity:   {} failed: {}",
+                settings.quote_provider, e
+            )),
+        }
+    } else {
+        reporter.print("connectivity:   skipped");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0, "0s")]
+    #[case(59, "59s")]
+    #[case(60, "1m")]
+    #[case(7200, "2h")]
+    #[case(3 * 86400 + 5, "3d")]
+    fn test_format_age(#[case] secs: u64, #[case] expected: &str) {
+        assert_eq!(format_age(Duration::from_secs(secs)), expected);
+    }
+
+    #[test]
+    fn test_dir_usage_counts_nested_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        std::fs::write(dir.path().join("x"), "123").unwrap();
+        std::fs::write(dir.path().join("a/b/y"), "45").unwrap();
+        assert_eq!(dir_usage(dir.path()), (5, 2));
+        assert_eq!(dir_usage(&dir.path().join("missing")), (0, 0));
+    }
+
+    #[rstest]
+    #[case(0, "0 B")]
+    #[case(1023, "1023 B")]
+    #[case(1536, "1.5 KiB")]
+    #[case(5 * 1024 * 1024, "5.0 MiB")]
+    fn test_format_size(#[case] bytes: u64, #[case] expected: &str) {
+        assert_eq!(format_size(bytes), expected);
+    }
+
+    #[test]
+    fn test_survey_counts_portfolios_trades_and_state_by_kind() {
+        use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+        use time::macros::datetime;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        create_test_csv(
+            &dir,
+            "main",
+            "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,0
+1704067200,ETH/USD,BUY,1,2000,0
+",
+        );
+        create_test_csv(
+            &dir,
+            "cold",
+            "# base_currency: USD\n# kind: holdings\nasset,amount,cost\nBTC,1,\n",
+        );
+        create_test_csv(&dir, "broken", "created_at,pair\nx,y\n");
+        let state = dir.path().join(".csvpt");
+        std::fs::create_dir_all(state.join("positions")).unwrap();
+        std::fs::create_dir_all(state.join("snapshots")).unwrap();
+        std::fs::write(state.join("positions/main.json"), "{}").unwrap();
+        std::fs::write(state.join("index.json"), "{}").unwrap();
+        std::fs::write(state.join("snapshots/main.csv"), "ts,value\n").unwrap();
+        std::fs::write(state.join("alerts.json"), "[]").unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+
+        let s = survey(&settings).unwrap();
+        assert_eq!((s.trade_portfolios, s.holdings_portfolios), (1, 1));
+        assert_eq!(s.unreadable, vec!["broken"]);
+        assert_eq!(s.trades, 2);
+        assert_eq!(s.first, Some(datetime!(2024-01-01 0:00 UTC)));
+        assert_eq!(s.last, Some(datetime!(2024-01-10 10:40 UTC)));
+        assert_eq!(s.data.1, 3);
+        assert_eq!(s.cache, (4, 2));
+        assert_eq!(s.snapshots, (9, 1));
+        assert_eq!(s.state, (2, 1));
+    }
+}
@@ -0,0 +1,41 @@
+//! Ctrl-C handling
+//!
+//! The first Ctrl-C asks long operations to stop at their next safe point:
+//! before the next network request, before an import writes the portfolio
+//! file, between two polls of `watch`. Files are only ever replaced by a
+//! rename, so an operation stopped this way leaves them as they were. A
+//! second Ctrl-C exits right away.
+
+use crate::warnings;
+use anyhow::{Result, bail};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Exit status of a run stopped by Ctrl-C, 128 + SIGINT like shells report
+pub const EXIT_CODE: i32 = 130;
+
+/// Install the Ctrl-C handler, once at startup
+pub fn install() {
+    let handler = || {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(EXIT_CODE);
+        }
+    };
+    // without it Ctrl-C stops the process right away, as it always did
+    if let Err(e) = ctrlc::set_handler(handler) {
+        warnings::warn(format!("couldn't install the Ctrl-C handler: {}", e));
+    }
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Fails once Ctrl-C was pressed, called where work can be dropped cleanly
+pub fn check() -> Result<()> {
+    if interrupted() {
+        bail!("interrupted");
+    }
+    Ok(())
+}
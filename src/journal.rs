@@ -0,0 +1,143 @@
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{find_by_id, parse_csv_file};
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use time::OffsetDateTime;
+
+/// Free-text note on one trade, e.g. why it was made
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// `Trade::id` of the trade
+    pub tx: String,
+    #[serde(with = "time::serde::timestamp")]
+    pub written_at: OffsetDateTime,
+    pub text: String,
+}
+
+/// `<state dir>/journal/<portfolio>.json`, entries in the order written
+fn journal_path(name: &str, settings: &Settings) -> PathBuf {
+    settings
+        .state_dir()
+        .join("journal")
+        .join(name)
+        .with_extension("json")
+}
+
+pub fn load(name: &str, settings: &Settings) -> Result<Vec<Entry>> {
+    let path = journal_path(name, settings);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+fn save(name: &str, entries: &[Entry], settings: &Settings) -> Result<()> {
+    let path = journal_path(name, settings);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(path, serde_json::to_vec_pretty(entries)?)?;
+    Ok(())
+}
+
+/// Entries by trade id
+pub fn by_tx(entries: Vec<Entry>) -> BTreeMap<String, Vec<Entry>> {
+    let mut by_tx: BTreeMap<String, Vec<Entry>> = BTreeMap::new();
+    for entry in entries {
+        by_tx.entry(entry.tx.clone()).or_default().push(entry);
+    }
+    by_tx
+}
+
+/// Move the entries of trade `from` to each of the trades `to`, after the
+/// trade was rewritten, e.g. split
+pub(crate) fn carry_over(name: &str, from: &str, to: &[String], settings: &Settings) -> Result<()> {
+    let entries = load(name, settings)?;
+    if !entries.iter().any(|e| e.tx == from) {
+        return Ok(());
+    }
+    let mut carried = Vec::new();
+    for entry in entries {
+        if entry.tx != from {
+            carried.push(entry);
+            continue;
+        }
+        carried.extend(to.iter().map(|tx| Entry {
+            tx: tx.clone(),
+            ..entry.clone()
+        }));
+    }
+    save(name, &carried, settings)
+}
+
+/// Note `text` on the trade with id (or id prefix) `tx`
+pub fn add(
+    name: &str,
+    tx: &str,
+    text: &str,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<Entry> {
+    let (_, trades) = parse_csv_file(settings.path_for(name))?;
+    let trade = find_by_id(&trades, tx)?;
+    if text.trim().is_empty() {
+        bail!("journal entry is empty");
+    }
+    let entry = Entry {
+        tx: trade.id(),
+        written_at: settings.clock.now().replace_nanosecond(0)?,
+        text: text.trim().to_string(),
+    };
+    let mut entries = load(name, settings)?;
+    entries.push(entry.clone());
+    save(name, &entries, settings)?;
+    reporter.status(&format!(
+        "📝 Added journal entry to trade {} in '{}'",
+        entry.tx, name
+    ));
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::Silent;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_add_links_entry_to_trade_id() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        create_test_csv(
+            &dir,
+            "pf",
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,0
+1705056000,BTC/USD,SELL,0.5,50000,0
+",
+        );
+        let (_, trades) = parse_csv_file(settings.path_for("pf")).unwrap();
+        let sell = trades[1].id();
+
+        let reporter = Silent;
+        let entry = add(
+            "pf",
+            &sell[..5],
+            " took profit at 50k ",
+            &reporter,
+            &settings,
+        )
+        .unwrap();
+        assert_eq!(entry.tx, sell);
+        add("pf", &sell, "second thoughts", &reporter, &settings).unwrap();
+        assert!(add("pf", "zzzz", "no such trade", &reporter, &settings).is_err());
+        assert!(add("pf", "", "matches every trade", &reporter, &settings).is_err());
+
+        let journal = by_tx(load("pf", &settings).unwrap());
+        let texts: Vec<&str> = journal[&sell].iter().map(|e| e.text.as_str()).collect();
+        assert_eq!(texts, vec!["took profit at 50k", "second thoughts"]);
+        assert!(!journal.contains_key(&trades[0].id()));
+    }
+}
@@ -0,0 +1,198 @@
+//! Plain text accounting journals, for ledger-cli and hledger
+//!
+//! Every row becomes one transaction with a posting per currency leg. Lots
+//! bought or received carry their unit cost as a `{cost}` annotation, sales
+//! into the base currency are annotated with the average cost of what was
+//! sold and book the difference as a capital gain.
+
+use crate::trade::{Side, Trade};
+use anyhow::Result;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fmt::Write;
+use time::macros::format_description;
+
+const TRANSFERS: &str = "Equity:Transfers";
+const FEES: &str = "Expenses:Fees";
+const INCOME: &str = "Income:Rewards";
+const GAINS: &str = "Income:Capital Gains";
+
+/// Commodity symbol, quoted unless it's letters only (`"1INCH"`)
+fn commodity(ticker: &str) -> String {
+    if ticker.chars().all(|c| c.is_ascii_alphabetic()) {
+        ticker.to_string()
+    } else {
+        format!("\"{}\"", ticker)
+    }
+}
+
+fn amount(value: Decimal, ticker: &str) -> String {
+    format!("{} {}", value.normalize(), commodity(ticker))
+}
+
+/// Amount of a lot with its unit cost, no annotation for the cost currency itself
+fn lot(value: Decimal, ticker: &str, unit_cost: Decimal, cost: &str) -> String {
+    if ticker == cost {
+        amount(value, ticker)
+    } else {
+        format!("{} {{{}}}", amount(value, ticker), amount(unit_cost, cost))
+    }
+}
+
+/// Quantity and cost in the base currency of everything held
+#[derive(Default)]
+struct Basis(HashMap<String, (Decimal, Decimal)>);
+
+impl Basis {
+    fn add(&mut self, ticker: &str, qty: Decimal, cost: Decimal) {
+        let entry = self.0.entry(ticker.to_string()).or_default();
+        entry.0 += qty;
+        entry.1 += cost;
+    }
+
+    /// Average unit cost, 1 for the base currency
+    fn unit_cost(&self, ticker: &str, base: &str) -> Decimal {
+        if ticker == base {
+            return Decimal::ONE;
+        }
+        match self.0.get(ticker) {
+            Some((qty, cost)) if !qty.is_zero() => *cost / *qty,
+            _ => Decimal::ZERO,
+        }
+    }
+
+    /// Take `qty` out at the average cost, returning the cost taken
+    fn remove(&mut self, ticker: &str, qty: Decimal, base: &str) -> Decimal {
+        let cost = self.unit_cost(ticker, base) * qty;
+        self.add(ticker, -qty, -cost);
+        cost
+    }
+}
+
+/// Journal of the rows of one portfolio, its assets under `Assets:<name>`
+pub fn journal(name: &str, base: &str, trades: &[Trade]) -> Result<String> {
+    let account = format!("Assets:{}", name);
+    let mut basis = Basis::default();
+    let mut out = String::new();
+    for t in trades {
+        let date = t
+            .created_at
+            .format(format_description!("[year]-[month]-[day]"))?;
+        writeln!(out, "{} * {} {}", date, t.side, t.pair)?;
+        writeln!(out, "    ; id: {}", t.id())?;
+        if let Some(source) = &t.source {
+            writeln!(out, "    ; source: {}", source)?;
+        }
+        let mut post =
+            |account: &str, amount: String| writeln!(out, "    {:<30}  {}", account, amount);
+
+        if t.side.is_cash() {
+            let asset = t.pair.asset(t.side)?.ticker();
+            let value = t.amount * t.price;
+            match t.side {
+                Side::Deposit | Side::Income => {
+                    basis.add(asset, t.amount, value);
+                    let from = if t.side == Side::Deposit {
+                        TRANSFERS
+                    } else {
+                        INCOME
+                    };
+                    post(&account, lot(t.amount, asset, t.price, base))?;
+                    post(from, amount(-value, base))?;
+                }
+                _ => {
+                    let unit_cost = basis.unit_cost(asset, base);
+                    let cost = basis.remove(asset, t.amount, base);
+                    let to = if t.side == Side::Withdraw {
+                        TRANSFERS
+                    } else {
+                        FEES
+                    };
+                    post(&account, lot(-t.amount, asset, unit_cost, base))?;
+                    post(to, amount(cost, base))?;
+                }
+            }
+        } else {
+            let pair = t.pair.trading_pair(t.side)?;
+            let (asset, quote) = (pair.base.ticker(), pair.quote.ticker());
+            let tx = t.to_tx()?;
+            if t.side == Side::Buy {
+                let cost = basis.remove(quote, tx.sell_size, base);
+                basis.add(asset, tx.buy_size, cost);
+                post(
+                    &account,
+                    lot(tx.buy_size, asset, tx.sell_size / tx.buy_size, quote),
+                )?;
+                post(&account, amount(-tx.sell_size, quote))?;
+            } else if quote == base {
+                let unit_cost = basis.unit_cost(asset, base);
+                let cost = basis.remove(asset, tx.sell_size, base);
+                basis.add(quote, tx.buy_size, tx.buy_size);
+                post(&account, lot(-tx.sell_size, asset, unit_cost, base))?;
+                post(&account, amount(tx.buy_size, quote))?;
+                let gain = tx.buy_size - cost;
+                if !gain.is_zero() {
+                    post(GAINS, amount(-gain, base))?;
+                }
+            } else {
+                // swaps carry the cost over, the gain is only booked when sold for the base
+                let cost = basis.remove(asset, tx.sell_size, base);
+                basis.add(quote, tx.buy_size, cost);
+                post(
+                    &account,
+                    format!(
+                        "{} @ {}",
+                        amount(-tx.sell_size, asset),
+                        amount(t.price, quote)
+                    ),
+                )?;
+                post(&account, amount(tx.buy_size, quote))?;
+                if !t.fee.is_zero() {
+                    post(FEES, amount(t.fee, quote))?;
+                }
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::transactions_from;
+
+    #[test]
+    fn test_journal_annotates_lots_and_books_gains() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee,source
+1704067200,USD,DEPOSIT,30000,1,0,manual
+1704883200,BTC/USD,BUY,0.5,40000,20,
+1710460800,BTC/USD,SELL,0.25,60000,10,
+1711929600,ETH,INCOME,0.01,3000,0,
+1712016000,ETH/BTC,SELL,0.01,0.05,0,
+",
+        );
+        let journal = journal("main", "USD", &trades).unwrap();
+        let entries: Vec<&str> = journal.split("\n\n").collect();
+        assert_eq!(
+            entries[0],
+            "2024-01-01 * Deposit USD
+    ; id: 22b986c4
+    ; source: manual
+    Assets:main                     30000 USD
+    Equity:Transfers                -30000 USD"
+        );
+        // the fee is part of the cost of the lot
+        assert!(entries[1].contains("Assets:main                     0.5 BTC {40040 USD}\n"));
+        assert!(entries[1].ends_with("Assets:main                     -20020 USD"));
+        // 0.25 BTC at 40040 sold for 14990 after fees
+        assert!(entries[2].contains("    Assets:main                     -0.25 BTC {40040 USD}\n"));
+        assert!(entries[2].contains("    Assets:main                     14990 USD\n"));
+        assert!(entries[2].ends_with("    Income:Capital Gains            -4980 USD"));
+        assert!(entries[3].contains("0.01 ETH {3000 USD}"));
+        assert!(entries[3].ends_with("Income:Rewards                  -30 USD"));
+        assert!(entries[4].contains("-0.01 ETH @ 0.05 BTC"));
+        assert_eq!(commodity("1INCH"), "\"1INCH\"");
+    }
+}
@@ -1,10 +1,86 @@
+pub mod alert;
+pub mod anonymize;
+pub mod assertions;
+pub mod beancount;
+pub mod behavior;
+pub mod benchmark;
+pub mod canonical;
+pub mod chart;
 pub mod cli;
+pub mod clock;
+pub mod close;
+pub mod config_check;
+pub mod config_show;
+pub mod confirm;
+pub mod conflict;
+pub mod context;
+pub mod contribution;
 pub mod currency;
+pub mod dca;
+pub mod doctor;
+pub mod dust;
+pub mod export;
+pub mod fees;
+pub mod format;
+pub mod fx;
+pub mod fx_effect;
+pub mod history;
+pub mod holdings;
+pub mod http;
+pub mod import;
+pub mod import_profile;
+pub mod inbox;
+pub mod income;
+pub mod index;
+pub mod info;
+pub mod interrupt;
+pub mod journal;
+pub mod ledger;
+pub mod lint;
+pub mod lots;
+pub mod manual_price;
+pub mod math;
+pub mod monthly;
+pub mod ofx;
+pub mod output;
+pub mod parquet;
+pub mod period;
+pub mod piped;
 pub mod portfolio;
+pub mod position;
+pub mod position_cache;
+pub mod price_history;
+pub mod qif;
 pub mod quote;
+pub mod refresh;
+pub mod report;
+pub mod report_diff;
+pub mod reporter;
+pub mod scenario;
+pub mod serve;
 pub mod settings;
+pub mod snapshot;
+pub mod split;
+pub mod sqlite;
+pub mod statement;
+pub mod stats;
+pub mod stops;
+pub mod supply;
+pub mod tags;
+pub mod tax;
+pub mod tickers;
 pub mod trade;
 pub mod tx;
+pub mod tx_template;
+pub mod universal;
+pub mod usage;
+pub mod warnings;
+pub mod watch;
+pub mod widget;
+pub mod wizard;
+pub mod workspace;
+pub mod writer;
+pub mod xlsx;
 
 // testing
 #[cfg(test)]
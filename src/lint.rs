@@ -0,0 +1,377 @@
+//! Lint rules over trade files
+//!
+//! Rows can parse and still be wrong: typed in out of order, with a fee
+//! that is really the total, at a price off by a digit, or for nothing at
+//! all. `doctor` checks every trades portfolio against these rules, and
+//! `add-tx` checks the new row when `lint.on_add_tx` is set. Each rule has
+//! a severity in the `[lint]` table of the config:
+//! ```toml
+//! [lint]
+//! high_fee = "error"
+//! price_deviation = "ignore"
+//! on_add_tx = true
+//! ```
+
+use crate::math;
+use crate::portfolio::list_entries;
+use crate::price_history;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{Instrument, PortfolioKind, Side, Trade, extract_csv_config, trades_reader};
+use anyhow::{Context, Result, bail};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use time::OffsetDateTime;
+
+/// Fees above this share of the amount × price of a buy or sell are flagged
+const MAX_FEE_PCT: Decimal = dec!(10);
+/// Prices further than this from the close of their day are flagged
+const MAX_PRICE_DEVIATION_PCT: Decimal = dec!(50);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Ignore,
+    #[default]
+    Warn,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// A row dated before the row above it
+    OutOfOrder,
+    /// A fee above `MAX_FEE_PCT` of the amount traded
+    HighFee,
+    /// A price more than `MAX_PRICE_DEVIATION_PCT` off the close of its day
+    PriceDeviation,
+    /// A row moving nothing
+    ZeroAmount,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Rule::OutOfOrder => "out_of_order",
+            Rule::HighFee => "high_fee",
+            Rule::PriceDeviation => "price_deviation",
+            Rule::ZeroAmount => "zero_amount",
+        })
+    }
+}
+
+/// Severities of the rules and when they run, the `[lint]` table of the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LintConfig {
+    pub out_of_order: Severity,
+    pub high_fee: Severity,
+    pub price_deviation: Severity,
+    pub zero_amount: Severity,
+    /// Check the row `add-tx` is about to add
+    pub on_add_tx: bool,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            out_of_order: Severity::Warn,
+            high_fee: Severity::Warn,
+            price_deviation: Severity::Warn,
+            zero_amount: Severity::Error,
+            on_add_tx: false,
+        }
+    }
+}
+
+impl LintConfig {
+    pub fn severity(&self, rule: Rule) -> Severity {
+        match rule {
+            Rule::OutOfOrder => self.out_of_order,
+            Rule::HighFee => self.high_fee,
+            Rule::PriceDeviation => self.price_deviation,
+            Rule::ZeroAmount => self.zero_amount,
+        }
+    }
+}
+
+/// A row breaking a rule, `line` counted from 1 in the whole file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub line: usize,
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// A portfolio row read without the checks of `Trade`, which would reject
+/// some of the rows linted here, e.g. zero amounts
+#[derive(Debug, Clone, Deserialize)]
+struct Row {
+    created_at: i64,
+    pair: String,
+    side: Side,
+    amount: Decimal,
+    price: Decimal,
+    fee: Decimal,
+}
+
+impl From<&Trade> for Row {
+    fn from(t: &Trade) -> Self {
+        Row {
+            created_at: t.created_at.unix_timestamp(),
+            pair: t.pair.to_string(),
+            side: t.side,
+            amount: t.amount,
+            price: t.price,
+            fee: t.fee,
+        }
+    }
+}
+
+struct Linter<'a> {
+    settings: &'a Settings,
+    latest: i64,
+    findings: Vec<Finding>,
+}
+
+impl Linter<'_> {
+    fn flag(&mut self, line: usize, rule: Rule, message: String) {
+        let severity = self.settings.lint.severity(rule);
+        if severity != Severity::Ignore {
+            self.findings.push(Finding {
+                line,
+                rule,
+                severity,
+                message,
+            });
+        }
+    }
+
+    fn row(&mut self, line: usize, row: &Row) -> Result<()> {
+        if row.created_at < self.latest {
+            self.flag(
+                line,
+                Rule::OutOfOrder,
+                format!("dated {} before the row above it", date(row.created_at)),
+            );
+        }
+        self.latest = self.latest.max(row.created_at);
+        if row.amount.is_zero() {
+            self.flag(line, Rule::ZeroAmount, "amount is 0".to_string());
+        }
+        let Instrument::Pair(pair) = row.pair.parse()? else {
+            return Ok(());
+        };
+        if row.side.is_cash() {
+            return Ok(());
+        }
+
+        let notional = row.amount * row.price;
+        if let Some(fee_pct) = math::percent_of(row.fee, notional)
+            && fee_pct.value() > MAX_FEE_PCT
+        {
+            self.flag(
+                line,
+                Rule::HighFee,
+                format!(
+                    "fee {} {} is {:.2} of the {} {} traded",
+                    row.fee,
+                    pair.quote,
+                    fee_pct.round_dp(2),
+                    notional.normalize(),
+                    pair.quote
+                ),
+            );
+        }
+
+        if self.settings.lint.price_deviation == Severity::Ignore {
+            return Ok(());
+        }
+        let day = OffsetDateTime::from_unix_timestamp(row.created_at)?.date();
+        if let Some(close) =
+            price_history::known_price_on(&pair.base, pair.quote.ticker(), day, self.settings)
+            && let Some(deviation) = math::change_pct(close, row.price)
+            && deviation.abs().value() > MAX_PRICE_DEVIATION_PCT
+        {
+            self.flag(
+                line,
+                Rule::PriceDeviation,
+                format!(
+                    "price {} {} is {:+.2} off the {} close of {} {}",
+                    row.price,
+                    pair.quote,
+                    deviation.round_dp(2),
+                    day,
+                    close.normalize(),
+                    pair.quote
+                ),
+            );
+        }
+        Ok(())
+    }
+}
+
+fn date(ts: i64) -> String {
+    OffsetDateTime::from_unix_timestamp(ts)
+        .map(|t| t.date().to_string())
+        .unwrap_or_else(|_| ts.to_string())
+}
+
+/// Lint the rows of the portfolio file at `path`, holdings-only files
+/// have none
+fn lint_file<'a, T: AsRef<Path>>(path: T, settings: &'a Settings) -> Result<Linter<'a>> {
+    let mut linter = Linter {
+        settings,
+        latest: i64::MIN,
+        findings: Vec::new(),
+    };
+    let input = std::fs::read_to_string(&path)?;
+    let (config, data) = extract_csv_config(&input)?;
+    if config.kind == PortfolioKind::Holdings {
+        return Ok(linter);
+    }
+    // metadata lines and the header come before the first row
+    let first_line = input[..input.len() - data.len()].lines().count() + 2;
+
+    for (i, row) in trades_reader(data.as_bytes()).deserialize().enumerate() {
+        let line = first_line + i;
+        let row: Row = row.with_context(|| format!("reading line {}", line))?;
+        linter
+            .row(line, &row)
+            .with_context(|| format!("linting line {}", line))?;
+    }
+    Ok(linter)
+}
+
+/// Check the rows of the portfolio file at `path`
+pub fn check<T: AsRef<Path>>(path: T, settings: &Settings) -> Result<Vec<Finding>> {
+    Ok(lint_file(path, settings)?.findings)
+}
+
+/// For `add-tx` with `lint.on_add_tx`: check `trade` as the next row of
+/// the file at `path`, warning about it or failing on rules set to error
+pub fn check_new_row<T: AsRef<Path>>(path: T, trade: &Trade, settings: &Settings) -> Result<()> {
+    // only the new row is reported, the file is `doctor`'s business
+    let mut linter = lint_file(path, settings)?;
+    linter.findings.clear();
+    linter.row(0, &Row::from(trade))?;
+    for finding in &linter.findings {
+        if finding.severity == Severity::Error {
+            bail!(
+                "row not added, it breaks lint rule {}: {} (set lint.{} to warn to allow it)",
+                finding.rule,
+                finding.message,
+                finding.rule
+            );
+        }
+    }
+    for finding in linter.findings {
+        crate::warnings::warn(format!("lint {}: {}", finding.rule, finding.message));
+    }
+    Ok(())
+}
+
+/// For `doctor`: print the findings of every trades portfolio, returning
+/// how many there are and how many of them are errors
+pub fn print_findings(reporter: &dyn Reporter, settings: &Settings) -> Result<(usize, usize)> {
+    let (mut problems, mut errors) = (0, 0);
+    for entry in list_entries(settings)? {
+        match check(&entry.path, settings) {
+            Ok(findings) => {
+                for f in &findings {
+                    let icon = match f.severity {
+                        Severity::Error => "❌",
+                        _ => "⚠️ ",
+                    };
+                    reporter.print(&format!(
+                        "{} {} line {}: {} ({})",
+                        icon, entry.name, f.line, f.message, f.rule
+                    ));
+                }
+                problems += findings.len();
+                errors += findings
+                    .iter()
+                    .filter(|f| f.severity == Severity::Error)
+                    .count();
+            }
+            Err(e) => {
+                reporter.print(&format!("⚠️  can't lint '{}': {:#}", entry.name, e));
+                problems += 1;
+            }
+        }
+    }
+    Ok((problems, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_flags_rows_by_rule_at_their_severity() {
+        let dir = TempDir::new().unwrap();
+        let path = create_test_csv(
+            &dir,
+            "pf",
+            "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,0
+1704800000,BTC/USD,BUY,0.01,41000,50
+1704969600,ETH/USD,BUY,0,2500,0
+1704969600,BTC/USD,SELL,0.5,4600,0
+",
+        );
+        std::fs::create_dir_all(dir.path().join(".csvpt/prices")).unwrap();
+        std::fs::write(
+            dir.path().join(".csvpt/prices/BTC-USD.json"),
+            r#"{"2024-01-11": "46000"}"#,
+        )
+        .unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+
+        let findings = check(&path, &settings).unwrap();
+        let flagged: Vec<(usize, Rule, Severity)> = findings
+            .iter()
+            .map(|f| (f.line, f.rule, f.severity))
+            .collect();
+        assert_eq!(
+            flagged,
+            vec![
+                (4, Rule::OutOfOrder, Severity::Warn),
+                (4, Rule::HighFee, Severity::Warn),
+                (5, Rule::ZeroAmount, Severity::Error),
+                (6, Rule::PriceDeviation, Severity::Warn),
+            ]
+        );
+        assert_eq!(
+            findings[1].message,
+            "fee 50 USD is 12.20% of the 410 USD traded"
+        );
+        assert_eq!(
+            findings[3].message,
+            "price 4600 USD is -90.00% off the 2024-01-11 close of 46000 USD"
+        );
+
+        let settings = Settings {
+            lint: LintConfig {
+                high_fee: Severity::Ignore,
+                out_of_order: Severity::Error,
+                ..LintConfig::default()
+            },
+            ..(*settings).clone()
+        };
+        let rules: Vec<(Rule, Severity)> = check(&path, &settings)
+            .unwrap()
+            .iter()
+            .map(|f| (f.rule, f.severity))
+            .collect();
+        assert_eq!(rules[0], (Rule::OutOfOrder, Severity::Error));
+        assert!(!rules.iter().any(|(rule, _)| *rule == Rule::HighFee));
+    }
+}
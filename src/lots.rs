@@ -0,0 +1,327 @@
+use crate::currency::{Currency, CurrencyType};
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{
+    PortfolioKind, Side, Trade, parse_csv_file, read_csv_config, sort_chronologically,
+};
+use anyhow::Result;
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use std::collections::{HashMap, VecDeque};
+use time::{Duration, OffsetDateTime};
+
+/// Units of an asset acquired at one time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lot {
+    pub acquired: OffsetDateTime,
+    pub amount: Decimal,
+    /// Unit cost in the base currency, including fees
+    pub cost: Decimal,
+}
+
+/// What replaying one row did to the lots
+#[derive(Debug, Clone, PartialEq)]
+pub struct Booking {
+    /// Crypto lots consumed, oldest first
+    pub disposed: Vec<Lot>,
+    /// Cost in the base currency of everything the row gave up, or of what
+    /// a deposit or income brought in
+    pub cost: Decimal,
+    /// Lot of the asset received, none for the base currency
+    pub acquired: Option<Lot>,
+}
+
+/// Open lots per crypto asset, oldest first
+///
+/// Disposals consume the oldest lots first (FIFO), which is how holding
+/// periods are counted for short-term tax rules. Costs are tracked in the
+/// base currency: what a lot cost is the cost of what was given up for it,
+/// so swaps carry the cost over and only sales for the base realize a gain.
+/// Fiat and stable coins other than the base are kept at their average cost.
+#[derive(Debug)]
+pub struct Lots {
+    pub open: HashMap<Currency, VecDeque<Lot>>,
+    base: Currency,
+    /// Balance and total cost of the fiat and stable coins other than the base
+    cash: HashMap<Currency, (Decimal, Decimal)>,
+}
+
+impl Lots {
+    pub fn new(base: Currency) -> Self {
+        Lots {
+            open: HashMap::new(),
+            base,
+            cash: HashMap::new(),
+        }
+    }
+
+    /// Replay `trades` in chronological order
+    pub fn from_trades(trades: &[Trade], base: &Currency) -> Result<Self> {
+        let mut trades: Vec<&Trade> = trades.iter().collect();
+        sort_chronologically(&mut trades);
+        let mut lots = Lots::new(base.clone());
+        for t in trades {
+            lots.apply(t)?;
+        }
+        Ok(lots)
+    }
+
+    /// Replay one row, rows have to come in chronological order
+    pub fn apply(&mut self, t: &Trade) -> Result<Booking> {
+        let (cost, disposed, acquired) = match t.side {
+            Side::Buy | Side::Sell => {
+                let pair = t.pair.trading_pair(t.side)?;
+                let tx = t.to_tx()?;
+                let (given, received) = if t.side == Side::Buy {
+                    (&pair.quote, &pair.base)
+                } else {
+                    (&pair.base, &pair.quote)
+                };
+                let (cost, disposed) = self.dispose(given, tx.sell_size);
+                let acquired = self.acquire(received, t.created_at, tx.buy_size, cost);
+                (cost, disposed, acquired)
+            }
+            Side::Deposit | Side::Income => {
+                let cost = t.amount * t.price;
+                let acquired = self.acquire(t.pair.asset(t.side)?, t.created_at, t.amount, cost);
+                (cost, Vec::new(), acquired)
+            }
+            Side::Withdraw | Side::Fee => {
+                let (cost, disposed) = self.dispose(t.pair.asset(t.side)?, t.amount);
+                (cost, disposed, None)
+            }
+        };
+        Ok(Booking {
+            disposed,
+            cost,
+            acquired,
+        })
+    }
+
+    fn acquire(
+        &mut self,
+        asset: &Currency,
+        acquired: OffsetDateTime,
+        amount: Decimal,
+        cost: Decimal,
+    ) -> Option<Lot> {
+        if *asset == self.base || amount <= dec!(0) {
+            return None;
+        }
+        let lot = Lot {
+            acquired,
+            amount,
+            cost: cost / amount,
+        };
+        if asset.currency_type() == CurrencyType::Crypto {
+            self.open.entry(asset.clone()).or_default().push_back(lot);
+        } else {
+            let held = self.cash.entry(asset.clone()).or_default();
+            held.0 += amount;
+            held.1 += cost;
+        }
+        Some(lot)
+    }
+
+    /// Give up `amount`, returning its cost and the crypto lots consumed,
+    /// oldest first; what isn't held comes at no cost
+    fn dispose(&mut self, asset: &Currency, mut amount: Decimal) -> (Decimal, Vec<Lot>) {
+        if *asset == self.base {
+            return (amount, Vec::new());
+        }
+        if asset.currency_type() != CurrencyType::Crypto {
+            let Some(held) = self.cash.get_mut(asset) else {
+                return (dec!(0), Vec::new());
+            };
+            let take = held.0.min(amount);
+            let cost = if held.0.is_zero() {
+                dec!(0)
+            } else {
+                held.1 * take / held.0
+            };
+            held.0 -= take;
+            held.1 -= cost;
+            return (cost, Vec::new());
+        }
+        let mut consumed = Vec::new();
+        let Some(lots) = self.open.get_mut(asset) else {
+            return (dec!(0), consumed);
+        };
+        while amount > dec!(0) {
+            let Some(lot) = lots.front_mut() else {
+                break;
+            };
+            let take = lot.amount.min(amount);
+            consumed.push(Lot {
+                amount: take,
+                ..*lot
+            });
+            lot.amount -= take;
+            amount -= take;
+            if lot.amount.is_zero() {
+                lots.pop_front();
+            }
+        }
+        let cost = consumed.iter().map(|lot| lot.amount * lot.cost).sum();
+        (cost, consumed)
+    }
+
+    /// Parts of the lots a sale of `amount` at `at` would dispose of which
+    /// were held for less than `days`
+    pub fn short_term_sale(
+        &self,
+        asset: &Currency,
+        amount: Decimal,
+        at: OffsetDateTime,
+        days: u32,
+    ) -> Vec<Lot> {
+        let mut lots = Lots::new(self.base.clone());
+        lots.open.insert(
+            asset.clone(),
+            self.open.get(asset).cloned().unwrap_or_default(),
+        );
+        let cutoff = at - Duration::days(days as i64);
+        lots.dispose(asset, amount)
+            .1
+            .into_iter()
+            .filter(|lot| lot.acquired > cutoff)
+            .collect()
+    }
+
+    /// Open lots held for less than `days` at `at`, per asset in ticker order
+    pub fn short_term(&self, at: OffsetDateTime, days: u32) -> Vec<(&Currency, Vec<Lot>)> {
+        let cutoff = at - Duration::days(days as i64);
+        let mut young: Vec<(&Currency, Vec<Lot>)> = self
+            .open
+            .iter()
+            .map(|(asset, lots)| {
+                let young: Vec<Lot> = lots
+                    .iter()
+                    .filter(|lot| lot.acquired > cutoff)
+                    .copied()
+                    .collect();
+                (asset, young)
+            })
+            .filter(|(_, young)| !young.is_empty())
+            .collect();
+        young.sort_by_key(|(asset, _)| asset.ticker());
+        young
+    }
+}
+
+/// Warning about a sale disposing of lots held for less than `days`, if any
+pub fn short_term_sale_warning(
+    trades: &[Trade],
+    base: &Currency,
+    sale: &Trade,
+    days: u32,
+) -> Result<Option<String>> {
+    if sale.side != Side::Sell {
+        return Ok(None);
+    }
+    let asset = &sale.pair.trading_pair(sale.side)?.base;
+    let young =
+        Lots::from_trades(trades, base)?.short_term_sale(asset, sale.amount, sale.created_at, days);
+    let Some(newest) = young.iter().map(|lot| lot.acquired).max() else {
+        return Ok(None);
+    };
+    let amount: Decimal = young.iter().map(|lot| lot.amount).sum();
+    Ok(Some(format!(
+        "selling {} {} held for less than {} days (bought {}), may be taxed as short-term",
+        amount.normalize(),
+        asset,
+        days,
+        newest.date()
+    )))
+}
+
+/// Print the lots of a portfolio which would be short-term if sold now,
+/// holdings-only portfolios have no lots
+pub fn report(name: &str, days: u32, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let path = settings.path_for(name);
+    if read_csv_config(&path)?.kind == PortfolioKind::Holdings {
+        return Ok(());
+    }
+    let (config, trades) = parse_csv_file(&path)?;
+    let now = settings.clock.now();
+    let lots = Lots::from_trades(&trades, &Currency::new(&config.base_currency)?)?;
+    for (asset, young) in lots.short_term(now, days) {
+        let amount: Decimal = young.iter().map(|lot| lot.amount).sum();
+        let until =
+            young.iter().map(|lot| lot.acquired).max().unwrap() + Duration::days(days as i64);
+        reporter.print(&format!(
+            "⚠️  {} {} held for less than {} days, all long-term from {}",
+            amount.normalize(),
+            asset,
+            days,
+            until.date()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::transactions_from;
+    use time::macros::datetime;
+
+    const TRADES: &str = "created_at,pair,side,amount,price,fee
+1704067200,BTC/USD,BUY,1,40000,0
+1706745600,BTC/USD,BUY,0.5,42000,0
+1707350400,BTC/USD,SELL,0.25,45000,0
+1707350400,ETH/BTC,BUY,2,0.05,0
+";
+
+    #[test]
+    fn test_lots_are_consumed_oldest_first() {
+        let usd = Currency::new("USD").unwrap();
+        let lots = Lots::from_trades(&transactions_from(TRADES), &usd).unwrap();
+        let btc = Currency::new("BTC").unwrap();
+        let eth = Currency::new("ETH").unwrap();
+
+        // 0.25 sold and 0.1 spent on ETH out of the january lot
+        let open: Vec<Decimal> = lots.open[&btc].iter().map(|l| l.amount).collect();
+        assert_eq!(open, vec![dec!(0.65), dec!(0.5)]);
+        assert_eq!(lots.open[&eth][0].amount, dec!(2));
+        // the 0.1 BTC paid for it cost 4000 USD, carried over to the ETH
+        assert_eq!(lots.open[&eth][0].cost, dec!(2000));
+
+        let feb_10 = datetime!(2024-02-10 0:00 UTC);
+        let young = lots.short_term_sale(&btc, dec!(1), feb_10, 30);
+        assert_eq!(
+            young,
+            vec![Lot {
+                acquired: datetime!(2024-02-01 0:00 UTC),
+                amount: dec!(0.35),
+                cost: dec!(42000),
+            }]
+        );
+        assert!(lots.short_term_sale(&btc, dec!(0.5), feb_10, 30).is_empty());
+        assert_eq!(lots.short_term(feb_10, 30).len(), 2);
+    }
+
+    #[test]
+    fn test_short_term_sale_warning_only_for_young_lots() {
+        let trades = transactions_from(TRADES);
+        let sale = |amount: &str| {
+            transactions_from(&format!(
+                "created_at,pair,side,amount,price,fee\n1707523200,BTC/USD,SELL,{},45000,0\n",
+                amount
+            ))
+            .remove(0)
+        };
+        let usd = Currency::new("USD").unwrap();
+        let warning = short_term_sale_warning(&trades, &usd, &sale("1"), 30).unwrap();
+        assert_eq!(
+            warning.as_deref(),
+            Some(
+                "selling 0.35 BTC held for less than 30 days (bought 2024-02-01), may be taxed as short-term"
+            )
+        );
+        assert_eq!(
+            short_term_sale_warning(&trades, &usd, &sale("0.6"), 30).unwrap(),
+            None
+        );
+    }
+}
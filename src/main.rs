@@ -1,44 +1,708 @@
-use anyhow::Result;
-use clap::Parser;
-use portfolio_tracker::cli::{Cli, Cmd};
+use anyhow::{Result, bail};
+use clap::{CommandFactory, FromArgMatches, ValueEnum};
+use portfolio_tracker::alert::{self, PositionLevel, Trigger};
+use portfolio_tracker::anonymize;
+use portfolio_tracker::assertions;
+use portfolio_tracker::behavior;
+use portfolio_tracker::benchmark;
+use portfolio_tracker::canonical;
+use portfolio_tracker::cli::{
+    AlertCmd, Cli, Cmd, ConfigCmd, DcaCmd, JournalCmd, PositionCmd, PriceCmd, ScenarioCmd,
+    SnapshotCmd,
+};
+use portfolio_tracker::close;
+use portfolio_tracker::config_check;
+use portfolio_tracker::config_show;
+use portfolio_tracker::conflict;
+use portfolio_tracker::context::AppContext;
+use portfolio_tracker::dca;
+use portfolio_tracker::doctor;
+use portfolio_tracker::dust;
+use portfolio_tracker::export;
+use portfolio_tracker::fees;
+use portfolio_tracker::fx_effect;
+use portfolio_tracker::history;
+use portfolio_tracker::holdings;
+use portfolio_tracker::import;
+use portfolio_tracker::income;
+use portfolio_tracker::info;
+use portfolio_tracker::interrupt;
+use portfolio_tracker::journal;
+use portfolio_tracker::lots;
+use portfolio_tracker::manual_price;
+use portfolio_tracker::monthly;
+use portfolio_tracker::output;
+use portfolio_tracker::piped;
 use portfolio_tracker::portfolio;
-use portfolio_tracker::settings::Settings;
-use portfolio_tracker::trade;
-use std::cell::RefCell;
+use portfolio_tracker::position;
+use portfolio_tracker::report;
+use portfolio_tracker::report_diff;
+use portfolio_tracker::reporter::{self, OutputMode};
+use portfolio_tracker::scenario;
+use portfolio_tracker::serve;
+use portfolio_tracker::settings::{config_origins, config_path};
+use portfolio_tracker::snapshot;
+use portfolio_tracker::split;
+use portfolio_tracker::statement;
+use portfolio_tracker::stats;
+use portfolio_tracker::stops::{self, Level};
+use portfolio_tracker::supply;
+use portfolio_tracker::tags;
+use portfolio_tracker::tax;
+use portfolio_tracker::tickers;
+use portfolio_tracker::trade::{self, PortfolioKind};
+use portfolio_tracker::tx_template;
+use portfolio_tracker::usage;
+use portfolio_tracker::warnings;
+use portfolio_tracker::watch;
+use portfolio_tracker::widget;
+use portfolio_tracker::wizard;
+use portfolio_tracker::workspace;
+use std::time::Instant;
 
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if matches!(cli.output, OutputMode::Json | OutputMode::Csv) && !cli.commands.machine_output() {
+        bail!(
+            "`{}` doesn't print rows here, there is no --output {} for it",
+            usage::command_name(&matches),
+            cli.output.to_possible_value().unwrap().get_name()
+        );
+    }
+
+    // before the settings are loaded, which fail on the same problems
+    if let Cmd::Config {
+        cmd: ConfigCmd::Validate { file },
+    } = &cli.commands
+    {
+        return config_check::validate(
+            file.as_deref().unwrap_or(&config_path()),
+            reporter::for_mode(cli.output, cli.quiet).as_ref(),
+        );
+    }
 
-    let mut settings: RefCell<Settings> = RefCell::new(Settings::load(&cli)?);
+    if wizard::should_run(&cli) {
+        wizard::run_interactive()?;
+    }
 
+    let mut ctx = AppContext::load(&cli)?;
+    interrupt::install();
+
+    let output =
+        output::Output::start(cli.commands.pages() && !cli.no_pager, cli.commands.copies())?;
+    let started = Instant::now();
+    let result = run(&cli, &mut ctx);
+    let command = usage::command_name(&matches);
+    if let Err(e) = usage::record(&command, started.elapsed(), result.is_ok(), &ctx.settings) {
+        warnings::warn(format!("couldn't record usage stats: {}", e));
+    }
+    if let Some(output) = output {
+        output.finish()?;
+    }
+    if result.is_err() && interrupt::interrupted() {
+        eprintln!("Interrupted, files were left as they were");
+        std::process::exit(interrupt::EXIT_CODE);
+    }
+    result
+}
+
+fn run(cli: &Cli, ctx: &mut AppContext) -> Result<()> {
     match &cli.commands {
-        Cmd::List => {
-            portfolio::list_csv_files(&settings.borrow())?;
+        Cmd::List {
+            pattern,
+            base_currency,
+            include_ignored,
+            sort,
+            format,
+        } => {
+            let filter = portfolio::ListFilter::new(pattern.as_deref(), base_currency.as_deref())?;
+            portfolio::list_csv_files(
+                &ctx.settings,
+                &filter,
+                *include_ignored,
+                *sort,
+                *format,
+                ctx.reporter.as_ref(),
+            )?;
         }
-        Cmd::New { name, currency } => {
+        Cmd::New {
+            name,
+            currency,
+            holdings,
+        } => {
             if let Some(curr) = currency {
-                settings.get_mut().update_base_currency(curr)?;
+                ctx.settings.update_base_currency(curr)?;
+            }
+            let kind = if *holdings {
+                PortfolioKind::Holdings
+            } else {
+                PortfolioKind::Trades
+            };
+            portfolio::new(name.as_str(), kind, ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Show {
+            name,
+            source,
+            format,
+            with_journal,
+            ..
+        } => match name {
+            Some(name) => portfolio::show_trades(
+                name,
+                source.as_deref(),
+                *format,
+                *with_journal,
+                ctx.reporter.as_ref(),
+                &ctx.settings,
+            )?,
+            // clap requires --name unless --stdin is given
+            None => piped::show(
+                source.as_deref(),
+                *format,
+                ctx.reporter.as_ref(),
+                &ctx.settings,
+            )?,
+        },
+        Cmd::Stats {
+            name,
+            by,
+            by_tag,
+            fee_check,
+            heatmap,
+            behavior,
+            prices,
+        } => {
+            if *prices {
+                stats::print_prices(name, ctx.reporter.as_ref(), &ctx.settings)?;
+            } else if *behavior {
+                behavior::run(name, ctx.reporter.as_ref(), &ctx.settings)?;
+            } else if let Some(value) = heatmap {
+                stats::print_heatmap(name, *value, ctx.reporter.as_ref(), &ctx.settings)?;
+            } else if *fee_check {
+                fees::print_check(name, ctx.reporter.as_ref(), &ctx.settings)?;
+            } else if *by_tag {
+                tags::report(name, ctx.reporter.as_ref(), &ctx.settings)?;
+            } else {
+                stats::run(name, *by, ctx.reporter.as_ref(), &ctx.settings)?;
+            }
+        }
+        Cmd::Fees { name } => {
+            fees::run(name, ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Income { name } => {
+            income::run(name, ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Report {
+            name,
+            widget: Some(widget),
+            ..
+        } => widget::print(
+            *widget,
+            name.as_deref(),
+            ctx.reporter.as_ref(),
+            &ctx.settings,
+        )?,
+        Cmd::Report {
+            name,
+            all,
+            include_ignored,
+            diff,
+            save,
+            supply,
+            benchmark,
+            fx,
+            as_of,
+            fail_on_warning,
+            stdin,
+            ..
+        } => {
+            match name {
+                None if *stdin => {
+                    piped::report(ctx.prices.as_ref(), ctx.reporter.as_ref(), &ctx.settings)?
+                }
+                Some(name) if as_of.is_some() => {
+                    statement::report(name, as_of.unwrap(), ctx.reporter.as_ref(), &ctx.settings)?;
+                }
+                Some(name) if !all => {
+                    report::show(
+                        ctx.settings.path_for(name),
+                        ctx.prices.as_ref(),
+                        ctx.reporter.as_ref(),
+                        &ctx.settings,
+                    )?;
+                    stops::report(
+                        name,
+                        ctx.prices.as_ref(),
+                        ctx.reporter.as_ref(),
+                        &ctx.settings,
+                    )?;
+                    assertions::report(name, ctx.reporter.as_ref(), &ctx.settings)?;
+                    if let Some(days) = ctx.settings.short_term_days {
+                        lots::report(name, days, ctx.reporter.as_ref(), &ctx.settings)?;
+                    }
+                    if *supply {
+                        supply::report(name, ctx.reporter.as_ref(), &ctx.settings)?;
+                    }
+                    if let Some(benchmark) = benchmark {
+                        benchmark::report(name, benchmark, ctx.reporter.as_ref(), &ctx.settings)?;
+                    }
+                    if *fx {
+                        fx_effect::report(name, ctx.reporter.as_ref(), &ctx.settings)?;
+                    }
+                    report_diff::after_report(
+                        name,
+                        diff.as_deref(),
+                        save.as_deref(),
+                        ctx.reporter.as_ref(),
+                        &ctx.settings,
+                    )?;
+                }
+                _ => portfolio::report_all(
+                    ctx.prices.as_ref(),
+                    *include_ignored,
+                    ctx.reporter.as_ref(),
+                    &ctx.settings,
+                )?,
+            }
+            if *fail_on_warning && warnings::count() > 0 {
+                bail!(
+                    "{} warnings while reporting, failing because of --fail-on-warning",
+                    warnings::count()
+                );
             }
-            portfolio::new(name.as_str(), &settings.borrow())?;
         }
-        Cmd::Show { name } => {
-            portfolio::show_trades(name, &settings.borrow())?;
+        Cmd::Tax {
+            name,
+            year,
+            currency,
+        } => {
+            tax::run(
+                name,
+                *year,
+                currency.as_deref(),
+                ctx.reporter.as_ref(),
+                &ctx.settings,
+            )?;
         }
-        Cmd::Report { name } => {
-            portfolio::Portfolio::print_unrealized_pnl(
-                settings.borrow().path_for(name),
-                settings.borrow().base_currency.ticker(),
+        Cmd::Statement {
+            name,
+            month,
+            format,
+        } => {
+            monthly::run(name, *month, *format, ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Scenario {
+            name,
+            saved,
+            shocks,
+        } => {
+            scenario::run(
+                name,
+                shocks,
+                saved.as_deref(),
+                ctx.reporter.as_ref(),
+                &ctx.settings,
             )?;
         }
+        Cmd::Scenarios { cmd } => match cmd {
+            ScenarioCmd::List => scenario::print_list(ctx.reporter.as_ref(), &ctx.settings)?,
+            ScenarioCmd::Save { scenario, shocks } => {
+                scenario::save(
+                    &config_path(),
+                    scenario,
+                    &scenario::parse_shocks(shocks)?,
+                    ctx.reporter.as_ref(),
+                )?;
+            }
+            ScenarioCmd::Remove { scenario } => {
+                scenario::remove(&config_path(), scenario, ctx.reporter.as_ref())?
+            }
+        },
+        Cmd::Dca { cmd } => match cmd {
+            DcaCmd::Due { name, days } => {
+                dca::due(
+                    name.as_deref(),
+                    *days,
+                    ctx.now().date(),
+                    ctx.reporter.as_ref(),
+                    &ctx.settings,
+                )?;
+            }
+            DcaCmd::Status { name, days } => {
+                dca::status(
+                    name.as_deref(),
+                    *days,
+                    ctx.now().date(),
+                    ctx.reporter.as_ref(),
+                    &ctx.settings,
+                )?;
+            }
+        },
+        Cmd::Position {
+            name,
+            pair,
+            history,
+            entries,
+            cmd,
+        } => match cmd {
+            None => {
+                let (Some(name), Some(pair)) = (name, pair) else {
+                    bail!("position needs --name and a pair");
+                };
+                position::run(
+                    name,
+                    pair,
+                    *history,
+                    *entries,
+                    ctx.reporter.as_ref(),
+                    &ctx.settings,
+                )?;
+            }
+            Some(PositionCmd::SetStop {
+                name,
+                ticker,
+                price,
+            }) => {
+                stops::set(
+                    name,
+                    ticker,
+                    Level::Stop,
+                    *price,
+                    ctx.reporter.as_ref(),
+                    &ctx.settings,
+                )?;
+            }
+            Some(PositionCmd::SetTarget {
+                name,
+                ticker,
+                price,
+            }) => {
+                stops::set(
+                    name,
+                    ticker,
+                    Level::Target,
+                    *price,
+                    ctx.reporter.as_ref(),
+                    &ctx.settings,
+                )?;
+            }
+            Some(PositionCmd::ClearLevels { name, ticker }) => {
+                stops::clear(name, ticker, ctx.reporter.as_ref(), &ctx.settings)?;
+            }
+            Some(PositionCmd::Levels { name }) => {
+                stops::list(name.as_deref(), ctx.reporter.as_ref(), &ctx.settings)?;
+            }
+        },
         Cmd::AddTx {
+            tx,
+            template,
+            mixed,
+        } => {
+            let tx = tx_template::resolve(tx, template.as_deref(), &ctx.settings)?;
+            trade::tx_to_csv(
+                &tx.name,
+                &tx.ticker,
+                &tx.side,
+                tx.qty,
+                tx.price,
+                tx.fee,
+                &tx.source,
+                *mixed,
+                ctx.reporter.as_ref(),
+                &ctx.settings,
+            )?;
+        }
+        Cmd::Hold {
             name,
             ticker,
-            side,
             qty,
-            price,
-            fee,
+            cost,
+        } => {
+            holdings::hold(
+                name,
+                ticker,
+                *qty,
+                *cost,
+                ctx.reporter.as_ref(),
+                &ctx.settings,
+            )?;
+        }
+        Cmd::Dust {
+            name,
+            threshold,
+            into,
+            emit,
+        } => {
+            dust::run(
+                name,
+                *threshold,
+                into.as_deref(),
+                emit.as_deref(),
+                ctx.reporter.as_ref(),
+                &ctx.settings,
+            )?;
+        }
+        Cmd::Export {
+            name,
+            format,
+            anonymized,
+            out,
+        } => match (format, name) {
+            (Some(format), _) => {
+                export::run(
+                    name.as_deref(),
+                    *format,
+                    out,
+                    ctx.reporter.as_ref(),
+                    &ctx.settings,
+                )?;
+            }
+            (None, Some(name)) if *anonymized => {
+                anonymize::run(name, out, ctx.reporter.as_ref(), &ctx.settings)?;
+            }
+            _ => unreachable!("clap requires --format, or --anonymized with --name"),
+        },
+        Cmd::ExportWorkspace { out } => {
+            workspace::export(out, ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Import {
+            name,
+            dir,
+            clipboard: _,
+            wizard,
+            create,
+            yes,
+        } => {
+            import::run(
+                name,
+                dir.as_deref(),
+                wizard.as_deref(),
+                *create,
+                *yes,
+                ctx.reporter.as_ref(),
+                &ctx.settings,
+            )?;
+        }
+        Cmd::ImportWorkspace {
+            archive,
+            force,
+            yes,
+        } => {
+            workspace::import(archive, *force, *yes, ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Config {
+            cmd: ConfigCmd::Show { origins },
+        } => {
+            let origins = match origins {
+                true => Some(config_origins(cli)?),
+                false => None,
+            };
+            config_show::print(ctx.reporter.as_ref(), &ctx.settings, origins.as_ref())?;
+        }
+        Cmd::Config {
+            cmd: ConfigCmd::Validate { .. },
+        } => unreachable!("handled before loading the settings"),
+        Cmd::Doctor => {
+            doctor::run(ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Fmt { name, check } => {
+            canonical::run(
+                name.as_deref(),
+                *check,
+                ctx.reporter.as_ref(),
+                &ctx.settings,
+            )?;
+        }
+        Cmd::Info { stats: true, .. } => {
+            usage::print(ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Info {
+            workspace: true, ..
+        } => {
+            info::workspace(ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Info { offline, .. } => {
+            info::run(cli, !offline, ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Serve { port, cache_secs } => {
+            serve::run(*port, *cache_secs, ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::SplitTx { name, id, into } => {
+            split::run(name, id, into, ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Tickers { query, limit } => {
+            tickers::run(query, *limit, ctx.reporter.as_ref())?;
+        }
+        Cmd::TickerInfo {
+            tickers, offline, ..
         } => {
-            trade::tx_to_csv(name, ticker, side, *qty, *price, *fee, &settings.borrow())?;
+            supply::ticker_info(tickers, *offline, ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Price { cmd } => match cmd {
+            PriceCmd::Set {
+                ticker,
+                price,
+                currency,
+                until,
+            } => manual_price::set(
+                ticker,
+                manual_price::ManualPrice {
+                    price: *price,
+                    currency: currency.clone(),
+                    until: *until,
+                },
+                ctx.reporter.as_ref(),
+                &ctx.settings,
+            )?,
+            PriceCmd::List => manual_price::print_list(ctx.reporter.as_ref(), &ctx.settings)?,
+            PriceCmd::Remove { ticker } => {
+                manual_price::remove(ticker, ctx.reporter.as_ref(), &ctx.settings)?
+            }
+        },
+        Cmd::Alert { cmd } => match cmd {
+            AlertCmd::Price {
+                ticker,
+                above,
+                below,
+                cooldown,
+            } => {
+                let trigger = match (above, below) {
+                    (Some(price), _) => Trigger::PriceAbove {
+                        ticker: ticker.to_ascii_uppercase(),
+                        price: *price,
+                    },
+                    (None, Some(price)) => Trigger::PriceBelow {
+                        ticker: ticker.to_ascii_uppercase(),
+                        price: *price,
+                    },
+                    (None, None) => unreachable!("clap requires --above or --below"),
+                };
+                alert::add(trigger, *cooldown, ctx.reporter.as_ref(), &ctx.settings)?;
+            }
+            AlertCmd::Move {
+                name,
+                pct,
+                cooldown,
+            } => {
+                let trigger = Trigger::PortfolioMove {
+                    portfolio: name.clone(),
+                    percent: *pct,
+                };
+                alert::add(trigger, *cooldown, ctx.reporter.as_ref(), &ctx.settings)?;
+            }
+            AlertCmd::Position {
+                name,
+                asset,
+                above,
+                below,
+                above_pct,
+                below_pct,
+                cooldown,
+            } => {
+                let portfolio = name.clone();
+                let asset = asset.to_ascii_uppercase();
+                let trigger = match (above, below, above_pct, below_pct) {
+                    (Some(value), ..) => Trigger::PositionAbove {
+                        portfolio,
+                        asset,
+                        level: PositionLevel::Value(*value),
+                    },
+                    (_, Some(value), ..) => Trigger::PositionBelow {
+                        portfolio,
+                        asset,
+                        level: PositionLevel::Value(*value),
+                    },
+                    (_, _, Some(pct), _) => Trigger::PositionAbove {
+                        portfolio,
+                        asset,
+                        level: PositionLevel::Weight(*pct),
+                    },
+                    (_, _, _, Some(pct)) => Trigger::PositionBelow {
+                        portfolio,
+                        asset,
+                        level: PositionLevel::Weight(*pct),
+                    },
+                    _ => unreachable!("clap requires a level"),
+                };
+                alert::add(trigger, *cooldown, ctx.reporter.as_ref(), &ctx.settings)?;
+            }
+            AlertCmd::List => alert::print_list(ctx.reporter.as_ref(), &ctx.settings)?,
+            AlertCmd::Remove { id } => alert::remove(*id, ctx.reporter.as_ref(), &ctx.settings)?,
+            AlertCmd::Snooze { id, duration } => {
+                alert::snooze(*id, *duration, ctx.reporter.as_ref(), &ctx.settings)?
+            }
+        },
+        Cmd::Tag {
+            name,
+            tx,
+            tags,
+            remove,
+        } => {
+            tags::tag(
+                name,
+                tx,
+                tags,
+                *remove,
+                ctx.reporter.as_ref(),
+                &ctx.settings,
+            )?;
+        }
+        Cmd::Journal { cmd } => match cmd {
+            JournalCmd::Add { name, tx, text } => {
+                journal::add(name, tx, text, ctx.reporter.as_ref(), &ctx.settings)?;
+            }
+        },
+        Cmd::Freeze { name, date } => {
+            statement::freeze(name, *date, ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Close { name, reopen } => {
+            close::close(name, *reopen, ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::History {
+            name: Some(name),
+            reconstruct,
+            ..
+        } => {
+            history::run(name, *reconstruct, ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::History { name: None, .. } => {
+            history::run_total(ctx.reporter.as_ref(), &ctx.settings)?;
+        }
+        Cmd::Snapshot { name, cmd } => match cmd {
+            None => snapshot::take(name.as_deref(), ctx.reporter.as_ref(), &ctx.settings)?,
+            Some(SnapshotCmd::Compact { name, yes }) => {
+                snapshot::run_compact(name.as_deref(), *yes, ctx.reporter.as_ref(), &ctx.settings)?
+            }
+        },
+        Cmd::Watch {
+            interval,
+            once,
+            inbox,
+            ..
+        } => {
+            watch::run(
+                *interval,
+                *once,
+                *inbox,
+                ctx.reporter.as_ref(),
+                &ctx.settings,
+            )?;
+        }
+        Cmd::MergeConflict {
+            name,
+            from,
+            keep,
+            yes,
+        } => {
+            conflict::merge(
+                name,
+                from.as_deref(),
+                *keep,
+                *yes,
+                ctx.reporter.as_ref(),
+                &ctx.settings,
+            )?;
         }
     }
 
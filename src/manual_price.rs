@@ -0,0 +1,211 @@
+//! Manual prices
+//!
+//! `price set ASTER 0.95 --until 2026-01-01` keeps a price for an asset the
+//! quote providers don't quote, e.g. a token only a new exchange lists yet.
+//! It's used only when none of the providers quotes the asset, up to and
+//! including its `--until` day, and reports warn in the week before that.
+//! Prices are kept in `<state dir>/manual_prices.json`.
+
+use crate::currency::Currency;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use anyhow::{Result, bail};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use time::{Date, Duration};
+
+const MANUAL_PRICES_FILE: &str = "manual_prices.json";
+/// Reports warn about manual prices expiring within this many days
+const EXPIRY_WARNING_DAYS: i64 = 7;
+
+time::serde::format_description!(price_date, Date, "[year]-[month]-[day]");
+
+/// Price of an asset set with `price set`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManualPrice {
+    pub price: Decimal,
+    /// Currency of the price, the asset is valued in other bases as this
+    /// many units of it
+    pub currency: String,
+    /// Last day the price is used on, without one it never expires
+    #[serde(default, with = "price_date::option")]
+    pub until: Option<Date>,
+}
+
+impl ManualPrice {
+    pub fn expired(&self, today: Date) -> bool {
+        self.until.is_some_and(|until| until < today)
+    }
+
+    /// The `until` day, if it's within `EXPIRY_WARNING_DAYS` of `today`
+    pub fn expires_soon(&self, today: Date) -> Option<Date> {
+        self.until
+            .filter(|until| *until >= today && *until - today < Duration::days(EXPIRY_WARNING_DAYS))
+    }
+}
+
+/// Manual prices by ticker
+pub type ManualPrices = BTreeMap<String, ManualPrice>;
+
+fn prices_path(settings: &Settings) -> PathBuf {
+    settings.state_dir().join(MANUAL_PRICES_FILE)
+}
+
+pub fn load(settings: &Settings) -> Result<ManualPrices> {
+    let path = prices_path(settings);
+    if !path.exists() {
+        return Ok(ManualPrices::new());
+    }
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+fn save(prices: &ManualPrices, settings: &Settings) -> Result<()> {
+    std::fs::create_dir_all(settings.state_dir())?;
+    std::fs::write(prices_path(settings), serde_json::to_vec_pretty(prices)?)?;
+    Ok(())
+}
+
+/// `price set`: store the manual price of `ticker`, replacing an earlier one
+pub fn set(
+    ticker: &str,
+    price: ManualPrice,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let ticker = Currency::new(ticker)?.ticker().to_string();
+    let currency = Currency::new(&price.currency)?.ticker().to_string();
+    if currency == ticker {
+        bail!("{} can't be priced in itself", ticker);
+    }
+    if price.price <= Decimal::ZERO {
+        bail!("price must be greater than zero");
+    }
+    let today = settings.clock.now().date();
+    if let Some(until) = price.until.filter(|until| *until < today) {
+        bail!("--until {} is in the past", until);
+    }
+
+    let mut prices = load(settings)?;
+    let until = match price.until {
+        Some(until) => format!(" until {}", until),
+        None => String::new(),
+    };
+    reporter.status(&format!(
+        "Set manual price of {}: {} {}{}",
+        ticker,
+        price.price.normalize(),
+        currency,
+        until
+    ));
+    prices.insert(ticker, ManualPrice { currency, ..price });
+    save(&prices, settings)
+}
+
+/// `price remove`
+pub fn remove(ticker: &str, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let ticker = ticker.trim().to_ascii_uppercase();
+    let mut prices = load(settings)?;
+    if prices.remove(&ticker).is_none() {
+        bail!("no manual price for {}", ticker);
+    }
+    save(&prices, settings)?;
+    reporter.status(&format!("Removed manual price of {}", ticker));
+    Ok(())
+}
+
+/// `price list`
+pub fn print_list(reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let prices = load(settings)?;
+    if prices.is_empty() {
+        reporter.print("No manual prices set");
+        return Ok(());
+    }
+    let today = settings.clock.now().date();
+    let mut table = Table::new();
+    table.add_row(row!["Ticker", "Price", "Until", "Status"]);
+    for (ticker, price) in prices {
+        let status = if price.expired(today) {
+            "expired".to_string()
+        } else if let Some(until) = price.expires_soon(today) {
+            format!("expires in {} days", (until - today).whole_days())
+        } else {
+            String::new()
+        };
+        table.add_row(row![
+            ticker,
+            r->format!("{} {}", price.price.normalize(), price.currency),
+            price.until.map(|d| d.to_string()).unwrap_or("-".to_string()),
+            status
+        ]);
+    }
+    reporter.table(&table)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::reporter::Silent;
+    use crate::test_utils::helpers::create_test_settings;
+    use rust_decimal::dec;
+    use tempfile::TempDir;
+    use time::macros::{date, datetime};
+
+    #[test]
+    fn test_set_validates_and_stores_prices_with_expiry() {
+        let dir = TempDir::new().unwrap();
+        let settings = Settings {
+            clock: Clock::Frozen(datetime!(2025-12-20 12:00 UTC)),
+            ..(*create_test_settings(dir.path().to_path_buf())).clone()
+        };
+        let price = |price, currency: &str, until| ManualPrice {
+            price,
+            currency: currency.to_string(),
+            until,
+        };
+
+        set(
+            "aster",
+            price(dec!(0.95), "usd", Some(date!(2026 - 01 - 01))),
+            &Silent,
+            &settings,
+        )
+        .unwrap();
+        set("M", price(dec!(2), "USDC", None), &Silent, &settings).unwrap();
+        let prices = load(&settings).unwrap();
+        assert_eq!(
+            prices["ASTER"],
+            price(dec!(0.95), "USD", Some(date!(2026 - 01 - 01)))
+        );
+        assert_eq!(prices["M"].until, None);
+        let json = std::fs::read_to_string(dir.path().join(".csvpt/manual_prices.json")).unwrap();
+        assert!(json.contains(r#""until": "2026-01-01""#), "{json}");
+
+        let bad = [
+            ("USDT0", price(dec!(1), "USD", None)),
+            ("BTC", price(dec!(0), "USD", None)),
+            ("BTC", price(dec!(1), "BTC", None)),
+            ("BTC", price(dec!(1), "USD", Some(date!(2025 - 12 - 19)))),
+        ];
+        for (ticker, price) in bad {
+            assert!(set(ticker, price, &Silent, &settings).is_err());
+        }
+
+        let aster = &prices["ASTER"];
+        assert_eq!(aster.expires_soon(date!(2025 - 12 - 20)), None);
+        assert_eq!(
+            aster.expires_soon(date!(2025 - 12 - 26)),
+            Some(date!(2026 - 01 - 01))
+        );
+        assert!(!aster.expired(date!(2026 - 01 - 01)));
+        assert!(aster.expired(date!(2026 - 01 - 02)));
+
+        remove("aster", &Silent, &settings).unwrap();
+        assert!(!load(&settings).unwrap().contains_key("ASTER"));
+        assert!(remove("ASTER", &Silent, &settings).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+//! Percentages and ratios of decimals
+//!
+//! `Decimal` division panics on a zero divisor, and zero turns up easily:
+//! positions from airdrops or income have no cost, a portfolio can be worth
+//! nothing, a delisted asset can have a final price of 0. PnL percentages,
+//! weights and returns go through these helpers, which return `None` instead.
+
+use rust_decimal::Decimal;
+use std::fmt;
+
+/// A percentage, `Percent::new(dec!(12.5))` is 12.5%
+///
+/// Displays with a `%` sign; precision and sign flags apply to the number,
+/// so `format!("{:+.2}", pct)` gives `+12.50%`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Percent(Decimal);
+
+impl Percent {
+    pub const ZERO: Percent = Percent(Decimal::ZERO);
+
+    /// `value` percent, e.g. a percentage from the config
+    pub fn new(value: Decimal) -> Self {
+        Percent(value)
+    }
+
+    /// The number of percent, 12.5 for 12.5%
+    pub fn value(self) -> Decimal {
+        self.0
+    }
+
+    pub fn round_dp(self, dp: u32) -> Self {
+        Percent(self.0.round_dp(dp))
+    }
+
+    pub fn abs(self) -> Self {
+        Percent(self.0.abs())
+    }
+}
+
+impl fmt::Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)?;
+        f.write_str("%")
+    }
+}
+
+/// `numerator / denominator`, `None` for a zero denominator or an overflow
+pub fn ratio(numerator: Decimal, denominator: Decimal) -> Option<Decimal> {
+    if denominator.is_zero() {
+        return None;
+    }
+    numerator.checked_div(denominator)
+}
+
+/// `part` as a percentage of `whole`, e.g. the weight of a position
+pub fn percent_of(part: Decimal, whole: Decimal) -> Option<Percent> {
+    ratio(part, whole)?
+        .checked_mul(Decimal::ONE_HUNDRED)
+        .map(Percent)
+}
+
+/// Change from `from` to `to` in percent of `from`, e.g. PnL over the cost
+/// base or a return over the start value
+pub fn change_pct(from: Decimal, to: Decimal) -> Option<Percent> {
+    percent_of(to.checked_sub(from)?, from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::dec;
+
+    #[test]
+    fn test_helpers_return_none_instead_of_dividing_by_zero() {
+        assert_eq!(ratio(dec!(1), dec!(4)), Some(dec!(0.25)));
+        assert_eq!(ratio(dec!(1), dec!(0)), None);
+        assert_eq!(ratio(Decimal::MAX, dec!(0.1)), None);
+
+        assert_eq!(
+            percent_of(dec!(30), dec!(120)),
+            Some(Percent::new(dec!(25)))
+        );
+        assert_eq!(percent_of(dec!(0), dec!(120)), Some(Percent::ZERO));
+        assert_eq!(percent_of(dec!(30), dec!(0)), None);
+
+        assert_eq!(
+            change_pct(dec!(80), dec!(100)),
+            Some(Percent::new(dec!(25)))
+        );
+        assert_eq!(
+            change_pct(dec!(100), dec!(80)),
+            Some(Percent::new(dec!(-20)))
+        );
+        assert_eq!(
+            change_pct(dec!(-50), dec!(-25)),
+            Some(Percent::new(dec!(-50)))
+        );
+        assert_eq!(change_pct(dec!(0), dec!(100)), None);
+        assert_eq!(change_pct(Decimal::MIN, Decimal::MAX), None);
+    }
+
+    #[test]
+    fn test_percent_displays_with_the_flags_of_the_number() {
+        let pct = Percent::new(dec!(12.346));
+        assert_eq!(pct.to_string(), "12.346%");
+        // like `Decimal`, precision truncates, round first to round
+        assert_eq!(format!("{:.2}", pct), "12.34%");
+        assert_eq!(format!("{:.2}", pct.round_dp(2)), "12.35%");
+        assert_eq!(format!("{:+.1}", pct), "+12.3%");
+        assert_eq!(format!("{:+.2}", Percent::new(dec!(-7.5))), "-7.50%");
+        assert_eq!(pct.round_dp(1).value(), dec!(12.3));
+        assert_eq!(Percent::new(dec!(-3)).abs(), Percent::new(dec!(3)));
+    }
+}
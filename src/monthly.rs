@@ -0,0 +1,295 @@
+//! Monthly statements
+//!
+//! `statement` sums up one calendar month of a portfolio: its value at the
+//! end of the month before and of the month itself (see `statement::compute`),
+//! the money deposited and withdrawn, gains realized by sales, income and
+//! fees. Written to stdout as text, Markdown or HTML, so a cron job on the
+//! 1st can mail or archive the statement of the month that just ended.
+
+use crate::currency::{Currency, CurrencyType};
+use crate::fees;
+use crate::math;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::statement;
+use crate::tax;
+use crate::trade::{Side, parse_csv_file, sort_chronologically};
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use thousands::Separable;
+use time::{Date, Duration, Month};
+
+/// Output format of `statement`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum StatementFormat {
+    #[default]
+    Text,
+    Markdown,
+    Html,
+}
+
+/// One month of a portfolio, amounts in its base currency
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonthlyStatement {
+    pub portfolio: String,
+    /// First day of the month
+    pub month: Date,
+    pub base: String,
+    /// Value of the crypto positions at the end of the month before
+    pub start_value: Decimal,
+    /// Value of the crypto positions at the end of the month
+    pub end_value: Decimal,
+    /// Deposits less withdrawals
+    pub net_deposits: Decimal,
+    /// Gains of the sales of the month at average cost, `None` for bases
+    /// other than fiat
+    pub realized: Option<Decimal>,
+    /// Income rows valued when received
+    pub income: Decimal,
+    /// Network fees, and trading fees paid in the base currency
+    pub fees: Decimal,
+}
+
+/// First day of the month before the one of `today`
+pub fn previous_month(today: Date) -> Date {
+    let last_month = today.replace_day(1).unwrap_or(today) - Duration::days(1);
+    last_month.replace_day(1).unwrap_or(last_month)
+}
+
+fn next_month(month: Date) -> Date {
+    let (year, next) = match month.month() {
+        Month::December => (month.year() + 1, Month::January),
+        m => (month.year(), m.next()),
+    };
+    Date::from_calendar_date(year, next, 1).unwrap_or(month)
+}
+
+/// Statement of portfolio `name` for the month starting on `month`
+pub fn compute(name: &str, month: Date, settings: &Settings) -> Result<MonthlyStatement> {
+    let end = next_month(month);
+    let last_day = end.previous_day().unwrap_or(end);
+    let (config, mut trades) = parse_csv_file(settings.path_for(name))?;
+    sort_chronologically(&mut trades);
+    let base = Currency::new(&config.base_currency)?;
+    let first = trades.partition_point(|t| t.created_at.date() < month);
+    let last = trades.partition_point(|t| t.created_at.date() < end);
+    let month_trades = &trades[first..last];
+
+    // deposits and withdrawals counted like `Portfolio.contributions`
+    let mut net_deposits = dec!(0);
+    let mut income = dec!(0);
+    for t in month_trades {
+        match t.side {
+            Side::Deposit => net_deposits += t.amount * t.price,
+            Side::Withdraw => net_deposits -= t.amount * t.price,
+            Side::Income => income += t.amount * t.price,
+            _ => {}
+        }
+    }
+    // trading fees paid in other currencies than the base aren't valued
+    let fees = fees::by_asset(month_trades)?
+        .iter()
+        .map(|(asset, f)| {
+            f.network_value
+                + if *asset == base.ticker() {
+                    f.trading
+                } else {
+                    dec!(0)
+                }
+        })
+        .sum();
+
+    let realized = if base.currency_type() == CurrencyType::Fiat {
+        let fx = tax::load_fx(&trades, &base, base.ticker(), settings)?;
+        let gains = tax::disposals(&trades, &base, base.ticker(), &fx)?
+            .iter()
+            .filter(|d| d.at.date() >= month && d.at.date() < end)
+            .map(|d| d.gain())
+            .sum();
+        Some(gains)
+    } else {
+        None
+    };
+
+    let start = month
+        .previous_day()
+        .ok_or(anyhow!("no month before {}", month))?;
+    Ok(MonthlyStatement {
+        portfolio: name.to_string(),
+        month,
+        base: base.to_string(),
+        start_value: statement::compute(name, start, settings)?.value,
+        end_value: statement::compute(name, last_day, settings)?.value,
+        net_deposits,
+        realized,
+        income,
+        fees,
+    })
+}
+
+impl MonthlyStatement {
+    /// Label of the month, `YYYY-MM`
+    pub fn label(&self) -> String {
+        format!("{}-{:02}", self.month.year(), self.month.month() as u8)
+    }
+
+    /// (label, amount) lines of the statement
+    fn lines(&self) -> Vec<(String, String)> {
+        let money = |d: Decimal| {
+            format!(
+                "{} {}",
+                d.round_dp(2).normalize().separate_with_commas(),
+                self.base
+            )
+        };
+        let change = self.end_value - self.start_value;
+        let change_pct = math::percent_of(change, self.start_value)
+            .map_or(String::new(), |p| format!(" ({:+})", p.round_dp(2)));
+        let end = next_month(self.month).previous_day().unwrap_or(self.month);
+        vec![
+            (
+                format!(
+                    "Value on {}",
+                    self.month.previous_day().unwrap_or(self.month)
+                ),
+                money(self.start_value),
+            ),
+            (format!("Value on {}", end), money(self.end_value)),
+            (
+                "Change".to_string(),
+                format!("{}{}", money(change), change_pct),
+            ),
+            ("Net deposits".to_string(), money(self.net_deposits)),
+            (
+                "Realized gains".to_string(),
+                self.realized.map_or("-".to_string(), money),
+            ),
+            ("Income".to_string(), money(self.income)),
+            ("Fees".to_string(), money(self.fees)),
+        ]
+    }
+
+    pub fn render(&self, format: StatementFormat) -> String {
+        let title = format!("Statement of {} for {}", self.portfolio, self.label());
+        let lines = self.lines();
+        let mut out = String::new();
+        match format {
+            StatementFormat::Text => {
+                let width = lines.iter().map(|(l, _)| l.len()).max().unwrap_or(0);
+                out.push_str(&format!("{}\n", title));
+                for (label, amount) in &lines {
+                    out.push_str(&format!(
+                        "{:<width$}  {:>20}\n",
+                        label,
+                        amount,
+                        width = width + 1
+                    ));
+                }
+            }
+            StatementFormat::Markdown => {
+                out.push_str(&format!("# {}\n\n| | {} |\n|---|---:|\n", title, self.base));
+                for (label, amount) in &lines {
+                    out.push_str(&format!("| {} | {} |\n", label, amount));
+                }
+            }
+            StatementFormat::Html => {
+                out.push_str(&format!(
+                    "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{0}</title></head>\n<body>\n<h1>{0}</h1>\n<table>\n",
+                    html_escape(&title)
+                ));
+                for (label, amount) in &lines {
+                    out.push_str(&format!(
+                        "<tr><th align=\"left\">{}</th><td align=\"right\">{}</td></tr>\n",
+                        html_escape(label),
+                        html_escape(amount)
+                    ));
+                }
+                out.push_str("</table>\n</body>\n</html>\n");
+            }
+        }
+        out
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `statement`: print the statement of `month`, the month before today by default
+pub fn run(
+    name: &str,
+    month: Option<Date>,
+    format: StatementFormat,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let month = month.unwrap_or_else(|| previous_month(settings.clock.today()));
+    reporter.print(compute(name, month, settings)?.render(format).trim_end());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use tempfile::TempDir;
+    use time::macros::{date, datetime};
+
+    #[test]
+    fn test_statement_of_a_month_sums_its_rows_and_values_both_ends() {
+        let dir = TempDir::new().unwrap();
+        let settings = Settings {
+            clock: Clock::Frozen(datetime!(2024-03-01 06:00 UTC)),
+            ..(*create_test_settings(dir.path().to_path_buf())).clone()
+        };
+        create_test_csv(
+            &dir,
+            "main",
+            "created_at,pair,side,amount,price,fee
+1704067200,USD,DEPOSIT,50000,1,0
+1704110400,BTC/USD,BUY,1,40000,20
+1707220800,USD,DEPOSIT,1000,1,0
+1707307200,BTC/USD,SELL,0.5,45000,10
+1707393600,BTC,INCOME,0.01,44000,0
+1707480000,BTC,FEE,0.001,44000,0
+",
+        );
+        let prices = dir.path().join(".csvpt/prices");
+        std::fs::create_dir_all(&prices).unwrap();
+        std::fs::write(
+            prices.join("BTC-USD.json"),
+            r#"{"2024-01-31": 42000, "2024-02-29": 50000}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            previous_month(settings.clock.today()),
+            date!(2024 - 02 - 01)
+        );
+        assert_eq!(previous_month(date!(2024 - 01 - 15)), date!(2023 - 12 - 01));
+        let s = compute("main", date!(2024 - 02 - 01), &settings).unwrap();
+        assert_eq!(s.start_value, dec!(42000));
+        // 1 - 0.5 + 0.01 - 0.001 BTC
+        assert_eq!(s.end_value, dec!(25450));
+        assert_eq!(s.net_deposits, dec!(1000));
+        // 0.5 × 45000 - 10 for half of 40020
+        assert_eq!(s.realized, Some(dec!(2480)));
+        assert_eq!(s.income, dec!(440));
+        assert_eq!(s.fees, dec!(54));
+
+        let markdown = s.render(StatementFormat::Markdown);
+        assert!(markdown.starts_with("# Statement of main for 2024-02\n"));
+        assert!(markdown.contains("| Value on 2024-01-31 | 42,000 USD |\n"));
+        assert!(markdown.contains("| Change | -16,550 USD (-39.40%) |\n"));
+        let html = s.render(StatementFormat::Html);
+        assert!(
+            html.contains("<tr><th align=\"left\">Fees</th><td align=\"right\">54 USD</td></tr>")
+        );
+    }
+}
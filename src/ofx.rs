@@ -0,0 +1,261 @@
+//! OFX 2.2 investment statements, for GnuCash and other tools reading
+//! bank and brokerage downloads
+//!
+//! One statement per portfolio, with the same actions as the QIF export and
+//! every security in the security list. Tickers are used as security ids.
+
+use crate::qif::{Action, CashKind, Entry};
+use anyhow::Result;
+use rust_decimal::Decimal;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use time::OffsetDateTime;
+use time::macros::format_description;
+
+const HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<?OFX OFXHEADER="200" VERSION="220" SECURITY="NONE" OLDFILEUID="NONE" NEWFILEUID="NONE"?>"#;
+
+/// A statement of one portfolio: its name, base currency and actions
+pub struct Statement {
+    pub name: String,
+    pub base: String,
+    pub entries: Vec<Entry>,
+}
+
+fn datetime(at: OffsetDateTime) -> Result<String> {
+    Ok(at.format(format_description!(
+        "[year][month][day][hour][minute][second]"
+    ))?)
+}
+
+fn num(d: Decimal) -> String {
+    d.round_dp(8).normalize().to_string()
+}
+
+/// `<TAG>value</TAG>` with the value escaped
+fn element(out: &mut String, tag: &str, value: &str) -> std::fmt::Result {
+    let value = value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    write!(out, "<{tag}>{value}</{tag}>")
+}
+
+fn invtran(out: &mut String, e: &Entry) -> Result<()> {
+    out.push_str("<INVTRAN>");
+    element(out, "FITID", &e.id)?;
+    element(out, "DTTRADE", &datetime(e.at)?)?;
+    element(out, "MEMO", &e.memo)?;
+    out.push_str("</INVTRAN>");
+    Ok(())
+}
+
+fn secid(out: &mut String, security: &str) -> std::fmt::Result {
+    out.push_str("<SECID>");
+    element(out, "UNIQUEID", security)?;
+    element(out, "UNIQUEIDTYPE", "TICKER")?;
+    out.push_str("</SECID>");
+    Ok(())
+}
+
+fn transaction(out: &mut String, e: &Entry) -> Result<()> {
+    match &e.action {
+        Action::Buy {
+            security,
+            units,
+            price,
+            commission,
+            total,
+        } => {
+            out.push_str("<BUYSTOCK><INVBUY>");
+            invtran(out, e)?;
+            secid(out, security)?;
+            element(out, "UNITS", &num(*units))?;
+            element(out, "UNITPRICE", &num(*price))?;
+            element(out, "COMMISSION", &num(*commission))?;
+            element(out, "TOTAL", &num(-*total))?;
+            element(out, "SUBACCTSEC", "CASH")?;
+            element(out, "SUBACCTFUND", "CASH")?;
+            out.push_str("</INVBUY>");
+            element(out, "BUYTYPE", "BUY")?;
+            out.push_str("</BUYSTOCK>");
+        }
+        Action::Sell {
+            security,
+            units,
+            price,
+            commission,
+            total,
+        } => {
+            out.push_str("<SELLSTOCK><INVSELL>");
+            invtran(out, e)?;
+            secid(out, security)?;
+            element(out, "UNITS", &num(-*units))?;
+            element(out, "UNITPRICE", &num(*price))?;
+            element(out, "COMMISSION", &num(*commission))?;
+            element(out, "TOTAL", &num(*total))?;
+            element(out, "SUBACCTSEC", "CASH")?;
+            element(out, "SUBACCTFUND", "CASH")?;
+            out.push_str("</INVSELL>");
+            element(out, "SELLTYPE", "SELL")?;
+            out.push_str("</SELLSTOCK>");
+        }
+        Action::SharesIn {
+            security,
+            units,
+            price,
+        }
+        | Action::SharesOut {
+            security,
+            units,
+            price,
+        } => {
+            let (units, action) = match e.action {
+                Action::SharesIn { .. } => (*units, "IN"),
+                _ => (-*units, "OUT"),
+            };
+            out.push_str("<TRANSFER>");
+            invtran(out, e)?;
+            secid(out, security)?;
+            element(out, "SUBACCTSEC", "CASH")?;
+            element(out, "UNITS", &num(units))?;
+            element(out, "TFERACTION", action)?;
+            element(out, "POSTYPE", "LONG")?;
+            element(out, "UNITPRICE", &num(*price))?;
+            out.push_str("</TRANSFER>");
+        }
+        Action::Reinvest {
+            security,
+            units,
+            price,
+        } => {
+            out.push_str("<REINVEST>");
+            invtran(out, e)?;
+            secid(out, security)?;
+            element(out, "INCOMETYPE", "MISC")?;
+            element(out, "TOTAL", &num(-*units * *price))?;
+            element(out, "SUBACCTSEC", "CASH")?;
+            element(out, "UNITS", &num(*units))?;
+            element(out, "UNITPRICE", &num(*price))?;
+            out.push_str("</REINVEST>");
+        }
+        Action::Cash { kind, amount } => {
+            let (kind, amount) = match kind {
+                CashKind::Deposit => ("CREDIT", *amount),
+                CashKind::Withdraw => ("DEBIT", -*amount),
+                CashKind::Fee => ("FEE", -*amount),
+                CashKind::Interest => ("INT", *amount),
+            };
+            out.push_str("<INVBANKTRAN><STMTTRN>");
+            element(out, "TRNTYPE", kind)?;
+            element(out, "DTPOSTED", &datetime(e.at)?)?;
+            element(out, "TRNAMT", &num(amount))?;
+            element(out, "FITID", &e.id)?;
+            element(out, "NAME", &e.memo)?;
+            out.push_str("</STMTTRN>");
+            element(out, "SUBACCTFUND", "CASH")?;
+            out.push_str("</INVBANKTRAN>");
+        }
+    }
+    out.push('\n');
+    Ok(())
+}
+
+fn security(action: &Action) -> Option<&str> {
+    match action {
+        Action::Buy { security, .. }
+        | Action::Sell { security, .. }
+        | Action::SharesIn { security, .. }
+        | Action::SharesOut { security, .. }
+        | Action::Reinvest { security, .. } => Some(security),
+        Action::Cash { .. } => None,
+    }
+}
+
+/// OFX document of `statements`, generated at `now`
+pub fn document(statements: &[Statement], now: OffsetDateTime) -> Result<String> {
+    let now = datetime(now)?;
+    let mut out = String::new();
+    writeln!(out, "{}", HEADER)?;
+    out.push_str(
+        "<OFX>\n<SIGNONMSGSRSV1><SONRS><STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>",
+    );
+    element(&mut out, "DTSERVER", &now)?;
+    out.push_str("<LANGUAGE>ENG</LANGUAGE></SONRS></SIGNONMSGSRSV1>\n<INVSTMTMSGSRSV1>\n");
+    for (i, s) in statements.iter().enumerate() {
+        out.push_str("<INVSTMTTRNRS>");
+        element(&mut out, "TRNUID", &(i + 1).to_string())?;
+        out.push_str("<STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS><INVSTMTRS>");
+        element(&mut out, "DTASOF", &now)?;
+        element(&mut out, "CURDEF", &s.base)?;
+        out.push_str("<INVACCTFROM>");
+        element(&mut out, "BROKERID", "portfolio-tracker")?;
+        element(&mut out, "ACCTID", &s.name)?;
+        out.push_str("</INVACCTFROM>\n<INVTRANLIST>");
+        let start = s
+            .entries
+            .first()
+            .map_or(Ok(now.clone()), |e| datetime(e.at))?;
+        element(&mut out, "DTSTART", &start)?;
+        element(&mut out, "DTEND", &now)?;
+        out.push('\n');
+        for e in &s.entries {
+            transaction(&mut out, e)?;
+        }
+        out.push_str("</INVTRANLIST></INVSTMTRS></INVSTMTTRNRS>\n");
+    }
+    out.push_str("</INVSTMTMSGSRSV1>\n");
+
+    let securities: BTreeSet<&str> = statements
+        .iter()
+        .flat_map(|s| s.entries.iter().filter_map(|e| security(&e.action)))
+        .collect();
+    if !securities.is_empty() {
+        out.push_str("<SECLISTMSGSRSV1><SECLIST>\n");
+        for ticker in securities {
+            out.push_str("<STOCKINFO><SECINFO>");
+            secid(&mut out, ticker)?;
+            element(&mut out, "SECNAME", ticker)?;
+            element(&mut out, "TICKER", ticker)?;
+            out.push_str("</SECINFO></STOCKINFO>\n");
+        }
+        out.push_str("</SECLIST></SECLISTMSGSRSV1>\n");
+    }
+    out.push_str("</OFX>\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::Currency;
+    use crate::qif::entries;
+    use crate::test_utils::helpers::transactions_from;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_document_has_a_statement_and_security_list() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704067200,USD,DEPOSIT,30000,1,0
+1704883200,BTC/USD,BUY,0.5,40000,20
+1710460800,BTC,WITHDRAW,0.1,60000,0
+",
+        );
+        let usd = Currency::new("USD").unwrap();
+        let statement = Statement {
+            name: "main".to_string(),
+            base: "USD".to_string(),
+            entries: entries(&trades, &usd).unwrap(),
+        };
+        let doc = document(&[statement], datetime!(2024-04-01 0:00 UTC)).unwrap();
+        let lines: Vec<&str> = doc.lines().collect();
+        assert!(lines[1].starts_with("<?OFX OFXHEADER=\"200\""));
+        assert!(doc.contains("<CURDEF>USD</CURDEF><INVACCTFROM><BROKERID>portfolio-tracker</BROKERID><ACCTID>main</ACCTID>"));
+        assert!(doc.contains("<INVBANKTRAN><STMTTRN><TRNTYPE>CREDIT</TRNTYPE><DTPOSTED>20240101000000</DTPOSTED><TRNAMT>30000</TRNAMT>"));
+        assert!(doc.contains("<UNITS>0.5</UNITS><UNITPRICE>40000</UNITPRICE><COMMISSION>20</COMMISSION><TOTAL>-20020</TOTAL>"));
+        assert!(doc.contains("<UNITS>-0.1</UNITS><TFERACTION>OUT</TFERACTION>"));
+        assert!(doc.contains("<STOCKINFO><SECINFO><SECID><UNIQUEID>BTC</UNIQUEID><UNIQUEIDTYPE>TICKER</UNIQUEIDTYPE></SECID><SECNAME>BTC</SECNAME>"));
+        assert!(doc.ends_with("</SECLIST></SECLISTMSGSRSV1>\n</OFX>\n"));
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{Context, Result};
+use std::io::{IsTerminal, Read, Seek, Write};
+use std::process::{Command, Stdio};
+
+/// Pager used when `$PAGER` isn't set
+const DEFAULT_PAGER: &str = "less";
+/// `less` options when `$LESS` isn't set, same as git: quit when the output
+/// fits on one screen, keep colors, don't clear the screen on exit
+const DEFAULT_LESS: &str = "FRX";
+
+/// Pager command from the value of `$PAGER`, `None` when paging is disabled
+/// with an empty value or `cat`
+pub fn pager_command(env: Option<&str>) -> Option<String> {
+    match env.map(str::trim) {
+        None => Some(DEFAULT_PAGER.to_string()),
+        Some("") | Some("cat") => None,
+        Some(cmd) => Some(cmd.to_string()),
+    }
+}
+
+/// Whether `text` takes more than `rows` lines on a terminal `cols` wide,
+/// counting wrapped lines
+pub fn exceeds_screen(text: &str, rows: usize, cols: usize) -> bool {
+    let cols = cols.max(1);
+    let mut lines = 0;
+    for line in text.lines() {
+        lines += line.chars().count().div_ceil(cols).max(1);
+        if lines > rows {
+            return true;
+        }
+    }
+    false
+}
+
+/// Redirects everything printed to stdout into a temporary file
+///
+/// Commands keep printing to stdout through their reporter, the captured
+/// text is returned by `finish` once they are done.
+pub struct Capture {
+    buffer: std::fs::File,
+    #[cfg(unix)]
+    saved_stdout: std::os::fd::OwnedFd,
+}
+
+impl Capture {
+    #[cfg(unix)]
+    pub fn start() -> Result<Capture> {
+        let buffer = tempfile::tempfile()?;
+        std::io::stdout().flush()?;
+        let saved_stdout = nix::unistd::dup(std::io::stdout())?;
+        nix::unistd::dup2_stdout(&buffer)?;
+        Ok(Capture {
+            buffer,
+            saved_stdout,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn start() -> Result<Capture> {
+        anyhow::bail!("capturing output is only supported on unix")
+    }
+
+    /// Restore stdout and return everything printed since `start`
+    pub fn finish(mut self) -> Result<String> {
+        std::io::stdout().flush()?;
+        self.restore()?;
+        let mut text = String::new();
+        self.buffer.rewind()?;
+        self.buffer.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[cfg(unix)]
+    fn restore(&mut self) -> Result<()> {
+        nix::unistd::dup2_stdout(&self.saved_stdout)?;
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn restore(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Output of a printing command, shown once the command is done: through
+/// `$PAGER` when it doesn't fit on the terminal, and copied to the clipboard
+/// with `--copy`
+pub struct Output {
+    capture: Capture,
+    pager: Option<String>,
+    copy: bool,
+}
+
+impl Output {
+    /// Start capturing stdout when there is something to do with the output:
+    /// paging (if `page` is set, stdout is a terminal and `$PAGER` doesn't
+    /// disable it) or copying
+    pub fn start(page: bool, copy: bool) -> Result<Option<Output>> {
+        let pager = if page && std::io::stdout().is_terminal() {
+            pager_command(std::env::var("PAGER").ok().as_deref())
+        } else {
+            None
+        };
+        if pager.is_none() && !copy {
+            return Ok(None);
+        }
+        match Capture::start() {
+            Ok(capture) => Ok(Some(Output {
+                capture,
+                pager,
+                copy,
+            })),
+            // paging is best effort, copying was asked for
+            Err(_) if !copy => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Show the captured output, then copy it when asked
+    pub fn finish(self) -> Result<()> {
+        let text = self.capture.finish()?;
+        let paged = match &self.pager {
+            Some(command) => {
+                let (cols, rows) = terminal_size::terminal_size()
+                    .map(|(w, h)| (w.0 as usize, h.0 as usize))
+                    .unwrap_or((usize::MAX, usize::MAX));
+                // leave a line for the shell prompt
+                exceeds_screen(&text, rows.saturating_sub(1), cols) && page(command, &text).is_ok()
+            }
+            None => false,
+        };
+        if !paged {
+            let mut stdout = std::io::stdout();
+            stdout.write_all(text.as_bytes())?;
+            stdout.flush()?;
+        }
+        if self.copy {
+            copy_to_clipboard(&text)?;
+            eprintln!("📋 Copied {} lines to the clipboard", text.lines().count());
+        }
+        Ok(())
+    }
+}
+
+fn page(command: &str, text: &str) -> Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env(
+            "LESS",
+            std::env::var("LESS").unwrap_or(DEFAULT_LESS.to_string()),
+        )
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // the user may quit the pager before reading everything
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Put `text` on the system clipboard
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut clipboard =
+        arboard::Clipboard::new().context("no system clipboard available for --copy")?;
+    clipboard
+        .set_text(text)
+        .context("copying output to the clipboard")?;
+    Ok(())
+}
+
+/// Text on the system clipboard
+pub fn paste_from_clipboard() -> Result<String> {
+    let mut clipboard = arboard::Clipboard::new().context("no system clipboard available")?;
+    clipboard
+        .get_text()
+        .context("reading text from the clipboard")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pager_command_from_env() {
+        assert_eq!(pager_command(None), Some("less".to_string()));
+        assert_eq!(pager_command(Some("more -s")), Some("more -s".to_string()));
+        assert_eq!(pager_command(Some("cat")), None);
+        assert_eq!(pager_command(Some(" ")), None);
+    }
+
+    #[test]
+    fn test_exceeds_screen_counts_wrapped_lines() {
+        let text = "a\nbb\nccc\n";
+        assert!(!exceeds_screen(text, 3, 80));
+        assert!(exceeds_screen(text, 2, 80));
+        // "ccc" wraps onto a second line
+        assert!(exceeds_screen(text, 3, 2));
+        assert!(!exceeds_screen("", 0, 80));
+    }
+}
@@ -0,0 +1,207 @@
+//! Parquet export
+//!
+//! One row group per table. Decimals are `DECIMAL(38, 18)`, enough for any
+//! amount or price without rounding; a value needing more digits is an error
+//! rather than a rounded number.
+
+use crate::export::{Column, ColumnType, Table, Value};
+use anyhow::{Context, Result, anyhow, bail};
+use parquet::basic::{LogicalType, Repetition, TimeUnit, Type as PhysicalType};
+use parquet::data_type::{ByteArray, ByteArrayType, FixedLenByteArray, FixedLenByteArrayType};
+use parquet::data_type::{DataType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{SerializedColumnWriter, SerializedFileWriter};
+use parquet::format::MilliSeconds;
+use parquet::schema::types::{Type, TypePtr};
+use rust_decimal::Decimal;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+const DECIMAL_PRECISION: u32 = 38;
+const DECIMAL_SCALE: u32 = 18;
+
+fn field(column: &Column) -> Result<TypePtr> {
+    let builder = match column.kind {
+        ColumnType::Text => Type::primitive_type_builder(column.name, PhysicalType::BYTE_ARRAY)
+            .with_logical_type(Some(LogicalType::String)),
+        ColumnType::Timestamp => Type::primitive_type_builder(column.name, PhysicalType::INT64)
+            .with_logical_type(Some(LogicalType::Timestamp {
+                is_adjusted_to_u_t_c: true,
+                unit: TimeUnit::MILLIS(MilliSeconds {}),
+            })),
+        ColumnType::Decimal => {
+            Type::primitive_type_builder(column.name, PhysicalType::FIXED_LEN_BYTE_ARRAY)
+                .with_length(16)
+                .with_logical_type(Some(LogicalType::Decimal {
+                    scale: DECIMAL_SCALE as i32,
+                    precision: DECIMAL_PRECISION as i32,
+                }))
+                .with_precision(DECIMAL_PRECISION as i32)
+                .with_scale(DECIMAL_SCALE as i32)
+        }
+    };
+    let repetition = if column.nullable {
+        Repetition::OPTIONAL
+    } else {
+        Repetition::REQUIRED
+    };
+    Ok(Arc::new(builder.with_repetition(repetition).build()?))
+}
+
+/// The unscaled value of `d` at `DECIMAL_SCALE`, as the big-endian two's
+/// complement Parquet stores decimals in
+fn decimal_bytes(d: Decimal) -> Result<FixedLenByteArray> {
+    let d = d.normalize();
+    let unscaled = if d.scale() <= DECIMAL_SCALE {
+        d.mantissa()
+            .checked_mul(10i128.pow(DECIMAL_SCALE - d.scale()))
+    } else {
+        None
+    };
+    let unscaled = unscaled
+        .filter(|u| u.unsigned_abs() < 10u128.pow(DECIMAL_PRECISION))
+        .ok_or(anyhow!(
+            "{} doesn't fit DECIMAL({}, {})",
+            d,
+            DECIMAL_PRECISION,
+            DECIMAL_SCALE
+        ))?;
+    Ok(FixedLenByteArray::from(unscaled.to_be_bytes().to_vec()))
+}
+
+/// The non-null `values` of one column, converted by `convert`
+fn non_null<T>(
+    column: &Column,
+    values: &[&Value],
+    convert: impl Fn(&Value) -> Option<Result<T>>,
+) -> Result<Vec<T>> {
+    values
+        .iter()
+        .filter(|v| **v != &Value::Null)
+        .map(|v| convert(v).unwrap_or_else(|| bail!("{:?} in column {}", v, column.name)))
+        .collect()
+}
+
+fn write_column<T: DataType>(
+    writer: &mut SerializedColumnWriter<'_>,
+    data: &[T::T],
+    def_levels: Option<&[i16]>,
+) -> Result<()> {
+    writer.typed::<T>().write_batch(data, def_levels, None)?;
+    Ok(())
+}
+
+/// Write `table` as a new Parquet file at `path`
+pub fn write(path: &Path, table: &Table) -> Result<()> {
+    let schema = Type::group_type_builder("schema")
+        .with_fields(table.columns.iter().map(field).collect::<Result<_>>()?)
+        .build()?;
+    let props = WriterProperties::builder()
+        .set_created_by(format!(
+            "portfolio-tracker version {}",
+            env!("CARGO_PKG_VERSION")
+        ))
+        .build();
+    let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let mut writer = SerializedFileWriter::new(file, Arc::new(schema), Arc::new(props))?;
+
+    let mut row_group = writer.next_row_group()?;
+    for (c, column) in table.columns.iter().enumerate() {
+        let values: Vec<&Value> = table.rows.iter().map(|row| &row[c]).collect();
+        let levels: Vec<i16> = values.iter().map(|v| (*v != &Value::Null) as i16).collect();
+        let def_levels = column.nullable.then_some(levels.as_slice());
+        let mut out = row_group
+            .next_column()?
+            .ok_or(anyhow!("no column {} in the schema", column.name))?;
+        match column.kind {
+            ColumnType::Text => {
+                let data = non_null(column, &values, |v| match v {
+                    Value::Text(s) => Some(Ok(ByteArray::from(s.as_str()))),
+                    _ => None,
+                })?;
+                write_column::<ByteArrayType>(&mut out, &data, def_levels)?;
+            }
+            ColumnType::Timestamp => {
+                let data = non_null(column, &values, |v| match v {
+                    Value::Timestamp(ts) => {
+                        Some(Ok((ts.unix_timestamp_nanos() / 1_000_000) as i64))
+                    }
+                    _ => None,
+                })?;
+                write_column::<Int64Type>(&mut out, &data, def_levels)?;
+            }
+            ColumnType::Decimal => {
+                let data = non_null(column, &values, |v| match v {
+                    Value::Decimal(d) => {
+                        Some(decimal_bytes(*d).with_context(|| format!("column {}", column.name)))
+                    }
+                    _ => None,
+                })?;
+                write_column::<FixedLenByteArrayType>(&mut out, &data, def_levels)?;
+            }
+        }
+        out.close()?;
+    }
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::trades_table;
+    use crate::test_utils::helpers::transactions_from;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+    use rust_decimal::dec;
+
+    fn bytes(d: Decimal) -> Vec<u8> {
+        decimal_bytes(d).unwrap().data().to_vec()
+    }
+
+    #[test]
+    fn test_decimal_bytes() {
+        assert_eq!(bytes(dec!(1)), 10i128.pow(18).to_be_bytes());
+        assert_eq!(bytes(dec!(-0.00000001)), (-10i128.pow(10)).to_be_bytes());
+        assert_eq!(bytes(dec!(2.50)), bytes(dec!(2.5)));
+        let err = decimal_bytes(dec!(0.0000000000000000001)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "0.0000000000000000001 doesn't fit DECIMAL(38, 18)"
+        );
+    }
+
+    #[test]
+    fn test_write_reads_back_with_types() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee,source
+1704883200,BTC/USD,BUY,0.1,40000.10,0.00000001,manual
+1704969600,USD,DEPOSIT,100,1,0,
+",
+        );
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("trades.parquet");
+        write(&path, &trades_table(&[("main".to_string(), trades)])).unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        let rows: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get_timestamp_millis(2).unwrap(), 1_704_883_200_000);
+        let decimal = |i: usize| {
+            let d = rows[0].get_decimal(i).unwrap();
+            assert_eq!((d.precision(), d.scale()), (38, 18));
+            d.data().to_vec()
+        };
+        assert_eq!(decimal(7), bytes(dec!(0.1)));
+        assert_eq!(decimal(8), bytes(dec!(40000.1)));
+        assert_eq!(decimal(9), bytes(dec!(0.00000001)));
+        assert_eq!(rows[0].get_string(10).unwrap(), "manual");
+        assert!(rows[1].get_string(10).is_err());
+    }
+}
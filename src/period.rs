@@ -0,0 +1,124 @@
+use crate::settings::{Settings, WeekStart};
+use anyhow::Result;
+use time::{Date, Duration, Month};
+
+/// Length of the periods rows are grouped into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Period {
+    Week,
+    Month,
+    Year,
+}
+
+/// Period boundaries following the user's conventions: the first day of a
+/// week and the month a (fiscal) year starts in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calendar {
+    pub week_starts_on: WeekStart,
+    pub year_start: Month,
+}
+
+impl Default for Calendar {
+    fn default() -> Self {
+        Calendar {
+            week_starts_on: WeekStart::default(),
+            year_start: Month::January,
+        }
+    }
+}
+
+impl Calendar {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Calendar {
+            week_starts_on: settings.week_starts_on,
+            // out of range values are reset by `Settings::validate`
+            year_start: Month::try_from(settings.fiscal_year_start_month).unwrap_or(Month::January),
+        }
+    }
+
+    /// First day of the week containing `date`
+    pub fn week_start(&self, date: Date) -> Date {
+        let first = self.week_starts_on.weekday();
+        let days_in =
+            (date.weekday().number_days_from_monday() + 7 - first.number_days_from_monday()) % 7;
+        date - Duration::days(days_in as i64)
+    }
+
+    /// Calendar year the (fiscal) year containing `date` starts in
+    pub fn year_of(&self, date: Date) -> i32 {
+        if (date.month() as u8) < (self.year_start as u8) {
+            date.year() - 1
+        } else {
+            date.year()
+        }
+    }
+
+    /// First day and the day after the last day of the (fiscal) year starting in `year`
+    pub fn year_range(&self, year: i32) -> Result<(Date, Date)> {
+        Ok((
+            Date::from_calendar_date(year, self.year_start, 1)?,
+            Date::from_calendar_date(year + 1, self.year_start, 1)?,
+        ))
+    }
+
+    /// Label of a (fiscal) year: `2024`, or `2024/25` when it doesn't start in January
+    pub fn year_label(&self, year: i32) -> String {
+        if self.year_start == Month::January {
+            year.to_string()
+        } else {
+            format!("{}/{:02}", year, (year + 1) % 100)
+        }
+    }
+
+    /// Sortable label of the period containing `date`
+    pub fn label(&self, period: Period, date: Date) -> String {
+        match period {
+            Period::Week => self.week_start(date).to_string(),
+            Period::Month => format!("{}-{:02}", date.year(), date.month() as u8),
+            Period::Year => self.year_label(self.year_of(date)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use time::macros::date;
+
+    #[rstest]
+    #[case(WeekStart::Monday, date!(2024 - 01 - 08))]
+    #[case(WeekStart::Sunday, date!(2024 - 01 - 07))]
+    #[case(WeekStart::Saturday, date!(2024 - 01 - 06))]
+    fn test_week_start(#[case] week_starts_on: WeekStart, #[case] expected: Date) {
+        let calendar = Calendar {
+            week_starts_on,
+            ..Calendar::default()
+        };
+        // a wednesday
+        assert_eq!(calendar.week_start(date!(2024 - 01 - 10)), expected);
+        assert_eq!(calendar.week_start(expected), expected);
+    }
+
+    #[test]
+    fn test_fiscal_year_starting_in_april() {
+        let calendar = Calendar {
+            year_start: Month::April,
+            ..Calendar::default()
+        };
+        assert_eq!(calendar.year_of(date!(2024 - 03 - 31)), 2023);
+        assert_eq!(calendar.year_of(date!(2024 - 04 - 01)), 2024);
+        assert_eq!(
+            calendar.label(Period::Year, date!(2025 - 01 - 15)),
+            "2024/25"
+        );
+        assert_eq!(
+            calendar.year_range(2024).unwrap(),
+            (date!(2024 - 04 - 01), date!(2025 - 04 - 01))
+        );
+        assert_eq!(
+            Calendar::default().label(Period::Year, date!(2025 - 01 - 15)),
+            "2025"
+        );
+    }
+}
@@ -0,0 +1,52 @@
+//! Trades piped in on standard input
+//!
+//! `show --stdin` and `report --stdin` read trades the way `import` reads an
+//! export: the tracker's own CSV, a format it detects or one of the import
+//! profiles. Nothing is written to the portfolio dir, so an export can be
+//! looked at and valued in a pipeline before it's imported.
+
+use crate::format::OutputFormat;
+use crate::import;
+use crate::portfolio::{self, Portfolio};
+use crate::quote::Prices;
+use crate::report;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{CsvConfig, Trade, render_csv_records};
+use anyhow::{Result, bail};
+use std::io::{IsTerminal, Read, Write};
+
+fn read(settings: &Settings) -> Result<(CsvConfig, Vec<Trade>)> {
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        bail!(
+            "--stdin reads trades piped in, e.g. `portfolio-tracker report --stdin < export.csv`"
+        );
+    }
+    let mut text = String::new();
+    stdin.lock().read_to_string(&mut text)?;
+    import::parse_text(&text, settings)
+}
+
+/// `show --stdin`: print the trades piped in, only those from `source` when given
+pub fn show(
+    source: Option<&str>,
+    format: OutputFormat,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let (_, trades) = read(settings)?;
+    portfolio::print_trades(&trades, source, format, None, reporter)
+}
+
+/// `report --stdin`: value the trades piped in as `report --name` values a
+/// portfolio
+pub fn report(prices: &dyn Prices, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let (config, trades) = read(settings)?;
+    let records: Vec<csv::StringRecord> = trades.iter().map(Trade::to_record).collect();
+    // valuing reads a portfolio file, a scratch one outside the portfolio dir
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(&render_csv_records(&config, &records, true)?)?;
+    let pf = Portfolio::from_csv(file.path())?;
+    report::show_portfolio(&pf, file.path(), prices, reporter, settings)
+}
@@ -1,26 +1,46 @@
+use crate::conflict::{
+    find_conflicts, find_duplicates, print_conflict_warnings, print_duplicate_warnings,
+};
+use crate::contribution::Contribution;
 use crate::currency::{Currency, CurrencyType};
-use crate::quote::quote_in_base;
+use crate::format::{OutputFormat, Records};
+use crate::holdings::{self, HOLDINGS_HEADER};
+use crate::index::{self, WorkspaceIndex};
+use crate::journal;
+use crate::math::{self, Percent};
+use crate::position_cache;
+use crate::quote::{LiveQuotes, Prices};
+use crate::reporter::Reporter;
 use crate::settings::Settings;
-use crate::trade::{CSV_HEADER, parse_csv_file};
+use crate::trade::{
+    CSV_HEADER, CsvConfig, Instrument, PortfolioKind, SOURCE_COLUMN, Side, Trade, TradingPair,
+    for_each_trade, parse_csv_file, read_base_currency, read_csv_config,
+};
 use crate::tx::Tx;
-use anyhow::{Result, anyhow};
-use prettytable::{Cell, Row, Table, row};
+use crate::warnings;
+use anyhow::{Context, Result, anyhow, bail};
+use prettytable::{Cell, Table, row};
 use rust_decimal::Decimal;
 use rust_decimal::dec;
-use std::collections::HashMap;
-use std::ffi::OsString;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{DirEntry, File};
-use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 use thousands::Separable;
-use time::OffsetDateTime;
 use time::macros::format_description;
+use time::{Date, OffsetDateTime};
 
-#[derive(Debug)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Portfolio {
     pub positions: HashMap<Currency, Position>,
     pub base: Currency,
+    /// Base currency added to fund buys not covered by recorded deposits
+    pub implicit_funding: Decimal,
+    /// Deposits (positive) and withdrawals (negative) valued in the base currency
+    pub contributions: Vec<Contribution>,
+    /// Holdings recorded without a cost, valued but left out of PnL
+    pub unknown_cost: HashSet<Currency>,
 }
 
 impl Portfolio {
@@ -28,10 +48,19 @@ impl Portfolio {
         Portfolio {
             positions: HashMap::new(),
             base: Currency::default(),
+            implicit_funding: dec!(0),
+            contributions: Vec::new(),
+            unknown_cost: HashSet::new(),
         }
     }
 
-    pub fn deposit(&mut self, currency: Currency, amount: Decimal) -> Result<()> {
+    /// Deposit valued at `prices`, the base currency at face value
+    pub fn deposit(
+        &mut self,
+        currency: Currency,
+        amount: Decimal,
+        prices: &dyn Prices,
+    ) -> Result<()> {
         let pos = self
             .positions
             .entry(currency.clone())
@@ -43,13 +72,44 @@ impl Portfolio {
         if currency == self.base {
             pos.cost_base += amount;
         } else {
-            pos.cost_base += amount * quote_in_base(&currency, &self.base.ticker())?;
+            pos.cost_base += amount * prices.price_in(&currency, self.base.ticker())?;
         }
 
         Ok(())
     }
 
-    // withdraw currency TODO
+    /// Deposit with a known cost, e.g. from a deposit row with the unit value at the time
+    pub fn deposit_at_cost(&mut self, currency: Currency, amount: Decimal, cost: Decimal) {
+        let pos = self
+            .positions
+            .entry(currency.clone())
+            .or_insert(Position::new(currency));
+        pos.balance += amount;
+        pos.cost_base += cost;
+    }
+
+    /// Move `amount` out of the portfolio, reducing the cost base proportionally
+    pub fn withdraw(&mut self, currency: &Currency, amount: Decimal) -> Result<()> {
+        let pos = self
+            .positions
+            .get_mut(currency)
+            .filter(|p| p.balance >= amount)
+            .ok_or(anyhow!(
+                "Insufficient balance to withdraw {} {}",
+                amount,
+                currency
+            ))?;
+        pos.cost_base -= pos.cost_base * amount / pos.balance;
+        pos.balance -= amount;
+        Ok(())
+    }
+
+    pub fn balance(&self, currency: &Currency) -> Decimal {
+        self.positions
+            .get(currency)
+            .map(|p| p.balance)
+            .unwrap_or_default()
+    }
 
     // buy side - sell side
     // 1   BTC for 100_000 USD
@@ -65,7 +125,7 @@ impl Portfolio {
         }
 
         // Calculate proportional cost basis being sold
-        let avg_cost = if tx.sell.ticker() == "USD".to_string() {
+        let avg_cost = if tx.sell.ticker() == "USD" {
             dec!(1)
         } else {
             // (sell_pos.cost_base / sell_pos.balance).round_dp(2)
@@ -90,107 +150,186 @@ impl Portfolio {
         Ok(())
     }
 
-    pub fn from_csv<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let (csv_conf, trades) = parse_csv_file(&path)?;
+    /// The crypto positions valued at `prices` in the base currency, with
+    /// their PnL where the cost is known
+    pub fn valuation(&self, prices: &dyn Prices) -> Result<Valuation> {
+        let mut valuation = Valuation {
+            base: self.base.clone(),
+            ..Valuation::default()
+        };
+        for (currency, position) in self.positions.iter() {
+            if currency.currency_type() != CurrencyType::Crypto {
+                continue;
+            }
+            let value = position.balance * prices.price_in(currency, self.base.ticker())?;
+            let cost_base = (!self.unknown_cost.contains(currency)).then_some(position.cost_base);
+            valuation.value += value;
+            if let Some(cost_base) = cost_base {
+                valuation.cost_base += cost_base;
+                valuation.pnl += value - cost_base;
+            }
+            valuation.positions.push(ValuedPosition {
+                currency: currency.clone(),
+                balance: position.balance,
+                cost_base,
+                value,
+            });
+        }
+        Ok(valuation)
+    }
 
-        let mut pf = Portfolio::new();
-        pf.base = Currency::new(&csv_conf.base_currency)?;
+    /// Market value of the crypto positions at `prices`, in the portfolio base currency
+    pub fn market_value(&self, prices: &dyn Prices) -> Result<Decimal> {
+        Ok(self.valuation(prices)?.value)
+    }
 
-        for trade in trades {
-            let amount = trade.amount * trade.price + trade.fee;
-            // deposit base currency (USD), so I can add tx
-            pf.deposit(Currency::new(csv_conf.base_currency.as_str())?, amount)?;
-            pf.add_tx(trade.to_tx()?)?;
+    /// Value of each position at `prices` in the base currency: crypto and
+    /// stable coins at their price, cash in the base currency at face value;
+    /// other fiat is left out
+    pub fn position_values(&self, prices: &dyn Prices) -> Result<HashMap<Currency, Decimal>> {
+        let mut values = HashMap::new();
+        for (currency, position) in self.positions.iter() {
+            let value = if *currency == self.base {
+                position.balance
+            } else if currency.currency_type() == CurrencyType::Fiat {
+                continue;
+            } else {
+                position.balance * prices.price_in(currency, self.base.ticker())?
+            };
+            values.insert(currency.clone(), value);
         }
+        Ok(values)
+    }
 
-        Ok(pf)
+    /// Unrealized PnL at `prices` of the crypto positions with a known cost,
+    /// in the base currency
+    pub fn unrealized_pnl(&self, prices: &dyn Prices) -> Result<Decimal> {
+        Ok(self.valuation(prices)?.pnl)
     }
 
-    // +--------+---------+---------------+---------------+---------+
-    // | Ticker | Balance | Cost Base     | Avg Price     | PnL %   |
-    // +--------+---------+---------------+---------------+---------+
-    // | ETH    | 11.0    | 40,705.50 USD | 3,700.50 USD  | -23.86% |
-    // +--------+---------+---------------+---------------+---------+
-    // | BTC    | 3.5     | 87,515.31 USD | 25,004.38 USD | 242.57% |
-    // +--------+---------+---------------+---------------+---------+
-    // =================================
-    // Portfolio:      330_793.85 USD
-    // Total PnL:      202_573.04 USD
-    // Total PnL:      157.99%
-    // =================================
-    pub fn print_unrealized_pnl<P: AsRef<Path>>(path: P, ticker: &str) -> Result<()> {
-        let pf = Portfolio::from_csv(path)?;
-
-        if pf.positions.len() == 0 {
-            println!("No positions in portfolio");
-            return Ok(());
-        }
+    pub fn from_csv<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_csv_as_of(path, None)
+    }
 
-        let mut total_cost_base = dec!(0);
-        let mut total_balance = dec!(0);
-
-        use prettytable::{Table, row};
-
-        let mut table = Table::new();
-        table.add_row(row!["Ticker", "Balance", "Cost Base", "Avg Price", "PnL %"]);
-
-        for (currency, position) in pf.positions.iter() {
-            if currency.currency_type() == CurrencyType::Crypto {
-                let avg_price = position.cost_base / position.balance;
-                let current_balance = position.balance * quote_in_base(currency, ticker)?;
-
-                total_balance += current_balance;
-                total_cost_base += position.cost_base;
-
-                let pnl_percent =
-                    ((current_balance - position.cost_base) / position.cost_base) * dec!(100);
-
-                table.add_row(row![
-                    currency,
-                    position.balance.round_dp(2),
-                    format!(
-                        "{} {}",
-                        position.cost_base.round_dp(2).separate_with_commas(),
-                        pf.base
-                    ),
-                    format!(
-                        "{} {}",
-                        avg_price.round_dp(2).separate_with_commas(),
-                        pf.base
-                    ),
-                    format!("{:.2}%", pnl_percent)
-                ]);
+    /// Portfolio from the trades up to and including `as_of` (UTC), all when `None`
+    pub fn from_csv_as_of<P: AsRef<Path>>(path: P, as_of: Option<Date>) -> Result<Self> {
+        let csv_conf = read_csv_config(&path)?;
+        if csv_conf.kind == PortfolioKind::Holdings {
+            if as_of.is_some() {
+                bail!("holdings-only portfolios have no history to value at a past date");
             }
+            let (config, rows) = holdings::load(&path)?;
+            return holdings::to_portfolio(&config, &rows);
         }
+        let base = Currency::new(&csv_conf.base_currency)?;
 
-        table.printstd();
+        let mut pf = Portfolio::new();
+        pf.base = base.clone();
+        // pairs repeat a lot, parse each distinct one once
+        let mut pairs: HashMap<String, TradingPair> = HashMap::new();
 
-        let total_pnl_percent = ((total_balance - total_cost_base) / total_cost_base) * dec!(100);
+        for_each_trade(&path, |trade| {
+            if as_of.is_some_and(|day| trade.created_at.date() > day) {
+                return Ok(());
+            }
+            match trade.side {
+                Side::Deposit | Side::Withdraw | Side::Fee | Side::Income => {
+                    let instrument: Instrument = trade.pair.parse()?;
+                    let asset = instrument.asset(trade.side)?.clone();
+                    let value = trade.amount * trade.price;
+                    match trade.side {
+                        Side::Deposit => {
+                            pf.deposit_at_cost(asset, trade.amount, value);
+                            pf.contributions
+                                .push(Contribution::new(trade.created_at, value));
+                            return Ok(());
+                        }
+                        // income is a return on the portfolio, not money put in,
+                        // so it comes at no cost and shows in the PnL
+                        Side::Income => {
+                            pf.deposit_at_cost(asset, trade.amount, dec!(0));
+                            return Ok(());
+                        }
+                        Side::Withdraw => pf
+                            .contributions
+                            .push(Contribution::new(trade.created_at, -value)),
+                        // fees leave the portfolio as a cost, not a contribution
+                        _ => {}
+                    }
+                    return pf.withdraw(&asset, trade.amount);
+                }
+                Side::Buy | Side::Sell => {}
+            }
 
-        println!("=================================");
-        println!(
-            "Portfolio:\t{} {}",
-            total_balance.round_dp(2).separate_with_underscores(),
-            pf.base
-        );
-        println!(
-            "Total PnL:\t{} {}",
-            (total_balance - total_cost_base)
-                .round_dp(2)
-                .separate_with_underscores(),
-            pf.base
-        );
-        println!(
-            "Total PnL:\t{}%",
-            total_pnl_percent.round_dp(2).separate_with_underscores()
-        );
-        println!("=================================");
+            let pair = match pairs.get(trade.pair) {
+                Some(pair) => pair,
+                None => pairs
+                    .entry(trade.pair.to_string())
+                    .or_insert(trade.pair.parse()?),
+            };
+            if trade.side == Side::Buy && pair.quote == base {
+                // buys not covered by deposited base currency are funded implicitly
+                let cost = trade.amount * trade.price + trade.fee;
+                let shortfall = cost - pf.balance(&base);
+                if shortfall > dec!(0) {
+                    pf.deposit_at_cost(base.clone(), shortfall, shortfall);
+                    pf.implicit_funding += shortfall;
+                }
+            }
+            pf.add_tx(trade.to_tx(pair))
+        })?;
 
-        Ok(())
+        Ok(pf)
+    }
+}
+
+/// A crypto position valued in the base currency of its portfolio
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValuedPosition {
+    pub currency: Currency,
+    pub balance: Decimal,
+    /// `None` for holdings recorded without a cost
+    pub cost_base: Option<Decimal>,
+    pub value: Decimal,
+}
+
+impl ValuedPosition {
+    /// Average price paid per unit, `None` without a cost or balance
+    pub fn avg_price(&self) -> Option<Decimal> {
+        math::ratio(self.cost_base?, self.balance)
+    }
+
+    /// PnL over the cost base, `None` without a cost, e.g. for airdrops
+    pub fn pnl_pct(&self) -> Option<Percent> {
+        math::change_pct(self.cost_base?, self.value)
+    }
+}
+
+/// The crypto positions of a portfolio at some prices, see `Portfolio::valuation`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Valuation {
+    pub base: Currency,
+    pub positions: Vec<ValuedPosition>,
+    /// Value of all the positions
+    pub value: Decimal,
+    /// Cost and PnL of the positions with a known cost
+    pub cost_base: Decimal,
+    pub pnl: Decimal,
+}
+
+impl Valuation {
+    /// PnL over the cost base of the positions with a known cost
+    pub fn pnl_pct(&self) -> Option<Percent> {
+        math::percent_of(self.pnl, self.cost_base)
+    }
+
+    /// Whether the valued positions all lack a cost, so there is no PnL to show
+    pub fn all_uncosted(&self) -> bool {
+        !self.positions.is_empty() && self.positions.iter().all(|p| p.cost_base.is_none())
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Position {
     pub currency: Currency,
     pub balance: Decimal,
@@ -207,74 +346,413 @@ impl Position {
     }
 }
 
-// +---------------+---------------------+
-// | CSV file name | Created at          |
-// +---------------+---------------------+
-// | example.csv   | 2025-12-05 20:01:21 |
-// +---------------+---------------------+
-pub fn list_csv_files(settings: &Settings) -> Result<()> {
-    let mut files: Vec<(OsString, SystemTime)> = Vec::new();
+/// A portfolio file found in the portfolio dir, with its metadata
+#[derive(Debug, Clone)]
+pub struct PortfolioEntry {
+    pub name: String,
+    pub path: PathBuf,
+    /// From the portfolio metadata, falls back to the filesystem for older files
+    pub created: SystemTime,
+    pub modified: SystemTime,
+    /// `None` when the metadata can't be read
+    pub base_currency: Option<String>,
+    /// When the portfolio was closed, see `close`
+    pub closed_at: Option<OffsetDateTime>,
+}
+
+/// Filters applied by `list`
+#[derive(Debug, Default)]
+pub struct ListFilter {
+    /// Glob pattern matched against the portfolio name, e.g. `kraken*`
+    pub pattern: Option<glob::Pattern>,
+    pub base_currency: Option<Currency>,
+}
+
+impl ListFilter {
+    pub fn new(pattern: Option<&str>, base_currency: Option<&str>) -> Result<Self> {
+        Ok(ListFilter {
+            pattern: pattern
+                .map(glob::Pattern::new)
+                .transpose()
+                .map_err(|e| anyhow!("invalid --match pattern: {}", e))?,
+            base_currency: base_currency.map(Currency::new).transpose()?,
+        })
+    }
 
-    for entry in settings.portfolio_dir.read_dir()? {
+    pub fn matches(&self, entry: &PortfolioEntry) -> bool {
+        let name_ok = self.pattern.as_ref().is_none_or(|p| p.matches(&entry.name));
+        let base_ok = self
+            .base_currency
+            .as_ref()
+            .is_none_or(|c| entry.base_currency.as_deref() == Some(c.ticker()));
+        name_ok && base_ok
+    }
+}
+
+/// Sort order of `list`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ListSort {
+    /// Alphabetically by portfolio name
+    #[default]
+    Name,
+    /// Oldest first, by creation time from the portfolio metadata
+    Created,
+    /// Least recently modified first
+    Modified,
+    /// Highest current market value first (fetches quotes)
+    Value,
+}
+
+/// Collect portfolio files with their metadata, sorted by name
+pub fn list_entries(settings: &Settings) -> Result<Vec<PortfolioEntry>> {
+    list_entries_in(&settings.portfolio_dir)
+}
+
+/// Portfolio files of all `data_dirs`, sorted by name, then in the order of the dirs
+pub fn list_all_entries(settings: &Settings) -> Result<Vec<PortfolioEntry>> {
+    let mut entries = Vec::new();
+    for dir in settings.data_dirs() {
+        entries
+            .extend(list_entries_in(&dir).with_context(|| format!("reading {}", dir.display()))?);
+    }
+    // stable sort keeps the order of the dirs for equal names
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+/// `entries` without those matching `ignore_patterns`, unless `include_ignored`
+pub fn skip_ignored(
+    entries: Vec<PortfolioEntry>,
+    include_ignored: bool,
+    settings: &Settings,
+) -> Vec<PortfolioEntry> {
+    entries
+        .into_iter()
+        .filter(|e| include_ignored || !settings.is_ignored(&e.name))
+        .collect()
+}
+
+/// Directory of an entry, for the directory column of federated listings
+fn entry_dir(entry: &PortfolioEntry) -> String {
+    entry
+        .path
+        .parent()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default()
+}
+
+fn list_entries_in(dir: &Path) -> Result<Vec<PortfolioEntry>> {
+    let mut entries: Vec<PortfolioEntry> = Vec::new();
+
+    for entry in dir.read_dir()? {
         let entry: DirEntry = entry?;
         let metadata: std::fs::Metadata = entry.metadata()?;
 
-        // Skip directories or special files
-        if !metadata.is_file() {
+        // Skip directories, special files and anything but portfolios, e.g.
+        // notes or editor backups
+        let path = entry.path();
+        if !metadata.is_file() || path.extension().is_none_or(|ext| ext != "csv") {
             continue;
         }
 
-        let created = metadata.created().or_else(|_| metadata.modified())?; // fallback for Unix consistency
-        let path = entry.path();
+        let modified = metadata.modified()?;
         let name = path.file_stem().ok_or(anyhow!("err getting name"))?;
-        files.push((name.to_os_string(), created));
+        let config = read_csv_config(&path).ok();
+        let created = match config.as_ref().and_then(|c| c.created_at) {
+            Some(created_at) => created_at.into(),
+            None => metadata.created().unwrap_or(modified), // fallback for Unix consistency
+        };
+        entries.push(PortfolioEntry {
+            name: name.to_string_lossy().to_string(),
+            closed_at: config.as_ref().and_then(|c| c.closed_at),
+            base_currency: read_base_currency(&path).ok().flatten(),
+            path,
+            created,
+            modified,
+        });
     }
 
-    // Sort oldest → newest
-    files.sort_unstable_by_key(|(_, t)| *t);
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
 
-    // pretty table
-    let mut table = Table::new();
-    table.add_row(row!["CSV file name", "Created at"]);
+    Ok(entries)
+}
+
+/// Sort entries in place; ties keep the alphabetical order, so output is stable
+pub fn sort_entries(entries: &mut [PortfolioEntry], sort: ListSort) {
+    match sort {
+        ListSort::Name => entries.sort_by(|a, b| a.name.cmp(&b.name)),
+        ListSort::Created => entries.sort_by_key(|e| e.created),
+        ListSort::Modified => entries.sort_by_key(|e| e.modified),
+        ListSort::Value => {
+            let mut values: HashMap<PathBuf, Decimal> = HashMap::new();
+            for e in entries.iter() {
+                match Portfolio::from_csv(&e.path).and_then(|pf| pf.market_value(&LiveQuotes)) {
+                    Ok(value) => {
+                        values.insert(e.path.clone(), value);
+                    }
+                    Err(err) => {
+                        warnings::warn(format!("can't value portfolio '{}': {}", e.name, err))
+                    }
+                }
+            }
+            // highest value first, portfolios without value last
+            entries.sort_by_key(|e| std::cmp::Reverse(values.get(&e.path).copied()));
+        }
+    }
+}
+
+// +---------------+---------------------+--------+
+// | CSV file name | Created at          | Trades |
+// +---------------+---------------------+--------+
+// | example       | 2025-12-05 20:01:21 | 12     |
+// +---------------+---------------------+--------+
+pub fn list_csv_files(
+    settings: &Settings,
+    filter: &ListFilter,
+    include_ignored: bool,
+    sort: ListSort,
+    format: OutputFormat,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let format = reporter.format(format);
+    let federated = settings.data_dirs().len() > 1;
+    let mut entries: Vec<PortfolioEntry> =
+        skip_ignored(list_all_entries(settings)?, include_ignored, settings)
+            .into_iter()
+            .filter(|e| filter.matches(e))
+            .collect();
+    sort_entries(&mut entries, sort);
+
+    // the index covers the portfolio dir, files of other dirs are read as listed
+    let in_portfolio_dir =
+        |e: &PortfolioEntry| e.path.parent() == Some(settings.portfolio_dir.as_path());
+    let own: Vec<PortfolioEntry> = entries
+        .iter()
+        .filter(|e| in_portfolio_dir(e))
+        .cloned()
+        .collect();
+    let mut index = WorkspaceIndex::load(settings);
+    if index.refresh(&own)? {
+        // the index is only a cache, listing works without it
+        index.save(settings).ok();
+    }
 
-    let format = format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
-    for (name, timestamp) in &files {
-        let created = OffsetDateTime::from(*timestamp);
-        table.add_row(row![name.to_string_lossy(), created.format(format)?]);
+    let mut columns = vec!["CSV file name", "Created at", "Trades"];
+    if federated {
+        columns.push("Directory");
+    }
+    let mut records = Records::new(&columns);
+    for entry in entries.iter() {
+        let indexed = if in_portfolio_dir(entry) {
+            index.entries.get(&entry.name).cloned()
+        } else {
+            index::index_file(&entry.path).ok()
+        };
+        let trades = match indexed {
+            Some(e) if e.is_valid() => e.rows.to_string(),
+            Some(_) => "invalid".to_string(),
+            None => "-".to_string(),
+        };
+        let name = match entry.closed_at {
+            Some(_) if format == OutputFormat::Table => format!("{} [closed]", entry.name),
+            _ => entry.name.clone(),
+        };
+        let mut row = vec![
+            name.into(),
+            OffsetDateTime::from(entry.created).into(),
+            trades.into(),
+        ];
+        if federated {
+            row.push(entry_dir(entry).into());
+        }
+        records.push(row);
     }
 
-    table.printstd();
+    let display = format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+    reporter.records(&records, format, &|ts| {
+        ts.format(display).unwrap_or_default()
+    })?;
+
+    if format == OutputFormat::Table {
+        print_conflict_warnings(&find_conflicts(settings)?, reporter);
+        print_duplicate_warnings(&find_duplicates(settings)?, reporter, settings);
+    }
 
     Ok(())
 }
 
-/// Display trades from the CSV file
-pub fn show_trades(name: &str, settings: &Settings) -> Result<()> {
+// +-----------+----------+----------------+---------------+
+// | Portfolio | Kind     | Value          | PnL           |
+// +-----------+----------+----------------+---------------+
+// | cold      | holdings | 120,000.00 USD | -             |
+// | kraken    | trades   | 45,210.50 USD  | 12,004.10 USD |
+// +-----------+----------+----------------+---------------+
+/// Value every portfolio of the workspace at `prices`, trade-based and holdings-only
+pub fn report_all(
+    prices: &dyn Prices,
+    include_ignored: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let federated = settings.data_dirs().len() > 1;
+    let mut table = Table::new();
+    let mut header = row!["Portfolio", "Kind", "Value", "PnL"];
+    if federated {
+        header.add_cell(Cell::new("Directory"));
+    }
+    table.add_row(header);
+    let mut totals: BTreeMap<String, Decimal> = BTreeMap::new();
+
+    for entry in skip_ignored(list_all_entries(settings)?, include_ignored, settings) {
+        let valued = read_csv_config(&entry.path).and_then(|config| {
+            let pf = position_cache::load(&entry.path, settings)?;
+            Ok((config.kind, pf.valuation(prices)?))
+        });
+        let (kind, valuation) = match valued {
+            Ok(valued) => valued,
+            Err(err) => {
+                warnings::warn(format!("can't value portfolio '{}': {}", entry.name, err));
+                continue;
+            }
+        };
+        let pnl = if valuation.all_uncosted() {
+            "-".to_string()
+        } else {
+            format!(
+                "{} {}",
+                settings
+                    .rounding
+                    .cents(valuation.pnl)
+                    .normalize()
+                    .separate_with_commas(),
+                valuation.base
+            )
+        };
+        let mut row = row![
+            entry.name,
+            kind,
+            r->format!(
+                "{} {}",
+                settings
+                    .rounding
+                    .cents(valuation.value)
+                    .normalize()
+                    .separate_with_commas(),
+                valuation.base
+            ),
+            r->pnl
+        ];
+        if federated {
+            row.add_cell(Cell::new(&entry_dir(&entry)));
+        }
+        table.add_row(row);
+        *totals.entry(valuation.base.to_string()).or_default() += valuation.value;
+    }
+
+    if table.len() == 1 {
+        reporter.print("No portfolios found");
+        return Ok(());
+    }
+    reporter.table(&table)?;
+    for (base, total) in totals {
+        reporter.print(&format!(
+            "Total:\t\t{} {}",
+            settings
+                .rounding
+                .cents(total)
+                .normalize()
+                .separate_with_underscores(),
+            base
+        ));
+    }
+
+    Ok(())
+}
+
+/// Display trades from the CSV file, optionally only rows from `source`
+pub fn show_trades(
+    name: &str,
+    source: Option<&str>,
+    format: OutputFormat,
+    with_journal: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let format = reporter.format(format);
     let path = settings.path_for(name);
+    if read_csv_config(&path)?.kind == PortfolioKind::Holdings {
+        return holdings::print_table(path, format, reporter);
+    }
 
     let (_, trades) = parse_csv_file(path)?;
+    let journal = match with_journal {
+        true => Some(journal::by_tx(journal::load(name, settings)?)),
+        false => None,
+    };
+    print_trades(&trades, source, format, journal.as_ref(), reporter)
+}
 
-    // prettytable
-    let mut table = Table::new();
-    let header_row = Row::new(CSV_HEADER.iter().map(|&c| Cell::new(c)).collect());
-    table.add_row(header_row);
-
-    for t in trades.iter() {
-        let row = t.to_table_row();
-        table.add_row(row);
+/// The rows of `show`, only those from `source` when given, with trade ids
+/// and their entries when `journal` is given
+pub fn print_trades(
+    trades: &[Trade],
+    source: Option<&str>,
+    format: OutputFormat,
+    journal: Option<&BTreeMap<String, Vec<journal::Entry>>>,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let format = reporter.format(format);
+    let with_source = trades.iter().any(|t| t.source.is_some());
+
+    let mut columns = CSV_HEADER.to_vec();
+    if with_source {
+        columns.push(SOURCE_COLUMN);
+    }
+    if journal.is_some() {
+        columns.extend(["id", "journal"]);
+    }
+    let mut records = Records::new(&columns);
+    for t in trades
+        .iter()
+        .filter(|t| source.is_none_or(|s| t.source_matches(s)))
+    {
+        let mut row = t.to_fields();
+        if with_source {
+            row.push(t.source.as_deref().unwrap_or("-").into());
+        }
+        if let Some(journal) = journal {
+            let id = t.id();
+            let notes: Vec<&str> = journal
+                .get(&id)
+                .map(|entries| entries.iter().map(|e| e.text.as_str()).collect())
+                .unwrap_or_default();
+            row.push(id.into());
+            row.push(notes.join("; ").into());
+        }
+        records.push(row);
     }
 
-    if table.len() > 1 {
-        table.printstd();
+    if !records.is_empty() || format != OutputFormat::Table {
+        reporter.records(&records, format, &|ts| {
+            ts.format(&time::format_description::well_known::Rfc2822)
+                .unwrap_or_else(|_| "Invalid date".to_string())
+        })?;
+    } else if let Some(source) = source {
+        reporter.print(&format!("No trades from source '{}'", source));
     } else {
-        println!("No trades found");
+        reporter.print("No trades found");
     }
 
     Ok(())
 }
 
-/// Create a new trades CSV file with headers
-pub fn new(name: &str, settings: &Settings) -> Result<()> {
+/// Create a new trades CSV file with headers, or a holdings-only file
+pub fn new(
+    name: &str,
+    kind: PortfolioKind,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
     let file_path = settings.path_for(name);
 
     if file_path.exists() {
@@ -282,16 +760,33 @@ pub fn new(name: &str, settings: &Settings) -> Result<()> {
     }
 
     let mut file = File::create_new(&file_path)?;
-    writeln!(file, "# base_currency: {}", settings.base_currency)?;
+    let config = CsvConfig {
+        base_currency: settings.base_currency.to_string(),
+        created_at: Some(settings.clock.now().replace_nanosecond(0)?),
+        kind,
+        closed_at: None,
+        other: Vec::new(),
+    };
+    config.write_to(&mut file)?;
 
     // let file = std::fs::OpenOptions::new().append(true).open(&file_path)?;
     let mut wtr = csv::Writer::from_writer(file);
 
     // Explicitly write header
-    wtr.write_record(CSV_HEADER)?;
+    match kind {
+        PortfolioKind::Trades => wtr.write_record(CSV_HEADER)?,
+        PortfolioKind::Holdings => wtr.write_record(HOLDINGS_HEADER)?,
+    }
     wtr.flush()?;
 
-    println!("Created trades file: {}", file_path.display()); // TODO rename trades file to portfolio file
+    index::record_write(name, settings);
+
+    // TODO rename trades file to portfolio file
+    let kind = match kind {
+        PortfolioKind::Trades => "trades",
+        PortfolioKind::Holdings => "holdings",
+    };
+    reporter.status(&format!("Created {} file: {}", kind, file_path.display()));
 
     Ok(())
 }
@@ -313,12 +808,16 @@ mod tests {
     static USD: LazyLock<Currency> =
         LazyLock::new(|| Currency::new("USD").expect("USD should be valid"));
 
+    fn prices() -> HashMap<String, Decimal> {
+        HashMap::from([("BTC".to_string(), dec!(40000))])
+    }
+
     // Test fixtures for common scenarios
     #[fixture]
     fn portfolio_with_1m_usd() -> Portfolio {
         let mut pf = Portfolio::new();
         // Initial deposit: $1M USD
-        pf.deposit(USD.clone(), dec!(1000_000)).unwrap();
+        pf.deposit(USD.clone(), dec!(1000_000), &prices()).unwrap();
         pf
     }
 
@@ -326,7 +825,7 @@ mod tests {
     fn portfolio_with_10_btc() -> Portfolio {
         let mut pf = Portfolio::new();
         // Initial deposit: 10 BTC
-        pf.deposit(BTC.clone(), dec!(10)).unwrap();
+        pf.deposit(BTC.clone(), dec!(10), &prices()).unwrap();
         pf
     }
 
@@ -341,8 +840,7 @@ mod tests {
     #[rstest]
     fn test_deposit_sets_initial_cost_basis(portfolio_with_10_btc: Portfolio) {
         let pos = portfolio_with_10_btc.positions.get(&BTC).unwrap();
-        let btc_val = dec!(10) * quote_in_base(&BTC, "USD").unwrap(); // TODO fix hardcoded USD
-        assert_eq!(pos.cost_base, btc_val);
+        assert_eq!(pos.cost_base, dec!(400_000));
     }
 
     // ========== Buy Tests ==========
@@ -379,7 +877,7 @@ mod tests {
         let mut pf = Portfolio::new();
 
         // Deposit only $1,000
-        pf.deposit(USD.clone(), dec!(1000)).unwrap();
+        pf.deposit(USD.clone(), dec!(1000), &prices()).unwrap();
 
         // Try to buy 10 BTC for $100K (should fail)
         let res = pf.add_tx(Tx::parse("10 btc for 100000 usd").unwrap());
@@ -398,7 +896,7 @@ mod tests {
 
         assert!(res.is_err());
         // Should not create BTC position
-        assert!(pf.positions.get(&BTC).is_none());
+        assert!(!pf.positions.contains_key(&BTC));
     }
 
     // ========== Sell Tests ==========
@@ -570,4 +1068,152 @@ mod tests {
         assert_eq!(pf.positions.get(&USD).unwrap().balance, dec!(0));
         assert_eq!(pf.positions.get(&BTC).unwrap().balance, dec!(10));
     }
+
+    // ========== Valuation Tests ==========
+
+    #[test]
+    fn test_valuation_leaves_holdings_without_cost_out_of_pnl() {
+        let eth = Currency::new("ETH").unwrap();
+        let mut pf = portfolio_with_1m_usd();
+        pf.base = USD.clone();
+        pf.add_tx(Tx::parse("2 btc for 60000 usd").unwrap())
+            .unwrap();
+        pf.deposit_at_cost(eth.clone(), dec!(5), dec!(0));
+        pf.unknown_cost.insert(eth.clone());
+        let mut prices = prices();
+        prices.insert("ETH".to_string(), dec!(2000));
+
+        let valuation = pf.valuation(&prices).unwrap();
+        assert_eq!(valuation.value, dec!(90000));
+        assert_eq!(valuation.cost_base, dec!(60000));
+        assert_eq!(valuation.pnl, dec!(20000));
+        assert_eq!(
+            valuation.pnl_pct().map(|p| p.round_dp(2)),
+            Some(Percent::new(dec!(33.33)))
+        );
+        assert!(!valuation.all_uncosted());
+
+        let btc = valuation.positions.iter().find(|p| p.currency == *BTC);
+        assert_eq!(btc.unwrap().avg_price(), Some(dec!(30000)));
+        let eth = valuation.positions.iter().find(|p| p.currency == eth);
+        assert_eq!(eth.unwrap().cost_base, None);
+        assert_eq!(eth.unwrap().pnl_pct(), None);
+
+        assert_eq!(pf.market_value(&prices).unwrap(), valuation.value);
+        assert_eq!(pf.unrealized_pnl(&prices).unwrap(), valuation.pnl);
+    }
+
+    // ========== List Filter ==========
+
+    fn entry(name: &str, base: Option<&str>) -> PortfolioEntry {
+        PortfolioEntry {
+            name: name.to_string(),
+            path: PathBuf::from(format!("{name}.csv")),
+            created: SystemTime::UNIX_EPOCH,
+            modified: SystemTime::UNIX_EPOCH,
+            base_currency: base.map(str::to_string),
+            closed_at: None,
+        }
+    }
+
+    #[rstest]
+    #[case(Some("kraken*"), None, "kraken_eur", Some("EUR"), true)]
+    #[case(Some("kraken*"), None, "binance", Some("EUR"), false)]
+    #[case(None, Some("eur"), "kraken_eur", Some("EUR"), true)]
+    #[case(None, Some("eur"), "kraken_usd", Some("USD"), false)]
+    #[case(None, Some("eur"), "notes", None, false)]
+    #[case(Some("*_usd"), Some("usd"), "kraken_usd", Some("USD"), true)]
+    #[case(None, None, "anything", None, true)]
+    fn test_list_filter_matches(
+        #[case] pattern: Option<&str>,
+        #[case] base: Option<&str>,
+        #[case] name: &str,
+        #[case] entry_base: Option<&str>,
+        #[case] expected: bool,
+    ) {
+        let filter = ListFilter::new(pattern, base).unwrap();
+        assert_eq!(filter.matches(&entry(name, entry_base)), expected);
+    }
+
+    #[test]
+    fn test_list_filter_rejects_invalid_input() {
+        assert!(ListFilter::new(Some("[abc"), None).is_err());
+        assert!(ListFilter::new(None, Some("NOTACOIN")).is_err());
+    }
+
+    #[test]
+    fn test_sort_entries_by_name_created_and_modified() {
+        let at = |secs: u64| SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        let mut entries = vec![
+            PortfolioEntry {
+                created: at(2),
+                modified: at(1),
+                ..entry("beta", None)
+            },
+            PortfolioEntry {
+                created: at(3),
+                modified: at(3),
+                ..entry("alpha", None)
+            },
+            PortfolioEntry {
+                created: at(1),
+                modified: at(2),
+                ..entry("gamma", None)
+            },
+        ];
+        let names = |e: &[PortfolioEntry]| e.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+
+        sort_entries(&mut entries, ListSort::Name);
+        assert_eq!(names(&entries), ["alpha", "beta", "gamma"]);
+        sort_entries(&mut entries, ListSort::Created);
+        assert_eq!(names(&entries), ["gamma", "beta", "alpha"]);
+        sort_entries(&mut entries, ListSort::Modified);
+        assert_eq!(names(&entries), ["beta", "gamma", "alpha"]);
+    }
+
+    #[test]
+    fn test_list_entries_reads_created_at_from_metadata() {
+        let dir = tempfile::TempDir::new().unwrap();
+        crate::test_utils::helpers::create_test_csv(
+            &dir,
+            "pf",
+            "# base_currency: EUR\n# created_at: 2024-01-10T10:40:00Z\ncreated_at,pair,side,amount,price,fee\n",
+        );
+        let settings = crate::test_utils::helpers::create_test_settings(dir.path().to_path_buf());
+
+        let entries = list_entries(&settings).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            OffsetDateTime::from(entries[0].created),
+            time::macros::datetime!(2024-01-10 10:40 UTC)
+        );
+        assert_eq!(entries[0].base_currency.as_deref(), Some("EUR"));
+    }
+
+    #[test]
+    fn test_list_entries_skips_other_files_and_unknown_bases() {
+        let dir = tempfile::TempDir::new().unwrap();
+        crate::test_utils::helpers::create_test_csv(
+            &dir,
+            "usd",
+            "# base_currency: USD\ncreated_at,pair,side,amount,price,fee\n",
+        );
+        crate::test_utils::helpers::create_test_csv(
+            &dir,
+            "old",
+            "created_at,pair,side,amount,price,fee\n",
+        );
+        std::fs::write(dir.path().join("notes.txt"), "buy the dip\n").unwrap();
+        std::fs::write(dir.path().join("usd.csv~"), "# base_currency: USD\n").unwrap();
+        let settings = crate::test_utils::helpers::create_test_settings(dir.path().to_path_buf());
+
+        let entries = list_entries(&settings).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "old");
+        assert_eq!(entries[0].base_currency, None);
+        let usd = ListFilter::new(None, Some("usd")).unwrap();
+        let listed: Vec<&PortfolioEntry> = entries.iter().filter(|e| usd.matches(e)).collect();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "usd");
+    }
 }
@@ -0,0 +1,306 @@
+use crate::chart;
+use crate::currency::Currency;
+use crate::format;
+use crate::journal;
+use crate::math;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{Instrument, Side, Trade, TradingPair, parse_csv_file, sort_chronologically};
+use anyhow::{Result, bail};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use thousands::Separable;
+use time::OffsetDateTime;
+use time::macros::format_description;
+
+/// State of a position after one of its trades
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvgPricePoint {
+    pub at: OffsetDateTime,
+    pub side: Side,
+    pub amount: Decimal,
+    pub price: Decimal,
+    /// Quantity held after the trade
+    pub quantity: Decimal,
+    /// Average entry price after the trade, fees included
+    pub avg_price: Decimal,
+}
+
+/// Average entry price of `pair` after each of its trades, oldest first
+///
+/// Buys move the average (fees count as cost), sells reduce the quantity at
+/// the current average. Only trades, no market prices are used.
+pub fn avg_price_history(trades: &[Trade], pair: &TradingPair) -> Vec<AvgPricePoint> {
+    let mut quantity = dec!(0);
+    let mut cost = dec!(0);
+    let mut points = Vec::new();
+
+    let mut trades: Vec<&Trade> = trades
+        .iter()
+        .filter(|t| matches!(&t.pair, Instrument::Pair(p) if p == pair))
+        .collect();
+    sort_chronologically(&mut trades);
+
+    for t in trades {
+        match t.side {
+            Side::Buy => {
+                quantity += t.amount;
+                cost += t.amount * t.price + t.fee;
+            }
+            Side::Sell => {
+                let sold = t.amount.min(quantity);
+                if quantity > dec!(0) {
+                    cost -= cost * sold / quantity;
+                }
+                quantity -= sold;
+            }
+            Side::Deposit | Side::Withdraw | Side::Fee | Side::Income => continue,
+        }
+        let avg_price = if quantity > dec!(0) {
+            math::ratio(cost, quantity).unwrap_or_default()
+        } else {
+            dec!(0)
+        };
+        points.push(AvgPricePoint {
+            at: t.created_at,
+            side: t.side,
+            amount: t.amount,
+            price: t.price,
+            quantity,
+            avg_price,
+        });
+    }
+
+    points
+}
+
+/// Buys of a position in one calendar quarter
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuarterEntries {
+    /// `2024-Q1`
+    pub quarter: String,
+    pub buys: usize,
+    pub quantity: Decimal,
+    /// Paid for the buys, fees included
+    pub cost: Decimal,
+}
+
+impl QuarterEntries {
+    pub fn avg_price(&self) -> Decimal {
+        math::ratio(self.cost, self.quantity).unwrap_or_default()
+    }
+}
+
+/// Buys of `pair` bucketed by calendar quarter (UTC), oldest first
+pub fn entries_by_quarter(trades: &[Trade], pair: &TradingPair) -> Vec<QuarterEntries> {
+    let mut buys: Vec<&Trade> = trades
+        .iter()
+        .filter(|t| t.side == Side::Buy && matches!(&t.pair, Instrument::Pair(p) if p == pair))
+        .collect();
+    sort_chronologically(&mut buys);
+
+    let mut buckets: Vec<QuarterEntries> = Vec::new();
+    for t in buys {
+        let date = t.created_at.date();
+        let quarter = format!("{}-Q{}", date.year(), (date.month() as u8 - 1) / 3 + 1);
+        if buckets.last().is_none_or(|b| b.quarter != quarter) {
+            buckets.push(QuarterEntries {
+                quarter,
+                buys: 0,
+                quantity: dec!(0),
+                cost: dec!(0),
+            });
+        }
+        let bucket = buckets.last_mut().unwrap();
+        bucket.buys += 1;
+        bucket.quantity += t.amount;
+        bucket.cost += t.amount * t.price + t.fee;
+    }
+    buckets
+}
+
+/// `BTC/EUR` or a bare ticker, which is paired with the portfolio base currency
+fn parse_pair(s: &str, base: &str) -> Result<TradingPair> {
+    if s.contains('/') {
+        s.parse()
+    } else {
+        TradingPair::new(Currency::new(s)?, Currency::new(base)?)
+    }
+}
+
+/// Summary of one position, with `history` the average price after every
+/// trade and `entries` its buys per quarter
+pub fn run(
+    name: &str,
+    pair: &str,
+    history: bool,
+    entries: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let (config, trades) = parse_csv_file(settings.path_for(name))?;
+    let pair = parse_pair(pair, &config.base_currency)?;
+    let points = avg_price_history(&trades, &pair);
+
+    let Some(last) = points.last() else {
+        bail!("no {} trades in portfolio '{}'", pair, name);
+    };
+
+    let buys = points.iter().filter(|p| p.side == Side::Buy).count();
+    reporter.print(&format!("Position:\t{} {}", last.quantity, pair.base));
+    reporter.print(&format!(
+        "Avg price:\t{} {}",
+        format::price(last.avg_price, settings),
+        pair.quote
+    ));
+    reporter.print(&format!(
+        "Trades:\t\t{} buys, {} sells",
+        buys,
+        points.len() - buys
+    ));
+
+    let journal = journal::by_tx(journal::load(name, settings)?);
+    let format = format_description!("[year]-[month]-[day]");
+    for t in trades
+        .iter()
+        .filter(|t| matches!(&t.pair, Instrument::Pair(p) if p == &pair))
+    {
+        for entry in journal.get(&t.id()).into_iter().flatten() {
+            reporter.print(&format!(
+                "📝 {} {} {} @ {}: {}",
+                t.created_at.format(format)?,
+                t.side,
+                t.amount,
+                format::price(t.price, settings),
+                entry.text
+            ));
+        }
+    }
+
+    if history {
+        let mut table = Table::new();
+        table.add_row(row![
+            "Date",
+            "Side",
+            "Amount",
+            "Price",
+            "Position",
+            "Avg price"
+        ]);
+        for p in &points {
+            table.add_row(row![
+                p.at.format(format)?,
+                p.side,
+                p.amount,
+                format::price(p.price, settings),
+                p.quantity,
+                format::price(p.avg_price, settings)
+            ]);
+        }
+        reporter.table(&table)?;
+
+        let avg: Vec<Decimal> = points
+            .iter()
+            .filter(|p| p.quantity > dec!(0))
+            .map(|p| p.avg_price)
+            .collect();
+        reporter.print(chart::line(&avg, 8).trim_end());
+    }
+
+    if entries {
+        let quarters = entries_by_quarter(&trades, &pair);
+        let total: Decimal = quarters.iter().map(|q| q.cost).sum();
+        let mut table = Table::new();
+        table.add_row(row![
+            "Quarter", "Buys", "Quantity", "Cost", "Avg cost", "Share"
+        ]);
+        for q in &quarters {
+            let share = math::percent_of(q.cost, total).unwrap_or_default();
+            table.add_row(row![
+                q.quarter,
+                r->q.buys,
+                r->q.quantity.normalize(),
+                r->q.cost.round_dp(2).normalize().separate_with_commas(),
+                r->format::price(q.avg_price(), settings),
+                r->format!("{:.1}", share.round_dp(1))
+            ]);
+        }
+        // the rows of the result are those of the history when both are asked
+        if history {
+            reporter.print(table.to_string().trim_end());
+        } else {
+            reporter.table(&table)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::transactions_from;
+
+    #[test]
+    fn test_avg_price_history_follows_buys_and_sells() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,0
+1704969600,ETH/USD,BUY,1,2000,0
+1705056000,BTC/USD,BUY,1,20000,10
+1705142400,BTC/USD,SELL,1.5,50000,5
+1705228800,BTC/USD,BUY,0.5,60000,0
+",
+        );
+        let pair: TradingPair = "BTC/USD".parse().unwrap();
+        let avg: Vec<(Decimal, Decimal)> = avg_price_history(&trades, &pair)
+            .iter()
+            .map(|p| (p.quantity, p.avg_price))
+            .collect();
+
+        assert_eq!(
+            avg,
+            vec![
+                (dec!(1), dec!(40000)),
+                (dec!(2), dec!(30005)),
+                // selling doesn't change the average
+                (dec!(0.5), dec!(30005)),
+                (dec!(1.0), dec!(45002.5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_entries_by_quarter_buckets_buys() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,0
+1711843200,BTC/USD,BUY,1,60000,20
+1711929600,BTC/USD,SELL,1,70000,0
+1712016000,ETH/USD,BUY,1,3000,0
+1719792000,BTC/USD,BUY,0.5,60000,0
+",
+        );
+        let pair: TradingPair = "BTC/USD".parse().unwrap();
+        let quarters = entries_by_quarter(&trades, &pair);
+        let rows: Vec<(&str, usize, Decimal, Decimal)> = quarters
+            .iter()
+            .map(|q| (q.quarter.as_str(), q.buys, q.quantity, q.avg_price()))
+            .collect();
+
+        assert_eq!(
+            rows,
+            vec![
+                ("2024-Q1", 2, dec!(2), dec!(50010)),
+                ("2024-Q3", 1, dec!(0.5), dec!(60000)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_pair_defaults_quote_to_base() {
+        assert_eq!(parse_pair("btc", "EUR").unwrap().to_string(), "BTC/EUR");
+        assert_eq!(parse_pair("ETH/USD", "EUR").unwrap().to_string(), "ETH/USD");
+    }
+}
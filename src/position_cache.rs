@@ -0,0 +1,110 @@
+use crate::portfolio::Portfolio;
+use crate::settings::{CostBasisMethod, Settings};
+use crate::workspace::sha256_hex;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever `Portfolio` or the way positions are computed changes
+const CACHE_VERSION: u32 = 1;
+
+/// Positions computed from one portfolio file, valid while the file content
+/// (and the cost basis method) stays the same
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedPortfolio {
+    version: u32,
+    checksum: String,
+    cost_basis: CostBasisMethod,
+    portfolio: Portfolio,
+}
+
+fn cache_path(path: &Path, settings: &Settings) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    settings
+        .state_dir()
+        .join("positions")
+        .join(format!("{}.json", stem))
+}
+
+/// Portfolio of the file at `path`, computed only when the file changed
+/// since the last call
+///
+/// Keyed by the SHA-256 of the file content, so repeated `report` and
+/// `watch` runs only refetch quotes. The cache is best effort: unreadable
+/// entries are recomputed and failing writes are ignored.
+pub fn load<P: AsRef<Path>>(path: P, settings: &Settings) -> Result<Portfolio> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let checksum = sha256_hex(&data);
+    let cache = cache_path(path, settings);
+
+    let cached = std::fs::read(&cache)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<CachedPortfolio>(&data).ok())
+        .filter(|c| {
+            c.version == CACHE_VERSION
+                && c.checksum == checksum
+                && c.cost_basis == settings.cost_basis
+        });
+    if let Some(cached) = cached {
+        return Ok(cached.portfolio);
+    }
+
+    let portfolio = Portfolio::from_csv(path)?;
+    let entry = CachedPortfolio {
+        version: CACHE_VERSION,
+        checksum,
+        cost_basis: settings.cost_basis,
+        portfolio,
+    };
+    if let Ok(json) = serde_json::to_vec(&entry) {
+        let _ = std::fs::create_dir_all(cache.parent().unwrap())
+            .and_then(|_| std::fs::write(&cache, json));
+    }
+    Ok(entry.portfolio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::Currency;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use rust_decimal::dec;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_reuses_positions_until_file_changes() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        let path = create_test_csv(
+            &dir,
+            "pf",
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,10
+",
+        );
+        let btc = Currency::new("BTC").unwrap();
+
+        let first = load(&path, &settings).unwrap();
+        assert_eq!(first.balance(&btc), dec!(1));
+        assert!(dir.path().join(".csvpt/positions/pf.json").is_file());
+
+        // a tampered cache entry with the right checksum is served as is
+        let cache = cache_path(&path, &settings);
+        let mut entry: CachedPortfolio =
+            serde_json::from_slice(&std::fs::read(&cache).unwrap()).unwrap();
+        entry.portfolio.positions.get_mut(&btc).unwrap().balance = dec!(5);
+        std::fs::write(&cache, serde_json::to_vec(&entry).unwrap()).unwrap();
+        assert_eq!(load(&path, &settings).unwrap().balance(&btc), dec!(5));
+
+        // any change to the file invalidates it
+        std::fs::write(
+            &path,
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,2,40000,10
+",
+        )
+        .unwrap();
+        assert_eq!(load(&path, &settings).unwrap().balance(&btc), dec!(2));
+    }
+}
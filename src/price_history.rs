@@ -0,0 +1,135 @@
+use crate::currency::Currency;
+use crate::http;
+use crate::quote;
+use crate::settings::Settings;
+use anyhow::{Context, Result, anyhow};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use time::Date;
+use time::macros::format_description;
+
+/// Daily closing prices of one ticker in one quote currency, by `YYYY-MM-DD`
+///
+/// Cached in `<state dir>/prices/<TICKER>-<QUOTE>.json`. Past prices don't
+/// change, so cached days are never refetched.
+pub type DailyPrices = BTreeMap<String, Decimal>;
+
+fn cache_path(ticker: &str, quote: &str, settings: &Settings) -> PathBuf {
+    settings
+        .state_dir()
+        .join("prices")
+        .join(format!("{}-{}.json", ticker, quote))
+}
+
+pub fn load_cached(ticker: &str, quote: &str, settings: &Settings) -> Result<DailyPrices> {
+    let path = cache_path(ticker, quote, settings);
+    match std::fs::read(&path) {
+        Ok(data) => {
+            serde_json::from_slice(&data).with_context(|| format!("parsing {}", path.display()))
+        }
+        Err(_) => Ok(DailyPrices::new()),
+    }
+}
+
+pub(crate) fn save_cached(
+    ticker: &str,
+    quote: &str,
+    prices: &DailyPrices,
+    settings: &Settings,
+) -> Result<()> {
+    let path = cache_path(ticker, quote, settings);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(&path, serde_json::to_vec_pretty(prices)?)?;
+    Ok(())
+}
+
+/// Market data of the CoinGecko `coins/{id}/history` response
+#[derive(Debug, Deserialize)]
+struct CoinHistory {
+    market_data: Option<HistoryMarketData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryMarketData {
+    current_price: HashMap<String, f64>,
+}
+
+fn fetch(ticker: &str, quote: &str, date: Date) -> Result<Decimal> {
+    let id = quote::to_ids(&[ticker.to_string()])?.remove(0);
+    let day = date.format(format_description!("[day]-[month]-[year]"))?;
+    let url = format!(
+        "https://api.coingecko.com/api/v3/coins/{}/history?date={}&localization=false",
+        id, day
+    );
+    let history: CoinHistory = http::get_json(&url)?;
+    let price = history
+        .market_data
+        .and_then(|m| m.current_price.get(&quote.to_ascii_lowercase()).copied())
+        .ok_or(anyhow!("no {}/{} price for {}", ticker, quote, date))?;
+    Decimal::from_f64_retain(price).ok_or(anyhow!("can't decimal from f64"))
+}
+
+/// Price of `currency` in `quote` on `date`, from the cache or CoinGecko, or
+/// only from the file of `--fixture-prices`
+pub fn price_on(
+    currency: &Currency,
+    quote: &str,
+    date: Date,
+    settings: &Settings,
+) -> Result<Decimal> {
+    let ticker = currency.ticker();
+    if let Some(path) = &settings.fixture_prices {
+        return quote::file_price_on(path, ticker, date);
+    }
+    let mut prices = load_cached(ticker, quote, settings)?;
+    if let Some(price) = prices.get(&date.to_string()) {
+        return Ok(*price);
+    }
+    let price = fetch(ticker, quote, date)
+        .with_context(|| format!("fetching {} price of {}", ticker, date))?;
+    prices.insert(date.to_string(), price);
+    save_cached(ticker, quote, &prices, settings)?;
+    Ok(price)
+}
+
+/// Price of `currency` in `quote` on `date` if known without fetching:
+/// from the cache, or the file of `--fixture-prices`
+pub fn known_price_on(
+    currency: &Currency,
+    quote: &str,
+    date: Date,
+    settings: &Settings,
+) -> Option<Decimal> {
+    let ticker = currency.ticker();
+    if let Some(path) = &settings.fixture_prices {
+        return quote::file_price_on(path, ticker, date).ok();
+    }
+    load_cached(ticker, quote, settings)
+        .ok()?
+        .get(&date.to_string())
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::create_test_settings;
+    use rust_decimal::dec;
+    use tempfile::TempDir;
+    use time::macros::date;
+
+    #[test]
+    fn test_price_on_reads_cached_day() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        let prices = DailyPrices::from([("2024-01-10".to_string(), dec!(46000))]);
+        save_cached("BTC", "USD", &prices, &settings).unwrap();
+
+        let btc = Currency::new("BTC").unwrap();
+        let price = price_on(&btc, "USD", date!(2024 - 01 - 10), &settings).unwrap();
+        assert_eq!(price, dec!(46000));
+        assert!(dir.path().join(".csvpt/prices/BTC-USD.json").is_file());
+    }
+}
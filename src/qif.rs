@@ -0,0 +1,336 @@
+//! QIF investment accounts, for Quicken and GnuCash
+//!
+//! Rows are mapped onto the actions of an investment account with one cash
+//! balance in the base currency: every other asset, stable coins included,
+//! is a security. Trades without the base on either side can't be expressed
+//! as one action, they sell what was given and buy what was received at the
+//! cost carried over by the lot engine.
+
+use crate::currency::Currency;
+use crate::lots::Lots;
+use crate::trade::{Side, Trade, sort_chronologically};
+use anyhow::Result;
+use rust_decimal::Decimal;
+use std::fmt::Write;
+use time::OffsetDateTime;
+use time::macros::format_description;
+
+/// Cash moves of an investment account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CashKind {
+    Deposit,
+    Withdraw,
+    Fee,
+    Interest,
+}
+
+/// What one row did to an investment account, amounts in the base currency
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// `total` paid including the commission
+    Buy {
+        security: String,
+        units: Decimal,
+        price: Decimal,
+        commission: Decimal,
+        total: Decimal,
+    },
+    /// `total` received after the commission
+    Sell {
+        security: String,
+        units: Decimal,
+        price: Decimal,
+        commission: Decimal,
+        total: Decimal,
+    },
+    SharesIn {
+        security: String,
+        units: Decimal,
+        price: Decimal,
+    },
+    SharesOut {
+        security: String,
+        units: Decimal,
+        price: Decimal,
+    },
+    /// Income received as units of the security
+    Reinvest {
+        security: String,
+        units: Decimal,
+        price: Decimal,
+    },
+    Cash {
+        kind: CashKind,
+        amount: Decimal,
+    },
+}
+
+/// One action with the row it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub at: OffsetDateTime,
+    /// Row id, suffixed when a row is split into several actions
+    pub id: String,
+    /// `Buy BTC/USD`
+    pub memo: String,
+    pub action: Action,
+}
+
+/// Actions of the rows of a portfolio with base currency `base`
+pub fn entries(trades: &[Trade], base: &Currency) -> Result<Vec<Entry>> {
+    let mut trades: Vec<&Trade> = trades.iter().collect();
+    sort_chronologically(&mut trades);
+    let mut lots = Lots::new(base.clone());
+    let mut entries = Vec::new();
+    for t in trades {
+        let booking = lots.apply(t)?;
+        let mut actions = Vec::new();
+        if t.side.is_cash() {
+            let asset = t.pair.asset(t.side)?;
+            let security = asset.ticker().to_string();
+            let (units, price) = (t.amount, t.price);
+            let value = units * price;
+            actions.push(match (t.side, asset == base) {
+                (Side::Deposit, true) => Action::Cash {
+                    kind: CashKind::Deposit,
+                    amount: value,
+                },
+                (Side::Withdraw, true) => Action::Cash {
+                    kind: CashKind::Withdraw,
+                    amount: value,
+                },
+                (Side::Fee, true) => Action::Cash {
+                    kind: CashKind::Fee,
+                    amount: value,
+                },
+                (Side::Income, true) => Action::Cash {
+                    kind: CashKind::Interest,
+                    amount: value,
+                },
+                (Side::Deposit, false) => Action::SharesIn {
+                    security,
+                    units,
+                    price,
+                },
+                (Side::Income, false) => Action::Reinvest {
+                    security,
+                    units,
+                    price,
+                },
+                _ => Action::SharesOut {
+                    security,
+                    units,
+                    price,
+                },
+            });
+        } else {
+            let pair = t.pair.trading_pair(t.side)?;
+            let tx = t.to_tx()?;
+            let security = pair.base.ticker().to_string();
+            if pair.quote == *base {
+                let (units, price, commission) = (t.amount, t.price, t.fee);
+                actions.push(if t.side == Side::Buy {
+                    Action::Buy {
+                        security,
+                        units,
+                        price,
+                        commission,
+                        total: tx.sell_size,
+                    }
+                } else {
+                    Action::Sell {
+                        security,
+                        units,
+                        price,
+                        commission,
+                        total: tx.buy_size,
+                    }
+                });
+            } else {
+                let (given, received) = if t.side == Side::Buy {
+                    (&pair.quote, &pair.base)
+                } else {
+                    (&pair.base, &pair.quote)
+                };
+                // a swap is cash neutral at the cost carried over, unless one side is the base
+                let proceeds = if received == base {
+                    tx.buy_size
+                } else {
+                    booking.cost
+                };
+                if given != base {
+                    actions.push(Action::Sell {
+                        security: given.ticker().to_string(),
+                        units: tx.sell_size,
+                        price: proceeds / tx.sell_size,
+                        commission: Decimal::ZERO,
+                        total: proceeds,
+                    });
+                }
+                if received != base {
+                    actions.push(Action::Buy {
+                        security: received.ticker().to_string(),
+                        units: tx.buy_size,
+                        price: booking.cost / tx.buy_size,
+                        commission: Decimal::ZERO,
+                        total: booking.cost,
+                    });
+                }
+            }
+        }
+        let split = actions.len() > 1;
+        for (i, action) in actions.into_iter().enumerate() {
+            let id = if split {
+                format!("{}-{}", t.id(), i + 1)
+            } else {
+                t.id()
+            };
+            entries.push(Entry {
+                at: t.created_at,
+                id,
+                memo: format!("{} {}", t.side, t.pair),
+                action,
+            });
+        }
+    }
+    Ok(entries)
+}
+
+fn num(d: Decimal) -> String {
+    d.round_dp(8).normalize().to_string()
+}
+
+/// QIF records of one investment account named `name`
+pub fn account(name: &str, entries: &[Entry]) -> Result<String> {
+    let mut out = String::new();
+    writeln!(out, "!Account\nN{}\nTInvst\n^\n!Type:Invst", name)?;
+    for e in entries {
+        let date = e.at.format(format_description!("[month]/[day]/[year]"))?;
+        writeln!(out, "D{}", date)?;
+        match &e.action {
+            Action::Buy {
+                security,
+                units,
+                price,
+                commission,
+                total,
+            }
+            | Action::Sell {
+                security,
+                units,
+                price,
+                commission,
+                total,
+            } => {
+                let action = if matches!(e.action, Action::Buy { .. }) {
+                    "Buy"
+                } else {
+                    "Sell"
+                };
+                writeln!(
+                    out,
+                    "N{}\nY{}\nI{}\nQ{}",
+                    action,
+                    security,
+                    num(*price),
+                    num(*units)
+                )?;
+                if !commission.is_zero() {
+                    writeln!(out, "O{}", num(*commission))?;
+                }
+                writeln!(out, "T{}", num(*total))?;
+            }
+            Action::SharesIn {
+                security,
+                units,
+                price,
+            }
+            | Action::SharesOut {
+                security,
+                units,
+                price,
+            }
+            | Action::Reinvest {
+                security,
+                units,
+                price,
+            } => {
+                let action = match e.action {
+                    Action::SharesIn { .. } => "ShrsIn",
+                    Action::SharesOut { .. } => "ShrsOut",
+                    _ => "ReinvInt",
+                };
+                writeln!(
+                    out,
+                    "N{}\nY{}\nI{}\nQ{}",
+                    action,
+                    security,
+                    num(*price),
+                    num(*units)
+                )?;
+                writeln!(out, "T{}", num(*units * *price))?;
+            }
+            Action::Cash { kind, amount } => {
+                let action = match kind {
+                    CashKind::Deposit => "XIn",
+                    CashKind::Withdraw => "XOut",
+                    CashKind::Fee => "MiscExp",
+                    CashKind::Interest => "IntInc",
+                };
+                writeln!(out, "N{}\nT{}", action, num(*amount))?;
+            }
+        }
+        writeln!(out, "M{}, id {}\n^", e.memo, e.id)?;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::transactions_from;
+
+    #[test]
+    fn test_swaps_split_into_a_sale_and_a_buy_at_cost() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704067200,USD,DEPOSIT,30000,1,0
+1704883200,BTC/USD,BUY,0.5,40000,20
+1710460800,ETH/BTC,BUY,2,0.05,0
+1711929600,ETH,INCOME,0.01,3000,0
+",
+        );
+        let usd = Currency::new("USD").unwrap();
+        let entries = entries(&trades, &usd).unwrap();
+        let actions: Vec<&Action> = entries.iter().map(|e| &e.action).collect();
+        assert_eq!(
+            actions[2..4],
+            [
+                &Action::Sell {
+                    security: "BTC".to_string(),
+                    units: Decimal::new(1, 1),
+                    price: Decimal::new(40040, 0),
+                    commission: Decimal::ZERO,
+                    total: Decimal::new(4004, 0),
+                },
+                &Action::Buy {
+                    security: "ETH".to_string(),
+                    units: Decimal::new(2, 0),
+                    price: Decimal::new(2002, 0),
+                    commission: Decimal::ZERO,
+                    total: Decimal::new(4004, 0),
+                },
+            ]
+        );
+        assert!(entries[2].id.ends_with("-1") && entries[3].id.ends_with("-2"));
+
+        let qif = account("main", &entries).unwrap();
+        assert!(
+            qif.starts_with("!Account\nNmain\nTInvst\n^\n!Type:Invst\nD01/01/2024\nNXIn\nT30000\n")
+        );
+        assert!(
+            qif.contains("D01/10/2024\nNBuy\nYBTC\nI40000\nQ0.5\nO20\nT20020\nMBuy BTC/USD, id ")
+        );
+        assert!(qif.contains("NReinvInt\nYETH\nI3000\nQ0.01\nT30\n"));
+    }
+}
@@ -1,60 +1,528 @@
 use crate::currency::{CRYPTO, Currency};
-use anyhow::{Context, Ok, Result, anyhow};
+use crate::http;
+use crate::manual_price::{ManualPrice, ManualPrices};
+use crate::warnings;
+use anyhow::{Context, Ok, Result, anyhow, bail};
 use rust_decimal::Decimal;
-use serde::Deserialize;
-use std::collections::HashMap;
-use std::sync::{LazyLock, Mutex};
+use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
 use std::time::{Duration, Instant};
+use time::Date;
 
-static QUOTE_CACHE: LazyLock<Mutex<Option<QuoteCache>>> = LazyLock::new(|| Mutex::new(None));
+static QUOTE_CACHE: LazyLock<Mutex<QuoteCache>> =
+    LazyLock::new(|| Mutex::new(QuoteCache::default()));
 const CACHE_DURATION: Duration = Duration::from_secs(60);
-const GECKO_TICKER_IDS: &str = "data/coingecko.csv";
+pub const GECKO_TICKER_IDS: &str = "data/coingecko.csv";
 
+/// Providers used by `quote_in_base`, set once from the settings at startup
+static PROVIDERS: LazyLock<Mutex<Providers>> = LazyLock::new(|| {
+    Mutex::new(Providers {
+        list: vec![Box::new(CoinGecko)],
+        discrepancy_pct: 2.0,
+    })
+});
+
+struct Providers {
+    list: Vec<Box<dyn QuoteProvider>>,
+    discrepancy_pct: f64,
+}
+
+/// Assets valued at their final price instead of a quote, set once from
+/// the settings at startup
+static DELISTED: LazyLock<Mutex<BTreeMap<String, Delisted>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// Final price of an asset the providers no longer quote, e.g. after it was
+/// delisted or renamed; the `[delisted.<TICKER>]` tables of the config
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Delisted {
+    pub price: Decimal,
+    /// Currency of the price, the asset is valued in other bases as this
+    /// many units of it
+    #[serde(default = "default_delisted_currency")]
+    pub currency: String,
+}
+
+fn default_delisted_currency() -> String {
+    "USD".to_string()
+}
+
+/// Value these assets at their final price from now on, by ticker
+pub fn configure_delisted(delisted: BTreeMap<String, Delisted>) {
+    *DELISTED.lock().unwrap() = delisted;
+}
+
+/// Prices of `price set`, for assets the providers don't quote; set once
+/// at startup, see `manual_price`
+static MANUAL: LazyLock<Mutex<Manual>> = LazyLock::new(|| {
+    Mutex::new(Manual {
+        prices: ManualPrices::new(),
+        today: Date::MIN,
+        unquoted: HashSet::new(),
+    })
+});
+
+struct Manual {
+    prices: ManualPrices,
+    /// Day expiry is checked against
+    today: Date,
+    /// Tickers the providers didn't quote in this run, valued at their
+    /// manual price without asking the providers again
+    unquoted: HashSet<String>,
+}
+
+/// Fall back to these manual prices from now on, as of `today`
+pub fn configure_manual(prices: ManualPrices, today: Date) {
+    *MANUAL.lock().unwrap() = Manual {
+        prices,
+        today,
+        unquoted: HashSet::new(),
+    };
+}
+
+/// Manual price of `ticker`, which the providers didn't quote with
+/// `quote_err`; warns once when it expires soon
+fn unquoted_price(ticker: &str, quote_err: anyhow::Error) -> Result<ManualPrice> {
+    let mut manual = MANUAL.lock().unwrap();
+    let Some(price) = manual.prices.get(ticker).cloned() else {
+        return Err(quote_err);
+    };
+    if price.expired(manual.today) {
+        bail!(
+            "{}, its manual price expired on {}, renew it with `price set`",
+            quote_err,
+            price.until.map(|d| d.to_string()).unwrap_or_default()
+        );
+    }
+    if manual.unquoted.insert(ticker.to_string())
+        && let Some(until) = price.expires_soon(manual.today)
+    {
+        warnings::warn(format!(
+            "manual price of {} expires on {}, renew it with `price set`",
+            ticker, until
+        ));
+    }
+    Ok(price)
+}
+
+/// Source of current prices
+pub trait QuoteProvider: Send {
+    fn name(&self) -> String;
+
+    /// Price of each ticker in `base`, tickers the provider doesn't know are left out
+    fn quotes(&self, tickers: &[String], base: &str) -> Result<HashMap<String, f64>>;
+}
+
+/// CoinGecko simple price API
+pub struct CoinGecko;
+
+impl QuoteProvider for CoinGecko {
+    fn name(&self) -> String {
+        "coingecko".to_string()
+    }
+
+    fn quotes(&self, tickers: &[String], base: &str) -> Result<HashMap<String, f64>> {
+        get_quotes(tickers, base)
+    }
+}
+
+/// Binance spot ticker prices, USD is quoted as USDT
+pub struct Binance;
+
+#[derive(Debug, Deserialize)]
+struct BinancePrice {
+    symbol: String,
+    price: String,
+}
+
+impl QuoteProvider for Binance {
+    fn name(&self) -> String {
+        "binance".to_string()
+    }
+
+    fn quotes(&self, tickers: &[String], base: &str) -> Result<HashMap<String, f64>> {
+        let quote = match base.to_ascii_uppercase().as_str() {
+            "USD" => "USDT".to_string(),
+            other => other.to_string(),
+        };
+        // the symbols filter fails the whole request on unknown symbols, so fetch all
+        let prices: Vec<BinancePrice> =
+            http::get_json("https://api.binance.com/api/v3/ticker/price")?;
+        let by_symbol: HashMap<String, String> =
+            prices.into_iter().map(|p| (p.symbol, p.price)).collect();
+
+        Ok(tickers
+            .iter()
+            .filter_map(|t| {
+                let price = by_symbol.get(&format!("{}{}", t.to_ascii_uppercase(), quote))?;
+                Some((t.to_string(), price.parse().ok()?))
+            })
+            .collect())
+    }
+}
+
+/// Fixed prices from a local CSV file with `ticker,price` columns, in any base
+///
+/// Rows with a date in an optional `date` column are daily prices instead,
+/// see `file_price_on`.
+pub struct FileQuotes {
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct FilePrice {
+    ticker: String,
+    price: f64,
+    #[serde(default)]
+    date: Option<String>,
+}
+
+fn read_file_prices(path: &Path) -> Result<Vec<FilePrice>> {
+    let mut reader =
+        csv::Reader::from_path(path).with_context(|| format!("opening {}", path.display()))?;
+    Ok(reader
+        .deserialize::<FilePrice>()
+        .collect::<Result<_, csv::Error>>()?)
+}
+
+/// Price of `ticker` on `date` from the dated rows of a `FileQuotes` file,
+/// in any base
+pub fn file_price_on(path: &Path, ticker: &str, date: Date) -> Result<Decimal> {
+    let day = date.to_string();
+    let price = read_file_prices(path)?
+        .into_iter()
+        .find(|p| p.ticker.eq_ignore_ascii_case(ticker) && p.date.as_deref() == Some(&day))
+        .ok_or(anyhow!(
+            "no {} price of {} in {}",
+            ticker,
+            day,
+            path.display()
+        ))?
+        .price;
+    Ok(Decimal::try_from(price)?)
+}
+
+impl QuoteProvider for FileQuotes {
+    fn name(&self) -> String {
+        format!("file:{}", self.path.display())
+    }
+
+    fn quotes(&self, tickers: &[String], _base: &str) -> Result<HashMap<String, f64>> {
+        let prices: HashMap<String, f64> = read_file_prices(&self.path)?
+            .into_iter()
+            .filter(|p| p.date.is_none())
+            .map(|p| (p.ticker.to_ascii_uppercase(), p.price))
+            .collect();
+        Ok(tickers
+            .iter()
+            .filter_map(|t| Some((t.clone(), *prices.get(&t.to_ascii_uppercase())?)))
+            .collect())
+    }
+}
+
+/// Parse the `quote_provider` setting: a comma separated list of
+/// `coingecko`, `binance` or `file:<path>`
+pub fn parse_providers(spec: &str) -> Result<Vec<Box<dyn QuoteProvider>>> {
+    let mut providers: Vec<Box<dyn QuoteProvider>> = Vec::new();
+    for name in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        match name.to_ascii_lowercase().as_str() {
+            "coingecko" => providers.push(Box::new(CoinGecko)),
+            "binance" => providers.push(Box::new(Binance)),
+            _ => match name.strip_prefix("file:") {
+                Some(path) => providers.push(Box::new(FileQuotes {
+                    path: PathBuf::from(shellexpand::tilde(path).to_string()),
+                })),
+                None => bail!("unsupported quote provider '{}'", name),
+            },
+        }
+    }
+    if providers.is_empty() {
+        bail!("no quote provider configured");
+    }
+    Ok(providers)
+}
+
+/// Use these providers for all following quote lookups
+///
+/// With more than one provider, prices are the median of all providers and
+/// a warning is printed when they differ by more than `discrepancy_pct`.
+pub fn configure(providers: Vec<Box<dyn QuoteProvider>>, discrepancy_pct: Decimal) {
+    let mut current = PROVIDERS.lock().unwrap();
+    current.list = providers;
+    current.discrepancy_pct = discrepancy_pct.to_f64().unwrap_or(2.0);
+    QUOTE_CACHE.lock().unwrap().pairs.clear();
+}
+
+/// Names of the configured providers, e.g. `coingecko+binance`
+pub fn provider_names() -> String {
+    let providers = PROVIDERS.lock().unwrap();
+    let names: Vec<String> = providers.list.iter().map(|p| p.name()).collect();
+    names.join("+")
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Combine the quotes of several providers into their median per ticker,
+/// returning warnings for tickers where providers disagree too much
+pub fn consensus(
+    results: &[(String, HashMap<String, f64>)],
+    discrepancy_pct: f64,
+) -> (HashMap<String, f64>, Vec<String>) {
+    let mut by_ticker: HashMap<&str, Vec<(&str, f64)>> = HashMap::new();
+    for (provider, quotes) in results {
+        for (ticker, price) in quotes {
+            by_ticker
+                .entry(ticker.as_str())
+                .or_default()
+                .push((provider.as_str(), *price));
+        }
+    }
+
+    let mut quotes = HashMap::new();
+    let mut warnings = Vec::new();
+    for (ticker, prices) in by_ticker {
+        let mut values: Vec<f64> = prices.iter().map(|(_, p)| *p).collect();
+        let median = median(&mut values);
+        let spread = values[values.len() - 1] - values[0];
+        if median > 0.0 && spread / median * 100.0 > discrepancy_pct {
+            let detail: Vec<String> = prices.iter().map(|(n, p)| format!("{} {}", n, p)).collect();
+            warnings.push(format!(
+                "{} quotes differ by {:.2}% ({}), using median {}",
+                ticker,
+                spread / median * 100.0,
+                detail.join(", "),
+                median
+            ));
+        }
+        quotes.insert(ticker.to_string(), median);
+    }
+    (quotes, warnings)
+}
+
+/// Quotes from all configured providers; a failing provider is skipped with
+/// a warning as long as another one answers
+fn fetch_quotes(tickers: &[String], base: &str) -> Result<HashMap<String, f64>> {
+    let providers = PROVIDERS.lock().unwrap();
+    if providers.list.len() == 1 {
+        return providers.list[0].quotes(tickers, base);
+    }
+
+    let mut results = Vec::new();
+    let mut last_err = None;
+    for provider in providers.list.iter() {
+        match provider.quotes(tickers, base) {
+            Result::Ok(quotes) => results.push((provider.name(), quotes)),
+            Err(e) => {
+                warnings::warn(format!("quote provider {} failed: {}", provider.name(), e));
+                last_err = Some(e);
+            }
+        }
+    }
+    if results.is_empty() {
+        return Err(last_err.unwrap_or(anyhow!("no quote provider configured")));
+    }
+
+    let (quotes, warnings) = consensus(&results, providers.discrepancy_pct);
+    for warning in warnings {
+        warnings::warn(warning);
+    }
+    Ok(quotes)
+}
+
+/// Quotes keyed by (ticker, quote currency) pair, e.g. ("ETH", "EUR")
+///
+/// All tickers are fetched at once per quote currency, so a report in EUR
+/// and one in USD share the cache without refetching each other's quotes.
 struct QuoteCache {
-    quotes: HashMap<String, f64>,
-    last_updated: Instant,
+    pairs: HashMap<(String, String), CachedQuote>,
+    /// How long a quote is used before it's refetched
+    max_age: Duration,
+}
+
+impl Default for QuoteCache {
+    fn default() -> Self {
+        QuoteCache {
+            pairs: HashMap::new(),
+            max_age: CACHE_DURATION,
+        }
+    }
 }
 
-fn get_cached_quotes(base: &str) -> Result<HashMap<String, f64>> {
+#[derive(Clone, Copy)]
+struct CachedQuote {
+    price: f64,
+    fetched: Instant,
+}
+
+impl QuoteCache {
+    fn fresh(&self, ticker: &str, quote: &str, now: Instant) -> Option<CachedQuote> {
+        self.pairs
+            .get(&(ticker.to_string(), quote.to_string()))
+            .filter(|q| now.duration_since(q.fetched) < self.max_age)
+            .copied()
+    }
+
+    fn insert(&mut self, ticker: &str, quote: &str, cached: CachedQuote) {
+        self.pairs
+            .insert((ticker.to_string(), quote.to_string()), cached);
+    }
+
+    /// Price of `ticker` in a crypto `quote`, crossed through the USD quotes
+    /// of both when they are cached, e.g. ETH/BTC from ETH/USD and BTC/USD
+    fn cross(&self, ticker: &str, quote: &str, now: Instant) -> Option<CachedQuote> {
+        if !CRYPTO.contains(&quote) {
+            return None;
+        }
+        let ticker_usd = self.fresh(ticker, "USD", now)?;
+        let quote_usd = self.fresh(quote, "USD", now)?;
+        (quote_usd.price > 0.0).then(|| CachedQuote {
+            price: ticker_usd.price / quote_usd.price,
+            // expires with the older of both legs
+            fetched: ticker_usd.fetched.min(quote_usd.fetched),
+        })
+    }
+}
+
+fn get_cached_quote(ticker: &str, base: &str) -> Result<f64> {
+    let base = base.to_ascii_uppercase();
+    if ticker == base {
+        return Ok(1.0);
+    }
     let mut cache = QUOTE_CACHE.lock().unwrap();
+    let now = Instant::now();
 
-    // Check if cache is valid
-    let needs_refresh = cache
-        .as_ref()
-        .map(|c| c.last_updated.elapsed() >= CACHE_DURATION)
-        .unwrap_or(true);
-
-    if needs_refresh {
-        // Fetch fresh quotes
-        let quotes = get_quotes(&*CRYPTO, base)?;
-        *cache = Some(QuoteCache {
-            quotes: quotes.clone(),
-            last_updated: Instant::now(),
-        });
-        Ok(quotes)
-    } else {
-        // Return cached quotes
-        Ok(cache.as_ref().unwrap().quotes.clone())
+    if let Some(cached) = cache.fresh(ticker, &base, now) {
+        return Ok(cached.price);
     }
+    if let Some(cached) = cache.cross(ticker, &base, now) {
+        cache.insert(ticker, &base, cached);
+        return Ok(cached.price);
+    }
+
+    // one request refreshes all tickers in this quote currency
+    let tickers: Vec<String> = CRYPTO.iter().map(|t| t.to_string()).collect();
+    let quotes = fetch_quotes(&tickers, &base)?;
+    for (t, price) in &quotes {
+        cache.insert(
+            t,
+            &base,
+            CachedQuote {
+                price: *price,
+                fetched: now,
+            },
+        );
+    }
+    quotes
+        .get(ticker)
+        .copied()
+        .ok_or(anyhow!("quote missing for {}/{}", ticker, base))
 }
 
-pub fn quote_in_base(currency: &Currency, base: &str) -> Result<Decimal> {
-    let quotes = get_cached_quotes(base)?;
-    let quote = quotes
-        .get(currency.ticker())
-        .ok_or(anyhow!("quote missing"))?;
-    Ok(Decimal::from_f64_retain(*quote).ok_or(anyhow!("can't decimal from f64"))?)
+/// Use cached quotes for up to `max_age`, for `watch` which refreshes them
+/// on its own schedule, see `refresh::Schedule`
+pub fn keep_quotes_for(max_age: Duration) {
+    QUOTE_CACHE.lock().unwrap().max_age = max_age;
+}
+
+/// Forget all cached quotes, so the next lookups fetch them fresh
+pub fn clear_cache() {
+    QUOTE_CACHE.lock().unwrap().pairs.clear();
+}
+
+/// Fetch the quotes of only `tickers` in `base` into the cache
+pub fn refresh(tickers: &[String], base: &str) -> Result<()> {
+    let base = base.to_ascii_uppercase();
+    let quotes = fetch_quotes(tickers, &base)?;
+    let mut cache = QUOTE_CACHE.lock().unwrap();
+    let now = Instant::now();
+    for (ticker, price) in &quotes {
+        cache.insert(
+            ticker,
+            &base,
+            CachedQuote {
+                price: *price,
+                fetched: now,
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Check the quote provider is reachable, returning the round trip time
+pub fn ping() -> Result<Duration> {
+    let started = Instant::now();
+    let _: serde_json::Value = http::get_json("https://api.coingecko.com/api/v3/ping")?;
+    Ok(started.elapsed())
+}
+
+/// Prices valuation is done with, in a base currency
+///
+/// Portfolio valuation takes one of these instead of fetching quotes itself,
+/// so the math is deterministic given prices and testable offline.
+pub trait Prices {
+    fn price_in(&self, currency: &Currency, base: &str) -> Result<Decimal>;
 }
 
-// needed for deserialization of api return price, which is in format
-// {"bitcoin":{"usd":109509},"ethereum":{"usd":3885.46}
-// ** currently only suporting USD quotes
-#[derive(Debug, serde::Deserialize)]
-struct Price {
-    usd: f64,
+/// Current quotes from the configured providers, see `quote_in_base`
+pub struct LiveQuotes;
+
+impl Prices for LiveQuotes {
+    fn price_in(&self, currency: &Currency, base: &str) -> Result<Decimal> {
+        quote_in_base(currency, base)
+    }
+}
+
+/// Fixed prices by ticker, whatever base they are asked in
+impl Prices for HashMap<String, Decimal> {
+    fn price_in(&self, currency: &Currency, base: &str) -> Result<Decimal> {
+        if currency.ticker().eq_ignore_ascii_case(base) {
+            return Ok(Decimal::ONE);
+        }
+        self.get(currency.ticker())
+            .copied()
+            .ok_or(anyhow!("no price for {}/{}", currency, base))
+    }
+}
+
+pub fn quote_in_base(currency: &Currency, base: &str) -> Result<Decimal> {
+    let delisted = DELISTED.lock().unwrap().get(currency.ticker()).cloned();
+    if let Some(delisted) = delisted {
+        if delisted.currency.eq_ignore_ascii_case(base) {
+            return Ok(delisted.price);
+        }
+        let currency = Currency::new(&delisted.currency)?;
+        return Ok(delisted.price * quote_in_base(&currency, base)?);
+    }
+    let known_unquoted = MANUAL.lock().unwrap().unquoted.contains(currency.ticker());
+    let quote = if known_unquoted {
+        Err(anyhow!("quote missing for {}/{}", currency, base))
+    } else {
+        get_cached_quote(currency.ticker(), base)
+    };
+    let manual = match quote {
+        Result::Ok(quote) => {
+            return Ok(Decimal::from_f64_retain(quote).ok_or(anyhow!("can't decimal from f64"))?);
+        }
+        Err(e) => unquoted_price(currency.ticker(), e)?,
+    };
+    if manual.currency.eq_ignore_ascii_case(base) {
+        return Ok(manual.price);
+    }
+    Ok(manual.price * quote_in_base(&Currency::new(&manual.currency)?, base)?)
 }
 
-/// Obtaining current ticker quotes in USD for ticker list
+/// Obtaining current ticker quotes in `base` for ticker list
 ///
 /// Coingecko API accepts ids, while we are using short tickers elsewhere
 /// that is why translation from ticker to id is required
@@ -80,54 +548,119 @@ where
         &ids.join(","),
         base
     );
-    let res = reqwest::blocking::get(endpoint)?.json::<HashMap<String, Price>>()?;
+    // {"bitcoin":{"usd":109509},"ethereum":{"usd":3885.46}}
+    let res: HashMap<String, HashMap<String, f64>> = http::get_json(&endpoint)?;
 
     // need to convert back ids to tickers
+    let vs = base.to_ascii_lowercase();
     let quotes_hm = res
         .into_iter()
-        .map(|(id, price)| (id_ticker_hm.get(&id).unwrap().clone(), price.usd))
+        .filter_map(|(id, prices)| Some((id_ticker_hm.get(&id)?.clone(), *prices.get(&vs)?)))
         .collect();
 
     Ok(quotes_hm)
 }
 
 /// Getting quotes from coingecko api
-/// data/coingecko.csv table is holding (id, symbol, name) required for the coingecko API
-/// *name is not actually required
+/// data/coingecko.csv table is holding (id, symbol, name) required for the coingecko API,
+/// ordered by market cap
 #[derive(Debug, Deserialize)]
-struct CsvRow {
-    id: String,     // long id, e.g. bitcoin, ethereum, etc.
-    symbol: String, // short ticker
-    #[allow(dead_code)]
-    name: String,
+pub(crate) struct CsvRow {
+    pub(crate) id: String,     // long id, e.g. bitcoin, ethereum, etc.
+    pub(crate) symbol: String, // short ticker
+    pub(crate) name: String,
 }
 
-fn to_ids(tickers: &[String]) -> Result<Vec<String>> {
+/// All coins of the CoinGecko registry, largest market cap first
+pub(crate) fn registry() -> Result<Vec<CsvRow>> {
     let mut reader = csv::Reader::from_path(GECKO_TICKER_IDS)
         .with_context(|| format!("opening {}", GECKO_TICKER_IDS))?;
-
-    let symbol_to_id: HashMap<String, String> = reader
+    reader
         .deserialize()
         .collect::<Result<Vec<CsvRow>, _>>() // csv::Error -> anyhow::Error via ?
-        .context("parsing coins CSV")?
-        .into_iter()
-        .map(|coin| (coin.symbol.to_ascii_uppercase(), coin.id))
-        .collect();
+        .context("parsing coins CSV")
+}
 
-    let ids = tickers
-        .iter()
-        .map(|t| {
-            let key = t.to_ascii_uppercase();
-            symbol_to_id
-                .get(&key)
-                .cloned()
-                .ok_or_else(|| anyhow!("Ticker not found: {}", t)) // <- produce an Error, not String
-        })
-        .collect::<Result<Vec<_>>>()?; // inferred as Result<Vec<String>, anyhow::Error>
+/// CoinGecko ids by upper case symbol, largest market cap first; a few
+/// symbols are shared, e.g. SOL by Solana and Wrapped SOL
+type GeckoIds = Arc<HashMap<String, Vec<String>>>;
+
+/// The ids, read from `GECKO_TICKER_IDS` on first use; a report asks for
+/// ids several times
+static GECKO_IDS: LazyLock<Mutex<Option<GeckoIds>>> = LazyLock::new(|| Mutex::new(None));
 
+/// The symbol to id map, loaded once; a failed load isn't kept, so the
+/// next call tries again
+fn gecko_ids() -> Result<GeckoIds> {
+    let mut cached = GECKO_IDS.lock().unwrap();
+    if let Some(ids) = cached.as_ref() {
+        return Ok(Arc::clone(ids));
+    }
+    if !Path::new(GECKO_TICKER_IDS).exists() {
+        bail!(
+            "ticker table {} is missing, CoinGecko quotes need it to look up coin ids",
+            GECKO_TICKER_IDS
+        );
+    }
+    let mut ids: HashMap<String, Vec<String>> = HashMap::new();
+    for coin in registry()? {
+        ids.entry(coin.symbol.to_ascii_uppercase())
+            .or_default()
+            .push(coin.id);
+    }
+    let ids = Arc::new(ids);
+    *cached = Some(Arc::clone(&ids));
     Ok(ids)
 }
 
+/// CoinGecko ids picked in the config for shared symbols, set once from the
+/// settings at startup, and the shared symbols already warned about
+static SYMBOLS: LazyLock<Mutex<Symbols>> = LazyLock::new(|| Mutex::new(Symbols::default()));
+
+#[derive(Default)]
+struct Symbols {
+    overrides: BTreeMap<String, String>,
+    warned: HashSet<String>,
+}
+
+/// Look up these symbols by the CoinGecko id given, by upper case symbol
+pub fn configure_symbol_overrides(overrides: BTreeMap<String, String>) {
+    SYMBOLS.lock().unwrap().overrides = overrides;
+}
+
+/// The id of `symbol`: its override, else the largest of the coins sharing
+/// it, with a warning once per symbol that it was picked for the user
+fn resolve_id(symbol: &str, ids: &HashMap<String, Vec<String>>) -> Result<String> {
+    let key = symbol.to_ascii_uppercase();
+    let mut symbols = SYMBOLS.lock().unwrap();
+    if let Some(id) = symbols.overrides.get(&key) {
+        return Ok(id.clone());
+    }
+    let candidates = ids
+        .get(&key)
+        .ok_or_else(|| anyhow!("Ticker not found: {}", symbol))?;
+    if candidates.len() > 1 && symbols.warned.insert(key.clone()) {
+        warnings::warn(format!(
+            "{} is the symbol of several coins ({}), using {}, the largest; \
+             set symbol_overrides.{} in the config to pick another",
+            key,
+            candidates.join(", "),
+            candidates[0],
+            key
+        ));
+    }
+    Ok(candidates[0].clone())
+}
+
+pub(crate) fn to_ids(tickers: &[String]) -> Result<Vec<String>> {
+    let symbol_to_ids = gecko_ids()?;
+
+    tickers
+        .iter()
+        .map(|t| resolve_id(t, &symbol_to_ids))
+        .collect()
+}
+
 //
 // = = = = = TEST = = = = =
 //
@@ -142,6 +675,103 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_consensus_uses_median_and_warns_on_discrepancy() {
+        let results = vec![
+            (
+                "a".to_string(),
+                HashMap::from([("BTC".to_string(), 100.0), ("ETH".to_string(), 10.0)]),
+            ),
+            (
+                "b".to_string(),
+                HashMap::from([("BTC".to_string(), 110.0), ("ETH".to_string(), 10.1)]),
+            ),
+            ("c".to_string(), HashMap::from([("BTC".to_string(), 104.0)])),
+        ];
+        let (quotes, warnings) = consensus(&results, 5.0);
+
+        assert_eq!(quotes["BTC"], 104.0);
+        assert_eq!(quotes["ETH"], 10.05);
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0].starts_with("BTC quotes differ by 9.62%"),
+            "{}",
+            warnings[0]
+        );
+    }
+
+    #[test]
+    fn test_cache_keys_quotes_by_pair_and_crosses_crypto_bases() {
+        let now = Instant::now();
+        let quote = |price| CachedQuote {
+            price,
+            fetched: now,
+        };
+        let mut cache = QuoteCache::default();
+        cache.insert("BTC", "USD", quote(100_000.0));
+        cache.insert("ETH", "USD", quote(4_000.0));
+        cache.insert("ETH", "EUR", quote(3_500.0));
+
+        assert_eq!(cache.fresh("ETH", "EUR", now).unwrap().price, 3_500.0);
+        assert_eq!(cache.fresh("ETH", "USD", now).unwrap().price, 4_000.0);
+        assert!(cache.fresh("BTC", "EUR", now).is_none());
+        // crypto quotes are crossed from cached USD quotes, fiat ones are fetched
+        assert_eq!(cache.cross("ETH", "BTC", now).unwrap().price, 0.04);
+        assert!(cache.cross("BTC", "EUR", now).is_none());
+
+        let later = now + CACHE_DURATION;
+        assert!(cache.fresh("ETH", "USD", later).is_none());
+        assert!(cache.cross("ETH", "BTC", later).is_none());
+    }
+
+    #[test]
+    fn test_parse_providers() {
+        let names: Vec<String> = parse_providers("coingecko, Binance,file:prices.csv")
+            .unwrap()
+            .iter()
+            .map(|p| p.name())
+            .collect();
+        assert_eq!(names, vs!["coingecko", "binance", "file:prices.csv"]);
+        assert!(parse_providers("kraken").is_err());
+        assert!(parse_providers(" , ").is_err());
+    }
+
+    #[test]
+    fn test_file_quotes_reads_known_tickers() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("prices.csv");
+        std::fs::write(&path, "ticker,price\nbtc,50000\nETH,3000.5\n").unwrap();
+
+        let tickers = vs!["BTC", "ETH", "SOL"];
+        let quotes = FileQuotes { path }.quotes(&tickers, "USD").unwrap();
+        assert_eq!(
+            quotes,
+            HashMap::from([("BTC".to_string(), 50000.0), ("ETH".to_string(), 3000.5)])
+        );
+    }
+
+    #[test]
+    fn test_file_quotes_keeps_dated_rows_for_daily_prices() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("prices.csv");
+        std::fs::write(
+            &path,
+            "ticker,price,date\nBTC,50000,\nbtc,42000.5,2024-01-10\nBTC,43000,2024-01-11\n",
+        )
+        .unwrap();
+
+        let quotes = FileQuotes { path: path.clone() }
+            .quotes(&["BTC".to_string()], "USD")
+            .unwrap();
+        assert_eq!(quotes, HashMap::from([("BTC".to_string(), 50000.0)]));
+        assert_eq!(
+            file_price_on(&path, "BTC", time::macros::date!(2024 - 01 - 10)).unwrap(),
+            rust_decimal::dec!(42000.5)
+        );
+        assert!(file_price_on(&path, "BTC", time::macros::date!(2024 - 01 - 12)).is_err());
+        assert!(file_price_on(&path, "ETH", time::macros::date!(2024 - 01 - 10)).is_err());
+    }
+
     #[test]
     fn test_to_ids_single_match() {
         let tickers = vs!["BTC"];
@@ -171,6 +801,31 @@ mod tests {
         assert!(err.to_string().contains("Ticker not found: aaabtccc"))
     }
 
+    #[test]
+    fn test_gecko_ids_are_loaded_once() {
+        let ids = gecko_ids().unwrap();
+        assert!(Arc::ptr_eq(&ids, &gecko_ids().unwrap()));
+        assert_eq!(ids["BTC"], vs!["bitcoin"]);
+    }
+
+    #[test]
+    fn test_shared_symbols_resolve_to_the_largest_coin_unless_overridden() {
+        let ids = HashMap::from([
+            ("SOL".to_string(), vs!["solana", "wrapped-solana"]),
+            ("DOGE".to_string(), vs!["dogecoin", "binance-peg-dogecoin"]),
+        ]);
+        assert_eq!(resolve_id("sol", &ids).unwrap(), "solana");
+        assert!(SYMBOLS.lock().unwrap().warned.contains("SOL"));
+
+        configure_symbol_overrides(BTreeMap::from([(
+            "DOGE".to_string(),
+            "binance-peg-dogecoin".to_string(),
+        )]));
+        assert_eq!(resolve_id("doge", &ids).unwrap(), "binance-peg-dogecoin");
+        assert!(!SYMBOLS.lock().unwrap().warned.contains("DOGE"));
+        configure_symbol_overrides(BTreeMap::new());
+    }
+
     #[test]
     fn test_ticker_to_id_order() {
         let tickers = vs!["ADA", "eth", "BTC"];
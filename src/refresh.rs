@@ -0,0 +1,192 @@
+//! Staggered refresh of the quote cache in `watch`
+//!
+//! Instead of refetching every quote at every poll, the quote of each held
+//! asset is refreshed every 1, 4 or 16 polls depending on its weight in the
+//! portfolio, and the assets of a tier take turns. Requests stay small, and
+//! the largest positions, which move the totals most, are never more than a
+//! poll old. Quotes of price alerts and of positions with a stop or target
+//! level are refreshed at every poll.
+
+use crate::alert::{self, Trigger};
+use crate::currency::CurrencyType;
+use crate::math;
+use crate::portfolio::list_entries;
+use crate::position_cache;
+use crate::quote::{self, LiveQuotes};
+use crate::settings::Settings;
+use crate::stops;
+use crate::warnings;
+use anyhow::Result;
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+/// Polls between refreshes of a quote, for positions of at least a weight in %
+const TIERS: [(Decimal, u64); 3] = [(dec!(10), 1), (dec!(1), 4), (Decimal::ZERO, 16)];
+
+/// Polls between refreshes of a quote of a position weighing `weight` %
+pub fn every(weight: Decimal) -> u64 {
+    TIERS
+        .iter()
+        .find(|(min, _)| weight >= *min)
+        .map_or(TIERS[TIERS.len() - 1].1, |(_, every)| *every)
+}
+
+/// How long quotes are used for when polling every `interval`: until the
+/// slowest tier refreshed them, with a poll to spare
+pub fn max_age(interval: Duration) -> Duration {
+    interval * (TIERS[TIERS.len() - 1].1 as u32 + 1)
+}
+
+/// A (ticker, base) quote
+type Pair = (String, String);
+
+/// Which quotes are due at each poll of `watch`
+#[derive(Debug, Default)]
+pub struct Schedule {
+    polls: u64,
+    /// Largest weight in % of each pair in any portfolio, as of the last poll
+    weights: BTreeMap<Pair, Decimal>,
+}
+
+impl Schedule {
+    /// Tickers due at this poll by base: pairs not weighed yet, and each
+    /// tier's pairs in turn, so that every pair comes once per `every` polls
+    pub fn due(&self, pairs: &BTreeSet<Pair>) -> BTreeMap<String, Vec<String>> {
+        let mut due: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut turns: BTreeMap<u64, u64> = BTreeMap::new();
+        for pair in pairs {
+            let is_due = match self.weights.get(pair) {
+                None => true,
+                Some(weight) => {
+                    let every = every(*weight);
+                    let turn = turns.entry(every).or_default();
+                    *turn += 1;
+                    (self.polls + *turn - 1).is_multiple_of(every)
+                }
+            };
+            if is_due {
+                due.entry(pair.1.clone()).or_default().push(pair.0.clone());
+            }
+        }
+        due
+    }
+
+    /// Refresh the quotes due, then weigh the positions at the cached
+    /// quotes for the next poll; called before each poll
+    pub fn refresh(&mut self, settings: &Settings) -> Result<()> {
+        let mut portfolios = Vec::new();
+        for entry in list_entries(settings)? {
+            // `watch` warns about portfolios it can't value
+            if let Ok(pf) = position_cache::load(&entry.path, settings) {
+                portfolios.push((entry.name, pf));
+            }
+        }
+
+        let mut pairs = BTreeSet::new();
+        for (_, pf) in &portfolios {
+            for currency in pf.positions.keys() {
+                if currency.currency_type() != CurrencyType::Fiat {
+                    pairs.insert((currency.ticker().to_string(), pf.base.ticker().to_string()));
+                }
+            }
+        }
+        let base = settings.base_currency.ticker();
+        let mut alerted = BTreeSet::new();
+        for alert in alert::load(settings)? {
+            if let Trigger::PriceAbove { ticker, .. } | Trigger::PriceBelow { ticker, .. } =
+                alert.trigger
+            {
+                alerted.insert((ticker, base.to_string()));
+            }
+        }
+        let stops = stops::load(settings)?;
+        for (name, pf) in &portfolios {
+            for asset in stops.get(name).into_iter().flat_map(|levels| levels.keys()) {
+                alerted.insert((asset.clone(), pf.base.ticker().to_string()));
+            }
+        }
+        pairs.extend(alerted.iter().cloned());
+
+        for (base, tickers) in self.due(&pairs) {
+            if let Err(e) = quote::refresh(&tickers, &base) {
+                warnings::warn(format!("can't refresh quotes in {}: {}", base, e));
+            }
+        }
+
+        let mut weights: BTreeMap<Pair, Decimal> = BTreeMap::new();
+        for (_, pf) in &portfolios {
+            let Ok(values) = pf.position_values(&LiveQuotes) else {
+                continue;
+            };
+            let total: Decimal = values.values().sum();
+            if total <= Decimal::ZERO {
+                continue;
+            }
+            for (currency, value) in values {
+                let pair = (currency.ticker().to_string(), pf.base.ticker().to_string());
+                let weight = math::percent_of(value, total).unwrap_or_default().value();
+                let max = weights.entry(pair).or_default();
+                *max = (*max).max(weight);
+            }
+        }
+        for pair in alerted {
+            weights.insert(pair, dec!(100));
+        }
+        self.weights = weights;
+        self.polls += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(ticker: &str) -> Pair {
+        (ticker.to_string(), "USD".to_string())
+    }
+
+    #[test]
+    fn test_large_positions_refresh_every_poll_small_ones_take_turns() {
+        let mut schedule = Schedule::default();
+        let pairs: BTreeSet<Pair> = ["BTC", "ETH", "SOL", "ADA", "DOT"]
+            .into_iter()
+            .map(pair)
+            .collect();
+        // nothing weighed yet, so everything is due
+        assert_eq!(schedule.due(&pairs)["USD"].len(), 5);
+
+        schedule.weights = BTreeMap::from([
+            (pair("BTC"), dec!(70)),
+            (pair("ETH"), dec!(20)),
+            (pair("SOL"), dec!(5)),
+            (pair("ADA"), dec!(4)),
+            (pair("DOT"), dec!(0.5)),
+        ]);
+        let due: Vec<Vec<String>> = (1..=8)
+            .map(|polls| {
+                schedule.polls = polls;
+                schedule.due(&pairs).remove("USD").unwrap_or_default()
+            })
+            .collect();
+        assert_eq!(due[0], ["BTC", "ETH"]);
+        // ADA and SOL share the 4-poll tier and come one poll apart
+        assert_eq!(due[2], ["BTC", "ETH", "SOL"]);
+        assert_eq!(due[3], ["ADA", "BTC", "ETH"]);
+        assert_eq!(due[6], ["BTC", "ETH", "SOL"]);
+        // DOT is refreshed every 16 polls, at none of these
+        assert!(
+            due.iter()
+                .all(|tickers| !tickers.contains(&"DOT".to_string()))
+        );
+
+        assert_eq!(every(dec!(10)), 1);
+        assert_eq!(every(dec!(0)), 16);
+        assert_eq!(
+            max_age(Duration::from_secs(60)),
+            Duration::from_secs(17 * 60)
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! The positions table of `report --name`
+//!
+//! Valuing a portfolio is `Portfolio::valuation`, the same numbers `report
+//! --all`, `--diff` and the snapshots use. This module only renders them:
+//! the table of positions, the totals and the notes below them.
+
+use crate::contribution;
+use crate::format;
+use crate::portfolio::{Portfolio, Valuation};
+use crate::position_cache;
+use crate::quote::Prices;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{self, PortfolioKind, read_csv_config};
+use anyhow::Result;
+use prettytable::{Table, row};
+use std::path::Path;
+use thousands::Separable;
+
+// +--------+---------+---------------+---------------+---------+
+// | Ticker | Balance | Cost Base     | Avg Price     | PnL %   |
+// +--------+---------+---------------+---------------+---------+
+// | ETH    | 11.0    | 40,705.50 USD | 3,700.50 USD  | -23.86% |
+// +--------+---------+---------------+---------------+---------+
+// | BTC    | 3.5     | 87,515.31 USD | 25,004.38 USD | 242.57% |
+// +--------+---------+---------------+---------------+---------+
+// =================================
+// Portfolio:      330_793.85 USD
+// Total PnL:      202_573.04 USD
+// Total PnL:      157.99%
+// =================================
+/// Print the positions of the portfolio at `path` valued at `prices`
+pub fn show<P: AsRef<Path>>(
+    path: P,
+    prices: &dyn Prices,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let pf = position_cache::load(&path, settings)?;
+    show_portfolio(&pf, path.as_ref(), prices, reporter, settings)
+}
+
+/// Print the positions of `pf`, read from the file at `path`
+pub fn show_portfolio(
+    pf: &Portfolio,
+    path: &Path,
+    prices: &dyn Prices,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    if pf.positions.is_empty() {
+        reporter.print("No positions in portfolio");
+        return Ok(());
+    }
+
+    let valuation = pf.valuation(prices)?;
+    reporter.table(&positions_table(&valuation, settings))?;
+    print_totals(&valuation, reporter, settings);
+    print_notes(pf, &valuation, reporter, settings);
+    print_mixed(path, &valuation, reporter)?;
+    contribution::print_section(pf, valuation.value, reporter);
+
+    Ok(())
+}
+
+/// A row per position, delisted assets marked with ☠
+pub fn positions_table(valuation: &Valuation, settings: &Settings) -> Table {
+    let base = &valuation.base;
+    let mut table = Table::new();
+    table.add_row(row!["Ticker", "Balance", "Cost Base", "Avg Price", "PnL %"]);
+
+    for position in &valuation.positions {
+        let label = if settings.delisted.contains_key(position.currency.ticker()) {
+            format!("{} ☠", position.currency)
+        } else {
+            position.currency.to_string()
+        };
+        let balance = position.balance.round_dp(2);
+
+        let Some(cost_base) = position.cost_base else {
+            table.add_row(row![label, balance, "-", "-", "-"]);
+            continue;
+        };
+        table.add_row(row![
+            label,
+            balance,
+            format!(
+                "{} {}",
+                settings.rounding.cents(cost_base).separate_with_commas(),
+                base
+            ),
+            position.avg_price().map_or("-".to_string(), |p| format!(
+                "{} {}",
+                format::price(p, settings),
+                base
+            )),
+            // positions from airdrops or income can have no cost
+            position
+                .pnl_pct()
+                .map_or("-".to_string(), |p| format!("{:.2}", p))
+        ]);
+    }
+    table
+}
+
+pub fn print_totals(valuation: &Valuation, reporter: &dyn Reporter, settings: &Settings) {
+    let base = &valuation.base;
+    reporter.print("=================================");
+    reporter.print(&format!(
+        "Portfolio:\t{} {}",
+        settings
+            .rounding
+            .cents(valuation.value)
+            .separate_with_underscores(),
+        base
+    ));
+    reporter.print(&format!(
+        "Total PnL:\t{} {}",
+        settings
+            .rounding
+            .cents(valuation.pnl)
+            .separate_with_underscores(),
+        base
+    ));
+    reporter.print(&format!(
+        "Total PnL:\t{}%",
+        valuation
+            .pnl_pct()
+            .unwrap_or_default()
+            .round_dp(2)
+            .value()
+            .separate_with_underscores()
+    ));
+    reporter.print("=================================");
+}
+
+/// Name the fiat currencies besides the base that trades are quoted in,
+/// rows `add-tx --mixed` let in or that were edited in by hand
+fn print_mixed(path: &Path, valuation: &Valuation, reporter: &dyn Reporter) -> Result<()> {
+    if read_csv_config(path)?.kind == PortfolioKind::Holdings {
+        return Ok(());
+    }
+    let mixed = trade::mixed_quotes(path)?;
+    if !mixed.is_empty() {
+        reporter.print(&format!(
+            "Mixed currencies: trades quoted in {} besides the {} base currency",
+            mixed.into_iter().collect::<Vec<_>>().join(", "),
+            valuation.base
+        ));
+    }
+    Ok(())
+}
+
+/// Notes on the holdings without a cost and the delisted assets
+fn print_notes(
+    pf: &Portfolio,
+    valuation: &Valuation,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) {
+    if !pf.unknown_cost.is_empty() {
+        reporter.print("Holdings without cost are valued but left out of PnL");
+    }
+    let delisted: Vec<String> = valuation
+        .positions
+        .iter()
+        .filter_map(|p| {
+            let final_price = settings.delisted.get(p.currency.ticker())?;
+            Some(format!(
+                "{} at {} {}",
+                p.currency, final_price.price, final_price.currency
+            ))
+        })
+        .collect();
+    if !delisted.is_empty() {
+        reporter.print(&format!(
+            "☠ Delisted, valued at the final price of the config: {}",
+            delisted.join(", ")
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::Currency;
+    use crate::quote::Delisted;
+    use crate::reporter::Buffer;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use anyhow::{bail, ensure};
+    use rust_decimal::{Decimal, dec};
+    use tempfile::TempDir;
+
+    /// Quotes in EUR only, so valuing in the USD of the settings fails
+    struct EurQuotes;
+
+    impl Prices for EurQuotes {
+        fn price_in(&self, currency: &Currency, base: &str) -> Result<Decimal> {
+            ensure!(base == "EUR", "quoted in EUR only, asked in {}", base);
+            match currency.ticker() {
+                "BTC" => Ok(dec!(50000)),
+                "ETH" => Ok(dec!(1000)),
+                "ADA" => Ok(dec!(0.5)),
+                ticker => bail!("no quote for {}", ticker),
+            }
+        }
+    }
+
+    fn eur_portfolio(dir: &TempDir) -> Portfolio {
+        let path = create_test_csv(
+            dir,
+            "eur",
+            "# base_currency: EUR
+created_at,pair,side,amount,price,fee
+1704067200,EUR,DEPOSIT,100000,1,0
+1704153600,BTC/EUR,BUY,1,40000,0
+1704240000,ETH/EUR,BUY,10,2000,0
+1704326400,ADA/EUR,BUY,1000,1,0
+",
+        );
+        Portfolio::from_csv(path).unwrap()
+    }
+
+    #[test]
+    fn test_valuation_is_in_the_portfolio_base() {
+        let dir = TempDir::new().unwrap();
+        let valuation = eur_portfolio(&dir).valuation(&EurQuotes).unwrap();
+
+        assert_eq!(valuation.base, Currency::new("EUR").unwrap());
+        assert_eq!(valuation.value, dec!(60500));
+        assert_eq!(valuation.cost_base, dec!(61000));
+        assert_eq!(valuation.pnl, dec!(-500));
+        // cash in the base is no position to value
+        assert_eq!(valuation.positions.len(), 3);
+        let btc = valuation
+            .positions
+            .iter()
+            .find(|p| p.currency.ticker() == "BTC")
+            .unwrap();
+        assert_eq!(btc.balance, dec!(1));
+        assert_eq!(btc.cost_base, Some(dec!(40000)));
+        assert_eq!(btc.value, dec!(50000));
+    }
+
+    #[test]
+    fn test_positions_table_shows_amounts_in_the_portfolio_base() {
+        let dir = TempDir::new().unwrap();
+        let mut settings = (*create_test_settings(dir.path().to_path_buf())).clone();
+        settings.delisted.insert(
+            "ADA".to_string(),
+            Delisted {
+                price: dec!(0.5),
+                currency: "EUR".to_string(),
+            },
+        );
+        let valuation = eur_portfolio(&dir).valuation(&EurQuotes).unwrap();
+
+        let table = positions_table(&valuation, &settings).to_string();
+        for row in [
+            "| BTC    | 1       | 40,000 EUR | 40,000 EUR | 25.00%  |",
+            "| ETH    | 10      | 20,000 EUR | 2,000 EUR  | -50.00% |",
+            "| ADA ☠  | 1000    | 1,000 EUR  | 1 EUR      | -50.00% |",
+        ] {
+            assert!(table.contains(row), "no {} in\n{}", row, table);
+        }
+        assert!(!table.contains("USD"));
+    }
+
+    #[test]
+    fn test_show_portfolio_prints_totals_in_the_portfolio_base() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        let pf = eur_portfolio(&dir);
+        let reporter = Buffer::default();
+        show_portfolio(
+            &pf,
+            &settings.path_for("eur"),
+            &EurQuotes,
+            &reporter,
+            &settings,
+        )
+        .unwrap();
+        let text = reporter.text();
+        assert!(text.contains("Portfolio:\t60_500.0 EUR"), "{}", text);
+        assert!(text.contains("Total PnL:\t-500.0 EUR"), "{}", text);
+        assert!(!text.contains("USD"), "{}", text);
+    }
+}
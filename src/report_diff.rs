@@ -0,0 +1,292 @@
+use crate::math::{self, Percent};
+use crate::portfolio::Portfolio;
+use crate::position_cache;
+use crate::quote::{LiveQuotes, Prices};
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use anyhow::{Context, Result, bail};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use thousands::Separable;
+use time::OffsetDateTime;
+use time::macros::format_description;
+
+/// Label of the state saved by every report run
+pub const LAST: &str = "last";
+
+/// Result of a report run, saved so a later run can show what moved
+///
+/// Stored in `<state dir>/reports/<portfolio>/<label>.json`, `last.json`
+/// is overwritten by every run, other labels only by `report --save`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportState {
+    /// Unix timestamp, seconds
+    pub ts: i64,
+    pub base: String,
+    pub value: Decimal,
+    pub pnl: Decimal,
+    /// Crypto positions by ticker
+    pub positions: BTreeMap<String, PositionState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionState {
+    pub balance: Decimal,
+    pub value: Decimal,
+}
+
+impl ReportState {
+    /// Value the crypto positions of the portfolio at `prices`
+    pub fn from_portfolio(pf: &Portfolio, ts: i64, prices: &dyn Prices) -> Result<Self> {
+        let valuation = pf.valuation(prices)?;
+        let positions = valuation
+            .positions
+            .iter()
+            .map(|p| {
+                let state = PositionState {
+                    balance: p.balance,
+                    value: p.value,
+                };
+                (p.currency.to_string(), state)
+            })
+            .collect();
+        Ok(ReportState {
+            ts,
+            base: pf.base.to_string(),
+            value: valuation.value,
+            pnl: valuation.pnl,
+            positions,
+        })
+    }
+
+    /// Share of the portfolio value in `ticker`
+    pub fn weight(&self, ticker: &str) -> Percent {
+        self.positions
+            .get(ticker)
+            .and_then(|p| math::percent_of(p.value, self.value))
+            .unwrap_or_default()
+    }
+}
+
+/// Change of one position between two report runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionDiff {
+    pub ticker: String,
+    pub balance: Decimal,
+    pub value: Decimal,
+    pub weight_before: Percent,
+    pub weight_after: Percent,
+}
+
+/// Position changes from `before` to `after`, biggest value move first
+pub fn diff(before: &ReportState, after: &ReportState) -> Vec<PositionDiff> {
+    let tickers: BTreeSet<&String> = before
+        .positions
+        .keys()
+        .chain(after.positions.keys())
+        .collect();
+    let zero = PositionState {
+        balance: dec!(0),
+        value: dec!(0),
+    };
+    let mut diffs: Vec<PositionDiff> = tickers
+        .into_iter()
+        .map(|ticker| {
+            let b = before.positions.get(ticker).unwrap_or(&zero);
+            let a = after.positions.get(ticker).unwrap_or(&zero);
+            PositionDiff {
+                ticker: ticker.clone(),
+                balance: a.balance - b.balance,
+                value: a.value - b.value,
+                weight_before: before.weight(ticker),
+                weight_after: after.weight(ticker),
+            }
+        })
+        .collect();
+    diffs.sort_by_key(|d| std::cmp::Reverse(d.value.abs()));
+    diffs
+}
+
+fn state_path(name: &str, label: &str, settings: &Settings) -> PathBuf {
+    settings
+        .state_dir()
+        .join("reports")
+        .join(name)
+        .join(format!("{}.json", label))
+}
+
+pub fn save(name: &str, label: &str, state: &ReportState, settings: &Settings) -> Result<()> {
+    let path = state_path(name, label, settings);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(&path, serde_json::to_vec_pretty(state)?)
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(())
+}
+
+/// Saved state of a report run, `None` when there is none with this label
+pub fn load(name: &str, label: &str, settings: &Settings) -> Result<Option<ReportState>> {
+    let path = state_path(name, label, settings);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = std::fs::read(&path)?;
+    let state =
+        serde_json::from_slice(&data).with_context(|| format!("parsing {}", path.display()))?;
+    Ok(Some(state))
+}
+
+fn signed(d: Decimal) -> String {
+    let d = d.round_dp(2).normalize();
+    if d > dec!(0) {
+        format!("+{}", d.separate_with_commas())
+    } else {
+        d.separate_with_commas()
+    }
+}
+
+fn print_diff(
+    label: &str,
+    before: &ReportState,
+    after: &ReportState,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let format = format_description!("[year]-[month]-[day] [hour]:[minute]");
+    let since = OffsetDateTime::from_unix_timestamp(before.ts)?.format(format)?;
+    reporter.print("");
+    reporter.print(&format!("Changes since {} report ({}):", label, since));
+
+    let mut table = Table::new();
+    table.add_row(row!["", "Ticker", "Balance", "Value", "Weight"]);
+    for d in diff(before, after) {
+        let mark = match d.value.cmp(&dec!(0)) {
+            std::cmp::Ordering::Greater => "▲",
+            std::cmp::Ordering::Less => "▼",
+            std::cmp::Ordering::Equal => "",
+        };
+        table.add_row(row![
+            mark,
+            d.ticker,
+            r->signed(d.balance),
+            r->format!("{} {}", signed(d.value), after.base),
+            r->format!(
+                "{} → {}",
+                d.weight_before.round_dp(1),
+                d.weight_after.round_dp(1)
+            )
+        ]);
+    }
+    reporter.print(table.to_string().trim_end());
+
+    let pct = math::change_pct(before.value, after.value)
+        .map_or(String::new(), |p| format!(" ({}%)", signed(p.value())));
+    reporter.print(&format!(
+        "Value:\t\t{} {}{}",
+        signed(after.value - before.value),
+        after.base,
+        pct
+    ));
+    reporter.print(&format!(
+        "PnL:\t\t{} {}",
+        signed(after.pnl - before.pnl),
+        after.base
+    ));
+    Ok(())
+}
+
+/// Save the state of this report run; with `diff_label`, first print what
+/// changed since the run saved under that label
+pub fn after_report(
+    name: &str,
+    diff_label: Option<&str>,
+    save_label: Option<&str>,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let pf = position_cache::load(settings.path_for(name), settings)?;
+    let now = settings.clock.now().unix_timestamp();
+    let state = ReportState::from_portfolio(&pf, now, &LiveQuotes)?;
+
+    if let Some(label) = diff_label {
+        match load(name, label, settings)? {
+            Some(before) => print_diff(label, &before, &state, reporter)?,
+            None if label == LAST => reporter.print("\nNo previous report to compare with"),
+            None => bail!("no saved report '{}' for portfolio '{}'", label, name),
+        }
+    }
+
+    save(name, LAST, &state, settings)?;
+    if let Some(label) = save_label {
+        save(name, label, &state, settings)?;
+        reporter.print(&format!("Saved report as '{}'", label));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::create_test_settings;
+    use tempfile::TempDir;
+
+    fn state(ts: i64, positions: &[(&str, Decimal, Decimal)]) -> ReportState {
+        let positions: BTreeMap<String, PositionState> = positions
+            .iter()
+            .map(|(t, balance, value)| {
+                (
+                    t.to_string(),
+                    PositionState {
+                        balance: *balance,
+                        value: *value,
+                    },
+                )
+            })
+            .collect();
+        ReportState {
+            ts,
+            base: "USD".to_string(),
+            value: positions.values().map(|p| p.value).sum(),
+            pnl: dec!(0),
+            positions,
+        }
+    }
+
+    #[test]
+    fn test_diff_orders_by_biggest_move_and_tracks_weights() {
+        let before = state(
+            1,
+            &[
+                ("BTC", dec!(1), dec!(60)),
+                ("ETH", dec!(10), dec!(40)),
+                ("SOL", dec!(5), dec!(0.5)),
+            ],
+        );
+        let after = state(2, &[("BTC", dec!(1), dec!(90)), ("ETH", dec!(5), dec!(10))]);
+
+        let diffs = diff(&before, &after);
+        let moved: Vec<(&str, Decimal)> =
+            diffs.iter().map(|d| (d.ticker.as_str(), d.value)).collect();
+        assert_eq!(
+            moved,
+            vec![("BTC", dec!(30)), ("ETH", dec!(-30)), ("SOL", dec!(-0.5))]
+        );
+        assert_eq!(diffs[1].balance, dec!(-5));
+        assert_eq!(diffs[0].weight_after, Percent::new(dec!(90)));
+        assert_eq!(diffs[2].weight_after, Percent::ZERO);
+    }
+
+    #[test]
+    fn test_save_then_load_by_label() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        let s = state(1704883200, &[("BTC", dec!(1), dec!(40000))]);
+
+        assert_eq!(load("pf", LAST, &settings).unwrap(), None);
+        save("pf", "q1", &s, &settings).unwrap();
+        assert_eq!(load("pf", "q1", &settings).unwrap(), Some(s));
+        assert!(dir.path().join(".csvpt/reports/pf/q1.json").is_file());
+    }
+}
@@ -0,0 +1,190 @@
+//! Where commands send what they print
+//!
+//! Commands hand their rows, result lines and confirmations to a `Reporter`
+//! picked per invocation (`--output`, `--quiet`), so the same command can
+//! print for people, print JSON or CSV only, stay silent, or be captured by
+//! tests and library users.
+
+use crate::format::{OutputFormat, Records, iso_utc};
+use anyhow::Result;
+use clap::ValueEnum;
+use prettytable::Table;
+use std::cell::RefCell;
+use time::OffsetDateTime;
+
+/// Reporter of an invocation, see `--output`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputMode {
+    /// Rows in the command's --format, with result and status lines
+    #[default]
+    #[value(alias = "terminal")]
+    Table,
+    /// Rows as JSON only, every other line goes to stderr
+    Json,
+    /// Rows as CSV only, every other line goes to stderr
+    Csv,
+    /// Nothing, only the exit code tells how it went
+    Silent,
+}
+
+pub trait Reporter {
+    /// Format rows asked in `requested` are printed in
+    fn format(&self, requested: OutputFormat) -> OutputFormat {
+        requested
+    }
+    /// Rows of a result, in `self.format(format)`
+    fn records(
+        &self,
+        records: &Records,
+        format: OutputFormat,
+        display_time: &dyn Fn(OffsetDateTime) -> String,
+    ) -> Result<()>;
+    /// Rows of a result built as a terminal table, its first row naming the
+    /// columns; machine reporters print them as records
+    fn table(&self, table: &Table) -> Result<()> {
+        self.records(&Records::from(table), OutputFormat::Table, &iso_utc)
+    }
+    /// A line of a result, like `No trades found`
+    fn print(&self, line: &str);
+    /// What a command did, like `Created trades file: ...`, left out by `--quiet`
+    fn status(&self, line: &str);
+}
+
+/// Reporter for `mode`, without status lines when `quiet`
+pub fn for_mode(mode: OutputMode, quiet: bool) -> Box<dyn Reporter> {
+    match mode {
+        OutputMode::Table => Box::new(Terminal { quiet }),
+        OutputMode::Json => Box::new(Machine {
+            format: OutputFormat::Json,
+            quiet,
+        }),
+        OutputMode::Csv => Box::new(Machine {
+            format: OutputFormat::Csv,
+            quiet,
+        }),
+        OutputMode::Silent => Box::new(Silent),
+    }
+}
+
+/// Everything to stdout
+#[derive(Debug, Default)]
+pub struct Terminal {
+    pub quiet: bool,
+}
+
+impl Reporter for Terminal {
+    fn records(
+        &self,
+        records: &Records,
+        format: OutputFormat,
+        display_time: &dyn Fn(OffsetDateTime) -> String,
+    ) -> Result<()> {
+        records.print(format, display_time)
+    }
+
+    /// As built, keeping its alignment
+    fn table(&self, table: &Table) -> Result<()> {
+        print!("{}", table);
+        Ok(())
+    }
+
+    fn print(&self, line: &str) {
+        println!("{}", line);
+    }
+
+    fn status(&self, line: &str) {
+        if !self.quiet {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Rows in a machine format on stdout, lines on stderr so stdout always parses
+#[derive(Debug, Default)]
+pub struct Machine {
+    pub format: OutputFormat,
+    pub quiet: bool,
+}
+
+impl Reporter for Machine {
+    fn format(&self, _: OutputFormat) -> OutputFormat {
+        self.format
+    }
+
+    fn records(
+        &self,
+        records: &Records,
+        _: OutputFormat,
+        display_time: &dyn Fn(OffsetDateTime) -> String,
+    ) -> Result<()> {
+        records.print(self.format, display_time)
+    }
+
+    fn print(&self, line: &str) {
+        eprintln!("{}", line);
+    }
+
+    fn status(&self, line: &str) {
+        if !self.quiet {
+            eprintln!("{}", line);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Silent;
+
+impl Reporter for Silent {
+    fn records(
+        &self,
+        _: &Records,
+        _: OutputFormat,
+        _: &dyn Fn(OffsetDateTime) -> String,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn print(&self, _: &str) {}
+
+    fn status(&self, _: &str) {}
+}
+
+/// Keeps everything as a terminal would show it, for tests and library users
+#[derive(Debug, Default)]
+pub struct Buffer {
+    text: RefCell<String>,
+}
+
+impl Buffer {
+    pub fn text(&self) -> String {
+        self.text.borrow().clone()
+    }
+}
+
+impl Reporter for Buffer {
+    fn records(
+        &self,
+        records: &Records,
+        format: OutputFormat,
+        display_time: &dyn Fn(OffsetDateTime) -> String,
+    ) -> Result<()> {
+        let rendered = records.render(format, display_time)?;
+        self.text.borrow_mut().push_str(&rendered);
+        Ok(())
+    }
+
+    fn table(&self, table: &Table) -> Result<()> {
+        self.text.borrow_mut().push_str(&table.to_string());
+        Ok(())
+    }
+
+    fn print(&self, line: &str) {
+        let mut text = self.text.borrow_mut();
+        text.push_str(line);
+        text.push('\n');
+    }
+
+    fn status(&self, line: &str) {
+        self.print(line);
+    }
+}
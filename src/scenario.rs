@@ -0,0 +1,407 @@
+use crate::currency::{Currency, CurrencyType};
+use crate::math;
+use crate::portfolio::Portfolio;
+use crate::quote::quote_in_base;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::warnings;
+use anyhow::{Context, Result, anyhow, bail};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use rust_decimal::prelude::ToPrimitive;
+use std::collections::HashMap;
+use std::path::Path;
+use thousands::Separable;
+
+/// Hypothetical price move of one asset, in percent (e.g. -30)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shock {
+    pub currency: Currency,
+    pub percent: Decimal,
+}
+
+fn parse_percent(s: &str) -> Result<Decimal> {
+    let pct: Decimal = s
+        .trim()
+        .trim_end_matches('%')
+        .parse()
+        .with_context(|| format!("invalid shock '{}', expecting e.g. -30%", s))?;
+    if pct <= dec!(-100) {
+        bail!("shock {}% would make the price negative", pct);
+    }
+    Ok(pct)
+}
+
+/// Parse shocks given as `--btc -30% --eth -40%` or `btc=-30%`
+pub fn parse_shocks(args: &[String]) -> Result<Vec<Shock>> {
+    let mut shocks = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let (ticker, pct) = match arg.split_once('=') {
+            Some((ticker, pct)) => (ticker, pct.to_string()),
+            None => {
+                let pct = iter
+                    .next()
+                    .ok_or(anyhow!("missing percent after '{}'", arg))?;
+                (arg.as_str(), pct.clone())
+            }
+        };
+        shocks.push(Shock {
+            currency: Currency::new(ticker.trim_start_matches('-'))?,
+            percent: parse_percent(&pct)?,
+        });
+    }
+    if shocks.is_empty() {
+        bail!("no shocks given, e.g. --btc -30%");
+    }
+    Ok(shocks)
+}
+
+/// Shocks of a scenario saved in the config file, matched case-insensitively
+///
+/// ```toml
+/// [scenarios.bear2026]
+/// BTC = -60
+/// ETH = -75
+/// ```
+pub fn saved(name: &str, settings: &Settings) -> Result<Vec<Shock>> {
+    let Some((_, shocks)) = settings
+        .scenarios
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+    else {
+        bail!(
+            "unknown scenario '{}', save it with `scenarios save {} --btc -30%`",
+            name,
+            name
+        );
+    };
+    let mut parsed = Vec::new();
+    for (ticker, percent) in shocks {
+        parsed.push(Shock {
+            currency: Currency::new(ticker)?,
+            percent: parse_percent(&percent.to_string())
+                .with_context(|| format!("in scenario '{}'", name))?,
+        });
+    }
+    if parsed.is_empty() {
+        bail!("scenario '{}' has no shocks", name);
+    }
+    Ok(parsed)
+}
+
+/// `BTC -60%, ETH -75%`
+pub fn describe(shocks: &[Shock]) -> String {
+    shocks
+        .iter()
+        .map(|s| format!("{} {:+}%", s.currency, s.percent.normalize()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn toml_number(d: Decimal) -> toml::Value {
+    match d.fract().is_zero().then(|| d.to_i64()).flatten() {
+        Some(i) => toml::Value::Integer(i),
+        None => toml::Value::Float(d.to_f64().unwrap_or_default()),
+    }
+}
+
+fn read_config(path: &Path) -> Result<toml::Table> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    content
+        .parse()
+        .with_context(|| format!("parsing {}", path.display()))
+}
+
+fn write_config(path: &Path, config: &toml::Table) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, toml::to_string(config)?)
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// Save `shocks` as scenario `name` in the config file at `path`, replacing
+/// a scenario of that name
+pub fn save(path: &Path, name: &str, shocks: &[Shock], reporter: &dyn Reporter) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        bail!(
+            "invalid scenario name '{}', use letters, digits, _ and -",
+            name
+        );
+    }
+    let mut config = read_config(path)?;
+    let scenarios = config
+        .entry("scenarios")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or(anyhow!("'scenarios' in {} isn't a table", path.display()))?;
+    let table: toml::Table = shocks
+        .iter()
+        .map(|s| (s.currency.to_string(), toml_number(s.percent)))
+        .collect();
+    scenarios.insert(name.to_string(), toml::Value::Table(table));
+    write_config(path, &config)?;
+    reporter.status(&format!(
+        "💾 Saved scenario '{}': {}",
+        name,
+        describe(shocks)
+    ));
+    Ok(())
+}
+
+/// Remove scenario `name` from the config file at `path`
+pub fn remove(path: &Path, name: &str, reporter: &dyn Reporter) -> Result<()> {
+    let mut config = read_config(path)?;
+    let removed = config
+        .get_mut("scenarios")
+        .and_then(|s| s.as_table_mut())
+        .and_then(|s| s.remove(name));
+    if removed.is_none() {
+        bail!("no scenario '{}' in {}", name, path.display());
+    }
+    write_config(path, &config)?;
+    reporter.status(&format!("Removed scenario '{}'", name));
+    Ok(())
+}
+
+pub fn print_list(reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    if settings.scenarios.is_empty() {
+        reporter.print("No saved scenarios");
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.add_row(row!["Scenario", "Shocks"]);
+    for name in settings.scenarios.keys() {
+        table.add_row(row![name, describe(&saved(name, settings)?)]);
+    }
+    reporter.table(&table)?;
+    Ok(())
+}
+
+/// One crypto position valued at current and shocked prices
+#[derive(Debug, PartialEq)]
+pub struct ScenarioLine {
+    pub currency: Currency,
+    pub balance: Decimal,
+    pub cost_base: Decimal,
+    pub price: Decimal,
+    pub shock: Decimal,
+    pub shocked_price: Decimal,
+}
+
+impl ScenarioLine {
+    pub fn value(&self) -> Decimal {
+        self.balance * self.price
+    }
+
+    pub fn shocked_value(&self) -> Decimal {
+        self.balance * self.shocked_price
+    }
+}
+
+/// Value the crypto positions at `prices`, and again with the shocks applied
+pub fn evaluate(
+    pf: &Portfolio,
+    prices: &HashMap<Currency, Decimal>,
+    shocks: &[Shock],
+) -> Result<Vec<ScenarioLine>> {
+    let mut lines = Vec::new();
+    for (currency, position) in pf.positions.iter() {
+        if currency.currency_type() != CurrencyType::Crypto {
+            continue;
+        }
+        let price = *prices
+            .get(currency)
+            .ok_or(anyhow!("missing price for {}", currency))?;
+        let shock = shocks
+            .iter()
+            .find(|s| &s.currency == currency)
+            .map(|s| s.percent)
+            .unwrap_or_default();
+        lines.push(ScenarioLine {
+            currency: currency.clone(),
+            balance: position.balance,
+            cost_base: position.cost_base,
+            price,
+            shock,
+            shocked_price: price * (dec!(100) + shock) / dec!(100),
+        });
+    }
+    lines.sort_by_key(|l| std::cmp::Reverse(l.value()));
+    Ok(lines)
+}
+
+/// Print the base case next to the shocked scenario, with the shocks given
+/// as arguments or saved under `saved`
+pub fn run(
+    name: &str,
+    shock_args: &[String],
+    saved_name: Option<&str>,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let shocks = match saved_name {
+        Some(saved_name) => {
+            let shocks = saved(saved_name, settings)?;
+            reporter.print(&format!("Scenario {}: {}", saved_name, describe(&shocks)));
+            shocks
+        }
+        None => parse_shocks(shock_args)?,
+    };
+    let pf = Portfolio::from_csv(settings.path_for(name))?;
+
+    for shock in &shocks {
+        if !pf.positions.contains_key(&shock.currency) {
+            warnings::warn(format!(
+                "no {} position in '{}', shock ignored",
+                shock.currency, name
+            ));
+        }
+    }
+
+    let mut prices = HashMap::new();
+    for currency in pf.positions.keys() {
+        if currency.currency_type() == CurrencyType::Crypto {
+            prices.insert(currency.clone(), quote_in_base(currency, pf.base.ticker())?);
+        }
+    }
+    let lines = evaluate(&pf, &prices, &shocks)?;
+    print_lines(&lines, &pf.base, reporter)
+}
+
+fn print_lines(lines: &[ScenarioLine], base: &Currency, reporter: &dyn Reporter) -> Result<()> {
+    let money = |d: Decimal| format!("{} {}", d.round_dp(2).separate_with_commas(), base);
+
+    let mut table = Table::new();
+    table.add_row(row![
+        "Ticker",
+        "Balance",
+        "Price",
+        "Shock",
+        "Value",
+        "Scenario value"
+    ]);
+    for l in lines {
+        table.add_row(row![
+            l.currency,
+            l.balance.round_dp(8),
+            money(l.price),
+            format!("{:+}%", l.shock),
+            money(l.value()),
+            money(l.shocked_value())
+        ]);
+    }
+    reporter.table(&table)?;
+
+    let cost: Decimal = lines.iter().map(|l| l.cost_base).sum();
+    let value: Decimal = lines.iter().map(|l| l.value()).sum();
+    let shocked: Decimal = lines.iter().map(|l| l.shocked_value()).sum();
+    let pct = |v: Decimal| math::change_pct(cost, v).unwrap_or_default().round_dp(2);
+
+    reporter.print("=================================");
+    reporter.print("\t\tBase case\tScenario");
+    reporter.print(&format!("Portfolio:\t{}\t{}", money(value), money(shocked)));
+    reporter.print(&format!(
+        "PnL:\t\t{}\t{}",
+        money(value - cost),
+        money(shocked - cost)
+    ));
+    reporter.print(&format!("PnL %:\t\t{}\t\t{}", pct(value), pct(shocked)));
+    reporter.print("=================================");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::Silent;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_shocks_accepts_flags_and_assignments() {
+        let shocks = parse_shocks(&args("--btc -30% eth=-40 --sol 15%")).unwrap();
+        let parsed: Vec<(&str, Decimal)> = shocks
+            .iter()
+            .map(|s| (s.currency.ticker(), s.percent))
+            .collect();
+        assert_eq!(
+            parsed,
+            vec![("BTC", dec!(-30)), ("ETH", dec!(-40)), ("SOL", dec!(15))]
+        );
+    }
+
+    #[test]
+    fn test_parse_shocks_rejects_bad_input() {
+        assert!(parse_shocks(&[]).is_err());
+        assert!(parse_shocks(&args("--btc")).is_err());
+        assert!(parse_shocks(&args("--btc lots")).is_err());
+        assert!(parse_shocks(&args("--btc -100%")).is_err());
+    }
+
+    #[test]
+    fn test_saved_scenarios_round_trip_through_config_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "base_currency = \"EUR\"\n").unwrap();
+
+        save(
+            &path,
+            "bear2026",
+            &parse_shocks(&args("--btc -60% --eth -75.5%")).unwrap(),
+            &Silent,
+        )
+        .unwrap();
+        save(
+            &path,
+            "bull",
+            &parse_shocks(&args("--btc 50%")).unwrap(),
+            &Silent,
+        )
+        .unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("base_currency = \"EUR\""));
+        let settings: Settings = toml::from_str(&content).unwrap();
+        assert_eq!(
+            describe(&saved("BEAR2026", &settings).unwrap()),
+            "BTC -60%, ETH -75.5%"
+        );
+
+        remove(&path, "bull", &Silent).unwrap();
+        assert!(remove(&path, "bull", &Silent).is_err());
+        let settings: Settings = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(saved("bull", &settings).is_err());
+        assert!(save(&path, "no spaces", &[], &Silent).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_applies_shocks_per_asset() {
+        let btc = Currency::new("BTC").unwrap();
+        let eth = Currency::new("ETH").unwrap();
+        let mut pf = Portfolio::new();
+        pf.deposit_at_cost(btc.clone(), dec!(1), dec!(40000));
+        pf.deposit_at_cost(eth.clone(), dec!(10), dec!(20000));
+        pf.deposit_at_cost(Currency::new("USD").unwrap(), dec!(5), dec!(5));
+        let prices = HashMap::from([(btc.clone(), dec!(50000)), (eth, dec!(3000))]);
+
+        let lines = evaluate(&pf, &prices, &parse_shocks(&args("--btc -30%")).unwrap()).unwrap();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].currency, btc);
+        assert_eq!(lines[0].shocked_value(), dec!(35000));
+        // unshocked assets keep their price
+        assert_eq!(lines[1].shocked_value(), dec!(30000));
+    }
+}
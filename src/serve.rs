@@ -0,0 +1,214 @@
+//! Local quote proxy
+//!
+//! `serve` answers `GET /prices?tickers=BTC,ETH` on localhost with the
+//! current quotes as JSON, so status bars and spreadsheets reuse the quotes
+//! the tracker already fetched instead of calling the providers themselves.
+//! Requests are answered one at a time from the quote cache, so however
+//! often they come, each quote currency is fetched at most once per
+//! `--cache-secs`. Nothing but prices is served and only on 127.0.0.1.
+
+use crate::currency::Currency;
+use crate::interrupt;
+use crate::quote::{self, LiveQuotes, Prices};
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::warnings;
+use anyhow::{Context, Result};
+use rust_decimal::prelude::ToPrimitive;
+use serde_json::{Value, json};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+/// Status and JSON body answering the request line `request`, e.g.
+/// `GET /prices?tickers=BTC,ETH&base=EUR HTTP/1.1`
+pub fn respond(request: &str, default_base: &str, prices: &dyn Prices) -> (u16, Value) {
+    let mut parts = request.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    if method != "GET" {
+        return (405, json!({"error": "only GET is supported"}));
+    }
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path != "/prices" {
+        return (
+            404,
+            json!({"error": "not found, try /prices?tickers=BTC,ETH"}),
+        );
+    }
+
+    let mut tickers = "";
+    let mut base = default_base;
+    for (key, value) in query.split('&').filter_map(|p| p.split_once('=')) {
+        match key {
+            "tickers" => tickers = value,
+            "base" => base = value,
+            _ => {}
+        }
+    }
+    let tickers = tickers.replace("%2C", ",").replace("%2c", ",");
+    if tickers.trim().is_empty() {
+        return (
+            400,
+            json!({"error": "missing tickers, e.g. ?tickers=BTC,ETH"}),
+        );
+    }
+    let base = match Currency::new(base) {
+        Ok(base) => base,
+        Err(e) => return (400, json!({"error": e.to_string()})),
+    };
+
+    let mut quotes = serde_json::Map::new();
+    for ticker in tickers.split(',').filter(|t| !t.trim().is_empty()) {
+        let currency = match Currency::new(ticker) {
+            Ok(currency) => currency,
+            Err(e) => return (400, json!({"error": e.to_string()})),
+        };
+        match prices.price_in(&currency, base.ticker()) {
+            Ok(price) => {
+                quotes.insert(currency.to_string(), json!(price.to_f64()));
+            }
+            Err(e) => return (502, json!({"error": e.to_string()})),
+        }
+    }
+    (200, json!({"base": base.to_string(), "prices": quotes}))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Bad Gateway",
+    }
+}
+
+/// Answer one connection, returning its request line and status
+fn handle(stream: TcpStream, default_base: &str, prices: &dyn Prices) -> Result<(String, u16)> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // the headers aren't needed, but are read so the client sees a clean close
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let request = request.trim().to_string();
+    let (status, body) = respond(&request, default_base, prices);
+    let body = body.to_string();
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok((request, status))
+}
+
+/// `serve`: answer price requests on 127.0.0.1:`port` until Ctrl-C
+pub fn run(port: u16, cache_secs: u64, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .with_context(|| format!("listening on 127.0.0.1:{}", port))?;
+    // polled, so Ctrl-C is noticed between requests
+    listener.set_nonblocking(true)?;
+    quote::keep_quotes_for(Duration::from_secs(cache_secs));
+    let base = settings.base_currency.to_string();
+    reporter.print(&format!(
+        "Serving prices on http://{}/prices?tickers=BTC,ETH, Ctrl-C to stop",
+        listener.local_addr()?
+    ));
+
+    while !interrupt::interrupted() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                match handle(stream, &base, &LiveQuotes) {
+                    Ok((request, status)) => reporter.print(&format!("{} {}", status, request)),
+                    Err(e) => warnings::warn(format!("request failed: {}", e)),
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => warnings::warn(format!("accepting a connection failed: {}", e)),
+        }
+    }
+    reporter.print("Stopped serving");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use rust_decimal::dec;
+    use std::collections::HashMap;
+    use std::io::Read;
+
+    #[test]
+    fn test_prices_endpoint_answers_json_and_rejects_other_requests() {
+        let prices: HashMap<String, Decimal> = [
+            ("BTC".to_string(), dec!(50000.5)),
+            ("ETH".to_string(), dec!(3000)),
+        ]
+        .into();
+
+        let (status, body) = respond("GET /prices?tickers=btc%2CETH HTTP/1.1", "USD", &prices);
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            json!({"base": "USD", "prices": {"BTC": 50000.5, "ETH": 3000.0}})
+        );
+        let (_, body) = respond(
+            "GET /prices?base=usdc&tickers=USDC HTTP/1.1",
+            "USD",
+            &prices,
+        );
+        assert_eq!(body["prices"]["USDC"], json!(1.0));
+
+        assert_eq!(respond("GET /prices HTTP/1.1", "USD", &prices).0, 400);
+        assert_eq!(
+            respond("GET /prices?tickers=NOPE HTTP/1.1", "USD", &prices).0,
+            400
+        );
+        assert_eq!(
+            respond("GET /prices?tickers=SOL HTTP/1.1", "USD", &prices).0,
+            502
+        );
+        assert_eq!(respond("GET /portfolios HTTP/1.1", "USD", &prices).0, 404);
+        assert_eq!(
+            respond("POST /prices?tickers=BTC HTTP/1.1", "USD", &prices).0,
+            405
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .write_all(b"GET /prices?tickers=ETH HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let (request, status) = handle(stream, "USD", &prices).unwrap();
+        assert_eq!(
+            (request.as_str(), status),
+            ("GET /prices?tickers=ETH HTTP/1.1", 200)
+        );
+        let response = client.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(
+            response.ends_with(r#"{"base":"USD","prices":{"ETH":3000.0}}"#),
+            "{response}"
+        );
+    }
+}
@@ -1,27 +1,359 @@
-use crate::{cli::Cli, currency::Currency};
-use anyhow::{Context, Result};
+use crate::{
+    cli::Cli,
+    clock::Clock,
+    config_check,
+    currency::Currency,
+    dca::DcaPlan,
+    fees::FeeTier,
+    http,
+    lint::LintConfig,
+    quote::{self, Delisted},
+    reporter,
+    snapshot::Retention,
+    tx_template, warnings,
+};
+use anyhow::{Context, Result, bail};
 use config::Config;
+use rust_decimal::{Decimal, RoundingStrategy, dec};
 use serde::{Deserialize, Serialize};
 use shellexpand::tilde;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Settings {
     #[serde(default = "default_portfolio_dir")]
     pub portfolio_dir: PathBuf,
+    /// More directories `list` and `report --all` read portfolios from, e.g.
+    /// separately synced personal and business records
+    #[serde(default)]
+    pub portfolio_dirs: Vec<PathBuf>,
+    /// Portfolio name patterns (globs) `list` and `report --all` skip unless
+    /// `--include-ignored` is passed, e.g. `["scratch*", "*_test"]`
+    #[serde(default)]
+    pub ignore_patterns: Vec<String>,
     #[serde(default)]
     pub base_currency: Currency,
+    /// One or more comma separated providers, see `quote::parse_providers`
+    #[serde(default = "default_quote_provider")]
+    pub quote_provider: String,
+    /// Warn when configured quote providers disagree by more than this percentage
+    #[serde(default = "default_quote_discrepancy_pct")]
+    pub quote_discrepancy_pct: Decimal,
+    #[serde(default)]
+    pub cost_basis: CostBasisMethod,
+    /// How `tax` and `report` round money amounts to cents
+    #[serde(default)]
+    pub rounding: Rounding,
+    /// Positions worth less than this, in the base currency, are listed by `dust`
+    #[serde(default = "default_dust_threshold")]
+    pub dust_threshold: Decimal,
+    /// Timeout for quote provider requests, in seconds
+    #[serde(default = "default_http_timeout_secs")]
+    pub http_timeout_secs: u64,
+    /// First day of the week for weekly groupings
+    #[serde(default)]
+    pub week_starts_on: WeekStart,
+    /// Month (1-12) the fiscal year starts in, for yearly groupings and `tax --year`
+    #[serde(default = "default_fiscal_year_start_month")]
+    pub fiscal_year_start_month: u8,
+    /// Warn about sells of lots held for fewer days than this, off when unset
+    #[serde(default)]
+    pub short_term_days: Option<u32>,
+    /// Composite benchmarks for `report --benchmark`, weights in percent by ticker
+    #[serde(default)]
+    pub benchmarks: BTreeMap<String, BTreeMap<String, Decimal>>,
+    /// Saved scenarios for `scenario --saved`, price shocks in percent by ticker
+    #[serde(default)]
+    pub scenarios: BTreeMap<String, BTreeMap<String, Decimal>>,
+    /// Dollar-cost averaging schedules for `dca due` and `export --format ics`, by name
+    #[serde(default)]
+    pub dca: BTreeMap<String, DcaPlan>,
+    /// Exchange fee tiers for `stats --fee-check`, by the exchange of
+    /// `import:<exchange>` sources, `default` for all other trades
+    #[serde(default)]
+    pub fee_tiers: BTreeMap<String, FeeTier>,
+    /// Fees further than this percentage off their tier are flagged by `stats --fee-check`
+    #[serde(default = "default_fee_check_tolerance_pct")]
+    pub fee_check_tolerance_pct: Decimal,
+    /// Directory `inbox` imports dropped exports from
+    #[serde(default)]
+    pub inbox_dir: Option<PathBuf>,
+    /// Inbox file name patterns (globs, case-insensitive) to the portfolio they go to
+    #[serde(default)]
+    pub inbox_rules: BTreeMap<String, String>,
+    /// Named `add-tx` options, e.g. `weekly_btc = "--ticker BTC/EUR --side BUY"`,
+    /// used by `add-tx --template`
+    #[serde(default)]
+    pub templates: BTreeMap<String, String>,
+    /// Prices below this are shown with `price_sig_figs` significant figures
+    /// instead of 2 decimals, so e.g. PEPE doesn't show as 0.00
+    #[serde(default = "default_small_price_below")]
+    pub small_price_below: Decimal,
+    #[serde(default = "default_price_sig_figs")]
+    pub price_sig_figs: u32,
+    /// Largest difference, in the quote currency, between the `total` column
+    /// of an imported buy or sell and its amount × price ± fee before the
+    /// row is flagged by `import`
+    #[serde(default = "default_import_total_tolerance")]
+    pub import_total_tolerance: Decimal,
+    /// Batch operations rewriting or deleting more rows or files than this
+    /// (merge-conflict, import, import-workspace --force) ask first, or need --yes
+    #[serde(default = "default_confirm_threshold")]
+    pub confirm_threshold: usize,
+    /// Final prices of assets the quote providers no longer quote, by ticker
+    #[serde(default)]
+    pub delisted: BTreeMap<String, Delisted>,
+    /// Severities of the lint rules of `doctor`, and whether `add-tx` runs them
+    #[serde(default)]
+    pub lint: LintConfig,
+    /// CoinGecko ids of symbols several coins share, by ticker, e.g.
+    /// `SOL = "solana"`; others resolve to the coin with the largest market cap
+    #[serde(default)]
+    pub symbol_overrides: BTreeMap<String, String>,
+    /// Resolution `snapshot compact` keeps snapshots at, by age
+    #[serde(default)]
+    pub snapshot_retention: Retention,
+    /// Minutes before an alert that keeps firing notifies again in `watch`,
+    /// unless the alert has its own `--cooldown`
+    #[serde(default = "default_alert_cooldown_mins")]
+    pub alert_cooldown_mins: u64,
+    /// Count command runs and durations in `<state dir>/usage.json`, see
+    /// `info --stats`; kept locally, never sent anywhere
+    #[serde(default)]
+    pub usage_stats: bool,
+    /// Source of the current time, frozen by `--now` or `CSVPT_FAKE_NOW`
+    #[serde(skip)]
+    pub clock: Clock,
+    /// Quote file of `--fixture-prices`, also the source of daily prices
+    #[serde(skip)]
+    pub fixture_prices: Option<PathBuf>,
+    /// Don't reuse cached quotes and supply data, set by `--no-cache`
+    #[serde(skip)]
+    pub no_cache: bool,
+}
+
+/// Day weeks start on, by locale convention
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    #[default]
+    Monday,
+    Saturday,
+    Sunday,
+}
+
+impl WeekStart {
+    pub fn weekday(self) -> time::Weekday {
+        match self {
+            WeekStart::Monday => time::Weekday::Monday,
+            WeekStart::Saturday => time::Weekday::Saturday,
+            WeekStart::Sunday => time::Weekday::Sunday,
+        }
+    }
+}
+
+/// How the cost of sold units is determined
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CostBasisMethod {
+    /// Weighted average cost of all units held
+    #[default]
+    Average,
+}
+
+/// Rounding of money amounts to cents, as tax authorities require
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rounding {
+    /// Ties to the even cent, 0.125 → 0.12 (banker's rounding)
+    #[default]
+    HalfEven,
+    /// Ties away from zero, 0.125 → 0.13
+    HalfUp,
+}
+
+impl Rounding {
+    pub fn cents(self, d: Decimal) -> Decimal {
+        let strategy = match self {
+            Rounding::HalfEven => RoundingStrategy::MidpointNearestEven,
+            Rounding::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+        };
+        d.round_dp_with_strategy(2, strategy)
+    }
 }
 
 fn default_portfolio_dir() -> PathBuf {
     PathBuf::from("./portfolios")
 }
 
+fn default_quote_discrepancy_pct() -> Decimal {
+    dec!(2)
+}
+
+fn default_dust_threshold() -> Decimal {
+    dec!(10)
+}
+
+fn default_fee_check_tolerance_pct() -> Decimal {
+    dec!(50)
+}
+
+fn default_http_timeout_secs() -> u64 {
+    http::DEFAULT_TIMEOUT_SECS
+}
+
+fn default_alert_cooldown_mins() -> u64 {
+    60
+}
+
+fn default_fiscal_year_start_month() -> u8 {
+    1
+}
+
+fn default_small_price_below() -> Decimal {
+    dec!(1)
+}
+
+fn default_price_sig_figs() -> u32 {
+    8
+}
+
+fn default_import_total_tolerance() -> Decimal {
+    dec!(0.01)
+}
+
+fn default_confirm_threshold() -> usize {
+    100
+}
+
+fn default_quote_provider() -> String {
+    "coingecko".to_string()
+}
+
+/// Location of the optional user dotfile
+pub fn config_path() -> PathBuf {
+    PathBuf::from(tilde("~/.local/share/csvpt/config.toml").to_string())
+}
+
+/// Describe the configuration layers `Settings::load` will merge, lowest priority first
+pub fn config_layers(cli: &Cli) -> Vec<String> {
+    let mut layers = vec!["defaults".to_string()];
+
+    let dotfile_path = config_path();
+    if std::fs::exists(&dotfile_path).unwrap_or(false) {
+        layers.push(format!("dotfile {}", dotfile_path.display()));
+    }
+
+    let mut env_vars: Vec<String> = std::env::vars()
+        .map(|(k, _)| k)
+        .filter(|k| k.starts_with("LPT_"))
+        .collect();
+    if !env_vars.is_empty() {
+        env_vars.sort();
+        layers.push(format!("env {}", env_vars.join(", ")));
+    }
+
+    if cli.portfolio_dir.is_some() {
+        layers.push("cli --portfolio-dir".to_string());
+    }
+    if cli.fixture_prices.is_some() {
+        layers.push("cli --fixture-prices".to_string());
+    }
+
+    layers
+}
+
+/// Layer of `Settings::load` the effective value of a setting came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    Dotfile(PathBuf),
+    Env(String),
+    Cli(&'static str),
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::Dotfile(path) => write!(f, "dotfile {}", path.display()),
+            Origin::Env(var) => write!(f, "env {}", var),
+            Origin::Cli(flag) => write!(f, "cli {}", flag),
+        }
+    }
+}
+
+/// Origin of each top-level setting, the highest layer of `Settings::load`
+/// setting it
+pub fn config_origins(cli: &Cli) -> Result<BTreeMap<String, Origin>> {
+    let dotfile_path = config_path();
+    let dotfile: toml::Table = if std::fs::exists(&dotfile_path).unwrap_or(false) {
+        toml::from_str(&std::fs::read_to_string(&dotfile_path)?)
+            .with_context(|| format!("parsing {}", dotfile_path.display()))?
+    } else {
+        toml::Table::new()
+    };
+    let provider = cli
+        .commands
+        .quote_overrides()
+        .is_some_and(|o| o.provider.is_some());
+
+    let serde_json::Value::Object(fields) = serde_json::to_value(Settings::default())? else {
+        bail!("settings don't serialize to a table");
+    };
+    let origins = fields.keys().map(|key| {
+        let env = format!("LPT_{}", key.to_ascii_uppercase());
+        let origin = match key.as_str() {
+            "portfolio_dir" if cli.portfolio_dir.is_some() => Origin::Cli("--portfolio-dir"),
+            "quote_provider" if cli.fixture_prices.is_some() => Origin::Cli("--fixture-prices"),
+            "quote_provider" if provider => Origin::Cli("--provider"),
+            _ if std::env::var_os(&env).is_some() => Origin::Env(env),
+            _ if dotfile.contains_key(key) => Origin::Dotfile(dotfile_path.clone()),
+            _ => Origin::Default,
+        };
+        (key.clone(), origin)
+    });
+    Ok(origins.collect())
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Self {
             portfolio_dir: default_portfolio_dir(),
+            portfolio_dirs: Vec::new(),
+            ignore_patterns: Vec::new(),
             base_currency: Currency::default(),
+            quote_provider: default_quote_provider(),
+            quote_discrepancy_pct: default_quote_discrepancy_pct(),
+            cost_basis: CostBasisMethod::default(),
+            rounding: Rounding::default(),
+            dust_threshold: default_dust_threshold(),
+            http_timeout_secs: default_http_timeout_secs(),
+            week_starts_on: WeekStart::default(),
+            fiscal_year_start_month: default_fiscal_year_start_month(),
+            short_term_days: None,
+            benchmarks: BTreeMap::new(),
+            scenarios: BTreeMap::new(),
+            dca: BTreeMap::new(),
+            fee_tiers: BTreeMap::new(),
+            fee_check_tolerance_pct: default_fee_check_tolerance_pct(),
+            inbox_dir: None,
+            inbox_rules: BTreeMap::new(),
+            templates: BTreeMap::new(),
+            small_price_below: default_small_price_below(),
+            price_sig_figs: default_price_sig_figs(),
+            import_total_tolerance: default_import_total_tolerance(),
+            confirm_threshold: default_confirm_threshold(),
+            delisted: BTreeMap::new(),
+            lint: LintConfig::default(),
+            symbol_overrides: BTreeMap::new(),
+            snapshot_retention: Retention::default(),
+            alert_cooldown_mins: default_alert_cooldown_mins(),
+            usage_stats: false,
+            clock: Clock::default(),
+            fixture_prices: None,
+            no_cache: false,
         }
     }
 }
@@ -35,10 +367,16 @@ impl Settings {
         // Layer 1: Built-in defaults (via serde defaults)
 
         // Layer 2: Dotfile (optional, won't fail if missing)
-        let dotfile_path = tilde("~/.local/share/csvpt/config.toml").to_string();
+        let dotfile_path = config_path();
+        // the context and its reporter come after the settings
+        let reporter = reporter::for_mode(cli.output, cli.quiet);
         if std::fs::exists(&dotfile_path).unwrap_or(false) {
-            println!("Loading config from: {}", dotfile_path);
-            builder = builder.add_source(config::File::with_name(&dotfile_path).required(false));
+            let problems = config_check::check_file(&dotfile_path)?;
+            if !problems.is_empty() {
+                bail!("invalid config file:\n{}", problems.join("\n"));
+            }
+            reporter.status(&format!("Loading config from: {}", dotfile_path.display()));
+            builder = builder.add_source(config::File::from(dotfile_path).required(false));
         }
 
         // Layer 3: Environment variables (LPT_PORTFOLIO_DIR, LPT_BASE_CURRENCY, etc.)
@@ -46,32 +384,48 @@ impl Settings {
         builder = builder.add_source(
             config::Environment::with_prefix("LPT")
                 .prefix_separator("_")
-                .try_parsing(true),
+                .try_parsing(true)
+                .list_separator(",")
+                .with_list_parse_key("portfolio_dirs")
+                .with_list_parse_key("ignore_patterns"),
         );
 
         // Layer 4: CLI arguments (highest priority)
         if let Some(portfolio_dir) = &cli.portfolio_dir {
-            println!("CLI orverride for portfolio dir: {portfolio_dir}");
+            reporter.status(&format!("CLI orverride for portfolio dir: {portfolio_dir}"));
             builder = builder.set_override("portfolio_dir", portfolio_dir.to_string())?;
         }
+        let overrides = cli.commands.quote_overrides().cloned().unwrap_or_default();
+        if let Some(provider) = &overrides.provider {
+            builder = builder.set_override("quote_provider", provider.as_str())?;
+        }
+        if let Some(path) = &cli.fixture_prices {
+            builder = builder.set_override("quote_provider", format!("file:{}", path.display()))?;
+        }
 
         // Build and deserialize
         let config = builder.build()?;
         let mut settings: Settings = config
             .try_deserialize()
             .with_context(|| "Failed to deserialize configuration")?;
+        settings.clock = match cli.now {
+            Some(at) => Clock::Frozen(at),
+            None => Clock::from_env()?,
+        };
+        settings.fixture_prices = cli.fixture_prices.clone();
+        settings.no_cache = overrides.no_cache;
 
         // Validate and show warnings
         let warnings = settings.validate();
         for warning in warnings {
-            eprintln!("Config warning: {}", warning);
+            warnings::warn(format!("Config warning: {}", warning));
         }
 
         Ok(settings)
     }
 
     /// Validate settings and return warnings for invalid values
-    fn validate(&mut self) -> Vec<String> {
+    pub(crate) fn validate(&mut self) -> Vec<String> {
         let mut warnings = Vec::new();
 
         // Validate data_dir (attempt to create if doesn't exist)
@@ -85,9 +439,193 @@ impl Settings {
             self.portfolio_dir = default_portfolio_dir();
         }
 
+        self.portfolio_dirs = std::mem::take(&mut self.portfolio_dirs)
+            .into_iter()
+            .map(|dir| PathBuf::from(tilde(&dir.to_string_lossy()).to_string()))
+            .filter(|dir| {
+                let exists = dir.is_dir();
+                if !exists {
+                    warnings.push(format!(
+                        "ignoring portfolio_dirs entry '{}', not a directory",
+                        dir.display()
+                    ));
+                }
+                exists
+            })
+            .collect();
+
+        if let Err(e) = quote::parse_providers(&self.quote_provider) {
+            warnings.push(format!(
+                "{}, using default '{}'",
+                e,
+                default_quote_provider()
+            ));
+            self.quote_provider = default_quote_provider();
+        }
+
+        if self.http_timeout_secs == 0 {
+            warnings.push(format!(
+                "http_timeout_secs must be positive, using default {}",
+                default_http_timeout_secs()
+            ));
+            self.http_timeout_secs = default_http_timeout_secs();
+        }
+
+        if !(1..=12).contains(&self.fiscal_year_start_month) {
+            warnings.push(format!(
+                "fiscal_year_start_month must be 1-12, got {}, using default {}",
+                self.fiscal_year_start_month,
+                default_fiscal_year_start_month()
+            ));
+            self.fiscal_year_start_month = default_fiscal_year_start_month();
+        }
+
+        if !(1..=28).contains(&self.price_sig_figs) {
+            warnings.push(format!(
+                "price_sig_figs must be 1-28, got {}, using default {}",
+                self.price_sig_figs,
+                default_price_sig_figs()
+            ));
+            self.price_sig_figs = default_price_sig_figs();
+        }
+
+        self.dca.retain(|name, plan| {
+            let valid = plan.amount > Decimal::ZERO
+                && plan.tolerance_pct >= Decimal::ZERO
+                && Currency::new(&plan.asset).is_ok();
+            if !valid {
+                warnings.push(format!(
+                    "ignoring DCA plan '{}', it needs a known asset, a positive amount and a tolerance of 0 or more",
+                    name
+                ));
+            }
+            valid
+        });
+
+        self.fee_tiers.retain(|name, tier| {
+            let valid = tier.taker_bps >= tier.maker_bps && tier.taker_bps >= Decimal::ZERO;
+            if !valid {
+                warnings.push(format!(
+                    "ignoring fee tier '{}', taker_bps must be at least maker_bps and not negative",
+                    name
+                ));
+            }
+            valid
+        });
+
+        if self.import_total_tolerance < Decimal::ZERO {
+            warnings.push(format!(
+                "import_total_tolerance can't be negative, using default {}",
+                default_import_total_tolerance()
+            ));
+            self.import_total_tolerance = default_import_total_tolerance();
+        }
+
+        if self.fee_check_tolerance_pct < Decimal::ZERO {
+            warnings.push(format!(
+                "fee_check_tolerance_pct can't be negative, using default {}",
+                default_fee_check_tolerance_pct()
+            ));
+            self.fee_check_tolerance_pct = default_fee_check_tolerance_pct();
+        }
+
+        self.templates.retain(|name, template| {
+            let parsed = tx_template::parse(template);
+            if let Err(e) = &parsed {
+                warnings.push(format!("ignoring template '{}': {}", name, e));
+            }
+            parsed.is_ok()
+        });
+
+        self.delisted = std::mem::take(&mut self.delisted)
+            .into_iter()
+            .filter_map(|(ticker, delisted)| {
+                let valid = delisted.price >= Decimal::ZERO
+                    && Currency::new(&ticker).is_ok()
+                    && Currency::new(&delisted.currency).is_ok()
+                    && !delisted.currency.eq_ignore_ascii_case(&ticker);
+                if !valid {
+                    warnings.push(format!(
+                        "ignoring delisted asset '{}', it needs a known ticker, a known currency and a price of at least 0",
+                        ticker
+                    ));
+                }
+                valid.then(|| {
+                    let currency = delisted.currency.to_ascii_uppercase();
+                    (ticker.to_ascii_uppercase(), Delisted { currency, ..delisted })
+                })
+            })
+            .collect();
+
+        self.symbol_overrides = std::mem::take(&mut self.symbol_overrides)
+            .into_iter()
+            .filter_map(|(ticker, id)| {
+                let id = id.trim().to_lowercase();
+                if id.is_empty() {
+                    warnings.push(format!(
+                        "ignoring symbol override '{}', it needs a CoinGecko id",
+                        ticker
+                    ));
+                    return None;
+                }
+                Some((ticker.to_ascii_uppercase(), id))
+            })
+            .collect();
+
+        if self.snapshot_retention.daily_days < self.snapshot_retention.hourly_days {
+            warnings.push(format!(
+                "snapshot_retention.daily_days can't be less than hourly_days, using defaults {} and {}",
+                Retention::default().hourly_days,
+                Retention::default().daily_days
+            ));
+            self.snapshot_retention = Retention::default();
+        }
+
+        self.inbox_rules
+            .retain(|pattern, _| match glob::Pattern::new(pattern) {
+                Ok(_) => true,
+                Err(e) => {
+                    warnings.push(format!("ignoring inbox rule '{}': {}", pattern, e));
+                    false
+                }
+            });
+
+        self.ignore_patterns
+            .retain(|pattern| match glob::Pattern::new(pattern) {
+                Ok(_) => true,
+                Err(e) => {
+                    warnings.push(format!("ignoring ignore pattern '{}': {}", pattern, e));
+                    false
+                }
+            });
+
         warnings
     }
 
+    /// Whether portfolio `name` matches one of `ignore_patterns`
+    pub fn is_ignored(&self, name: &str) -> bool {
+        self.ignore_patterns
+            .iter()
+            .filter_map(|p| glob::Pattern::new(p).ok())
+            .any(|p| p.matches(name))
+    }
+
+    /// Directory holding tool-managed state (snapshots, caches) next to the portfolios
+    pub fn state_dir(&self) -> PathBuf {
+        self.portfolio_dir.join(".csvpt")
+    }
+
+    /// `portfolio_dir` followed by the other `portfolio_dirs`
+    pub fn data_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = vec![self.portfolio_dir.clone()];
+        for dir in &self.portfolio_dirs {
+            if !dirs.contains(dir) {
+                dirs.push(dir.clone());
+            }
+        }
+        dirs
+    }
+
     pub fn path_for(&self, name: &str) -> PathBuf {
         self.portfolio_dir.clone().join(name).with_extension("csv")
     }
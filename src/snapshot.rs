@@ -0,0 +1,376 @@
+use crate::confirm::confirm;
+use crate::portfolio::list_entries;
+use crate::position_cache;
+use crate::quote::{LiveQuotes, Prices};
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use anyhow::{Context, Result};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use time::{Duration, OffsetDateTime};
+
+/// Market value of one portfolio at a point in time
+///
+/// Stored per portfolio in `<state dir>/snapshots/<name>.csv`:
+/// ```csv
+/// ts,value
+/// 1704883200,41250.10
+/// ```
+///
+/// Snapshots of all portfolios at once also add their sum, in the
+/// `base_currency` of the config, to `<state dir>/total_snapshots.csv`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Unix timestamp, seconds
+    pub ts: i64,
+    pub value: Decimal,
+}
+
+/// How long snapshots are kept at which resolution by `snapshot compact`,
+/// the `[snapshot_retention]` table of the config
+///
+/// Snapshots younger than `hourly_days` are kept one per hour, younger than
+/// `daily_days` one per day, older ones one per week; the last snapshot of
+/// each hour, day or week is the one kept.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Retention {
+    pub hourly_days: u32,
+    pub daily_days: u32,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Retention {
+            hourly_days: 7,
+            daily_days: 730,
+        }
+    }
+}
+
+impl Retention {
+    /// Hour, day or week a snapshot at `ts` is kept for, by its age at `now`
+    fn bucket(&self, ts: i64, now: i64) -> (u8, i64) {
+        const HOUR: i64 = 3600;
+        const DAY: i64 = 24 * HOUR;
+        let age = now - ts;
+        if age < self.hourly_days as i64 * DAY {
+            (0, ts.div_euclid(HOUR))
+        } else if age < self.daily_days as i64 * DAY {
+            (1, ts.div_euclid(DAY))
+        } else {
+            // day 0 of the epoch is a Thursday, weeks start on Monday
+            (2, (ts.div_euclid(DAY) + 3).div_euclid(7))
+        }
+    }
+}
+
+fn snapshots_dir(settings: &Settings) -> PathBuf {
+    settings.state_dir().join("snapshots")
+}
+
+fn snapshot_path(name: &str, settings: &Settings) -> PathBuf {
+    snapshots_dir(settings).join(format!("{}.csv", name))
+}
+
+/// Outside `snapshots_dir`, so it can't be taken for a portfolio's series
+fn total_path(settings: &Settings) -> PathBuf {
+    settings.state_dir().join("total_snapshots.csv")
+}
+
+/// Append a snapshot to the portfolio's series
+pub fn record(name: &str, snapshot: Snapshot, settings: &Settings) -> Result<()> {
+    std::fs::create_dir_all(snapshots_dir(settings))?;
+    record_at(&snapshot_path(name, settings), snapshot)
+}
+
+/// Append a snapshot to the workspace total series
+pub fn record_total(snapshot: Snapshot, settings: &Settings) -> Result<()> {
+    std::fs::create_dir_all(settings.state_dir())?;
+    record_at(&total_path(settings), snapshot)
+}
+
+fn record_at(path: &Path, snapshot: Snapshot) -> Result<()> {
+    let is_new = !path.exists();
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("opening {}", path.display()))?;
+    if is_new {
+        writeln!(file, "ts,value")?;
+    }
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(file);
+    wtr.serialize(snapshot)?;
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Replace the portfolio's series with `snapshots`
+pub fn replace(name: &str, snapshots: &[Snapshot], settings: &Settings) -> Result<()> {
+    std::fs::create_dir_all(snapshots_dir(settings))?;
+    replace_at(&snapshot_path(name, settings), snapshots)
+}
+
+fn replace_at(path: &Path, snapshots: &[Snapshot]) -> Result<()> {
+    let tmp = path.with_extension("csv.tmp");
+    let mut wtr = csv::Writer::from_path(&tmp)?;
+    for snapshot in snapshots {
+        wtr.serialize(snapshot)?;
+    }
+    wtr.flush()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// All snapshots of a portfolio, oldest first; empty when none were taken yet
+pub fn load(name: &str, settings: &Settings) -> Result<Vec<Snapshot>> {
+    load_from(&snapshot_path(name, settings))
+}
+
+/// The workspace total series, oldest first, see `Snapshot`
+pub fn load_total(settings: &Settings) -> Result<Vec<Snapshot>> {
+    load_from(&total_path(settings))
+}
+
+fn load_from(path: &Path) -> Result<Vec<Snapshot>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut snapshots: Vec<Snapshot> = reader
+        .deserialize()
+        .collect::<Result<_, csv::Error>>()
+        .with_context(|| format!("parsing {}", path.display()))?;
+    snapshots.sort_by_key(|s| s.ts);
+    Ok(snapshots)
+}
+
+/// The snapshots `retention` keeps at `now`, oldest first
+pub fn compact(
+    snapshots: &[Snapshot],
+    now: OffsetDateTime,
+    retention: &Retention,
+) -> Vec<Snapshot> {
+    let now = now.unix_timestamp();
+    let mut kept: Vec<Snapshot> = Vec::new();
+    for snapshot in snapshots {
+        match kept.last_mut() {
+            Some(last) if retention.bucket(last.ts, now) == retention.bucket(snapshot.ts, now) => {
+                *last = *snapshot;
+            }
+            _ => kept.push(*snapshot),
+        }
+    }
+    kept
+}
+
+/// `snapshot compact`: downsample the snapshots of one portfolio, or all of
+/// them and the workspace total, by `snapshot_retention`
+pub fn run_compact(
+    name: Option<&str>,
+    yes: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let series: Vec<(String, PathBuf)> = match name {
+        Some(name) => vec![(name.to_string(), snapshot_path(name, settings))],
+        None => list_entries(settings)?
+            .into_iter()
+            .map(|e| {
+                let path = snapshot_path(&e.name, settings);
+                (e.name, path)
+            })
+            .chain([("workspace total".to_string(), total_path(settings))])
+            .collect(),
+    };
+
+    let now = settings.clock.now();
+    let mut compacted = Vec::new();
+    for (name, path) in series {
+        let snapshots = load_from(&path)?;
+        let kept = compact(&snapshots, now, &settings.snapshot_retention);
+        if kept.len() < snapshots.len() {
+            compacted.push((name, path, snapshots.len(), kept));
+        }
+    }
+    let removed: usize = compacted
+        .iter()
+        .map(|(_, _, before, kept)| before - kept.len())
+        .sum();
+    if removed == 0 {
+        reporter.status("Nothing to compact");
+        return Ok(());
+    }
+    let details: Vec<String> = compacted
+        .iter()
+        .map(|(name, _, before, kept)| format!("{}: {} → {} snapshots", name, before, kept.len()))
+        .collect();
+    confirm(
+        &format!("Remove {} snapshots", removed),
+        &details,
+        removed,
+        yes,
+        settings,
+    )?;
+    for ((_, path, _, kept), line) in compacted.iter().zip(&details) {
+        replace_at(path, kept)?;
+        reporter.status(&format!("🗜️  {}", line));
+    }
+    Ok(())
+}
+
+/// Latest snapshot taken at least `age` before `now`
+pub fn before(snapshots: &[Snapshot], now: OffsetDateTime, age: Duration) -> Option<Snapshot> {
+    let cutoff = (now - age).unix_timestamp();
+    snapshots.iter().rev().find(|s| s.ts <= cutoff).copied()
+}
+
+/// Current market value of the portfolio
+pub fn current_value(name: &str, settings: &Settings) -> Result<Decimal> {
+    position_cache::load(settings.path_for(name), settings)?.market_value(&LiveQuotes)
+}
+
+/// `value` of portfolio `name` in the `base_currency` of the config, the
+/// currency of the workspace total
+pub fn in_workspace_base(name: &str, value: Decimal, settings: &Settings) -> Result<Decimal> {
+    let base = position_cache::load(settings.path_for(name), settings)?.base;
+    if base == settings.base_currency {
+        return Ok(value);
+    }
+    Ok(value * LiveQuotes.price_in(&base, settings.base_currency.ticker())?)
+}
+
+/// Snapshot the current value of one portfolio, or all of them and their total
+pub fn take(name: Option<&str>, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let names: Vec<String> = match name {
+        Some(name) => vec![name.to_string()],
+        None => list_entries(settings)?
+            .into_iter()
+            .map(|e| e.name)
+            .collect(),
+    };
+
+    let now = settings.clock.now().unix_timestamp();
+    let mut total = Decimal::ZERO;
+    for each in &names {
+        let value = current_value(each, settings)?;
+        record(each, Snapshot { ts: now, value }, settings)?;
+        reporter.status(&format!("📸 {}: {}", each, value.round_dp(2)));
+        if name.is_none() {
+            total += in_workspace_base(each, value, settings)?;
+        }
+    }
+    if name.is_none() && !names.is_empty() {
+        record_total(
+            Snapshot {
+                ts: now,
+                value: total,
+            },
+            settings,
+        )?;
+        reporter.status(&format!(
+            "📸 total: {} {}",
+            total.round_dp(2).normalize(),
+            settings.base_currency
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::create_test_settings;
+    use rust_decimal::dec;
+    use tempfile::TempDir;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_record_then_load_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        assert!(load("alpha", &settings).unwrap().is_empty());
+
+        let later = Snapshot {
+            ts: 1704969600,
+            value: dec!(41000.5),
+        };
+        let earlier = Snapshot {
+            ts: 1704883200,
+            value: dec!(40000),
+        };
+        record("alpha", later, &settings).unwrap();
+        record("alpha", earlier, &settings).unwrap();
+
+        assert_eq!(load("alpha", &settings).unwrap(), vec![earlier, later]);
+        let content =
+            std::fs::read_to_string(dir.path().join(".csvpt/snapshots/alpha.csv")).unwrap();
+        assert!(content.starts_with("ts,value\n1704969600,41000.5\n"));
+
+        assert!(load_total(&settings).unwrap().is_empty());
+        record_total(later, &settings).unwrap();
+        assert_eq!(load_total(&settings).unwrap(), vec![later]);
+        assert_eq!(load("alpha", &settings).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_before_picks_latest_old_enough_snapshot() {
+        let now = datetime!(2024-01-12 12:00 UTC);
+        let day = Duration::days(1);
+        let snaps = [
+            Snapshot {
+                ts: (now - Duration::hours(50)).unix_timestamp(),
+                value: dec!(1),
+            },
+            Snapshot {
+                ts: (now - Duration::hours(25)).unix_timestamp(),
+                value: dec!(2),
+            },
+            Snapshot {
+                ts: (now - Duration::hours(1)).unix_timestamp(),
+                value: dec!(3),
+            },
+        ];
+        assert_eq!(before(&snaps, now, day).unwrap().value, dec!(2));
+        assert_eq!(before(&snaps[2..], now, day), None);
+    }
+
+    #[test]
+    fn test_compact_keeps_the_last_snapshot_of_each_hour_day_and_week() {
+        let now = datetime!(2024-06-15 12:00 UTC);
+        let at = |dt: OffsetDateTime, value| Snapshot {
+            ts: dt.unix_timestamp(),
+            value,
+        };
+        let snaps = [
+            // two in the week of Monday 2021-06-07, older than the daily window
+            at(datetime!(2021-06-07 09:00 UTC), dec!(1)),
+            at(datetime!(2021-06-13 23:00 UTC), dec!(2)),
+            at(datetime!(2021-06-14 01:00 UTC), dec!(3)),
+            // two on one day a month ago
+            at(datetime!(2024-05-15 08:00 UTC), dec!(4)),
+            at(datetime!(2024-05-15 20:00 UTC), dec!(5)),
+            // every 5 minutes within the last hours
+            at(datetime!(2024-06-15 10:50 UTC), dec!(6)),
+            at(datetime!(2024-06-15 10:55 UTC), dec!(7)),
+            at(datetime!(2024-06-15 11:00 UTC), dec!(8)),
+            at(datetime!(2024-06-15 11:05 UTC), dec!(9)),
+        ];
+        let retention = Retention {
+            hourly_days: 7,
+            daily_days: 730,
+        };
+        let values: Vec<Decimal> = compact(&snaps, now, &retention)
+            .iter()
+            .map(|s| s.value)
+            .collect();
+        assert_eq!(values, [dec!(2), dec!(3), dec!(5), dec!(7), dec!(9)]);
+        assert_eq!(compact(&snaps[5..], now, &retention).len(), 2);
+    }
+}
@@ -0,0 +1,160 @@
+//! Splitting a trade into smaller ones
+//!
+//! One recorded fill sometimes stands for several tax lots, e.g. a buy made
+//! for two accounts. `split-tx` replaces the trade with one row per part,
+//! at the same time, pair and price, with the fee shared in proportion, so
+//! totals and the PnL don't change. Tags and journal entries of the trade
+//! are carried over to every part.
+
+use crate::index;
+use crate::journal;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::tags;
+use crate::trade::{Trade, find_by_id, parse_csv_file, write_csv_records};
+use crate::writer;
+use anyhow::{Result, bail};
+use rust_decimal::Decimal;
+
+const FEE_DECIMALS: u32 = 8;
+
+/// `trade` as one trade per amount of `parts`, plus one for the rest of its
+/// amount if the parts add up to less
+pub fn split(trade: &Trade, parts: &[Decimal]) -> Result<Vec<Trade>> {
+    if parts.iter().any(|p| p.is_sign_negative() || p.is_zero()) {
+        bail!("parts must be greater than zero");
+    }
+    let total: Decimal = parts.iter().sum();
+    if total > trade.amount {
+        bail!(
+            "parts add up to {}, more than the trade amount {}",
+            total.normalize(),
+            trade.amount.normalize()
+        );
+    }
+    let mut amounts = parts.to_vec();
+    if total < trade.amount {
+        amounts.push(trade.amount - total);
+    }
+    if amounts.len() < 2 {
+        bail!("nothing to split, the part is the whole trade");
+    }
+
+    // rows are read back through f64, so fees are kept to 8 decimals and
+    // the last part takes what rounding left
+    let mut fee_left = trade.fee;
+    let last = amounts.len() - 1;
+    Ok(amounts
+        .iter()
+        .enumerate()
+        .map(|(i, amount)| {
+            let fee = if i == last {
+                fee_left
+            } else {
+                (trade.fee * amount / trade.amount).round_dp(FEE_DECIMALS)
+            };
+            fee_left -= fee;
+            Trade {
+                created_at: trade.created_at,
+                pair: trade.pair.clone(),
+                side: trade.side,
+                amount: *amount,
+                price: trade.price,
+                fee,
+                source: trade.source.clone(),
+            }
+        })
+        .collect())
+}
+
+/// `split-tx`: replace trade `tx` (an id or its start) of portfolio `name`
+/// with its parts, see `split`
+pub fn run(
+    name: &str,
+    tx: &str,
+    parts: &[Decimal],
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<Vec<String>> {
+    let path = settings.path_for(name);
+    let _lock = writer::lock(name, settings)?;
+    let (config, mut trades) = parse_csv_file(&path)?;
+    config.ensure_open()?;
+    let id = find_by_id(&trades, tx)?.id();
+    let Some(at) = trades.iter().position(|t| t.id() == id) else {
+        bail!("no trade with id '{}'", id);
+    };
+    let split = split(&trades[at], parts)?;
+    let ids: Vec<String> = split.iter().map(Trade::id).collect();
+    trades.splice(at..=at, split);
+    let records: Vec<csv::StringRecord> = trades.iter().map(Trade::to_record).collect();
+    write_csv_records(&path, &config, &records)?;
+    index::record_write(name, settings);
+    // equal parts are the same row and share an id
+    let mut parts_ids = ids.clone();
+    parts_ids.dedup();
+    tags::carry_over(name, &id, &parts_ids, settings)?;
+    journal::carry_over(name, &id, &parts_ids, settings)?;
+    reporter.status(&format!(
+        "✂️  Split trade {} of '{}' into {}",
+        id,
+        name,
+        ids.join(", ")
+    ));
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::Silent;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use rust_decimal::dec;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_split_keeps_totals_and_carries_tags_and_journal_over() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        create_test_csv(
+            &dir,
+            "pf",
+            "created_at,pair,side,amount,price,fee,source
+1704883200,BTC/USD,BUY,0.6,40000,10,manual
+1705056000,BTC/USD,SELL,0.1,50000,1,manual
+",
+        );
+        let (_, trades) = parse_csv_file(settings.path_for("pf")).unwrap();
+        let buy = trades[0].id();
+        tags::tag("pf", &buy, &["dca".to_string()], false, &Silent, &settings).unwrap();
+        journal::add("pf", &buy, "two accounts", &Silent, &settings).unwrap();
+
+        let parts = split(&trades[0], &[dec!(0.3), dec!(0.2)]).unwrap();
+        let amounts: Vec<_> = parts.iter().map(|t| t.amount).collect();
+        assert_eq!(amounts, [dec!(0.3), dec!(0.2), dec!(0.1)]);
+        assert_eq!(parts.iter().map(|t| t.fee).sum::<Decimal>(), dec!(10));
+        assert_eq!(parts[0].fee, dec!(5));
+        let thirds = split(&trades[0], &[dec!(0.2), dec!(0.4)]).unwrap();
+        assert_eq!(thirds[0].fee + thirds[1].fee, dec!(10));
+        assert!(split(&trades[0], &[dec!(0.6)]).is_err());
+        assert!(split(&trades[0], &[dec!(0.5), dec!(0.2)]).is_err());
+        assert!(split(&trades[0], &[dec!(0), dec!(0.2)]).is_err());
+
+        let ids = run("pf", &buy[..6], &[dec!(0.4)], &Silent, &settings).unwrap();
+        let (_, after) = parse_csv_file(settings.path_for("pf")).unwrap();
+        assert_eq!(after.len(), 3);
+        assert_eq!(after[0].id(), ids[0]);
+        assert_eq!(
+            (after[1].amount, after[1].fee),
+            (dec!(0.2), dec!(3.33333333))
+        );
+        assert_eq!(after[1].source.as_deref(), Some("manual"));
+        assert_eq!(after[2].id(), trades[1].id());
+        let tags = tags::load("pf", &settings).unwrap();
+        assert!(!tags.contains_key(&buy));
+        assert!(ids.iter().all(|id| tags[id].contains("dca")));
+        let journal = journal::by_tx(journal::load("pf", &settings).unwrap());
+        assert!(ids.iter().all(|id| journal[id][0].text == "two accounts"));
+        assert!(run("pf", &buy, &[dec!(0.1)], &Silent, &settings).is_err());
+    }
+}
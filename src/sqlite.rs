@@ -0,0 +1,87 @@
+//! SQLite export
+//!
+//! Decimals are stored as their exact text: SQLite has no exact numeric
+//! type, and its arithmetic converts the text when it needs a number.
+
+use crate::export::{Table, Value};
+use crate::format::iso_utc;
+use anyhow::{Context, Result};
+use rusqlite::types::{ToSqlOutput, ValueRef};
+use rusqlite::{Connection, ToSql, params_from_iter};
+use std::path::Path;
+
+impl ToSql for Value {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(match self {
+            Value::Null => ToSqlOutput::Borrowed(ValueRef::Null),
+            Value::Decimal(d) => ToSqlOutput::from(d.normalize().to_string()),
+            Value::Text(s) => ToSqlOutput::Borrowed(ValueRef::Text(s.as_bytes())),
+            Value::Timestamp(ts) => ToSqlOutput::from(iso_utc(*ts)),
+        })
+    }
+}
+
+/// Write `tables` as a new SQLite database at `path`, replacing the file
+pub fn write(path: &Path, tables: &[Table]) -> Result<()> {
+    if path.is_file() {
+        std::fs::remove_file(path).with_context(|| format!("replacing {}", path.display()))?;
+    }
+    let mut db = Connection::open(path).with_context(|| format!("creating {}", path.display()))?;
+    let tx = db.transaction()?;
+    for table in tables {
+        tx.execute(&table.create_sql(), [])?;
+        let placeholders = vec!["?"; table.columns.len()].join(", ");
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {} VALUES ({})",
+            table.name, placeholders
+        ))?;
+        for row in &table.rows {
+            insert.execute(params_from_iter(row))?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::trades_table;
+    use crate::test_utils::helpers::transactions_from;
+
+    #[test]
+    fn test_write_keeps_decimals_exact_and_replaces_the_file() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,0.1,40000.10,0.00000001
+",
+        );
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("out.db");
+        std::fs::write(&path, "not a database").unwrap();
+        write(&path, &[trades_table(&[("main".to_string(), trades)])]).unwrap();
+
+        let db = Connection::open(&path).unwrap();
+        let row: (String, String, String, String, Option<String>) = db
+            .query_row(
+                "SELECT created_at, amount, price, fee, source FROM trades",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            row,
+            (
+                "2024-01-10T10:40:00Z".to_string(),
+                "0.1".to_string(),
+                "40000.1".to_string(),
+                "0.00000001".to_string(),
+                None
+            )
+        );
+        let total: f64 = db
+            .query_row("SELECT SUM(amount * price) FROM trades", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(total, 4000.01);
+    }
+}
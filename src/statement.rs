@@ -0,0 +1,274 @@
+use crate::currency::{Currency, CurrencyType};
+use crate::format;
+use crate::math;
+use crate::portfolio::Portfolio;
+use crate::price_history;
+use crate::reporter::Reporter;
+use crate::settings::{CostBasisMethod, Settings};
+use crate::workspace::sha256_hex;
+use anyhow::{Context, Result, bail};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use thousands::Separable;
+use time::Date;
+
+/// Value of a portfolio at the end of one day, at that day's prices
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Statement {
+    pub portfolio: String,
+    /// Day of the statement, `YYYY-MM-DD`, trades up to its end (UTC) are included
+    pub as_of: String,
+    pub base: String,
+    pub cost_basis: CostBasisMethod,
+    /// Where the prices come from
+    pub prices: String,
+    /// Crypto positions in ticker order
+    pub positions: Vec<StatementLine>,
+    pub value: Decimal,
+    pub cost_base: Decimal,
+    pub pnl: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatementLine {
+    pub ticker: String,
+    pub balance: Decimal,
+    pub price: Decimal,
+    pub value: Decimal,
+    pub cost_base: Decimal,
+}
+
+/// Statement file written by `freeze`, with the SHA-256 of the statement JSON
+///
+/// Stored in `<state dir>/statements/<portfolio>/<YYYY-MM-DD>.json`, never
+/// overwritten, so the numbers can be reproduced later as they were quoted.
+#[derive(Debug, Serialize, Deserialize)]
+struct FrozenStatement {
+    sha256: String,
+    statement: Statement,
+}
+
+fn checksum(statement: &Statement) -> Result<String> {
+    Ok(sha256_hex(&serde_json::to_vec(statement)?))
+}
+
+fn statement_path(name: &str, as_of: Date, settings: &Settings) -> PathBuf {
+    settings
+        .state_dir()
+        .join("statements")
+        .join(name)
+        .join(format!("{}.json", as_of))
+}
+
+/// Value the portfolio as of the end of `as_of`, pricing every crypto
+/// position with `price_of` (in the portfolio base currency)
+pub fn from_portfolio(
+    name: &str,
+    pf: &Portfolio,
+    as_of: Date,
+    prices: &str,
+    cost_basis: CostBasisMethod,
+    mut price_of: impl FnMut(&Currency) -> Result<Decimal>,
+) -> Result<Statement> {
+    let mut positions = Vec::new();
+    for (currency, position) in pf.positions.iter() {
+        if currency.currency_type() != CurrencyType::Crypto || position.balance.is_zero() {
+            continue;
+        }
+        let price = price_of(currency)?;
+        positions.push(StatementLine {
+            ticker: currency.to_string(),
+            balance: position.balance,
+            price,
+            value: position.balance * price,
+            cost_base: position.cost_base,
+        });
+    }
+    positions.sort_by(|a, b| a.ticker.cmp(&b.ticker));
+    let value: Decimal = positions.iter().map(|p| p.value).sum();
+    let cost_base: Decimal = positions.iter().map(|p| p.cost_base).sum();
+    Ok(Statement {
+        portfolio: name.to_string(),
+        as_of: as_of.to_string(),
+        base: pf.base.to_string(),
+        cost_basis,
+        prices: prices.to_string(),
+        positions,
+        value,
+        cost_base,
+        pnl: value - cost_base,
+    })
+}
+
+/// Statement of the portfolio as of the end of `as_of`, at CoinGecko daily prices
+pub fn compute(name: &str, as_of: Date, settings: &Settings) -> Result<Statement> {
+    let pf = Portfolio::from_csv_as_of(settings.path_for(name), Some(as_of))?;
+    let base = pf.base.to_string();
+    from_portfolio(
+        name,
+        &pf,
+        as_of,
+        &format!("CoinGecko daily price of {}", as_of),
+        settings.cost_basis,
+        |c| price_history::price_on(c, &base, as_of, settings),
+    )
+}
+
+/// The frozen statement of `name` as of `as_of`, if any, after checking it
+/// wasn't modified since
+pub fn load_frozen(name: &str, as_of: Date, settings: &Settings) -> Result<Option<Statement>> {
+    let path = statement_path(name, as_of, settings);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = std::fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+    let frozen: FrozenStatement =
+        serde_json::from_slice(&data).with_context(|| format!("parsing {}", path.display()))?;
+    if checksum(&frozen.statement)? != frozen.sha256 {
+        bail!(
+            "{} was modified after it was frozen, its checksum doesn't match",
+            path.display()
+        );
+    }
+    Ok(Some(frozen.statement))
+}
+
+/// Write the statement of `name` as of `as_of`, refusing to replace one
+pub fn freeze(name: &str, as_of: Date, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let path = statement_path(name, as_of, settings);
+    if path.exists() {
+        bail!(
+            "statement of '{}' as of {} is already frozen in {}",
+            name,
+            as_of,
+            path.display()
+        );
+    }
+    let statement = compute(name, as_of, settings)?;
+    let frozen = FrozenStatement {
+        sha256: checksum(&statement)?,
+        statement,
+    };
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(&path, serde_json::to_vec_pretty(&frozen)?)?;
+    let mut permissions = std::fs::metadata(&path)?.permissions();
+    permissions.set_readonly(true);
+    std::fs::set_permissions(&path, permissions)?;
+
+    print(&frozen.statement, reporter, settings)?;
+    reporter.status(&format!(
+        "🔒 Froze statement in {} (sha256 {})",
+        path.display(),
+        frozen.sha256
+    ));
+    Ok(())
+}
+
+/// `report --as-of`: the frozen statement of that day when there is one,
+/// computed otherwise
+pub fn report(name: &str, as_of: Date, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    match load_frozen(name, as_of, settings)? {
+        Some(statement) => {
+            print(&statement, reporter, settings)?;
+            reporter.print(&format!(
+                "Frozen statement, {}",
+                statement_path(name, as_of, settings).display()
+            ));
+        }
+        None => print(&compute(name, as_of, settings)?, reporter, settings)?,
+    }
+    Ok(())
+}
+
+pub fn print(statement: &Statement, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let money = |d: Decimal| {
+        format!(
+            "{} {}",
+            d.round_dp(2).normalize().separate_with_commas(),
+            statement.base
+        )
+    };
+    reporter.print(&format!(
+        "Statement of {} as of {}",
+        statement.portfolio, statement.as_of
+    ));
+    if statement.positions.is_empty() {
+        reporter.print("No positions in portfolio");
+    } else {
+        let mut table = Table::new();
+        table.add_row(row!["Ticker", "Balance", "Price", "Value", "Cost Base"]);
+        for p in &statement.positions {
+            table.add_row(row![
+                p.ticker,
+                r->p.balance.normalize(),
+                r->format!("{} {}", format::price(p.price, settings), statement.base),
+                r->money(p.value),
+                r->money(p.cost_base)
+            ]);
+        }
+        reporter.table(&table)?;
+    }
+    let pnl_pct = math::percent_of(statement.pnl, statement.cost_base).unwrap_or_default();
+    reporter.print(&format!("Value:\t\t{}", money(statement.value)));
+    reporter.print(&format!("Cost base:\t{}", money(statement.cost_base)));
+    reporter.print(&format!(
+        "PnL:\t\t{} ({:.2})",
+        money(statement.pnl),
+        pnl_pct.round_dp(2)
+    ));
+    reporter.print(&format!("Prices:\t\t{}", statement.prices));
+    reporter.print(&format!("Cost basis:\t{:?}", statement.cost_basis));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::Silent;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use rust_decimal::dec;
+    use tempfile::TempDir;
+    use time::macros::date;
+
+    #[test]
+    fn test_freeze_writes_statement_once_and_detects_changes() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        create_test_csv(
+            &dir,
+            "pf",
+            "created_at,pair,side,amount,price,fee
+1735603200,BTC/USD,BUY,1,90000,10
+1735776000,BTC/USD,BUY,1,95000,10
+",
+        );
+        // only the december buy counts, priced at the cached year-end price
+        let prices = dir.path().join(".csvpt/prices");
+        std::fs::create_dir_all(&prices).unwrap();
+        std::fs::write(prices.join("BTC-USD.json"), r#"{"2024-12-31":"93000"}"#).unwrap();
+        let year_end = date!(2024 - 12 - 31);
+
+        freeze("pf", year_end, &Silent, &settings).unwrap();
+        let statement = load_frozen("pf", year_end, &settings).unwrap().unwrap();
+        assert_eq!(statement.positions.len(), 1);
+        assert_eq!(statement.positions[0].balance, dec!(1));
+        assert_eq!(statement.value, dec!(93000));
+        assert_eq!(statement.pnl, dec!(2990));
+        assert!(freeze("pf", year_end, &Silent, &settings).is_err());
+
+        // edited by hand: refused instead of quoting wrong numbers
+        let path = statement_path("pf", year_end, &settings);
+        let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+        assert!(permissions.readonly());
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        std::fs::set_permissions(&path, permissions).unwrap();
+        let edited = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("93000", "99000");
+        std::fs::write(&path, edited).unwrap();
+        assert!(load_frozen("pf", year_end, &settings).is_err());
+    }
+}
@@ -0,0 +1,468 @@
+use crate::chart;
+use crate::currency::Currency;
+use crate::format::{self, OutputFormat, Records, iso_utc};
+use crate::math::{self, Percent};
+use crate::period::{Calendar, Period};
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{Instrument, Side, Trade, parse_csv_file};
+use anyhow::Result;
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime};
+
+/// Label for rows of files written before the `source` column existed
+const UNRECORDED: &str = "(unrecorded)";
+
+/// Row counts and date range of the trades from one source
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceStats {
+    pub rows: usize,
+    pub first: OffsetDateTime,
+    pub last: OffsetDateTime,
+}
+
+/// Group trades by their `source`, rows without one under `(unrecorded)`
+pub fn by_source(trades: &[Trade]) -> BTreeMap<String, SourceStats> {
+    let mut stats: BTreeMap<String, SourceStats> = BTreeMap::new();
+    for t in trades {
+        let source = t.source.as_deref().unwrap_or(UNRECORDED);
+        stats
+            .entry(source.to_string())
+            .and_modify(|s| {
+                s.rows += 1;
+                s.first = s.first.min(t.created_at);
+                s.last = s.last.max(t.created_at);
+            })
+            .or_insert(SourceStats {
+                rows: 1,
+                first: t.created_at,
+                last: t.created_at,
+            });
+    }
+    stats
+}
+
+/// Row counts per side within one period
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeriodStats {
+    pub buys: usize,
+    pub sells: usize,
+    pub deposits: usize,
+    pub withdrawals: usize,
+    pub fees: usize,
+    pub incomes: usize,
+}
+
+impl PeriodStats {
+    pub fn rows(&self) -> usize {
+        self.buys + self.sells + self.deposits + self.withdrawals + self.fees + self.incomes
+    }
+}
+
+/// Group trades by the period they were made in, keyed by sortable period labels
+pub fn by_period(
+    trades: &[Trade],
+    period: Period,
+    calendar: &Calendar,
+) -> BTreeMap<String, PeriodStats> {
+    let mut stats: BTreeMap<String, PeriodStats> = BTreeMap::new();
+    for t in trades {
+        let s = stats
+            .entry(calendar.label(period, t.created_at.date()))
+            .or_default();
+        match t.side {
+            Side::Buy => s.buys += 1,
+            Side::Sell => s.sells += 1,
+            Side::Deposit => s.deposits += 1,
+            Side::Withdraw => s.withdrawals += 1,
+            Side::Fee => s.fees += 1,
+            Side::Income => s.incomes += 1,
+        }
+    }
+    stats
+}
+
+/// What `stats --heatmap` shows per day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum HeatmapValue {
+    /// Number of rows
+    #[default]
+    Trades,
+    /// Amount paid for buys in the base currency, fee included
+    Invested,
+}
+
+/// `value` of the trades per day; invested amounts only count buys priced in `base`
+pub fn per_day(trades: &[Trade], value: HeatmapValue, base: &Currency) -> BTreeMap<Date, Decimal> {
+    let mut days: BTreeMap<Date, Decimal> = BTreeMap::new();
+    for t in trades {
+        let amount = match value {
+            HeatmapValue::Trades => Decimal::ONE,
+            HeatmapValue::Invested => match &t.pair {
+                Instrument::Pair(pair) if t.side == Side::Buy && pair.quote == *base => {
+                    t.amount * t.price + t.fee
+                }
+                _ => continue,
+            },
+        };
+        *days.entry(t.created_at.date()).or_default() += amount;
+    }
+    days
+}
+
+/// `stats --heatmap`: a calendar of trades or invested amounts per day over
+/// the past year
+///
+/// Machine output has the days instead of the chart.
+pub fn print_heatmap(
+    name: &str,
+    value: HeatmapValue,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let (config, trades) = parse_csv_file(settings.path_for(name))?;
+    let base = Currency::new(&config.base_currency)?;
+    let today = settings.clock.now().date();
+    let first = Calendar::from_settings(settings).week_start(today - Duration::weeks(52));
+    let days: BTreeMap<Date, Decimal> = per_day(&trades, value, &base)
+        .into_iter()
+        .filter(|(day, _)| (first..=today).contains(day))
+        .collect();
+
+    if reporter.format(OutputFormat::Table) != OutputFormat::Table {
+        let column = match value {
+            HeatmapValue::Trades => "trades",
+            HeatmapValue::Invested => "invested",
+        };
+        let mut records = Records::new(&["day", column]);
+        for (day, v) in &days {
+            records.push(vec![day.to_string().into(), (*v).into()]);
+        }
+        return reporter.records(&records, OutputFormat::Table, &iso_utc);
+    }
+
+    reporter.print(chart::heatmap(&days, first, today).trim_end());
+    // the first of equally busy days
+    let Some((busiest, most)) = days.iter().rev().max_by_key(|(_, v)| **v) else {
+        reporter.print(&format!("No activity since {}", first));
+        return Ok(());
+    };
+    let total: Decimal = days.values().sum();
+    let (total, most) = match value {
+        HeatmapValue::Trades => (format!("{} trades", total), most.to_string()),
+        HeatmapValue::Invested => (
+            format!(
+                "{} {} invested",
+                settings.rounding.cents(total).normalize(),
+                base
+            ),
+            format!("{} {}", settings.rounding.cents(*most).normalize(), base),
+        ),
+    };
+    reporter.print(&format!(
+        "{} on {} days since {}, most on {} ({})",
+        total,
+        days.len(),
+        first,
+        busiest,
+        most
+    ));
+    Ok(())
+}
+
+/// Buys and sells of one pair within one period, weighted by amount
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PriceStats {
+    pub bought: Decimal,
+    pub bought_for: Decimal,
+    pub sold: Decimal,
+    pub sold_for: Decimal,
+}
+
+impl PriceStats {
+    /// Volume-weighted average buy price, in the quote currency, fees left out
+    pub fn avg_buy(&self) -> Option<Decimal> {
+        math::ratio(self.bought_for, self.bought)
+    }
+
+    pub fn avg_sell(&self) -> Option<Decimal> {
+        math::ratio(self.sold_for, self.sold)
+    }
+
+    /// How far the average sell is above the average buy, negative when
+    /// selling below what was paid
+    pub fn sell_vs_buy(&self) -> Option<Percent> {
+        math::change_pct(self.avg_buy()?, self.avg_sell()?)
+    }
+}
+
+/// Buys and sells per pair and per (fiscal) year, keyed by pair and year label
+pub fn prices_by_year(
+    trades: &[Trade],
+    calendar: &Calendar,
+) -> BTreeMap<(String, String), PriceStats> {
+    let mut stats: BTreeMap<(String, String), PriceStats> = BTreeMap::new();
+    for t in trades {
+        if !matches!(t.side, Side::Buy | Side::Sell) {
+            continue;
+        }
+        if !matches!(t.pair, Instrument::Pair(_)) {
+            continue;
+        }
+        let s = stats
+            .entry((
+                t.pair.to_string(),
+                calendar.label(Period::Year, t.created_at.date()),
+            ))
+            .or_default();
+        if t.side == Side::Buy {
+            s.bought += t.amount;
+            s.bought_for += t.amount * t.price;
+        } else {
+            s.sold += t.amount;
+            s.sold_for += t.amount * t.price;
+        }
+    }
+    stats
+}
+
+/// `stats --prices`: the average buy and sell price per pair and year, to
+/// tell whether buys come in high and sells low
+pub fn print_prices(name: &str, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let (_, trades) = parse_csv_file(settings.path_for(name))?;
+    let stats = prices_by_year(&trades, &Calendar::from_settings(settings));
+    if stats.is_empty() {
+        reporter.print("No buys or sells found");
+        return Ok(());
+    }
+
+    let price = |p: Option<Decimal>| p.map_or("-".to_string(), |p| format::price(p, settings));
+    let mut table = Table::new();
+    table.add_row(row![
+        "Pair",
+        "Year",
+        "Bought",
+        "Avg buy",
+        "Sold",
+        "Avg sell",
+        "Sell vs buy"
+    ]);
+    for ((pair, year), s) in &stats {
+        table.add_row(row![
+            pair,
+            year,
+            r->s.bought.normalize(),
+            r->price(s.avg_buy()),
+            r->s.sold.normalize(),
+            r->price(s.avg_sell()),
+            r->s.sell_vs_buy().map_or("-".to_string(), |p| format!("{:+.2}", p.round_dp(2)))
+        ]);
+    }
+    reporter.table(&table)?;
+    reporter.print("Prices in the quote currency of each pair, fees left out");
+    Ok(())
+}
+
+/// Print row counts of a portfolio per side and per source, and optionally per period
+///
+/// The rows of the result are the sources, or the periods with `by`.
+pub fn run(
+    name: &str,
+    by: Option<Period>,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let (config, trades) = parse_csv_file(settings.path_for(name))?;
+    if trades.is_empty() {
+        reporter.print("No trades found");
+        return Ok(());
+    }
+
+    let count = |side: Side| trades.iter().filter(|t| t.side == side).count();
+    reporter.print(&format!("Portfolio:\t{} ({})", name, config.base_currency));
+    reporter.print(&format!(
+        "Rows:\t\t{} ({} buys, {} sells, {} deposits, {} withdrawals, {} fees, {} income)",
+        trades.len(),
+        count(Side::Buy),
+        count(Side::Sell),
+        count(Side::Deposit),
+        count(Side::Withdraw),
+        count(Side::Fee),
+        count(Side::Income)
+    ));
+
+    let format = format_description!("[year]-[month]-[day]");
+    let mut table = Table::new();
+    table.add_row(row!["Source", "Rows", "First", "Last"]);
+    for (source, s) in by_source(&trades) {
+        table.add_row(row![
+            source,
+            r->s.rows,
+            s.first.format(format)?,
+            s.last.format(format)?
+        ]);
+    }
+    let Some(period) = by else {
+        return reporter.table(&table);
+    };
+    reporter.print(table.to_string().trim_end());
+
+    let calendar = Calendar::from_settings(settings);
+    let mut table = Table::new();
+    table.add_row(row![
+        "Period",
+        "Rows",
+        "Buys",
+        "Sells",
+        "Deposits",
+        "Withdrawals",
+        "Fees",
+        "Income"
+    ]);
+    for (label, s) in by_period(&trades, period, &calendar) {
+        table.add_row(row![
+            label,
+            r->s.rows(),
+            r->s.buys,
+            r->s.sells,
+            r->s.deposits,
+            r->s.withdrawals,
+            r->s.fees,
+            r->s.incomes
+        ]);
+    }
+    reporter.table(&table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::WeekStart;
+    use crate::test_utils::helpers::transactions_from;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_by_source_groups_rows_and_keeps_date_range() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee,source
+1704883200,BTC/USD,BUY,1,40000,0,import:binance
+1704969600,ETH/USD,BUY,1,2000,0,manual
+1705056000,BTC/USD,SELL,1,50000,0,import:binance
+1705142400,BTC/USD,BUY,1,45000,0,
+",
+        );
+        let stats = by_source(&trades);
+
+        assert_eq!(
+            stats.keys().collect::<Vec<_>>(),
+            vec!["(unrecorded)", "import:binance", "manual"]
+        );
+        assert_eq!(
+            stats["import:binance"],
+            SourceStats {
+                rows: 2,
+                first: datetime!(2024-01-10 10:40 UTC),
+                last: datetime!(2024-01-12 10:40 UTC),
+            }
+        );
+        assert_eq!(stats["(unrecorded)"].rows, 1);
+    }
+
+    #[test]
+    fn test_per_day_counts_rows_or_sums_buys_in_the_base() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,10
+1704886800,ETH/USD,BUY,2,2000,0
+1704886800,ETH/BTC,BUY,2,0.05,0
+1704969600,BTC/USD,SELL,1,50000,0
+",
+        );
+        let usd = Currency::new("USD").unwrap();
+        let counts = per_day(&trades, HeatmapValue::Trades, &usd);
+        assert_eq!(
+            counts.values().copied().collect::<Vec<_>>(),
+            [Decimal::from(3), Decimal::ONE]
+        );
+        let invested = per_day(&trades, HeatmapValue::Invested, &usd);
+        assert_eq!(
+            invested.into_iter().collect::<Vec<_>>(),
+            [(time::macros::date!(2024 - 01 - 10), Decimal::from(44010))]
+        );
+    }
+
+    #[test]
+    fn test_prices_by_year_weighs_prices_by_amount() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,10
+1704969600,BTC/USD,BUY,3,48000,0
+1705056000,BTC/USD,SELL,2,45000,0
+1705056000,USD,DEPOSIT,1000,1,0
+1736640000,BTC/USD,SELL,1,90000,0
+1736640000,ETH/BTC,BUY,2,0.05,0
+",
+        );
+        let stats = prices_by_year(&trades, &Calendar::default());
+        assert_eq!(
+            stats.keys().collect::<Vec<_>>(),
+            vec![
+                &("BTC/USD".to_string(), "2024".to_string()),
+                &("BTC/USD".to_string(), "2025".to_string()),
+                &("ETH/BTC".to_string(), "2025".to_string()),
+            ]
+        );
+
+        // (40000 + 3 × 48000) / 4, sold 2 at 45000
+        let year = &stats[&("BTC/USD".to_string(), "2024".to_string())];
+        assert_eq!(year.avg_buy(), Some(Decimal::from(46000)));
+        assert_eq!(year.avg_sell(), Some(Decimal::from(45000)));
+        assert_eq!(
+            year.sell_vs_buy().map(|p| p.round_dp(2)),
+            Some(Percent::new(rust_decimal::dec!(-2.17)))
+        );
+
+        let only_sells = &stats[&("BTC/USD".to_string(), "2025".to_string())];
+        assert_eq!(only_sells.avg_buy(), None);
+        assert_eq!(only_sells.sell_vs_buy(), None);
+    }
+
+    #[test]
+    fn test_by_period_follows_week_start_and_fiscal_year() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1711843200,BTC/USD,BUY,1,40000,0
+1711929600,BTC/USD,SELL,1,50000,0
+1712016000,USD,DEPOSIT,100,1,0
+",
+        );
+        // 2024-03-31 is a sunday, followed by monday 2024-04-01
+        let calendar = Calendar {
+            week_starts_on: WeekStart::Sunday,
+            year_start: time::Month::April,
+        };
+        let weeks = by_period(&trades, Period::Week, &calendar);
+        assert_eq!(weeks.keys().collect::<Vec<_>>(), vec!["2024-03-31"]);
+        assert_eq!(weeks["2024-03-31"].rows(), 3);
+
+        let years = by_period(&trades, Period::Year, &calendar);
+        assert_eq!(years.keys().collect::<Vec<_>>(), vec!["2023/24", "2024/25"]);
+        assert_eq!(
+            years["2024/25"],
+            PeriodStats {
+                sells: 1,
+                deposits: 1,
+                ..PeriodStats::default()
+            }
+        );
+
+        let weeks = by_period(&trades, Period::Week, &Calendar::default());
+        assert_eq!(
+            weeks.keys().collect::<Vec<_>>(),
+            vec!["2024-03-25", "2024-04-01"]
+        );
+    }
+}
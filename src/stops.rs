@@ -0,0 +1,283 @@
+//! Stop-loss and take-profit levels of positions
+//!
+//! Levels are prices of an asset in the portfolio base currency, kept per
+//! portfolio in `<portfolio dir>/.csvpt/stops.json`. `report` shows how far
+//! the price is from them and `watch` notifies when one is hit. Nothing is
+//! ever sold, the levels are only tracked.
+
+use crate::currency::Currency;
+use crate::math::{self, Percent};
+use crate::position_cache;
+use crate::quote::Prices;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use anyhow::{Result, bail};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+const STOPS_FILE: &str = "stops.json";
+
+/// Levels of one position, prices in the portfolio base currency
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Levels {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<Decimal>,
+}
+
+/// Levels by portfolio, then by asset
+pub type Stops = BTreeMap<String, BTreeMap<String, Levels>>;
+
+/// Which level to set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Stop-loss, hit when the price falls to it
+    Stop,
+    /// Take-profit, hit when the price rises to it
+    Target,
+}
+
+fn stops_path(settings: &Settings) -> PathBuf {
+    settings.state_dir().join(STOPS_FILE)
+}
+
+pub fn load(settings: &Settings) -> Result<Stops> {
+    let path = stops_path(settings);
+    if !path.exists() {
+        return Ok(Stops::new());
+    }
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+fn save(stops: &Stops, settings: &Settings) -> Result<()> {
+    std::fs::create_dir_all(settings.state_dir())?;
+    std::fs::write(stops_path(settings), serde_json::to_vec_pretty(stops)?)?;
+    Ok(())
+}
+
+/// `position set-stop`, `position set-target`: set a level of a position
+pub fn set(
+    name: &str,
+    asset: &str,
+    level: Level,
+    price: Decimal,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    if !settings.path_for(name).is_file() {
+        bail!("portfolio '{}' doesn't exist", name);
+    }
+    let asset = Currency::new(asset)?;
+    if price <= dec!(0) {
+        bail!("level must be positive");
+    }
+    let mut stops = load(settings)?;
+    let levels = stops
+        .entry(name.to_string())
+        .or_default()
+        .entry(asset.ticker().to_string())
+        .or_default();
+    match level {
+        Level::Stop => levels.stop = Some(price),
+        Level::Target => levels.target = Some(price),
+    }
+    if let Levels {
+        stop: Some(stop),
+        target: Some(target),
+    } = *levels
+        && stop >= target
+    {
+        bail!(
+            "stop {} of {} must be below its target {}",
+            stop,
+            asset,
+            target
+        );
+    }
+    save(&stops, settings)?;
+    let (emoji, what) = match level {
+        Level::Stop => ("🛑", "Stop"),
+        Level::Target => ("🎯", "Target"),
+    };
+    reporter.status(&format!(
+        "{} {} of {} in {} set at {}",
+        emoji, what, asset, name, price
+    ));
+    Ok(())
+}
+
+/// `position clear-levels`: remove both levels of a position
+pub fn clear(name: &str, asset: &str, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let asset = asset.to_ascii_uppercase();
+    let mut stops = load(settings)?;
+    let removed = stops
+        .get_mut(name)
+        .and_then(|levels| levels.remove(&asset))
+        .is_some();
+    if !removed {
+        bail!("{} in {} has no levels", asset, name);
+    }
+    stops.retain(|_, levels| !levels.is_empty());
+    save(&stops, settings)?;
+    reporter.status(&format!("Cleared the levels of {} in {}", asset, name));
+    Ok(())
+}
+
+fn level_or_dash(level: Option<Decimal>) -> String {
+    level.map_or("-".to_string(), |l| l.to_string())
+}
+
+/// `position levels`: the levels of all positions, or of one portfolio
+pub fn list(name: Option<&str>, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let stops = load(settings)?;
+    let mut table = Table::new();
+    table.add_row(row!["Portfolio", "Asset", "Stop", "Target"]);
+    let mut any = false;
+    for (portfolio, levels) in stops.iter().filter(|(p, _)| name.is_none_or(|n| n == *p)) {
+        for (asset, levels) in levels {
+            table.add_row(row![
+                portfolio,
+                asset,
+                level_or_dash(levels.stop),
+                level_or_dash(levels.target)
+            ]);
+            any = true;
+        }
+    }
+    if !any {
+        reporter.print("No stop or target levels set");
+        return Ok(());
+    }
+    reporter.table(&table)?;
+    Ok(())
+}
+
+/// Message when `price` of `asset` hit one of its levels
+pub fn check(asset: &str, levels: &Levels, price: Decimal) -> Option<String> {
+    match (levels.stop, levels.target) {
+        (Some(stop), _) if price <= stop => {
+            Some(format!("{} at {} hit its stop at {}", asset, price, stop))
+        }
+        (_, Some(target)) if price >= target => Some(format!(
+            "{} at {} reached its target at {}",
+            asset, price, target
+        )),
+        _ => None,
+    }
+}
+
+/// Signed distance from `price` to `level`, in percent of the price; `None`
+/// at a price of 0
+pub fn distance_pct(price: Decimal, level: Decimal) -> Option<Percent> {
+    math::change_pct(price, level)
+}
+
+/// Section of `report`: the levels of the portfolio's positions with the
+/// distance of the current price to them, nothing when none are set
+pub fn report(
+    name: &str,
+    prices: &dyn Prices,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let stops = load(settings)?;
+    let Some(levels) = stops.get(name).filter(|levels| !levels.is_empty()) else {
+        return Ok(());
+    };
+    let base = position_cache::load(settings.path_for(name), settings)?.base;
+
+    let mut table = Table::new();
+    table.add_row(row![
+        "Asset",
+        "Price",
+        "Stop",
+        "To stop",
+        "Target",
+        "To target"
+    ]);
+    for (asset, levels) in levels {
+        let price = prices.price_in(&Currency::new(asset)?, base.ticker())?;
+        let distance = |level: Option<Decimal>| match level {
+            Some(level) if price > dec!(0) => {
+                distance_pct(price, level).map_or("-".to_string(), |p| format!("{:+.2}", p))
+            }
+            _ => "-".to_string(),
+        };
+        table.add_row(row![
+            asset,
+            format!("{} {}", price.round_dp(2), base),
+            level_or_dash(levels.stop),
+            r->distance(levels.stop),
+            level_or_dash(levels.target),
+            r->distance(levels.target)
+        ]);
+    }
+    reporter.print(table.to_string().trim_end());
+    for (asset, levels) in levels {
+        let price = prices.price_in(&Currency::new(asset)?, base.ticker())?;
+        if let Some(message) = check(asset, levels, price) {
+            reporter.print(&format!("⚠️  {}", message));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::Silent;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_levels_are_stored_per_position_and_checked() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        create_test_csv(&dir, "main", "created_at,pair,side,amount,price,fee\n");
+
+        set("main", "btc", Level::Stop, dec!(74000), &Silent, &settings).unwrap();
+        set(
+            "main",
+            "BTC",
+            Level::Target,
+            dec!(100000),
+            &Silent,
+            &settings,
+        )
+        .unwrap();
+        assert!(set("main", "BTC", Level::Stop, dec!(120000), &Silent, &settings).is_err());
+        assert!(set("other", "BTC", Level::Stop, dec!(1), &Silent, &settings).is_err());
+        let levels = load(&settings).unwrap()["main"]["BTC"];
+        assert_eq!(
+            levels,
+            Levels {
+                stop: Some(dec!(74000)),
+                target: Some(dec!(100000)),
+            }
+        );
+
+        assert_eq!(check("BTC", &levels, dec!(80000)), None);
+        assert_eq!(
+            check("BTC", &levels, dec!(73500)).unwrap(),
+            "BTC at 73500 hit its stop at 74000"
+        );
+        assert_eq!(
+            check("BTC", &levels, dec!(100000)).unwrap(),
+            "BTC at 100000 reached its target at 100000"
+        );
+        assert_eq!(
+            distance_pct(dec!(80000), dec!(74000)),
+            Some(Percent::new(dec!(-7.5)))
+        );
+        assert_eq!(distance_pct(dec!(0), dec!(74000)), None);
+
+        clear("main", "btc", &Silent, &settings).unwrap();
+        assert!(load(&settings).unwrap().is_empty());
+        assert!(clear("main", "BTC", &Silent, &settings).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use crate::currency::{Currency, CurrencyType};
+use crate::http;
+use crate::math::{self, Percent};
+use crate::position_cache;
+use crate::quote;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use anyhow::{Context, Result};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use thousands::Separable;
+
+const SUPPLY_CACHE_FILE: &str = "supply.json";
+
+/// Supply and market cap of one asset, `None` where the provider has no data
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SupplyInfo {
+    pub market_cap: Option<Decimal>,
+    pub circulating: Option<Decimal>,
+    pub max: Option<Decimal>,
+}
+
+impl SupplyInfo {
+    /// Share of the max supply in circulation
+    pub fn circulating_pct(&self) -> Option<Percent> {
+        math::percent_of(self.circulating?, self.max?)
+    }
+}
+
+/// Supply data of one day, market caps in `currency`
+///
+/// Cached in `<state dir>/supply.json`; supply changes slowly, so the data is
+/// refetched only on the next day or for tickers not fetched yet.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SupplyCache {
+    /// Day of the fetch, `YYYY-MM-DD`
+    pub date: String,
+    pub currency: String,
+    pub assets: BTreeMap<String, SupplyInfo>,
+}
+
+/// One entry of the CoinGecko `coins/markets` response
+#[derive(Debug, Deserialize)]
+struct MarketEntry {
+    id: String,
+    market_cap: Option<f64>,
+    circulating_supply: Option<f64>,
+    max_supply: Option<f64>,
+}
+
+fn to_decimal(value: Option<f64>) -> Option<Decimal> {
+    value.and_then(|v| Decimal::from_f64_retain(v).map(|d| d.round_dp(0)))
+}
+
+fn cache_path(settings: &Settings) -> PathBuf {
+    settings.state_dir().join(SUPPLY_CACHE_FILE)
+}
+
+fn fetch(tickers: &[String], currency: &str) -> Result<BTreeMap<String, SupplyInfo>> {
+    let ids = quote::to_ids(tickers)?;
+    let id_ticker: BTreeMap<&String, &String> = ids.iter().zip(tickers).collect();
+    let url = format!(
+        "https://api.coingecko.com/api/v3/coins/markets?vs_currency={}&ids={}",
+        currency.to_ascii_lowercase(),
+        ids.join(",")
+    );
+    let entries: Vec<MarketEntry> = http::get_json(&url).context("fetching supply data")?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|e| {
+            let ticker = id_ticker.get(&e.id)?;
+            Some((
+                ticker.to_string(),
+                SupplyInfo {
+                    market_cap: to_decimal(e.market_cap),
+                    circulating: to_decimal(e.circulating_supply),
+                    max: to_decimal(e.max_supply),
+                },
+            ))
+        })
+        .collect())
+}
+
+/// Supply data of the crypto `tickers`, from today's cache when possible
+/// unless `--no-cache` was given
+pub fn supply_info(
+    tickers: &[String],
+    currency: &str,
+    settings: &Settings,
+) -> Result<BTreeMap<String, SupplyInfo>> {
+    let path = cache_path(settings);
+    let today = settings.clock.today().to_string();
+    let mut cache = std::fs::read(&path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<SupplyCache>(&data).ok())
+        .filter(|c| !settings.no_cache && c.date == today && c.currency == currency)
+        .unwrap_or(SupplyCache {
+            date: today,
+            currency: currency.to_string(),
+            assets: BTreeMap::new(),
+        });
+
+    let missing: Vec<String> = tickers
+        .iter()
+        .filter(|t| !cache.assets.contains_key(*t))
+        .cloned()
+        .collect();
+    if !missing.is_empty() {
+        let fetched = fetch(&missing, currency)?;
+        // remember tickers without data too, so they aren't refetched today
+        for ticker in missing {
+            let info = fetched.get(&ticker).cloned().unwrap_or_default();
+            cache.assets.insert(ticker, info);
+        }
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(&path, serde_json::to_vec_pretty(&cache)?)?;
+    }
+
+    Ok(tickers
+        .iter()
+        .filter_map(|t| Some((t.clone(), cache.assets.get(t)?.clone())))
+        .collect())
+}
+
+/// Supply and market cap of crypto tickers, others are skipped; `None`
+/// without crypto tickers
+pub fn supply_table(
+    currencies: &[Currency],
+    currency: &str,
+    settings: &Settings,
+) -> Result<Option<Table>> {
+    let tickers: Vec<String> = currencies
+        .iter()
+        .filter(|c| c.currency_type() == CurrencyType::Crypto)
+        .map(|c| c.to_string())
+        .collect();
+    if tickers.is_empty() {
+        return Ok(None);
+    }
+    let info = supply_info(&tickers, currency, settings)?;
+
+    let amount = |d: Option<Decimal>| {
+        d.map(|d| d.separate_with_commas())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let mut table = Table::new();
+    table.add_row(row![
+        "Ticker",
+        "Market cap",
+        "Circulating",
+        "Max supply",
+        "Circulating %"
+    ]);
+    for (ticker, s) in &info {
+        table.add_row(row![
+            ticker,
+            r->s.market_cap
+                .map(|m| format!("{} {}", m.separate_with_commas(), currency))
+                .unwrap_or_else(|| "-".to_string()),
+            r->amount(s.circulating),
+            r->amount(s.max),
+            r->s.circulating_pct()
+                .map(|p| format!("{:.2}", p))
+                .unwrap_or_else(|| "-".to_string())
+        ]);
+    }
+    Ok(Some(table))
+}
+
+/// Supply table of the crypto positions of a portfolio, for `report --supply`
+pub fn report(name: &str, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let pf = position_cache::load(settings.path_for(name), settings)?;
+    let mut currencies: Vec<Currency> = pf.positions.keys().cloned().collect();
+    currencies.sort_by_key(|c| c.to_string());
+    if let Some(table) = supply_table(&currencies, pf.base.ticker(), settings)? {
+        reporter.print("");
+        reporter.print(table.to_string().trim_end());
+    }
+    Ok(())
+}
+
+/// Print what is known about tickers: type, and supply data unless `offline`
+pub fn ticker_info(
+    tickers: &[String],
+    offline: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let currencies: Vec<Currency> = tickers
+        .iter()
+        .map(|t| Currency::new(t))
+        .collect::<Result<_>>()?;
+    for c in &currencies {
+        reporter.print(&format!("{}:\t{:?}", c, c.currency_type()));
+    }
+    if !offline
+        && let Some(table) = supply_table(&currencies, settings.base_currency.ticker(), settings)?
+    {
+        reporter.table(&table)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::test_utils::helpers::create_test_settings;
+    use rust_decimal::dec;
+    use tempfile::TempDir;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_circulating_pct_needs_max_supply() {
+        let btc = SupplyInfo {
+            market_cap: Some(dec!(1_300_000_000_000)),
+            circulating: Some(dec!(19_950_000)),
+            max: Some(dec!(21_000_000)),
+        };
+        assert_eq!(btc.circulating_pct().unwrap().round_dp(2).value(), dec!(95));
+        let eth = SupplyInfo {
+            max: None,
+            ..btc.clone()
+        };
+        assert_eq!(eth.circulating_pct(), None);
+    }
+
+    #[test]
+    fn test_supply_info_served_from_todays_cache() {
+        let dir = TempDir::new().unwrap();
+        let settings = Settings {
+            clock: Clock::Frozen(datetime!(2024-03-15 18:00 UTC)),
+            ..(*create_test_settings(dir.path().to_path_buf())).clone()
+        };
+        let btc = SupplyInfo {
+            market_cap: None,
+            circulating: Some(dec!(19_950_000)),
+            max: Some(dec!(21_000_000)),
+        };
+        let cache = SupplyCache {
+            date: "2024-03-15".to_string(),
+            currency: "USD".to_string(),
+            assets: BTreeMap::from([("BTC".to_string(), btc.clone())]),
+        };
+        std::fs::create_dir_all(settings.state_dir()).unwrap();
+        std::fs::write(cache_path(&settings), serde_json::to_vec(&cache).unwrap()).unwrap();
+
+        let info = supply_info(&["BTC".to_string()], "USD", &settings).unwrap();
+        assert_eq!(info["BTC"], btc);
+    }
+}
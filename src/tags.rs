@@ -0,0 +1,255 @@
+use crate::currency::Currency;
+use crate::quote::quote_in_base;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::trade::{Instrument, Side, Trade, find_by_id, parse_csv_file, sort_chronologically};
+use anyhow::{Result, bail};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use rust_decimal::dec;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use thousands::Separable;
+
+/// Tags of a portfolio's trades, by `Trade::id`
+pub type Tags = BTreeMap<String, BTreeSet<String>>;
+
+/// `<state dir>/tags/<portfolio>.json`
+fn tags_path(name: &str, settings: &Settings) -> PathBuf {
+    settings
+        .state_dir()
+        .join("tags")
+        .join(name)
+        .with_extension("json")
+}
+
+pub fn load(name: &str, settings: &Settings) -> Result<Tags> {
+    let path = tags_path(name, settings);
+    if !path.exists() {
+        return Ok(Tags::new());
+    }
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+fn save(name: &str, tags: &Tags, settings: &Settings) -> Result<()> {
+    let path = tags_path(name, settings);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(path, serde_json::to_vec_pretty(tags)?)?;
+    Ok(())
+}
+
+/// Move the tags of trade `from` to each of the trades `to`, after the
+/// trade was rewritten, e.g. split
+pub(crate) fn carry_over(name: &str, from: &str, to: &[String], settings: &Settings) -> Result<()> {
+    let mut all = load(name, settings)?;
+    let Some(tags) = all.remove(from) else {
+        return Ok(());
+    };
+    for tx in to {
+        all.entry(tx.clone())
+            .or_default()
+            .extend(tags.iter().cloned());
+    }
+    save(name, &all, settings)
+}
+
+/// Add `tags` to the trade with id (or id prefix) `tx`, or remove them
+pub fn tag(
+    name: &str,
+    tx: &str,
+    tags: &[String],
+    remove: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let (_, trades) = parse_csv_file(settings.path_for(name))?;
+    let id = find_by_id(&trades, tx)?.id();
+    let mut all = load(name, settings)?;
+    let trade_tags = all.entry(id.clone()).or_default();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() || tag.contains(char::is_whitespace) {
+            bail!("invalid tag '{}', expected a single word", tag);
+        }
+        if remove {
+            trade_tags.remove(&tag);
+        } else {
+            trade_tags.insert(tag);
+        }
+    }
+    let now: Vec<String> = trade_tags.iter().cloned().collect();
+    all.retain(|_, tags| !tags.is_empty());
+    save(name, &all, settings)?;
+    if now.is_empty() {
+        reporter.status(&format!("🏷️  Trade {} has no tags", id));
+    } else {
+        reporter.status(&format!("🏷️  Trade {} tagged {}", id, now.join(", ")));
+    }
+    Ok(())
+}
+
+/// Outcome of the trades carrying one tag, in the portfolio base currency
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TagPerformance {
+    pub trades: usize,
+    /// Paid for buys, fees included
+    pub invested: Decimal,
+    /// Sell proceeds over the average cost of the tag's units sold
+    pub realized: Decimal,
+    /// Units still held from the tag's buys, with their cost, by ticker
+    pub open: BTreeMap<String, (Decimal, Decimal)>,
+}
+
+impl TagPerformance {
+    /// Value of the open units at `price_of` less their cost
+    pub fn unrealized(
+        &self,
+        mut price_of: impl FnMut(&Currency) -> Result<Decimal>,
+    ) -> Result<Decimal> {
+        let mut pnl = dec!(0);
+        for (ticker, (quantity, cost)) in &self.open {
+            pnl += quantity * price_of(&Currency::new(ticker)?)? - cost;
+        }
+        Ok(pnl)
+    }
+}
+
+/// Performance per tag of the buys and sells quoted in `base`
+///
+/// Each tag is tracked as its own position at average cost, so selling
+/// units tagged `degen` doesn't touch the cost of units tagged `dca`.
+/// Trades in other quote currencies, and deposits and withdrawals, are left out.
+pub fn performance(
+    trades: &[Trade],
+    tags: &Tags,
+    base: &Currency,
+) -> BTreeMap<String, TagPerformance> {
+    let mut by_tag: BTreeMap<String, TagPerformance> = BTreeMap::new();
+    let mut trades: Vec<&Trade> = trades.iter().collect();
+    sort_chronologically(&mut trades);
+    for t in trades {
+        let Instrument::Pair(pair) = &t.pair else {
+            continue;
+        };
+        if pair.quote != *base {
+            continue;
+        }
+        let Some(trade_tags) = tags.get(&t.id()) else {
+            continue;
+        };
+        for tag in trade_tags {
+            let p = by_tag.entry(tag.clone()).or_default();
+            let (quantity, cost) = p.open.entry(pair.base.to_string()).or_default();
+            match t.side {
+                Side::Buy => {
+                    let paid = t.amount * t.price + t.fee;
+                    *quantity += t.amount;
+                    *cost += paid;
+                    p.invested += paid;
+                }
+                Side::Sell => {
+                    let sold = t.amount.min(*quantity);
+                    let sold_cost = if quantity.is_zero() {
+                        dec!(0)
+                    } else {
+                        *cost * sold / *quantity
+                    };
+                    *quantity -= sold;
+                    *cost -= sold_cost;
+                    p.realized += t.amount * t.price - t.fee - sold_cost;
+                }
+                Side::Deposit | Side::Withdraw | Side::Fee | Side::Income => continue,
+            }
+            p.trades += 1;
+        }
+    }
+    for p in by_tag.values_mut() {
+        p.open.retain(|_, (quantity, _)| !quantity.is_zero());
+    }
+    by_tag
+}
+
+/// `stats --by-tag`: invested, realized and unrealized PnL per tag
+pub fn report(name: &str, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let (config, trades) = parse_csv_file(settings.path_for(name))?;
+    let base = Currency::new(&config.base_currency)?;
+    let tags = load(name, settings)?;
+    let by_tag = performance(&trades, &tags, &base);
+    if by_tag.is_empty() {
+        reporter.print(&format!(
+            "No tagged trades, tag them with `tag --name {} --tx <id> <tags>`",
+            name
+        ));
+        return Ok(());
+    }
+
+    let money = |d: Decimal| d.round_dp(2).normalize().separate_with_commas();
+    let mut table = Table::new();
+    table.add_row(row![
+        "Tag",
+        "Trades",
+        "Invested",
+        "Realized PnL",
+        "Unrealized PnL"
+    ]);
+    for (tag, p) in &by_tag {
+        let unrealized = p.unrealized(|asset| quote_in_base(asset, &config.base_currency))?;
+        table.add_row(row![
+            tag,
+            r->p.trades,
+            r->money(p.invested),
+            r->money(p.realized),
+            r->money(unrealized)
+        ]);
+    }
+    reporter.table(&table)?;
+    reporter.print(&format!(
+        "Amounts in {}, trades in other quote currencies aren't included",
+        base
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::transactions_from;
+
+    #[test]
+    fn test_performance_tracks_each_tag_at_its_own_average_cost() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704067200,BTC/USD,BUY,1,40000,10
+1706745600,BTC/USD,BUY,1,20000,10
+1709251200,BTC/USD,SELL,1,50000,0
+1709337600,ETH/BTC,BUY,1,0.05,0
+",
+        );
+        let tag = |i: usize, names: &[&str]| {
+            (
+                trades[i].id(),
+                names.iter().map(|n| n.to_string()).collect::<BTreeSet<_>>(),
+            )
+        };
+        let tags: Tags = [
+            tag(0, &["dca"]),
+            tag(1, &["dca", "dip"]),
+            tag(2, &["dca"]),
+            tag(3, &["dca"]),
+        ]
+        .into();
+        let usd = Currency::new("USD").unwrap();
+        let by_tag = performance(&trades, &tags, &usd);
+
+        let dca = &by_tag["dca"];
+        assert_eq!(dca.trades, 3);
+        assert_eq!(dca.invested, dec!(60020));
+        // sold one of two units at an average cost of 30010
+        assert_eq!(dca.realized, dec!(19990));
+        assert_eq!(dca.open["BTC"], (dec!(1), dec!(30010)));
+        assert_eq!(dca.unrealized(|_| Ok(dec!(45000))).unwrap(), dec!(14990));
+
+        let dip = &by_tag["dip"];
+        assert_eq!((dip.invested, dip.realized), (dec!(20010), dec!(0)));
+    }
+}
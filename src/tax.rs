@@ -0,0 +1,321 @@
+use crate::currency::{Currency, CurrencyType};
+use crate::fx::{FxConverter, fx_currency};
+use crate::period::Calendar;
+use crate::reporter::Reporter;
+use crate::settings::{Rounding, Settings};
+use crate::trade::{Side, Trade, parse_csv_file, sort_chronologically};
+use anyhow::{Result, anyhow, bail};
+use prettytable::{Table, row};
+use rust_decimal::Decimal;
+use std::collections::{BTreeSet, HashMap};
+use thousands::Separable;
+use time::macros::format_description;
+use time::{Month, OffsetDateTime};
+
+/// Sale of a crypto asset, valued in the tax currency
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disposal {
+    pub at: OffsetDateTime,
+    pub asset: Currency,
+    pub amount: Decimal,
+    /// Sale value net of fees, at the FX rate of the sale date
+    pub proceeds: Decimal,
+    /// Average cost of the sold units, each buy at the FX rate of its date
+    pub cost: Decimal,
+}
+
+impl Disposal {
+    pub fn gain(&self) -> Decimal {
+        self.proceeds - self.cost
+    }
+
+    /// Proceeds and cost rounded to cents, as reported for each disposal
+    pub fn rounded(&self, rounding: Rounding) -> Disposal {
+        Disposal {
+            proceeds: rounding.cents(self.proceeds),
+            cost: rounding.cents(self.cost),
+            ..self.clone()
+        }
+    }
+}
+
+/// Fiat currency of the amounts of a row: the quote of a trade, the
+/// portfolio base for cash rows (valued in the base)
+fn row_currency<'a>(trade: &'a Trade, base: &'a Currency) -> Result<Option<&'a str>> {
+    let currency = match trade.side {
+        Side::Buy | Side::Sell => &trade.pair.trading_pair(trade.side)?.quote,
+        Side::Deposit | Side::Withdraw | Side::Fee | Side::Income => base,
+    };
+    Ok(fx_currency(currency))
+}
+
+/// Disposals of crypto assets at average cost, converted into `tax_currency`
+///
+/// Every buy adds its cost converted at the rate of the buy date, every sell
+/// converts its proceeds at the rate of the sale date. Crypto deposits and
+/// income are acquisitions at their recorded value, withdrawals and fees
+/// reduce the holding without a disposal. Fiat and stable coin positions aren't taxed.
+pub fn disposals(
+    trades: &[Trade],
+    base: &Currency,
+    tax_currency: &str,
+    fx: &FxConverter,
+) -> Result<Vec<Disposal>> {
+    let mut trades: Vec<&Trade> = trades.iter().collect();
+    sort_chronologically(&mut trades);
+
+    // asset -> (quantity, cost in the tax currency)
+    let mut held: HashMap<Currency, (Decimal, Decimal)> = HashMap::new();
+    let mut disposals = Vec::new();
+
+    for t in trades {
+        let asset = match t.side {
+            Side::Buy | Side::Sell => &t.pair.trading_pair(t.side)?.base,
+            Side::Deposit | Side::Withdraw | Side::Fee | Side::Income => t.pair.asset(t.side)?,
+        };
+        if asset.currency_type() != CurrencyType::Crypto {
+            continue;
+        }
+        let currency = row_currency(t, base)?.ok_or(anyhow!(
+            "{} {} is valued in crypto, only fiat or stable coin quotes can be converted",
+            t.side,
+            t.pair
+        ))?;
+        let day = t.created_at.date();
+        let to_tax = |amount: Decimal| fx.convert(amount, currency, tax_currency, day);
+        let (quantity, cost) = held.entry(asset.clone()).or_default();
+
+        match t.side {
+            Side::Buy => {
+                *quantity += t.amount;
+                *cost += to_tax(t.amount * t.price + t.fee)?;
+            }
+            Side::Deposit | Side::Income => {
+                *quantity += t.amount;
+                *cost += to_tax(t.amount * t.price)?;
+            }
+            Side::Sell | Side::Withdraw | Side::Fee => {
+                if *quantity < t.amount {
+                    bail!(
+                        "{} {} {} on {} but only {} held",
+                        t.side,
+                        t.amount,
+                        asset,
+                        day,
+                        quantity
+                    );
+                }
+                let sold_cost = *cost * t.amount / *quantity;
+                *quantity -= t.amount;
+                *cost -= sold_cost;
+                if t.side == Side::Sell {
+                    disposals.push(Disposal {
+                        at: t.created_at,
+                        asset: asset.clone(),
+                        amount: t.amount,
+                        proceeds: to_tax(t.amount * t.price - t.fee)?,
+                        cost: sold_cost,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(disposals)
+}
+
+/// The ECB rates `disposals` needs to convert `trades` into `tax_currency`,
+/// loaded up front for the whole period, one request per currency
+pub fn load_fx(
+    trades: &[Trade],
+    base: &Currency,
+    tax_currency: &str,
+    settings: &Settings,
+) -> Result<FxConverter> {
+    let mut currencies: BTreeSet<&str> = BTreeSet::new();
+    for t in trades {
+        if let Some(currency) = row_currency(t, base)?.filter(|c| *c != tax_currency) {
+            currencies.insert(currency);
+        }
+    }
+    match (
+        trades.iter().map(|t| t.created_at).min(),
+        trades.iter().map(|t| t.created_at).max(),
+    ) {
+        (Some(first), Some(last)) if !currencies.is_empty() => {
+            currencies.insert(tax_currency);
+            let currencies: Vec<&str> = currencies.into_iter().collect();
+            FxConverter::load(&currencies, first.date(), last.date(), settings)
+        }
+        _ => Ok(FxConverter::default()),
+    }
+}
+
+/// Print the disposals of a portfolio with their gains in `tax_currency`
+/// (the portfolio base by default), optionally only of the fiscal year
+/// starting in `year`
+pub fn run(
+    name: &str,
+    year: Option<i32>,
+    tax_currency: Option<&str>,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let (config, trades) = parse_csv_file(settings.path_for(name))?;
+    let base = Currency::new(&config.base_currency)?;
+    let tax = Currency::new(tax_currency.unwrap_or(&config.base_currency))?;
+    if tax.currency_type() != CurrencyType::Fiat {
+        bail!("tax currency must be fiat, got {}", tax);
+    }
+    let tax = tax.ticker();
+    let fx = load_fx(&trades, &base, tax, settings)?;
+
+    let calendar = Calendar::from_settings(settings);
+    let disposals: Vec<Disposal> = disposals(&trades, &base, tax, &fx)?
+        .into_iter()
+        .filter(|d| year.is_none_or(|y| calendar.year_of(d.at.date()) == y))
+        .map(|d| d.rounded(settings.rounding))
+        .collect();
+    if let Some(year) = year.filter(|_| calendar.year_start != Month::January) {
+        let (start, end) = calendar.year_range(year)?;
+        reporter.print(&format!(
+            "Tax year {}: {} to {}",
+            calendar.year_label(year),
+            start,
+            end.previous_day().unwrap_or(end)
+        ));
+    }
+    if disposals.is_empty() {
+        reporter.print("No disposals found");
+        return Ok(());
+    }
+
+    let format = format_description!("[year]-[month]-[day]");
+    // amounts are already in cents, totals add up the rounded disposals
+    let money = |d: Decimal| format!("{} {}", d.separate_with_commas(), tax);
+    let mut table = Table::new();
+    table.add_row(row!["Date", "Asset", "Amount", "Proceeds", "Cost", "Gain"]);
+    for d in &disposals {
+        table.add_row(row![
+            d.at.format(format)?,
+            d.asset,
+            d.amount,
+            r->money(d.proceeds),
+            r->money(d.cost),
+            r->money(d.gain())
+        ]);
+    }
+    reporter.table(&table)?;
+
+    let total = |f: fn(&Disposal) -> Decimal| disposals.iter().map(f).sum::<Decimal>();
+    reporter.print(&format!("Proceeds:\t{}", money(total(|d| d.proceeds))));
+    reporter.print(&format!("Cost:\t\t{}", money(total(|d| d.cost))));
+    reporter.print(&format!("Gain:\t\t{}", money(total(Disposal::gain))));
+    if !fx.is_empty() {
+        reporter.print("Converted at the ECB reference rates of each trade date");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fx::EcbRates;
+    use crate::test_utils::helpers::transactions_from;
+    use rust_decimal::dec;
+    use std::collections::BTreeMap;
+
+    fn usd_rates() -> FxConverter {
+        FxConverter::from_rates(vec![EcbRates {
+            currency: "USD".to_string(),
+            from: "2024-01-01".to_string(),
+            to: "2024-03-31".to_string(),
+            rates: BTreeMap::from([
+                ("2024-01-10".to_string(), dec!(1.25)),
+                ("2024-03-15".to_string(), dec!(1.0)),
+            ]),
+        }])
+    }
+
+    #[test]
+    fn test_disposals_convert_cost_and_proceeds_at_their_dates() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,2,40000,0
+1710460800,BTC/USDT,SELL,1,50000,100
+",
+        );
+        let usd = Currency::new("USD").unwrap();
+        let disposals = disposals(&trades, &usd, "EUR", &usd_rates()).unwrap();
+
+        assert_eq!(disposals.len(), 1);
+        let d = &disposals[0];
+        // bought for 80,000 USD at 1.25 USD per EUR, sold at parity
+        assert_eq!(d.cost, dec!(32000));
+        assert_eq!(d.proceeds, dec!(49900));
+        assert_eq!(d.gain(), dec!(17900));
+    }
+
+    #[test]
+    fn test_disposals_in_base_need_no_rates() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704883200,USD,DEPOSIT,1000,1,0
+1704883200,ETH/USD,BUY,1,2000,10
+1704969600,ETH,DEPOSIT,1,3000,0
+1710460800,ETH/USD,SELL,1,4000,0
+1710547200,ETH,WITHDRAW,0.5,1,0
+",
+        );
+        let usd = Currency::new("USD").unwrap();
+        let disposals = disposals(&trades, &usd, "USD", &FxConverter::default()).unwrap();
+
+        assert_eq!(disposals.len(), 1);
+        assert_eq!(disposals[0].cost, dec!(2505));
+        assert_eq!(disposals[0].gain(), dec!(1495));
+    }
+
+    #[test]
+    fn test_disposals_round_to_cents_half_even_or_half_up() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704883200,ETH/USD,BUY,1,1000.125,0
+1710460800,ETH/USD,SELL,0.5,2000.25,0
+1710547200,ETH/USD,SELL,0.5,2000.27,0
+",
+        );
+        let usd = Currency::new("USD").unwrap();
+        let disposals = disposals(&trades, &usd, "USD", &FxConverter::default()).unwrap();
+        // cost 500.0625 each, proceeds 1000.125 and 1000.135
+        let amounts = |rounding| -> Vec<(Decimal, Decimal)> {
+            disposals
+                .iter()
+                .map(|d| d.rounded(rounding))
+                .map(|d| (d.proceeds, d.cost))
+                .collect()
+        };
+        assert_eq!(
+            amounts(Rounding::HalfEven),
+            vec![(dec!(1000.12), dec!(500.06)), (dec!(1000.14), dec!(500.06))]
+        );
+        assert_eq!(
+            amounts(Rounding::HalfUp),
+            vec![(dec!(1000.13), dec!(500.06)), (dec!(1000.14), dec!(500.06))]
+        );
+        assert_eq!(Rounding::HalfUp.cents(dec!(-0.125)), dec!(-0.13));
+        assert_eq!(Rounding::HalfEven.cents(dec!(-0.125)), dec!(-0.12));
+    }
+
+    #[test]
+    fn test_disposals_reject_crypto_quotes() {
+        let trades = transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704883200,ETH/BTC,BUY,1,0.05,0
+",
+        );
+        let usd = Currency::new("USD").unwrap();
+        let err = disposals(&trades, &usd, "USD", &FxConverter::default()).unwrap_err();
+        assert!(err.to_string().contains("valued in crypto"), "{err}");
+    }
+}
@@ -49,10 +49,66 @@ pub mod helpers {
         file_path
     }
 
+    /// Write a workbook of XML `parts` as a stored (uncompressed) zip archive
+    pub fn create_test_xlsx(dir: &TempDir, name: &str, parts: &[(&str, &str)]) -> PathBuf {
+        fn crc32(data: &[u8]) -> u32 {
+            !data.iter().fold(!0u32, |crc, &b| {
+                (0..8).fold(crc ^ b as u32, |c, _| {
+                    if c & 1 == 1 {
+                        (c >> 1) ^ 0xedb8_8320
+                    } else {
+                        c >> 1
+                    }
+                })
+            })
+        }
+        let (mut zip, mut central) = (Vec::new(), Vec::new());
+        for (part, content) in parts {
+            let offset = zip.len() as u32;
+            let (crc, size) = (crc32(content.as_bytes()), content.len() as u32);
+            // version 2.0, no flags, stored, no time
+            let fields = [&20u16.to_le_bytes()[..], &[0; 8], &crc.to_le_bytes()].concat();
+            let sizes = [size.to_le_bytes(), size.to_le_bytes()].concat();
+            let name_len = (part.len() as u16).to_le_bytes();
+
+            zip.extend(0x0403_4b50u32.to_le_bytes());
+            zip.extend(&fields);
+            zip.extend(&sizes);
+            zip.extend(name_len);
+            zip.extend([0; 2]);
+            zip.extend(part.as_bytes());
+            zip.extend(content.as_bytes());
+
+            central.extend(0x0201_4b50u32.to_le_bytes());
+            central.extend(20u16.to_le_bytes());
+            central.extend(&fields);
+            central.extend(&sizes);
+            central.extend(name_len);
+            central.extend([0; 12]);
+            central.extend(offset.to_le_bytes());
+            central.extend(part.as_bytes());
+        }
+        let count = (parts.len() as u16).to_le_bytes();
+        let (central_offset, central_len) = (zip.len() as u32, central.len() as u32);
+        zip.extend(central);
+        zip.extend(0x0605_4b50u32.to_le_bytes());
+        zip.extend([0; 4]);
+        zip.extend(count);
+        zip.extend(count);
+        zip.extend(central_len.to_le_bytes());
+        zip.extend(central_offset.to_le_bytes());
+        zip.extend([0; 2]);
+
+        let file_path = dir.path().join(format!("{}.xlsx", name));
+        fs::write(&file_path, zip).unwrap();
+        file_path
+    }
+
     pub fn create_test_settings(base_path: PathBuf) -> Rc<Settings> {
         Rc::new(Settings {
             portfolio_dir: base_path,
             base_currency: Currency::new("USD").unwrap(),
+            ..Settings::default()
         })
     }
 
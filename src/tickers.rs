@@ -0,0 +1,135 @@
+//! Ticker search
+//!
+//! `tickers <query>` looks a coin up in the CoinGecko registry
+//! (`data/coingecko.csv`) by symbol, name or id, so the exact ticker `add-tx`
+//! expects can be found from "doge" or a misspelled "etherum". Matches are
+//! ranked by how closely they match, then by market cap.
+
+use crate::config_check::distance;
+use crate::currency::Currency;
+use crate::quote;
+use crate::reporter::Reporter;
+use anyhow::Result;
+use prettytable::{Table, row};
+
+/// A coin of the registry matching a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickerMatch {
+    /// Position by market cap, 1 is the largest
+    pub rank: usize,
+    pub id: String,
+    pub symbol: String,
+    pub name: String,
+    /// Whether `add-tx` and valuation accept the symbol
+    pub supported: bool,
+}
+
+/// How well `query` matches a coin, lower is closer: the exact symbol,
+/// the exact name or id, a prefix, a substring, the letters in order, or
+/// a typo of the symbol or name
+fn score(query: &str, symbol: &str, name: &str, id: &str) -> Option<usize> {
+    let (symbol, name) = (symbol.to_lowercase(), name.to_lowercase());
+    let fields = [symbol.as_str(), name.as_str(), id];
+    if symbol == query {
+        Some(0)
+    } else if name == query || id == query {
+        Some(1)
+    } else if fields.iter().any(|f| f.starts_with(query)) {
+        Some(2)
+    } else if fields.iter().any(|f| f.contains(query)) {
+        Some(3)
+    } else if fields.iter().any(|f| is_subsequence(query, f)) {
+        Some(4)
+    } else if [symbol.as_str(), name.as_str()]
+        .iter()
+        .any(|f| distance(query, f) <= (query.chars().count() / 4).max(1))
+    {
+        Some(5)
+    } else {
+        None
+    }
+}
+
+/// Whether the characters of `query` appear in `text` in order
+fn is_subsequence(query: &str, text: &str) -> bool {
+    let mut chars = text.chars();
+    query.chars().all(|q| chars.any(|c| c == q))
+}
+
+/// Coins of the registry matching `query`, closest and largest first
+pub fn search(query: &str) -> Result<Vec<TickerMatch>> {
+    let query = query.trim().to_lowercase();
+    let mut matches: Vec<(usize, TickerMatch)> = quote::registry()?
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, coin)| {
+            let score = score(&query, &coin.symbol, &coin.name, &coin.id)?;
+            Some((
+                score,
+                TickerMatch {
+                    rank: i + 1,
+                    supported: Currency::is_valid(&coin.symbol),
+                    id: coin.id,
+                    symbol: coin.symbol.to_uppercase(),
+                    name: coin.name,
+                },
+            ))
+        })
+        .collect();
+    matches.sort_by_key(|(score, m)| (*score, m.rank));
+    Ok(matches.into_iter().map(|(_, m)| m).collect())
+}
+
+/// `tickers`: print up to `limit` coins matching `query`
+pub fn run(query: &str, limit: usize, reporter: &dyn Reporter) -> Result<()> {
+    let matches = search(query)?;
+    if matches.is_empty() {
+        reporter.print(&format!("No ticker matches '{}'", query));
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.add_row(row!["Symbol", "Name", "CoinGecko id", "Rank", "Supported"]);
+    for m in matches.iter().take(limit) {
+        table.add_row(row![
+            m.symbol,
+            m.name,
+            m.id,
+            r->m.rank,
+            if m.supported { "yes" } else { "no" }
+        ]);
+    }
+    reporter.table(&table)?;
+    if matches.len() > limit {
+        reporter.print(&format!(
+            "{} more, refine the query or pass --limit",
+            matches.len() - limit
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_ranks_exact_symbols_first_and_tolerates_typos() {
+        let matches = search("eth").unwrap();
+        assert_eq!(matches[0].symbol, "ETH");
+        assert_eq!(matches[0].id, "ethereum");
+        assert_eq!(matches[0].rank, 2);
+        assert!(matches[0].supported);
+        assert!(matches.iter().any(|m| m.symbol == "ETC"));
+
+        let matches = search("Etherum").unwrap();
+        assert_eq!(matches[0].symbol, "ETH");
+        assert_eq!(search(" doge ").unwrap()[0].id, "dogecoin");
+        assert!(search("zzzzqqqq").unwrap().is_empty());
+
+        assert_eq!(score("btc", "BTC", "Bitcoin", "bitcoin"), Some(0));
+        assert_eq!(score("bitc", "BTC", "Bitcoin", "bitcoin"), Some(2));
+        assert_eq!(score("bcn", "BTC", "Bitcoin", "bitcoin"), Some(4));
+        assert_eq!(score("xyz", "BTC", "Bitcoin", "bitcoin"), None);
+    }
+}
@@ -1,17 +1,24 @@
-use crate::currency::Currency;
+use crate::currency::{Currency, CurrencyType};
+use crate::format::Field;
+use crate::reporter::Reporter;
 use crate::settings::Settings;
 use crate::tx::Tx;
-use anyhow::{Context, Result, bail};
-use prettytable::{Row, row};
+use crate::workspace::sha256_hex;
+use anyhow::{Context, Result, anyhow, bail};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeSet;
 use std::fmt;
+use std::io::{BufRead, Write};
 use std::path::Path;
+use std::str::FromStr;
 use time::OffsetDateTime;
 use time::format_description;
 
 // TODO could this be replaced with serialized Trade?
 pub static CSV_HEADER: [&str; 6] = ["created_at", "pair", "side", "amount", "price", "fee"];
+/// Optional last column recording how a row entered the file, e.g. `manual` or `import:binance`
+pub const SOURCE_COLUMN: &str = "source";
 
 /// Represents a single executed trade in a portfolio.
 ///
@@ -30,62 +37,238 @@ pub struct Trade {
     /// while in the runtime we would like to have OffsetDateTime type
     #[serde(with = "ts_seconds")]
     pub created_at: OffsetDateTime,
-    pub pair: TradingPair,
+    /// Trading pair for buys and sells, the moved asset for deposits and withdrawals
+    pub pair: Instrument,
     pub side: Side,
-    #[serde(deserialize_with = "positive_decimal")]
+    #[serde(
+        deserialize_with = "positive_decimal",
+        serialize_with = "canonical_decimal"
+    )]
     pub amount: Decimal,
-    #[serde(deserialize_with = "positive_decimal")]
+    #[serde(
+        deserialize_with = "positive_decimal",
+        serialize_with = "canonical_decimal"
+    )]
     pub price: Decimal,
-    #[serde(deserialize_with = "positive_decimal")] // TODO accept fee=0.0
+    #[serde(
+        deserialize_with = "non_negative_decimal",
+        serialize_with = "canonical_decimal"
+    )]
     pub fee: Decimal,
+    /// Provenance of the row, `None` for files without the `source` column
+    #[serde(
+        default,
+        deserialize_with = "optional_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub source: Option<String>,
+}
+
+fn trade_tx(pair: &TradingPair, side: Side, amount: Decimal, price: Decimal, fee: Decimal) -> Tx {
+    match side {
+        Side::Buy => Tx {
+            buy: pair.base.clone(),
+            buy_size: amount,
+            sell: pair.quote.clone(),
+            sell_size: amount * price + fee,
+        },
+        Side::Sell => Tx {
+            buy: pair.quote.clone(),
+            buy_size: amount * price - fee,
+            sell: pair.base.clone(),
+            sell_size: amount,
+        },
+        Side::Deposit | Side::Withdraw | Side::Fee | Side::Income => {
+            unreachable!("cash rows have no trading pair")
+        }
+    }
 }
 
 impl Trade {
     pub fn to_tx(&self) -> Result<Tx> {
-        match self.side {
-            Side::Buy => Ok(Tx {
-                buy: self.pair.base.clone(),
-                buy_size: self.amount,
-                sell: self.pair.quote.clone(),
-                sell_size: self.amount * self.price + self.fee,
-            }),
-            Side::Sell => Ok(Tx {
-                buy: self.pair.quote.clone(),
-                buy_size: self.amount * self.price - self.fee,
-                sell: self.pair.base.clone(),
-                sell_size: self.amount,
-            }),
-        }
-    }
-
-    pub fn to_table_row(&self) -> Row {
-        let datetime = self
-            .created_at
-            .format(&format_description::well_known::Rfc2822)
-            .unwrap_or_else(|_| "Invalid date".to_string());
-        row![
-            datetime,
-            self.pair,
+        Ok(trade_tx(
+            self.pair.trading_pair(self.side)?,
             self.side,
             self.amount,
             self.price,
-            self.fee
+            self.fee,
+        ))
+    }
+
+    /// Canonical CSV fields of the row, the same as written by serde
+    ///
+    /// Epoch seconds, uppercase pair and side, plain decimals without
+    /// trailing zeros or exponent, so rows written by any command diff
+    /// cleanly and hash stably.
+    pub fn to_record(&self) -> csv::StringRecord {
+        let mut record = csv::StringRecord::from(vec![
+            self.created_at.unix_timestamp().to_string(),
+            self.pair.to_string(),
+            self.side.as_str().to_string(),
+            canonical_decimal_string(self.amount),
+            canonical_decimal_string(self.price),
+            canonical_decimal_string(self.fee),
+        ]);
+        if let Some(source) = &self.source {
+            record.push_field(source);
+        }
+        record
+    }
+
+    /// Short id of the row, the start of the SHA-256 of its canonical fields
+    /// without the source, so it survives re-sorting and re-imports
+    pub fn id(&self) -> String {
+        let record = self.to_record();
+        let fields: Vec<&str> = record.iter().take(CSV_HEADER.len()).collect();
+        sha256_hex(fields.join(",").as_bytes())[..8].to_string()
+    }
+
+    /// Whether the row came from `filter`, either exactly or as its kind,
+    /// so `import` matches `import:binance`
+    pub fn source_matches(&self, filter: &str) -> bool {
+        self.source.as_deref().is_some_and(|source| {
+            source == filter
+                || source
+                    .strip_prefix(filter)
+                    .is_some_and(|rest| rest.starts_with(':'))
+        })
+    }
+
+    /// Output fields in `CSV_HEADER` order
+    pub fn to_fields(&self) -> Vec<Field> {
+        vec![
+            self.created_at.into(),
+            self.pair.to_string().into(),
+            self.side.to_string().into(),
+            self.amount.into(),
+            self.price.into(),
+            self.fee.into(),
         ]
     }
 }
 
+/// Borrowed view of one trade row, used on the hot read path
+///
+/// The pair is kept as the `&str` slice of the CSV record, numbers and
+/// timestamp are parsed in place, so reading a row doesn't allocate.
+/// Convert to an owned `Trade` with [`TradeRef::to_trade`] when needed.
+#[derive(Debug, Deserialize)]
+pub struct TradeRef<'a> {
+    #[serde(with = "ts_seconds")]
+    pub created_at: OffsetDateTime,
+    pub pair: &'a str,
+    pub side: Side,
+    #[serde(deserialize_with = "positive_decimal")]
+    pub amount: Decimal,
+    #[serde(deserialize_with = "positive_decimal")]
+    pub price: Decimal,
+    #[serde(deserialize_with = "non_negative_decimal")]
+    pub fee: Decimal,
+    #[serde(default)]
+    pub source: &'a str,
+}
+
+impl TradeRef<'_> {
+    pub fn to_trade(&self) -> Result<Trade> {
+        Ok(Trade {
+            created_at: self.created_at,
+            pair: self.pair.parse()?,
+            side: self.side,
+            amount: self.amount,
+            price: self.price,
+            fee: self.fee,
+            source: (!self.source.is_empty()).then(|| self.source.to_string()),
+        })
+    }
+
+    /// Same as `Trade::to_tx`, with the pair already parsed by the caller,
+    /// only valid for buys and sells
+    pub fn to_tx(&self, pair: &TradingPair) -> Tx {
+        trade_tx(pair, self.side, self.amount, self.price, self.fee)
+    }
+}
+
+/// Most decimal places of an amount, price or fee
+pub const MAX_SCALE: u32 = 18;
+/// Largest amount, price or fee, so that `amount * price` and the sums of
+/// averages and PnL stay well within `Decimal`'s ~7.9e28
+pub const MAX_MAGNITUDE: Decimal = rust_decimal::dec!(100_000_000_000_000);
+
+/// Rejects values too precise or too large to compute with safely
+pub fn check_precision(d: Decimal) -> Result<Decimal, String> {
+    if d.normalize().scale() > MAX_SCALE {
+        return Err(format!(
+            "value {} has more than {} decimal places",
+            d.normalize(),
+            MAX_SCALE
+        ));
+    }
+    if d.abs() > MAX_MAGNITUDE {
+        return Err(format!("value {} is larger than {}", d, MAX_MAGNITUDE));
+    }
+    Ok(d)
+}
+
+/// The decimal written in a field, exactly as written; `1e-9` style
+/// exponents are read too
+fn exact_decimal<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = <&str>::deserialize(deserializer)?;
+    // trailing zeros count against the 28 digits a decimal holds
+    let digits = match s.contains('.') && !s.contains(['e', 'E']) {
+        true => s.trim_end_matches('0'),
+        false => s,
+    };
+    let d = Decimal::from_str_exact(digits)
+        .or_else(|_| Decimal::from_scientific(s))
+        .map_err(|_| serde::de::Error::custom(format!("invalid number '{}'", s)))?;
+    check_precision(d).map_err(serde::de::Error::custom)
+}
+
 fn positive_decimal<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let num = f64::deserialize(deserializer)?;
-    let d = Decimal::try_from(num).map_err(serde::de::Error::custom)?;
+    let d = exact_decimal(deserializer)?;
     if d <= Decimal::ZERO {
         return Err(serde::de::Error::custom("value must be positive number"));
     }
     Ok(d)
 }
 
+fn canonical_decimal_string(d: Decimal) -> String {
+    // Decimal's Display never uses scientific notation
+    d.normalize().to_string()
+}
+
+fn canonical_decimal<S>(d: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&canonical_decimal_string(*d))
+}
+
+fn optional_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Ok((!s.is_empty()).then_some(s))
+}
+
+fn non_negative_decimal<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let d = exact_decimal(deserializer)?;
+    if d < Decimal::ZERO {
+        return Err(serde::de::Error::custom("value can't be negative"));
+    }
+    Ok(d)
+}
+
 /// Module to implment serde traits for inmported type OffsetDateTime
 mod ts_seconds {
     use serde::{self, Deserialize, Deserializer, Serializer};
@@ -116,7 +299,7 @@ mod ts_seconds {
         }
 
         // Validate: timestamp can't be in the future
-        let now_epoch = OffsetDateTime::now_utc().unix_timestamp();
+        let now_epoch = crate::clock::env().now().unix_timestamp();
         if ts > now_epoch {
             return Err(serde::de::Error::custom(
                 format!(
@@ -132,11 +315,41 @@ mod ts_seconds {
     }
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum Side {
     Buy,
     Sell,
+    /// Asset moved into the portfolio, `price` is its unit value in the base currency
+    Deposit,
+    /// Asset moved out of the portfolio, `price` is its unit value in the base currency
+    Withdraw,
+    /// Asset paid as a network or withdrawal fee, `price` is its unit value in the base currency
+    Fee,
+    /// Asset received as interest or staking reward, `price` is its unit value in the base currency
+    Income,
+}
+
+impl Side {
+    /// Canonical uppercase form, as written to CSV files
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Side::Buy => "BUY",
+            Side::Sell => "SELL",
+            Side::Deposit => "DEPOSIT",
+            Side::Withdraw => "WITHDRAW",
+            Side::Fee => "FEE",
+            Side::Income => "INCOME",
+        }
+    }
+
+    /// Deposits, withdrawals, fees and income move assets in or out, without a counter asset
+    pub fn is_cash(&self) -> bool {
+        matches!(
+            self,
+            Side::Deposit | Side::Withdraw | Side::Fee | Side::Income
+        )
+    }
 }
 
 /// Accepting any case, but serialize to uppercase
@@ -145,13 +358,40 @@ impl<'de> Deserialize<'de> for Side {
     where
         D: Deserializer<'de>,
     {
-        // Deserialize as a plain string first
-        let s = String::deserialize(deserializer)?;
-        match s.trim().to_ascii_uppercase().as_str() {
-            "BUY" => Ok(Side::Buy),
-            "SELL" => Ok(Side::Sell),
-            other => Err(serde::de::Error::unknown_variant(other, &["BUY", "SELL"])),
+        struct SideVisitor;
+
+        impl serde::de::Visitor<'_> for SideVisitor {
+            type Value = Side;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("BUY, SELL, DEPOSIT, WITHDRAW, FEE or INCOME")
+            }
+
+            // visiting a borrowed str avoids allocating a String per row
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<Side, E> {
+                let s = s.trim();
+                if s.eq_ignore_ascii_case("BUY") {
+                    Ok(Side::Buy)
+                } else if s.eq_ignore_ascii_case("SELL") {
+                    Ok(Side::Sell)
+                } else if s.eq_ignore_ascii_case("DEPOSIT") {
+                    Ok(Side::Deposit)
+                } else if s.eq_ignore_ascii_case("WITHDRAW") {
+                    Ok(Side::Withdraw)
+                } else if s.eq_ignore_ascii_case("FEE") {
+                    Ok(Side::Fee)
+                } else if s.eq_ignore_ascii_case("INCOME") {
+                    Ok(Side::Income)
+                } else {
+                    Err(E::unknown_variant(
+                        s,
+                        &["BUY", "SELL", "DEPOSIT", "WITHDRAW", "FEE", "INCOME"],
+                    ))
+                }
+            }
         }
+
+        deserializer.deserialize_str(SideVisitor)
     }
 }
 
@@ -161,6 +401,20 @@ pub struct TradingPair {
     pub quote: Currency,
 }
 
+impl TradingPair {
+    /// Pair of two different currencies, e.g. BTC/USD
+    pub fn new(base: Currency, quote: Currency) -> Result<Self> {
+        if base == quote {
+            bail!(
+                "base and quote can't be equal, got '{}/{}' (trade against a different currency)",
+                base,
+                quote
+            );
+        }
+        Ok(TradingPair { base, quote })
+    }
+}
+
 impl Serialize for TradingPair {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -171,42 +425,111 @@ impl Serialize for TradingPair {
     }
 }
 
+impl FromStr for TradingPair {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let parts: Vec<&str> = s.split('/').collect();
+
+        if parts.len() != 2 {
+            bail!("expected format 'BASE/QUOTE', got '{}'", s);
+        }
+
+        // base should not be empty string
+        if parts[0].trim().is_empty() {
+            bail!("base can't be empty");
+        }
+
+        let base = Currency::new(parts[0]).map_err(|e| anyhow!("base err: {}", e))?;
+        let quote = Currency::new(parts[1]).map_err(|e| anyhow!("quote err: {}", e))?;
+        TradingPair::new(base, quote)
+    }
+}
+
 impl<'de> Deserialize<'de> for TradingPair {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        let parts: Vec<String> = s.split('/').map(|t| t.to_uppercase()).collect();
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
 
-        if parts.len() != 2 {
-            return Err(serde::de::Error::custom(format!(
-                "expected format 'BASE/QUOTE', got '{}'",
-                s
-            )));
+/// Value of the `pair` column: `BASE/QUOTE` for trades, a single ticker for
+/// deposits and withdrawals
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Instrument {
+    Pair(TradingPair),
+    Asset(Currency),
+}
+
+impl Instrument {
+    /// The trading pair of a buy or sell row
+    pub fn trading_pair(&self, side: Side) -> Result<&TradingPair> {
+        match (self, side.is_cash()) {
+            (Instrument::Pair(pair), false) => Ok(pair),
+            (Instrument::Asset(asset), false) => {
+                bail!("{} row needs a 'BASE/QUOTE' pair, got '{}'", side, asset)
+            }
+            (_, true) => bail!("{} row has no trading pair", side),
         }
+    }
 
-        // base should not be empty string
-        if parts[0].trim().is_empty() {
-            return Err(serde::de::Error::custom("base can't be empty"));
+    /// The moved asset of a deposit or withdrawal row
+    pub fn asset(&self, side: Side) -> Result<&Currency> {
+        match (self, side.is_cash()) {
+            (Instrument::Asset(asset), true) => Ok(asset),
+            (Instrument::Pair(pair), true) => {
+                bail!("{} row needs a single ticker, got '{}'", side, pair)
+            }
+            (_, false) => bail!("{} row has no single asset", side),
         }
+    }
+}
 
-        // base and quote should not be equal
-        let base = Currency::new(parts[0].trim())
-            .map_err(|e| serde::de::Error::custom(format!("base err: {}", e)))?;
-        let quote = Currency::new(parts[1].trim())
-            .map_err(|e| serde::de::Error::custom(format!("quote err: {}", e)))?;
-        if base == quote {
-            return Err(serde::de::Error::custom("base and quote can't be equal"));
+impl FromStr for Instrument {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.contains('/') {
+            Ok(Instrument::Pair(s.parse()?))
+        } else {
+            Ok(Instrument::Asset(Currency::new(s)?))
+        }
+    }
+}
+
+impl Serialize for Instrument {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Instrument::Pair(pair) => pair.serialize(serializer),
+            Instrument::Asset(asset) => serializer.serialize_str(asset.ticker()),
         }
+    }
+}
 
-        let base_curr = Currency::new(&parts[0]).map_err(serde::de::Error::custom)?;
-        let quote_curr = Currency::new(&parts[1]).map_err(serde::de::Error::custom)?;
+impl<'de> Deserialize<'de> for Instrument {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
 
-        Ok(TradingPair {
-            base: base_curr,
-            quote: quote_curr,
-        })
+impl fmt::Display for Instrument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instrument::Pair(pair) => pair.fmt(f),
+            Instrument::Asset(asset) => asset.fmt(f),
+        }
     }
 }
 
@@ -221,111 +544,597 @@ impl fmt::Display for Side {
         match self {
             Side::Buy => write!(f, "Buy"),
             Side::Sell => write!(f, "Sell"),
+            Side::Deposit => write!(f, "Deposit"),
+            Side::Withdraw => write!(f, "Withdraw"),
+            Side::Fee => write!(f, "Fee"),
+            Side::Income => write!(f, "Income"),
         }
     }
 }
 
+/// Price of a row added with `add-tx`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxPrice {
+    Fixed(Decimal),
+    /// The current quote, see `market_price`
+    Market,
+}
+
+/// Current quote for a row of `side` in `pair`: the asset in the quote
+/// currency for buys and sells, in the portfolio base currency otherwise
+fn market_price(pair: &Instrument, side: Side, path: &Path) -> Result<Decimal> {
+    let (asset, quote) = if side.is_cash() {
+        let (config, _) = parse_csv_records(path)?;
+        (pair.asset(side)?.clone(), config.base_currency)
+    } else {
+        let pair = pair.trading_pair(side)?;
+        (pair.base.clone(), pair.quote.to_string())
+    };
+    // quotes are f64, keep the digits providers actually quote
+    let price = crate::quote::quote_in_base(&asset, &quote)?;
+    let price = price.round_sf(10).unwrap_or(price);
+    check_precision(price).map_err(|e| anyhow!("market price of {}: {}", asset, e))
+}
+
+/// A buy or sell of a bare ticker, e.g. `BTC`, is quoted in `base`
+fn default_quote(pair: Instrument, side: Side, base: &Currency) -> Result<Instrument> {
+    match pair {
+        Instrument::Asset(asset) if !side.is_cash() => {
+            Ok(Instrument::Pair(TradingPair::new(asset, base.clone())?))
+        }
+        pair => Ok(pair),
+    }
+}
+
+/// Whether `pair` is quoted in a fiat currency other than `base`, likely a
+/// USD row added to EUR books by mistake; swaps for crypto or stable coins
+/// are fine
+fn is_mixed(pair: &TradingPair, base: &Currency) -> bool {
+    pair.quote != *base && pair.quote.currency_type() == CurrencyType::Fiat
+}
+
+/// Fiat currencies other than the base the buys and sells of a trades
+/// portfolio are quoted in, see `is_mixed`
+pub fn mixed_quotes<T: AsRef<Path>>(path: T) -> Result<BTreeSet<String>> {
+    let base = Currency::new(&read_csv_config(&path)?.base_currency)?;
+    let mut mixed = BTreeSet::new();
+    for_each_trade(&path, |trade| {
+        if let Instrument::Pair(pair) = trade.pair.parse()?
+            && is_mixed(&pair, &base)
+        {
+            mixed.insert(pair.quote.to_string());
+        }
+        Ok(())
+    })?;
+    Ok(mixed)
+}
+
+/// Fail on a buy or sell `is_mixed` in `base`
+fn check_quote(pair: &TradingPair, portfolio: &str, base: &Currency) -> Result<()> {
+    if is_mixed(pair, base) {
+        bail!(
+            "{} is quoted in {}, but portfolio '{}' is kept in {}; use {}/{}, or pass --mixed to add it anyway",
+            pair,
+            pair.quote,
+            portfolio,
+            base,
+            pair.base,
+            base
+        );
+    }
+    Ok(())
+}
+
 /// Add new tx to csv portfolio file
+///
+/// The row is recorded with its `source`; files without the source column
+/// are rewritten once to add it. Rows priced at `TxPrice::Market` record
+/// the quote providers in the source, e.g. `manual:market:coingecko`.
+/// A bare ticker is traded against the portfolio base currency, and pairs
+/// quoted in another fiat currency need `mixed`.
+#[allow(clippy::too_many_arguments)]
 pub fn tx_to_csv(
     portfolio: &str,
     symbol: &str,
     side: &str,
     qty: Decimal,
-    price: Decimal,
+    price: TxPrice,
     fee: Decimal,
+    source: &str,
+    mixed: bool,
+    reporter: &dyn Reporter,
     settings: &Settings,
 ) -> Result<()> {
+    let source = source.trim();
+    if source.is_empty() {
+        bail!("source can't be empty, e.g. 'manual' or 'import:binance'");
+    }
+    let path = settings.path_for(portfolio);
+    ensure_portfolio_file(&path)?;
+    let base = Currency::new(&read_csv_config(&path)?.base_currency)?;
+
+    let side = serde_plain::from_str::<Side>(side).unwrap();
+    let pair = default_quote(symbol.parse()?, side, &base)?;
+    if side.is_cash() {
+        pair.asset(side)?;
+    } else if !mixed {
+        check_quote(pair.trading_pair(side)?, portfolio, &base)?;
+    } else {
+        pair.trading_pair(side)?;
+    }
+
+    let (price, source) = match price {
+        TxPrice::Fixed(price) => (price, source.to_string()),
+        TxPrice::Market => (
+            market_price(&pair, side, &path)?,
+            format!("{}:market:{}", source, crate::quote::provider_names()),
+        ),
+    };
     let tx = Trade {
-        created_at: time::OffsetDateTime::now_utc(),
-        pair: serde_plain::from_str::<TradingPair>(&symbol).unwrap(),
-        side: serde_plain::from_str::<Side>(&side).unwrap(),
+        created_at: settings.clock.now(),
+        pair,
+        side,
         amount: qty,
-        price: price,
-        fee: fee,
+        price,
+        fee,
+        source: Some(source),
     };
+    if settings.lint.on_add_tx {
+        crate::lint::check_new_row(&path, &tx, settings)?;
+    }
+    if let Some(days) = settings.short_term_days {
+        let (_, trades) = parse_csv_file(&path)?;
+        if let Some(warning) = crate::lots::short_term_sale_warning(&trades, &base, &tx, days)? {
+            crate::warnings::warn(warning);
+        }
+    }
 
-    let path = settings.path_for(portfolio);
-
-    let csv_file = std::fs::OpenOptions::new()
-        .append(true)
-        .open(&path)
-        .expect(format!("expecting csv file, but not found: {:?}", &path).as_str());
-    let mut wrt = csv::WriterBuilder::new()
-        .has_headers(false)
-        .from_writer(csv_file);
-    wrt.serialize(&tx).unwrap();
-    println!(
+    let _lock = crate::writer::lock(portfolio, settings)?;
+    if has_source_column(&path)? {
+        let csv_file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap_or_else(|_| panic!("expecting csv file, but not found: {:?}", &path));
+        let mut wrt = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(csv_file);
+        wrt.serialize(&tx).unwrap();
+        wrt.flush()?;
+    } else {
+        let (config, mut records) = parse_csv_records(&path)?;
+        records.push(tx.to_record());
+        write_csv_records(&path, &config, &records)?;
+    }
+    crate::index::record_write(portfolio, settings);
+    reporter.status(&format!(
         "✅ Added transaction to portfolio csv file: {:?}\n{:?}",
         path, tx
-    );
+    ));
+    Ok(())
+}
+
+/// CSV reader for portfolio rows, tolerating padded values like `" BTC/USD "` or `"buy "`
+pub(crate) fn trades_reader<R: std::io::Read>(rdr: R) -> csv::Reader<R> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(rdr)
+}
+
+/// Whether the header of a portfolio file has the optional `source` column
+/// Fail unless `path` is a trades portfolio: valid metadata lines followed by
+/// the portfolio header, so writers never append to unrelated CSV files
+pub fn ensure_portfolio_file<T: AsRef<Path>>(path: T) -> Result<()> {
+    let path = path.as_ref();
+    if !path.is_file() {
+        bail!(
+            "portfolio file {} not found, create it with `new`",
+            path.display()
+        );
+    }
+    let input_data =
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let not_portfolio = || {
+        format!(
+            "{} doesn't look like a portfolio file, refusing to write to it",
+            path.display()
+        )
+    };
+    let (config, data) = extract_csv_config(input_data.as_str()).with_context(not_portfolio)?;
+    config.ensure_trades()?;
+    config.ensure_open()?;
+
+    let header: Vec<&str> = data
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .collect();
+    let expected = CSV_HEADER.len();
+    let matches = header.get(..expected) == Some(CSV_HEADER.as_slice())
+        && (header.len() == expected
+            || (header.len() == expected + 1 && header[expected] == SOURCE_COLUMN));
+    if !matches {
+        return Err(anyhow!(
+            "expected header '{}' (optionally followed by ',{}'), found '{}'. \
+Create portfolios with `new`, or fix the header if this is one",
+            CSV_HEADER.join(","),
+            SOURCE_COLUMN,
+            header.join(",")
+        ))
+        .with_context(not_portfolio);
+    }
     Ok(())
 }
 
+pub fn has_source_column<T: AsRef<Path>>(path: T) -> Result<bool> {
+    let input_data = std::fs::read_to_string(path)?;
+    let (_, data) = extract_csv_config(input_data.as_str())?;
+    let mut reader = trades_reader(data.as_bytes());
+    Ok(reader.headers()?.iter().any(|h| h == SOURCE_COLUMN))
+}
+
 pub fn read_trades_from_csv(name: &str, settings: &Settings) -> Result<Vec<Trade>> {
     let path = settings.path_for(name);
     let file = std::fs::File::open(&path)
         .with_context(|| format!("Failed to open file: {}", path.display()))?;
-    let mut reader = csv::Reader::from_reader(file);
+    let mut reader = trades_reader(file);
     let trades: Vec<Trade> = reader
         .deserialize() // returns iterator of Result<Trade, csv::Error>
         .collect::<Result<Vec<Trade>, csv::Error>>()?;
     Ok(trades)
 }
 
-#[derive(Debug)]
+/// Portfolio metadata, stored as `# key: value` comment lines above the CSV header
+///
+/// ```csv
+/// # base_currency: EUR
+/// # created_at: 2025-12-05T20:01:21Z
+/// created_at,pair,side,amount,price,fee
+/// ```
+#[derive(Debug, Clone, PartialEq)]
 pub struct CsvConfig {
     pub base_currency: String,
+    /// Missing in files created by older versions
+    pub created_at: Option<OffsetDateTime>,
+    pub kind: PortfolioKind,
+    /// Set by `close`: the portfolio is kept for reports but no longer written
+    pub closed_at: Option<OffsetDateTime>,
+    /// Metadata lines of unknown keys, e.g. from newer versions, written
+    /// back as they were after the known ones
+    pub other: Vec<String>,
+}
+
+/// What the rows of a portfolio file hold, `# kind: holdings` in the metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PortfolioKind {
+    /// Trades, deposits and withdrawals
+    #[default]
+    Trades,
+    /// Only current holdings (`ticker,qty,cost`), see `holdings`
+    Holdings,
+}
+
+impl fmt::Display for PortfolioKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortfolioKind::Trades => write!(f, "trades"),
+            PortfolioKind::Holdings => write!(f, "holdings"),
+        }
+    }
 }
 
 impl Default for CsvConfig {
     fn default() -> Self {
         Self {
             base_currency: "USD".to_string(),
+            created_at: None,
+            kind: PortfolioKind::Trades,
+            closed_at: None,
+            other: Vec::new(),
+        }
+    }
+}
+
+impl CsvConfig {
+    /// Write the metadata comment lines
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        writeln!(w, "# base_currency: {}", self.base_currency)?;
+        if let Some(created_at) = self.created_at {
+            writeln!(
+                w,
+                "# created_at: {}",
+                created_at.format(&format_description::well_known::Rfc3339)?
+            )?;
+        }
+        if self.kind == PortfolioKind::Holdings {
+            writeln!(w, "# kind: holdings")?;
+        }
+        if let Some(closed_at) = self.closed_at {
+            writeln!(
+                w,
+                "# closed_at: {}",
+                closed_at.format(&format_description::well_known::Rfc3339)?
+            )?;
+        }
+        for line in &self.other {
+            writeln!(w, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Fail for portfolios marked closed, which commands must not write to
+    pub fn ensure_open(&self) -> Result<()> {
+        if let Some(closed_at) = self.closed_at {
+            bail!(
+                "portfolio was closed on {}, reopen it with `close --reopen` to change it",
+                closed_at.date()
+            );
+        }
+        Ok(())
+    }
+
+    /// Fail for holdings-only portfolios, which have no trade rows
+    pub fn ensure_trades(&self) -> Result<()> {
+        if self.kind == PortfolioKind::Holdings {
+            bail!("holdings-only portfolio has no trades (update it with `hold`)");
         }
+        Ok(())
     }
 }
 
-fn parse_csv_config(line: &str) -> Result<CsvConfig> {
-    let curr_sufix = line.strip_prefix("# base_currency:").context(format!(
-        "expecting comment line to start with '# base_currency:'"
-    ))?;
+fn parse_csv_config(config: &mut CsvConfig, line: &str) -> Result<()> {
+    let (key, value) = line
+        .strip_prefix('#')
+        .and_then(|l| l.split_once(':'))
+        .with_context(|| format!("expecting metadata line '# key: value', got '{}'", line))?;
+    let value = value.trim();
+
+    match key.trim() {
+        "base_currency" => {
+            if value.is_empty() {
+                bail!("empty currency parsing");
+            }
+            config.base_currency = value.to_ascii_uppercase();
+        }
+        "created_at" => {
+            let ts = OffsetDateTime::parse(value, &format_description::well_known::Rfc3339)
+                .with_context(|| format!("invalid created_at metadata '{}'", value))?;
+            config.created_at = Some(ts);
+        }
+        "closed_at" => {
+            let ts = OffsetDateTime::parse(value, &format_description::well_known::Rfc3339)
+                .with_context(|| format!("invalid closed_at metadata '{}'", value))?;
+            config.closed_at = Some(ts);
+        }
+        "kind" => {
+            config.kind = match value {
+                "trades" => PortfolioKind::Trades,
+                "holdings" => PortfolioKind::Holdings,
+                _ => bail!(
+                    "unknown portfolio kind '{}', expecting trades or holdings",
+                    value
+                ),
+            };
+        }
+        // unknown keys are kept as they are, so older versions can read
+        // and rewrite newer files
+        _ => config.other.push(line.to_string()),
+    }
 
-    let currency = curr_sufix.trim();
+    Ok(())
+}
 
-    if currency.is_empty() {
-        bail!("empty currency parsing");
+/// Read only the metadata comments of a portfolio file, without parsing trades
+pub fn read_csv_config<T: AsRef<Path>>(path: T) -> Result<CsvConfig> {
+    let file = std::fs::File::open(path)?;
+    let mut config = CsvConfig::default();
+    for line in std::io::BufReader::new(file).lines() {
+        let line = line?;
+        if !line.starts_with('#') {
+            break;
+        }
+        parse_csv_config(&mut config, line.trim_end())?;
     }
+    Ok(config)
+}
 
-    Ok(CsvConfig {
-        base_currency: currency.to_ascii_uppercase(),
-    })
+/// The `base_currency` metadata of a portfolio file, `None` when it has
+/// none; `read_csv_config` falls back to USD for those
+pub fn read_base_currency<T: AsRef<Path>>(path: T) -> Result<Option<String>> {
+    let config = read_csv_config(&path)?;
+    let file = std::fs::File::open(path)?;
+    let given = std::io::BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .take_while(|line| line.starts_with('#'))
+        .any(|line| {
+            line[1..]
+                .split_once(':')
+                .is_some_and(|(key, _)| key.trim() == "base_currency")
+        });
+    Ok(given.then_some(config.base_currency))
 }
 
-fn extract_csv_config(input_data: &str) -> Result<(CsvConfig, &str)> {
-    let first_line = input_data.lines().next().context("no data")?;
+pub(crate) fn extract_csv_config(input_data: &str) -> Result<(CsvConfig, &str)> {
+    let mut config = CsvConfig::default();
+    let mut rest = input_data;
 
-    if let Ok(conf) = parse_csv_config(first_line) {
-        let remain = input_data
-            .split_once("\n")
-            .map(|(_, rem)| rem)
-            .context("no remaining csv data")?;
-        Ok((conf, remain))
-    } else {
-        Ok((CsvConfig::default(), input_data))
+    while rest.starts_with('#') {
+        let (line, remain) = rest.split_once('\n').unwrap_or((rest, ""));
+        parse_csv_config(&mut config, line.trim_end())?;
+        rest = remain;
     }
+
+    Ok((config, rest))
 }
 
 pub fn parse_csv_file<T: AsRef<Path>>(path: T) -> Result<(CsvConfig, Vec<Trade>)> {
     let input_data = std::fs::read_to_string(path)?;
     let (config, data) = extract_csv_config(input_data.as_str())?;
-    let mut reader = csv::ReaderBuilder::new().from_reader(data.as_bytes());
+    config.ensure_trades()?;
+    let mut reader = trades_reader(data.as_bytes());
     let trades: Vec<Trade> = reader.deserialize().collect::<Result<_, csv::Error>>()?;
     Ok((config, trades))
 }
 
+/// The trade whose `Trade::id` starts with `prefix`
+pub fn find_by_id<'a>(trades: &'a [Trade], prefix: &str) -> Result<&'a Trade> {
+    let prefix = prefix.to_lowercase();
+    let mut matches = trades.iter().filter(|t| t.id().starts_with(&prefix));
+    match (matches.next(), matches.next()) {
+        (Some(trade), None) => Ok(trade),
+        (None, _) => bail!("no trade with id '{}', see show --with-journal", prefix),
+        (Some(_), Some(_)) => bail!("id '{}' matches several trades, use more of it", prefix),
+    }
+}
+
+/// Order trades are replayed in: by `created_at`, trades of the same second
+/// in the order of their rows
+///
+/// Bots often trade several times a second, so every replay (positions, lots,
+/// tax, exports) and every rewrite of a file goes through this order, which
+/// the stable sort guarantees.
+pub fn sort_chronologically<T: std::borrow::Borrow<Trade>>(trades: &mut [T]) {
+    trades.sort_by_key(|t| t.borrow().created_at);
+}
+
+/// `sort_chronologically` for canonical records (see `Trade::to_record`)
+pub fn sort_records_chronologically(records: &mut [csv::StringRecord]) {
+    records.sort_by_key(|r| r.get(0).and_then(|ts| ts.trim().parse::<i64>().ok()));
+}
+
+/// Stream the trades of a portfolio file through `f` as borrowed rows, in
+/// the order of `sort_chronologically`
+///
+/// A single record buffer is reused for the whole file, no `Trade` is
+/// allocated unless the callback converts one. Only files edited out of
+/// order are read into memory to be sorted first.
+pub fn for_each_trade<T, F>(path: T, mut f: F) -> Result<CsvConfig>
+where
+    T: AsRef<Path>,
+    F: FnMut(&TradeRef) -> Result<()>,
+{
+    let input_data = std::fs::read_to_string(path)?;
+    let (config, data) = extract_csv_config(input_data.as_str())?;
+    config.ensure_trades()?;
+    let mut reader = trades_reader(data.as_bytes());
+    let headers = reader.headers()?.clone();
+    let mut record = csv::StringRecord::new();
+    if is_chronological(data, &headers)? {
+        while reader.read_record(&mut record)? {
+            let trade: TradeRef = record.deserialize(Some(&headers))?;
+            f(&trade)?;
+        }
+        return Ok(config);
+    }
+    let mut rows: Vec<(OffsetDateTime, csv::StringRecord)> = Vec::new();
+    while reader.read_record(&mut record)? {
+        let trade: TradeRef = record.deserialize(Some(&headers))?;
+        rows.push((trade.created_at, record.clone()));
+    }
+    rows.sort_by_key(|(created_at, _)| *created_at);
+    for (_, record) in &rows {
+        f(&record.deserialize(Some(&headers))?)?;
+    }
+    Ok(config)
+}
+
+/// Whether no row of `data` is older than the one before, comparing the raw
+/// `created_at` fields; unparsable ones are left for deserializing to report
+fn is_chronological(data: &str, headers: &csv::StringRecord) -> Result<bool> {
+    let Some(column) = headers.iter().position(|h| h == "created_at") else {
+        return Ok(true);
+    };
+    let mut reader = trades_reader(data.as_bytes());
+    let mut record = csv::ByteRecord::new();
+    let mut last = i64::MIN;
+    while reader.read_byte_record(&mut record)? {
+        let ts = record
+            .get(column)
+            .and_then(|ts| std::str::from_utf8(ts).ok())
+            .and_then(|ts| ts.trim().parse::<i64>().ok());
+        match ts {
+            Some(ts) if ts < last => return Ok(false),
+            Some(ts) => last = ts,
+            None => {}
+        }
+    }
+    Ok(true)
+}
+
+/// Read the CSV records in their canonical form (see `Trade::to_record`)
+///
+/// Every record is validated as a `Trade`, so callers can compare and
+/// rewrite rows as plain strings while relying on them being valid.
+pub fn parse_csv_records<T: AsRef<Path>>(path: T) -> Result<(CsvConfig, Vec<csv::StringRecord>)> {
+    parse_csv_text_records(&std::fs::read_to_string(path)?)
+}
+
+/// `parse_csv_records` of the text of a portfolio file
+pub fn parse_csv_text_records(input_data: &str) -> Result<(CsvConfig, Vec<csv::StringRecord>)> {
+    let (config, data) = extract_csv_config(input_data)?;
+    config.ensure_trades()?;
+    let mut reader = trades_reader(data.as_bytes());
+    let headers = reader.headers()?.clone();
+    let mut records = Vec::new();
+    for record in reader.records() {
+        let trade: Trade = record?.deserialize(Some(&headers))?;
+        records.push(trade.to_record());
+    }
+    Ok((config, records))
+}
+
+/// Write a complete portfolio file (metadata comment, header and rows)
+///
+/// The `source` column is written when any record has one, rows without
+/// it get an empty value. Data is written to a temporary file first and
+/// renamed over the target, so readers never observe a partially written
+/// portfolio.
+pub fn write_csv_records<T: AsRef<Path>>(
+    path: T,
+    config: &CsvConfig,
+    records: &[csv::StringRecord],
+) -> Result<()> {
+    let path = path.as_ref();
+    let with_source = records.iter().any(|r| r.len() > CSV_HEADER.len());
+    let tmp = path.with_extension("csv.tmp");
+    std::fs::write(&tmp, render_csv_records(config, records, with_source)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// The text of a complete portfolio file, with the `source` column when
+/// `with_source`
+pub fn render_csv_records(
+    config: &CsvConfig,
+    records: &[csv::StringRecord],
+    with_source: bool,
+) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    config.write_to(&mut out)?;
+    let mut wtr = csv::Writer::from_writer(out);
+    if with_source {
+        wtr.write_record(CSV_HEADER.iter().chain([&SOURCE_COLUMN]))?;
+    } else {
+        wtr.write_record(CSV_HEADER)?;
+    }
+    for record in records {
+        if with_source && record.len() == CSV_HEADER.len() {
+            wtr.write_record(record.iter().chain([""]))?;
+        } else {
+            wtr.write_record(record)?;
+        }
+    }
+    wtr.into_inner()
+        .map_err(|e| anyhow!("writing records: {}", e))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::reporter::Silent;
+    use rust_decimal::dec;
     use serde_json::json;
     use time::macros::datetime;
 
@@ -435,10 +1244,10 @@ mod tests {
     //     );
     // }
 
-    /// Deserializes `{"amount": 5}` and succeeds with `Decimal(5)`.
+    /// Deserializes `{"amount": "5"}` and succeeds with `Decimal(5)`.
     #[test]
     fn test_deser_accepts_simple_positive_integer() {
-        let d: ValTest = serde_json::from_str(r#"{"amount": 5}"#).unwrap();
+        let d: ValTest = serde_json::from_str(r#"{"amount": "5"}"#).unwrap();
         println!("{:?}", &d);
         assert_eq!(
             d,
@@ -448,10 +1257,10 @@ mod tests {
         );
     }
 
-    /// Deserializes `{"amount": 5.75}` and succeeds with `Decimal(5.75)`.
+    /// Deserializes `{"amount": "5.75"}` and succeeds with `Decimal(5.75)`.
     #[test]
     fn test_deser_accepts_simple_positive_decimal() {
-        let d: ValTest = serde_json::from_str(r#"{"amount": 5.75}"#).unwrap();
+        let d: ValTest = serde_json::from_str(r#"{"amount": "5.75"}"#).unwrap();
         println!("{:?}", &d);
         assert_eq!(
             d,
@@ -461,10 +1270,10 @@ mod tests {
         );
     }
 
-    /// Deserializes `{"amount": 1e6}` and succeeds with `Decimal(1000000)`.
+    /// Deserializes `{"amount": "1e6"}` and succeeds with `Decimal(1000000)`.
     #[test]
     fn test_deser_accepts_scientific_notation_positive() {
-        let d: ValTest = serde_json::from_str(r#"{"amount": 1e6}"#).unwrap();
+        let d: ValTest = serde_json::from_str(r#"{"amount": "1e6"}"#).unwrap();
         println!("{:?}", &d);
         assert_eq!(
             d,
@@ -474,10 +1283,10 @@ mod tests {
         );
     }
 
-    /// Deserializes a very small positive like `{"amount": 1e-9}` and succeeds (still > 0).
+    /// Deserializes a very small positive like `{"amount": "1e-9"}` and succeeds (still > 0).
     #[test]
     fn test_deser_accepts_small_positive_decimal_epsilon() {
-        let d: ValTest = serde_json::from_str(r#"{"amount": 1e-9}"#).unwrap();
+        let d: ValTest = serde_json::from_str(r#"{"amount": "1e-9"}"#).unwrap();
         println!("{:?}", &d);
         assert_eq!(
             d,
@@ -502,9 +1311,9 @@ mod tests {
     #[test]
     fn test_deser_rejects_non_positive_values() {
         for json in [
-            r#"{"amount": 0}"#,
-            r#"{"amount": -3}"#,
-            r#"{"amount": -0.0001}"#,
+            r#"{"amount": "0"}"#,
+            r#"{"amount": "-3"}"#,
+            r#"{"amount": "-0.0001"}"#,
         ] {
             assert_rejects_invalid_value(json);
         }
@@ -522,7 +1331,7 @@ mod tests {
     #[test]
     fn test_deser_rejects_type_error() {
         for json in [
-            r#"{"amount": "1.23"}"#,
+            r#"{"amount": 1.23}"#,
             r#"{"amount": true}"#,
             r#"{"amount": [1,2]}"#,
             r#"{"amount": {"k": 1}}"#,
@@ -531,6 +1340,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_deser_rejects_values_beyond_precision_limits() {
+        for (json, msg) in [
+            (r#"{"amount": "1e-19"}"#, "more than 18 decimal places"),
+            (r#"{"amount": "1e15"}"#, "is larger than 100000000000000"),
+        ] {
+            let err = serde_json::from_str::<ValTest>(json).unwrap_err();
+            assert!(err.to_string().contains(msg), "unexpected error: {err}");
+        }
+        let d: ValTest = serde_json::from_str(r#"{"amount": "1e-18"}"#).unwrap();
+        assert_eq!(d.amount, rust_decimal::dec!(0.000000000000000001));
+        assert_eq!(check_precision(MAX_MAGNITUDE), Ok(MAX_MAGNITUDE));
+    }
+
+    #[test]
+    fn test_csv_rows_keep_every_decimal_place_up_to_the_limit() {
+        let trades = crate::test_utils::helpers::transactions_from(
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,0.123456789012345678,12345678901234.123456789012345,0.000000000000000001
+",
+        );
+        assert_eq!(trades[0].amount, dec!(0.123456789012345678));
+        assert_eq!(trades[0].price, dec!(12345678901234.123456789012345));
+        assert_eq!(trades[0].fee, dec!(0.000000000000000001));
+        assert_eq!(trades[0].to_record().get(3), Some("0.123456789012345678"));
+
+        let err = trades_reader(
+            "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,0.1234567890123456789,40000,0
+"
+            .as_bytes(),
+        )
+        .deserialize::<Trade>()
+        .next()
+        .unwrap()
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("more than 18 decimal places"),
+            "{err}"
+        );
+    }
+
     /// Deserializes an extremely large number (e.g., `{"amount": 1e309}`), expecting failure from `Decimal::try_from(f64)` with a custom-converted error.
     #[test]
     fn deser_maps_try_from_overflow_error() {}
@@ -577,7 +1428,7 @@ mod tests {
     }
 
     fn assert_rejects_invalid_csv(csv_data: &str) {
-        let result: Result<ValTest, csv::Error> = from_csv_str(&csv_data);
+        let result: Result<ValTest, csv::Error> = from_csv_str(csv_data);
         assert!(
             result.is_err(),
             "expected CSV deserialization to fail for:\n{csv_data}"
@@ -594,6 +1445,332 @@ mod tests {
         assert_rejects_invalid_csv("amount\n-1\n");
     }
 
+    // - - - - - - - - - - - - - - - - - - - - - - - -
+    // metadata comments
+    // - - - - - - - - - - - - - - - - - - - - - - - -
+
+    #[test]
+    fn test_extract_csv_config_reads_all_metadata_lines() {
+        let data = "# base_currency: eur\n# created_at: 2024-01-10T10:40:00Z\ncreated_at,pair\n";
+        let (config, rest) = extract_csv_config(data).unwrap();
+        assert_eq!(config.base_currency, "EUR");
+        assert_eq!(config.created_at, Some(datetime!(2024-01-10 10:40 UTC)));
+        assert_eq!(rest, "created_at,pair\n");
+    }
+
+    #[test]
+    fn test_extract_csv_config_defaults_without_metadata() {
+        let data = "created_at,pair\n";
+        let (config, rest) = extract_csv_config(data).unwrap();
+        assert_eq!(config, CsvConfig::default());
+        assert_eq!(rest, data);
+    }
+
+    #[test]
+    fn test_extract_csv_config_ignores_unknown_keys() {
+        let (config, _) =
+            extract_csv_config("# future_key: 1\r\n# base_currency: CAD\r\n").unwrap();
+        assert_eq!(config.base_currency, "CAD");
+    }
+
+    #[test]
+    fn test_extract_csv_config_rejects_malformed_metadata() {
+        assert!(extract_csv_config("# created_at: yesterday\n").is_err());
+        assert!(extract_csv_config("# no separator\n").is_err());
+    }
+
+    #[test]
+    fn test_holdings_kind_roundtrip_and_has_no_trades() {
+        let config = CsvConfig {
+            kind: PortfolioKind::Holdings,
+            ..CsvConfig::default()
+        };
+        let mut out = Vec::new();
+        config.write_to(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text, "# base_currency: USD\n# kind: holdings\n");
+        assert_eq!(extract_csv_config(&text).unwrap().0, config);
+        assert!(config.ensure_trades().is_err());
+        assert!(extract_csv_config("# kind: wallet\n").is_err());
+    }
+
+    #[test]
+    fn test_csv_config_write_roundtrip() {
+        let config = CsvConfig {
+            base_currency: "EUR".to_string(),
+            created_at: Some(datetime!(2024-01-10 10:40 UTC)),
+            kind: PortfolioKind::Trades,
+            closed_at: None,
+            other: vec!["# synced_by: nas".to_string()],
+        };
+        let mut out = Vec::new();
+        config.write_to(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(
+            text,
+            "# base_currency: EUR\n# created_at: 2024-01-10T10:40:00Z\n# synced_by: nas\n"
+        );
+        assert_eq!(extract_csv_config(&text).unwrap().0, config);
+        assert!(config.ensure_open().is_ok());
+
+        let closed = CsvConfig {
+            closed_at: Some(datetime!(2025-06-30 18:00 UTC)),
+            other: Vec::new(),
+            ..config
+        };
+        let mut out = Vec::new();
+        closed.write_to(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(
+            text.ends_with("# closed_at: 2025-06-30T18:00:00Z\n"),
+            "{text}"
+        );
+        assert_eq!(extract_csv_config(&text).unwrap().0, closed);
+        assert!(closed.ensure_open().is_err());
+    }
+
+    #[test]
+    fn test_trade_ref_borrows_pair_from_record() {
+        let mut reader = csv::Reader::from_reader(
+            "created_at,pair,side,amount,price,fee\n1704883200,btc/usd,sell,1.0,40000.00,7.50\n"
+                .as_bytes(),
+        );
+        let headers = reader.headers().unwrap().clone();
+        let record = reader.records().next().unwrap().unwrap();
+        let trade: TradeRef = record.deserialize(Some(&headers)).unwrap();
+
+        assert_eq!(trade.pair, "btc/usd");
+        assert_eq!(trade.side, Side::Sell);
+        assert_eq!(trade.to_trade().unwrap().pair.to_string(), "BTC/USD");
+    }
+
+    // as exported by spreadsheets and exchanges: padded cells, CRLF, mixed case
+    const MESSY_CSV: &str = "# base_currency: usd \r
+created_at , pair , side , amount , price , fee\r
+ 1704883200 , btc/usd ,buy , 1.0, 40000.00 ,7.50\r
+1710460800,\" ETH/USD \",\"Sell \",3 ,2000, 0\r
+1710547200, USD ,  deposit,100,1,0
+";
+
+    const CLEAN_CSV: &str = "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1.0,40000.00,7.50
+1710460800,ETH/USD,SELL,3,2000,0
+1710547200,USD,DEPOSIT,100,1,0
+";
+
+    #[test]
+    fn test_canonical_record_matches_serde_output() {
+        let trades = parse_csv_str(MESSY_CSV);
+        let mut wtr = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(Vec::new());
+        for trade in &trades {
+            wtr.serialize(trade).unwrap();
+        }
+        let serialized = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+
+        let mut wtr = csv::Writer::from_writer(Vec::new());
+        for trade in &trades {
+            wtr.write_record(&trade.to_record()).unwrap();
+        }
+        let records = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+
+        assert_eq!(serialized, records);
+        assert_eq!(
+            serialized,
+            "1704883200,BTC/USD,BUY,1,40000,7.5\n1710460800,ETH/USD,SELL,3,2000,0\n1710547200,USD,DEPOSIT,100,1,0\n"
+        );
+    }
+
+    #[test]
+    fn test_canonical_decimal_string_has_no_exponent_or_trailing_zeros() {
+        use rust_decimal::dec;
+        assert_eq!(canonical_decimal_string(dec!(1.50)), "1.5");
+        assert_eq!(canonical_decimal_string(dec!(40000.00)), "40000");
+        assert_eq!(canonical_decimal_string(dec!(1e-8)), "0.00000001");
+        assert_eq!(
+            canonical_decimal_string(dec!(1e20)),
+            "100000000000000000000"
+        );
+    }
+
+    fn parse_csv_str(content: &str) -> Vec<Trade> {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("p.csv");
+        std::fs::write(&path, content).unwrap();
+        parse_csv_file(&path).unwrap().1
+    }
+
+    #[test]
+    fn test_messy_file_parses_like_clean_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let messy = dir.path().join("messy.csv");
+        let clean = dir.path().join("clean.csv");
+        std::fs::write(&messy, MESSY_CSV).unwrap();
+        std::fs::write(&clean, CLEAN_CSV).unwrap();
+
+        assert_eq!(
+            parse_csv_file(&messy).unwrap(),
+            parse_csv_file(&clean).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_messy_file_roundtrip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let messy = dir.path().join("messy.csv");
+        std::fs::write(&messy, MESSY_CSV).unwrap();
+        let (config, trades) = parse_csv_file(&messy).unwrap();
+
+        // write the parsed trades back and read them again
+        let written = dir.path().join("written.csv");
+        let mut out = Vec::new();
+        config.write_to(&mut out).unwrap();
+        let mut wtr = csv::Writer::from_writer(&mut out);
+        for trade in &trades {
+            wtr.serialize(trade).unwrap();
+        }
+        wtr.flush().unwrap();
+        drop(wtr);
+        std::fs::write(&written, &out).unwrap();
+
+        assert_eq!(parse_csv_file(&written).unwrap(), (config, trades));
+    }
+
+    #[test]
+    fn test_for_each_trade_matches_owned_parsing() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("p.csv");
+        std::fs::write(
+            &path,
+            "# base_currency: EUR
+created_at,pair,side,amount,price,fee
+1704883200,BTC/EUR,BUY,1.0,40000.00,7.50
+1710460800,ETH/EUR,Sell,3,2000.00,10.00
+",
+        )
+        .unwrap();
+
+        let mut streamed = Vec::new();
+        let config = for_each_trade(&path, |t| {
+            streamed.push(t.to_trade()?);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!((config, streamed), parse_csv_file(&path).unwrap());
+    }
+
+    #[test]
+    fn test_trades_of_the_same_second_keep_their_row_order() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("p.csv");
+        std::fs::write(
+            &path,
+            "created_at,pair,side,amount,price,fee
+1704883260,BTC/USD,SELL,1,41000,0
+1704883200,BTC/USD,BUY,3,40000,0
+1704883200,BTC/USD,BUY,1,40000,0
+1704883200,BTC/USD,BUY,2,40000,0
+",
+        )
+        .unwrap();
+        let amounts =
+            |trades: &[Trade]| -> Vec<Decimal> { trades.iter().map(|t| t.amount).collect() };
+
+        let mut streamed = Vec::new();
+        for_each_trade(&path, |t| {
+            streamed.push(t.to_trade()?);
+            Ok(())
+        })
+        .unwrap();
+        let expected = [dec!(3), dec!(1), dec!(2), dec!(1)];
+        assert_eq!(amounts(&streamed), expected);
+
+        let (_, mut owned) = parse_csv_file(&path).unwrap();
+        sort_chronologically(&mut owned);
+        assert_eq!(amounts(&owned), expected);
+    }
+
+    #[test]
+    fn test_ensure_portfolio_file_checks_header_and_kind() {
+        use crate::test_utils::helpers::create_test_csv;
+        let dir = tempfile::TempDir::new().unwrap();
+        let ok = [
+            "created_at,pair,side,amount,price,fee\n",
+            "# base_currency: EUR\ncreated_at, pair ,side,amount,price,fee,source\n",
+        ];
+        for (i, content) in ok.iter().enumerate() {
+            let path = create_test_csv(&dir, &format!("ok{}", i), content);
+            assert!(ensure_portfolio_file(&path).is_ok(), "{}", content);
+        }
+
+        let bad = [
+            "",
+            "date,description,amount\n2024-01-01,coffee,3.5\n",
+            "created_at,pair,side,amount,price\n",
+            "created_at,pair,side,amount,price,fee,note\n",
+            "# kind: holdings\nticker,qty,cost\n",
+            "#!/bin/sh\ncreated_at,pair,side,amount,price,fee\n",
+        ];
+        for (i, content) in bad.iter().enumerate() {
+            let path = create_test_csv(&dir, &format!("bad{}", i), content);
+            assert!(ensure_portfolio_file(&path).is_err(), "{}", content);
+        }
+        assert!(ensure_portfolio_file(dir.path().join("missing.csv")).is_err());
+    }
+
+    #[test]
+    fn test_tx_to_csv_adds_source_column_to_older_files() {
+        use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = create_test_csv(
+            &dir,
+            "pf",
+            "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1.0,40000.00,7.50
+",
+        );
+        let settings = create_test_settings(dir.path().to_path_buf());
+
+        tx_to_csv(
+            "pf",
+            "eth/usd",
+            "BUY",
+            dec!(2),
+            TxPrice::Fixed(dec!(3000)),
+            dec!(1),
+            "manual",
+            false,
+            &Silent,
+            &settings,
+        )
+        .unwrap();
+        assert!(has_source_column(&path).unwrap());
+        tx_to_csv(
+            "pf",
+            "ETH/USD",
+            "SELL",
+            dec!(1),
+            TxPrice::Fixed(dec!(3500)),
+            dec!(1),
+            "import:kraken",
+            false,
+            &Silent,
+            &settings,
+        )
+        .unwrap();
+
+        let (_, trades) = parse_csv_file(&path).unwrap();
+        let sources: Vec<Option<&str>> = trades.iter().map(|t| t.source.as_deref()).collect();
+        assert_eq!(sources, vec![None, Some("manual"), Some("import:kraken")]);
+        assert!(trades[2].source_matches("import"));
+        assert!(!trades[2].source_matches("imp"));
+        assert!(!trades[0].source_matches("manual"));
+    }
+
     mod trading_pair {
         use super::*;
         use crate::test_utils::fixtures::tickers;
@@ -615,11 +1792,12 @@ mod tests {
 
         #[rstest]
         fn test_base_and_quote_cannot_be_the_same_btc(_tickers: ()) {
-            let p = serde_json::from_str::<TestPair>(r#"{"pair":"BTC/BTC"}"#);
-            // TODO once we accept both btc and usd, this shoud not fail
-            // currently it's passing since only USD is allowed
-            println!("{:?}", &p);
-            assert!(p.is_err(), "expected err, got {:?}", &p);
+            let p = serde_json::from_str::<TestPair>(r#"{"pair":"btc/BTC"}"#);
+            let err = p.unwrap_err().to_string();
+            assert!(
+                err.contains("base and quote can't be equal, got 'BTC/BTC'"),
+                "{err}"
+            );
 
             // let pair = TradingPair {
             //     base: Ticker::from_str("BTC").unwrap(),
@@ -627,6 +1805,15 @@ mod tests {
             // };
         }
 
+        #[test]
+        fn test_new_validates_base_differs_from_quote() {
+            let btc = Currency::new("BTC").unwrap();
+            let usd = Currency::new("USD").unwrap();
+            let pair = TradingPair::new(btc.clone(), usd).unwrap();
+            assert_eq!(pair.to_string(), "BTC/USD");
+            assert!(TradingPair::new(btc.clone(), btc).is_err());
+        }
+
         /// Verifies that the serialized output follows the "BASE/QUOTE" format with a single `/` separator.
         #[rstest]
         fn test_serialize_uses_slash_separator(_tickers: ()) {
@@ -642,11 +1829,28 @@ mod tests {
             );
         }
 
+        #[rstest]
+        #[case(r#"{"pair":" BTC/USD "}"#)]
+        #[case(r#"{"pair":"btc / usd"}"#)]
+        #[case(r#"{"pair":"\tBTC/USD\n"}"#)]
+        fn test_deserialize_trims_padded_pair(#[case] json: &str) {
+            let p = serde_json::from_str::<TestPair>(json).unwrap();
+            assert_eq!(p.pair.to_string(), "BTC/USD");
+        }
+
+        #[rstest]
+        #[case("buy ", Side::Buy)]
+        #[case(" SELL", Side::Sell)]
+        #[case("\tDeposit ", Side::Deposit)]
+        fn test_side_deserialize_trims(#[case] input: &str, #[case] expected: Side) {
+            assert_eq!(serde_plain::from_str::<Side>(input).unwrap(), expected);
+        }
+
         /// Checks that input without `/` (e.g., "BTCUSD") returns a format error.
         #[test]
         fn test_deserialize_rejects_missing_separator() {
             let json_str = r#"{"pair":"BTCUSDT"}"#;
-            let err = serde_json::from_str::<TestPair>(&json_str).unwrap_err();
+            let err = serde_json::from_str::<TestPair>(json_str).unwrap_err();
             assert!(
                 err.to_string()
                     .contains("expected format 'BASE/QUOTE', got 'BTCUSDT'")
@@ -656,7 +1860,7 @@ mod tests {
         #[test]
         fn invalid_trading_pair_format_doubleslash() {
             let json_str = r#"{"pair":"BTC/ETH/USD"}"#;
-            let err = serde_json::from_str::<TestPair>(&json_str).unwrap_err();
+            let err = serde_json::from_str::<TestPair>(json_str).unwrap_err();
             // println!("{:?}", &err);
             assert!(
                 err.to_string()
@@ -680,7 +1884,7 @@ mod tests {
         }
 
         // TODO add mock for CRYPTO static
-        /// Checks that non-alphabetic symbols in `base` (like "eth2") are preserved during serialization.
+        // Checks that non-alphabetic symbols in `base` (like "eth2") are preserved during serialization.
         // #[rstest]
         // fn test_serialize_preserves_alphanumeric_symbols(_tickers: ()) {
         //     let d = serde_json::from_str::<TestPair>(r#"{"pair":"usdt0/USD"}"#).unwrap();
@@ -701,7 +1905,7 @@ mod tests {
         #[test]
         fn test_deserialize_rejects_empty_string() {
             let json_str = r#"{"pair":""}"#;
-            let err = serde_json::from_str::<TestPair>(&json_str).unwrap_err();
+            let err = serde_json::from_str::<TestPair>(json_str).unwrap_err();
             // println!("{:?}", &err);
             assert!(
                 err.to_string()
@@ -713,7 +1917,7 @@ mod tests {
         #[test]
         fn test_deserialize_rejects_only_base_no_quote() {
             let json_str = r#"{"pair":"BTC/"}"#;
-            let err = serde_json::from_str::<TestPair>(&json_str).unwrap_err();
+            let err = serde_json::from_str::<TestPair>(json_str).unwrap_err();
 
             pretty_assertions::assert_eq!(
                 err.to_string(),
@@ -726,7 +1930,7 @@ mod tests {
         #[test]
         fn test_deserialize_rejects_only_quote_no_base() {
             let json_str = r#"{"pair":"/USD"}"#;
-            let err = serde_json::from_str::<TestPair>(&json_str).unwrap_err();
+            let err = serde_json::from_str::<TestPair>(json_str).unwrap_err();
             // println!("{:?}", &err);
             assert!(err.to_string().contains("base can't be empty"));
         }
@@ -0,0 +1,143 @@
+//! Trade-entry templates
+//!
+//! Recurring `add-tx` options can be named in the config:
+//! ```toml
+//! [templates]
+//! weekly_btc = "--name main --ticker BTC/EUR --side BUY --qty 0.005 --fee 0.3"
+//! ```
+//! `add-tx --template weekly_btc --price 91000` then takes the options it
+//! isn't given from the template.
+
+use crate::cli::TxArgs;
+use crate::settings::Settings;
+use crate::trade::TxPrice;
+use anyhow::{Result, anyhow, bail};
+use clap::Parser;
+use rust_decimal::Decimal;
+
+/// How `add-tx` records the row's source unless told otherwise
+const DEFAULT_SOURCE: &str = "manual";
+
+#[derive(Debug, Parser)]
+#[command(no_binary_name = true, disable_help_flag = true)]
+struct TemplateArgs {
+    #[command(flatten)]
+    tx: TxArgs,
+}
+
+/// Parse a template, the `add-tx` options it gives separated by whitespace
+pub fn parse(template: &str) -> Result<TxArgs> {
+    TemplateArgs::try_parse_from(template.split_whitespace())
+        .map(|args| args.tx)
+        .map_err(|e| {
+            let message = e.to_string();
+            let first = message.lines().next().unwrap_or_default();
+            anyhow!("{}", first.trim_start_matches("error: "))
+        })
+}
+
+/// A row to add, every option of `add-tx` given
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewTx {
+    pub name: String,
+    pub ticker: String,
+    pub side: String,
+    pub qty: Decimal,
+    pub price: TxPrice,
+    pub fee: Decimal,
+    pub source: String,
+}
+
+/// `value`, noting `flag` in `missing` when there is none
+fn given<T>(value: Option<T>, flag: &'static str, missing: &mut Vec<&'static str>) -> Option<T> {
+    if value.is_none() {
+        missing.push(flag);
+    }
+    value
+}
+
+/// The options of `add-tx`, those not given taken from the config template
+/// named `template`
+pub fn resolve(args: &TxArgs, template: Option<&str>, settings: &Settings) -> Result<NewTx> {
+    let defaults = match template {
+        Some(name) => match settings.templates.get(name) {
+            Some(template) => parse(template)?,
+            None => bail!("no template '{}' in the config", name),
+        },
+        None => TxArgs::default(),
+    };
+    let args = args.clone();
+    let mut missing = Vec::new();
+    let tx = (
+        given(args.name.or(defaults.name), "--name", &mut missing),
+        given(args.ticker.or(defaults.ticker), "--ticker", &mut missing),
+        given(args.side.or(defaults.side), "--side", &mut missing),
+        given(args.qty.or(defaults.qty), "--qty", &mut missing),
+        given(args.price.or(defaults.price), "--price", &mut missing),
+        given(args.fee.or(defaults.fee), "--fee", &mut missing),
+    );
+    let (Some(name), Some(ticker), Some(side), Some(qty), Some(price), Some(fee)) = tx else {
+        bail!(
+            "add-tx is missing {}, pass it or use a --template that sets it",
+            missing.join(", ")
+        );
+    };
+    Ok(NewTx {
+        name,
+        ticker,
+        side,
+        qty,
+        price,
+        fee,
+        source: args
+            .source
+            .or(defaults.source)
+            .unwrap_or(DEFAULT_SOURCE.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::create_test_settings;
+    use rust_decimal::dec;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resolve_fills_options_not_given_from_the_template() {
+        let dir = TempDir::new().unwrap();
+        let mut settings = (*create_test_settings(dir.path().to_path_buf())).clone();
+        settings.templates.insert(
+            "weekly_btc".to_string(),
+            "--name main  --ticker BTC/EUR --side BUY --qty 0.005 --fee 0.3".to_string(),
+        );
+        let args = TxArgs {
+            price: Some(TxPrice::Fixed(dec!(91000))),
+            fee: Some(dec!(0.25)),
+            ..TxArgs::default()
+        };
+
+        assert_eq!(
+            resolve(&args, Some("weekly_btc"), &settings).unwrap(),
+            NewTx {
+                name: "main".to_string(),
+                ticker: "BTC/EUR".to_string(),
+                side: "BUY".to_string(),
+                qty: dec!(0.005),
+                price: TxPrice::Fixed(dec!(91000)),
+                fee: dec!(0.25),
+                source: "manual".to_string(),
+            }
+        );
+        let err = resolve(&args, None, &settings).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "add-tx is missing --name, --ticker, --side, --qty, pass it or use a --template that sets it"
+        );
+        assert!(resolve(&args, Some("monthly"), &settings).is_err());
+
+        assert_eq!(parse("").unwrap().qty, None);
+        assert!(parse("--qty lots").is_err());
+        assert!(parse("--ticker BTC/EUR extra").is_err());
+    }
+}
@@ -0,0 +1,393 @@
+//! Universal CSV formats of Koinly and CoinTracking, which many exchanges
+//! and wallets can export to, mapped onto portfolio rows
+
+use crate::currency::{Currency, CurrencyType};
+use crate::price_history;
+use crate::settings::Settings;
+use crate::trade::{Instrument, Side, Trade, TradingPair};
+use anyhow::{Context, Result, anyhow, bail};
+use csv::StringRecord;
+use rust_decimal::Decimal;
+use std::str::FromStr;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+/// Koinly labels of incoming rows which are income rather than deposits
+const KOINLY_INCOME: &[&str] = &[
+    "airdrop",
+    "fork",
+    "income",
+    "lending interest",
+    "loan interest",
+    "mining",
+    "other income",
+    "reward",
+    "staking",
+];
+/// Koinly labels of outgoing rows which are fees rather than withdrawals
+const KOINLY_FEES: &[&str] = &["cost", "loan fee", "margin fee"];
+
+const COINTRACKING_INCOME: &[&str] = &[
+    "airdrop",
+    "income",
+    "income (non taxable)",
+    "interest income",
+    "lending income",
+    "mining",
+    "mining (commercial)",
+    "reward / bonus",
+    "staking",
+];
+const COINTRACKING_FEES: &[&str] = &["borrowing fee", "margin fee", "other fee"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Koinly,
+    CoinTracking,
+}
+
+impl Format {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Koinly => "Koinly",
+            Format::CoinTracking => "CoinTracking",
+        }
+    }
+}
+
+/// The universal format of a CSV header, if it's one of them
+pub fn detect(headers: &StringRecord) -> Option<Format> {
+    let has = |name: &str| headers.iter().any(|h| h.eq_ignore_ascii_case(name));
+    if [
+        "Date",
+        "Sent Amount",
+        "Sent Currency",
+        "Received Amount",
+        "Received Currency",
+    ]
+    .iter()
+    .all(|h| has(h))
+    {
+        return Some(Format::Koinly);
+    }
+    let leading: Vec<&str> = headers.iter().take(7).collect();
+    if leading == ["Type", "Buy", "Cur.", "Sell", "Cur.", "Fee", "Cur."] && has("Date") {
+        return Some(Format::CoinTracking);
+    }
+    None
+}
+
+/// What one row of a universal export moved
+#[derive(Debug, Default)]
+struct Movement {
+    date: Option<OffsetDateTime>,
+    received: Option<(Decimal, Currency)>,
+    sent: Option<(Decimal, Currency)>,
+    fee: Option<(Decimal, Currency)>,
+    /// Value of the row in some fiat currency, Koinly's net worth
+    value: Option<(Decimal, String)>,
+    income: bool,
+    is_fee: bool,
+}
+
+impl Movement {
+    /// Unit price of `amount` of `asset` from the row's value, when that's in `base`
+    fn unit_value_of(
+        &self,
+        asset: &Currency,
+        amount: Decimal,
+        base: &str,
+    ) -> Option<(Currency, Decimal)> {
+        match &self.value {
+            Some((value, currency)) if currency == base => Some((asset.clone(), *value / amount)),
+            _ => None,
+        }
+    }
+}
+
+fn amount(
+    record: &StringRecord,
+    amount: usize,
+    currency: usize,
+) -> Result<Option<(Decimal, Currency)>> {
+    let value = record.get(amount).unwrap_or("").trim();
+    let ticker = record.get(currency).unwrap_or("").trim();
+    if value.is_empty() && ticker.is_empty() {
+        return Ok(None);
+    }
+    let value = Decimal::from_str(value)
+        .or_else(|_| Decimal::from_scientific(value))
+        .with_context(|| format!("invalid amount '{}'", value))?
+        .abs();
+    if value.is_zero() {
+        return Ok(None);
+    }
+    Ok(Some((value, Currency::new(ticker)?)))
+}
+
+/// Dates of both formats: `2024-01-10 10:40[:00] [UTC]`, RFC 3339, or
+/// CoinTracking's `10.01.2024 10:40[:00]`, all in UTC
+pub fn parse_date(s: &str) -> Result<OffsetDateTime> {
+    let s = s.trim();
+    if let Ok(ts) = OffsetDateTime::parse(s, &Rfc3339) {
+        return Ok(ts);
+    }
+    let s = s.trim_end_matches("UTC").trim();
+    let formats = [
+        format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"),
+        format_description!("[year]-[month]-[day] [hour]:[minute]"),
+        format_description!("[day].[month].[year] [hour]:[minute]:[second]"),
+        format_description!("[day].[month].[year] [hour]:[minute]"),
+    ];
+    formats
+        .iter()
+        .find_map(|f| PrimitiveDateTime::parse(s, f).ok())
+        .map(|dt| dt.assume_utc())
+        .ok_or(anyhow!("invalid date '{}'", s))
+}
+
+fn column(headers: &StringRecord, name: &str) -> Option<usize> {
+    headers.iter().position(|h| h.eq_ignore_ascii_case(name))
+}
+
+fn koinly(headers: &StringRecord, record: &StringRecord) -> Result<Movement> {
+    let col = |name: &str| column(headers, name).unwrap_or(usize::MAX);
+    let text = |name: &str| record.get(col(name)).unwrap_or("").trim();
+    let label = text("Label").to_lowercase();
+    let value = amount(record, col("Net Worth Amount"), col("Net Worth Currency"))?
+        .map(|(v, c)| (v, c.ticker().to_string()));
+    Ok(Movement {
+        date: Some(parse_date(text("Date"))?),
+        received: amount(record, col("Received Amount"), col("Received Currency"))?,
+        sent: amount(record, col("Sent Amount"), col("Sent Currency"))?,
+        fee: amount(record, col("Fee Amount"), col("Fee Currency"))?,
+        value,
+        income: KOINLY_INCOME.contains(&label.as_str()),
+        is_fee: KOINLY_FEES.contains(&label.as_str()),
+    })
+}
+
+fn cointracking(headers: &StringRecord, record: &StringRecord) -> Result<Movement> {
+    let kind = record.get(0).unwrap_or("").trim().to_lowercase();
+    let date = column(headers, "Date")
+        .and_then(|c| record.get(c))
+        .unwrap_or("");
+    Ok(Movement {
+        date: Some(parse_date(date)?),
+        received: amount(record, 1, 2)?,
+        sent: amount(record, 3, 4)?,
+        fee: amount(record, 5, 6)?,
+        value: None,
+        income: COINTRACKING_INCOME.contains(&kind.as_str()),
+        is_fee: COINTRACKING_FEES.contains(&kind.as_str()),
+    })
+}
+
+/// Unit value of `currency` in `base` on the row's date: 1 for the base
+/// itself, a price already known from the row, else the day's CoinGecko price
+fn unit_value(
+    currency: &Currency,
+    known: &[(Currency, Decimal)],
+    date: OffsetDateTime,
+    base: &str,
+    settings: &Settings,
+) -> Result<Decimal> {
+    if currency.ticker() == base {
+        return Ok(Decimal::ONE);
+    }
+    match known.iter().find(|(c, _)| c == currency) {
+        Some((_, price)) => Ok(*price),
+        None => price_history::price_on(currency, base, date.date(), settings),
+    }
+}
+
+fn cash_row(
+    date: OffsetDateTime,
+    side: Side,
+    asset: &Currency,
+    amount: Decimal,
+    price: Decimal,
+) -> Trade {
+    Trade {
+        created_at: date,
+        pair: Instrument::Asset(asset.clone()),
+        side,
+        amount,
+        price,
+        fee: Decimal::ZERO,
+        source: None,
+    }
+}
+
+/// Portfolio rows of one row of a universal export, valued in `base`
+pub fn to_trades(
+    format: Format,
+    headers: &StringRecord,
+    record: &StringRecord,
+    base: &str,
+    settings: &Settings,
+) -> Result<Vec<Trade>> {
+    let m = match format {
+        Format::Koinly => koinly(headers, record)?,
+        Format::CoinTracking => cointracking(headers, record)?,
+    };
+    let date = m.date.ok_or(anyhow!("missing date"))?;
+    let mut rows = Vec::new();
+    let mut fee_paid = false;
+    // unit prices in the base currency the row itself tells
+    let mut known = Vec::new();
+
+    match (&m.received, &m.sent) {
+        (Some((received, bought)), Some((sent, spent))) => {
+            // selling crypto for fiat or stable coins, anything else buys what was received
+            let sell = spent.currency_type() == CurrencyType::Crypto
+                && bought.currency_type() != CurrencyType::Crypto;
+            let (pair, amount, price, quote) = if sell {
+                (
+                    TradingPair::new(spent.clone(), bought.clone())?,
+                    *sent,
+                    *received / *sent,
+                    bought,
+                )
+            } else {
+                (
+                    TradingPair::new(bought.clone(), spent.clone())?,
+                    *received,
+                    *sent / *received,
+                    spent,
+                )
+            };
+            let fee = match &m.fee {
+                Some((fee, currency)) if currency == quote => {
+                    fee_paid = true;
+                    *fee
+                }
+                _ => Decimal::ZERO,
+            };
+            if quote.ticker() == base {
+                known.push((pair.base.clone(), price));
+            }
+            rows.push(Trade {
+                created_at: date,
+                pair: Instrument::Pair(pair),
+                side: if sell { Side::Sell } else { Side::Buy },
+                amount,
+                price,
+                fee,
+                source: None,
+            });
+        }
+        (Some((received, asset)), None) => {
+            let side = if m.income {
+                Side::Income
+            } else {
+                Side::Deposit
+            };
+            known.extend(m.unit_value_of(asset, *received, base));
+            let price = unit_value(asset, &known, date, base, settings)?;
+            rows.push(cash_row(date, side, asset, *received, price));
+        }
+        (None, Some((sent, asset))) => {
+            let side = if m.is_fee { Side::Fee } else { Side::Withdraw };
+            known.extend(m.unit_value_of(asset, *sent, base));
+            let price = unit_value(asset, &known, date, base, settings)?;
+            rows.push(cash_row(date, side, asset, *sent, price));
+        }
+        (None, None) if m.fee.is_none() => bail!("row moves nothing"),
+        (None, None) => {}
+    }
+
+    // fees not in the quote of a trade leave the portfolio as their own row
+    if let Some((fee, asset)) = &m.fee
+        && !fee_paid
+    {
+        let price = unit_value(asset, &known, date, base, settings)?;
+        rows.push(cash_row(date, Side::Fee, asset, *fee, price));
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::create_test_settings;
+    use tempfile::TempDir;
+
+    fn convert(format: Format, csv: &str) -> Vec<Result<Vec<String>>> {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        let headers = reader.headers().unwrap().clone();
+        assert_eq!(detect(&headers), Some(format));
+        reader
+            .records()
+            .map(|r| {
+                let rows = to_trades(format, &headers, &r.unwrap(), "USD", &settings)?;
+                Ok(rows
+                    .iter()
+                    .map(|t| t.to_record().iter().collect::<Vec<_>>().join(","))
+                    .collect())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_koinly_rows_map_to_trades_deposits_and_income() {
+        let rows = convert(
+            Format::Koinly,
+            "Date,Sent Amount,Sent Currency,Received Amount,Received Currency,Fee Amount,Fee Currency,Net Worth Amount,Net Worth Currency,Label,Description,TxHash
+2024-01-10 10:40 UTC,20000,USD,0.5,BTC,10,USD,20000,USD,,,
+2024-03-15 12:00 UTC,0.25,BTC,15000,USDT,,,15000,USD,,,
+2024-04-01 00:00 UTC,,,1000,USD,,,,,,,
+2024-05-01 00:00 UTC,,,0.01,ETH,,,30,USD,staking,,
+2024-06-01 00:00 UTC,0.1,BTC,,,0.0001,BTC,6000,USD,,,
+2024-07-01 00:00 UTC,1,XYZ,,,,,,,,,
+",
+        );
+        let ok: Vec<&Vec<String>> = rows.iter().filter_map(|r| r.as_ref().ok()).collect();
+        assert_eq!(
+            ok,
+            vec![
+                &vec!["1704883200,BTC/USD,BUY,0.5,40000,10".to_string()],
+                &vec!["1710504000,BTC/USDT,SELL,0.25,60000,0".to_string()],
+                &vec!["1711929600,USD,DEPOSIT,1000,1,0".to_string()],
+                &vec!["1714521600,ETH,INCOME,0.01,3000,0".to_string()],
+                &vec![
+                    "1717200000,BTC,WITHDRAW,0.1,60000,0".to_string(),
+                    "1717200000,BTC,FEE,0.0001,60000,0".to_string()
+                ],
+            ]
+        );
+        assert!(
+            format!("{:#}", rows[5].as_ref().unwrap_err()).contains("Unsupported ticker 'XYZ'")
+        );
+    }
+
+    #[test]
+    fn test_cointracking_types_map_to_sides() {
+        let rows = convert(
+            Format::CoinTracking,
+            r#""Type","Buy","Cur.","Sell","Cur.","Fee","Cur.","Exchange","Group","Comment","Date"
+"Trade","2","ETH","4000","USD","4","USD","Kraken","","","10.01.2024 10:40"
+"Deposit","500","USD","","","","","Kraken","","","11.01.2024 08:00:00"
+"Other Fee","","","25","USD","","","Kraken","","","12.01.2024 08:00"
+"Interest Income","1.5","USD","","","","","Nexo","","","13.01.2024 08:00"
+"#,
+        );
+        let rows: Vec<String> = rows.into_iter().flat_map(|r| r.unwrap()).collect();
+        assert_eq!(
+            rows,
+            vec![
+                "1704883200,ETH/USD,BUY,2,2000,4",
+                "1704960000,USD,DEPOSIT,500,1,0",
+                "1705046400,USD,FEE,25,1,0",
+                "1705132800,USD,INCOME,1.5,1,0",
+            ]
+        );
+        assert_eq!(
+            parse_date("2024-01-10T10:40:00Z").unwrap().unix_timestamp(),
+            1704883200
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Local usage statistics
+//!
+//! With `usage_stats = true` in the config, every command run is counted in
+//! `<state dir>/usage.json`: how often it ran and failed, when it last ran
+//! and how long it took. Nothing is ever sent anywhere; `info --stats` shows
+//! which commands dominate a workflow and how fast they usually are.
+
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use anyhow::Result;
+use prettytable::{Table, row};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use time::OffsetDateTime;
+use time::macros::format_description;
+
+/// Runs of one command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandUsage {
+    pub runs: u64,
+    pub failures: u64,
+    #[serde(with = "time::serde::timestamp")]
+    pub last_run: OffsetDateTime,
+    /// Time of all runs together, in milliseconds
+    pub total_ms: u64,
+    /// Time of the slowest run, in milliseconds
+    pub max_ms: u64,
+}
+
+/// Usage by command, e.g. `report` or `alert add`
+pub type Usage = BTreeMap<String, CommandUsage>;
+
+/// `<state dir>/usage.json`
+fn usage_path(settings: &Settings) -> PathBuf {
+    settings.state_dir().join("usage.json")
+}
+
+pub fn load(settings: &Settings) -> Result<Usage> {
+    let path = usage_path(settings);
+    if !path.exists() {
+        return Ok(Usage::new());
+    }
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+/// Name of the command run, with its subcommands, e.g. `snapshot compact`
+pub fn command_name(matches: &clap::ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut current = matches;
+    while let Some((name, sub)) = current.subcommand() {
+        names.push(name);
+        current = sub;
+    }
+    names.join(" ")
+}
+
+/// Count a run of `command` which took `took`, if `usage_stats` is on
+pub fn record(command: &str, took: Duration, ok: bool, settings: &Settings) -> Result<()> {
+    if !settings.usage_stats || command.is_empty() {
+        return Ok(());
+    }
+    let mut usage = load(settings)?;
+    let now = settings.clock.now().replace_nanosecond(0)?;
+    let ms = u64::try_from(took.as_millis()).unwrap_or(u64::MAX);
+    let entry = usage
+        .entry(command.to_string())
+        .or_insert_with(|| CommandUsage {
+            runs: 0,
+            failures: 0,
+            last_run: now,
+            total_ms: 0,
+            max_ms: 0,
+        });
+    entry.runs += 1;
+    entry.failures += u64::from(!ok);
+    entry.last_run = now;
+    entry.total_ms = entry.total_ms.saturating_add(ms);
+    entry.max_ms = entry.max_ms.max(ms);
+
+    let path = usage_path(settings);
+    std::fs::create_dir_all(settings.state_dir())?;
+    std::fs::write(path, serde_json::to_vec_pretty(&usage)?)?;
+    Ok(())
+}
+
+fn format_ms(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    }
+}
+
+/// `info --stats`: print the usage of each command, most run first
+pub fn print(reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    let usage = load(settings)?;
+    if !settings.usage_stats {
+        reporter.print("Usage stats are off, set `usage_stats = true` in the config to keep them");
+    }
+    if usage.is_empty() {
+        reporter.print(&format!(
+            "No usage recorded in {}",
+            usage_path(settings).display()
+        ));
+        return Ok(());
+    }
+
+    let mut commands: Vec<(&String, &CommandUsage)> = usage.iter().collect();
+    commands.sort_by(|a, b| b.1.runs.cmp(&a.1.runs).then(a.0.cmp(b.0)));
+    let last_run = format_description!("[year]-[month]-[day] [hour]:[minute]");
+    let mut table = Table::new();
+    table.add_row(row!["Command", "Runs", "Failed", "Last run", "Avg", "Max"]);
+    for (command, u) in commands {
+        table.add_row(row![
+            command,
+            r->u.runs,
+            r->u.failures,
+            u.last_run.format(&last_run)?,
+            r->format_ms(u.total_ms / u.runs.max(1)),
+            r->format_ms(u.max_ms)
+        ]);
+    }
+    reporter.table(&table)?;
+    reporter.print(&format!(
+        "Kept locally in {}, never sent anywhere",
+        usage_path(settings).display()
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::test_utils::helpers::create_test_settings;
+    use clap::CommandFactory;
+    use tempfile::TempDir;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_record_counts_runs_only_when_enabled() {
+        let dir = TempDir::new().unwrap();
+        let off = create_test_settings(dir.path().to_path_buf());
+        record("report", Duration::from_millis(120), true, &off).unwrap();
+        assert!(load(&off).unwrap().is_empty());
+
+        let settings = Settings {
+            usage_stats: true,
+            clock: Clock::Frozen(datetime!(2024-03-15 12:00 UTC)),
+            ..(*off).clone()
+        };
+        record("report", Duration::from_millis(120), true, &settings).unwrap();
+        record("report", Duration::from_millis(480), false, &settings).unwrap();
+        record("alert add", Duration::from_millis(5), true, &settings).unwrap();
+        let usage = load(&settings).unwrap();
+        assert_eq!(
+            usage["report"],
+            CommandUsage {
+                runs: 2,
+                failures: 1,
+                last_run: datetime!(2024-03-15 12:00 UTC),
+                total_ms: 600,
+                max_ms: 480,
+            }
+        );
+        assert_eq!(usage["alert add"].runs, 1);
+
+        let matches = crate::cli::Cli::command()
+            .try_get_matches_from(["portfolio-tracker", "snapshot", "compact", "--yes"])
+            .unwrap();
+        assert_eq!(command_name(&matches), "snapshot compact");
+        assert_eq!(format_ms(1250), "1.2s");
+    }
+}
@@ -0,0 +1,16 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Warnings printed so far by this run
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Print a warning to stderr and count it, see `report --fail-on-warning`
+pub fn warn(message: impl Display) {
+    COUNT.fetch_add(1, Ordering::Relaxed);
+    eprintln!("⚠️  {}", message);
+}
+
+/// Number of warnings printed so far
+pub fn count() -> usize {
+    COUNT.load(Ordering::Relaxed)
+}
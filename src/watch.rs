@@ -0,0 +1,239 @@
+use crate::alert::{self, Alert, Throttle, Trigger};
+use crate::currency::Currency;
+use crate::dca;
+use crate::inbox;
+use crate::interrupt;
+use crate::portfolio::list_entries;
+use crate::position_cache;
+use crate::quote::{self, LiveQuotes, Prices, quote_in_base};
+use crate::refresh;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::snapshot::{self, Snapshot};
+use crate::stops;
+use crate::warnings;
+use anyhow::Result;
+use rust_decimal::Decimal;
+use std::collections::BTreeSet;
+use std::sync::mpsc;
+use std::time::Instant;
+use time::Duration;
+
+/// `watch` keeps at most one snapshot per hour of each portfolio
+const SNAPSHOT_EVERY: Duration = Duration::hours(1);
+/// Quiet time after the last change in the inbox before its files are
+/// imported, so a file still being written isn't picked up half way
+const INBOX_SETTLE: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Print `message` of a firing alert, unless it's snoozed or notified
+/// within its cooldown; whether it was printed
+fn notify(
+    alert: &Alert,
+    message: &str,
+    throttle: &mut Throttle,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> bool {
+    if !throttle.notify(alert, settings) {
+        return false;
+    }
+    reporter.print(&format!("🔔 alert #{}: {}", alert.id, message));
+    true
+}
+
+/// Value all portfolios, record hourly snapshots and evaluate the alerts;
+/// missed DCA buys already in `flagged` aren't flagged again
+fn poll(
+    reporter: &dyn Reporter,
+    settings: &Settings,
+    flagged: &mut BTreeSet<(String, time::Date)>,
+) -> Result<usize> {
+    let now = settings.clock.now();
+    let alerts = alert::load(settings)?;
+    let mut throttle = Throttle::load(settings)?;
+    let stops = stops::load(settings)?;
+    let mut fired = 0;
+    // left out when a portfolio can't be valued, a partial sum would look like a drop
+    let entries = list_entries(settings)?;
+    let mut total = (!entries.is_empty()).then_some(Decimal::ZERO);
+
+    for entry in entries {
+        let value = match snapshot::current_value(&entry.name, settings) {
+            Ok(value) => value,
+            Err(e) => {
+                warnings::warn(format!("can't value '{}': {}", entry.name, e));
+                total = None;
+                continue;
+            }
+        };
+        if let Some(sum) = total {
+            total = match snapshot::in_workspace_base(&entry.name, value, settings) {
+                Ok(value) => Some(sum + value),
+                Err(e) => {
+                    warnings::warn(format!("can't add '{}' to the total: {}", entry.name, e));
+                    None
+                }
+            };
+        }
+        let history = snapshot::load(&entry.name, settings)?;
+
+        let position_alerts: Vec<&Alert> = alerts
+            .iter()
+            .filter(|a| {
+                matches!(&a.trigger,
+                    Trigger::PositionAbove { portfolio, .. } | Trigger::PositionBelow { portfolio, .. }
+                    if *portfolio == entry.name)
+            })
+            .collect();
+        if !position_alerts.is_empty() {
+            let pf = position_cache::load(&entry.path, settings)?;
+            let values = pf.position_values(&LiveQuotes)?;
+            for alert in position_alerts {
+                if let Some(message) = alert::check_position(&alert.trigger, &values, &pf.base)
+                    && notify(alert, &message, &mut throttle, reporter, settings)
+                {
+                    fired += 1;
+                }
+            }
+        }
+
+        if let Some(levels) = stops.get(&entry.name) {
+            let base = position_cache::load(&entry.path, settings)?.base;
+            for (asset, levels) in levels {
+                let price = LiveQuotes.price_in(&Currency::new(asset)?, base.ticker())?;
+                if let Some(message) = stops::check(asset, levels, price) {
+                    reporter.print(&format!("🔔 {}: {}", entry.name, message));
+                    fired += 1;
+                }
+            }
+        }
+
+        for alert in alerts.iter().filter(|a| {
+            matches!(&a.trigger, Trigger::PortfolioMove { portfolio, .. } if *portfolio == entry.name)
+        }) {
+            if let Some(message) = alert::check_move(&alert.trigger, value, &history, now)
+                && notify(alert, &message, &mut throttle, reporter, settings)
+            {
+                fired += 1;
+            }
+        }
+
+        let due = history
+            .last()
+            .is_none_or(|last| now.unix_timestamp() - last.ts >= SNAPSHOT_EVERY.whole_seconds());
+        if due {
+            let snapshot = Snapshot {
+                ts: now.unix_timestamp(),
+                value,
+            };
+            snapshot::record(&entry.name, snapshot, settings)?;
+        }
+    }
+    if let Some(total) = total {
+        let due = snapshot::load_total(settings)?
+            .last()
+            .is_none_or(|last| now.unix_timestamp() - last.ts >= SNAPSHOT_EVERY.whole_seconds());
+        if due {
+            let snapshot = Snapshot {
+                ts: now.unix_timestamp(),
+                value: total,
+            };
+            snapshot::record_total(snapshot, settings)?;
+        }
+    }
+
+    for alert in &alerts {
+        if let Trigger::PriceAbove { ticker, .. } | Trigger::PriceBelow { ticker, .. } =
+            &alert.trigger
+        {
+            let price = quote_in_base(&Currency::new(ticker)?, settings.base_currency.ticker())?;
+            if let Some(message) = alert::check_price(&alert.trigger, price)
+                && notify(alert, &message, &mut throttle, reporter, settings)
+            {
+                fired += 1;
+            }
+        }
+    }
+
+    for (plan, day) in dca::newly_missed(now.date(), settings) {
+        if flagged.insert((plan.clone(), day)) {
+            reporter.print(&format!(
+                "🔔 DCA plan {}: no buy recorded for {}",
+                plan, day
+            ));
+            fired += 1;
+        }
+    }
+
+    throttle.save(settings)?;
+    Ok(fired)
+}
+
+/// Poll every `interval_secs` until Ctrl-C, or once with `once`; with
+/// `inbox`, exports dropped into the inbox are imported first, and as soon
+/// as they are dropped while waiting for the next poll
+///
+/// Quotes are refreshed on a staggered schedule, see `refresh::Schedule`.
+pub fn run(
+    interval_secs: u64,
+    once: bool,
+    inbox: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    if inbox && !once {
+        reporter.print(&format!(
+            "Importing exports dropped into {}",
+            inbox::inbox_dir(settings)?.display()
+        ));
+    }
+    let dropped = if inbox && !once {
+        let (tx, rx) = mpsc::channel();
+        Some((inbox::watcher(tx, settings)?, rx))
+    } else {
+        None
+    };
+    let mut schedule = refresh::Schedule::default();
+    let mut flagged = BTreeSet::new();
+    if !once && !settings.no_cache {
+        quote::keep_quotes_for(refresh::max_age(std::time::Duration::from_secs(
+            interval_secs,
+        )));
+    }
+    loop {
+        if inbox {
+            inbox::poll(reporter, settings)?;
+        }
+        if settings.no_cache {
+            quote::clear_cache();
+        } else {
+            schedule.refresh(settings)?;
+        }
+        let fired = poll(reporter, settings, &mut flagged)?;
+        if fired == 0 {
+            reporter.print(&format!(
+                "{} no alerts",
+                settings.clock.now().unix_timestamp()
+            ));
+        }
+        if once {
+            return Ok(());
+        }
+        let next = Instant::now() + std::time::Duration::from_secs(interval_secs);
+        while Instant::now() < next && !interrupt::interrupted() {
+            let tick = std::time::Duration::from_millis(100);
+            match &dropped {
+                Some((_, rx)) if rx.recv_timeout(tick).is_ok() => {
+                    while rx.recv_timeout(INBOX_SETTLE).is_ok() {}
+                    inbox::poll(reporter, settings)?;
+                }
+                Some(_) => {}
+                None => std::thread::sleep(tick),
+            }
+        }
+        if interrupt::interrupted() {
+            reporter.print("Stopped watching");
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Status bar widgets
+//!
+//! `report --widget waybar` prints the JSON line a waybar `custom` module
+//! with `"return-type": "json"` reads, `--widget polybar` the text line of a
+//! polybar `custom/script`. Positions come from the position cache and the
+//! daily change from the snapshots `watch` or `snapshot` take, so a run only
+//! fetches quotes and is cheap enough for every minute.
+
+use crate::currency::Currency;
+use crate::math::{self, Percent};
+use crate::portfolio::list_entries;
+use crate::position_cache;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+use crate::snapshot::{self, Snapshot};
+use anyhow::Result;
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+use serde_json::json;
+use thousands::Separable;
+use time::Duration;
+
+/// Status bar a widget line is printed for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Widget {
+    /// JSON with text, tooltip and class, for a `custom` module
+    Waybar,
+    /// Plain text, for a `custom/script` module
+    Polybar,
+}
+
+/// Value of a portfolio, or the workspace total, and its change since the
+/// latest snapshot a day old
+#[derive(Debug, Clone, PartialEq)]
+struct Reading {
+    label: String,
+    value: Decimal,
+    base: Currency,
+    change: Option<Percent>,
+}
+
+impl Reading {
+    fn new(
+        label: &str,
+        value: Decimal,
+        base: Currency,
+        history: &[Snapshot],
+        settings: &Settings,
+    ) -> Self {
+        let change = snapshot::before(history, settings.clock.now(), Duration::days(1))
+            .and_then(|previous| math::change_pct(previous.value, value));
+        Reading {
+            label: label.to_string(),
+            value,
+            base,
+            change,
+        }
+    }
+
+    /// `12,345 USD +2.10%`, whole units so the bar stays narrow
+    fn text(&self) -> String {
+        let value = format!(
+            "{} {}",
+            self.value.round_dp(0).separate_with_commas(),
+            self.base
+        );
+        match self.change {
+            Some(change) => format!("{} {:+.2}", value, change.round_dp(2)),
+            None => value,
+        }
+    }
+
+    fn tooltip_line(&self) -> String {
+        let change = match self.change {
+            Some(change) => format!("{:+.2} in 24h", change.round_dp(2)),
+            None => "no snapshot from a day ago".to_string(),
+        };
+        format!(
+            "{}: {} {}, {}",
+            self.label,
+            self.value.round_dp(2).separate_with_commas(),
+            self.base,
+            change
+        )
+    }
+
+    /// Waybar class by the sign of the daily change, for styling
+    fn class(&self) -> &'static str {
+        match self.change {
+            None => "unknown",
+            Some(change) if change > Percent::ZERO => "up",
+            Some(change) if change < Percent::ZERO => "down",
+            Some(_) => "flat",
+        }
+    }
+}
+
+fn portfolio(name: &str, settings: &Settings) -> Result<Reading> {
+    let value = snapshot::current_value(name, settings)?;
+    let base = position_cache::load(settings.path_for(name), settings)?.base;
+    let history = snapshot::load(name, settings)?;
+    Ok(Reading::new(name, value, base, &history, settings))
+}
+
+/// The workspace total in the configured base currency, then every portfolio
+fn all(settings: &Settings) -> Result<Vec<Reading>> {
+    let mut readings = Vec::new();
+    let mut total = Decimal::ZERO;
+    for entry in list_entries(settings)? {
+        let reading = portfolio(&entry.name, settings)?;
+        total += snapshot::in_workspace_base(&entry.name, reading.value, settings)?;
+        readings.push(reading);
+    }
+    let history = snapshot::load_total(settings)?;
+    let base = settings.base_currency.clone();
+    readings.insert(0, Reading::new("total", total, base, &history, settings));
+    Ok(readings)
+}
+
+/// The widget line of `readings`, the first shown in the bar and all of
+/// them in the tooltip
+fn line(widget: Widget, readings: &[Reading]) -> String {
+    let shown = &readings[0];
+    match widget {
+        Widget::Waybar => {
+            let tooltip: Vec<String> = readings.iter().map(Reading::tooltip_line).collect();
+            json!({
+                "text": shown.text(),
+                "tooltip": tooltip.join("\n"),
+                "class": shown.class(),
+            })
+            .to_string()
+        }
+        Widget::Polybar => shown.text(),
+    }
+}
+
+/// `report --widget`: print the line of portfolio `name`, or of all of them
+///
+/// Errors are printed as the widget instead of failing, a status bar shows
+/// nothing of a script that fails.
+pub fn print(
+    widget: Widget,
+    name: Option<&str>,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    let readings = match name {
+        Some(name) => portfolio(name, settings).map(|r| vec![r]),
+        None => all(settings),
+    };
+    let line = match (readings, widget) {
+        (Ok(readings), _) => line(widget, &readings),
+        (Err(e), Widget::Waybar) => json!({
+            "text": "⚠️",
+            "tooltip": format!("{:#}", e),
+            "class": "error",
+        })
+        .to_string(),
+        (Err(e), Widget::Polybar) => format!("⚠️ {:#}", e),
+    };
+    reporter.print(&line);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::test_utils::helpers::create_test_settings;
+    use rust_decimal::dec;
+    use tempfile::TempDir;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_line_shows_the_daily_change_and_classes_its_sign() {
+        let dir = TempDir::new().unwrap();
+        let now = datetime!(2025-03-02 12:00 UTC);
+        let settings = Settings {
+            clock: Clock::Frozen(now),
+            ..(*create_test_settings(dir.path().to_path_buf())).clone()
+        };
+        let usd = Currency::new("USD").unwrap();
+        let history = [
+            Snapshot {
+                ts: (now - Duration::hours(30)).unix_timestamp(),
+                value: dec!(10000),
+            },
+            Snapshot {
+                ts: (now - Duration::hours(1)).unix_timestamp(),
+                value: dec!(12000),
+            },
+        ];
+        let total = Reading::new("total", dec!(12345.678), usd.clone(), &history, &settings);
+        let main = Reading::new("main", dec!(9000), usd.clone(), &history[1..], &settings);
+
+        assert_eq!(
+            line(Widget::Waybar, &[total.clone(), main]),
+            r#"{"class":"up","text":"12,346 USD +23.46%","tooltip":"total: 12,345.68 USD, +23.46% in 24h\nmain: 9,000 USD, no snapshot from a day ago"}"#
+        );
+        assert_eq!(line(Widget::Polybar, &[total]), "12,346 USD +23.46%");
+
+        let down = Reading::new("main", dec!(9000), usd.clone(), &history, &settings);
+        assert_eq!(down.class(), "down");
+        let flat = Reading::new("main", dec!(10000), usd.clone(), &history, &settings);
+        assert_eq!(flat.class(), "flat");
+        assert_eq!(
+            Reading::new("main", dec!(1), usd, &[], &settings).class(),
+            "unknown"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use crate::cli::Cli;
+use crate::currency::Currency;
+use crate::quote;
+use crate::settings::{CostBasisMethod, Settings, config_path};
+use anyhow::{Context, Result, bail};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Supported quote providers, offered by the wizard
+pub const QUOTE_PROVIDERS: [&str; 2] = ["coingecko", "binance"];
+
+/// The wizard is offered only on the first run in an interactive terminal
+pub fn should_run(cli: &Cli) -> bool {
+    !cli.no_wizard
+        && cli.commands.offers_wizard()
+        && !config_path().exists()
+        && std::io::stdin().is_terminal()
+        && std::io::stdout().is_terminal()
+}
+
+/// Ask the setup questions on the terminal and write the config file
+pub fn run_interactive() -> Result<()> {
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    writeln!(
+        stdout,
+        "No config file found, let's create one (skip with --no-wizard).\n\
+         Press enter to accept the [default]."
+    )?;
+    let settings = ask(&mut stdin.lock(), &mut stdout)?;
+    let path = config_path();
+    write_config(&path, &settings)?;
+    writeln!(stdout, "✅ Config written to: {}\n", path.display())?;
+    Ok(())
+}
+
+/// Ask all wizard questions, re-asking until an answer is valid
+pub fn ask<R: BufRead, W: Write>(input: &mut R, output: &mut W) -> Result<Settings> {
+    let mut settings = Settings::default();
+
+    settings.portfolio_dir = prompt(
+        input,
+        output,
+        "Data directory for portfolio files",
+        &settings.portfolio_dir.display().to_string(),
+        |s| Ok(PathBuf::from(shellexpand::tilde(s).to_string())),
+    )?;
+    settings.base_currency = prompt(
+        input,
+        output,
+        "Base currency",
+        settings.base_currency.ticker(),
+        Currency::new,
+    )?;
+    settings.quote_provider = prompt(
+        input,
+        output,
+        &format!(
+            "Quote provider, comma separated for consensus ({})",
+            QUOTE_PROVIDERS.join(", ")
+        ),
+        &settings.quote_provider,
+        |s| quote::parse_providers(s).map(|_| s.to_string()),
+    )?;
+    settings.cost_basis = prompt(
+        input,
+        output,
+        "Cost basis method (average)",
+        "average",
+        |s| {
+            serde_plain::from_str::<CostBasisMethod>(&s.to_ascii_lowercase())
+                .with_context(|| format!("unsupported cost basis method '{}'", s))
+        },
+    )?;
+
+    Ok(settings)
+}
+
+/// Ask `question` until `parse` accepts the answer, `default` for an empty one
+pub fn prompt<R, W, T, F>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+    default: &str,
+    parse: F,
+) -> Result<T>
+where
+    R: BufRead,
+    W: Write,
+    F: Fn(&str) -> Result<T>,
+{
+    loop {
+        write!(output, "{} [{}]: ", question, default)?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            bail!("wizard aborted (end of input)");
+        }
+        let answer = match line.trim() {
+            "" => default,
+            other => other,
+        };
+
+        match parse(answer) {
+            Ok(value) => return Ok(value),
+            Err(e) => writeln!(output, "  invalid answer: {}", e)?,
+        }
+    }
+}
+
+/// The config keys the wizard asks for
+const ASKED: [&str; 4] = [
+    "portfolio_dir",
+    "base_currency",
+    "quote_provider",
+    "cost_basis",
+];
+
+/// Write the answers of the wizard; the other keys are left out, so they
+/// keep following the defaults of later versions
+pub fn write_config(path: &Path, settings: &Settings) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut answers = toml::Table::try_from(settings).context("serializing config")?;
+    answers.retain(|key, _| ASKED.contains(&key));
+    let content = toml::to_string(&answers).context("serializing config")?;
+    std::fs::write(path, content).with_context(|| format!("writing {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_defaults_accepted_with_enter() {
+        let mut input = Cursor::new("\n\n\n\n");
+        let mut output = Vec::new();
+        let settings = ask(&mut input, &mut output).unwrap();
+
+        assert_eq!(settings.portfolio_dir, PathBuf::from("./portfolios"));
+        assert_eq!(settings.base_currency.ticker(), "USD");
+        assert_eq!(settings.quote_provider, "coingecko");
+        assert_eq!(settings.cost_basis, CostBasisMethod::Average);
+    }
+
+    #[test]
+    fn test_invalid_answer_is_asked_again() {
+        let mut input = Cursor::new("/tmp/pf\nNOTACOIN\neur\nkraken\ncoingecko,binance\n\n");
+        let mut output = Vec::new();
+        let settings = ask(&mut input, &mut output).unwrap();
+
+        assert_eq!(settings.portfolio_dir, PathBuf::from("/tmp/pf"));
+        assert_eq!(settings.base_currency.ticker(), "EUR");
+        assert_eq!(settings.quote_provider, "coingecko,binance");
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("invalid answer: Unsupported ticker 'NOTACOIN'"));
+        assert!(output.contains("invalid answer: unsupported quote provider 'kraken'"));
+    }
+
+    #[test]
+    fn test_end_of_input_aborts() {
+        let mut input = Cursor::new("");
+        let err = ask(&mut input, &mut Vec::new()).unwrap_err();
+        assert!(err.to_string().contains("aborted"));
+    }
+
+    #[test]
+    fn test_written_config_is_loadable() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("csvpt/config.toml");
+        let settings = ask(&mut Cursor::new("/data\ncad\n\n\n"), &mut Vec::new()).unwrap();
+        write_config(&path, &settings).unwrap();
+
+        let loaded: Settings = config::Config::builder()
+            .add_source(config::File::from(path))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        assert_eq!(loaded.portfolio_dir, PathBuf::from("/data"));
+        assert_eq!(loaded.base_currency.ticker(), "CAD");
+
+        let written: toml::Table = std::fs::read_to_string(dir.path().join("csvpt/config.toml"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut keys: Vec<&str> = written.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "base_currency",
+                "cost_basis",
+                "portfolio_dir",
+                "quote_provider"
+            ]
+        );
+    }
+}
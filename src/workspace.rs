@@ -0,0 +1,408 @@
+use crate::confirm::confirm;
+use crate::reporter::Reporter;
+use crate::settings::{Settings, config_path};
+use crate::writer;
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever the archive layout changes in a non backward compatible way
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+const MANIFEST_NAME: &str = "manifest.json";
+const PORTFOLIOS_PREFIX: &str = "portfolios";
+const CONFIG_ENTRY: &str = "config/config.toml";
+
+/// Describes the content of a workspace archive.
+///
+/// Written as the first entry of the archive, so import can verify
+/// every file before anything is written to disk.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Manifest {
+    pub format_version: u32,
+    pub crate_version: String,
+    pub created_at: i64,
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ManifestEntry {
+    /// Path inside the archive, always with `/` separators
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Collect all regular files below `dir`, recursively (portfolios and the state dir)
+fn collect_files(dir: &Path, skip: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in dir.read_dir()? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(collect_files(&path, skip)?);
+        } else if path.is_file() && std::path::absolute(&path)? != skip {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn archive_name(prefix: &str, rel: &Path) -> String {
+    let parts: Vec<String> = rel
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    format!("{}/{}", prefix, parts.join("/"))
+}
+
+/// Bundle portfolios, tool state (snapshots, caches) and the config dotfile into one tar archive
+///
+/// The state dir goes in whole, so the price history and fx rate caches
+/// travel along and an imported workspace values past days without
+/// refetching them. Only the locks are left out.
+pub fn export(out: &Path, reporter: &dyn Reporter, settings: &Settings) -> Result<()> {
+    export_with_config(out, reporter, settings, &config_path())
+}
+
+fn export_with_config(
+    out: &Path,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+    config: &Path,
+) -> Result<()> {
+    let out_abs = std::path::absolute(out)?;
+    let mut entries: Vec<(String, Vec<u8>)> = Vec::new();
+
+    // locks only matter to processes running on this machine
+    let locks = writer::locks_dir(settings);
+    for path in collect_files(&settings.portfolio_dir, &out_abs)? {
+        if path.starts_with(&locks) {
+            continue;
+        }
+        let rel = path.strip_prefix(&settings.portfolio_dir)?;
+        let data = std::fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+        entries.push((archive_name(PORTFOLIOS_PREFIX, rel), data));
+    }
+
+    if config.is_file() {
+        entries.push((CONFIG_ENTRY.to_string(), std::fs::read(config)?));
+    }
+
+    let now = settings.clock.now().unix_timestamp();
+    let manifest = Manifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: now,
+        files: entries
+            .iter()
+            .map(|(path, data)| ManifestEntry {
+                path: path.clone(),
+                size: data.len() as u64,
+                sha256: sha256_hex(data),
+            })
+            .collect(),
+    };
+
+    let file = File::create(out).with_context(|| format!("creating {}", out.display()))?;
+    let mut builder = tar::Builder::new(file);
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    append(&mut builder, MANIFEST_NAME, &manifest_json, now)?;
+    for (path, data) in &entries {
+        append(&mut builder, path, data, now)?;
+    }
+    builder.into_inner()?.sync_all()?;
+
+    reporter.status(&format!(
+        "Exported {} files to workspace archive: {}",
+        manifest.files.len(),
+        out.display()
+    ));
+    Ok(())
+}
+
+fn append(builder: &mut tar::Builder<File>, path: &str, data: &[u8], mtime: i64) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+/// Read and verify the archive, returning the manifest and the verified file contents
+pub fn read_archive(archive: &Path) -> Result<(Manifest, HashMap<String, Vec<u8>>)> {
+    let file = File::open(archive).with_context(|| format!("opening {}", archive.display()))?;
+    let mut tar = tar::Archive::new(file);
+    let mut contents: HashMap<String, Vec<u8>> = HashMap::new();
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        contents.insert(path, data);
+    }
+
+    let manifest_data = contents
+        .remove(MANIFEST_NAME)
+        .ok_or(anyhow!("archive has no {}", MANIFEST_NAME))?;
+    let manifest: Manifest =
+        serde_json::from_slice(&manifest_data).context("parsing archive manifest")?;
+
+    if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+        bail!(
+            "archive format version {} is newer than supported {} (exported by version {})",
+            manifest.format_version,
+            ARCHIVE_FORMAT_VERSION,
+            manifest.crate_version
+        );
+    }
+
+    for entry in &manifest.files {
+        let data = contents.get(&entry.path).ok_or(anyhow!(
+            "file listed in manifest is missing: {}",
+            entry.path
+        ))?;
+        if data.len() as u64 != entry.size || sha256_hex(data) != entry.sha256 {
+            bail!("checksum mismatch for {}", entry.path);
+        }
+        // reject anything that could escape the target directory
+        if entry.path.split('/').any(|p| p == ".." || p.is_empty()) {
+            bail!("invalid path in archive: {}", entry.path);
+        }
+    }
+
+    Ok((manifest, contents))
+}
+
+/// Restore a workspace archive into the configured portfolio dir and config location
+pub fn import(
+    archive: &Path,
+    force: bool,
+    yes: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+) -> Result<()> {
+    import_with_config(archive, force, yes, reporter, settings, &config_path())
+}
+
+fn import_with_config(
+    archive: &Path,
+    force: bool,
+    yes: bool,
+    reporter: &dyn Reporter,
+    settings: &Settings,
+    config: &Path,
+) -> Result<()> {
+    let (manifest, contents) = read_archive(archive)?;
+
+    let mut targets: Vec<(PathBuf, &[u8])> = Vec::new();
+    for entry in &manifest.files {
+        let data = contents[&entry.path].as_slice();
+        let target = if entry.path == CONFIG_ENTRY {
+            config.to_path_buf()
+        } else if let Some(rel) = entry.path.strip_prefix(&format!("{}/", PORTFOLIOS_PREFIX)) {
+            rel.split('/')
+                .fold(settings.portfolio_dir.clone(), |p, c| p.join(c))
+        } else {
+            bail!("unexpected file in archive: {}", entry.path);
+        };
+        targets.push((target, data));
+    }
+
+    // check all targets first, so import doesn't leave a half written workspace
+    let existing: Vec<String> = targets
+        .iter()
+        .filter(|(t, _)| t.exists())
+        .map(|(t, _)| t.display().to_string())
+        .collect();
+    if !force && !existing.is_empty() {
+        bail!(
+            "refusing to overwrite existing files (use --force):\n{}",
+            existing.join("\n")
+        );
+    }
+    confirm(
+        &format!("Importing overwrites {} existing files", existing.len()),
+        &existing,
+        existing.len(),
+        yes,
+        settings,
+    )?;
+
+    for (target, data) in &targets {
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(target, data).with_context(|| format!("writing {}", target.display()))?;
+    }
+
+    reporter.status(&format!(
+        "Imported {} files from workspace archive: {} (exported by version {})",
+        targets.len(),
+        archive.display(),
+        manifest.crate_version
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::reporter::Silent;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use rust_decimal::dec;
+    use tempfile::TempDir;
+
+    const CSV: &str = "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1.0,40000.00,7.50
+";
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_export_then_import_restores_identical_files() {
+        let src = TempDir::new().unwrap();
+        create_test_csv(&src, "alpha", CSV);
+        std::fs::create_dir_all(src.path().join(".csvpt/snapshots")).unwrap();
+        std::fs::write(src.path().join(".csvpt/snapshots/alpha.csv"), "ts,value\n").unwrap();
+        let settings = create_test_settings(src.path().to_path_buf());
+        crate::price_history::save_cached(
+            "BTC",
+            "USD",
+            &[("2024-01-10".to_string(), dec!(46000))].into(),
+            &settings,
+        )
+        .unwrap();
+
+        let out = TempDir::new().unwrap();
+        let config = out.path().join("config.toml");
+        std::fs::write(&config, "base_currency = \"EUR\"\n").unwrap();
+        let archive = out.path().join("ws.cpt");
+        export_with_config(&archive, &Silent, &settings, &config).unwrap();
+
+        let (manifest, _) = read_archive(&archive).unwrap();
+        let paths: Vec<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "portfolios/.csvpt/prices/BTC-USD.json",
+                "portfolios/.csvpt/snapshots/alpha.csv",
+                "portfolios/alpha.csv",
+                CONFIG_ENTRY
+            ]
+        );
+
+        let dst = TempDir::new().unwrap();
+        let dst_config = dst.path().join("conf/config.toml");
+        let dst_settings = create_test_settings(dst.path().to_path_buf());
+        import_with_config(&archive, false, false, &Silent, &dst_settings, &dst_config).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dst.path().join("alpha.csv")).unwrap(),
+            CSV
+        );
+        assert!(dst.path().join(".csvpt/snapshots/alpha.csv").is_file());
+        assert_eq!(
+            crate::price_history::load_cached("BTC", "USD", &dst_settings).unwrap()["2024-01-10"],
+            dec!(46000)
+        );
+        assert_eq!(
+            std::fs::read_to_string(dst_config).unwrap(),
+            "base_currency = \"EUR\"\n"
+        );
+    }
+
+    #[test]
+    fn test_export_is_dated_by_the_settings_clock() {
+        let src = TempDir::new().unwrap();
+        create_test_csv(&src, "alpha", CSV);
+        let mut settings = (*create_test_settings(src.path().to_path_buf())).clone();
+        settings.clock = Clock::Frozen(time::macros::datetime!(2024-03-15 12:00 UTC));
+
+        let out = TempDir::new().unwrap();
+        let config = out.path().join("config.toml");
+        let first = out.path().join("first.cpt");
+        let second = out.path().join("second.cpt");
+        export_with_config(&first, &Silent, &settings, &config).unwrap();
+        export_with_config(&second, &Silent, &settings, &config).unwrap();
+
+        let (manifest, _) = read_archive(&first).unwrap();
+        assert_eq!(manifest.created_at, 1710504000);
+        let mut tar = tar::Archive::new(File::open(&first).unwrap());
+        for entry in tar.entries().unwrap() {
+            assert_eq!(entry.unwrap().header().mtime().unwrap(), 1710504000);
+        }
+        assert_eq!(
+            std::fs::read(first).unwrap(),
+            std::fs::read(second).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_import_refuses_to_overwrite_without_force() {
+        let src = TempDir::new().unwrap();
+        create_test_csv(&src, "alpha", CSV);
+        let archive = src.path().join("ws.cpt");
+        let settings = create_test_settings(src.path().to_path_buf());
+        let no_config = src.path().join("missing.toml");
+        export_with_config(&archive, &Silent, &settings, &no_config).unwrap();
+
+        let (manifest, _) = read_archive(&archive).unwrap();
+        // archive written inside the portfolio dir must not include itself
+        assert!(manifest.files.iter().all(|f| !f.path.ends_with("ws.cpt")));
+
+        let err =
+            import_with_config(&archive, false, false, &Silent, &settings, &no_config).unwrap_err();
+        assert!(err.to_string().contains("refusing to overwrite"));
+    }
+
+    #[test]
+    fn test_read_archive_detects_tampered_file() {
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("bad.cpt");
+        let manifest = Manifest {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            crate_version: "0.0.0".to_string(),
+            created_at: 0,
+            files: vec![ManifestEntry {
+                path: "portfolios/a.csv".to_string(),
+                size: 3,
+                sha256: sha256_hex(b"abc"),
+            }],
+        };
+        let mut builder = tar::Builder::new(File::create(&archive).unwrap());
+        append(
+            &mut builder,
+            MANIFEST_NAME,
+            &serde_json::to_vec(&manifest).unwrap(),
+            0,
+        )
+        .unwrap();
+        append(&mut builder, "portfolios/a.csv", b"abd", 0).unwrap();
+        builder.finish().unwrap();
+        drop(builder);
+
+        let err = read_archive(&archive).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"), "got: {err}");
+    }
+}
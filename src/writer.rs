@@ -0,0 +1,195 @@
+//! Batched appends for programs embedding the crate
+//!
+//! Bots and sync daemons writing many trades use a `PortfolioWriter` instead
+//! of calling `trade::tx_to_csv` per trade: rows are validated as they are
+//! appended, and `flush` writes them all at once under the portfolio lock,
+//! replacing the file atomically so readers never see half a batch.
+//!
+//! ```no_run
+//! # use portfolio_tracker::settings::Settings;
+//! # use portfolio_tracker::writer::PortfolioWriter;
+//! # fn main() -> anyhow::Result<()> {
+//! # let settings = Settings::default();
+//! # let trades = Vec::new();
+//! let mut writer = PortfolioWriter::open("main", "bot:grid", &settings)?;
+//! for trade in trades {
+//!     writer.append(trade)?;
+//! }
+//! writer.flush()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::index;
+use crate::settings::Settings;
+use crate::trade::{
+    CSV_HEADER, SOURCE_COLUMN, Trade, ensure_portfolio_file, parse_csv_records,
+    sort_records_chronologically, write_csv_records,
+};
+use anyhow::{Context, Result, bail};
+use std::fs::File;
+use std::path::PathBuf;
+
+const LOCKS_DIR: &str = "locks";
+
+/// Exclusive lock of a portfolio between writing processes, released when dropped
+///
+/// Taken by `trade::tx_to_csv` and `PortfolioWriter::flush`, waiting while
+/// another process holds it.
+pub struct PortfolioLock {
+    _file: File,
+}
+
+/// Where the lock files of `lock` are, left out of workspace exports
+pub fn locks_dir(settings: &Settings) -> PathBuf {
+    settings.state_dir().join(LOCKS_DIR)
+}
+
+/// Lock portfolio `name` for writing, see `PortfolioLock`
+pub fn lock(name: &str, settings: &Settings) -> Result<PortfolioLock> {
+    let dir = locks_dir(settings);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.lock", name));
+    let file = File::create(&path).with_context(|| format!("creating {}", path.display()))?;
+    file.lock()
+        .with_context(|| format!("locking {}", path.display()))?;
+    Ok(PortfolioLock { _file: file })
+}
+
+/// Appends trades to a portfolio in batches, see the module docs
+///
+/// Trades not flushed are discarded when the writer is dropped.
+pub struct PortfolioWriter<'a> {
+    name: String,
+    path: PathBuf,
+    source: String,
+    pending: Vec<csv::StringRecord>,
+    settings: &'a Settings,
+}
+
+impl<'a> PortfolioWriter<'a> {
+    /// Writer for the trades portfolio `name`; appended trades without a
+    /// source are recorded with `source`, e.g. `bot:grid`
+    pub fn open(name: &str, source: &str, settings: &'a Settings) -> Result<Self> {
+        let source = source.trim();
+        if source.is_empty() {
+            bail!("source can't be empty, e.g. 'manual' or 'import:binance'");
+        }
+        let path = settings.path_for(name);
+        ensure_portfolio_file(&path)?;
+        Ok(PortfolioWriter {
+            name: name.to_string(),
+            path,
+            source: source.to_string(),
+            pending: Vec::new(),
+            settings,
+        })
+    }
+
+    /// Validate `trade` like a row read from a portfolio file and queue it
+    pub fn append(&mut self, mut trade: Trade) -> Result<()> {
+        if trade.source.as_deref().is_none_or(|s| s.trim().is_empty()) {
+            trade.source = Some(self.source.clone());
+        }
+        if trade.side.is_cash() {
+            trade.pair.asset(trade.side)?;
+        } else {
+            trade.pair.trading_pair(trade.side)?;
+        }
+        let record = trade.to_record();
+        let headers = csv::StringRecord::from_iter(CSV_HEADER.iter().chain([&SOURCE_COLUMN]));
+        record
+            .deserialize::<Trade>(Some(&headers))
+            .with_context(|| {
+                format!(
+                    "invalid trade {}",
+                    record.iter().collect::<Vec<_>>().join(",")
+                )
+            })?;
+        self.pending.push(record);
+        Ok(())
+    }
+
+    /// Trades appended since the last flush
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Write the pending trades to the portfolio, returning how many
+    pub fn flush(&mut self) -> Result<usize> {
+        if self.pending.is_empty() {
+            return Ok(0);
+        }
+        let _lock = lock(&self.name, self.settings)?;
+        // re-read under the lock, other processes may have written since `open`
+        let (config, mut records) = parse_csv_records(&self.path)?;
+        let written = self.pending.len();
+        records.append(&mut self.pending);
+        sort_records_chronologically(&mut records);
+        write_csv_records(&self.path, &config, &records)?;
+        index::record_write(&self.name, self.settings);
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::{create_test_csv, create_test_settings};
+    use crate::trade::{Side, parse_csv_file};
+    use rust_decimal::dec;
+    use tempfile::TempDir;
+    use time::macros::datetime;
+
+    fn buy(at: time::OffsetDateTime, amount: rust_decimal::Decimal) -> Trade {
+        Trade {
+            created_at: at,
+            pair: "BTC/USD".parse().unwrap(),
+            side: Side::Buy,
+            amount,
+            price: dec!(40000),
+            fee: dec!(0),
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_writer_validates_appends_and_flushes_them_in_order() {
+        let dir = TempDir::new().unwrap();
+        let settings = create_test_settings(dir.path().to_path_buf());
+        create_test_csv(
+            &dir,
+            "main",
+            "created_at,pair,side,amount,price,fee
+1704200000,BTC/USD,BUY,1,42000,0
+",
+        );
+
+        let mut writer = PortfolioWriter::open("main", "bot:grid", &settings).unwrap();
+        writer
+            .append(buy(datetime!(2024-01-03 00:00 UTC), dec!(0.5)))
+            .unwrap();
+        writer
+            .append(buy(datetime!(2024-01-01 00:00 UTC), dec!(0.25)))
+            .unwrap();
+        assert!(
+            writer
+                .append(buy(datetime!(2024-01-02 00:00 UTC), dec!(0)))
+                .is_err()
+        );
+        let mut deposit = buy(datetime!(2024-01-02 00:00 UTC), dec!(1));
+        deposit.side = Side::Deposit;
+        assert!(writer.append(deposit).is_err());
+        assert_eq!(writer.pending(), 2);
+
+        assert_eq!(writer.flush().unwrap(), 2);
+        assert_eq!(writer.flush().unwrap(), 0);
+        let (_, trades) = parse_csv_file(settings.path_for("main")).unwrap();
+        let amounts: Vec<_> = trades.iter().map(|t| t.amount).collect();
+        assert_eq!(amounts, [dec!(0.25), dec!(1), dec!(0.5)]);
+        assert_eq!(trades[0].source.as_deref(), Some("bot:grid"));
+        assert_eq!(trades[1].source, None);
+
+        assert!(PortfolioWriter::open("other", "bot:grid", &settings).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+//! XLSX workbooks
+//!
+//! Reads the cells of the first worksheet with calamine: strings, numbers,
+//! booleans and date formatted numbers, formulas as their cached value.
+//! Enough to import the trade exports some brokers only offer as
+//! spreadsheets, through the same importers as CSV exports.
+
+use anyhow::{Context, Result, anyhow};
+use calamine::{Data, Reader, Xlsx, open_workbook};
+use std::path::Path;
+use time::macros::format_description;
+use time::{Date, Month, PrimitiveDateTime, Time};
+
+/// Whether `path` is named like a workbook
+pub fn is_xlsx(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("xlsx"))
+}
+
+/// Value of a cell
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Text(String),
+    /// Number shown in a date or time format
+    Date(PrimitiveDateTime),
+}
+
+/// Rows of the first worksheet, without its empty rows
+pub fn read_rows(path: &Path) -> Result<Vec<Vec<Cell>>> {
+    read_workbook(path).with_context(|| format!("reading workbook {}", path.display()))
+}
+
+/// The first worksheet as CSV, for the importers of CSV exports
+///
+/// Dates are written as `YYYY-MM-DD HH:MM:SS`, or as epoch seconds in a
+/// `created_at` column, like portfolio files have them.
+pub fn to_csv(path: &Path) -> Result<String> {
+    let rows = read_rows(path)?;
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let is_created_at: Vec<bool> = (0..width)
+        .map(|i| {
+            matches!(rows.first().and_then(|header| header.get(i)),
+                Some(Cell::Text(h)) if h.trim() == "created_at")
+        })
+        .collect();
+
+    let mut out = csv::Writer::from_writer(Vec::new());
+    for row in &rows {
+        let fields = (0..width).map(|i| match row.get(i) {
+            None => String::new(),
+            Some(Cell::Text(text)) => text.clone(),
+            Some(Cell::Date(at)) if is_created_at[i] => {
+                at.assume_utc().unix_timestamp().to_string()
+            }
+            Some(Cell::Date(at)) => at
+                .format(format_description!(
+                    "[year]-[month]-[day] [hour]:[minute]:[second]"
+                ))
+                .unwrap_or_default(),
+        });
+        out.write_record(fields)?;
+    }
+    Ok(String::from_utf8(out.into_inner()?)?)
+}
+
+fn read_workbook(path: &Path) -> Result<Vec<Vec<Cell>>> {
+    let mut workbook: Xlsx<_> = open_workbook(path)?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or(anyhow!("the workbook has no worksheet"))??;
+    // the range starts at its first cell, columns before it are empty
+    let first_col = range.start().map_or(0, |(_, col)| col as usize);
+    let mut rows = Vec::new();
+    for cells in range.rows() {
+        let mut row = vec![Cell::Text(String::new()); first_col];
+        row.extend(cells.iter().map(cell));
+        while matches!(row.last(), Some(Cell::Text(t)) if t.is_empty()) {
+            row.pop();
+        }
+        let blank = row
+            .iter()
+            .all(|c| matches!(c, Cell::Text(t) if t.trim().is_empty()));
+        if !blank {
+            rows.push(row);
+        }
+    }
+    Ok(rows)
+}
+
+fn cell(data: &Data) -> Cell {
+    match data {
+        Data::DateTime(dt) if dt.is_datetime() => {
+            let (year, month, day, hour, min, sec, milli) = dt.to_ymd_hms_milli();
+            let at = Month::try_from(month)
+                .ok()
+                .and_then(|month| Date::from_calendar_date(year as i32, month, day).ok())
+                .zip(Time::from_hms_milli(hour, min, sec, milli).ok());
+            match at {
+                Some((date, time)) => Cell::Date(PrimitiveDateTime::new(date, time)),
+                None => Cell::Text(dt.as_f64().to_string()),
+            }
+        }
+        Data::DateTime(dt) => Cell::Text(dt.as_f64().to_string()),
+        Data::Bool(b) => Cell::Text(if *b { "TRUE" } else { "FALSE" }.into()),
+        Data::Empty => Cell::Text(String::new()),
+        other => Cell::Text(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::create_test_xlsx;
+    use tempfile::TempDir;
+    use time::macros::datetime;
+
+    const WORKBOOK: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><workbookPr/><sheets><sheet name="Trades" sheetId="1" r:id="rId3"/><sheet name="Notes" sheetId="2" r:id="rId1"/></sheets></workbook>"#;
+    const RELS: &str = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="worksheet" Target="worksheets/sheet1.xml"/><Relationship Id="rId3" Type="worksheet" Target="worksheets/sheet2.xml"/></Relationships>"#;
+    const STYLES: &str = r#"<styleSheet><numFmts count="1"><numFmt numFmtId="164" formatCode="[$-409]yyyy\-mm\-dd hh:mm"/><numFmt numFmtId="165" formatCode="&quot;USD&quot;\ #,##0.00"/></numFmts><cellXfs count="4"><xf numFmtId="0"/><xf numFmtId="14"/><xf numFmtId="164"/><xf numFmtId="165"/></cellXfs></styleSheet>"#;
+    const STRINGS: &str = r#"<sst count="4" uniqueCount="4"><si><t>pair</t></si><si><r><t>BTC/</t></r><r><rPr><b/></rPr><t>USD</t></r><rPh><t>x</t></rPh></si><si><t>BUY</t></si><si><t xml:space="preserve">Fish &amp; Chips </t></si></sst>"#;
+
+    #[test]
+    fn test_reads_the_first_sheet_with_shared_strings_and_dates() {
+        let dir = TempDir::new().unwrap();
+        let sheet = r#"<worksheet><sheetData>
+<row r="1"><c r="A1" t="inlineStr"><is><t>created_at</t></is></c><c r="B1" t="s"><v>0</v></c><c r="C1" t="inlineStr"><is><t>side</t></is></c><c r="D1" t="str"><f>"amount"</f><v>amount</v></c><c r="F1" t="inlineStr"><is><t>day</t></is></c></row>
+<row r="2"/>
+<row r="3"><c r="A3" s="2"><v>45301.5</v></c><c r="B3" t="s"><v>1</v></c><c r="C3" t="s"><v>2</v></c><c r="D3" s="3"><v>0.25</v></c><c r="E3" t="b"><v>1</v></c><c r="F3" s="1"><v>45301</v></c></row>
+<row r="4"><c r="C4" t="s"><v>3</v></c></row>
+</sheetData></worksheet>"#;
+        let path = create_test_xlsx(
+            &dir,
+            "kraken",
+            &[
+                ("xl/workbook.xml", WORKBOOK),
+                ("xl/_rels/workbook.xml.rels", RELS),
+                ("xl/styles.xml", STYLES),
+                ("xl/sharedStrings.xml", STRINGS),
+                ("xl/worksheets/sheet1.xml", "<worksheet/>"),
+                ("xl/worksheets/sheet2.xml", sheet),
+            ],
+        );
+
+        let rows = read_rows(&path).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1][0], Cell::Date(datetime!(2024-01-10 12:00)));
+        assert_eq!(rows[1][3], Cell::Text("0.25".into()));
+        assert_eq!(
+            to_csv(&path).unwrap(),
+            "created_at,pair,side,amount,,day\n\
+             1704888000,BTC/USD,BUY,0.25,TRUE,2024-01-10 00:00:00\n\
+             ,,Fish & Chips ,,,\n"
+        );
+    }
+}
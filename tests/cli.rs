@@ -3,17 +3,35 @@ mod common;
 #[path = "cli/add_tx_tests.rs"]
 mod add_tx_tests;
 
+#[path = "cli/alert_tests.rs"]
+mod alert_tests;
+
 #[path = "cli/help_tests.rs"]
 mod help_tests;
 
+#[path = "cli/import_tests.rs"]
+mod import_tests;
+
+#[path = "cli/info_tests.rs"]
+mod info_tests;
+
 #[path = "cli/list_tests.rs"]
 mod list_tests;
 
 #[path = "cli/new_tests.rs"]
 mod new_tests;
 
+#[path = "cli/position_tests.rs"]
+mod position_tests;
+
 #[path = "cli/report_tests.rs"]
 mod report_tests;
 
 #[path = "cli/show_tests.rs"]
 mod show_tests;
+
+#[path = "cli/tax_tests.rs"]
+mod tax_tests;
+
+#[path = "cli/workspace_tests.rs"]
+mod workspace_tests;
@@ -12,12 +12,12 @@ fn add_valid_tx_to_new_portfolio() {
     ctx.add_tx_buy_btc(name, "0.5", "96450", "37");
 
     let expected_stdout_header = "\
-+---------------------------------+---------+------+--------+-------+-----+
-| created_at                      | pair    | side | amount | price | fee |
-+---------------------------------+---------+------+--------+-------+-----+
++---------------------------------+---------+------+--------+-------+-----+--------+
+| created_at                      | pair    | side | amount | price | fee | source |
++---------------------------------+---------+------+--------+-------+-----+--------+
 ";
-    let expected_stdout_data = " | BTC/USD | Buy  | 0.5    | 96450 | 37  |
-+---------------------------------+---------+------+--------+-------+-----+";
+    let expected_stdout_data = " | BTC/USD | Buy  | 0.5    | 96450 | 37  | manual |
++---------------------------------+---------+------+--------+-------+-----+--------+";
 
     let p = ctx
         .cmd()
@@ -43,3 +43,471 @@ fn add_valid_tx_to_new_portfolio() {
 
     println!("DEBUG add_valid_tx_to_new_portfolio:\n\n{p:?}");
 }
+
+#[test]
+fn add_deposit_and_withdraw_rows() {
+    let ctx = TestContext::new();
+    let name = "savings";
+    ctx.create_portfolio(name);
+
+    for (side, qty) in [("DEPOSIT", "1000"), ("WITHDRAW", "250")] {
+        ctx.cmd()
+            .args([
+                "add-tx", "--name", name, "--ticker", "USD", "--side", side, "--qty", qty,
+                "--price", "1", "--fee", "0",
+            ])
+            .assert()
+            .success()
+            .stderr(predicate::str::is_empty());
+    }
+
+    ctx.cmd()
+        .args(["show", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("| USD  | Deposit  | 1000"))
+        .stdout(predicate::str::contains("| USD  | Withdraw | 250"));
+
+    // deposits move a single asset, not a pair
+    ctx.cmd()
+        .args([
+            "add-tx", "--name", name, "--ticker", "BTC/USD", "--side", "DEPOSIT", "--qty", "1",
+            "--price", "1", "--fee", "0",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("needs a single ticker"));
+}
+
+#[test]
+fn add_tx_rejects_pair_with_equal_base_and_quote() {
+    let ctx = TestContext::new();
+    let name = "basic";
+    ctx.create_portfolio(name);
+
+    ctx.cmd()
+        .args([
+            "add-tx", "--name", name, "--ticker", "btc/BTC", "--side", "BUY", "--qty", "1",
+            "--price", "1", "--fee", "1",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "base and quote can't be equal, got 'BTC/BTC'",
+        ));
+}
+
+#[test]
+fn add_tx_refuses_to_write_to_unrelated_csv() {
+    let ctx = TestContext::new();
+    let name = "expenses";
+    let content = "date,description,amount\n2024-01-01,coffee,3.5\n";
+    std::fs::write(ctx.portfolio_path(name), content).unwrap();
+
+    ctx.cmd()
+        .args([
+            "add-tx", "--name", name, "--ticker", "BTC/USD", "--side", "BUY", "--qty", "1",
+            "--price", "50000", "--fee", "0",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "doesn't look like a portfolio file, refusing to write to it",
+        ))
+        .stderr(predicate::str::contains("found 'date,description,amount'"));
+    assert_eq!(
+        std::fs::read_to_string(ctx.portfolio_path(name)).unwrap(),
+        content
+    );
+}
+
+#[test]
+fn add_tx_warns_when_selling_recently_bought_lots() {
+    let ctx = TestContext::new();
+    let name = "shortterm";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "1", "50000", "0");
+    ctx.cmd()
+        .env("LPT_SHORT_TERM_DAYS", "30")
+        .args([
+            "add-tx", "--name", name, "--ticker", "BTC/USD", "--side", "SELL", "--qty", "0.25",
+            "--price", "60000", "--fee", "0",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "⚠️  selling 0.25 BTC held for less than 30 days",
+        ));
+    // without the setting there is no warning
+    ctx.cmd()
+        .args([
+            "add-tx", "--name", name, "--ticker", "BTC/USD", "--side", "SELL", "--qty", "0.25",
+            "--price", "60000", "--fee", "0",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+
+    let prices = ctx.prices_file("p.csv", "ticker,price\nBTC,60000\n");
+    ctx.cmd()
+        .env("LPT_SHORT_TERM_DAYS", "30")
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .args(["report", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "⚠️  0.5 BTC held for less than 30 days, all long-term from",
+        ));
+}
+
+#[test]
+fn add_tx_fee_rows_reduce_balance_and_show_in_fees() {
+    let ctx = TestContext::new();
+    let name = "onchain";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "1", "40000", "10");
+    ctx.cmd()
+        .args([
+            "add-tx", "--name", name, "--ticker", "BTC", "--side", "FEE", "--qty", "0.0005",
+            "--price", "60000", "--fee", "0",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+
+    ctx.cmd()
+        .args(["fees", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| BTC   |            - |       0.0005 |             30 USD |",
+        ))
+        .stdout(predicate::str::contains(
+            "| USD   |           10 |            - |                  - |",
+        ))
+        .stdout(predicate::str::contains("Total fees:\t40 USD"));
+
+    let prices = ctx.prices_file("prices.csv", "ticker,price\nBTC,60000\n");
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .args(["report", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("| BTC    | 1.00    |"));
+}
+
+#[test]
+fn add_tx_income_rows_show_yield_in_income() {
+    let ctx = TestContext::new();
+    let name = "earn";
+    ctx.create_eur_portfolio(
+        name,
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704067200,USDC/USD,BUY,1000,1,0
+",
+    );
+    ctx.cmd()
+        .args([
+            "add-tx", "--name", name, "--ticker", "USDC", "--side", "INCOME", "--qty", "12.5",
+            "--price", "1", "--fee", "0",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+
+    ctx.cmd()
+        .args(["income", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| USDC  |   12.5 |     12.5 USD |",
+        ))
+        .stdout(predicate::str::contains("Total income:\t12.5 USD"));
+
+    ctx.cmd()
+        .args(["stats", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 fees, 1 income)"));
+}
+
+#[test]
+fn add_tx_and_validation_use_the_frozen_clock() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("frozen");
+    ctx.cmd()
+        .env("CSVPT_FAKE_NOW", "2024-03-15T12:00:00Z")
+        .args([
+            "add-tx", "--name", "frozen", "--ticker", "BTC/USD", "--side", "BUY", "--qty", "1",
+            "--price", "60000", "--fee", "0",
+        ])
+        .assert()
+        .success();
+    let csv = std::fs::read_to_string(ctx.portfolio_path("frozen")).unwrap();
+    assert!(csv.contains("\n1710504000,BTC/USD,BUY,1,60000,"), "{csv}");
+
+    // the row is in the future of a clock frozen a day earlier
+    ctx.cmd()
+        .env("CSVPT_FAKE_NOW", "1710417600")
+        .args(["show", "--name", "frozen"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("timestamp is in the future"));
+    ctx.cmd()
+        .env("CSVPT_FAKE_NOW", "tomorrow")
+        .args(["show", "--name", "frozen"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid CSVPT_FAKE_NOW: 'tomorrow'",
+        ));
+}
+
+#[test]
+fn add_tx_rejects_too_precise_or_too_large_values() {
+    let ctx = TestContext::new();
+    let name = "limits";
+    ctx.create_portfolio(name);
+    for (flag, value, msg) in [
+        (
+            "--qty",
+            "0.0000000000000000001",
+            "more than 18 decimal places",
+        ),
+        (
+            "--price",
+            "1000000000000000",
+            "is larger than 100000000000000",
+        ),
+        ("--fee", "1e3", "invalid number '1e3'"),
+    ] {
+        let mut args = vec![
+            "add-tx", "--name", name, "--ticker", "BTC/USD", "--side", "buy", "--qty", "1",
+            "--price", "100", "--fee", "0",
+        ];
+        let at = args.iter().position(|a| *a == flag).unwrap();
+        args[at + 1] = value;
+        ctx.cmd()
+            .args(&args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(msg));
+    }
+}
+
+#[test]
+fn add_tx_price_market_records_the_quote_and_its_provider() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("market");
+    let prices = ctx.prices_file("p.csv", "ticker,price\nBTC,61234.5\nETH,3000\n");
+    let provider = format!("file:{}", prices.display());
+    for args in [
+        ["--ticker", "BTC/USD", "--side", "BUY", "--qty", "0.5"],
+        ["--ticker", "ETH", "--side", "DEPOSIT", "--qty", "2"],
+    ] {
+        ctx.cmd()
+            .env("LPT_QUOTE_PROVIDER", &provider)
+            .args([
+                "add-tx", "--name", "market", "--price", "market", "--fee", "0",
+            ])
+            .args(args)
+            .assert()
+            .success();
+    }
+    let csv = std::fs::read_to_string(ctx.portfolio_path("market")).unwrap();
+    assert!(
+        csv.contains(&format!(
+            ",BTC/USD,BUY,0.5,61234.5,0,manual:market:{provider}\n"
+        )),
+        "{csv}"
+    );
+    assert!(
+        csv.contains(",ETH,DEPOSIT,2,3000,0,manual:market:"),
+        "{csv}"
+    );
+
+    ctx.cmd()
+        .args([
+            "add-tx", "--name", "market", "--ticker", "BTC/USD", "--side", "BUY", "--qty", "1",
+            "--price", "marked", "--fee", "0",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid number 'marked'"));
+}
+
+#[test]
+fn split_tx_replaces_a_trade_with_its_parts() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("lots");
+    ctx.add_tx_buy_btc("lots", "0.5", "40000", "10");
+    let (_, trades) = portfolio_tracker::trade::parse_csv_file(ctx.portfolio_path("lots")).unwrap();
+
+    ctx.cmd()
+        .args([
+            "split-tx",
+            "--name",
+            "lots",
+            "--id",
+            &trades[0].id(),
+            "--into",
+            "0.3,0.2",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Split trade"));
+    let csv = std::fs::read_to_string(ctx.portfolio_path("lots")).unwrap();
+    assert!(csv.contains(",BTC/USD,BUY,0.3,40000,6,manual\n"), "{csv}");
+    assert!(csv.contains(",BTC/USD,BUY,0.2,40000,4,manual\n"), "{csv}");
+
+    ctx.cmd()
+        .args([
+            "split-tx", "--name", "lots", "--id", "zzzz", "--into", "0.1",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no trade with id 'zzzz'"));
+}
+
+#[test]
+fn add_tx_takes_missing_options_from_a_config_template() {
+    let ctx = TestContext::new();
+    let name = "dca";
+    ctx.create_portfolio(name);
+    ctx.config_file(
+        r#"
+[templates]
+weekly_btc = "--name dca --ticker BTC/USD --side BUY --qty 0.005 --fee 0.3"
+broken = "--qty lots"
+"#,
+    );
+
+    ctx.cmd()
+        .args(["add-tx", "--template", "weekly_btc", "--price", "91000"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "ignoring template 'broken': invalid value 'lots'",
+        ));
+    ctx.cmd()
+        .args(["show", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| BTC/USD | Buy  | 0.005  | 91000 | 0.3 | manual |",
+        ));
+
+    ctx.cmd()
+        .args(["add-tx", "--template", "weekly_btc"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "add-tx is missing --price, pass it or use a --template that sets it",
+        ));
+    ctx.cmd()
+        .args(["add-tx", "--template", "broken", "--price", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "no template 'broken' in the config",
+        ));
+}
+
+#[test]
+fn add_tx_quotes_in_the_portfolio_base_and_blocks_other_fiat_without_mixed() {
+    let ctx = TestContext::new();
+    let name = "eur";
+    ctx.create_eur_portfolio(
+        name,
+        "# base_currency: EUR\ncreated_at,pair,side,amount,price,fee\n",
+    );
+    let add = |ticker: &str, side: &str, qty: &str, price: &str| {
+        ctx.cmd()
+            .args([
+                "add-tx", "--name", name, "--ticker", ticker, "--side", side, "--qty", qty,
+                "--price", price, "--fee", "0",
+            ])
+            .assert()
+    };
+
+    add("btc", "BUY", "0.01", "40000").success();
+    add("BTC/USD", "BUY", "0.01", "50000")
+        .failure()
+        .stderr(predicate::str::contains(
+            "BTC/USD is quoted in USD, but portfolio 'eur' is kept in EUR; use BTC/EUR, or pass --mixed to add it anyway",
+        ));
+    add("USD", "DEPOSIT", "1000", "0.9").success();
+    ctx.cmd()
+        .args([
+            "add-tx", "--name", name, "--ticker", "BTC/USD", "--side", "BUY", "--qty", "0.01",
+            "--price", "50000", "--fee", "0", "--mixed",
+        ])
+        .assert()
+        .success();
+    let csv = std::fs::read_to_string(ctx.portfolio_path(name)).unwrap();
+    assert!(csv.contains(",BTC/EUR,BUY,0.01,40000,0,manual\n"), "{csv}");
+    assert!(csv.contains(",BTC/USD,BUY,0.01,50000,0,manual\n"), "{csv}");
+
+    let prices = ctx.prices_file("prices.csv", "ticker,price\nBTC,45000\n");
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .args(["report", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Mixed currencies: trades quoted in USD besides the EUR base currency",
+        ));
+}
+
+#[test]
+fn doctor_lints_rows_and_add_tx_checks_new_ones_when_enabled() {
+    let ctx = TestContext::new();
+    let name = "lint";
+    ctx.create_eur_portfolio(
+        name,
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,0
+1704800000,ETH/USD,BUY,0,2500,0
+",
+    );
+
+    ctx.cmd()
+        .arg("doctor")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "⚠️  lint line 4: dated 2024-01-09 before the row above it (out_of_order)",
+        ))
+        .stdout(predicate::str::contains(
+            "❌ lint line 4: amount is 0 (zero_amount)",
+        ))
+        .stderr(predicate::str::contains(
+            "1 row(s) break lint rules set to error",
+        ));
+
+    ctx.config_file(
+        r#"
+[lint]
+zero_amount = "warn"
+high_fee = "error"
+on_add_tx = true
+"#,
+    );
+    ctx.cmd()
+        .arg("doctor")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Found 2 problem(s)"));
+    ctx.cmd()
+        .args([
+            "add-tx", "--name", name, "--ticker", "BTC/USD", "--side", "BUY", "--qty", "0.001",
+            "--price", "40000", "--fee", "10",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "row not added, it breaks lint rule high_fee: fee 10 USD is 25.00% of the 40 USD traded (set lint.high_fee to warn to allow it)",
+        ));
+}
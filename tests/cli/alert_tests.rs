@@ -0,0 +1,195 @@
+use crate::common::fixtures::TestContext;
+use predicates::prelude::*;
+
+#[test]
+fn alert_add_list_remove() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("alpha");
+
+    ctx.cmd()
+        .args(["alert", "move", "--name", "alpha", "--pct", "5"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Added alert #1: alpha moves > 5% in a day",
+        ));
+    ctx.cmd()
+        .args(["alert", "price", "btc", "--above", "100000"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Added alert #2: BTC >= 100000"));
+
+    ctx.cmd()
+        .args(["alert", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("alpha moves > 5% in a day"))
+        .stdout(predicate::str::contains("BTC >= 100000"));
+
+    ctx.cmd()
+        .args(["alert", "remove", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed alert #1"));
+    ctx.cmd()
+        .args(["alert", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("alpha moves").not());
+}
+
+#[test]
+fn alert_move_rejects_unknown_portfolio() {
+    let ctx = TestContext::new();
+
+    ctx.cmd()
+        .args(["alert", "move", "--name", "missing"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "portfolio 'missing' doesn't exist",
+        ));
+}
+
+#[test]
+fn alert_price_requires_a_level() {
+    let ctx = TestContext::new();
+
+    ctx.cmd()
+        .args(["alert", "price", "BTC"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--above"));
+}
+
+#[test]
+fn alert_position_on_value_and_weight() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("alpha");
+
+    ctx.cmd()
+        .args([
+            "alert",
+            "position",
+            "--name",
+            "alpha",
+            "eth",
+            "--above-pct",
+            "40",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Added alert #1: ETH in alpha >= 40% of the portfolio",
+        ));
+    ctx.cmd()
+        .args([
+            "alert", "position", "--name", "alpha", "stables", "--below", "5000",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Added alert #2: STABLES in alpha <= 5000",
+        ));
+    ctx.cmd()
+        .args([
+            "alert",
+            "position",
+            "--name",
+            "alpha",
+            "ETH",
+            "--above-pct",
+            "140",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "position weight must be between 0 and 100 percent",
+        ));
+    ctx.cmd()
+        .args([
+            "alert", "position", "--name", "alpha", "ETH", "--above", "1", "--below", "2",
+        ])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn watch_notifies_a_firing_alert_once_per_cooldown_and_not_while_snoozed() {
+    let ctx = TestContext::new();
+    let prices = ctx.prices_file("p.csv", "ticker,price\nBTC,130000\n");
+    let watch_at = |now: &str| {
+        let mut cmd = ctx.cmd();
+        cmd.args(["--fixture-prices", prices.to_str().unwrap(), "--now", now])
+            .args(["watch", "--once"]);
+        cmd
+    };
+
+    ctx.cmd()
+        .args([
+            "alert",
+            "price",
+            "BTC",
+            "--above",
+            "120000",
+            "--cooldown",
+            "6h",
+        ])
+        .assert()
+        .success();
+    watch_at("2025-03-01T12:00:00Z")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "🔔 alert #1: BTC is at 130000, above 120000",
+        ));
+    watch_at("2025-03-01T17:00:00Z")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("🔔").not());
+    watch_at("2025-03-01T18:00:00Z")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("🔔 alert #1"));
+
+    ctx.cmd()
+        .args([
+            "--now",
+            "2025-03-01T18:30:00Z",
+            "alert",
+            "snooze",
+            "1",
+            "24h",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Snoozed alert #1 until 2025-03-02 18:30 UTC",
+        ));
+    ctx.cmd()
+        .args(["--now", "2025-03-01T18:30:00Z", "alert", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| 1  | BTC >= 120000 | 6h       | 2025-03-02 18:30 UTC |",
+        ));
+    watch_at("2025-03-02T12:00:00Z")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("🔔").not());
+    watch_at("2025-03-02T18:30:00Z")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("🔔 alert #1"));
+
+    ctx.cmd()
+        .args(["alert", "snooze", "2", "1h"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no alert with id 2"));
+    ctx.cmd()
+        .args(["alert", "snooze", "1", "tomorrow"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid duration 'tomorrow'"));
+}
@@ -12,17 +12,79 @@ A command-line tool to manage CSV-based investment portfolios, calculate PnL, an
 Usage: portfolio-tracker [OPTIONS] <COMMAND>
 
 Commands:
-  list    List all portfolios [aliases: l, ls]
-  new     Create new portfolio
-  show    Show all transactions from portfolio
-  report  Report portfolio PnL
-  add-tx  Add transaction to portfolio
-  help    Print this message or the help of the given subcommand(s)
+  list              List all portfolios [aliases: l, ls]
+  new               Create new portfolio
+  show              Show all transactions from portfolio
+  stats             Summarize portfolio rows per side and per source
+  fees              Trading and network fees paid per asset
+  income            Income received per asset, with the yield it made on the average balance
+  report            Report portfolio PnL
+  tax               Realized gains of sold crypto, converted at the FX rates of the trade dates
+  statement         Monthly statement: values at both ends, deposits, realized gains, income and fees, e.g. from cron on the 1st
+  scenario          Recompute portfolio value and PnL under hypothetical price moves
+  scenarios         Manage named scenarios saved in the config file
+  dca               Scheduled dollar-cost averaging buys of the plans in the config file
+  position          Show a position of a portfolio, computed from its trades only, or manage its stop-loss and take-profit levels
+  add-tx            Add transaction to portfolio
+  hold              Set a holding of a holdings-only portfolio, quantity 0 removes it
+  dust              List positions worth less than a threshold and suggest consolidating them
+  export            Export trades and computed positions for analytics tools, e.g. DuckDB or Polars
+  export-workspace  Export all portfolios, state and config into a single archive
+  import            Import every CSV or XLSX export of a directory, or rows from the clipboard, into a portfolio
+  import-workspace  Restore a workspace archive created by export-workspace
+  doctor            Check the workspace for problems (e.g. sync conflict copies)
+  fmt               Rewrite trades portfolios in canonical form, keeping row order and metadata
+  config            Check the config file
+  info              Print version and environment details for bug reports
+  tickers           Search the CoinGecko registry by symbol, name or id, e.g. for the ticker `add-tx` expects
+  ticker-info       Show ticker type, supply and market cap
+  price             Manual prices for assets the quote providers don't quote
+  alert             Manage price and portfolio alerts, evaluated by `watch`
+  tag               Tag a trade, e.g. with its strategy, for `stats --by-tag`
+  split-tx          Replace a trade with smaller ones of the same price, sharing its fee in proportion, e.g. when it stands for several tax lots
+  journal           Notes on trades, e.g. why they were made
+  freeze            Write an immutable, checksummed statement of a portfolio at the end of a day
+  close             Mark a finished portfolio as closed: it's still reported, but no longer written to
+  history           Chart the daily values of a portfolio from its snapshots
+  snapshot          Record the current value of portfolios, used by alerts and history, or downsample old snapshots
+  watch             Periodically value portfolios, take snapshots and fire alerts
+  serve             Serve current quotes to other local tools at http://127.0.0.1:PORT/prices?tickers=BTC,ETH
+  merge-conflict    Merge sync conflict copies (Syncthing, Dropbox) back into the portfolio
+  help              Print this message or the help of the given subcommand(s)
 
 Options:
   -p, --portfolio-dir <PORTFOLIO_DIR>
           
 
+      --no-wizard
+          Don't offer the first-run setup wizard when no config file exists
+
+      --no-pager
+          Don't pipe output longer than the terminal through $PAGER
+
+      --quiet
+          Only print results, not where the config was loaded from or what a command changed
+
+      --output <OUTPUT>
+          Where results go
+
+          Possible values:
+          - table:  Rows in the command's --format, with result and status lines
+          - json:   Rows as JSON only, every other line goes to stderr
+          - csv:    Rows as CSV only, every other line goes to stderr
+          - silent: Nothing, only the exit code tells how it went
+          
+          [default: table]
+
+      --timeout <SECS>
+          Give up on network requests once they took this many seconds in total, so a hung provider can't stall a cron job
+
+      --fixture-prices <FILE>
+          Take all prices from this `ticker,price[,date]` CSV and make no network requests, for reproducible offline runs
+
+      --now <TIME>
+          Run as if it were this time, RFC 3339 or unix seconds, like `CSVPT_FAKE_NOW`
+
   -h, --help
           Print help (see a summary with '-h')
 ";
@@ -45,15 +107,55 @@ CSV Portfolio Tracker
 Usage: portfolio-tracker [OPTIONS] <COMMAND>
 
 Commands:
-  list    List all portfolios [aliases: l, ls]
-  new     Create new portfolio
-  show    Show all transactions from portfolio
-  report  Report portfolio PnL
-  add-tx  Add transaction to portfolio
-  help    Print this message or the help of the given subcommand(s)
+  list              List all portfolios [aliases: l, ls]
+  new               Create new portfolio
+  show              Show all transactions from portfolio
+  stats             Summarize portfolio rows per side and per source
+  fees              Trading and network fees paid per asset
+  income            Income received per asset, with the yield it made on the average balance
+  report            Report portfolio PnL
+  tax               Realized gains of sold crypto, converted at the FX rates of the trade dates
+  statement         Monthly statement: values at both ends, deposits, realized gains, income and fees, e.g. from cron on the 1st
+  scenario          Recompute portfolio value and PnL under hypothetical price moves
+  scenarios         Manage named scenarios saved in the config file
+  dca               Scheduled dollar-cost averaging buys of the plans in the config file
+  position          Show a position of a portfolio, computed from its trades only, or manage its stop-loss and take-profit levels
+  add-tx            Add transaction to portfolio
+  hold              Set a holding of a holdings-only portfolio, quantity 0 removes it
+  dust              List positions worth less than a threshold and suggest consolidating them
+  export            Export trades and computed positions for analytics tools, e.g. DuckDB or Polars
+  export-workspace  Export all portfolios, state and config into a single archive
+  import            Import every CSV or XLSX export of a directory, or rows from the clipboard, into a portfolio
+  import-workspace  Restore a workspace archive created by export-workspace
+  doctor            Check the workspace for problems (e.g. sync conflict copies)
+  fmt               Rewrite trades portfolios in canonical form, keeping row order and metadata
+  config            Check the config file
+  info              Print version and environment details for bug reports
+  tickers           Search the CoinGecko registry by symbol, name or id, e.g. for the ticker `add-tx` expects
+  ticker-info       Show ticker type, supply and market cap
+  price             Manual prices for assets the quote providers don't quote
+  alert             Manage price and portfolio alerts, evaluated by `watch`
+  tag               Tag a trade, e.g. with its strategy, for `stats --by-tag`
+  split-tx          Replace a trade with smaller ones of the same price, sharing its fee in proportion, e.g. when it stands for several tax lots
+  journal           Notes on trades, e.g. why they were made
+  freeze            Write an immutable, checksummed statement of a portfolio at the end of a day
+  close             Mark a finished portfolio as closed: it's still reported, but no longer written to
+  history           Chart the daily values of a portfolio from its snapshots
+  snapshot          Record the current value of portfolios, used by alerts and history, or downsample old snapshots
+  watch             Periodically value portfolios, take snapshots and fire alerts
+  serve             Serve current quotes to other local tools at http://127.0.0.1:PORT/prices?tickers=BTC,ETH
+  merge-conflict    Merge sync conflict copies (Syncthing, Dropbox) back into the portfolio
+  help              Print this message or the help of the given subcommand(s)
 
 Options:
   -p, --portfolio-dir <PORTFOLIO_DIR>  
+      --no-wizard                      Don't offer the first-run setup wizard when no config file exists
+      --no-pager                       Don't pipe output longer than the terminal through $PAGER
+      --quiet                          Only print results, not where the config was loaded from or what a command changed
+      --output <OUTPUT>                Where results go [default: table] [possible values: table, json, csv, silent]
+      --timeout <SECS>                 Give up on network requests once they took this many seconds in total, so a hung provider can't stall a cron job
+      --fixture-prices <FILE>          Take all prices from this `ticker,price[,date]` CSV and make no network requests, for reproducible offline runs
+      --now <TIME>                     Run as if it were this time, RFC 3339 or unix seconds, like `CSVPT_FAKE_NOW`
   -h, --help                           Print help (see more with '--help')
 ";
 
@@ -73,9 +175,15 @@ Create new portfolio
 Usage: portfolio-tracker new [OPTIONS] --name <NAME>
 
 Options:
-  -n, --name <NAME>          
-      --currency <CURRENCY>  
-  -h, --help                 Print help
+  -n, --name <NAME>            
+      --currency <CURRENCY>    
+      --holdings               Store only current holdings (ticker, qty, cost) instead of trades
+      --quiet                  Only print results, not where the config was loaded from or what a command changed
+      --output <OUTPUT>        Where results go [default: table] [possible values: table, json, csv, silent]
+      --timeout <SECS>         Give up on network requests once they took this many seconds in total, so a hung provider can't stall a cron job
+      --fixture-prices <FILE>  Take all prices from this `ticker,price[,date]` CSV and make no network requests, for reproducible offline runs
+      --now <TIME>             Run as if it were this time, RFC 3339 or unix seconds, like `CSVPT_FAKE_NOW`
+  -h, --help                   Print help (see more with '--help')
 ";
     let mut cmd = cargo_bin_cmd!("portfolio-tracker");
     cmd.args(["new", "-h"])
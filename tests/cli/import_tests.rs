@@ -0,0 +1,247 @@
+use crate::common::fixtures::TestContext;
+use predicates::prelude::*;
+
+#[test]
+fn import_dir_reports_each_file() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("main");
+    let exports = ctx.portfolio_path("main").with_file_name("exports");
+    std::fs::create_dir(&exports).unwrap();
+    std::fs::write(
+        exports.join("kraken.csv"),
+        "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,7.5
+1704969600,BTC/USD,BUY,-1,40000,7.5
+",
+    )
+    .unwrap();
+    std::fs::write(
+        exports.join("binance.csv"),
+        "created_at,pair,side,amount,price,fee
+1705056000,ETH/USD,BUY,2,2500,1
+",
+    )
+    .unwrap();
+
+    ctx.cmd()
+        .args(["import", "--name", "main", "--dir"])
+        .arg(&exports)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| binance.csv |        1 |       0 |      0 |",
+        ))
+        .stdout(predicate::str::contains(
+            "| kraken.csv  |        1 |       0 |      1 |",
+        ))
+        .stdout(predicate::str::contains("❌ kraken.csv: line 3: "))
+        .stdout(predicate::str::contains(
+            "✅ Imported 2 trades from 2 files",
+        ));
+
+    ctx.cmd()
+        .args(["show", "--name", "main", "--source", "import:kraken"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| BTC/USD | Buy  | 1      | 40000 | 7.5 | import:kraken |",
+        ));
+}
+
+#[test]
+fn import_above_confirm_threshold_needs_yes() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("main");
+    let exports = ctx.portfolio_path("main").with_file_name("exports");
+    std::fs::create_dir(&exports).unwrap();
+    std::fs::write(
+        exports.join("kraken.csv"),
+        "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,7.5
+1704969600,BTC/USD,BUY,1,41000,7.5
+",
+    )
+    .unwrap();
+
+    ctx.cmd()
+        .args(["import", "--name", "main", "--dir"])
+        .arg(&exports)
+        .env("LPT_CONFIRM_THRESHOLD", "1")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "more than confirm_threshold (1), pass --yes to go ahead",
+        ));
+    ctx.cmd()
+        .args(["show", "--name", "main"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No trades found"));
+
+    ctx.cmd()
+        .args(["import", "--yes", "--name", "main", "--dir"])
+        .arg(&exports)
+        .env("LPT_CONFIRM_THRESHOLD", "1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "✅ Imported 2 trades from 1 files",
+        ));
+}
+
+#[test]
+fn import_koinly_and_cointracking_universal_files() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("main");
+    let exports = ctx.portfolio_path("main").with_file_name("exports");
+    std::fs::create_dir(&exports).unwrap();
+    std::fs::write(
+        exports.join("koinly.csv"),
+        "Date,Sent Amount,Sent Currency,Received Amount,Received Currency,Fee Amount,Fee Currency,Net Worth Amount,Net Worth Currency,Label,Description,TxHash
+2024-01-09 09:00 UTC,,,50000,USD,,,,,,,
+2024-01-10 10:40 UTC,20000,USD,0.5,BTC,10,USD,20000,USD,,,
+2024-02-01 00:00 UTC,,,0.001,BTC,,,45,USD,staking,,
+2024-02-02 00:00 UTC,,,1,NOPE,,,,,,,
+",
+    )
+    .unwrap();
+    std::fs::write(
+        exports.join("cointracking.csv"),
+        r#""Type","Buy","Cur.","Sell","Cur.","Fee","Cur.","Exchange","Group","Comment","Date"
+"Trade","2000","USD","0.05","BTC","","","Kraken","","","15.03.2024 12:00"
+"Other Fee","","","25","USD","","","Kraken","","","16.03.2024 08:00"
+"#,
+    )
+    .unwrap();
+
+    ctx.cmd()
+        .args(["import", "--name", "main", "--dir"])
+        .arg(&exports)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| cointracking.csv |        2 |       0 |      0 |",
+        ))
+        .stdout(predicate::str::contains(
+            "| koinly.csv       |        3 |       0 |      1 |",
+        ))
+        .stdout(predicate::str::contains("❌ koinly.csv: line 5: "));
+
+    ctx.cmd()
+        .args(["show", "--name", "main", "--source", "import"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("| BTC/USD | Buy "))
+        .stdout(predicate::str::contains("| BTC/USD | Sell "))
+        .stdout(predicate::str::contains("| BTC     | Income "))
+        .stdout(predicate::str::contains("| USD     | Fee "));
+}
+
+#[test]
+fn import_needs_a_dir_or_the_clipboard() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("main");
+
+    ctx.cmd()
+        .args(["import", "--name", "main"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--dir <DIR>"));
+    ctx.cmd()
+        .args(["import", "--name", "main", "--clipboard", "--dir", "."])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn import_create_suggests_the_base_most_trades_are_quoted_in() {
+    let ctx = TestContext::new();
+    let exports = ctx.portfolio_path("main").with_file_name("exports");
+    std::fs::create_dir(&exports).unwrap();
+    std::fs::write(
+        exports.join("bitvavo.csv"),
+        "created_at,pair,side,amount,price,fee
+1704883200,BTC/EUR,BUY,1,37000,5
+1705056000,ETH/EUR,BUY,2,2300,1
+1705142400,ETH/USD,BUY,1,2500,1
+",
+    )
+    .unwrap();
+
+    ctx.cmd()
+        .args(["import", "--name", "main", "--create", "--dir"])
+        .arg(&exports)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "2 of 3 imported trades are quoted in EUR. Create 'main' with base currency EUR instead of USD? Pass --yes to accept",
+        ));
+    assert!(!ctx.portfolio_path("main").exists());
+
+    ctx.cmd()
+        .args(["import", "--name", "main", "--create", "--yes", "--dir"])
+        .arg(&exports)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Created trades file"))
+        .stdout(predicate::str::contains("✅ Imported 3 trades"));
+    let content = std::fs::read_to_string(ctx.portfolio_path("main")).unwrap();
+    assert!(content.contains("# base_currency: EUR"));
+}
+
+#[test]
+fn import_wizard_saves_a_profile_used_by_later_imports() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("main");
+    let exports = ctx.portfolio_path("main").with_file_name("exports");
+    std::fs::create_dir(&exports).unwrap();
+    let header = "Time,Market,Type,Filled,Avg. Price,Fee\n";
+    let sample = exports.join("bitpanda-2024-01.csv");
+    std::fs::write(
+        &sample,
+        format!("{}2024-01-10T10:40:00,BTCUSD,buy,0.5,40000,10\n", header),
+    )
+    .unwrap();
+
+    ctx.cmd()
+        .args(["import", "--name", "main", "--wizard"])
+        .arg(&sample)
+        .write_stdin("\n\n\n\n\n\ny\n\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  4. Filled: 0.5\n"))
+        .stdout(predicate::str::contains(
+            "  1704883200,BTC/USD,BUY,0.5,40000,10\n",
+        ))
+        .stdout(predicate::str::contains(
+            "✅ Saved import profile 'bitpanda'",
+        ))
+        .stdout(predicate::str::contains("✅ Imported 1 trades from"));
+
+    std::fs::remove_file(&sample).unwrap();
+    std::fs::write(
+        exports.join("bitpanda-2024-02.csv"),
+        format!("{}1707561600,ETHUSD,SELL,-1,2500,\n", header),
+    )
+    .unwrap();
+    ctx.cmd()
+        .args(["import", "--name", "main", "--dir"])
+        .arg(&exports)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "✅ Imported 1 trades from 1 files",
+        ));
+    let content = std::fs::read_to_string(ctx.portfolio_path("main")).unwrap();
+    assert!(content.contains("1704883200,BTC/USD,BUY,0.5,40000,10,import:bitpanda\n"));
+    assert!(content.contains("1707561600,ETH/USD,SELL,1,2500,0,import:bitpanda\n"));
+
+    ctx.cmd()
+        .args(["import", "--name", "main", "--wizard"])
+        .arg(exports.join("bitpanda-2024-02.csv"))
+        .write_stdin("\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("wizard aborted (end of input)"));
+}
@@ -0,0 +1,182 @@
+use crate::common::fixtures::TestContext;
+use predicates::prelude::*;
+
+#[test]
+fn info_prints_version_and_environment() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("alpha");
+
+    ctx.cmd()
+        .args(["info", "--offline"])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(concat!(
+            "portfolio-tracker ",
+            env!("CARGO_PKG_VERSION")
+        )))
+        .stdout(predicate::str::contains("config file:"))
+        .stdout(predicate::str::contains("(missing)"))
+        .stdout(predicate::str::contains("env LPT_PORTFOLIO_DIR"))
+        .stdout(predicate::str::contains("1 indexed portfolio(s)"))
+        .stdout(predicate::str::contains("connectivity:   skipped"));
+}
+
+#[test]
+fn ticker_info_shows_cached_supply() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("alpha");
+    // supply of today already cached, so nothing is fetched
+    let state_dir = ctx.portfolio_path("alpha").parent().unwrap().join(".csvpt");
+    std::fs::create_dir_all(&state_dir).unwrap();
+    let today = time::OffsetDateTime::now_utc().date();
+    std::fs::write(
+        state_dir.join("supply.json"),
+        format!(
+            r#"{{"date":"{}","currency":"USD","assets":{{"BTC":
+                {{"market_cap":"1900000000000","circulating":"19950000","max":"21000000"}}}}}}"#,
+            today
+        ),
+    )
+    .unwrap();
+
+    ctx.cmd()
+        .args(["ticker-info", "btc", "usdc"])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("BTC:\tCrypto"))
+        .stdout(predicate::str::contains("USDC:\tStableCoin"))
+        .stdout(predicate::str::contains(
+            "| BTC    | 1,900,000,000,000 USD |  19,950,000 | 21,000,000 |        95.00% |",
+        ));
+}
+
+#[test]
+fn config_validate_points_at_unknown_keys_and_commands_refuse_them() {
+    let ctx = TestContext::new();
+    ctx.config_file("base_currency = \"EUR\"\nportfolio_dri = \"~/portfolios\"\n");
+
+    ctx.cmd()
+        .args(["config", "validate"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "config.toml:2:1: unknown key `portfolio_dri`, did you mean `portfolio_dir`?",
+        ))
+        .stderr(predicate::str::contains("1 problems in"));
+    ctx.cmd()
+        .args(["list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid config file:"))
+        .stderr(predicate::str::contains("did you mean `portfolio_dir`?"));
+
+    ctx.config_file("base_currency = \"EUR\"\nfiscal_year_start_month = 13\n");
+    ctx.cmd()
+        .args(["config", "validate"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "fiscal_year_start_month must be 1-12",
+        ))
+        .stdout(predicate::str::contains("config.toml is valid"));
+}
+
+#[test]
+fn config_show_origins_names_the_layer_of_each_setting() {
+    let ctx = TestContext::new();
+    ctx.config_file("base_currency = \"EUR\"\nhttp_timeout_secs = 5\n");
+    let prices = ctx.prices_file("prices.csv", "ticker,price\nBTC,1\n");
+
+    ctx.cmd()
+        .env("LPT_HTTP_TIMEOUT_SECS", "20")
+        .args(["config", "show", "--origins", "--fixture-prices"])
+        .arg(&prices)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(r#"(?m)^base_currency = "EUR" +# dotfile .*config\.toml$"#)
+                .unwrap(),
+        )
+        .stdout(
+            predicate::str::is_match(r"(?m)^http_timeout_secs = 20 +# env LPT_HTTP_TIMEOUT_SECS$")
+                .unwrap(),
+        )
+        .stdout(
+            predicate::str::is_match(r"(?m)^portfolio_dir = .* +# env LPT_PORTFOLIO_DIR$").unwrap(),
+        )
+        .stdout(
+            predicate::str::is_match(
+                r"(?m)^quote_provider = .*prices\.csv\x22 +# cli --fixture-prices$",
+            )
+            .unwrap(),
+        )
+        .stdout(predicate::str::is_match(r"(?m)^cost_basis = .average. +# default$").unwrap())
+        .stdout(predicate::str::contains("# inbox_dir is not set"));
+
+    ctx.cmd()
+        .args(["config", "show"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("http_timeout_secs = 5\n"))
+        .stdout(predicate::str::contains("  # ").not());
+}
+
+#[test]
+fn info_stats_lists_command_runs_once_enabled() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("main");
+    ctx.cmd()
+        .args(["info", "--stats"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Usage stats are off"))
+        .stdout(predicate::str::contains("No usage recorded"));
+
+    for args in [&["list"][..], &["list"], &["show", "--name", "missing"]] {
+        ctx.cmd().env("LPT_USAGE_STATS", "true").args(args).assert();
+    }
+    ctx.cmd()
+        .env("LPT_USAGE_STATS", "true")
+        .args(["info", "--stats"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Usage stats are off").not())
+        .stdout(predicate::str::is_match(r"\| list +\| +2 \| +0 \|").unwrap())
+        .stdout(predicate::str::is_match(r"\| show +\| +1 \| +1 \|").unwrap())
+        .stdout(predicate::str::contains("never sent anywhere"));
+}
+
+#[test]
+fn info_workspace_counts_portfolios_and_trades_and_sizes_storage() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "main",
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704067200,BTC/USD,BUY,1,40000,0
+1704883200,ETH/USD,BUY,1,2000,0
+",
+    );
+    ctx.create_portfolio("empty");
+    // fills the position cache
+    ctx.cmd()
+        .args(["report", "--name", "empty"])
+        .assert()
+        .success();
+
+    ctx.cmd()
+        .args(["info", "--workspace"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "portfolios:     2 (2 with trades, 0 holdings-only)\n",
+        ))
+        .stdout(predicate::str::contains("trades:         2\n"))
+        .stdout(predicate::str::contains(
+            "activity:       2024-01-01 to 2024-01-10\n",
+        ))
+        .stdout(predicate::str::is_match(r"\| portfolios +\| +2 \| +\d+ B \|").unwrap())
+        .stdout(predicate::str::is_match(r"\| cache +\| +[1-9]\d* \|").unwrap());
+}
@@ -6,9 +6,9 @@ fn list_on_empty_workspace_shows_no_portfolios() {
     let ctx = TestContext::new();
 
     let expected_stdout = "\
-+---------------+------------+
-| CSV file name | Created at |
-+---------------+------------+
++---------------+------------+--------+
+| CSV file name | Created at | Trades |
++---------------+------------+--------+
 ";
 
     ctx.cmd()
@@ -18,3 +18,327 @@ fn list_on_empty_workspace_shows_no_portfolios() {
         .code(0)
         .stdout(predicate::str::diff(expected_stdout));
 }
+
+#[test]
+fn list_warns_about_sync_conflict_copies() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("alpha");
+    std::fs::copy(
+        ctx.portfolio_path("alpha"),
+        ctx.portfolio_path("alpha")
+            .with_file_name("alpha.sync-conflict-20240101-120000-ABCDEFG.csv"),
+    )
+    .unwrap();
+
+    ctx.cmd()
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("sync conflict copy of 'alpha'"));
+
+    ctx.cmd()
+        .args(["merge-conflict", "--name", "alpha"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Merged 1 conflict copies"));
+
+    ctx.cmd()
+        .arg("doctor")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No problems found"));
+}
+
+#[test]
+fn list_filters_by_name_pattern_and_base_currency() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("kraken_usd");
+    ctx.create_portfolio("binance_usd");
+    ctx.cmd()
+        .args(["new", "--name", "kraken_eur", "--currency", "EUR"])
+        .assert()
+        .success();
+
+    ctx.cmd()
+        .args(["list", "--match", "kraken*"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("kraken_usd")
+                .and(predicate::str::contains("kraken_eur"))
+                .and(predicate::str::contains("binance_usd").not()),
+        );
+
+    ctx.cmd()
+        .args(["list", "--match", "kraken*", "--base-currency", "eur"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("kraken_eur")
+                .and(predicate::str::contains("kraken_usd").not()),
+        );
+}
+
+#[test]
+fn list_and_report_all_skip_ignored_portfolios() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("main");
+    ctx.add_tx_buy_btc("main", "1", "50000", "0");
+    ctx.create_portfolio("scratch_dca");
+    ctx.add_tx_buy_btc("scratch_dca", "2", "50000", "0");
+    ctx.create_portfolio("import_test");
+    let patterns = "scratch*,*_test";
+
+    ctx.cmd()
+        .env("LPT_IGNORE_PATTERNS", patterns)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("| main "))
+        .stdout(predicate::str::contains("scratch_dca").not())
+        .stdout(predicate::str::contains("import_test").not());
+    ctx.cmd()
+        .env("LPT_IGNORE_PATTERNS", patterns)
+        .args(["list", "--include-ignored"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("| scratch_dca "))
+        .stdout(predicate::str::contains("| import_test "));
+
+    let prices = ctx.prices_file("p.csv", "ticker,price\nBTC,100000\n");
+    let report_all = |args: &[&str]| {
+        ctx.cmd()
+            .env("LPT_IGNORE_PATTERNS", patterns)
+            .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+            .args(["report", "--all"])
+            .args(args)
+            .assert()
+            .success()
+    };
+    report_all(&[])
+        .stdout(predicate::str::contains("scratch_dca").not())
+        .stdout(predicate::str::contains("Total:\t\t100_000 USD"));
+    report_all(&["--include-ignored"]).stdout(predicate::str::contains("Total:\t\t300_000 USD"));
+}
+
+#[test]
+fn list_and_report_all_aggregate_portfolio_dirs() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("personal");
+    ctx.add_tx_buy_btc("personal", "1", "50000", "10");
+    let business = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        business.path().join("company.csv"),
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,0.5,40000,0
+",
+    )
+    .unwrap();
+    let dirs = business.path().display().to_string();
+
+    ctx.cmd()
+        .env("LPT_PORTFOLIO_DIRS", &dirs)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("| Trades | Directory"))
+        .stdout(predicate::str::is_match(format!(r"\| company .*\| 1 +\| {} +\|", dirs)).unwrap())
+        .stdout(predicate::str::contains("| personal "));
+
+    let prices = ctx.prices_file("p.csv", "ticker,price\nBTC,100000\n");
+    ctx.cmd()
+        .env("LPT_PORTFOLIO_DIRS", &dirs)
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .args(["report", "--all"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "| company   | trades |  50,000 USD | 30,000 USD | {} |",
+            dirs
+        )))
+        .stdout(predicate::str::contains("Total:\t\t150_000 USD"));
+
+    // without portfolio_dirs only the portfolio dir is listed
+    ctx.cmd()
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("company").not())
+        .stdout(predicate::str::contains("Directory").not());
+}
+
+#[test]
+fn list_and_doctor_warn_about_portfolios_duplicated_across_dirs() {
+    let ctx = TestContext::new();
+    let head = "# base_currency: USD\ncreated_at,pair,side,amount,price,fee\n";
+    let buy = "1704883200,BTC/USD,BUY,0.5,40000,0\n";
+    let sell = "1710460800,BTC/USD,SELL,0.1,60000,0\n";
+    std::fs::write(ctx.portfolio_path("main"), format!("{head}{buy}")).unwrap();
+    let synced = tempfile::TempDir::new().unwrap();
+    let copy = synced.path().join("main.csv");
+    std::fs::write(&copy, format!("{head}{buy}{sell}")).unwrap();
+    std::fs::write(synced.path().join("old.csv"), format!("{head}{buy}")).unwrap();
+    let dirs = synced.path().display().to_string();
+
+    ctx.cmd()
+        .env("LPT_PORTFOLIO_DIRS", &dirs)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "portfolio 'main' exists in several data dirs: {}, {} (rename one, or run `merge-conflict --name main --from {}`)",
+            ctx.portfolio_path("main").display(),
+            copy.display(),
+            copy.display()
+        )))
+        .stdout(predicate::str::contains(format!(
+            "{} and {} share 1 trades",
+            ctx.portfolio_path("main").display(),
+            synced.path().join("old.csv").display()
+        )));
+    ctx.cmd()
+        .env("LPT_PORTFOLIO_DIRS", &dirs)
+        .arg("doctor")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Found 2 problem(s)"));
+
+    ctx.cmd()
+        .env("LPT_PORTFOLIO_DIRS", &dirs)
+        .args(["merge-conflict", "--name", "main", "--from"])
+        .arg(&copy)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Merged 1 new and 0 deleted trades from",
+        ));
+    assert!(!copy.exists());
+    assert!(
+        std::fs::read_to_string(ctx.portfolio_path("main"))
+            .unwrap()
+            .contains("SELL")
+    );
+}
+
+#[test]
+fn closed_portfolio_is_listed_and_reported_but_not_written() {
+    let ctx = TestContext::new();
+    let name = "old";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "1", "50000", "10");
+    let prices = ctx.prices_file("prices.csv", "ticker,price\nBTC,60000\n");
+
+    ctx.cmd()
+        .args(["close", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Closed old, reopen it with `close --reopen`",
+        ));
+    ctx.cmd()
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("| old [closed] "));
+    ctx.cmd()
+        .args(["list", "--format", "csv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\nold,"));
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .args(["report", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Portfolio:\t60_000 USD"));
+    ctx.cmd()
+        .args([
+            "add-tx", "--name", name, "--ticker", "BTC/USD", "--side", "SELL", "--qty", "1",
+            "--price", "60000", "--fee", "0",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("portfolio was closed on"))
+        .stderr(predicate::str::contains("reopen it with `close --reopen`"));
+
+    ctx.cmd()
+        .args(["close", "--name", name, "--reopen"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Reopened old"));
+    ctx.add_tx_buy_btc(name, "1", "50000", "10");
+}
+
+#[test]
+fn fmt_rewrites_hand_edited_rows_in_canonical_form_once() {
+    let ctx = TestContext::new();
+    let edited = "# base_currency: usd
+# synced_by: nas
+created_at,pair,side,amount,price,fee
+1704883200, btc/usd ,buy,0.50,40000.00,7.50
+1704800000,ETH/USD,Buy,1,2.5E3,0
+";
+    std::fs::write(ctx.portfolio_path("main"), edited).unwrap();
+
+    ctx.cmd()
+        .args(["fmt", "--check"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("main.csv is not canonical"))
+        .stderr(predicate::str::contains("1 portfolio(s) need `fmt`"));
+    ctx.cmd()
+        .args(["fmt", "--name", "main"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Formatted "));
+    assert_eq!(
+        std::fs::read_to_string(ctx.portfolio_path("main")).unwrap(),
+        "# base_currency: USD
+# synced_by: nas
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,0.5,40000,7.5
+1704800000,ETH/USD,BUY,1,2500,0
+"
+    );
+    ctx.cmd()
+        .args(["fmt", "--check"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 portfolio(s) already canonical"));
+}
+
+#[test]
+fn fmt_keeps_amounts_add_tx_wrote_with_every_decimal_place() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("p");
+    ctx.cmd()
+        .args([
+            "add-tx",
+            "--name",
+            "p",
+            "--ticker",
+            "BTC/USD",
+            "--side",
+            "BUY",
+            "--qty",
+            "0.123456789012345678",
+            "--price",
+            "40000",
+            "--fee",
+            "1",
+        ])
+        .assert()
+        .success();
+
+    ctx.cmd()
+        .args(["fmt", "--check"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 portfolio(s) already canonical"));
+    ctx.cmd()
+        .args(["show", "--name", "p", "--format", "csv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(",0.123456789012345678,40000,1"));
+}
@@ -10,13 +10,21 @@ fn create_new_portfolio_then_list_shows_it() {
     ctx.assert_portfolio_exists(name);
     ctx.assert_list_contains(name);
 
-    let expected = "\
-# base_currency: USD
-created_at,pair,side,amount,price,fee
-";
+    assert_portfolio_content(&ctx, name, "USD");
+}
+
+fn assert_portfolio_content(ctx: &TestContext, name: &str, base_currency: &str) {
     let p_path = ctx.portfolio_path(name);
     let p_content = std::fs::read_to_string(p_path).unwrap();
-    pretty_assertions::assert_eq!(expected, p_content.as_str());
+    let lines: Vec<&str> = p_content.lines().collect();
+    assert_eq!(lines.len(), 3, "unexpected content:\n{p_content}");
+    pretty_assertions::assert_eq!(lines[0], format!("# base_currency: {base_currency}"));
+    assert!(
+        lines[1].starts_with("# created_at: ") && lines[1].ends_with('Z'),
+        "unexpected created_at line: {}",
+        lines[1]
+    );
+    pretty_assertions::assert_eq!(lines[2], "created_at,pair,side,amount,price,fee");
 }
 
 #[test]
@@ -48,12 +56,5 @@ fn create_eur_portfolio() {
         .stdout(predicate::str::contains("Created trades file:"));
 
     ctx.assert_portfolio_exists(name);
-
-    let expected = "\
-# base_currency: EUR
-created_at,pair,side,amount,price,fee
-";
-    let p_path = ctx.portfolio_path(name);
-    let p_content = std::fs::read_to_string(p_path).unwrap();
-    pretty_assertions::assert_eq!(expected, p_content.as_str());
+    assert_portfolio_content(&ctx, name, "EUR");
 }
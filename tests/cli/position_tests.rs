@@ -0,0 +1,184 @@
+use crate::common::fixtures::TestContext;
+use predicates::prelude::*;
+
+#[test]
+fn position_history_shows_avg_price_after_each_trade() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "dca",
+        "# base_currency: EUR
+created_at,pair,side,amount,price,fee
+1704883200,BTC/EUR,BUY,1,40000,0
+1705056000,BTC/EUR,BUY,1,20000,0
+",
+    );
+
+    ctx.cmd()
+        .args(["position", "--name", "dca", "btc", "--history"])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Avg price:\t30,000 EUR"))
+        .stdout(predicate::str::contains(
+            "| 2024-01-12 | Buy  | 1      | 20,000 | 2        | 30,000    |",
+        ))
+        .stdout(predicate::str::contains("40000.00 |*"));
+}
+
+#[test]
+fn position_without_trades_fails() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("empty");
+
+    ctx.cmd()
+        .args(["position", "--name", "empty", "ETH"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "no ETH/USD trades in portfolio 'empty'",
+        ));
+}
+
+#[test]
+fn position_entries_groups_buys_by_quarter() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "dca",
+        "# base_currency: EUR
+created_at,pair,side,amount,price,fee
+1704883200,BTC/EUR,BUY,1,40000,0
+1705056000,BTC/EUR,BUY,1,20000,0
+1719792000,BTC/EUR,BUY,0.5,60000,0
+",
+    );
+
+    ctx.cmd()
+        .args(["position", "--name", "dca", "btc", "--entries"])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "| 2024-Q1 |    2 |        2 | 60,000 |   30,000 | 66.7% |",
+        ))
+        .stdout(predicate::str::contains(
+            "| 2024-Q3 |    1 |      0.5 | 30,000 |   60,000 | 33.3% |",
+        ));
+}
+
+#[test]
+fn journal_entries_show_in_position_and_show() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "dca",
+        "# base_currency: EUR
+created_at,pair,side,amount,price,fee
+1704883200,BTC/EUR,BUY,1,40000,0
+1705056000,BTC/EUR,SELL,0.5,50000,0
+",
+    );
+    let output = ctx
+        .cmd()
+        .args(["show", "--name", "dca", "--with-journal", "--format", "csv"])
+        .output()
+        .unwrap();
+    let csv = String::from_utf8(output.stdout).unwrap();
+    let sell = csv.lines().nth(2).unwrap();
+    assert!(sell.ends_with(','), "no journal yet: {}", sell);
+    let id = sell.split(',').nth(6).unwrap().to_string();
+
+    ctx.cmd()
+        .args(["journal", "add", "--name", "dca", "--tx", &id[..6]])
+        .arg("took profit at 50k")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Added journal entry to trade {} in 'dca'",
+            id
+        )));
+
+    ctx.cmd()
+        .args(["position", "--name", "dca", "btc"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "📝 2024-01-12 Sell 0.5 @ 50,000: took profit at 50k",
+        ));
+    ctx.cmd()
+        .args(["show", "--name", "dca", "--with-journal", "--format", "csv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "{},took profit at 50k\n",
+            id
+        )));
+}
+
+#[test]
+fn position_stop_and_target_show_in_report_and_fire_in_watch() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("main");
+    ctx.add_tx_buy_btc("main", "1", "50000", "10");
+    let prices = ctx.prices_file("p.csv", "ticker,price\nBTC,80000\n");
+    let provider = format!("file:{}", prices.display());
+
+    ctx.cmd()
+        .args(["position", "set-stop", "--name", "main", "btc", "74000"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "🛑 Stop of BTC in main set at 74000",
+        ));
+    ctx.cmd()
+        .args(["position", "set-target", "--name", "main", "BTC", "70000"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "stop 74000 of BTC must be below its target 70000",
+        ));
+    ctx.cmd()
+        .args(["position", "set-target", "--name", "main", "BTC", "100000"])
+        .assert()
+        .success();
+    ctx.cmd()
+        .args(["position", "levels"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| main      | BTC   | 74000 | 100000 |",
+        ));
+
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", &provider)
+        .args(["report", "--name", "main"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| BTC   | 80000 USD | 74000 |  -7.50% | 100000 |   +25.00% |",
+        ));
+
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", &provider)
+        .args(["watch", "--once"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("🔔").not());
+    let crashed = ctx.prices_file("p.csv", "ticker,price\nBTC,73000\n");
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", crashed.display()))
+        .args(["watch", "--once"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "🔔 main: BTC at 73000 hit its stop at 74000",
+        ));
+
+    ctx.cmd()
+        .args(["position", "clear-levels", "--name", "main", "BTC"])
+        .assert()
+        .success();
+    ctx.cmd()
+        .args(["position", "levels"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No stop or target levels set"));
+}
@@ -8,3 +8,748 @@ fn report_single_ticker_single_tx_test() {
     ctx.add_tx_buy_btc(name, "0.5", "96450", "37");
     ctx.report(name);
 }
+
+#[test]
+fn report_uses_median_of_providers_and_warns_on_discrepancy() {
+    let ctx = TestContext::new();
+    let name = "testfolio";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "1", "50000", "10");
+    let a = ctx.prices_file("a.csv", "ticker,price\nBTC,100000\n");
+    let b = ctx.prices_file("b.csv", "ticker,price\nBTC,90000\n");
+
+    ctx.cmd()
+        .env(
+            "LPT_QUOTE_PROVIDER",
+            format!("file:{},file:{}", a.display(), b.display()),
+        )
+        .args(["report", "--name", name])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("BTC quotes differ by 10.53%"))
+        .stdout(predicates::str::contains("Portfolio:\t95_000 USD"));
+}
+
+#[test]
+fn report_fail_on_warning_exits_nonzero_when_a_provider_fails() {
+    let ctx = TestContext::new();
+    let name = "testfolio";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "1", "50000", "10");
+    let a = ctx.prices_file("a.csv", "ticker,price\nBTC,100000\n");
+    let missing = a.with_file_name("missing.csv");
+    let providers = format!("file:{},file:{}", a.display(), missing.display());
+
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", &providers)
+        .args(["report", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Portfolio:\t100_000 USD"));
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", &providers)
+        .args(["report", "--name", name, "--fail-on-warning"])
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("Portfolio:\t100_000 USD"))
+        .stderr(predicates::str::contains("quote provider file:"))
+        .stderr(predicates::str::contains(
+            "1 warnings while reporting, failing because of --fail-on-warning",
+        ));
+}
+
+#[test]
+fn report_all_values_holdings_and_trade_portfolios() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("kraken");
+    ctx.add_tx_buy_btc("kraken", "1", "50000", "10");
+    ctx.cmd()
+        .args(["new", "--name", "cold", "--holdings"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Created holdings file: "));
+    for args in [
+        ["--ticker", "BTC", "--qty", "0.5", "--cost", "10000"].as_slice(),
+        ["--ticker", "ETH", "--qty", "2"].as_slice(),
+    ] {
+        ctx.cmd()
+            .args(["hold", "--name", "cold"])
+            .args(args)
+            .assert()
+            .success();
+    }
+    let prices = ctx.prices_file("p.csv", "ticker,price\nBTC,100000\nETH,3000\n");
+    let provider = format!("file:{}", prices.display());
+
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", &provider)
+        .args(["report", "--all"])
+        .assert()
+        .success()
+        .stderr(predicates::str::is_empty())
+        .stdout(predicates::str::contains(
+            "| cold      | holdings |  56,000 USD | 40,000 USD |",
+        ))
+        .stdout(predicates::str::contains(
+            "| kraken    | trades   | 100,000 USD | 49,990 USD |",
+        ))
+        .stdout(predicates::str::contains("Total:\t\t156_000 USD"));
+
+    // holdings without cost are valued, but have no PnL
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", &provider)
+        .args(["report", "--name", "cold"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("| ETH    | 2       | -"))
+        .stdout(predicates::str::contains("Portfolio:\t56_000.0 USD"))
+        .stdout(predicates::str::contains("Total PnL:\t40_000.0 USD"));
+
+    ctx.cmd()
+        .args([
+            "add-tx", "--name", "cold", "--ticker", "BTC/USD", "--side", "BUY", "--qty", "1",
+            "--price", "1", "--fee", "0",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "holdings-only portfolio has no trades",
+        ));
+}
+
+#[test]
+fn report_diff_shows_changes_since_previous_and_saved_runs() {
+    let ctx = TestContext::new();
+    let name = "testfolio";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "1", "50000", "10");
+    let before = ctx.prices_file("before.csv", "ticker,price\nBTC,100000\n");
+    let after = ctx.prices_file("after.csv", "ticker,price\nBTC,90000\n");
+    let report = |prices: &std::path::Path, args: &[&str]| {
+        ctx.cmd()
+            .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+            .args(["report", "--name", name])
+            .args(args)
+            .assert()
+            .success()
+    };
+
+    report(&before, &["--diff"]).stdout(predicates::str::contains(
+        "No previous report to compare with",
+    ));
+    report(&before, &["--save", "q1"]).stdout(predicates::str::contains("Saved report as 'q1'"));
+    report(&after, &["--diff"])
+        .stdout(predicates::str::contains("Changes since last report"))
+        .stdout(predicates::str::contains(
+            "| ▼ | BTC    |       0 | -10,000 USD | 100% → 100% |",
+        ))
+        .stdout(predicates::str::contains("Value:\t\t-10,000 USD (-10%)"))
+        .stdout(predicates::str::contains("PnL:\t\t-10,000 USD"));
+
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", after.display()))
+        .args(["report", "--name", name, "--diff", "q2"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("no saved report 'q2'"));
+}
+
+#[test]
+fn report_benchmark_compares_with_same_buys_in_benchmark() {
+    let ctx = TestContext::new();
+    let name = "testfolio";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "1", "50000", "10");
+    // price of the day of the buy already cached, so nothing is fetched
+    let prices_dir = ctx
+        .portfolio_path(name)
+        .parent()
+        .unwrap()
+        .join(".csvpt/prices");
+    std::fs::create_dir_all(&prices_dir).unwrap();
+    let today = time::OffsetDateTime::now_utc().date();
+    std::fs::write(
+        prices_dir.join("BTC-USD.json"),
+        format!(r#"{{"{}":"40000"}}"#, today),
+    )
+    .unwrap();
+    let prices = ctx.prices_file("prices.csv", "ticker,price\nBTC,100000\n");
+
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .args(["report", "--name", name, "--benchmark", "btc"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Benchmark BTC (100% BTC):"))
+        .stdout(predicates::str::contains("Invested:\t50,010 USD"))
+        .stdout(predicates::str::contains(
+            "Portfolio:\t100,000 USD (+99.96%)",
+        ))
+        .stdout(predicates::str::contains(
+            "Benchmark:\t125,025 USD (+150.00%)",
+        ))
+        .stdout(predicates::str::contains("Difference:\t-25,025 USD"));
+
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .args(["report", "--name", name, "--benchmark", "my60_40"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("unknown benchmark 'my60_40'"));
+}
+
+#[test]
+fn freeze_then_report_as_of_reproduces_statement() {
+    let ctx = TestContext::new();
+    let name = "testfolio";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "1", "50000", "10");
+    let prices_dir = ctx
+        .portfolio_path(name)
+        .parent()
+        .unwrap()
+        .join(".csvpt/prices");
+    std::fs::create_dir_all(&prices_dir).unwrap();
+    let today = time::OffsetDateTime::now_utc().date().to_string();
+    std::fs::write(
+        prices_dir.join("BTC-USD.json"),
+        format!(r#"{{"{}":"60000"}}"#, today),
+    )
+    .unwrap();
+
+    let frozen = ctx
+        .cmd()
+        .args(["freeze", "--name", name, "--date", &today])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!(
+            "Statement of {} as of {}",
+            name, today
+        )))
+        .stdout(predicates::str::contains(
+            "| BTC    |       1 | 60,000 USD | 60,000 USD | 50,010 USD |",
+        ))
+        .stdout(predicates::str::contains("PnL:\t\t9,990 USD (19.98%)"))
+        .stdout(predicates::str::contains("🔒 Froze statement in"));
+    let frozen = String::from_utf8(frozen.get_output().stdout.clone()).unwrap();
+
+    // later buys and price changes don't touch the frozen numbers
+    std::fs::write(
+        prices_dir.join("BTC-USD.json"),
+        format!(r#"{{"{}":"70000"}}"#, today),
+    )
+    .unwrap();
+    let report = ctx
+        .cmd()
+        .args(["report", "--name", name, "--as-of", &today])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Frozen statement,"));
+    let report = String::from_utf8(report.get_output().stdout.clone()).unwrap();
+    let statement = |out: &str| {
+        out.lines()
+            .take_while(|l| !l.contains("Froze"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    assert_eq!(statement(&frozen), statement(&report));
+
+    ctx.cmd()
+        .args(["freeze", "--name", name, "--date", &today])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("is already frozen"));
+    ctx.cmd()
+        .args(["report", "--name", name, "--as-of", "31.12.2024"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("expected YYYY-MM-DD"));
+}
+
+#[test]
+fn saved_scenario_runs_by_name() {
+    let ctx = TestContext::new();
+    let name = "testfolio";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "1", "50000", "0");
+    let prices = ctx.prices_file("prices.csv", "ticker,price\nBTC,100000\n");
+
+    ctx.cmd()
+        .args([
+            "scenarios",
+            "save",
+            "bear2026",
+            "--btc",
+            "-60%",
+            "--eth",
+            "-75%",
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "Saved scenario 'bear2026': BTC -60%, ETH -75%",
+        ));
+    ctx.cmd()
+        .args(["scenarios", "list"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "| bear2026 | BTC -60%, ETH -75% |",
+        ));
+
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .args(["scenario", "--name", name, "--saved", "bear2026"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "Portfolio:\t100,000 USD\t40,000 USD",
+        ));
+    ctx.cmd()
+        .args(["scenario", "--name", name, "--saved", "bull"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("unknown scenario 'bull'"));
+}
+
+#[test]
+fn report_fx_splits_asset_and_currency_pnl() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "eur",
+        "# base_currency: EUR
+created_at,pair,side,amount,price,fee
+1704883200,USDT/EUR,BUY,1000,0.8,0
+1704883200,BTC/USDT,BUY,0.01,40000,0
+",
+    );
+    // 1.25 USD per EUR when buying, parity today
+    let today = time::OffsetDateTime::now_utc().date().to_string();
+    let fx_dir = ctx
+        .portfolio_path("eur")
+        .parent()
+        .unwrap()
+        .join(".csvpt/fx");
+    std::fs::create_dir_all(&fx_dir).unwrap();
+    std::fs::write(
+        fx_dir.join("ecb-USD.json"),
+        format!(
+            r#"{{"currency":"USD","from":"2024-01-01","to":"{today}",
+                "rates":{{"2024-01-10":"1.25","{today}":"1.0"}}}}"#
+        ),
+    )
+    .unwrap();
+    let prices = ctx.prices_file("prices.csv", "ticker,price\nBTC,50000\n");
+
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .args(["report", "--name", "eur", "--fx"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("FX effect (USD/EUR):"))
+        .stdout(predicates::str::contains(
+            "| BTC   | 500 EUR | 320 EUR |   100 EUR |  80 EUR |",
+        ))
+        .stdout(predicates::str::contains("Asset PnL:\t100 EUR"))
+        .stdout(predicates::str::contains("FX PnL:\t\t200 EUR"));
+
+    ctx.cmd()
+        .args(["report", "--name", "eur", "--fx", "--as-of", "2024-01-10"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn history_charts_snapshots_and_hints_at_reconstruct() {
+    let ctx = TestContext::new();
+    let name = "testfolio";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "1", "50000", "10");
+
+    ctx.cmd()
+        .args(["history", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "No history of testfolio yet, take snapshots with `snapshot` or `watch`, or rebuild it from the trades with --reconstruct",
+        ));
+
+    let prices = ctx.prices_file("p.csv", "ticker,price\nBTC,100000\n");
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .args(["snapshot", "--name", name])
+        .assert()
+        .success();
+    ctx.cmd()
+        .args(["history", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("100000.00 |*"))
+        .stdout(predicates::str::contains(": 100,000 (+0.00%), 1 days"));
+}
+
+#[test]
+fn report_provider_overrides_the_configured_quote_provider() {
+    let ctx = TestContext::new();
+    let name = "testfolio";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "1", "50000", "10");
+    let configured = ctx.prices_file("configured.csv", "ticker,price\nBTC,100000\n");
+    let other = ctx.prices_file("other.csv", "ticker,price\nBTC,80000\n");
+    let provider = format!("file:{}", other.display());
+
+    ctx.cmd()
+        .env(
+            "LPT_QUOTE_PROVIDER",
+            format!("file:{}", configured.display()),
+        )
+        .args([
+            "report",
+            "--name",
+            name,
+            "--no-cache",
+            "--provider",
+            &provider,
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Portfolio:\t80_000 USD"));
+    ctx.cmd()
+        .args(["report", "--name", name, "--provider", "yahoo"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "unsupported quote provider 'yahoo'",
+        ));
+}
+
+#[test]
+fn snapshot_compact_downsamples_old_snapshots() {
+    let ctx = TestContext::new();
+    let name = "testfolio";
+    ctx.create_portfolio(name);
+    let snapshots = ctx.portfolio_path(name).with_file_name(".csvpt/snapshots");
+    std::fs::create_dir_all(&snapshots).unwrap();
+    // every 5 minutes from 2024-01-01 00:00 to 02:55, months before now
+    let mut rows = "ts,value\n".to_string();
+    for i in 0..36 {
+        rows.push_str(&format!("{},{}\n", 1704067200 + i * 300, 1000 + i));
+    }
+    std::fs::write(snapshots.join(format!("{}.csv", name)), rows).unwrap();
+
+    ctx.config_file(
+        "confirm_threshold = 100\n\n[snapshot_retention]\nhourly_days = 1\ndaily_days = 30\n",
+    );
+    ctx.cmd()
+        .env("CSVPT_FAKE_NOW", "2024-01-10T12:00:00Z")
+        .args(["snapshot", "compact", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("testfolio: 36 → 1 snapshots"));
+    ctx.cmd()
+        .args(["history", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("2024-01-01: 1,035"));
+}
+
+#[test]
+fn report_values_delisted_assets_at_their_final_price() {
+    let ctx = TestContext::new();
+    let name = "testfolio";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "1", "50000", "10");
+    ctx.cmd()
+        .args([
+            "add-tx", "-n", name, "-t", "PUMP/USD", "--side", "BUY", "-q", "100000", "-p", "0.005",
+            "-f", "0",
+        ])
+        .assert()
+        .success();
+    // the provider doesn't quote PUMP anymore
+    let prices = ctx.prices_file("p.csv", "ticker,price\nBTC,100000\n");
+    let provider = format!("file:{}", prices.display());
+
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", &provider)
+        .args(["report", "--name", name])
+        .assert()
+        .failure();
+    ctx.config_file("[delisted.PUMP]\nprice = 0.001\n");
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", &provider)
+        .args(["report", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("| PUMP ☠ |"))
+        .stdout(predicates::str::contains("Portfolio:\t100_100.00 USD"))
+        .stdout(predicates::str::contains(
+            "☠ Delisted, valued at the final price of the config: PUMP at 0.001 USD",
+        ));
+}
+
+#[test]
+fn history_all_charts_the_workspace_total_in_the_config_base() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("usd");
+    ctx.add_tx_buy_btc("usd", "1", "50000", "0");
+    ctx.create_portfolio("cold");
+    ctx.add_tx_buy_btc("cold", "0.5", "40000", "0");
+    ctx.cmd()
+        .args(["history", "--all"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("No workspace history yet"));
+
+    let prices = ctx.prices_file("p.csv", "ticker,price\nBTC,100000\n");
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .args(["snapshot"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("📸 total: 150000 USD"));
+    ctx.cmd()
+        .args(["history", "--all"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(": 150,000 (+0.00%), 1 days"));
+    ctx.cmd()
+        .args(["history", "--all", "--name", "usd"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn report_falls_back_to_manual_prices_until_they_expire() {
+    let ctx = TestContext::new();
+    let name = "testfolio";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "1", "50000", "0");
+    // the provider doesn't quote BTC
+    let prices = ctx.prices_file("p.csv", "ticker,price\nETH,3000\n");
+    let provider = format!("file:{}", prices.display());
+    let report = |now: &str| {
+        ctx.cmd()
+            .env("LPT_QUOTE_PROVIDER", &provider)
+            .env("CSVPT_FAKE_NOW", now)
+            .args(["report", "--name", name])
+            .assert()
+    };
+    report("2098-12-20T12:00:00Z")
+        .failure()
+        .stderr(predicates::str::contains("quote missing for BTC/USD"));
+
+    ctx.cmd()
+        .env("CSVPT_FAKE_NOW", "2098-12-20T12:00:00Z")
+        .args(["price", "set", "btc", "90000", "--until", "2099-01-01"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "Set manual price of BTC: 90000 USD until 2099-01-01",
+        ));
+    report("2098-12-20T12:00:00Z")
+        .success()
+        .stderr(predicates::str::is_empty())
+        .stdout(predicates::str::contains("Portfolio:\t90_000 USD"));
+    report("2098-12-28T12:00:00Z")
+        .success()
+        .stderr(predicates::str::contains(
+            "manual price of BTC expires on 2099-01-01",
+        ));
+    report("2099-01-02T12:00:00Z")
+        .failure()
+        .stderr(predicates::str::contains(
+            "its manual price expired on 2099-01-01",
+        ));
+    ctx.cmd()
+        .env("CSVPT_FAKE_NOW", "2098-12-28T12:00:00Z")
+        .args(["price", "list"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "| BTC    | 90000 USD | 2099-01-01 | expires in 4 days |",
+        ));
+}
+
+#[test]
+fn doctor_and_report_check_balance_assertions() {
+    let ctx = TestContext::new();
+    let name = "testfolio";
+    ctx.create_eur_portfolio(
+        name,
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1.2345,40000,0
+1705056000,BTC/USD,SELL,0.2,50000,0
+",
+    );
+    let dir = ctx.portfolio_path(name).with_file_name(".csvpt/assertions");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("testfolio.toml"),
+        r#"[[balance]]
+date = "2024-01-10"
+ticker = "BTC"
+balance = "1.2345"
+"#,
+    )
+    .unwrap();
+    let prices = ctx.prices_file("p.csv", "ticker,price\nBTC,100000\n");
+    let report = || {
+        ctx.cmd()
+            .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+            .args(["report", "--name", name, "--fail-on-warning"])
+            .assert()
+    };
+    report().success().stdout(predicates::str::contains(
+        "Assertions:\t1 balances as asserted",
+    ));
+    ctx.cmd()
+        .arg("doctor")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("No problems found"));
+
+    // a row mistyped later, before the asserted day
+    let csv = std::fs::read_to_string(ctx.portfolio_path(name)).unwrap();
+    std::fs::write(ctx.portfolio_path(name), csv.replace("1.2345", "1.2346")).unwrap();
+    report().failure().stderr(predicates::str::contains(
+        "assertion failed: on 2024-01-10 BTC balance is 1.2346 instead of 1.2345",
+    ));
+    ctx.cmd()
+        .arg("doctor")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "assertion of 'testfolio' failed: on 2024-01-10 BTC balance is 1.2346 instead of 1.2345",
+        ))
+        .stdout(predicates::str::contains("Found 1 problem(s)"));
+}
+
+#[test]
+fn fixture_prices_and_now_make_report_and_history_reproducible_offline() {
+    let ctx = TestContext::new();
+    let name = "testfolio";
+    ctx.create_portfolio(name);
+    std::fs::write(
+        ctx.portfolio_path(name),
+        "created_at,pair,side,amount,price,fee\n1704844800,BTC/USD,BUY,1,40000,0\n",
+    )
+    .unwrap();
+    let fixtures = ctx.prices_file(
+        "fixtures.csv",
+        "ticker,price,date\nBTC,50000,\nBTC,41000,2024-01-11\nBTC,42000,2024-01-12\nBTC,43000,2024-01-13\n",
+    );
+    let fixtures = fixtures.to_str().unwrap();
+    let offline = [
+        "--fixture-prices",
+        fixtures,
+        "--now",
+        "2024-01-13T12:00:00Z",
+    ];
+
+    ctx.cmd()
+        .args(["report", "--name", name])
+        .args(offline)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Portfolio:\t50_000 USD"));
+    ctx.cmd()
+        .args(["history", "--name", name, "--reconstruct"])
+        .args(offline)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "Reconstructed 3 daily values of testfolio from its trades",
+        ))
+        .stdout(predicates::str::contains(
+            "2024-01-11: 41,000 → 2024-01-13: 43,000 (+4.87%), 3 days",
+        ));
+
+    // a day missing from the fixtures fails instead of going to the network
+    std::fs::copy(ctx.portfolio_path(name), ctx.portfolio_path("other")).unwrap();
+    ctx.cmd()
+        .args(["history", "--name", "other", "--reconstruct"])
+        .args([
+            "--fixture-prices",
+            fixtures,
+            "--now",
+            "2024-01-14T12:00:00Z",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("no BTC price of 2024-01-14"));
+    ctx.cmd()
+        .args(["report", "--name", name, "--now", "yesterday"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn report_shows_no_pnl_percent_for_positions_without_cost() {
+    let ctx = TestContext::new();
+    let name = "staking";
+    ctx.create_portfolio(name);
+    std::fs::write(
+        ctx.portfolio_path(name),
+        "created_at,pair,side,amount,price,fee\n1704844800,ETH,INCOME,2,1500,0\n",
+    )
+    .unwrap();
+    let prices = ctx.prices_file("prices.csv", "ticker,price\nETH,3000\n");
+
+    ctx.cmd()
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .args(["report", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "| ETH    | 2       | 0 USD     | 0 USD     | -     |",
+        ))
+        .stdout(predicates::str::contains("Total PnL:\t6_000 USD"));
+}
+
+#[test]
+fn report_widget_prints_waybar_json_with_the_daily_change() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("main");
+    std::fs::write(
+        ctx.portfolio_path("main"),
+        "created_at,pair,side,amount,price,fee\n1704844800,BTC/USD,BUY,1,50000,10\n",
+    )
+    .unwrap();
+    let yesterday = ctx.prices_file("yesterday.csv", "ticker,price\nBTC,80000\n");
+    let today = ctx.prices_file("today.csv", "ticker,price\nBTC,84000\n");
+
+    ctx.cmd()
+        .arg("--fixture-prices")
+        .arg(&yesterday)
+        .args(["--now", "2025-03-01T12:00:00Z", "snapshot"])
+        .assert()
+        .success();
+    let widget = |args: &[&str]| {
+        let output = ctx
+            .cmd()
+            .arg("--fixture-prices")
+            .arg(&today)
+            .args(["--now", "2025-03-02T12:30:00Z", "report"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let line = widget(&["--name", "main", "--widget", "waybar"]);
+    let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(json["text"], "84,000 USD +5.00%");
+    assert_eq!(json["class"], "up");
+    assert_eq!(json["tooltip"], "main: 84,000 USD, +5.00% in 24h");
+    assert_eq!(
+        widget(&["--all", "--widget", "polybar"]),
+        "84,000 USD +5.00%\n"
+    );
+
+    let line = widget(&["--name", "missing", "--widget", "waybar"]);
+    let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(json["class"], "error");
+}
@@ -11,6 +11,47 @@ fn show_trades_on_empty_portfolio() {
     ctx.show_empty_portfolio(name);
 }
 
+#[test]
+fn show_and_report_read_trades_piped_to_stdin() {
+    let ctx = TestContext::new();
+    let trades = "created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,10
+1704969600,ETH/USD,BUY,2,2000,0
+";
+
+    ctx.cmd()
+        .args(["show", "--stdin", "--format", "csv"])
+        .write_stdin(trades)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "2024-01-10T10:40:00Z,BTC/USD,Buy,1,40000,10,stdin\n",
+        ));
+
+    let prices = ctx.prices_file("p.csv", "ticker,price\nBTC,50000\nETH,3000\n");
+    ctx.cmd()
+        .args(["--fixture-prices", prices.to_str().unwrap()])
+        .args(["report", "--stdin"])
+        .write_stdin(format!("# base_currency: USD\n{}", trades))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| BTC    | 1       | 40,010 USD | 40,010 USD | 24.96% |",
+        ))
+        .stdout(predicate::str::contains("Portfolio:\t56_000 USD"));
+
+    ctx.cmd()
+        .args(["show", "--stdin"])
+        .write_stdin("created_at,pair,side,amount,price,fee\n1704883200,BTC/USD,BUY,x,1,0\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("1 row(s) on stdin can't be read"));
+    ctx.cmd()
+        .args(["show", "--stdin", "--name", "main"])
+        .assert()
+        .failure();
+}
+
 #[test]
 fn show_trades_on_eur_base() {
     let ctx = TestContext::new();
@@ -38,3 +79,384 @@ created_at,pair,side,amount,price,fee
         .stderr(predicate::str::is_empty())
         .stdout(predicate::str::diff(exp));
 }
+
+#[test]
+fn show_filters_rows_by_source() {
+    let ctx = TestContext::new();
+    let name = "sourced";
+    let data = "# base_currency: EUR
+created_at,pair,side,amount,price,fee,source
+1704883200,BTC/EUR,BUY,1.0,40000.00,7.50,import:binance
+1704969600,ETH/EUR,BUY,2,2000,1,manual
+1705056000,BTC/EUR,SELL,0.5,45000,5,
+";
+
+    ctx.create_eur_portfolio(name, data);
+
+    ctx.cmd()
+        .args(["show", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("| source         |"))
+        .stdout(predicate::str::contains(
+            "| Sell | 0.5    | 45000 | 5   | -              |",
+        ));
+
+    ctx.cmd()
+        .args(["show", "--name", name, "--source", "import"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("import:binance"))
+        .stdout(predicate::str::contains("ETH/EUR").not())
+        .stdout(predicate::str::contains("Sell").not());
+
+    ctx.cmd()
+        .args(["show", "--name", name, "--source", "sync"])
+        .assert()
+        .success()
+        .stdout(predicate::str::diff("No trades from source 'sync'\n"));
+
+    ctx.cmd()
+        .args(["stats", "--name", name])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Rows:\t\t3 (2 buys, 1 sells, 0 deposits, 0 withdrawals, 0 fees, 0 income)",
+        ))
+        .stdout(predicate::str::contains(
+            "| import:binance |    1 | 2024-01-10 | 2024-01-10 |",
+        ))
+        .stdout(predicate::str::contains(
+            "| (unrecorded)   |    1 | 2024-01-12 | 2024-01-12 |",
+        ));
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+#[test]
+fn show_copy_prints_output_before_failing_without_clipboard() {
+    let ctx = TestContext::new();
+    let name = "copied";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "0.5", "96450", "37");
+
+    ctx.cmd()
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .args(["show", "--name", name, "--copy"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("| BTC/USD | Buy  | 0.5"))
+        .stderr(predicate::str::contains(
+            "no system clipboard available for --copy",
+        ));
+}
+
+#[test]
+fn show_machine_formats_use_iso_utc_timestamps() {
+    let ctx = TestContext::new();
+    let name = "machine";
+    let data = "# base_currency: EUR
+created_at,pair,side,amount,price,fee
+1704883200,BTC/EUR,BUY,1.0,40000.00,7.50
+";
+    ctx.create_eur_portfolio(name, data);
+
+    let exp = "\
+created_at,pair,side,amount,price,fee
+2024-01-10T10:40:00Z,BTC/EUR,Buy,1,40000,7.5
+";
+    ctx.cmd()
+        .args(["show", "--name", name, "--format", "csv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::diff(exp));
+    ctx.cmd()
+        .args(["show", "--name", name, "--output", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#""created_at": "2024-01-10T10:40:00Z""#,
+        ));
+}
+
+#[test]
+fn stats_by_tag_reports_pnl_per_tag() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "tagged",
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704067200,BTC/USD,BUY,1,40000,0
+1706745600,BTC/USD,BUY,1,20000,0
+1709251200,BTC/USD,SELL,0.5,50000,0
+",
+    );
+    let output = ctx
+        .cmd()
+        .args([
+            "show",
+            "--name",
+            "tagged",
+            "--with-journal",
+            "--format",
+            "csv",
+        ])
+        .output()
+        .unwrap();
+    let ids: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|l| l.split(',').nth(6).unwrap().to_string())
+        .collect();
+    for (id, tag) in [(&ids[0], "dca"), (&ids[1], "dip"), (&ids[2], "dip")] {
+        ctx.cmd()
+            .args(["tag", "--name", "tagged", "--tx", id, tag])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!(
+                "Trade {} tagged {}",
+                id, tag
+            )));
+    }
+
+    let prices = ctx.prices_file("prices.csv", "ticker,price\nBTC,60000\n");
+    ctx.cmd()
+        .args(["stats", "--name", "tagged", "--by-tag"])
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| dca |      1 |   40,000 |            0 |         20,000 |",
+        ))
+        .stdout(predicate::str::contains(
+            "| dip |      2 |   20,000 |       15,000 |         20,000 |",
+        ));
+}
+
+#[test]
+fn stats_heatmap_shows_activity_per_day_over_the_past_year() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "active",
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704067200,BTC/USD,BUY,1,40000,0
+1706745600,BTC/USD,BUY,1,20000,0
+1709251200,BTC/USD,SELL,0.5,50000,0
+",
+    );
+    let heatmap = |args: &[&str]| {
+        ctx.cmd()
+            .env("CSVPT_FAKE_NOW", "2024-03-15T12:00:00Z")
+            .args(["stats", "--name", "active", "--heatmap"])
+            .args(args)
+            .assert()
+            .success()
+    };
+    heatmap(&[])
+        .stdout(predicate::str::contains("Mon ·"))
+        .stdout(predicate::str::contains("Less ·░▒▓█ More"))
+        .stdout(predicate::str::contains(
+            "3 trades on 3 days since 2023-03-13, most on 2024-01-01 (1)",
+        ));
+    heatmap(&["invested"]).stdout(predicate::str::contains(
+        "60000 USD invested on 2 days since 2023-03-13, most on 2024-01-01 (40000 USD)",
+    ));
+}
+
+#[test]
+fn output_modes_route_rows_and_status_lines() {
+    let ctx = TestContext::new();
+    ctx.cmd()
+        .args(["new", "--name", "quiet", "--output", "silent"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+    ctx.cmd()
+        .args([
+            "add-tx", "--name", "quiet", "--ticker", "BTC/USD", "--side", "BUY", "--qty", "1",
+            "--price", "60000", "--fee", "0", "--quiet",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+
+    // rows as JSON on stdout whatever --format says, other lines on stderr
+    let out = ctx
+        .cmd()
+        .args(["show", "--name", "quiet", "--output", "json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let rows: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(rows[0]["pair"], "BTC/USD");
+
+    ctx.create_portfolio("empty");
+    ctx.cmd()
+        .args(["show", "--name", "empty", "--output", "json"])
+        .assert()
+        .success()
+        .stdout("[]\n");
+
+    // every command's rows, the lines around them go to stderr
+    let out = ctx
+        .cmd()
+        .args(["stats", "--name", "quiet", "--output", "json"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Portfolio:\tquiet (USD)"))
+        .get_output()
+        .stdout
+        .clone();
+    let rows: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(rows[0]["Rows"], "1");
+    let prices = ctx.prices_file("prices.csv", "ticker,price\nBTC,70000\n");
+    ctx.cmd()
+        .args(["report", "--name", "quiet", "--output", "silent"])
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::is_empty());
+    ctx.cmd()
+        .args(["report", "--name", "quiet", "--output", "csv"])
+        .env("LPT_QUOTE_PROVIDER", format!("file:{}", prices.display()))
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "Ticker,Balance,Cost Base,Avg Price,PnL %\nBTC,",
+        ));
+
+    // lines and charts have no machine format
+    ctx.cmd()
+        .args(["serve", "--output", "json"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "`serve` doesn't print rows here, there is no --output json for it",
+        ));
+    ctx.cmd()
+        .args(["stats", "--name", "quiet", "--behavior", "--output", "csv"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn stats_fee_check_flags_fees_off_the_exchange_tier() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "fees",
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee,source
+1704883200,BTC/USDT,BUY,1,40000,40,import:binance
+1704969600,BTC/USDT,SELL,0.5,42000,210,import:binance
+1705056000,ETH/USD,BUY,2,2500,12.5,manual
+1705142400,ETH/USD,BUY,2,2500,0,manual
+",
+    );
+
+    ctx.cmd()
+        .args(["stats", "--name", "fees", "--fee-check"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no fee tiers configured"));
+
+    ctx.config_file(
+        r#"
+[fee_tiers.binance]
+maker_bps = 10
+taker_bps = 10
+
+[fee_tiers.default]
+maker_bps = 15
+taker_bps = 25
+"#,
+    );
+    ctx.cmd()
+        .args(["--quiet", "stats", "--name", "fees", "--fee-check"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| BTC/USDT | Sell | binance | 210 USDT |        21 USDT | +900% |",
+        ))
+        .stdout(predicate::str::contains(
+            "| ETH/USD  | Buy  | default |    0 USD | 7.5 - 12.5 USD | -100% |",
+        ))
+        .stdout(predicate::str::contains(
+            "2 of 4 checked trades have fees off their schedule by more than 50%",
+        ));
+}
+
+#[test]
+fn stats_behavior_shows_buy_spacing_holding_time_and_win_rate() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "active",
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704067200,BTC/USD,BUY,1,40000,0
+1704153600,ETH/USD,BUY,10,2000,0
+1704499200,BTC/USD,BUY,1,50000,0
+1705276800,BTC/USD,SELL,1.5,48000,100
+1706140800,ETH/USD,SELL,10,1900,0
+",
+    );
+
+    ctx.cmd()
+        .args(["stats", "--name", "active", "--behavior"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Buys:\t\t3, a median 2.5 days apart\n",
+        ))
+        .stdout(predicate::str::contains(
+            "Holding:\t3 lots sold or swapped, held 15.3 days on average\n",
+        ))
+        .stdout(predicate::str::contains(
+            "Win rate:\t50.00% of 2 sales for USD (1 with a gain), realized 5,900 USD\n",
+        ));
+
+    ctx.create_portfolio("new");
+    ctx.cmd()
+        .args(["stats", "--name", "new", "--behavior"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("too few to space out"))
+        .stdout(predicate::str::contains("Win rate:\tno sales for USD yet"));
+}
+
+#[test]
+fn stats_prices_shows_average_buy_and_sell_price_per_pair_and_year() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "active",
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,1,40000,10
+1704969600,BTC/USD,BUY,3,48000,0
+1705056000,BTC/USD,SELL,2,45000,0
+1736640000,BTC/USD,SELL,1,90000,0
+",
+    );
+
+    ctx.cmd()
+        .args(["stats", "--name", "active", "--prices"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| BTC/USD | 2024 |      4 |  46,000 |    2 |   45,000 |      -2.17% |",
+        ))
+        .stdout(predicate::str::contains(
+            "| BTC/USD | 2025 |      0 |       - |    1 |   90,000 |           - |",
+        ));
+
+    ctx.create_portfolio("new");
+    ctx.cmd()
+        .args(["stats", "--name", "new", "--prices"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No buys or sells found"));
+}
@@ -0,0 +1,132 @@
+use crate::common::fixtures::TestContext;
+use predicates::prelude::*;
+
+#[test]
+fn tax_converts_disposals_with_cached_ecb_rates() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "usd",
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,2,40000,0
+1710460800,BTC/USD,SELL,1,50000,100
+",
+    );
+    // rates already cached for the period, so nothing is fetched
+    let fx_dir = ctx
+        .portfolio_path("usd")
+        .parent()
+        .unwrap()
+        .join(".csvpt/fx");
+    std::fs::create_dir_all(&fx_dir).unwrap();
+    std::fs::write(
+        fx_dir.join("ecb-USD.json"),
+        r#"{"currency":"USD","from":"2024-01-01","to":"2024-03-31",
+            "rates":{"2024-01-10":"1.25","2024-03-15":"1.0"}}"#,
+    )
+    .unwrap();
+
+    ctx.cmd()
+        .args([
+            "tax",
+            "--name",
+            "usd",
+            "--currency",
+            "eur",
+            "--year",
+            "2024",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "| 2024-03-15 | BTC   | 1      | 49,900 EUR | 32,000 EUR | 17,900 EUR |",
+        ))
+        .stdout(predicate::str::contains("Gain:\t\t17,900 EUR"))
+        .stdout(predicate::str::contains("ECB reference rates"));
+
+    ctx.cmd()
+        .args(["tax", "--name", "usd", "--year", "2023"])
+        .assert()
+        .success()
+        .stdout(predicate::str::diff("No disposals found\n"));
+
+    // the sale in March 2024 falls into the fiscal year starting April 2023
+    ctx.cmd()
+        .env("LPT_FISCAL_YEAR_START_MONTH", "4")
+        .args(["tax", "--name", "usd", "--year", "2023"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Tax year 2023/24: 2023-04-01 to 2024-03-31",
+        ))
+        .stdout(predicate::str::contains("Gain:\t\t9,900 USD"));
+}
+
+#[test]
+fn tax_rounds_disposals_per_the_configured_rounding() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "usd",
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,ETH/USD,BUY,1,1000,0
+1710460800,ETH/USD,SELL,0.5,2000.25,0
+1710547200,ETH/USD,SELL,0.5,2000.25,0
+",
+    );
+    let tax = || ctx.cmd().args(["tax", "--name", "usd"]).assert().success();
+    // proceeds of 1000.125 each
+    tax()
+        .stdout(predicate::str::contains("1,000.12 USD"))
+        .stdout(predicate::str::contains("Proceeds:\t2,000.24 USD"));
+
+    ctx.config_file("rounding = \"half-up\"\n");
+    tax()
+        .stdout(predicate::str::contains("1,000.13 USD"))
+        .stdout(predicate::str::contains("Proceeds:\t2,000.26 USD"))
+        .stdout(predicate::str::contains("Gain:\t\t1,000.26 USD"));
+}
+
+#[test]
+fn statement_defaults_to_the_month_before_today() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "usd",
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704067200,USD,DEPOSIT,50000,1,0
+1704110400,BTC/USD,BUY,1,40000,20
+1707307200,BTC/USD,SELL,0.5,45000,10
+",
+    );
+    let prices_dir = ctx
+        .portfolio_path("usd")
+        .parent()
+        .unwrap()
+        .join(".csvpt/prices");
+    std::fs::create_dir_all(&prices_dir).unwrap();
+    std::fs::write(
+        prices_dir.join("BTC-USD.json"),
+        r#"{"2024-01-31": 42000, "2024-02-29": 50000}"#,
+    )
+    .unwrap();
+
+    ctx.cmd()
+        .env("CSVPT_FAKE_NOW", "2024-03-01T06:00:00Z")
+        .args(["statement", "--name", "usd"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "Statement of usd for 2024-02\n",
+        ))
+        .stdout(predicate::str::contains("Value on 2024-02-29"))
+        .stdout(predicate::str::contains("-17,000 USD (-40.48%)"))
+        .stdout(predicate::str::contains("2,480 USD"));
+
+    ctx.cmd()
+        .args(["statement", "--name", "usd", "--month", "2024-13"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected YYYY-MM"));
+}
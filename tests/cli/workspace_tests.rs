@@ -0,0 +1,376 @@
+use crate::common::fixtures::TestContext;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use predicates::prelude::*;
+
+#[test]
+fn export_then_import_workspace_restores_portfolios() {
+    let src = TestContext::new();
+    let name = "wallet";
+    src.create_portfolio(name);
+    src.add_tx_buy_btc(name, "0.5", "96450", "37");
+
+    let archive = tempfile::TempDir::new().unwrap();
+    let archive_path = archive.path().join("ws.cpt");
+
+    src.cmd()
+        .args(["export-workspace", archive_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Exported 2 files to workspace archive",
+        ));
+
+    let dst = TestContext::new();
+    dst.cmd()
+        .args(["import-workspace", archive_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Imported 2 files"));
+
+    let original = std::fs::read_to_string(src.portfolio_path(name)).unwrap();
+    let restored = std::fs::read_to_string(dst.portfolio_path(name)).unwrap();
+    pretty_assertions::assert_eq!(original, restored);
+
+    // portfolio and its cached index entry
+    assert!(
+        dst.portfolio_path(name)
+            .with_file_name(".csvpt")
+            .join("index.json")
+            .is_file()
+    );
+
+    // second import would overwrite the restored portfolio
+    dst.cmd()
+        .args(["import-workspace", archive_path.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "refusing to overwrite existing files",
+        ));
+}
+
+#[test]
+fn export_writes_sqlite_and_parquet_files() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("main");
+    ctx.add_tx_buy_btc("main", "0.5", "40000", "7.5");
+    let out = ctx.portfolio_path("main").with_file_name("out");
+
+    let db = out.with_extension("db");
+    ctx.cmd()
+        .args(["export", "--format", "sqlite"])
+        .arg(&db)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Exported 1 trades and 2 positions of 1 portfolios to",
+        ));
+    let conn = rusqlite::Connection::open(&db).unwrap();
+    let trade: (String, String, String) = conn
+        .query_row("SELECT amount, price, fee FROM trades", [], |r| {
+            Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+        })
+        .unwrap();
+    assert_eq!(
+        trade,
+        ("0.5".to_string(), "40000".to_string(), "7.5".to_string())
+    );
+    let positions: i64 = conn
+        .query_row("SELECT COUNT(*) FROM positions", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(positions, 2);
+    drop(conn);
+
+    let dir = out.with_extension("parquet");
+    ctx.cmd()
+        .args(["export", "--name", "main", "--format", "parquet"])
+        .arg(&dir)
+        .assert()
+        .success();
+    for (table, rows) in [("trades", 1), ("positions", 2)] {
+        let file = std::fs::File::open(dir.join(table).with_extension("parquet")).unwrap();
+        let reader = SerializedFileReader::new(file).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), rows);
+    }
+
+    ctx.cmd()
+        .args(["export", "--name", "missing", "--format", "sqlite"])
+        .arg(&db)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no portfolio 'missing'"));
+}
+
+#[test]
+fn export_ledger_journal_to_stdout_or_file() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("main");
+    ctx.add_tx_buy_btc("main", "0.5", "40000", "7.5");
+
+    ctx.cmd()
+        .args(["export", "--format", "ledger", "-"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "; portfolio main, base currency USD",
+        ))
+        .stdout(predicate::str::contains(
+            "    Assets:main                     0.5 BTC {40015 USD}\n    Assets:main                     -20007.5 USD\n",
+        ));
+
+    let journal = ctx.portfolio_path("main").with_file_name("main.journal");
+    ctx.cmd()
+        .args(["export", "--format", "ledger"])
+        .arg(&journal)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Exported 1 trades of 1 portfolios to",
+        ));
+    assert!(
+        std::fs::read_to_string(&journal)
+            .unwrap()
+            .contains(" * Buy BTC/USD\n")
+    );
+}
+
+#[test]
+fn export_beancount_books_sales_against_lots() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("main");
+    std::fs::write(
+        ctx.portfolio_path("main"),
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704883200,BTC/USD,BUY,0.5,40000,0
+1709251200,BTC/USD,SELL,0.2,50000,0
+",
+    )
+    .unwrap();
+
+    ctx.cmd()
+        .args(["export", "--format", "beancount", "-"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(" open Assets:Main\n"))
+        .stdout(predicate::str::contains(
+            "  Assets:Main                     -0.2 BTC {40000 USD, 2024-01-10} @ 50000 USD\n",
+        ))
+        .stdout(predicate::str::contains(
+            "  Income:CapitalGains             -2000 USD\n",
+        ));
+}
+
+#[test]
+fn export_qif_and_ofx_investment_accounts() {
+    let ctx = TestContext::new();
+    ctx.create_portfolio("main");
+    ctx.add_tx_buy_btc("main", "0.5", "40000", "7.5");
+
+    ctx.cmd()
+        .args(["export", "--format", "qif", "-"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "!Account\nNmain\nTInvst\n^\n!Type:Invst\n",
+        ))
+        .stdout(predicate::str::contains(
+            "NBuy\nYBTC\nI40000\nQ0.5\nO7.5\nT20007.5\nMBuy BTC/USD, id ",
+        ));
+
+    let ofx = ctx.portfolio_path("main").with_file_name("main.ofx");
+    ctx.cmd()
+        .args(["export", "--format", "ofx"])
+        .arg(&ofx)
+        .assert()
+        .success();
+    let ofx = std::fs::read_to_string(ofx).unwrap();
+    assert!(ofx.contains("<ACCTID>main</ACCTID>"));
+    assert!(ofx.contains("<BUYSTOCK><INVBUY>"));
+    assert!(ofx.ends_with("</OFX>\n"));
+}
+
+#[test]
+fn dca_due_and_ics_export_of_configured_plans() {
+    let ctx = TestContext::new();
+    ctx.cmd()
+        .args(["dca", "due"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no DCA plans configured"));
+
+    ctx.config_file(
+        r#"
+[dca.btc-daily]
+portfolio = "main"
+asset = "BTC"
+amount = 25
+cadence = "daily"
+start = "2024-01-01"
+
+[dca.eth-monthly]
+portfolio = "other"
+asset = "ETH"
+amount = 100
+currency = "EUR"
+cadence = "monthly"
+start = "2024-01-31"
+"#,
+    );
+    ctx.cmd()
+        .args(["dca", "due", "--name", "main", "--days", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("today"))
+        .stdout(predicate::str::contains("tomorrow"))
+        .stdout(predicate::str::contains("buy 25 USD of BTC"))
+        .stdout(predicate::str::contains("ETH").not());
+
+    // without the config file status line, stdout is the calendar only
+    ctx.cmd()
+        .args(["export", "--format", "ics", "--quiet", "-"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("BEGIN:VCALENDAR\r\n"))
+        .stdout(predicate::str::contains(
+            "UID:dca-btc-daily@portfolio-tracker",
+        ))
+        .stdout(predicate::str::contains("RRULE:FREQ=DAILY\r\n"))
+        .stdout(predicate::str::contains(
+            "RRULE:FREQ=MONTHLY;BYMONTHDAY=28,29,30,31;BYSETPOS=-1\r\n",
+        ))
+        .stdout(predicate::str::contains(
+            "SUMMARY:DCA: buy 100 EUR of ETH\r\n",
+        ));
+
+    let out = ctx.portfolio_path("main").with_file_name("dca.ics");
+    ctx.cmd()
+        .args(["export", "--format", "ics", "--name", "other"])
+        .arg(&out)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Exported 1 DCA plans to"));
+    assert!(std::fs::read_to_string(&out).unwrap().contains("ETH"));
+}
+
+#[test]
+fn dca_status_and_watch_flag_missed_buys() {
+    let ctx = TestContext::new();
+    ctx.create_eur_portfolio(
+        "main",
+        "# base_currency: USD
+created_at,pair,side,amount,price,fee
+1704067200,BTC/USD,BUY,0.0025,40000,0
+1704758400,BTC/USD,BUY,0.001,40000,0
+1705363200,BTC/USD,BUY,0.0025,39900,0.25
+",
+    );
+    ctx.config_file(
+        r#"
+[dca.btc-weekly]
+portfolio = "main"
+asset = "BTC"
+amount = 100
+cadence = "weekly"
+start = "2024-01-01"
+
+[dca.eth-weekly]
+asset = "ETH"
+amount = 100
+cadence = "weekly"
+start = "2024-01-01"
+"#,
+    );
+    ctx.cmd()
+        .args(["--now", "2024-01-24T12:00:00Z", "dca", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| btc-weekly | main      |         4 |    2 |      1 | 2024-01-08               |",
+        ))
+        .stdout(predicate::str::contains("set `portfolio` to check"))
+        .stdout(predicate::str::contains(
+            "1 DCA buy(s) missed in the past 90 days",
+        ));
+
+    let prices = ctx.prices_file("p.csv", "ticker,price\nBTC,40000\n");
+    ctx.cmd()
+        .args(["--fixture-prices", prices.to_str().unwrap()])
+        .args(["--now", "2024-01-26T12:00:00Z", "watch", "--once"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "🔔 DCA plan btc-weekly: no buy recorded for 2024-01-22",
+        ));
+    ctx.cmd()
+        .args(["--fixture-prices", prices.to_str().unwrap()])
+        .args(["--now", "2024-01-27T12:00:00Z", "watch", "--once"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("🔔").not());
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_c_stops_watch_between_polls() {
+    use nix::sys::signal::{self, Signal};
+    use nix::unistd::Pid;
+    use std::io::{BufRead, BufReader, Read};
+    use std::process::Stdio;
+
+    let ctx = TestContext::new();
+    let mut child = ctx
+        .process()
+        .args(["--quiet", "watch", "--interval", "60"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut first = String::new();
+    stdout.read_line(&mut first).unwrap();
+    assert!(first.ends_with("no alerts\n"), "{}", first);
+
+    let pid = Pid::from_raw(child.id() as i32);
+    signal::kill(pid, Signal::SIGINT).unwrap();
+    let status = child.wait().unwrap();
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    assert!(status.success());
+    assert_eq!(rest, "Stopped watching\n");
+}
+
+#[test]
+fn export_anonymized_needs_a_portfolio_and_writes_a_portfolio_csv() {
+    let ctx = TestContext::new();
+    let name = "wallet";
+    ctx.create_portfolio(name);
+    ctx.add_tx_buy_btc(name, "0.5", "96450", "37");
+    let out = tempfile::TempDir::new().unwrap();
+    let out_path = out.path().join("anon.csv");
+
+    ctx.cmd()
+        .args(["export", "--anonymized", out_path.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--name <NAME>"));
+    ctx.cmd()
+        .args([
+            "export",
+            "-n",
+            name,
+            "--anonymized",
+            out_path.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Wrote 1 trades of wallet with amounts scaled by a random factor",
+        ));
+    let content = std::fs::read_to_string(&out_path).unwrap();
+    assert!(content.contains("created_at,pair,side,amount,price,fee\n"));
+    assert!(content.contains(",BTC/USD,BUY,"));
+    assert!(content.contains(",96450,"));
+    assert!(!content.contains("manual"));
+}
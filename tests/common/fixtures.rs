@@ -8,18 +8,30 @@ use tempfile::TempDir;
 
 pub struct TestContext {
     temp_dir: TempDir,
+    home_dir: TempDir,
 }
 
 #[allow(dead_code)]
 impl TestContext {
     pub fn new() -> Self {
         let temp_dir = TempDir::new().expect("failed to create temp dir");
-        Self { temp_dir }
+        let home_dir = TempDir::new().expect("failed to create temp home dir");
+        Self { temp_dir, home_dir }
     }
 
     pub fn cmd(&self) -> assert_cmd::Command {
         let mut cmd = cargo_bin_cmd!("portfolio-tracker");
         cmd.env("LPT_PORTFOLIO_DIR", self.temp_dir.path());
+        // keep the user's dotfile out of the tests
+        cmd.env("HOME", self.home_dir.path());
+        cmd
+    }
+
+    /// Command to spawn rather than run to completion, same environment as `cmd`
+    pub fn process(&self) -> std::process::Command {
+        let mut cmd = std::process::Command::new(env!("CARGO_BIN_EXE_portfolio-tracker"));
+        cmd.env("LPT_PORTFOLIO_DIR", self.temp_dir.path());
+        cmd.env("HOME", self.home_dir.path());
         cmd
     }
 
@@ -108,6 +120,20 @@ impl TestContext {
             .stderr(predicate::str::is_empty());
     }
 
+    /// Write a `ticker,price` file usable as `file:` quote provider, outside the portfolio dir
+    pub fn prices_file(&self, file_name: &str, content: &str) -> std::path::PathBuf {
+        let path = self.home_dir.path().join(file_name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// Write the config file read from `$HOME`
+    pub fn config_file(&self, content: &str) {
+        let dir = self.home_dir.path().join(".local/share/csvpt");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.toml"), content).unwrap();
+    }
+
     pub fn report(&self, portfolio: &str) {
         self.cmd()
             .args(["report", "--name", portfolio])